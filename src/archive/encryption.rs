@@ -0,0 +1,644 @@
+// Packages whose entries are encrypted with the traditional PKWARE scheme,
+// ZipCrypto, as some export tools apply to every entry. The zip library
+// refuses encrypted entries, so a package with any is read through a view of
+// it which the zip library can read as if it had never been encrypted: each
+// entry is decrypted as it is read, and the headers are changed on the way
+// so that they describe the decrypted entry. Nothing moves, as the 12 bytes
+// ZipCrypto puts before the data of an entry are given as a padding field at
+// the end of its local extra field, so the offsets of the zip directory and
+// of the hash index still hold, and nothing is decrypted ahead of being read
+// or written anywhere. Entries which aren't encrypted are read as they are,
+// and a package without any encrypted entry isn't read through a view at
+// all, so only its zip directory is read an extra time.
+//
+// ZipCrypto has no real way of telling a wrong password. The last of the 12
+// bytes it puts before each entry's data should decrypt to a byte of the CRC,
+// or of the time with a data descriptor, which is checked for every entry
+// before anything is read, but which a wrong password still matches once in
+// 256 times. The zip library checks the CRC of every entry as it inflates it
+// though, so an entry a wrong password got through with fails there, at the
+// latest once it is read to its end. Entries encrypted with AES or the
+// strong encryption of PKWARE are refused.
+
+use super::raw::{self, RawZipError};
+use failure::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::ZipArchive;
+
+const ENCRYPTED_FLAG: u16 = 1;
+const DATA_DESCRIPTOR_FLAG: u16 = 1 << 3;
+const STRONG_ENCRYPTION_FLAG: u16 = 1 << 6;
+/// The compression method of entries encrypted with AES.
+const AES_METHOD: u16 = 99;
+/// The bytes ZipCrypto puts before the data of each entry.
+const HEADER_LEN: usize = 12;
+/// The id of the extra field the header ZipCrypto put before an entry is
+/// given as, which zipalign uses for padding too.
+const PADDING_EXTRA_ID: u16 = 0xd935;
+
+/// Why the entries of an encrypted package can't be read.
+#[derive(Debug, Fail)]
+pub enum EncryptionError {
+    /// The package is encrypted, and no password was given for it.
+    #[fail(display = "{} is encrypted, and no password was given", _0)]
+    NoPassword(String),
+
+    /// The first encrypted entry doesn't decrypt with the password given.
+    #[fail(display = "The password is wrong: {} doesn't decrypt with it", _0)]
+    WrongPassword(String),
+
+    /// An entry is encrypted with a method other than ZipCrypto, such as AES.
+    #[fail(
+        display = "{} is encrypted with {}, and only ZipCrypto can be decrypted",
+        _0, _1
+    )]
+    Unsupported(String, &'static str),
+
+    /// An encrypted entry can't be decrypted as it is read, because of how
+    /// its headers are laid out.
+    #[fail(display = "{} can't be decrypted as it is read, as {}", _0, _1)]
+    Unreadable(String, &'static str),
+}
+
+/// The CRC-32 table, which ZipCrypto updates its keys with a byte at a time.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            bit += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32_byte(crc: u32, byte: u8) -> u32 {
+    CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+}
+
+/// The three keys of ZipCrypto, which every byte of the plain text updates.
+#[derive(Clone, Debug)]
+struct Keys([u32; 3]);
+
+impl Keys {
+    fn new(password: &[u8]) -> Keys {
+        let mut keys = Keys([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+        for &byte in password {
+            keys.update(byte);
+        }
+        keys
+    }
+
+    fn update(&mut self, plain: u8) {
+        let [k0, k1, k2] = &mut self.0;
+        *k0 = crc32_byte(*k0, plain);
+        *k1 = k1
+            .wrapping_add(*k0 & 0xff)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        *k2 = crc32_byte(*k2, (*k1 >> 24) as u8);
+    }
+
+    fn stream_byte(&self) -> u8 {
+        let temp = (self.0[2] | 2) & 0xffff;
+        ((temp * (temp ^ 1)) >> 8) as u8
+    }
+
+    fn decrypt(&mut self, data: &mut [u8]) {
+        for byte in data {
+            *byte ^= self.stream_byte();
+            self.update(*byte);
+        }
+    }
+}
+
+/// How many bytes ZipCrypto put before the compressed data of `entry`, none
+/// unless it is encrypted.
+pub fn header_len(entry: &raw::RawEntry) -> u64 {
+    if entry.flags & ENCRYPTED_FLAG != 0 {
+        (HEADER_LEN as u64).min(entry.compressed_size)
+    } else {
+        0
+    }
+}
+
+/// What the view of an encrypted package gives in place of the package, from
+/// `start` up to `end`.
+#[derive(Clone, Debug)]
+struct Region {
+    start: u64,
+    end: u64,
+    contents: Contents,
+}
+
+#[derive(Clone, Debug)]
+enum Contents {
+    /// Bytes which replace those of the package.
+    Patched(Vec<u8>),
+    /// The data of an encrypted entry, which the keys decrypt from `start`.
+    Decrypted(Keys),
+}
+
+/// How the view of an encrypted package differs from the package: the
+/// regions it changes, in the order of the package.
+#[derive(Debug)]
+pub struct Decryption {
+    regions: Vec<Region>,
+}
+
+/// The `len` bytes of `value`, in the little endian order of zip headers.
+fn le_bytes(value: u32, len: usize) -> Vec<u8> {
+    value.to_le_bytes()[..len].to_vec()
+}
+
+impl Decryption {
+    /// How to decrypt the package `reader` holds with `password`, or `None`
+    /// when none of its entries is encrypted. The first byte of every
+    /// encrypted entry is decrypted here, so that a wrong password is told
+    /// before any entry is read.
+    pub fn of_package<R: Read + Seek>(
+        reader: &mut R,
+        password: Option<&str>,
+    ) -> Result<Option<Decryption>, Error> {
+        // A zip directory which can't be read is left for the zip library to
+        // fail on, with the error it would have given anyway.
+        let headers = match raw::central_headers(reader) {
+            Ok(headers) => headers,
+            Err(_) => return Ok(None),
+        };
+        let mut regions = Vec::new();
+        for (entry, offsets) in &headers {
+            if entry.flags & ENCRYPTED_FLAG == 0 {
+                continue;
+            }
+            let name = entry.name_lossy();
+            if entry.compression_method == AES_METHOD {
+                return Err(Error::from(EncryptionError::Unsupported(name, "AES")));
+            }
+            if entry.flags & STRONG_ENCRYPTION_FLAG != 0 {
+                return Err(Error::from(EncryptionError::Unsupported(
+                    name,
+                    "strong encryption",
+                )));
+            }
+            let password = password.ok_or_else(|| EncryptionError::NoPassword(name.clone()))?;
+            let wrong_password = || Error::from(EncryptionError::WrongPassword(name.clone()));
+            if entry.compressed_size >= u64::from(u32::MAX) {
+                return Err(Error::from(EncryptionError::Unreadable(
+                    name,
+                    "its sizes need zip64 fields",
+                )));
+            }
+            let compressed_size = entry
+                .compressed_size
+                .checked_sub(HEADER_LEN as u64)
+                .ok_or_else(wrong_password)? as u32;
+            let local = raw::read_local_header(reader, offsets.local)?
+                .ok_or_else(|| RawZipError::BadLocalHeader(name.clone()))?;
+            let extra_len = local.data_start - offsets.local - 30 - local.name.len() as u64;
+            if extra_len + HEADER_LEN as u64 > u64::from(u16::MAX) {
+                return Err(Error::from(EncryptionError::Unreadable(
+                    name,
+                    "its local extra field has no room left",
+                )));
+            }
+
+            let mut header = [0u8; HEADER_LEN];
+            reader.seek(SeekFrom::Start(local.data_start))?;
+            reader.read_exact(&mut header)?;
+            let mut keys = Keys::new(password.as_bytes());
+            keys.decrypt(&mut header);
+            let check = if entry.flags & DATA_DESCRIPTOR_FLAG != 0 {
+                (entry.last_modified_time >> 8) as u8
+            } else {
+                (entry.crc32 >> 24) as u8
+            };
+            if header[HEADER_LEN - 1] != check {
+                return Err(wrong_password());
+            }
+
+            let flags = u32::from(entry.flags & !ENCRYPTED_FLAG);
+            let patch = |at: u64, bytes: Vec<u8>| Region {
+                start: at,
+                end: at + bytes.len() as u64,
+                contents: Contents::Patched(bytes),
+            };
+            regions.push(patch(offsets.central + 8, le_bytes(flags, 2)));
+            regions.push(patch(offsets.central + 20, le_bytes(compressed_size, 4)));
+            regions.push(patch(offsets.local + 6, le_bytes(flags, 2)));
+            // With a data descriptor the local header has no sizes to change.
+            if entry.flags & DATA_DESCRIPTOR_FLAG == 0 {
+                regions.push(patch(offsets.local + 18, le_bytes(compressed_size, 4)));
+            }
+            regions.push(patch(
+                offsets.local + 28,
+                le_bytes(extra_len as u32 + HEADER_LEN as u32, 2),
+            ));
+            let mut padding = le_bytes(u32::from(PADDING_EXTRA_ID), 2);
+            padding.extend(le_bytes(HEADER_LEN as u32 - 4, 2));
+            padding.resize(HEADER_LEN, 0);
+            regions.push(patch(local.data_start, padding));
+            let data_start = local.data_start + HEADER_LEN as u64;
+            regions.push(Region {
+                start: data_start,
+                end: data_start + u64::from(compressed_size),
+                contents: Contents::Decrypted(keys),
+            });
+        }
+        if regions.is_empty() {
+            return Ok(None);
+        }
+        regions.sort_by_key(|region| region.start);
+        Ok(Some(Decryption { regions }))
+    }
+
+    /// The region `position` is in, and otherwise the index of the first
+    /// region after it.
+    fn region_at(&self, position: u64) -> Result<&Region, usize> {
+        let next = self
+            .regions
+            .partition_point(|region| region.start <= position);
+        match next.checked_sub(1).map(|i| &self.regions[i]) {
+            Some(region) if position < region.end => Ok(region),
+            _ => Err(next),
+        }
+    }
+}
+
+/// A view of an encrypted package which reads as the package would if it
+/// had never been encrypted, decrypting each entry as it is read.
+pub struct DecryptingReader<'a, R> {
+    inner: R,
+    decryption: &'a Decryption,
+    position: u64,
+    /// Where `inner` is, so that it is only seeked when the view skips.
+    inner_position: Option<u64>,
+    /// The start of the entry data last decrypted, how far into the package
+    /// that got, and the keys which decrypt from there on. Reading an entry
+    /// from its start to its end decrypts each byte once, and seeking back
+    /// into an entry decrypts it again from its start.
+    keys: Option<(u64, u64, Keys)>,
+}
+
+impl<'a, R: Read + Seek> DecryptingReader<'a, R> {
+    pub fn new(inner: R, decryption: &'a Decryption) -> DecryptingReader<'a, R> {
+        DecryptingReader {
+            inner,
+            decryption,
+            position: 0,
+            inner_position: None,
+            keys: None,
+        }
+    }
+
+    fn seek_inner(&mut self, position: u64) -> std::io::Result<()> {
+        if self.inner_position != Some(position) {
+            self.inner.seek(SeekFrom::Start(position))?;
+            self.inner_position = Some(position);
+        }
+        Ok(())
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let result = self.inner.read(buf);
+        self.inner_position = match &result {
+            Ok(len) => self.inner_position.map(|at| at + *len as u64),
+            Err(_) => None,
+        };
+        result
+    }
+
+    /// The keys which decrypt the data of `region` from the current position.
+    fn keys_at(&mut self, region: &Region, keys: &Keys) -> std::io::Result<Keys> {
+        let (mut at, mut keys) = match self.keys.take() {
+            Some((start, at, keys)) if start == region.start && at <= self.position => (at, keys),
+            _ => (region.start, keys.clone()),
+        };
+        self.seek_inner(at)?;
+        let mut buf = [0u8; 4096];
+        while at < self.position {
+            let len = buf.len().min((self.position - at) as usize);
+            let len = self.read_inner(&mut buf[..len])?;
+            if len == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            keys.decrypt(&mut buf[..len]);
+            at += len as u64;
+        }
+        Ok(keys)
+    }
+}
+
+impl<'a, R: Read + Seek> Read for DecryptingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let decryption = self.decryption;
+        let len = match decryption.region_at(self.position) {
+            Ok(region) => {
+                let len = buf.len().min((region.end - self.position) as usize);
+                match &region.contents {
+                    Contents::Patched(bytes) => {
+                        let from = (self.position - region.start) as usize;
+                        buf[..len].copy_from_slice(&bytes[from..from + len]);
+                        len
+                    }
+                    Contents::Decrypted(keys) => {
+                        let mut keys = self.keys_at(region, keys)?;
+                        let len = self.read_inner(&mut buf[..len])?;
+                        keys.decrypt(&mut buf[..len]);
+                        self.keys = Some((region.start, self.position + len as u64, keys));
+                        len
+                    }
+                }
+            }
+            Err(next) => {
+                let len = match decryption.regions.get(next) {
+                    Some(region) => buf.len().min((region.start - self.position) as usize),
+                    None => buf.len(),
+                };
+                self.seek_inner(self.position)?;
+                self.read_inner(&mut buf[..len])?
+            }
+        };
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for DecryptingReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        // The view is as long as the package, so its end is the same.
+        self.position = match pos {
+            SeekFrom::Start(position) => position,
+            _ => {
+                self.seek_inner(self.position)?;
+                let position = self.inner.seek(pos)?;
+                self.inner_position = Some(position);
+                position
+            }
+        };
+        Ok(self.position)
+    }
+}
+
+/// A package read as it is, or through a view which decrypts it when any of
+/// its entries is encrypted.
+pub enum PackageView<'a, R> {
+    Plain(R),
+    Decrypting(DecryptingReader<'a, R>),
+}
+
+impl<'a, R: Read + Seek> PackageView<'a, R> {
+    pub fn new(inner: R, decryption: Option<&'a Decryption>) -> PackageView<'a, R> {
+        match decryption {
+            Some(decryption) => PackageView::Decrypting(DecryptingReader::new(inner, decryption)),
+            None => PackageView::Plain(inner),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Read for PackageView<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            PackageView::Plain(inner) => inner.read(buf),
+            PackageView::Decrypting(inner) => inner.read(buf),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for PackageView<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            PackageView::Plain(inner) => inner.seek(pos),
+            PackageView::Decrypting(inner) => inner.seek(pos),
+        }
+    }
+}
+
+/// How to decrypt the package at `slpk_file_path` with `password`, as
+/// `Decryption::of_package` gives it.
+pub fn decryption(
+    slpk_file_path: &Path,
+    password: Option<&str>,
+) -> Result<Option<Decryption>, Error> {
+    Decryption::of_package(&mut BufReader::new(File::open(slpk_file_path)?), password)
+}
+
+/// Opens the package at `slpk_file_path` for the zip library, decrypting it
+/// as it is read when `decryption` is given.
+pub fn open_archive<'a>(
+    slpk_file_path: &Path,
+    decryption: Option<&'a Decryption>,
+) -> Result<ZipArchive<PackageView<'a, BufReader<File>>>, Error> {
+    let reader = BufReader::new(File::open(slpk_file_path)?);
+    Ok(ZipArchive::new(PackageView::new(reader, decryption))?)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::archive::hash_index::{HashIndex, HASH_INDEX_ENTRY};
+    use crate::archive::raw::{raw_data, RawArchive, RawWriter};
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{error_kind, unpack_with_options, ErrorKind, UnpackOptions};
+    use std::io::Cursor;
+
+    /// `package` with every entry encrypted with `password`, and its hash
+    /// index made again for where the entries moved to.
+    pub(crate) fn encrypted(package: &[u8], password: &str) -> Vec<u8> {
+        let mut reader = Cursor::new(package);
+        let raw = RawArchive::read(&mut reader).unwrap();
+        let mut writer = RawWriter::new(Cursor::new(Vec::new()));
+        let mut offsets = Vec::new();
+        let mut index_entry = None;
+        for (i, entry) in raw.entries.iter().enumerate() {
+            let mut data = vec![i as u8; HEADER_LEN - 1];
+            data.push((entry.crc32 >> 24) as u8);
+            if entry.name_lossy() == HASH_INDEX_ENTRY {
+                index_entry = Some(entry.clone());
+                continue;
+            }
+            offsets.push((entry.name_lossy(), writer.offset()));
+            raw_data(&mut reader, entry)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            write_encrypted(&mut writer, entry, data, password);
+        }
+        if let Some(mut entry) = index_entry {
+            let index = HashIndex::of_entries(
+                offsets
+                    .iter()
+                    .map(|(name, offset)| (name.as_str(), *offset)),
+            );
+            let contents = entry.replace_contents(&index.to_bytes()).unwrap();
+            let mut data = vec![0; HEADER_LEN - 1];
+            data.push((entry.crc32 >> 24) as u8);
+            data.extend(contents);
+            write_encrypted(&mut writer, &entry, data, password);
+        }
+        writer.finish(&raw.comment).unwrap().into_inner()
+    }
+
+    /// Writes `entry` with `data`, its header and compressed data, encrypted.
+    fn write_encrypted(
+        writer: &mut RawWriter<Cursor<Vec<u8>>>,
+        entry: &raw::RawEntry,
+        mut data: Vec<u8>,
+        password: &str,
+    ) {
+        let mut keys = Keys::new(password.as_bytes());
+        for byte in &mut data {
+            let plain = *byte;
+            *byte ^= keys.stream_byte();
+            keys.update(plain);
+        }
+        let mut entry = entry.clone();
+        entry.flags |= ENCRYPTED_FLAG;
+        entry.compressed_size = data.len() as u64;
+        writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+    }
+
+    /// The name and contents of every entry of the package `reader` holds.
+    fn contents<R: Read + Seek>(reader: R) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(reader).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (entry.name().to_string(), contents)
+            })
+            .filter(|(name, _)| name != HASH_INDEX_ENTRY)
+            .collect()
+    }
+
+    #[test]
+    fn encrypted_entries_are_decrypted_as_they_are_read() {
+        let package = SyntheticPackage::standard().to_bytes().unwrap();
+        let encrypted = encrypted(&package, "s3cret");
+        let decryption = Decryption::of_package(&mut Cursor::new(&encrypted), Some("s3cret"))
+            .unwrap()
+            .unwrap();
+        let no_password = Decryption::of_package(&mut Cursor::new(&encrypted), None).unwrap_err();
+        let wrong_password =
+            Decryption::of_package(&mut Cursor::new(&encrypted), Some("secret")).unwrap_err();
+        let plain = Decryption::of_package(&mut Cursor::new(&package), None).unwrap();
+        let mut view = DecryptingReader::new(Cursor::new(&encrypted), &decryption);
+        let mut whole = Vec::new();
+        view.read_to_end(&mut whole).unwrap();
+        // Reading a byte of an entry after the view was seeked back decrypts
+        // it again from the start of the entry.
+        let mut byte = [0u8];
+        view.seek(SeekFrom::Start(whole.len() as u64 / 2)).unwrap();
+        view.read_exact(&mut byte).unwrap();
+
+        assert_eq!(
+            contents(DecryptingReader::new(Cursor::new(&encrypted), &decryption)),
+            contents(Cursor::new(&package))
+        );
+        assert_eq!(whole.len(), encrypted.len());
+        assert_eq!(byte[0], whole[whole.len() / 2]);
+        assert!(matches!(
+            no_password.downcast_ref::<EncryptionError>(),
+            Some(EncryptionError::NoPassword(_))
+        ));
+        assert!(matches!(
+            wrong_password.downcast_ref::<EncryptionError>(),
+            Some(EncryptionError::WrongPassword(_))
+        ));
+        assert!(plain.is_none());
+    }
+
+    #[test]
+    fn a_wrong_password_the_check_byte_lets_through_fails_the_crc() {
+        let package = SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json",
+                br#"{"id": 0, "layerType": "3DObject"}"#,
+            )
+            .to_bytes()
+            .unwrap();
+        let encrypted = encrypted(&package, "s3cret");
+        // One wrong password in 256 decrypts the check byte right.
+        let decryption = (0..)
+            .map(|i| format!("wrong{}", i))
+            .find_map(|password| {
+                Decryption::of_package(&mut Cursor::new(&encrypted), Some(&password)).ok()
+            })
+            .unwrap()
+            .unwrap();
+        let mut archive =
+            zip::ZipArchive::new(DecryptingReader::new(Cursor::new(&encrypted), &decryption))
+                .unwrap();
+        let mut entry = archive.by_index(0).unwrap();
+
+        assert!(entry.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn encrypted_packages_unpack_with_their_password() {
+        let dir = TempDir::new("encrypted");
+        let path = dir.join("package.slpk");
+        let package = SyntheticPackage::standard();
+        std::fs::write(&path, encrypted(&package.to_bytes().unwrap(), "s3cret")).unwrap();
+        let options = UnpackOptions::new().threads(2);
+
+        let no_password = unpack_with_options(&path, &options).unwrap_err();
+        let summary =
+            unpack_with_options(&path, &options.clone().password("s3cret".to_string())).unwrap();
+        let unpacked = package
+            .expected_files(false)
+            .into_iter()
+            .map(|(file, _)| std::fs::read(dir.join("package").join(&file)).unwrap())
+            .collect::<Vec<_>>();
+        let listed = crate::list::list(&path).unwrap();
+        let verified = crate::verify::verify_with_threads(&path, None, Some("s3cret")).unwrap();
+        let plain_path = dir.join("plain.slpk");
+        package.write_to_file(&plain_path).unwrap();
+        let validated = crate::validate::validate_with_options(
+            &path,
+            &crate::validate::ValidateOptions {
+                password: Some("s3cret".to_string()),
+                ..crate::validate::ValidateOptions::default()
+            },
+        )
+        .unwrap();
+        let plain_validated = crate::validate::validate(&plain_path, false).unwrap();
+        let plain_listed = crate::list::list(&plain_path).unwrap();
+        std::fs::remove_file(&plain_path).unwrap();
+        let mut files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        assert_eq!(error_kind(&no_password), ErrorKind::Password);
+        let expected: Vec<Vec<u8>> = package
+            .expected_files(false)
+            .into_iter()
+            .map(|(_, contents)| contents)
+            .collect();
+        assert_eq!(summary.entries_unpacked, expected.len());
+        assert_eq!(unpacked, expected);
+        // Listing needs no password, and gives the entries as they are once
+        // decrypted.
+        assert_eq!(listed, plain_listed);
+        assert!(verified.passed());
+        assert_eq!(validated.problems(), plain_validated.problems());
+        // Nothing was decrypted into a file of its own.
+        assert_eq!(files, ["package", "package.slpk"]);
+    }
+}
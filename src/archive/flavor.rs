@@ -0,0 +1,142 @@
+// Scene layer packages come in two flavors. The I3S specification has every
+// resource but `metadata.json` gzipped inside the zip, while the optimized
+// `.eslpk` packages ArcGIS writes store their resources as they are. An
+// `.eslpk` is told by its extension, and any other package by the first
+// bytes of a few of its JSON documents, which are gzipped in every `.slpk`
+// whatever their entries are named.
+
+use super::hash_index::HASH_INDEX_ENTRY;
+use super::is_gzip;
+use failure::Error;
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// How many JSON documents are sniffed before a package is taken to store
+/// them as they are.
+const SNIFFED_DOCUMENTS: usize = 8;
+
+/// How the resources of a package are stored.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PackageFlavor {
+    /// Resources gzipped one by one, as the I3S specification has them.
+    #[default]
+    Slpk,
+    /// Resources stored as they are, without gzip.
+    Eslpk,
+}
+
+impl PackageFlavor {
+    /// The flavor of the package at `package_name`, a path or URL, which
+    /// `archive` reads.
+    pub fn of_package<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        package_name: &str,
+    ) -> Result<PackageFlavor, Error> {
+        let extension = Path::new(package_name)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        if extension.is_some_and(|e| e.eq_ignore_ascii_case("eslpk")) {
+            return Ok(PackageFlavor::Eslpk);
+        }
+        let mut sniffed = 0;
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            let name = entry.name();
+            let document = name.strip_suffix(".gz").unwrap_or(name).ends_with(".json");
+            // metadata.json is never gzipped.
+            if !document || name == "metadata.json" || name == HASH_INDEX_ENTRY || entry.size() < 2
+            {
+                continue;
+            }
+            let mut magic = Vec::with_capacity(2);
+            entry.take(2).read_to_end(&mut magic)?;
+            if is_gzip(&magic) {
+                return Ok(PackageFlavor::Slpk);
+            }
+            sniffed += 1;
+            if sniffed == SNIFFED_DOCUMENTS {
+                break;
+            }
+        }
+        Ok(if sniffed > 0 {
+            PackageFlavor::Eslpk
+        } else {
+            PackageFlavor::Slpk
+        })
+    }
+}
+
+impl fmt::Display for PackageFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PackageFlavor::Slpk => "SLPK",
+            PackageFlavor::Eslpk => "ESLPK",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+
+    /// A package storing its documents as they are, one of them under a
+    /// `.gz` name.
+    fn eslpk() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in &[
+            ("metadata.json", &b"{\"I3SVersion\":\"1.8\"}"[..]),
+            ("3dSceneLayer.json.gz", b"{\"id\":0}"),
+            ("nodepages/0.json", b"{\"nodes\":[]}"),
+            ("nodes/0/geometries/0.bin", b"geometry"),
+        ] {
+            writer.start_file(*name, stored).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn flavors_are_told_by_extension_or_documents() {
+        let flavor = |bytes: Vec<u8>, name| {
+            let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+            PackageFlavor::of_package(&mut archive, name).unwrap()
+        };
+        let slpk = SyntheticPackage::standard().to_bytes().unwrap();
+        assert_eq!(flavor(slpk.clone(), "city.slpk"), PackageFlavor::Slpk);
+        assert_eq!(flavor(slpk, "city.ESLPK"), PackageFlavor::Eslpk);
+        assert_eq!(flavor(eslpk(), "city.slpk"), PackageFlavor::Eslpk);
+        assert_eq!(
+            flavor(eslpk(), "http://example.com/city.eslpk"),
+            PackageFlavor::Eslpk
+        );
+    }
+
+    #[test]
+    fn eslpk_entries_are_unpacked_as_they_are() {
+        let dir = TempDir::new("eslpk");
+        let path = dir.join("city.eslpk");
+        std::fs::write(&path, eslpk()).unwrap();
+        let summary = unpack_with_options(&path, &UnpackOptions::new()).unwrap();
+        let read = |name: &str| std::fs::read(dir.join("city").join(name)).unwrap();
+        let files = (
+            read("3dSceneLayer.json"),
+            read("nodepages/0.json"),
+            read("nodes/0/geometries/0.bin"),
+        );
+        let info = crate::info::info(&path);
+
+        assert_eq!(summary.flavor, PackageFlavor::Eslpk);
+        assert_eq!(summary.entries_unpacked, 4);
+        assert_eq!(files.0, b"{\"id\":0}");
+        assert_eq!(files.1, b"{\"nodes\":[]}");
+        assert_eq!(files.2, b"geometry");
+        assert_eq!(info.unwrap().flavor, PackageFlavor::Eslpk);
+    }
+}
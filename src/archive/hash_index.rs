@@ -0,0 +1,168 @@
+// The hash table index which ArcGIS Pro writes into packages. It is a
+// sorted array of 24 byte records: the MD5 of the lower case entry name,
+// followed by the little endian offset of the entry's local file header.
+
+use crate::md5::md5;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Read, Seek, SeekFrom};
+
+/// The name of the entry holding the hash index of a package.
+pub const HASH_INDEX_ENTRY: &str = "@specialIndexFileHASH128@";
+
+const RECORD_LEN: usize = 24;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+#[derive(Debug, Fail)]
+pub enum HashIndexError {
+    #[fail(
+        display = "The hash index is {} bytes long, which isn't a whole number of records",
+        _0
+    )]
+    TruncatedIndex(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashIndexRecord {
+    pub key: [u8; 16],
+    pub offset: u64,
+}
+
+#[derive(Debug)]
+pub struct HashIndex {
+    pub records: Vec<HashIndexRecord>,
+}
+
+/// The key under which an entry is stored in the index.
+pub fn key_of(entry_name: &str) -> [u8; 16] {
+    md5(entry_name.replace('\\', "/").to_lowercase().as_bytes())
+}
+
+impl HashIndex {
+    /// The index of entries, by name and the offset of their local header.
+    pub fn of_entries<'a>(entries: impl IntoIterator<Item = (&'a str, u64)>) -> HashIndex {
+        let mut records: Vec<HashIndexRecord> = entries
+            .into_iter()
+            .map(|(name, offset)| HashIndexRecord {
+                key: key_of(name),
+                offset,
+            })
+            .collect();
+        records.sort_by(|a, b| a.key.cmp(&b.key).then(a.offset.cmp(&b.offset)));
+        HashIndex { records }
+    }
+
+    /// The contents of the index entry.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.records.len() * RECORD_LEN);
+        for record in &self.records {
+            bytes.extend_from_slice(&record.key);
+            bytes.extend_from_slice(&record.offset.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<HashIndex, HashIndexError> {
+        if bytes.len() % RECORD_LEN != 0 {
+            return Err(HashIndexError::TruncatedIndex(bytes.len()));
+        }
+        let records = bytes
+            .chunks(RECORD_LEN)
+            .map(|record| {
+                let mut key = [0u8; 16];
+                key.copy_from_slice(&record[..16]);
+                let offset = (&record[16..]).read_u64::<LittleEndian>().unwrap();
+                HashIndexRecord { key, offset }
+            })
+            .collect();
+        Ok(HashIndex { records })
+    }
+
+    /// Whether the records are in key order, which lookups depend on.
+    pub fn is_sorted(&self) -> bool {
+        self.records
+            .windows(2)
+            .all(|pair| pair[0].key <= pair[1].key)
+    }
+
+    /// Returns the local header offset recorded for an entry.
+    pub fn lookup(&self, entry_name: &str) -> Option<u64> {
+        let key = key_of(entry_name);
+        if self.is_sorted() {
+            self.records
+                .binary_search_by(|record| record.key.cmp(&key))
+                .ok()
+                .map(|i| self.records[i].offset)
+        } else {
+            self.records
+                .iter()
+                .find(|record| record.key == key)
+                .map(|record| record.offset)
+        }
+    }
+}
+
+/// Reads the entry name from the local file header at `offset`, or `None`
+/// if there isn't a local file header there.
+pub fn local_header_name<R: Read + Seek>(reader: &mut R, offset: u64) -> Option<String> {
+    reader.seek(SeekFrom::Start(offset)).ok()?;
+    let mut header = [0u8; 30];
+    reader.read_exact(&mut header).ok()?;
+    let mut fields = &header[..];
+    if fields.read_u32::<LittleEndian>().ok()? != LOCAL_HEADER_SIGNATURE {
+        return None;
+    }
+    let name_len = (&header[26..28]).read_u16::<LittleEndian>().ok()? as usize;
+    let mut name = vec![0u8; name_len];
+    reader.read_exact(&mut name).ok()?;
+    Some(String::from_utf8_lossy(&name).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    #[test]
+    fn lookup_resolves_to_local_headers() {
+        let names = [
+            "3dSceneLayer.json.gz",
+            "nodes/0/3dNodeIndexDocument.json.gz",
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in &names {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(b"{}").unwrap();
+        }
+        let mut package = writer.finish().unwrap();
+
+        // The entries have no extra fields, so each local header is the fixed
+        // header and the name in front of the entry data.
+        let mut archive = ZipArchive::new(package.clone()).unwrap();
+        let mut records: Vec<([u8; 16], u64)> = (0..names.len())
+            .map(|i| {
+                let entry = archive.by_index(i).unwrap();
+                let offset = entry.data_start() - 30 - entry.name().len() as u64;
+                (key_of(entry.name()), offset)
+            })
+            .collect();
+        records.sort();
+        let mut index_bytes = Vec::new();
+        for (key, offset) in &records {
+            index_bytes.extend_from_slice(key);
+            index_bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        let index = HashIndex::parse(&index_bytes).unwrap();
+        assert!(index.is_sorted());
+        let offset = index.lookup("Nodes/0/3dNodeIndexDocument.json.gz").unwrap();
+        assert_eq!(
+            local_header_name(&mut package, offset).as_deref(),
+            Some("nodes/0/3dNodeIndexDocument.json.gz")
+        );
+        assert_eq!(local_header_name(&mut package, offset + 1), None);
+        assert_eq!(index.lookup("missing.json"), None);
+        assert!(HashIndex::parse(&index_bytes[1..]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+// Reads the entries of a package through its hash index, when it has one,
+// going straight to the local header the index records for an entry rather
+// than reading the central directory to find it. The central directory of a
+// package with millions of resources takes seconds to read, most of the
+// time of a command such as `slpkg cat` reading a single document. The index
+// itself is found among the last headers of the central directory, as
+// exporters write it last, and the directory as a whole is only read, once,
+// when something the index can't answer is asked for: an entry it has no
+// record of, a record pointing at the header of another entry, as when a
+// package was edited without updating its index, or an entry whose sizes
+// are in a data descriptor rather than its header. So packages without an
+// index, or with a stale one, read exactly as they did before. Entries read
+// through the index are checked against the CRC in their local header, as
+// the zip library checks them against the one in the central directory.
+
+use super::hash_index::{HashIndex, HASH_INDEX_ENTRY};
+use super::raw::{self, LocalHeader};
+use failure::Error;
+use flate2::read::DeflateDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// A package whose entries are found through its hash index where they
+/// can be.
+pub struct IndexedPackage {
+    path: PathBuf,
+    reader: BufReader<File>,
+    index: Option<HashIndex>,
+    /// The central directory, once something has needed it.
+    archive: Option<ZipArchive<BufReader<File>>>,
+}
+
+/// An entry being read, with the sizes its headers give.
+pub struct PackageEntry<'a> {
+    pub compressed_size: u64,
+    pub size: u64,
+    contents: Box<dyn Read + 'a>,
+}
+
+impl Read for PackageEntry<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.contents.read(buf)
+    }
+}
+
+impl<'a> PackageEntry<'a> {
+    fn of_zip_file(entry: ZipFile<'a>) -> PackageEntry<'a> {
+        PackageEntry {
+            compressed_size: entry.compressed_size(),
+            size: entry.size(),
+            contents: Box::new(entry),
+        }
+    }
+}
+
+/// Checks what is read through it against a CRC once it is read to its
+/// end.
+struct CrcChecked<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    expected: u32,
+}
+
+impl<R: Read> Read for CrcChecked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        if read == 0 && !buf.is_empty() && self.hasher.clone().finalize() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid checksum",
+            ));
+        }
+        Ok(read)
+    }
+}
+
+impl IndexedPackage {
+    /// Opens a package, reading its hash index if it has one. A package
+    /// whose index can't be read is read as if it had none.
+    pub fn open(path: &Path) -> Result<IndexedPackage, Error> {
+        let mut package = IndexedPackage {
+            path: path.to_path_buf(),
+            reader: BufReader::new(File::open(path)?),
+            index: None,
+            archive: None,
+        };
+        // Anything wrong with the package is left for the zip library to
+        // report, in case the central directory has to be read.
+        let trailing = raw::find_trailing_entry(&mut package.reader, HASH_INDEX_ENTRY.as_bytes())
+            .ok()
+            .flatten();
+        let header = match trailing {
+            Some(offset) => package.header_at(HASH_INDEX_ENTRY, offset)?,
+            None => None,
+        };
+        let bytes = match header {
+            Some(header) => {
+                let mut bytes = Vec::new();
+                package.read_at(&header)?.read_to_end(&mut bytes)?;
+                Some(bytes)
+            }
+            None => super::read_entry(package.archive()?, HASH_INDEX_ENTRY)?,
+        };
+        if let Some(bytes) = bytes {
+            match HashIndex::parse(&bytes) {
+                Ok(index) => package.index = Some(index),
+                Err(e) => warn!("The hash index of the package can't be used: {}", e),
+            }
+        }
+        Ok(package)
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// The central directory of the package, read the first time it is
+    /// asked for.
+    pub fn archive(&mut self) -> Result<&mut ZipArchive<BufReader<File>>, Error> {
+        if self.archive.is_none() {
+            let reader = BufReader::new(File::open(&self.path)?);
+            self.archive = Some(ZipArchive::new(reader)?);
+        }
+        Ok(self.archive.as_mut().unwrap())
+    }
+
+    /// The local header at `offset`, if it is that of the entry `name` and
+    /// the entry can be read from it.
+    fn header_at(&mut self, name: &str, offset: u64) -> Result<Option<LocalHeader>, Error> {
+        Ok(raw::read_local_header(&mut self.reader, offset)?
+            .filter(|header| header.name == name.as_bytes() && header.is_readable()))
+    }
+
+    /// The local header of the entry `name`, as the index records it.
+    fn indexed_header(&mut self, name: &str) -> Result<Option<LocalHeader>, Error> {
+        match self.index.as_ref().and_then(|index| index.lookup(name)) {
+            Some(offset) => self.header_at(name, offset),
+            None => Ok(None),
+        }
+    }
+
+    fn read_at(&mut self, header: &LocalHeader) -> Result<PackageEntry<'_>, Error> {
+        self.reader.seek(SeekFrom::Start(header.data_start))?;
+        let data = (&mut self.reader).take(header.compressed_size);
+        let decoded: Box<dyn Read + '_> = if header.is_deflated() {
+            Box::new(DeflateDecoder::new(data))
+        } else {
+            Box::new(data)
+        };
+        Ok(PackageEntry {
+            compressed_size: header.compressed_size,
+            size: header.size,
+            contents: Box::new(CrcChecked {
+                inner: decoded.take(header.size),
+                hasher: crc32fast::Hasher::new(),
+                expected: header.crc32,
+            }),
+        })
+    }
+
+    /// The position in `names` of the first name the package has an entry
+    /// for. The index is tried for every name before the central directory
+    /// is read.
+    pub fn find_any(&mut self, names: &[&str]) -> Result<Option<usize>, Error> {
+        for (i, name) in names.iter().enumerate() {
+            if self.indexed_header(name)?.is_some() {
+                return Ok(Some(i));
+            }
+        }
+        let archive = self.archive()?;
+        for (i, name) in names.iter().enumerate() {
+            match archive.by_name(name) {
+                Ok(_) => return Ok(Some(i)),
+                Err(ZipError::FileNotFound) => {}
+                Err(e) => return Err(Error::from(e)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Opens the first of `names` which the package has an entry for,
+    /// returning its position in `names` along with the entry.
+    pub fn open_any(&mut self, names: &[&str]) -> Result<Option<(usize, PackageEntry<'_>)>, Error> {
+        match self.find_any(names)? {
+            Some(i) => Ok(self.open_entry(names[i])?.map(|entry| (i, entry))),
+            None => Ok(None),
+        }
+    }
+
+    /// Opens the entry named exactly `name`, or returns `None` if there is
+    /// no such entry.
+    pub fn open_entry(&mut self, name: &str) -> Result<Option<PackageEntry<'_>>, Error> {
+        if let Some(header) = self.indexed_header(name)? {
+            return Ok(Some(self.read_at(&header)?));
+        }
+        match self.archive()?.by_name(name) {
+            Ok(entry) => Ok(Some(PackageEntry::of_zip_file(entry))),
+            Err(ZipError::FileNotFound) => Ok(None),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    /// Reads the full contents of an entry, decompressed as
+    /// `archive::read_entry` does.
+    pub fn read_entry(&mut self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let entry = match self.open_entry(name)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let mut contents = Vec::with_capacity(entry.size.min(super::MAX_PREALLOCATION) as usize);
+        super::decompressed(name, entry)?.read_to_end(&mut contents)?;
+        Ok(Some(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    #[test]
+    fn entries_are_read_through_the_index_without_the_directory() {
+        let dir = TempDir::new("indexed");
+        let path = dir.join("package.slpk");
+        let unindexed_package = SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"id":0}"#)
+            .entry("nodes/0/geometries/0.bin", &[7; 100]);
+        let package = unindexed_package.clone().with_hash_index();
+        package.write_to_file(&path).unwrap();
+
+        let mut indexed = IndexedPackage::open(&path).unwrap();
+        let document = indexed.read_entry("3dSceneLayer.json.gz").unwrap();
+        let mut geometry = Vec::new();
+        let (position, mut entry) = indexed
+            .open_any(&["nodes/0/geometries/0.bin.gz", "nodes/0/geometries/0.bin"])
+            .unwrap()
+            .unwrap();
+        entry.read_to_end(&mut geometry).unwrap();
+        let sizes = (entry.compressed_size, entry.size);
+        drop(entry);
+        let read_directory = indexed.archive.is_some();
+        // The index only gives lower case names, so a name in another case
+        // is looked for in the directory, where it isn't.
+        let other_case = indexed.read_entry("3dscenelayer.json.gz").unwrap();
+
+        // Without an index, the same entries are read from the directory.
+        unindexed_package.clone().write_to_file(&path).unwrap();
+        let mut unindexed = IndexedPackage::open(&path).unwrap();
+        let unindexed_document = unindexed.read_entry("3dSceneLayer.json.gz").unwrap();
+
+        // A record pointing at the header of another entry, here the first
+        // at the start of the package, is passed over.
+        unindexed_package
+            .with_hash_index_record("nodes/0/geometries/0.bin", 0)
+            .write_to_file(&path)
+            .unwrap();
+        let mut stale = IndexedPackage::open(&path).unwrap();
+        let stale_geometry = stale.read_entry("nodes/0/geometries/0.bin").unwrap();
+
+        assert_eq!(document.as_deref(), Some(&br#"{"id":0}"#[..]));
+        assert_eq!((position, geometry), (1, vec![7; 100]));
+        assert_eq!(sizes, (100, 100));
+        assert!(!read_directory);
+        assert_eq!(other_case, None);
+        assert!(!unindexed.has_index());
+        assert_eq!(unindexed_document, document);
+        assert!(stale.has_index());
+        assert_eq!(stale_geometry, Some(vec![7; 100]));
+    }
+}
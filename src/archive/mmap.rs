@@ -0,0 +1,150 @@
+// Maps a package into memory, read only, so that every worker can read it
+// through a cursor over the one mapping rather than through a file handle
+// of its own, with a read call for each buffer it fills. Packages with many
+// small entries spend much of their time in those calls, as every entry is
+// a seek to its local header followed by a few short reads. Mapping is only
+// done on Unix, through mmap itself; elsewhere, and whenever a file can't be
+// mapped, such as on a network file system which doesn't support it,
+// `Mapping::open` fails and the caller reads the file as usual. A package
+// must not be truncated while it is mapped, as reading a page past its new
+// end kills the process with SIGBUS rather than failing the read.
+
+use std::io;
+use std::path::Path;
+
+/// A read only mapping of a whole file, unmapped when dropped.
+pub struct Mapping {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The mapping is never written to, so it can be read from any thread.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Maps the file at `path`. An empty file can't be mapped, and fails
+    /// like any other.
+    #[cfg(unix)]
+    pub fn open(path: &Path) -> io::Result<Mapping> {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::other("the file is too large to map"))?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "an empty file can't be mapped",
+            ));
+        }
+        // The mapping holds on to the file, so it can be closed once mapped.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(_path: &Path) -> io::Result<Mapping> {
+        Err(io::Error::other("memory mapping is only supported on Unix"))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use std::time::{Duration, Instant};
+
+    /// A package of `count` small entries, written to `path`.
+    fn many_small_entries(path: &Path, count: usize) {
+        let mut package = SyntheticPackage::new().entry("3dSceneLayer.json.gz", b"{}");
+        for i in 0..count {
+            let name = format!("nodes/{}/features/0.json", i);
+            package = package.entry(&name, format!("{{\"id\":{}}}", i).as_bytes());
+        }
+        package.write_to_file(path).unwrap();
+    }
+
+    #[test]
+    fn mapped_packages_unpack_like_read_ones() {
+        let dir = TempDir::new("mmap");
+        let path = dir.join("package.slpk");
+        many_small_entries(&path, 20);
+        let mapped = Mapping::open(&path).map(|mapping| mapping.as_slice().to_vec());
+        let read = std::fs::read(&path).unwrap();
+        let options = UnpackOptions::new()
+            .mmap(true)
+            .output_dir(dir.join("mapped"));
+        let summary = unpack_with_options(&path, &options);
+        let written = std::fs::read(dir.join("mapped/package/nodes/7/features/0.json"));
+        let empty = dir.join("empty.slpk");
+        std::fs::write(&empty, b"").unwrap();
+        let empty_mapping = Mapping::open(&empty);
+
+        if cfg!(unix) {
+            assert_eq!(mapped.unwrap(), read);
+        }
+        assert_eq!(summary.unwrap().entries_unpacked, 21);
+        assert_eq!(written.unwrap(), b"{\"id\":7}");
+        assert!(empty_mapping.is_err());
+    }
+
+    /// Compares reading a package of many small entries through a mapping
+    /// with reading it through files. Dry runs read every entry without
+    /// writing any, so that writing files doesn't drown out the reads. Run
+    /// it with `cargo test --release -- --ignored --nocapture mmap`.
+    #[test]
+    #[ignore]
+    fn benchmark_mapped_and_file_reads() {
+        let dir = TempDir::new("mmap-bench");
+        let path = dir.join("package.slpk");
+        many_small_entries(&path, 20_000);
+        let time = |mmap: bool| {
+            let mut best = Duration::MAX;
+            for _ in 0..5 {
+                let options = UnpackOptions::new().mmap(mmap).dry_run(true);
+                let start = Instant::now();
+                unpack_with_options(&path, &options).unwrap();
+                best = best.min(start.elapsed());
+            }
+            best
+        };
+        let files = time(false);
+        let mapped = time(true);
+
+        println!(
+            "20000 entries: {:?} through files, {:?} mapped",
+            files, mapped
+        );
+    }
+}
@@ -0,0 +1,327 @@
+pub mod encryption;
+mod flavor;
+pub mod hash_index;
+pub mod indexed;
+pub mod mmap;
+pub mod names;
+pub mod raw;
+
+use crate::json::Value;
+use failure::Error;
+use flate2::read::GzDecoder;
+use flate2::GzHeader;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+pub use self::flavor::PackageFlavor;
+pub use self::indexed::IndexedPackage;
+
+/// The first bytes of every gzip stream.
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether content starts like a gzip stream, whatever its entry is named.
+pub fn is_gzip(prefix: &[u8]) -> bool {
+    prefix.starts_with(&GZIP_MAGIC)
+}
+
+/// What the header of a gzipped entry says about where its content came
+/// from. Some exporters record the name and export time of each resource
+/// here, but most leave the fields empty and the time zeroed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GzipProvenance {
+    /// Seconds since the Unix epoch.
+    pub mtime: Option<u32>,
+    pub filename: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl GzipProvenance {
+    /// Returns `None` when the header records nothing.
+    pub fn of_header(header: &GzHeader) -> Option<GzipProvenance> {
+        // The fields are Latin-1 by the specification, but exporters write
+        // UTF-8 if anything.
+        let text = |field: Option<&[u8]>| {
+            field
+                .filter(|bytes| !bytes.is_empty())
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        };
+        let provenance = GzipProvenance {
+            mtime: Some(header.mtime()).filter(|&mtime| mtime != 0),
+            filename: text(header.filename()),
+            comment: text(header.comment()),
+        };
+        if provenance == GzipProvenance::default() {
+            None
+        } else {
+            Some(provenance)
+        }
+    }
+
+    /// The fields the header records, as an object with `mtime`, `filename`
+    /// and `comment` members, leaving out those it doesn't.
+    pub fn to_json(&self) -> Value {
+        let mut header = Vec::new();
+        if let Some(mtime) = self.mtime {
+            header.push(("mtime".to_string(), Value::from(u64::from(mtime))));
+        }
+        if let Some(filename) = &self.filename {
+            header.push(("filename".to_string(), Value::from(filename.as_str())));
+        }
+        if let Some(comment) = &self.comment {
+            header.push(("comment".to_string(), Value::from(comment.as_str())));
+        }
+        Value::Object(header)
+    }
+
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.mtime
+            .map(|mtime| UNIX_EPOCH + Duration::from_secs(u64::from(mtime)))
+    }
+}
+
+/// What reading an entry tells about it beyond its zip headers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryMeta {
+    /// What the gzip header of a gzipped entry records, if anything.
+    pub gzip: Option<GzipProvenance>,
+}
+
+/// Reads what `EntryMeta` holds about entry `index`, which takes no more of
+/// a gzipped entry than its gzip header, and nothing of any other.
+pub fn entry_meta<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+) -> Result<EntryMeta, Error> {
+    let entry = archive.by_index(index)?;
+    let gzip = if entry.name().ends_with(".gz") {
+        GzDecoder::new(entry)
+            .header()
+            .and_then(GzipProvenance::of_header)
+    } else {
+        None
+    };
+    Ok(EntryMeta { gzip })
+}
+
+pub fn open_slpk_archive(slpk_file_path: &Path) -> Result<ZipArchive<impl Read + Seek>, Error> {
+    let file = File::open(slpk_file_path)?;
+    let buf_reader = BufReader::new(file);
+    Ok(ZipArchive::new(buf_reader)?)
+}
+
+/// The contents of the entry `name`, decompressed when it is named `.gz`
+/// and starts like a gzip stream. Packages such as `.eslpk` ones keep their
+/// `.gz` names for resources which are stored as they are.
+fn decompressed<'a>(name: &str, mut entry: impl Read + 'a) -> Result<Box<dyn Read + 'a>, Error> {
+    let named_gzip = name.ends_with(".gz");
+    let mut magic = Vec::with_capacity(GZIP_MAGIC.len());
+    (&mut entry)
+        .take(GZIP_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let gzipped = named_gzip && is_gzip(&magic);
+    let contents = Cursor::new(magic).chain(entry);
+    Ok(if gzipped {
+        Box::new(GzDecoder::new(contents))
+    } else {
+        Box::new(contents)
+    })
+}
+
+/// The most memory set aside for an entry before reading it, whatever size
+/// its zip header gives.
+pub(crate) const MAX_PREALLOCATION: u64 = 16 * 1024 * 1024;
+
+/// Reads the full contents of the named entry, decompressing it when the
+/// entry name ends with `.gz` and it is gzipped. Returns `None` if no such
+/// entry exists.
+pub fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    entry_name: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    let archive_entry = match archive.by_name(entry_name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(Error::from(e)),
+    };
+
+    // The size comes from the package, so it is only trusted so far.
+    let capacity = archive_entry.size().min(MAX_PREALLOCATION);
+    let mut contents = Vec::with_capacity(capacity as usize);
+    let name = archive_entry.name().to_string();
+    decompressed(&name, archive_entry)?.read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+/// Looks up a resource by its logical name, trying the gzipped variant of
+/// the name first since that is how resources are normally stored.
+pub fn read_resource<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    resource_name: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    if let Some(contents) = read_entry(archive, &format!("{}.gz", resource_name))? {
+        return Ok(Some(contents));
+    }
+    read_entry(archive, resource_name)
+}
+
+/// Reads up to `len` bytes from the start of an entry, decompressing it
+/// as `read_entry` does.
+pub fn read_entry_prefix<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    entry_name: &str,
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let entry = archive.by_name(entry_name)?;
+    let mut prefix = Vec::with_capacity(len);
+    decompressed(entry_name, entry)?
+        .take(len as u64)
+        .read_to_end(&mut prefix)?;
+    Ok(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::raw::{RawEntry, RawWriter};
+    use super::*;
+    use crate::synthetic::TempDir;
+    use flate2::write::GzEncoder;
+    use flate2::{Compression, GzBuilder};
+    use std::io::{SeekFrom, Write};
+    use std::ops::Range;
+
+    fn header_of(gzipped: Vec<u8>) -> Option<GzipProvenance> {
+        let mut decoder = GzDecoder::new(&gzipped[..]);
+        std::io::copy(&mut decoder, &mut std::io::sink()).unwrap();
+        GzipProvenance::of_header(decoder.header().unwrap())
+    }
+
+    #[test]
+    fn reads_provenance_from_gzip_headers() {
+        let mut encoder = GzBuilder::new()
+            .filename("0.bin")
+            .comment("exported by a tool")
+            .mtime(1_551_443_410)
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        let provenance = header_of(encoder.finish().unwrap()).unwrap();
+        assert_eq!(provenance.filename.as_deref(), Some("0.bin"));
+        assert_eq!(provenance.comment.as_deref(), Some("exported by a tool"));
+        assert_eq!(
+            provenance.modified(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_551_443_410))
+        );
+
+        let mut encoder = GzBuilder::new()
+            .mtime(1_551_443_410)
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        let provenance = header_of(encoder.finish().unwrap()).unwrap();
+        assert_eq!((provenance.filename, provenance.comment), (None, None));
+
+        // A zeroed header, as most exporters write.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        assert_eq!(header_of(encoder.finish().unwrap()), None);
+    }
+
+    /// Writes to a file, seeking over the bytes in `hole` rather than
+    /// writing them, so that a package can be gigabytes long without taking
+    /// up the space.
+    struct SparseFile {
+        file: File,
+        position: u64,
+        hole: Range<u64>,
+    }
+
+    impl Write for SparseFile {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = buf.len() as u64;
+            let written = if self.hole.contains(&self.position) {
+                let skipped = len.min(self.hole.end - self.position);
+                self.file.seek(SeekFrom::Current(skipped as i64))?;
+                skipped as usize
+            } else if self.position < self.hole.start {
+                let before_hole = len.min(self.hole.start - self.position) as usize;
+                self.file.write(&buf[..before_hole])?
+            } else {
+                self.file.write(buf)?
+            };
+            self.position += written as u64;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.file.flush()
+        }
+    }
+
+    /// Data which a `SparseFile` doesn't write, so it isn't filled in.
+    struct Unwritten;
+
+    impl Read for Unwritten {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn zip64_packages_are_listed_and_unpacked() {
+        let dir = TempDir::new("zip64");
+        let path = dir.join("package.slpk");
+        // A stored entry larger than 4 GiB, so that the entries after it
+        // start beyond 4 GiB, and more entries than the end of central
+        // directory record can count.
+        let large_size = 4_500_000_000;
+        let mut large = RawEntry::default();
+        large.name = b"nodes/0/textures/0.bin".to_vec();
+        (large.size, large.compressed_size) = (large_size, large_size);
+        // Its local header has a zip64 field with both of its sizes. The
+        // data is never read, so its CRC isn't worked out.
+        let data_start = 30 + large.name.len() as u64 + 20;
+        let mut writer = RawWriter::new(SparseFile {
+            file: File::create(&path).unwrap(),
+            position: 0,
+            hole: data_start..data_start + large_size,
+        });
+        writer.write_entry(&large, &mut Unwritten).unwrap();
+        let num_small = 70_000;
+        for i in 0..num_small + 1 {
+            let (name, contents) = if i < num_small {
+                (format!("nodes/{}/geometries/0.bin", i + 1), vec![1; 4])
+            } else {
+                (
+                    "metadata.json".to_string(),
+                    b"{\"folderPattern\":\"1\"}".to_vec(),
+                )
+            };
+            let mut entry = RawEntry::default();
+            entry.name = name.into_bytes();
+            let data = entry.replace_contents(&contents).unwrap();
+            writer.write_entry(&entry, &mut &data[..]).unwrap();
+        }
+        writer.finish(b"").unwrap();
+
+        let entries = crate::list::list(&path).unwrap();
+        let options = crate::unpack::UnpackOptions::new().hardened(true).filters(
+            crate::unpack::EntryFilters {
+                include: vec![crate::unpack::PathPattern::new("metadata.json")],
+                ..crate::unpack::EntryFilters::default()
+            },
+        );
+        let summary = crate::unpack::unpack_with_options(&path, &options).unwrap();
+        let metadata = std::fs::read(dir.join("package/metadata.json")).unwrap();
+
+        assert_eq!(entries.len(), num_small + 2);
+        assert_eq!(entries[0].size, large_size);
+        assert_eq!(entries[0].compressed_size, large_size);
+        assert_eq!(summary.entries_unpacked, 1);
+        assert_eq!(metadata, b"{\"folderPattern\":\"1\"}");
+    }
+}
@@ -0,0 +1,189 @@
+// Entry names are stored as bytes, and only a flag in the zip headers says
+// whether they are UTF-8. Without it, the zip library reads them as CP437,
+// the code page of DOS, which turns the UTF-8 names most tools write without
+// setting the flag into mojibake. So names are decoded here from their raw
+// bytes instead: as UTF-8 whenever they are valid UTF-8, as CP437 when they
+// aren't and the flag isn't set, and otherwise, for a name flagged as UTF-8
+// which isn't, with its invalid bytes percent-encoded, so that the entry is
+// still unpacked somewhere which is the same on every run. Lookups by name
+// still go through the zip library's names, which is how it indexes them.
+
+use super::raw::RawEntry;
+use std::path::{Component, Path, PathBuf};
+use zip::read::ZipFile;
+
+/// How the name of an entry was decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NameDecoding {
+    Utf8,
+    Cp437,
+    PercentEncoded,
+}
+
+/// Decodes the raw bytes of an entry name. `zip_name` is the zip library's
+/// decoding of them, which is CP437 unless the UTF-8 flag is set, when
+/// invalid bytes come out as replacement characters.
+pub fn decode_name(raw: &[u8], zip_name: &str) -> (String, NameDecoding) {
+    if let Ok(name) = std::str::from_utf8(raw) {
+        return (name.to_string(), NameDecoding::Utf8);
+    }
+    // CP437 gives every byte a character, and never the replacement one.
+    if !zip_name.contains('\u{fffd}') {
+        return (zip_name.to_string(), NameDecoding::Cp437);
+    }
+    let mut name = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Err(error) = std::str::from_utf8(rest) {
+        let (valid, after) = rest.split_at(error.valid_up_to());
+        name.push_str(std::str::from_utf8(valid).unwrap_or_default());
+        let (invalid, after) = after.split_at(error.error_len().unwrap_or(after.len()));
+        for byte in invalid {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+        rest = after;
+    }
+    name.push_str(std::str::from_utf8(rest).unwrap_or_default());
+    (name, NameDecoding::PercentEncoded)
+}
+
+/// Bit 11 of the flags: the name and comment of the entry are UTF-8.
+const UTF8_FLAG: u16 = 1 << 11;
+
+/// The characters of the bytes from 0x80 up in CP437, below which it is
+/// ASCII.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ',
+    'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ',
+    'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕',
+    '╣', '║', '╗', '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦',
+    '╠', '═', '╬', '╧', '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐',
+    '▀', 'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±',
+    '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// `raw` read as CP437, which gives every byte a character.
+fn cp437(raw: &[u8]) -> String {
+    raw.iter()
+        .map(|&byte| match byte {
+            0..=0x7f => char::from(byte),
+            _ => CP437_HIGH[usize::from(byte - 0x80)],
+        })
+        .collect()
+}
+
+/// The name of an entry, decoded by `decode_name`, with a warning when it
+/// isn't UTF-8.
+pub fn decoded_name(entry: &ZipFile) -> String {
+    warn_decoding(decode_name(entry.name_raw(), entry.name()))
+}
+
+/// The name of an entry read from the zip directory by `archive::raw`,
+/// decoded as `decoded_name` decodes those the zip library reads.
+pub fn decoded_raw_name(entry: &RawEntry) -> String {
+    let zip_name = if entry.flags & UTF8_FLAG != 0 {
+        String::from_utf8_lossy(&entry.name).into_owned()
+    } else {
+        cp437(&entry.name)
+    };
+    warn_decoding(decode_name(&entry.name, &zip_name))
+}
+
+fn warn_decoding((name, decoding): (String, NameDecoding)) -> String {
+    match decoding {
+        NameDecoding::Utf8 => {}
+        NameDecoding::Cp437 => warn!(
+            "The name of entry {} isn't UTF-8, so it was read as CP437",
+            name
+        ),
+        NameDecoding::PercentEncoded => warn!(
+            "The name of entry {} isn't valid UTF-8, so its invalid bytes were percent-encoded",
+            name
+        ),
+    }
+    name
+}
+
+/// An entry name as a relative path, as the zip library sanitizes its own
+/// names: cut at the first NUL, with either slash as a separator, and
+/// without roots, drive letters, `.` or `..`.
+pub fn sanitized_path(name: &str) -> PathBuf {
+    let name = name.split('\0').next().unwrap_or_default();
+    let opposite_separator = if std::path::MAIN_SEPARATOR == '/' {
+        '\\'
+    } else {
+        '/'
+    };
+    let name = name.replace(opposite_separator, std::path::MAIN_SEPARATOR_STR);
+    Path::new(&name)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The sanitized path of an entry, from its decoded name.
+pub fn entry_path(entry: &ZipFile) -> PathBuf {
+    sanitized_path(&decode_name(entry.name_raw(), entry.name()).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::list;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn names_fall_back_from_utf8_to_cp437_and_percent_encoding() {
+        // The zip library reads unflagged names as CP437.
+        assert_eq!(
+            decode_name("Curaçao/0.jpg".as_bytes(), "Cura├ºao/0.jpg"),
+            ("Curaçao/0.jpg".to_string(), NameDecoding::Utf8)
+        );
+        assert_eq!(
+            decode_name(b"Cura\x87ao/0.jpg", "Curaçao/0.jpg"),
+            ("Curaçao/0.jpg".to_string(), NameDecoding::Cp437)
+        );
+        assert_eq!(
+            decode_name(b"Cura\x87ao/0.jpg", "Cura\u{fffd}ao/0.jpg"),
+            ("Cura%87ao/0.jpg".to_string(), NameDecoding::PercentEncoded)
+        );
+        assert_eq!(
+            sanitized_path("/nodes/../0/a.bin\0junk"),
+            Path::new("nodes").join("0").join("a.bin")
+        );
+    }
+
+    #[test]
+    fn unpacking_and_listing_use_the_decoded_names() {
+        let dir = TempDir::new("names");
+        let path = dir.join("package.slpk");
+        let mut package = SyntheticPackage::new()
+            .entry("Cura_ao/0.jpg", b"cp437")
+            .entry("Bogot\u{e1}/0.jpg", b"utf-8")
+            .to_bytes()
+            .unwrap();
+        // An unflagged CP437 name, and a flagged name which isn't UTF-8, in
+        // both the local and the central headers.
+        let replace = |package: &mut Vec<u8>, from: &[u8], to: &[u8]| {
+            let mut at = 0;
+            while let Some(found) = package[at..].windows(from.len()).position(|w| w == from) {
+                package[at + found..at + found + from.len()].copy_from_slice(to);
+                at += found + from.len();
+            }
+        };
+        replace(&mut package, b"Cura_ao", b"Cura\x87ao");
+        replace(&mut package, "Bogot\u{e1}".as_bytes(), b"Bogot\xe1\xe1");
+        std::fs::write(&path, package).unwrap();
+        let names: Vec<String> = list(&path).unwrap().into_iter().map(|e| e.name).collect();
+        unpack_with_options(&path, &UnpackOptions::new()).unwrap();
+        let cp437 = std::fs::read(dir.join("package/Cura\u{e7}ao/0.jpg"));
+        let encoded = std::fs::read(dir.join("package/Bogot%E1%E1/0.jpg"));
+
+        assert_eq!(names, ["Cura\u{e7}ao/0.jpg", "Bogot%E1%E1/0.jpg"]);
+        assert_eq!(cp437.unwrap(), b"cp437");
+        assert_eq!(encoded.unwrap(), b"utf-8");
+    }
+}
@@ -0,0 +1,742 @@
+// Reading and writing zip records as they are, for the commands which rewrite
+// packages. The zip library only gives the name, sizes and times of an entry,
+// so copying entries through it drops their extra fields (NTFS timestamps,
+// Unix owners, alignment padding) and comments. Here entries are copied with
+// their compressed data and every field untouched, except for the zip64
+// fields, which are regenerated for where the entry ends up.
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use failure::Error;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// The Info-ZIP Unicode path field, which holds the CRC of the name it
+/// replaces, so it is wrong once an entry is renamed.
+const UNICODE_PATH_EXTRA_ID: u16 = 0x7075;
+
+/// Bit 3 of the flags: the sizes and CRC follow the data. The copies always
+/// have them in the header instead.
+const DATA_DESCRIPTOR_FLAG: u16 = 0x0008;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const ZIP64_VERSION_NEEDED: u16 = 45;
+
+/// Fields which don't fit are stored in the zip64 extra field instead.
+const U32_MAX: u64 = 0xffff_ffff;
+const U16_MAX: u64 = 0xffff;
+
+/// How far from the end of the package the end of central directory record
+/// can be: its own length plus the longest comment.
+const END_OF_CENTRAL_DIRECTORY_SEARCH: u64 = 22 + 0xffff;
+
+/// How much of the end of the central directory `find_trailing_entry`
+/// searches, which holds the last thousand or so headers.
+const TRAILING_ENTRY_SEARCH: u64 = 64 * 1024;
+
+#[derive(Debug, Fail)]
+pub enum RawZipError {
+    #[fail(display = "The package has no end of central directory record")]
+    NoEndOfCentralDirectory,
+
+    #[fail(display = "The zip64 end of central directory record is missing")]
+    NoZip64EndOfCentralDirectory,
+
+    #[fail(display = "Entry {} has no central directory header", _0)]
+    BadCentralHeader(usize),
+
+    #[fail(display = "Entry {} has no local header", _0)]
+    BadLocalHeader(String),
+}
+
+/// An entry as recorded in the central directory, with the extra field of
+/// its local header. The zip64 fields are folded into the sizes and left
+/// out of the extra fields.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawEntry {
+    pub name: Vec<u8>,
+    pub version_made_by: u16,
+    pub version_needed: u16,
+    pub flags: u16,
+    pub compression_method: u16,
+    pub last_modified_time: u16,
+    pub last_modified_date: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub extra: Vec<u8>,
+    pub local_extra: Vec<u8>,
+    pub comment: Vec<u8>,
+    pub internal_attributes: u16,
+    pub external_attributes: u32,
+    /// Where the compressed data starts in the package it was read from.
+    data_start: u64,
+}
+
+impl RawEntry {
+    pub fn name_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.name).into_owned()
+    }
+
+    /// Whether the data of the entry is its contents, rather than deflated.
+    pub fn is_stored(&self) -> bool {
+        self.compression_method == STORED
+    }
+
+    pub fn is_deflated(&self) -> bool {
+        self.compression_method == DEFLATED
+    }
+
+    /// Gives the entry a new name, dropping the fields which record the old
+    /// one.
+    pub fn rename(&mut self, name: &str) {
+        self.name = name.as_bytes().to_vec();
+        self.extra = without_field(&self.extra, UNICODE_PATH_EXTRA_ID);
+        self.local_extra = without_field(&self.local_extra, UNICODE_PATH_EXTRA_ID);
+    }
+
+    /// Gives the entry new contents, returning the data to write for it.
+    /// Stored entries stay stored, and the rest are deflated whatever they
+    /// were compressed with.
+    pub fn replace_contents(&mut self, contents: &[u8]) -> std::io::Result<Vec<u8>> {
+        let data = if self.compression_method == STORED {
+            contents.to_vec()
+        } else {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(contents)?;
+            self.compression_method = DEFLATED;
+            encoder.finish()?
+        };
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(contents);
+        self.crc32 = hasher.finalize();
+        self.size = contents.len() as u64;
+        self.compressed_size = data.len() as u64;
+        Ok(data)
+    }
+}
+
+/// The extra fields in `extra` with any field of type `id` taken out.
+/// Anything after a malformed field is kept as it is.
+fn without_field(extra: &[u8], id: u16) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(extra.len());
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let field_id = LittleEndian::read_u16(&rest[..2]);
+        let len = 4 + LittleEndian::read_u16(&rest[2..4]) as usize;
+        if len > rest.len() {
+            break;
+        }
+        if field_id != id {
+            kept.extend_from_slice(&rest[..len]);
+        }
+        rest = &rest[len..];
+    }
+    kept.extend_from_slice(rest);
+    kept
+}
+
+/// The data of the first field of type `id` in `extra`, if there is one.
+fn find_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let field_id = LittleEndian::read_u16(&rest[..2]);
+        let len = LittleEndian::read_u16(&rest[2..4]) as usize;
+        if 4 + len > rest.len() {
+            return None;
+        }
+        if field_id == id {
+            return Some(&rest[4..4 + len]);
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
+
+#[derive(Debug)]
+pub struct RawArchive {
+    pub entries: Vec<RawEntry>,
+    /// The comment of the package as a whole.
+    pub comment: Vec<u8>,
+}
+
+struct EndOfCentralDirectory {
+    entries: u64,
+    offset: u64,
+    comment: Vec<u8>,
+    /// Where the record itself starts.
+    position: u64,
+}
+
+fn read_end_of_central_directory<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<EndOfCentralDirectory, Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let search_start = len.saturating_sub(END_OF_CENTRAL_DIRECTORY_SEARCH);
+    reader.seek(SeekFrom::Start(search_start))?;
+    let mut tail = Vec::new();
+    reader.read_to_end(&mut tail)?;
+    let position = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| {
+            (&tail[i..]).read_u32::<LittleEndian>().ok() == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+        })
+        .ok_or(RawZipError::NoEndOfCentralDirectory)?;
+    let mut record = &tail[position + 4..];
+    let _disk = record.read_u16::<LittleEndian>()?;
+    let _central_directory_disk = record.read_u16::<LittleEndian>()?;
+    let _entries_on_disk = record.read_u16::<LittleEndian>()?;
+    let entries = record.read_u16::<LittleEndian>()?;
+    let size = record.read_u32::<LittleEndian>()?;
+    let offset = record.read_u32::<LittleEndian>()?;
+    let comment_len = record.read_u16::<LittleEndian>()? as usize;
+    let comment = record[..comment_len.min(record.len())].to_vec();
+    let position = search_start + position as u64;
+
+    if u64::from(entries) != U16_MAX && u64::from(size) != U32_MAX && u64::from(offset) != U32_MAX {
+        return Ok(EndOfCentralDirectory {
+            entries: u64::from(entries),
+            offset: u64::from(offset),
+            comment,
+            position,
+        });
+    }
+
+    // The locator is just in front of the end of central directory record.
+    let locator_start = position
+        .checked_sub(20)
+        .ok_or(RawZipError::NoZip64EndOfCentralDirectory)?;
+    reader.seek(SeekFrom::Start(locator_start))?;
+    if reader.read_u32::<LittleEndian>()? != ZIP64_LOCATOR_SIGNATURE {
+        return Err(RawZipError::NoZip64EndOfCentralDirectory.into());
+    }
+    let _disk = reader.read_u32::<LittleEndian>()?;
+    let zip64_end = reader.read_u64::<LittleEndian>()?;
+    reader.seek(SeekFrom::Start(zip64_end))?;
+    if reader.read_u32::<LittleEndian>()? != ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE {
+        return Err(RawZipError::NoZip64EndOfCentralDirectory.into());
+    }
+    let _record_size = reader.read_u64::<LittleEndian>()?;
+    let _version_made_by = reader.read_u16::<LittleEndian>()?;
+    let _version_needed = reader.read_u16::<LittleEndian>()?;
+    let _disk = reader.read_u32::<LittleEndian>()?;
+    let _central_directory_disk = reader.read_u32::<LittleEndian>()?;
+    let _entries_on_disk = reader.read_u64::<LittleEndian>()?;
+    let entries = reader.read_u64::<LittleEndian>()?;
+    let _size = reader.read_u64::<LittleEndian>()?;
+    let offset = reader.read_u64::<LittleEndian>()?;
+    Ok(EndOfCentralDirectory {
+        entries,
+        offset,
+        comment,
+        position,
+    })
+}
+
+/// How many entries the end of central directory record of a package says
+/// it holds, without reading the central directory itself.
+pub fn declared_entries<R: Read + Seek>(reader: &mut R) -> Result<u64, Error> {
+    Ok(read_end_of_central_directory(reader)?.entries)
+}
+
+/// Where the central directory of a package starts.
+pub fn central_directory_offset<R: Read + Seek>(reader: &mut R) -> Result<u64, Error> {
+    Ok(read_end_of_central_directory(reader)?.offset)
+}
+
+/// The offset of the local header of the entry named `name`, when its
+/// central header is among the last of the central directory, without
+/// reading the rest of the directory. Returns `None` when it isn't found
+/// there, whether or not the package has such an entry.
+pub fn find_trailing_entry<R: Read + Seek>(
+    reader: &mut R,
+    name: &[u8],
+) -> Result<Option<u64>, Error> {
+    let end = read_end_of_central_directory(reader)?;
+    let start = end
+        .position
+        .saturating_sub(TRAILING_ENTRY_SEARCH)
+        .max(end.offset);
+    if start >= end.position {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    let mut tail = vec![0u8; (end.position - start) as usize];
+    reader.read_exact(&mut tail)?;
+    for i in (0..tail.len().saturating_sub(46)).rev() {
+        let header = &tail[i..];
+        if LittleEndian::read_u32(header) != CENTRAL_HEADER_SIGNATURE {
+            continue;
+        }
+        let name_len = LittleEndian::read_u16(&header[28..]) as usize;
+        let extra_len = LittleEndian::read_u16(&header[30..]) as usize;
+        let extra_start = 46 + name_len;
+        if name_len != name.len()
+            || header.len() < extra_start + extra_len
+            || &header[46..extra_start] != name
+        {
+            continue;
+        }
+        let mut offset = u64::from(LittleEndian::read_u32(&header[42..]));
+        if offset == U32_MAX {
+            let extra = &header[extra_start..extra_start + extra_len];
+            let mut zip64 = match find_field(extra, ZIP64_EXTRA_ID) {
+                Some(zip64) => zip64,
+                None => return Ok(None),
+            };
+            // The sizes come first in the zip64 field when they don't fit.
+            for field in [24, 20] {
+                if u64::from(LittleEndian::read_u32(&header[field..])) == U32_MAX {
+                    zip64.read_u64::<LittleEndian>()?;
+                }
+            }
+            offset = zip64.read_u64::<LittleEndian>()?;
+        }
+        return Ok(Some(offset));
+    }
+    Ok(None)
+}
+
+/// What the local header of an entry says about it, for reading an entry
+/// from a known offset without the central directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalHeader {
+    pub name: Vec<u8>,
+    pub flags: u16,
+    pub compression_method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub data_start: u64,
+}
+
+impl LocalHeader {
+    /// Whether the entry can be read from what the header says: its sizes
+    /// and CRC are in the header rather than a data descriptor after the
+    /// data, and it is stored or deflated.
+    pub fn is_readable(&self) -> bool {
+        self.flags & DATA_DESCRIPTOR_FLAG == 0
+            && (self.compression_method == STORED || self.compression_method == DEFLATED)
+    }
+
+    pub fn is_deflated(&self) -> bool {
+        self.compression_method == DEFLATED
+    }
+}
+
+/// Reads the local header at `offset`, or returns `None` if there isn't a
+/// local header there.
+pub fn read_local_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<Option<LocalHeader>, Error> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut fixed = [0u8; 30];
+    match reader.read_exact(&mut fixed) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    if LittleEndian::read_u32(&fixed) != LOCAL_HEADER_SIGNATURE {
+        return Ok(None);
+    }
+    let name_len = LittleEndian::read_u16(&fixed[26..]) as usize;
+    let extra_len = LittleEndian::read_u16(&fixed[28..]) as usize;
+    let mut name = vec![0u8; name_len];
+    reader.read_exact(&mut name)?;
+    let mut extra = vec![0u8; extra_len];
+    reader.read_exact(&mut extra)?;
+    let mut compressed_size = u64::from(LittleEndian::read_u32(&fixed[18..]));
+    let mut size = u64::from(LittleEndian::read_u32(&fixed[22..]));
+    // The zip64 field of a local header holds both sizes, in this order.
+    if size == U32_MAX || compressed_size == U32_MAX {
+        if let Some(mut zip64) = find_field(&extra, ZIP64_EXTRA_ID) {
+            size = zip64.read_u64::<LittleEndian>()?;
+            compressed_size = zip64.read_u64::<LittleEndian>()?;
+        }
+    }
+    Ok(Some(LocalHeader {
+        name,
+        flags: LittleEndian::read_u16(&fixed[6..]),
+        compression_method: LittleEndian::read_u16(&fixed[8..]),
+        crc32: LittleEndian::read_u32(&fixed[14..]),
+        compressed_size,
+        size,
+        data_start: offset + 30 + name_len as u64 + extra_len as u64,
+    }))
+}
+
+/// Reads the central header of entry `i`, giving the entry without the
+/// fields of its local header, and the offset of that header.
+fn read_central_header<R: Read>(reader: &mut R, i: usize) -> Result<(RawEntry, u64), Error> {
+    if reader.read_u32::<LittleEndian>()? != CENTRAL_HEADER_SIGNATURE {
+        return Err(RawZipError::BadCentralHeader(i).into());
+    }
+    let version_made_by = reader.read_u16::<LittleEndian>()?;
+    let version_needed = reader.read_u16::<LittleEndian>()?;
+    let flags = reader.read_u16::<LittleEndian>()?;
+    let compression_method = reader.read_u16::<LittleEndian>()?;
+    let last_modified_time = reader.read_u16::<LittleEndian>()?;
+    let last_modified_date = reader.read_u16::<LittleEndian>()?;
+    let crc32 = reader.read_u32::<LittleEndian>()?;
+    let mut compressed_size = u64::from(reader.read_u32::<LittleEndian>()?);
+    let mut size = u64::from(reader.read_u32::<LittleEndian>()?);
+    let name_len = reader.read_u16::<LittleEndian>()? as usize;
+    let extra_len = reader.read_u16::<LittleEndian>()? as usize;
+    let comment_len = reader.read_u16::<LittleEndian>()? as usize;
+    let _disk = reader.read_u16::<LittleEndian>()?;
+    let internal_attributes = reader.read_u16::<LittleEndian>()?;
+    let external_attributes = reader.read_u32::<LittleEndian>()?;
+    let mut local_header_offset = u64::from(reader.read_u32::<LittleEndian>()?);
+    let mut name = vec![0u8; name_len];
+    reader.read_exact(&mut name)?;
+    let mut extra = vec![0u8; extra_len];
+    reader.read_exact(&mut extra)?;
+    let mut comment = vec![0u8; comment_len];
+    reader.read_exact(&mut comment)?;
+
+    // The zip64 field only holds the values which didn't fit, in this order.
+    if let Some(mut zip64) = find_field(&extra, ZIP64_EXTRA_ID) {
+        for value in [&mut size, &mut compressed_size, &mut local_header_offset] {
+            if *value == U32_MAX {
+                *value = zip64.read_u64::<LittleEndian>()?;
+            }
+        }
+    }
+    Ok((
+        RawEntry {
+            name,
+            version_made_by,
+            version_needed,
+            flags,
+            compression_method,
+            last_modified_time,
+            last_modified_date,
+            crc32,
+            compressed_size,
+            size,
+            extra: without_field(&extra, ZIP64_EXTRA_ID),
+            local_extra: Vec::new(),
+            comment,
+            internal_attributes,
+            external_attributes,
+            data_start: 0,
+        },
+        local_header_offset,
+    ))
+}
+
+/// The entries of a package as the central directory gives them, in its
+/// order, without reading their local headers.
+pub fn central_directory_entries<R: Read + Seek>(reader: &mut R) -> Result<Vec<RawEntry>, Error> {
+    Ok(central_headers(reader)?
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+/// Where the headers of an entry are in its package.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeaderOffsets {
+    pub central: u64,
+    pub local: u64,
+}
+
+/// The entries of a package as `central_directory_entries` gives them, each
+/// with where its central and local headers start.
+pub fn central_headers<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<Vec<(RawEntry, HeaderOffsets)>, Error> {
+    let end = read_end_of_central_directory(reader)?;
+    reader.seek(SeekFrom::Start(end.offset))?;
+    (0..end.entries as usize)
+        .map(|i| {
+            let central = reader.stream_position()?;
+            let (entry, local) = read_central_header(reader, i)?;
+            Ok((entry, HeaderOffsets { central, local }))
+        })
+        .collect()
+}
+
+impl RawArchive {
+    /// Reads the central directory, and the local header of every entry.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<RawArchive, Error> {
+        let end = read_end_of_central_directory(reader)?;
+        reader.seek(SeekFrom::Start(end.offset))?;
+        let mut entries = Vec::new();
+        let mut local_header_offsets = Vec::new();
+        for i in 0..end.entries as usize {
+            let (entry, local_header_offset) = read_central_header(reader, i)?;
+            entries.push(entry);
+            local_header_offsets.push(local_header_offset);
+        }
+
+        for (entry, offset) in entries.iter_mut().zip(local_header_offsets) {
+            reader.seek(SeekFrom::Start(offset))?;
+            if reader.read_u32::<LittleEndian>()? != LOCAL_HEADER_SIGNATURE {
+                return Err(RawZipError::BadLocalHeader(entry.name_lossy()).into());
+            }
+            reader.seek(SeekFrom::Current(22))?;
+            let name_len = reader.read_u16::<LittleEndian>()?;
+            let extra_len = reader.read_u16::<LittleEndian>()? as usize;
+            reader.seek(SeekFrom::Current(i64::from(name_len)))?;
+            let mut local_extra = vec![0u8; extra_len];
+            reader.read_exact(&mut local_extra)?;
+            entry.local_extra = without_field(&local_extra, ZIP64_EXTRA_ID);
+            entry.data_start = offset + 30 + u64::from(name_len) + extra_len as u64;
+        }
+        Ok(RawArchive {
+            entries,
+            comment: end.comment,
+        })
+    }
+}
+
+/// The compressed data of an entry of the package `reader` was read from.
+pub fn raw_data<'a, R: Read + Seek>(
+    reader: &'a mut R,
+    entry: &RawEntry,
+) -> std::io::Result<std::io::Take<&'a mut R>> {
+    reader.seek(SeekFrom::Start(entry.data_start))?;
+    Ok(reader.take(entry.compressed_size))
+}
+
+/// The zip64 extra field for the values which don't fit their field, or
+/// nothing when they all do. Local headers have to give both sizes if they
+/// give either.
+fn zip64_field(size: u64, compressed_size: u64, offset: Option<u64>) -> Vec<u8> {
+    let sizes_too_large = size >= U32_MAX || compressed_size >= U32_MAX;
+    let mut values = Vec::new();
+    match offset {
+        None if sizes_too_large => values.extend_from_slice(&[size, compressed_size]),
+        None => {}
+        Some(offset) => {
+            values.extend(
+                [size, compressed_size, offset]
+                    .iter()
+                    .filter(|&&value| value >= U32_MAX),
+            );
+        }
+    }
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let mut field = Vec::with_capacity(4 + 8 * values.len());
+    field.write_u16::<LittleEndian>(ZIP64_EXTRA_ID).unwrap();
+    field
+        .write_u16::<LittleEndian>(8 * values.len() as u16)
+        .unwrap();
+    for value in values {
+        field.write_u64::<LittleEndian>(value).unwrap();
+    }
+    field
+}
+
+fn clamp_u32(value: u64) -> u32 {
+    value.min(U32_MAX) as u32
+}
+
+/// Writes a package record by record. Entries are written in the order they
+/// are added, and the central directory once they are all written.
+pub struct RawWriter<W: Write> {
+    inner: W,
+    offset: u64,
+    central_directory: Vec<(RawEntry, u64)>,
+}
+
+impl<W: Write> RawWriter<W> {
+    pub fn new(inner: W) -> RawWriter<W> {
+        RawWriter {
+            inner,
+            offset: 0,
+            central_directory: Vec::new(),
+        }
+    }
+
+    /// Where the local header of the next entry will be.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Writes an entry whose compressed data, of `entry.compressed_size`
+    /// bytes, is read from `data`.
+    pub fn write_entry(&mut self, entry: &RawEntry, data: &mut dyn Read) -> std::io::Result<()> {
+        let mut entry = entry.clone();
+        entry.flags &= !DATA_DESCRIPTOR_FLAG;
+        let zip64 = zip64_field(entry.size, entry.compressed_size, None);
+        if !zip64.is_empty() {
+            entry.version_needed = entry.version_needed.max(ZIP64_VERSION_NEEDED);
+        }
+        let mut header = Vec::with_capacity(30 + entry.name.len() + entry.local_extra.len());
+        header.write_u32::<LittleEndian>(LOCAL_HEADER_SIGNATURE)?;
+        header.write_u16::<LittleEndian>(entry.version_needed)?;
+        header.write_u16::<LittleEndian>(entry.flags)?;
+        header.write_u16::<LittleEndian>(entry.compression_method)?;
+        header.write_u16::<LittleEndian>(entry.last_modified_time)?;
+        header.write_u16::<LittleEndian>(entry.last_modified_date)?;
+        header.write_u32::<LittleEndian>(entry.crc32)?;
+        if zip64.is_empty() {
+            header.write_u32::<LittleEndian>(entry.compressed_size as u32)?;
+            header.write_u32::<LittleEndian>(entry.size as u32)?;
+        } else {
+            header.write_u32::<LittleEndian>(U32_MAX as u32)?;
+            header.write_u32::<LittleEndian>(U32_MAX as u32)?;
+        }
+        header.write_u16::<LittleEndian>(entry.name.len() as u16)?;
+        header.write_u16::<LittleEndian>((zip64.len() + entry.local_extra.len()) as u16)?;
+        header.extend_from_slice(&entry.name);
+        header.extend_from_slice(&zip64);
+        header.extend_from_slice(&entry.local_extra);
+        self.inner.write_all(&header)?;
+
+        let copied = std::io::copy(&mut data.take(entry.compressed_size), &mut self.inner)?;
+        if copied != entry.compressed_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{} has {} bytes of data rather than {}",
+                    entry.name_lossy(),
+                    copied,
+                    entry.compressed_size
+                ),
+            ));
+        }
+        self.central_directory.push((entry, self.offset));
+        self.offset += header.len() as u64 + copied;
+        Ok(())
+    }
+
+    /// Writes the central directory, with `comment` as the comment of the
+    /// package, and returns the writer.
+    pub fn finish(mut self, comment: &[u8]) -> std::io::Result<W> {
+        let central_directory_start = self.offset;
+        let mut directory = Vec::new();
+        for (entry, offset) in &self.central_directory {
+            let zip64 = zip64_field(entry.size, entry.compressed_size, Some(*offset));
+            let version_needed = if zip64.is_empty() {
+                entry.version_needed
+            } else {
+                entry.version_needed.max(ZIP64_VERSION_NEEDED)
+            };
+            directory.write_u32::<LittleEndian>(CENTRAL_HEADER_SIGNATURE)?;
+            directory.write_u16::<LittleEndian>(entry.version_made_by)?;
+            directory.write_u16::<LittleEndian>(version_needed)?;
+            directory.write_u16::<LittleEndian>(entry.flags)?;
+            directory.write_u16::<LittleEndian>(entry.compression_method)?;
+            directory.write_u16::<LittleEndian>(entry.last_modified_time)?;
+            directory.write_u16::<LittleEndian>(entry.last_modified_date)?;
+            directory.write_u32::<LittleEndian>(entry.crc32)?;
+            directory.write_u32::<LittleEndian>(clamp_u32(entry.compressed_size))?;
+            directory.write_u32::<LittleEndian>(clamp_u32(entry.size))?;
+            directory.write_u16::<LittleEndian>(entry.name.len() as u16)?;
+            directory.write_u16::<LittleEndian>((zip64.len() + entry.extra.len()) as u16)?;
+            directory.write_u16::<LittleEndian>(entry.comment.len() as u16)?;
+            directory.write_u16::<LittleEndian>(0)?;
+            directory.write_u16::<LittleEndian>(entry.internal_attributes)?;
+            directory.write_u32::<LittleEndian>(entry.external_attributes)?;
+            directory.write_u32::<LittleEndian>(clamp_u32(*offset))?;
+            directory.extend_from_slice(&entry.name);
+            directory.extend_from_slice(&zip64);
+            directory.extend_from_slice(&entry.extra);
+            directory.extend_from_slice(&entry.comment);
+        }
+        self.inner.write_all(&directory)?;
+
+        let entries = self.central_directory.len() as u64;
+        let size = directory.len() as u64;
+        let mut end = Vec::new();
+        if entries >= U16_MAX || size >= U32_MAX || central_directory_start >= U32_MAX {
+            let zip64_end = central_directory_start + size;
+            end.write_u32::<LittleEndian>(ZIP64_END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+            end.write_u64::<LittleEndian>(44)?;
+            end.write_u16::<LittleEndian>(ZIP64_VERSION_NEEDED)?;
+            end.write_u16::<LittleEndian>(ZIP64_VERSION_NEEDED)?;
+            end.write_u32::<LittleEndian>(0)?;
+            end.write_u32::<LittleEndian>(0)?;
+            end.write_u64::<LittleEndian>(entries)?;
+            end.write_u64::<LittleEndian>(entries)?;
+            end.write_u64::<LittleEndian>(size)?;
+            end.write_u64::<LittleEndian>(central_directory_start)?;
+            end.write_u32::<LittleEndian>(ZIP64_LOCATOR_SIGNATURE)?;
+            end.write_u32::<LittleEndian>(0)?;
+            end.write_u64::<LittleEndian>(zip64_end)?;
+            end.write_u32::<LittleEndian>(1)?;
+        }
+        end.write_u32::<LittleEndian>(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+        end.write_u16::<LittleEndian>(0)?;
+        end.write_u16::<LittleEndian>(0)?;
+        end.write_u16::<LittleEndian>(entries.min(U16_MAX) as u16)?;
+        end.write_u16::<LittleEndian>(entries.min(U16_MAX) as u16)?;
+        end.write_u32::<LittleEndian>(clamp_u32(size))?;
+        end.write_u32::<LittleEndian>(clamp_u32(central_directory_start))?;
+        end.write_u16::<LittleEndian>(comment.len() as u16)?;
+        end.extend_from_slice(comment);
+        self.inner.write_all(&end)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn zip64_fields_hold_only_what_doesnt_fit() {
+        assert!(zip64_field(1, 2, Some(3)).is_empty());
+        assert!(zip64_field(1, 2, None).is_empty());
+        assert_eq!(zip64_field(1, 2, Some(5 << 32)).len(), 4 + 8);
+        // Local headers give both sizes.
+        assert_eq!(zip64_field(5 << 32, 2, None).len(), 4 + 16);
+        assert_eq!(zip64_field(5 << 32, 2, Some(3)).len(), 4 + 8);
+    }
+
+    #[test]
+    fn entries_read_back_with_their_fields() {
+        let ntfs_times = [0x0a, 0, 4, 0, 1, 2, 3, 4];
+        let mut entry = RawEntry {
+            name: b"nodes/0/3dNodeIndexDocument.json".to_vec(),
+            version_made_by: 0x031e,
+            last_modified_date: 0x5021,
+            extra: ntfs_times.to_vec(),
+            local_extra: ntfs_times.to_vec(),
+            comment: b"exported by hand".to_vec(),
+            external_attributes: 0o100644 << 16,
+            ..RawEntry::default()
+        };
+        let data = entry.replace_contents(b"{\"id\":\"0\"}").unwrap();
+
+        let mut writer = RawWriter::new(Cursor::new(Vec::new()));
+        writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        let mut package = writer.finish(b"package comment").unwrap();
+
+        let raw = RawArchive::read(&mut package).unwrap();
+        assert_eq!(raw.comment, b"package comment");
+        let read = &raw.entries[0];
+        assert_eq!(read.extra, entry.extra);
+        assert_eq!(read.local_extra, entry.local_extra);
+        assert_eq!(read.comment, entry.comment);
+        assert_eq!(read.external_attributes, entry.external_attributes);
+        let mut copied = Vec::new();
+        raw_data(&mut package, read)
+            .unwrap()
+            .read_to_end(&mut copied)
+            .unwrap();
+        assert_eq!(copied, data);
+
+        let mut archive = zip::ZipArchive::new(package).unwrap();
+        let mut contents = String::new();
+        let mut document = archive.by_index(0).unwrap();
+        assert_eq!(document.comment(), "exported by hand");
+        document.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "{\"id\":\"0\"}");
+    }
+}
@@ -0,0 +1,690 @@
+// Runs a command over many packages at once, such as a folder of exports
+// delivered together. Packages are taken from a queue by a fixed number of
+// jobs, and share one budget of worker threads between them: each package
+// takes an even share of the threads left when it starts, among the jobs
+// starting along with it, and gives them back when it is done. Packages
+// started while the queue is long get a few threads each, and the last ones
+// get the threads the others have finished with, so the machine is kept busy
+// without a small package holding every core while the rest wait.
+//
+// Packages can be unpacked, all with the same options, or checked, verified
+// or validated as those commands do. A package which doesn't pass a check
+// fails, as one which can't be read does. A package whose command panics
+// fails too, with the panic message, and its threads are given back, so
+// that the rest of the batch runs as it would have.
+
+use crate::archive;
+use crate::check;
+use crate::glob;
+use crate::json::Value;
+use crate::unpack::{self, StageTimings, UnpackOptions, UnpackSummary};
+use crate::validate;
+use crate::verify;
+use failure::Error;
+use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Why a batch can't be run.
+#[derive(Debug, Fail)]
+pub enum BatchError {
+    /// The command isn't one of unpack, check, verify or validate.
+    #[fail(display = "Unknown batch command '{}'", _0)]
+    UnknownCommand(String),
+
+    /// The patterns given match no package.
+    #[fail(display = "No packages match '{}'", _0)]
+    NoMatchingPackages(String),
+}
+
+/// The extensions of the packages found in a folder.
+const PACKAGE_EXTENSIONS: [&str; 2] = ["slpk", "eslpk"];
+
+/// The command run on each package of a batch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchCommand {
+    /// An unpack, with the options given for the batch.
+    Unpack,
+    /// The quick check, then a verification of every entry, as `check`
+    /// does.
+    Check,
+    /// A verification of every entry, as `verify` does.
+    Verify,
+    /// A validation of the structure of the package, as `validate` does.
+    Validate,
+}
+
+impl BatchCommand {
+    fn name(self) -> &'static str {
+        match self {
+            BatchCommand::Unpack => "unpack",
+            BatchCommand::Check => "check",
+            BatchCommand::Verify => "verify",
+            BatchCommand::Validate => "validate",
+        }
+    }
+}
+
+impl FromStr for BatchCommand {
+    type Err = BatchError;
+
+    fn from_str(s: &str) -> Result<BatchCommand, BatchError> {
+        match s {
+            "unpack" => Ok(BatchCommand::Unpack),
+            "check" => Ok(BatchCommand::Check),
+            "verify" => Ok(BatchCommand::Verify),
+            "validate" => Ok(BatchCommand::Validate),
+            _ => Err(BatchError::UnknownCommand(s.to_string())),
+        }
+    }
+}
+
+/// How a batch is run.
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    /// How many packages are processed at once.
+    pub jobs: usize,
+    /// The worker threads shared by the packages in flight, or the number of
+    /// cores.
+    pub threads: Option<usize>,
+    /// Don't start any more packages once one has failed.
+    pub fail_fast: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> BatchOptions {
+        BatchOptions {
+            jobs: 1,
+            threads: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// What the command run on a package made of it.
+#[derive(Debug, Default)]
+pub struct PackageOutcome {
+    /// The entries unpacked, or checked.
+    pub entries: usize,
+    /// The bytes of every file written, decompressed when the entry is
+    /// gzipped.
+    pub bytes_written: u64,
+    /// How long each stage of an unpack took.
+    pub timings: Option<StageTimings>,
+}
+
+impl From<UnpackSummary> for PackageOutcome {
+    fn from(summary: UnpackSummary) -> PackageOutcome {
+        PackageOutcome {
+            entries: summary.entries_unpacked,
+            bytes_written: summary.bytes_written,
+            timings: Some(summary.timings),
+        }
+    }
+}
+
+/// What became of one package of a batch.
+#[derive(Debug)]
+pub struct PackageResult {
+    /// The package.
+    pub path: PathBuf,
+    /// The command run on it.
+    pub command: BatchCommand,
+    /// The entries unpacked, or checked.
+    pub entries: usize,
+    /// The bytes of every file written, decompressed when the entry is
+    /// gzipped.
+    pub bytes_written: u64,
+    /// The worker threads the package was given.
+    pub threads: usize,
+    /// How long the command took on it.
+    pub seconds: f64,
+    /// How long each stage of an unpack took.
+    pub timings: Option<StageTimings>,
+    /// Why the command failed on it, if it did.
+    pub error: Option<String>,
+    /// Whether the package was left alone, with `fail_fast`, as an earlier
+    /// one failed.
+    pub skipped: bool,
+}
+
+impl PackageResult {
+    fn status(&self) -> &'static str {
+        match (&self.error, self.skipped) {
+            (Some(_), _) => "failed",
+            (None, true) => "skipped",
+            (None, false) => "ok",
+        }
+    }
+}
+
+impl std::fmt::Display for PackageResult {
+    /// A line for the package, as the CLI prints it.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let path = self.path.to_string_lossy();
+        match &self.error {
+            Some(error) => write!(f, "{}: failed, {}", path, error),
+            None if self.skipped => write!(f, "{}: skipped", path),
+            None if self.command == BatchCommand::Unpack => write!(
+                f,
+                "{}: {} files, {} bytes unpacked in {:.1}s with {} threads",
+                path, self.entries, self.bytes_written, self.seconds, self.threads
+            ),
+            None => write!(
+                f,
+                "{}: {} entries passed {} in {:.1}s with {} threads",
+                path,
+                self.entries,
+                self.command.name(),
+                self.seconds,
+                self.threads
+            ),
+        }
+    }
+}
+
+/// What a batch made of each of its packages.
+#[derive(Debug)]
+pub struct BatchReport {
+    /// The command run on each package.
+    pub command: BatchCommand,
+    /// Each package, in the order they were given.
+    pub packages: Vec<PackageResult>,
+    /// How long the whole batch took.
+    pub seconds: f64,
+}
+
+impl BatchReport {
+    /// The number of packages the command failed on.
+    pub fn failed(&self) -> usize {
+        self.packages.iter().filter(|p| p.error.is_some()).count()
+    }
+
+    /// The number of packages left alone after one failed, with `fail_fast`.
+    pub fn skipped(&self) -> usize {
+        self.packages.iter().filter(|p| p.skipped).count()
+    }
+
+    /// The entries unpacked, or checked, in every package.
+    pub fn entries(&self) -> usize {
+        self.packages.iter().map(|p| p.entries).sum()
+    }
+
+    /// The bytes written for every package.
+    pub fn bytes_written(&self) -> u64 {
+        self.packages.iter().map(|p| p.bytes_written).sum()
+    }
+
+    /// The report as a JSON object, with the totals and a member per package.
+    pub fn to_json(&self) -> Value {
+        let packages = self
+            .packages
+            .iter()
+            .map(|p| {
+                let mut members = vec![
+                    (
+                        "path".to_string(),
+                        Value::from(p.path.to_string_lossy().into_owned()),
+                    ),
+                    ("status".to_string(), Value::from(p.status())),
+                    ("entries".to_string(), Value::from(p.entries)),
+                    ("bytesWritten".to_string(), Value::from(p.bytes_written)),
+                    ("threads".to_string(), Value::from(p.threads)),
+                    ("seconds".to_string(), Value::from(p.seconds)),
+                ];
+                if let Some(timings) = &p.timings {
+                    members.push(("timings".to_string(), timings.to_json()));
+                }
+                if let Some(error) = &p.error {
+                    members.push(("error".to_string(), Value::from(error.as_str())));
+                }
+                Value::Object(members)
+            })
+            .collect();
+
+        Value::Object(vec![
+            ("command".to_string(), Value::from(self.command.name())),
+            ("packages".to_string(), Value::Array(packages)),
+            (
+                "succeeded".to_string(),
+                Value::from(self.packages.len() - self.failed() - self.skipped()),
+            ),
+            ("failed".to_string(), Value::from(self.failed())),
+            ("skipped".to_string(), Value::from(self.skipped())),
+            ("entries".to_string(), Value::from(self.entries())),
+            (
+                "bytesWritten".to_string(),
+                Value::from(self.bytes_written()),
+            ),
+            ("seconds".to_string(), Value::from(self.seconds)),
+        ])
+    }
+}
+
+/// The packages named by `sources`, each of which is a package, a wildcard
+/// pattern such as `exports/*.slpk`, or a folder, whose `.slpk` and `.eslpk`
+/// files are taken. Packages named more than once are only taken the first
+/// time, and a source which names none is an error.
+pub fn find_packages(sources: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut packages = Vec::new();
+    for source in sources {
+        let found = if Path::new(source).is_dir() {
+            let mut files = Vec::new();
+            glob::walk(Path::new(source), Some(0), &mut files)?;
+            files.retain(|file| {
+                file.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| {
+                        PACKAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                    })
+            });
+            files.sort();
+            files
+        } else {
+            glob::expand(source)?
+        };
+        if found.is_empty() {
+            return Err(Error::from(BatchError::NoMatchingPackages(source.clone())));
+        }
+        for package in found {
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    Ok(packages)
+}
+
+/// Runs `command` on the package at `path` with `threads` worker threads. A
+/// package is unpacked with `options`, and validated against the schemas of
+/// its resources with `options.validate_json`. A package which doesn't pass
+/// a check gives an error saying why.
+pub fn run_command(
+    command: BatchCommand,
+    path: &Path,
+    threads: usize,
+    options: &UnpackOptions,
+) -> Result<PackageOutcome, Error> {
+    let verified = || -> Result<PackageOutcome, Error> {
+        let report = verify::verify_with_threads(
+            path,
+            Some(threads),
+            options.encryption.password.as_deref(),
+        )?;
+        match report.failed_entries.first() {
+            None => Ok(PackageOutcome {
+                entries: report.entries,
+                ..PackageOutcome::default()
+            }),
+            Some(first) => Err(format_err!(
+                "{} of {} entries failed verification, the first of them {}: {}",
+                report.failed_entries.len(),
+                report.entries,
+                first.entry_name,
+                first.problem
+            )),
+        }
+    };
+    match command {
+        BatchCommand::Unpack => {
+            unpack::unpack_with_options(path, &options.clone().threads(threads))
+                .map(PackageOutcome::from)
+        }
+        BatchCommand::Check => {
+            check::quick_check(File::open(path)?)?;
+            verified()
+        }
+        BatchCommand::Verify => verified(),
+        BatchCommand::Validate => {
+            let mut validate_options = validate::ValidateOptions {
+                threads: Some(threads),
+                ..validate::ValidateOptions::default()
+            };
+            if options.integrity.validate_json {
+                validate_options.checks.push(validate::Check::Schema);
+            }
+            let report = validate::validate_with_options(path, &validate_options)?;
+            if report.passed() {
+                return Ok(PackageOutcome {
+                    entries: archive::open_slpk_archive(path)?.len(),
+                    ..PackageOutcome::default()
+                });
+            }
+            Err(format_err!("validation failed, {}", report.problems()))
+        }
+    }
+}
+
+/// The packages left to start, and how many are being processed.
+struct Queue {
+    packages: Vec<(usize, PathBuf)>,
+    running: usize,
+    /// The threads of the budget no package holds.
+    free_threads: usize,
+    failed: bool,
+}
+
+/// Runs `run` over every package `sources` name, as `find_packages` finds
+/// them, with up to `options.jobs` packages in flight at once. `run`
+/// receives the package path and the number of worker threads it may use,
+/// its share of the threads of the budget which are free when it starts. A
+/// package for which `run` panics is reported as failed.
+pub fn batch<F>(
+    sources: &[String],
+    command: BatchCommand,
+    options: &BatchOptions,
+    run: F,
+) -> Result<BatchReport, Error>
+where
+    F: Fn(&Path, usize) -> Result<PackageOutcome, Error> + Send + Sync + 'static,
+{
+    let packages = find_packages(sources)?;
+    let start = Instant::now();
+    let jobs = options.jobs.clamp(1, packages.len());
+    let fail_fast = options.fail_fast;
+
+    let queue = Arc::new(Mutex::new(Queue {
+        packages: packages.into_iter().enumerate().rev().collect(),
+        running: 0,
+        free_threads: options.threads.unwrap_or_else(num_cpus::get).max(1),
+        failed: false,
+    }));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let run = Arc::new(run);
+
+    let mut workers = Vec::with_capacity(jobs);
+    for _ in 0..jobs {
+        let queue = queue.clone();
+        let results = results.clone();
+        let run = run.clone();
+        workers.push(thread::spawn(move || loop {
+            let (order, path, threads, taken) = {
+                let mut queue = queue.lock().unwrap();
+                if fail_fast && queue.failed {
+                    break;
+                }
+                let (order, path) = match queue.packages.pop() {
+                    Some(next) => next,
+                    None => break,
+                };
+                // The free threads are shared with the jobs starting along
+                // with this one, as many as there are packages left for.
+                let starting = (jobs - queue.running).min(queue.packages.len() + 1);
+                let threads = (queue.free_threads / starting).max(1);
+                let taken = threads.min(queue.free_threads);
+                queue.free_threads -= taken;
+                queue.running += 1;
+                (order, path, threads, taken)
+            };
+
+            let package_start = Instant::now();
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| run(&path, threads)))
+                .unwrap_or_else(|payload| {
+                    Err(format_err!(
+                        "{} panicked: {}",
+                        command.name(),
+                        unpack::panic_message(payload)
+                    ))
+                });
+            let seconds = package_start.elapsed().as_secs_f64();
+            {
+                let mut queue = queue.lock().unwrap();
+                queue.free_threads += taken;
+                queue.running -= 1;
+                queue.failed |= outcome.is_err();
+            }
+            let result = match outcome {
+                Ok(outcome) => PackageResult {
+                    path,
+                    command,
+                    entries: outcome.entries,
+                    bytes_written: outcome.bytes_written,
+                    threads,
+                    seconds,
+                    timings: outcome.timings,
+                    error: None,
+                    skipped: false,
+                },
+                Err(e) => PackageResult {
+                    path,
+                    command,
+                    entries: 0,
+                    bytes_written: 0,
+                    threads,
+                    seconds,
+                    timings: None,
+                    error: Some(e.to_string()),
+                    skipped: false,
+                },
+            };
+            results.lock().unwrap().push((order, result));
+        }));
+    }
+    for worker in workers {
+        // Panics of `run` are caught, so only a poisoned lock can get here,
+        // and it has panicked already.
+        let _ = worker.join();
+    }
+
+    let mut results = std::mem::take(&mut *results.lock().unwrap());
+    let unstarted = std::mem::take(&mut queue.lock().unwrap().packages);
+    results.extend(unstarted.into_iter().map(|(order, path)| {
+        let skipped = PackageResult {
+            path,
+            command,
+            entries: 0,
+            bytes_written: 0,
+            threads: 0,
+            seconds: 0.0,
+            timings: None,
+            error: None,
+            skipped: true,
+        };
+        (order, skipped)
+    }));
+    results.sort_by_key(|(order, _)| *order);
+    Ok(BatchReport {
+        command,
+        packages: results.into_iter().map(|(_, r)| r).collect(),
+        seconds: start.elapsed().as_secs_f64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use std::time::Duration;
+
+    #[test]
+    fn failures_are_reported_per_package() {
+        let root = TempDir::new("batch");
+        for name in &["a.slpk", "b.slpk", "c.slpk"] {
+            std::fs::write(root.join(name), b"").unwrap();
+        }
+
+        let pattern = format!("{}/*.slpk", root.to_string_lossy());
+        let options = BatchOptions {
+            jobs: 2,
+            ..BatchOptions::default()
+        };
+        let report = batch(
+            &[pattern],
+            BatchCommand::Unpack,
+            &options,
+            |path, threads| {
+                assert!(threads >= 1);
+                if path.ends_with("b.slpk") {
+                    Err(format_err!("corrupt package"))
+                } else {
+                    Ok(PackageOutcome {
+                        entries: 10,
+                        timings: Some(StageTimings {
+                            read: Duration::from_millis(100),
+                            ..StageTimings::default()
+                        }),
+                        ..PackageOutcome::default()
+                    })
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.packages.len(), 3);
+        assert_eq!(report.failed(), 1);
+        assert!(report.packages[0].path.ends_with("a.slpk"));
+        assert_eq!(report.packages[1].error.as_deref(), Some("corrupt package"));
+
+        let json = report.to_json();
+        assert_eq!(json.get("succeeded").and_then(Value::as_u64), Some(2));
+        let packages = json.get("packages").unwrap().as_array().unwrap();
+        assert_eq!(packages[2].get("entries").and_then(Value::as_u64), Some(10));
+        let read = packages[2].get("timings").and_then(|t| t.get("read"));
+        assert_eq!(
+            read.and_then(|r| r.get("percent")).and_then(Value::as_f64),
+            Some(100.0)
+        );
+        assert!(packages[1].get("timings").is_none());
+    }
+
+    #[test]
+    fn empty_match_is_an_error() {
+        assert!(batch(
+            &["/nonexistent-slpkg-dir/*.slpk".to_string()],
+            BatchCommand::Unpack,
+            &BatchOptions::default(),
+            |_, _| Ok(PackageOutcome::default())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn folders_share_one_thread_budget_and_fail_fast_stops_the_rest() {
+        let root = TempDir::new("batch-folder");
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        for name in &[
+            "a.slpk",
+            "b.ESLPK",
+            "c.slpk",
+            "d.slpk",
+            "notes.txt",
+            "nested/e.slpk",
+        ] {
+            std::fs::write(root.join(name), b"").unwrap();
+        }
+        let folder = root.to_string_lossy().into_owned();
+        let found = find_packages(&[folder.clone(), format!("{}/a.slpk", folder)]).unwrap();
+
+        let in_use = Arc::new(Mutex::new((0, 0)));
+        let counted = in_use.clone();
+        let options = BatchOptions {
+            jobs: 3,
+            threads: Some(6),
+            fail_fast: false,
+        };
+        let shared = batch(
+            std::slice::from_ref(&folder),
+            BatchCommand::Unpack,
+            &options,
+            move |_, threads| {
+                {
+                    let mut in_use = counted.lock().unwrap();
+                    in_use.0 += threads;
+                    in_use.1 = in_use.1.max(in_use.0);
+                }
+                thread::sleep(Duration::from_millis(20));
+                counted.lock().unwrap().0 -= threads;
+                Ok(PackageOutcome {
+                    entries: 1,
+                    bytes_written: 100,
+                    ..PackageOutcome::default()
+                })
+            },
+        )
+        .unwrap();
+        let options = BatchOptions {
+            fail_fast: true,
+            ..BatchOptions::default()
+        };
+        let stopped = batch(&[folder], BatchCommand::Unpack, &options, |path, _| {
+            if path.ends_with("b.ESLPK") {
+                Err(format_err!("corrupt package"))
+            } else {
+                Ok(PackageOutcome::default())
+            }
+        })
+        .unwrap();
+
+        let names: Vec<_> = found
+            .iter()
+            .map(|p| p.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["a.slpk", "b.ESLPK", "c.slpk", "d.slpk"]);
+        assert!(in_use.lock().unwrap().1 <= 6);
+        assert_eq!((shared.entries(), shared.bytes_written()), (4, 400));
+        // The last package starts once the first ones are done with their
+        // threads.
+        assert!(shared.packages.iter().all(|p| p.threads >= 2));
+        assert_eq!((stopped.failed(), stopped.skipped()), (1, 2));
+        assert_eq!(
+            stopped.to_json().get("skipped").and_then(Value::as_u64),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn packages_which_panic_fail_and_give_back_their_threads() {
+        let root = TempDir::new("batch-panic");
+        let package = root.join("package.slpk");
+        crate::synthetic::SyntheticPackage::standard()
+            .write_to_file(&package)
+            .unwrap();
+        for name in &["a.slpk", "c.slpk"] {
+            std::fs::copy(&package, root.join(name)).unwrap();
+        }
+        std::fs::write(root.join("b.slpk"), b"").unwrap();
+        std::fs::remove_file(&package).unwrap();
+        let folder = root.to_string_lossy().into_owned();
+        let options = BatchOptions {
+            threads: Some(2),
+            ..BatchOptions::default()
+        };
+        let panicked = batch(
+            std::slice::from_ref(&folder),
+            BatchCommand::Unpack,
+            &options,
+            |path, threads| {
+                if path.ends_with("a.slpk") {
+                    panic!("out of cheese");
+                }
+                Ok(PackageOutcome {
+                    entries: threads,
+                    ..PackageOutcome::default()
+                })
+            },
+        )
+        .unwrap();
+        let verified = batch(
+            &[folder],
+            BatchCommand::Verify,
+            &options,
+            |path, threads| run_command(BatchCommand::Verify, path, threads, &UnpackOptions::new()),
+        )
+        .unwrap();
+
+        assert_eq!((panicked.failed(), panicked.skipped()), (1, 0));
+        assert_eq!(
+            panicked.packages[0].error.as_deref(),
+            Some("unpack panicked: out of cheese")
+        );
+        // The threads of the package which panicked were given back.
+        assert_eq!(panicked.packages[2].entries, 2);
+        assert_eq!(verified.failed(), 1);
+        assert!(verified.packages[1].error.is_some());
+        assert!(verified.packages[2].entries > 0);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::archive;
+use crate::footprint;
+use crate::footprint::Projector;
+use crate::i3s;
+use crate::i3s::Node;
+use crate::json::Value;
+use failure::Error;
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use zip::ZipArchive;
+
+/// A bounds format other than geojson or kml.
+#[derive(Debug, Fail)]
+#[fail(display = "Unknown bounds format '{}', expected geojson or kml", _0)]
+pub struct UnknownBoundsFormat(String);
+
+/// The file format the bounds are written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundsFormat {
+    /// A GeoJSON feature collection.
+    GeoJson,
+    /// A KML document with a placemark per node.
+    Kml,
+}
+
+impl FromStr for BoundsFormat {
+    type Err = UnknownBoundsFormat;
+
+    fn from_str(s: &str) -> Result<BoundsFormat, UnknownBoundsFormat> {
+        match s.to_ascii_lowercase().as_str() {
+            "geojson" => Ok(BoundsFormat::GeoJson),
+            "kml" => Ok(BoundsFormat::Kml),
+            _ => Err(UnknownBoundsFormat(s.to_string())),
+        }
+    }
+}
+
+/// The outline of the bounding volume of a node.
+#[derive(Debug)]
+pub struct NodeBounds {
+    /// The id of the node, as its node pages or node index document give it.
+    pub node_id: String,
+    /// The depth of the node below the root, which is at level 0.
+    pub level: usize,
+    /// The LOD threshold of the node, when it has one.
+    pub lod_threshold: Option<f64>,
+    /// Closed WGS84 ring outlining the node's bounding volume.
+    pub ring: Vec<(f64, f64)>,
+}
+
+/// Picks at most `max_features` items, spread evenly across the input so a
+/// sample still covers the whole extent of the layer.
+fn sample<T>(items: Vec<T>, max_features: Option<usize>) -> Vec<T> {
+    match max_features {
+        Some(max) if items.len() > max => {
+            let stride = items.len() as f64 / max as f64;
+            let mut picked = 0;
+            items
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| {
+                    if picked < max && *i == (picked as f64 * stride) as usize {
+                        picked += 1;
+                        true
+                    } else {
+                        false
+                    }
+                })
+                .map(|(_, item)| item)
+                .collect()
+        }
+        _ => items,
+    }
+}
+
+/// The bounds of the nodes of the first layer which have a bounding volume,
+/// those at `level` alone when it is given, and no more than `max_features`
+/// of them, picked evenly across the layer.
+pub fn node_bounds<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    level: Option<usize>,
+    max_features: Option<usize>,
+) -> Result<Vec<NodeBounds>, Error> {
+    let layer = i3s::read_scene_layer(archive)?;
+    let hierarchy = i3s::load_hierarchy(archive, &layer)?;
+    let projector = Projector::for_layer(&layer);
+    if let Some(warning) = projector.unsupported_warning(&layer) {
+        warn!("{}", warning);
+    }
+
+    let selected: Vec<&Node> = hierarchy
+        .nodes
+        .iter()
+        .filter(|node| node.volume.is_some())
+        .filter(|node| level.map_or(true, |level| node.level == level))
+        .collect();
+
+    Ok(sample(selected, max_features)
+        .into_iter()
+        .filter_map(|node| {
+            node.volume.as_ref().map(|volume| NodeBounds {
+                node_id: node.id.clone(),
+                level: node.level,
+                lod_threshold: node.lod_threshold,
+                ring: projector.footprint(volume),
+            })
+        })
+        .collect())
+}
+
+/// The bounds as a GeoJSON feature collection, with a polygon per node.
+pub fn to_geojson(bounds: &[NodeBounds]) -> String {
+    let features = bounds
+        .iter()
+        .map(|b| {
+            let mut properties = vec![
+                ("nodeId".to_string(), Value::from(b.node_id.as_str())),
+                ("level".to_string(), Value::from(b.level)),
+            ];
+            if let Some(lod_threshold) = b.lod_threshold {
+                properties.push(("lodThreshold".to_string(), Value::from(lod_threshold)));
+            }
+            footprint::feature(footprint::polygon_geometry(&b.ring), properties)
+        })
+        .collect();
+    footprint::feature_collection(features).to_pretty_string(2)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The bounds as a KML document, with a placemark per node.
+pub fn to_kml(bounds: &[NodeBounds]) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n",
+    );
+    for b in bounds {
+        let coordinates: Vec<String> = b
+            .ring
+            .iter()
+            .map(|(lon, lat)| format!("{},{},0", lon, lat))
+            .collect();
+        kml.push_str(&format!(
+            "<Placemark>\n<name>{}</name>\n<ExtendedData>\n\
+             <Data name=\"nodeId\"><value>{}</value></Data>\n\
+             <Data name=\"level\"><value>{}</value></Data>\n",
+            escape_xml(&b.node_id),
+            escape_xml(&b.node_id),
+            b.level
+        ));
+        if let Some(lod_threshold) = b.lod_threshold {
+            kml.push_str(&format!(
+                "<Data name=\"lodThreshold\"><value>{}</value></Data>\n",
+                lod_threshold
+            ));
+        }
+        kml.push_str(&format!(
+            "</ExtendedData>\n<Polygon><outerBoundaryIs><LinearRing><coordinates>{}\
+             </coordinates></LinearRing></outerBoundaryIs></Polygon>\n</Placemark>\n",
+            coordinates.join(" ")
+        ));
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+/// Writes the bounds of the nodes of a package to `output_path` in `format`,
+/// as `node_bounds` picks them. Returns the number of nodes written.
+pub fn export_bounds(
+    slpk_file_path: &Path,
+    output_path: &Path,
+    format: BoundsFormat,
+    level: Option<usize>,
+    max_features: Option<usize>,
+) -> Result<usize, Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let bounds = node_bounds(&mut slpk_archive, level, max_features)?;
+    let contents = match format {
+        BoundsFormat::GeoJson => to_geojson(&bounds),
+        BoundsFormat::Kml => to_kml(&bounds),
+    };
+
+    let mut output_file = File::create(output_path)?;
+    output_file.write_all(contents.as_bytes())?;
+    Ok(bounds.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn mesh_package() -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("3dSceneLayer.json", FileOptions::default())
+            .unwrap();
+        writer
+            .write_all(br#"{"spatialReference":{"wkid":4326},"nodePages":{}}"#)
+            .unwrap();
+        writer
+            .start_file("nodepages/0.json", FileOptions::default())
+            .unwrap();
+        writer
+            .write_all(
+                br#"{"nodes":[
+                {"index":0,"children":[1,2,3,4],"mbs":[10,20,0,1000]},
+                {"index":1,"lodThreshold":1,"mbs":[10,20,0,100]},
+                {"index":2,"lodThreshold":2,"mbs":[10,20,0,100]},
+                {"index":3,"lodThreshold":3,"mbs":[10,20,0,100]},
+                {"index":4,"lodThreshold":4,"mbs":[10,20,0,100]}]}"#,
+            )
+            .unwrap();
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn level_filter_and_sampling() {
+        let bounds = node_bounds(&mut mesh_package(), Some(1), None).unwrap();
+        assert_eq!(bounds.len(), 4);
+        assert!(bounds.iter().all(|b| b.level == 1));
+
+        let sampled = node_bounds(&mut mesh_package(), Some(1), Some(2)).unwrap();
+        let ids: Vec<&str> = sampled.iter().map(|b| b.node_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+    }
+
+    #[test]
+    fn kml_contains_closed_rings() {
+        let bounds = node_bounds(&mut mesh_package(), Some(0), None).unwrap();
+        let kml = to_kml(&bounds);
+        assert!(kml.contains("<Data name=\"nodeId\"><value>0</value></Data>"));
+        let ring = &bounds[0].ring;
+        assert_eq!(ring.first(), ring.last());
+        assert_eq!(kml.matches("<Placemark>").count(), 1);
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("KML".parse::<BoundsFormat>().unwrap(), BoundsFormat::Kml);
+        assert!("shp".parse::<BoundsFormat>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Checks that a package can be read, without unpacking it. The quick check
+// is meant as an acceptance gate for uploads: it reads the zip directory, a
+// few documents and a sample of entry headers only, so it takes about as long
+// for a 50 GB package as for a small one.
+
+use crate::i3s;
+use crate::i3s::SlpkVersion;
+use failure::Error;
+use std::cell::Cell;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use zip::ZipArchive;
+
+/// Why the quick check failed.
+#[derive(Debug, Fail)]
+pub enum CheckError {
+    /// The node index document of the root node isn't stored, or is empty.
+    #[fail(display = "The root node can't be reached, {} is missing or empty", _0)]
+    UnreachableRoot(String),
+}
+
+/// Counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes_read.set(self.bytes_read.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// How many entries the unpacked size is estimated from. Reading the size of
+/// an entry means reading its local header.
+const SIZE_SAMPLES: usize = 64;
+
+/// What the quick check read of a package.
+#[derive(Debug)]
+pub struct QuickCheckReport {
+    /// The entries of the zip directory.
+    pub entries: usize,
+    /// The size of the package file.
+    pub package_bytes: u64,
+    /// The uncompressed size of the entries, extrapolated from a sample of
+    /// them. Gzipped resources unpack to more than this.
+    pub estimated_unpacked_bytes: u64,
+    /// The type of the first layer, such as 3DObject.
+    pub layer_type: Option<String>,
+    /// The I3S version of the package, as `i3s::detect_version` gives it.
+    pub version: SlpkVersion,
+    /// The entry the root node was read from.
+    pub root_entry: String,
+    /// How much of the package was read for the check.
+    pub bytes_read: u64,
+    /// How long the check took.
+    pub elapsed: Duration,
+}
+
+/// Checks that `source` is a zip whose directory parses, that its first
+/// layer document exists and parses, and that the root node of that layer
+/// can be read. Only the zip directory, those documents, and the local
+/// headers of a sample of entries are read.
+pub fn quick_check<R: Read + Seek>(mut source: R) -> Result<QuickCheckReport, Error> {
+    let start = Instant::now();
+    let package_bytes = source.seek(SeekFrom::End(0))?;
+    let bytes_read = Rc::new(Cell::new(0));
+    let mut archive = ZipArchive::new(BufReader::new(CountingReader {
+        inner: source,
+        bytes_read: bytes_read.clone(),
+    }))?;
+
+    let entries = archive.len();
+    let samples = entries.min(SIZE_SAMPLES);
+    let mut sampled_bytes = 0;
+    for sample in 0..samples {
+        sampled_bytes += archive.by_index(sample * entries / samples)?.size();
+    }
+    let estimated_unpacked_bytes = match samples {
+        0 => 0,
+        _ => sampled_bytes / samples as u64 * entries as u64,
+    };
+
+    let layer = i3s::find_scene_layer(&mut archive)?;
+    // As `i3s::detect_version` does, but with the layer found by name
+    // rather than by listing every entry.
+    let metadata = i3s::read_metadata(&mut archive, Some(&layer))?;
+    let version = SlpkVersion::of_package(Some(&metadata.document), Some(&layer));
+    let root_entry = i3s::root_node_entry(&layer);
+    if i3s::read_root_node(&mut archive, &layer)?.is_none() {
+        return Err(Error::from(CheckError::UnreachableRoot(root_entry)));
+    }
+
+    Ok(QuickCheckReport {
+        entries,
+        package_bytes,
+        estimated_unpacked_bytes,
+        layer_type: layer.layer_type.clone(),
+        version,
+        root_entry,
+        bytes_read: bytes_read.get(),
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn reads_only_the_directory_and_root_documents() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("3dSceneLayer.json", FileOptions::default())
+            .unwrap();
+        writer
+            .write_all(
+                br#"{"layerType":"IntegratedMesh","store":{"version":"1.7"},"nodePages":{}}"#,
+            )
+            .unwrap();
+        writer
+            .start_file("nodepages/0.json", FileOptions::default())
+            .unwrap();
+        writer.write_all(br#"{"nodes":[{"index":0}]}"#).unwrap();
+        writer
+            .start_file(
+                "nodes/0/geometries/0.bin",
+                FileOptions::default().compression_method(zip::CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(&vec![0u8; 1 << 20]).unwrap();
+        let package = writer.finish().unwrap();
+
+        let report = quick_check(package).unwrap();
+        assert_eq!(report.entries, 3);
+        assert!(report.estimated_unpacked_bytes > 1 << 20);
+        assert_eq!(report.layer_type.as_deref(), Some("IntegratedMesh"));
+        assert_eq!(report.version, SlpkVersion::V1_7);
+        assert_eq!(report.root_entry, "nodepages/0.json");
+        // The geometry buffer is never read.
+        assert!(report.bytes_read < report.package_bytes / 2);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("3dSceneLayer.json", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"{}").unwrap();
+        assert!(quick_check(writer.finish().unwrap()).is_err());
+    }
+}
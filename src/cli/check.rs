@@ -0,0 +1,39 @@
+// The text of `slpkg check`.
+
+use super::verify;
+use failure::Error;
+use slpkg::check;
+use slpkg::unpack::EntryFilters;
+use std::fs::File;
+use std::path::Path;
+
+/// Runs the quick check, and unless `quick` is set, also verifies every
+/// entry which `filters` keep as `verify` does. Returns whether the package
+/// passed.
+pub fn print_check(
+    slpk_file_path: &Path,
+    quick: bool,
+    filters: &EntryFilters,
+) -> Result<bool, Error> {
+    let report = check::quick_check(File::open(slpk_file_path)?)?;
+    println!(
+        "{} entries, {} bytes, about {} bytes unpacked",
+        report.entries, report.package_bytes, report.estimated_unpacked_bytes
+    );
+    println!(
+        "The first layer is {} of I3S version {}",
+        report.layer_type.as_deref().unwrap_or("an unknown type"),
+        report.version
+    );
+    println!("Root node read from {}", report.root_entry);
+    println!(
+        "Checked in {} ms, reading {} bytes",
+        report.elapsed.as_millis(),
+        report.bytes_read
+    );
+    if quick {
+        return Ok(true);
+    }
+
+    verify::print_verify_filtered(slpk_file_path, None, None, filters)
+}
@@ -0,0 +1,20 @@
+// The text of `slpkg check-manifest`.
+
+use failure::Error;
+use slpkg::unpack;
+use std::path::Path;
+
+/// Checks a folder against its manifest, printing each file which doesn't
+/// match and then the count. Returns whether every file matched.
+pub fn print_check_manifest(folder: &Path) -> Result<bool, Error> {
+    let report = unpack::check_manifest(folder)?;
+    for mismatch in &report.mismatches {
+        println!("{}: {}", mismatch.path, mismatch.problem);
+    }
+    println!(
+        "{} of {} files are missing or have changed",
+        report.mismatches.len(),
+        report.files
+    );
+    Ok(report.mismatches.is_empty())
+}
@@ -0,0 +1,40 @@
+// The text of `slpkg diff`.
+
+use failure::Error;
+use slpkg::diff;
+use std::path::Path;
+
+/// Prints the number of entries added, removed and modified, and with
+/// `detailed` each of them. Returns whether the packages hold the same
+/// content.
+pub fn print_diff(left_path: &Path, right_path: &Path, detailed: bool) -> Result<bool, Error> {
+    let report = diff::diff(left_path, right_path)?;
+    if detailed {
+        for name in &report.added {
+            println!("+ {}", name);
+        }
+        for name in &report.removed {
+            println!("- {}", name);
+        }
+        for entry in &report.modified {
+            println!(
+                "M {} ({} -> {} bytes)",
+                entry.name, entry.left_size, entry.right_size
+            );
+        }
+    }
+    println!(
+        "{} entries added, {} removed, {} modified and {} unchanged",
+        report.added.len(),
+        report.removed.len(),
+        report.modified.len(),
+        report.unchanged
+    );
+    if report.regzipped > 0 {
+        println!(
+            "{} of the unchanged entries were gzipped again, with the same content",
+            report.regzipped
+        );
+    }
+    Ok(report.identical())
+}
@@ -0,0 +1,45 @@
+// The text of `slpkg export` and `slpkg export-attributes`.
+
+use failure::Error;
+use slpkg::export;
+use slpkg::export::{ExportFormat, TargetCrs};
+use std::path::Path;
+
+/// Exports a node, printing the files written and the size of its mesh.
+pub fn print_export_node(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    node_id: &str,
+    output: &Path,
+    format: Option<ExportFormat>,
+    target_crs: Option<TargetCrs>,
+) -> Result<(), Error> {
+    let summary =
+        export::export_layer_node(slpk_file_path, layer, node_id, output, format, target_crs)?;
+    for file in &summary.files {
+        println!("{}", file.to_string_lossy());
+    }
+    println!(
+        "Node {} exported, {} triangles of {} features",
+        node_id, summary.triangles, summary.features
+    );
+    Ok(())
+}
+
+/// Exports the attributes of every node, printing how many were written.
+pub fn print_export_attributes(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    output_dir: &Path,
+    merge: bool,
+) -> Result<(), Error> {
+    let summary = export::export_attributes(slpk_file_path, layer, output_dir, merge)?;
+    println!(
+        "Attributes of {} features of {} nodes written to {} files in {}",
+        summary.features,
+        summary.nodes,
+        summary.files.len(),
+        output_dir.to_string_lossy()
+    );
+    Ok(())
+}
@@ -0,0 +1,52 @@
+// The text of `slpkg extract-node` and `slpkg cat`.
+
+use failure::Error;
+use slpkg::extract;
+use slpkg::unpack::{self, JsonFormatting};
+use std::io::Write;
+use std::path::Path;
+
+/// Extracts a node, printing the entries extracted and then the count.
+pub fn print_extract_node(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    node_id: &str,
+    output_dir: &Path,
+) -> Result<(), Error> {
+    let summary = extract::extract_layer_node(slpk_file_path, layer, node_id, output_dir)?;
+    for entry_name in &summary.entries {
+        println!("{}", entry_name);
+    }
+    println!(
+        "{} entries of node {} extracted to {}, {} bytes in all",
+        summary.entries.len(),
+        node_id,
+        output_dir.to_string_lossy(),
+        summary.bytes_written
+    );
+    Ok(())
+}
+
+/// Writes an entry of a package to the standard output, pretty-printed
+/// when it is a JSON document unless `formatting` says otherwise.
+pub fn print_entry(
+    slpk_file_path: &Path,
+    entry_name: &str,
+    formatting: Option<JsonFormatting>,
+) -> Result<(), Error> {
+    let formatting = formatting.unwrap_or(JsonFormatting::Pretty(unpack::Indentation::Spaces(2)));
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let written = extract::extract_entry(slpk_file_path, entry_name, &mut stdout, formatting)
+        .and_then(|_| Ok(stdout.flush()?));
+    match written {
+        // Whatever reads the output, such as `head`, may stop early.
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        written => written,
+    }
+}
@@ -0,0 +1,66 @@
+// The text of `slpkg index`.
+
+use failure::Error;
+use slpkg::index;
+use std::path::Path;
+
+/// Prints the index report, and every record too when `list` is set.
+/// Returns whether the index is consistent with the package.
+pub fn print_hash_index(slpk_file_path: &Path, list: bool) -> Result<bool, Error> {
+    let report = match index::check_hash_index(slpk_file_path)? {
+        Some(report) => report,
+        None => {
+            println!("The package has no {} entry", index::HASH_INDEX_ENTRY);
+            return Ok(true);
+        }
+    };
+
+    if list {
+        for (key, offset, name) in &report.listing {
+            println!(
+                "{} {:>12} {}",
+                key,
+                offset,
+                name.as_deref().unwrap_or("<no local header>")
+            );
+        }
+        println!();
+    }
+
+    println!(
+        "{} records{}",
+        report.records,
+        if report.sorted {
+            ""
+        } else {
+            ", not sorted by key"
+        }
+    );
+    println!(
+        "{} of {} entries are covered by the index",
+        report.covered, report.entries
+    );
+    let print_names = |heading: &str, names: &[String]| {
+        if !names.is_empty() {
+            println!("{} {}:", names.len(), heading);
+            for name in names {
+                println!("    {}", name);
+            }
+        }
+    };
+    print_names("entries are missing from the index", &report.missing);
+    print_names("entries have a record pointing elsewhere", &report.stale);
+    if report.duplicate_keys > 0 {
+        println!(
+            "{} records duplicate another record's key",
+            report.duplicate_keys
+        );
+    }
+    if report.dangling > 0 {
+        println!("{} records don't match any entry", report.dangling);
+    }
+    for names in &report.colliding_names {
+        println!("These entries share a key: {}", names.join(", "));
+    }
+    Ok(report.is_consistent())
+}
@@ -0,0 +1,92 @@
+// The text of `slpkg info`.
+
+use failure::Error;
+use slpkg::info;
+use slpkg::info::PackageFlavor;
+use std::path::Path;
+
+/// Prints the summary of each layer, and with `attributes` a row per
+/// attribute joining its field definition with its storage info and saying
+/// what is wrong with it, if anything.
+pub fn print_info(slpk_file_path: &Path, attributes: bool) -> Result<(), Error> {
+    let unknown = || "unknown".to_string();
+    let info = info::info(slpk_file_path)?;
+    match info.flavor {
+        PackageFlavor::Slpk => println!("Package: SLPK, with gzipped resources"),
+        PackageFlavor::Eslpk => println!("Package: ESLPK, with resources stored as they are"),
+    }
+    if info.metadata_inferred {
+        println!(
+            "I3S version: {}, inferred as the package has no {}",
+            info.version,
+            info::METADATA_DOCUMENT
+        );
+    } else {
+        println!("I3S version: {}", info.version);
+    }
+    for layer in info.layers {
+        println!(
+            "Layer {} at {}: {} of I3S version {}, {} attributes",
+            layer.id,
+            if layer.root.is_empty() {
+                "the root"
+            } else {
+                layer.root.as_str()
+            },
+            layer.layer_type.as_deref().unwrap_or("an unknown type"),
+            layer.version,
+            layer.attributes.len()
+        );
+        println!("  Name: {}", layer.name.unwrap_or_else(unknown));
+        println!(
+            "  Spatial reference: {}",
+            layer
+                .wkid
+                .map_or_else(unknown, |wkid| format!("WKID {}", wkid))
+        );
+        println!(
+            "  Nodes: {}",
+            layer.nodes.map_or_else(unknown, |nodes| nodes.to_string())
+        );
+        if let Some(points) = layer.points {
+            println!("  Points: {}", points);
+        }
+        println!(
+            "  Texture encodings: {}",
+            if layer.texture_encodings.is_empty() {
+                "none".to_string()
+            } else {
+                layer.texture_encodings.join(", ")
+            }
+        );
+        if layer.geometry_buffers.is_empty() {
+            println!("  Geometry buffers: none declared");
+        } else {
+            println!("  Geometry buffers:");
+            for buffer in &layer.geometry_buffers {
+                println!("    {}", buffer);
+            }
+        }
+        for note in &layer.undecodable {
+            println!("  Note: {}", note);
+        }
+        if !attributes {
+            continue;
+        }
+        let position =
+            |position: Option<usize>| position.map_or_else(|| "-".to_string(), |p| p.to_string());
+        for attribute in &layer.attributes {
+            let row = format!(
+                "  {:<8} {:<24} {:<28} field {:<3} storage {:<3} {}",
+                attribute.key.as_deref().unwrap_or("-"),
+                attribute.name,
+                attribute.field_type.as_deref().unwrap_or("-"),
+                position(attribute.field_position),
+                position(attribute.storage_position),
+                attribute.problem().unwrap_or("")
+            );
+            println!("{}", row.trim_end());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+// The text of `slpkg lint`.
+
+use failure::Error;
+use slpkg::lint;
+use slpkg::lint::{FailOn, LintOptions};
+use std::path::Path;
+
+/// Prints each finding as it is found, then a count per severity and the
+/// time spent in each rule. Returns whether the findings pass `fail_on`.
+pub fn print_lint(
+    slpk_file_path: &Path,
+    options: &LintOptions,
+    fail_on: FailOn,
+) -> Result<bool, Error> {
+    let summary = lint::lint_package(slpk_file_path, options, |finding| println!("{}", finding))?;
+    let counts = &summary.counts;
+    println!(
+        "{} errors, {} warnings, {} info",
+        counts.errors, counts.warnings, counts.info
+    );
+    println!("Time spent in each rule, summed over the worker threads:");
+    for (rule, elapsed) in &summary.timings {
+        println!("  {}: {} ms", rule, elapsed.as_millis());
+    }
+    Ok(!fail_on.fails(counts))
+}
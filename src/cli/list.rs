@@ -0,0 +1,68 @@
+// The text of `slpkg list`.
+
+use failure::Error;
+use slpkg::list;
+use slpkg::unpack::{EntryFilters, Filter};
+use std::path::Path;
+
+/// Prints a row per entry which `filters` keep, largest first when
+/// `by_size` is set, and then the totals and how many entries each filter
+/// excluded, or with `json`, an array of the entries. With `deep`, the
+/// entries are listed by `list_deep`, with what their gzip headers record
+/// under their rows.
+pub fn print_list(
+    slpk_file_path: &Path,
+    by_size: bool,
+    json: bool,
+    deep: Option<Option<&str>>,
+    filters: &EntryFilters,
+) -> Result<(), Error> {
+    let (mut entries, excluded) = match deep {
+        Some(password) => list::list_deep(slpk_file_path, password, filters)?,
+        None => list::list_filtered(slpk_file_path, filters)?,
+    };
+    if by_size {
+        // Entries of the same size stay in the order of the zip directory.
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.size));
+    }
+    if json {
+        println!("{}", list::entries_to_json(&entries).to_pretty_string(2));
+        return Ok(());
+    }
+    println!("{:>12} {:>12} {:<4} Name", "Compressed", "Size", "Gzip");
+    for entry in &entries {
+        println!(
+            "{:>12} {:>12} {:<4} {}",
+            entry.compressed_size,
+            entry.size,
+            if entry.gzipped { "yes" } else { "" },
+            entry.name
+        );
+        if let Some(gzip) = entry.meta.as_ref().and_then(|meta| meta.gzip.as_ref()) {
+            let mut fields = Vec::new();
+            if let Some(filename) = &gzip.filename {
+                fields.push(format!("filename {}", filename));
+            }
+            if let Some(mtime) = gzip.mtime {
+                fields.push(format!("mtime {}", mtime));
+            }
+            if let Some(comment) = &gzip.comment {
+                fields.push(format!("comment {}", comment));
+            }
+            println!("{:>30} gzip header: {}", "", fields.join(", "));
+        }
+    }
+    println!(
+        "{} entries, {} bytes compressed, {} bytes uncompressed, {} gzipped",
+        entries.len(),
+        entries.iter().map(|e| e.compressed_size).sum::<u64>(),
+        entries.iter().map(|e| e.size).sum::<u64>(),
+        entries.iter().filter(|e| e.gzipped).count()
+    );
+    for filter in &Filter::ALL {
+        if let Some(n) = excluded.get(filter) {
+            println!("{} entries excluded by {}", n, filter);
+        }
+    }
+    Ok(())
+}
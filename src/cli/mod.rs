@@ -0,0 +1,24 @@
+// The text the slpkg binary prints for each command. The library returns
+// what a command found, and the functions here lay it out for a terminal,
+// so that a program using the library decides how its results are shown.
+// Each command which has more to print than a line or two has its own
+// module, named after the library module it prints the results of.
+
+pub mod check;
+pub mod checksums;
+pub mod diff;
+pub mod export;
+pub mod extract;
+pub mod index;
+pub mod info;
+pub mod lint;
+pub mod list;
+pub mod progress;
+pub mod repair;
+pub mod self_test;
+pub mod stats;
+pub mod textures;
+pub mod tree;
+pub mod validate;
+pub mod verify;
+pub mod watch;
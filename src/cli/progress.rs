@@ -0,0 +1,103 @@
+// The progress bar of `slpkg unpack --progress`, drawn from the progress
+// an unpack reports.
+
+use slpkg::unpack::Progress;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the bar is redrawn, at most.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+#[derive(Default)]
+struct BarState {
+    last_drawn: Option<Instant>,
+    latest: (usize, usize, u64),
+    /// Whether the line of the bar has been ended, once every entry is done.
+    ended: bool,
+}
+
+/// A progress bar drawn on one line of stderr.
+#[derive(Clone, Default)]
+pub struct ProgressBar {
+    state: Arc<Mutex<BarState>>,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar::default()
+    }
+
+    /// The progress of an unpack, as it draws the bar.
+    pub fn progress(&self) -> Progress {
+        let bar = self.clone();
+        Progress::new(move |done, total, bytes| bar.update(done, total, bytes))
+    }
+
+    fn update(&self, done: usize, total: usize, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        // Workers can report out of order, so a later count may arrive first.
+        if state.ended || done < state.latest.0 {
+            return;
+        }
+        state.latest = (done, total, bytes);
+        let due = state
+            .last_drawn
+            .map_or(true, |drawn| drawn.elapsed() >= REDRAW_INTERVAL);
+        if due || done == total {
+            state.last_drawn = Some(Instant::now());
+            draw(state.latest);
+        }
+        // The summary of the unpack follows the bar once it is complete.
+        if done == total {
+            eprintln!();
+            state.ended = true;
+        }
+    }
+
+    /// Draws the last progress reported and ends the line, if an unpack
+    /// stopped short of its last entry, ready for the next unpack.
+    pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.last_drawn.is_some() && !state.ended {
+            draw(state.latest);
+            eprintln!();
+        }
+        *state = BarState::default();
+    }
+}
+
+fn draw((done, total, bytes): (usize, usize, u64)) {
+    eprint!("\r{}", bar_line(done, total, bytes));
+    let _ = std::io::stderr().flush();
+}
+
+fn bar_line(done: usize, total: usize, bytes: u64) -> String {
+    let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+    format!(
+        "[{}{}] {}/{} entries, {:.1} MiB written",
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        done,
+        total,
+        bytes as f64 / (1024.0 * 1024.0)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bar_fills_with_the_entries_done() {
+        assert_eq!(
+            bar_line(20, 40, 3 * 1024 * 1024),
+            format!(
+                "[{}{}] 20/40 entries, 3.0 MiB written",
+                "#".repeat(15),
+                " ".repeat(15)
+            )
+        );
+    }
+}
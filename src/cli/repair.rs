@@ -0,0 +1,20 @@
+// The text of `slpkg repair --check`.
+
+use failure::Error;
+use slpkg::repair;
+use std::path::Path;
+
+/// Prints the naming problems of a package, returning whether there were
+/// none.
+pub fn check_naming(slpk_file_path: &Path) -> Result<bool, Error> {
+    let (problems, entries) = repair::naming_problems(slpk_file_path)?;
+    for problem in &problems {
+        println!("{} {}", problem.name, problem.mismatch);
+    }
+    println!(
+        "{} of {} entries have content which doesn't match their name",
+        problems.len(),
+        entries
+    );
+    Ok(problems.is_empty())
+}
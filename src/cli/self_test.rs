@@ -0,0 +1,33 @@
+// The text of `slpkg self-test`.
+
+use failure::Error;
+use slpkg::self_test;
+use slpkg::self_test::Outcome;
+use std::path::Path;
+
+/// Runs the checks and prints a line for each, then how many failed.
+/// Returns whether all the checks passed.
+pub fn print_self_test(dir: Option<&Path>) -> Result<bool, Error> {
+    let report = self_test::self_test(dir)?;
+    println!();
+    for (name, outcome) in &report.results {
+        match outcome {
+            Outcome::Pass => println!("PASS  {}", name),
+            Outcome::Fail(reason) => println!("FAIL  {}: {}", name, reason),
+            Outcome::Skip(reason) => println!("SKIP  {}: {}", name, reason),
+        }
+    }
+    if let Some(e) = &report.cleanup_error {
+        println!(
+            "Unable to remove {}: {}",
+            report.work_dir.to_string_lossy(),
+            e
+        );
+    }
+    println!(
+        "{} of {} checks failed",
+        report.failures(),
+        report.results.len()
+    );
+    Ok(report.failures() == 0)
+}
@@ -0,0 +1,103 @@
+// The text of `slpkg stats`.
+
+use failure::Error;
+use slpkg::log;
+use slpkg::stats;
+use slpkg::stats::CountSource;
+use std::path::Path;
+
+/// `part` as a percentage of `whole`, to one decimal place.
+fn percentage(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", part as f64 * 100.0 / whole as f64)
+}
+
+/// Prints a row per category, with its share of the compressed size of the
+/// package, the totals, and then the largest entries, or with `json`, all of
+/// it as an object. With `coded_values`, the features holding each value of
+/// the attributes with a coded-value domain of the given layer are counted
+/// too.
+pub fn print_stats(
+    slpk_file_path: &Path,
+    json: bool,
+    coded_values: Option<Option<usize>>,
+) -> Result<(), Error> {
+    let stats = stats::stats(slpk_file_path)?;
+    let fields = match coded_values {
+        Some(layer) => stats::coded_values(slpk_file_path, layer)?,
+        None => Vec::new(),
+    };
+    for field in &fields {
+        if let Some(problem) = &field.problem {
+            log::log(
+                log::Level::Warn,
+                format_args!(
+                    "The values of {} weren't all counted: {}",
+                    field.name, problem
+                ),
+            );
+        }
+    }
+    if json {
+        let document = match coded_values {
+            Some(_) => stats.to_json_with_coded_values(&fields),
+            None => stats.to_json(),
+        };
+        println!("{}", document.to_pretty_string(2));
+        return Ok(());
+    }
+    let total = stats.total();
+    println!(
+        "{:<16} {:>8} {:>12} {:>12} {:>12} {:>6}",
+        "Category", "Entries", "Compressed", "Size", "Unpacked", "Share"
+    );
+    let rows = stats.categories.iter().map(|c| (c.category.to_string(), c));
+    for (name, category) in rows.chain(std::iter::once(("total".to_string(), &total))) {
+        println!(
+            "{:<16} {:>8} {:>12} {:>12} {:>12} {:>6}",
+            name,
+            category.entries,
+            category.compressed_size,
+            category.size,
+            category.unpacked_size,
+            percentage(category.compressed_size, total.compressed_size)
+        );
+    }
+    println!();
+    println!("Largest entries:");
+    for entry in &stats.largest {
+        println!("{:>12} {}", entry.size, entry.name);
+    }
+    for field in &fields {
+        println!();
+        let source = match field.source {
+            CountSource::Statistics => "from its statistics",
+            CountSource::Buffers => "from its attribute buffers",
+        };
+        println!("{}, {}:", field.name, source);
+        for value in &field.values {
+            let features = stats::grouped(value.features);
+            match &value.label {
+                Some(label) => println!("  {} (code {}): {} features", label, value.code, features),
+                None => println!(
+                    "  code {}, not in the domain: {} features",
+                    value.code, features
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_are_rounded_to_a_tenth() {
+        assert_eq!(percentage(1, 3), "33.3%");
+        assert_eq!(percentage(0, 0), "-");
+    }
+}
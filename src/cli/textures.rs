@@ -0,0 +1,83 @@
+// The text of `slpkg textures`.
+
+use failure::Error;
+use slpkg::textures;
+use slpkg::textures::ImageFormat;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Prints a row per texture and the number of textures of each format.
+/// With `convert`, the textures are decoded into PNGs in the folder the
+/// package unpacks into, in `output_dir` when it is given, and with
+/// `split_atlas` the atlas of each node is cropped into PNGs there, with a
+/// line for each node.
+pub fn print_textures(
+    slpk_file_path: &Path,
+    convert: bool,
+    split_atlas: bool,
+    output_dir: Option<&Path>,
+) -> Result<(), Error> {
+    let textures = textures::textures(slpk_file_path)?;
+    println!("{:<12} {:>11} {:>12} Name", "Format", "Dimensions", "Size");
+    let mut formats = BTreeMap::new();
+    for texture in &textures {
+        let format = texture
+            .format
+            .as_ref()
+            .map_or_else(|| "unknown".to_string(), ImageFormat::to_string);
+        let dimensions = texture
+            .dimensions
+            .map_or_else(String::new, |(width, height)| {
+                format!("{}x{}", width, height)
+            });
+        println!(
+            "{:<12} {:>11} {:>12} {}",
+            format, dimensions, texture.size, texture.name
+        );
+        *formats.entry(format).or_insert(0) += 1;
+    }
+    let formats: Vec<String> = formats
+        .iter()
+        .map(|(format, count)| format!("{} {}", count, format))
+        .collect();
+    if formats.is_empty() {
+        println!("{} textures", textures.len());
+    } else {
+        println!("{} textures: {}", textures.len(), formats.join(", "));
+    }
+
+    if !convert && !split_atlas {
+        return Ok(());
+    }
+    let output_folder = textures::output_folder(slpk_file_path, output_dir)?;
+    if convert {
+        let summary = textures::convert_textures(slpk_file_path, &textures, &output_folder)?;
+        println!(
+            "{} textures converted to PNG in {}, {} already PNG, {} skipped",
+            summary.converted,
+            output_folder.to_string_lossy(),
+            summary.already_png,
+            summary.skipped
+        );
+    }
+    if split_atlas {
+        let summary = textures::split_atlases(slpk_file_path, &textures, &output_folder)?;
+        for node in &summary.nodes {
+            println!(
+                "Node {}: {} crops of {} features in {}",
+                node.node_id,
+                node.crops,
+                node.features,
+                node.folder.to_string_lossy()
+            );
+        }
+        let crops: usize = summary.nodes.iter().map(|node| node.crops).sum();
+        println!(
+            "{} crops of the atlases of {} nodes, {} skipped",
+            crops,
+            summary.nodes.len(),
+            summary.skipped
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+// The text of `slpkg tree`.
+
+use failure::Error;
+use slpkg::tree;
+use std::path::Path;
+
+/// Prints the tree of the first layer of a package, each node indented below
+/// its parent, or with `counts_only` just the number of nodes at each level.
+pub fn print_tree(
+    slpk_file_path: &Path,
+    max_depth: Option<usize>,
+    counts_only: bool,
+) -> Result<(), Error> {
+    let tree = tree::tree(slpk_file_path, max_depth)?;
+    if counts_only {
+        for (level, count) in tree.nodes_per_level.iter().enumerate() {
+            println!("Level {}: {} nodes", level, count);
+        }
+    } else {
+        for node in &tree.nodes {
+            println!("{}{}", "  ".repeat(node.depth), node);
+        }
+    }
+    println!(
+        "{} nodes in {} levels",
+        tree.nodes_per_level.iter().sum::<usize>(),
+        tree.nodes_per_level.len()
+    );
+    if tree.unreachable > 0 {
+        println!("{} nodes can't be reached from the root", tree.unreachable);
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+// The text of `slpkg validate`.
+
+use failure::Error;
+use slpkg::lint::FailOn;
+use slpkg::validate;
+use slpkg::validate::{Check, ValidateOptions};
+use std::path::Path;
+
+/// Validates a package, printing each finding as it is found, then what was
+/// checked of each layer, the coverage of the hash index, a count per
+/// severity and the time spent in each check. Returns whether the findings
+/// pass `fail_on`.
+pub fn print_validate(
+    slpk_file_path: &Path,
+    options: &ValidateOptions,
+    fail_on: FailOn,
+) -> Result<bool, Error> {
+    let mut naming_problems = 0;
+    let summary = validate::validate_streaming(slpk_file_path, options, |finding| {
+        if finding.check() == Check::Naming {
+            naming_problems += 1;
+        }
+        println!("{}", finding);
+    })?;
+    for layer in &summary.layers {
+        if layer.unknown_layout.is_some() {
+            println!(
+                "Layer {} of I3S version {} wasn't checked",
+                layer.id, layer.version
+            );
+            continue;
+        }
+        println!(
+            "Layer {} of I3S version {}, {} nodes, {} resources missing and {} entries orphaned",
+            layer.id, layer.version, layer.nodes, layer.missing, layer.orphaned
+        );
+    }
+    if naming_problems > 0 {
+        println!(
+            "{} entries have content which doesn't match their name, which `slpkg repair` fixes",
+            naming_problems
+        );
+    }
+    if let Some(hash_index) = &summary.hash_index {
+        println!(
+            "The hash index covers {} of {} entries",
+            hash_index.covered, hash_index.entries
+        );
+    }
+    let counts = &summary.counts;
+    println!(
+        "{} errors, {} warnings, {} info",
+        counts.errors, counts.warnings, counts.info
+    );
+    println!("Time spent in each check, summed over the worker threads:");
+    for (check, elapsed) in &summary.timings {
+        println!("  {}: {} ms", check, elapsed.as_millis());
+    }
+    Ok(!fail_on.fails(counts))
+}
@@ -0,0 +1,42 @@
+// The text of `slpkg verify`, which `slpkg check` ends with too.
+
+use failure::Error;
+use slpkg::unpack::{EntryFilters, Filter};
+use slpkg::verify;
+use std::path::Path;
+
+/// Verifies a package, printing each entry which failed and then the count.
+/// Returns whether every entry passed.
+pub fn print_verify(
+    slpk_file_path: &Path,
+    threads: Option<usize>,
+    password: Option<&str>,
+) -> Result<bool, Error> {
+    print_verify_filtered(slpk_file_path, threads, password, &EntryFilters::default())
+}
+
+/// Verifies the entries of a package which `filters` keep, as
+/// `print_verify` does, also printing how many entries each filter left
+/// out.
+pub fn print_verify_filtered(
+    slpk_file_path: &Path,
+    threads: Option<usize>,
+    password: Option<&str>,
+    filters: &EntryFilters,
+) -> Result<bool, Error> {
+    let report = verify::verify_filtered(slpk_file_path, threads, password, filters)?;
+    for failed in &report.failed_entries {
+        println!("{}: {}", failed.entry_name, failed.problem);
+    }
+    println!(
+        "{} of {} entries can't be read, don't match their CRC or don't decompress",
+        report.failed_entries.len(),
+        report.entries
+    );
+    for filter in &Filter::ALL {
+        if let Some(n) = report.excluded.get(filter) {
+            println!("{} entries excluded by {}", n, filter);
+        }
+    }
+    Ok(report.passed())
+}
@@ -0,0 +1,66 @@
+// The text of `slpkg unpack --watch`.
+
+use slpkg::unpack::UnpackSummary;
+use slpkg::watch::WatchEvent;
+use std::path::Path;
+
+/// What a cycle unpacked, and with an incremental unpack, what it left as
+/// it was and deleted.
+fn cycle_summary(summary: &UnpackSummary) -> String {
+    match &summary.incremental {
+        Some(incremental) => format!(
+            "{} files unpacked, {} unchanged, {} deleted",
+            summary.entries_unpacked, incremental.entries_unchanged, incremental.files_deleted
+        ),
+        None => format!("{} files unpacked", summary.entries_unpacked),
+    }
+}
+
+/// Prints a line when the watch of `slpk_file_path` starts and stops, and
+/// for each cycle.
+pub fn print_event(slpk_file_path: &Path, event: WatchEvent) {
+    match event {
+        WatchEvent::Started => println!(
+            "Watching {} for changes, press Ctrl-C to stop",
+            slpk_file_path.to_string_lossy()
+        ),
+        WatchEvent::Cycle {
+            number,
+            result: Ok(summary),
+            elapsed,
+        } => println!(
+            "[cycle {}] {} in {:.1}s",
+            number,
+            cycle_summary(summary),
+            elapsed.as_secs_f64()
+        ),
+        WatchEvent::Cycle {
+            number,
+            result: Err(e),
+            ..
+        } => println!("[cycle {}] failed: {}", number, e),
+        WatchEvent::Stopped => println!("Stopped watching"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slpkg::unpack::IncrementalStats;
+
+    #[test]
+    fn incremental_cycles_say_what_they_left_alone() {
+        let summary = UnpackSummary {
+            entries_unpacked: 2,
+            incremental: Some(IncrementalStats {
+                entries_unchanged: 11,
+                files_deleted: 1,
+            }),
+            ..UnpackSummary::default()
+        };
+        assert_eq!(
+            cycle_summary(&summary),
+            "2 files unpacked, 11 unchanged, 1 deleted"
+        );
+    }
+}
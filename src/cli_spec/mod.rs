@@ -0,0 +1,442 @@
+// Describes the command line interface for shells and GUI wrappers. clap 2
+// has no public accessors for the arguments of an `App`, so the description
+// is read from what clap prints for each command through its public API, so
+// it can't drift from the arguments the program actually accepts. The help
+// is asked for as `slpkg <command> -h` would, but with each description on
+// the lines below its argument, which clap indents by three tabs, so that
+// nothing in a description can be taken for an argument. Which arguments
+// are required is read from the error clap gives for the command run
+// without any, which lists each of them on a line of its own. Hidden
+// arguments aren't in the help, and so aren't described either, and neither
+// are the help and version flags and help subcommand clap adds to every
+// command.
+
+use crate::json::Value;
+use structopt::clap::{App, AppSettings, ErrorKind};
+
+/// The headings clap gives the sections of help.
+const HEADINGS: [&str; 5] = ["USAGE", "ARGS", "FLAGS", "OPTIONS", "SUBCOMMANDS"];
+
+/// How deeply clap indents the lines of a description below its argument.
+const DESCRIPTION_INDENT: &str = "            ";
+
+/// An argument of a command, as its entry in the help gives it.
+struct Arg {
+    kind: &'static str,
+    name: String,
+    short: Option<String>,
+    long: Option<String>,
+    required: bool,
+    help: Option<String>,
+    possible_values: Option<Vec<String>>,
+    default: Option<String>,
+}
+
+/// A command, with its arguments and subcommands.
+struct Command {
+    name: String,
+    version: Option<String>,
+    about: Option<String>,
+    args: Vec<Arg>,
+    subcommands: Vec<Command>,
+}
+
+fn optional_string(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, Value::from)
+}
+
+/// The error clap gives for the command `path` names below `app` when it is
+/// run with `args`, unwrapped and with descriptions below their arguments.
+fn error_of(app: &App, path: &[String], args: &[&str]) -> Option<(ErrorKind, String)> {
+    let mut command_line = vec![app.get_name().to_string()];
+    command_line.extend(path.iter().cloned());
+    command_line.extend(args.iter().map(|arg| arg.to_string()));
+    app.clone()
+        .global_setting(AppSettings::NextLineHelp)
+        .set_term_width(0)
+        .get_matches_from_safe(command_line)
+        .err()
+        .map(|e| (e.kind, e.message))
+}
+
+/// The help of the command `path` names below `app`.
+fn help_of(app: &App, path: &[String]) -> String {
+    match error_of(app, path, &["-h"]) {
+        Some((ErrorKind::HelpDisplayed, help)) => help,
+        _ => String::new(),
+    }
+}
+
+/// The required arguments of the command `path` names below `app`, as clap
+/// lists them when none are given, such as `<src_file>` or
+/// `--output <output>`. A command which needs a subcommand is refused for
+/// that first, and so lists none.
+fn required_of(app: &App, path: &[String]) -> Vec<String> {
+    match error_of(app, path, &[]) {
+        Some((ErrorKind::MissingRequiredArgument, message)) => message
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.trim().is_empty())
+            .map(|line| line.trim().to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The sections of `help`, by their heading, each with its lines. The lines
+/// before the first heading are under an empty one.
+fn sections(help: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut sections = vec![("", Vec::new())];
+    for line in help.lines() {
+        match line.strip_suffix(':') {
+            Some(heading) if HEADINGS.contains(&heading) => sections.push((heading, Vec::new())),
+            _ if line.trim().is_empty() => {}
+            _ => sections.last_mut().unwrap().1.push(line),
+        }
+    }
+    sections
+}
+
+/// The entries of a section of help, each what it shows of an argument or
+/// subcommand and the description on the lines below it.
+fn entries<'a>(lines: &[&'a str]) -> Vec<(&'a str, Option<String>)> {
+    let mut entries: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in lines {
+        match (line.strip_prefix(DESCRIPTION_INDENT), entries.last_mut()) {
+            (Some(description), Some((_, lines))) => lines.push(description),
+            _ => entries.push((line.trim(), Vec::new())),
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(shown, lines)| {
+            (
+                shown,
+                Some(lines.join("\n")).filter(|help| !help.is_empty()),
+            )
+        })
+        .collect()
+}
+
+/// `help` without the `[name: ...]` clap ends it with, and what that holds.
+/// clap adds those for the environment variable, the default and the possible
+/// values, in that order.
+fn strip_tag<'a>(help: &'a str, name: &str) -> (&'a str, Option<&'a str>) {
+    let tag = format!("[{}: ", name);
+    match help.rfind(&tag) {
+        Some(at) if help.ends_with(']') => (
+            help[..at].trim_end(),
+            Some(&help[at + tag.len()..help.len() - 1]),
+        ),
+        _ => (help, None),
+    }
+}
+
+/// The argument an entry of the section `kind` describes, given the
+/// required arguments of its command.
+fn parse_arg(kind: &'static str, shown: &str, help: Option<&str>, required: &[String]) -> Arg {
+    let (help, possible_values) = strip_tag(help.unwrap_or(""), "possible values");
+    let (help, default) = strip_tag(help, "default");
+    let (help, _) = strip_tag(help, "env");
+    let mut arg = Arg {
+        kind,
+        name: String::new(),
+        short: None,
+        long: None,
+        required: false,
+        help: Some(help.to_string()).filter(|help| !help.is_empty()),
+        possible_values: possible_values
+            .map(|values| values.split(", ").map(String::from).collect()),
+        default: default.map(String::from),
+    };
+    let mut value = None;
+    for token in shown.split([' ', '=']) {
+        let token = token.trim_end_matches(',');
+        if let Some(long) = token.strip_prefix("--") {
+            arg.long = Some(long.to_string());
+        } else if let Some(short) = token.strip_prefix('-') {
+            arg.short = Some(short.to_string());
+        } else if value.is_none() && token.starts_with('<') {
+            value = Some(token.trim_end_matches("...").trim_matches(['<', '>']));
+        }
+    }
+    // clap lists a missing argument by its long name when it has one,
+    // rather than both its names as the help does.
+    let listed = match (&arg.short, &arg.long) {
+        (Some(short), Some(_)) => shown
+            .strip_prefix(&format!("-{}, ", short))
+            .unwrap_or(shown),
+        _ => shown,
+    };
+    arg.required = required.iter().any(|required| required == listed);
+    arg.name = match kind {
+        // Flags show no name, so theirs is their long name, in the snake
+        // case structopt derives that from.
+        "flag" => arg
+            .long
+            .as_ref()
+            .or(arg.short.as_ref())
+            .map_or_else(String::new, |name| name.replace('-', "_")),
+        _ => value.unwrap_or_default().to_string(),
+    };
+    arg
+}
+
+/// The command `path` names below `app`, which is named `name`, with `about`
+/// as its parent lists it.
+fn command(app: &App, path: &mut Vec<String>, name: &str, about: Option<String>) -> Command {
+    let help = help_of(app, path);
+    let required = required_of(app, path);
+    let sections = sections(&help);
+    let section = |heading: &str| {
+        sections
+            .iter()
+            .find(|(name, _)| *name == heading)
+            .map_or_else(Vec::new, |(_, lines)| entries(lines))
+    };
+    let mut args = Vec::new();
+    for (heading, kind) in [
+        ("ARGS", "positional"),
+        ("FLAGS", "flag"),
+        ("OPTIONS", "option"),
+    ] {
+        args.extend(
+            section(heading)
+                .iter()
+                .map(|(shown, help)| parse_arg(kind, shown, help.as_deref(), &required))
+                .filter(|arg| !matches!(arg.long.as_deref(), Some("help") | Some("version"))),
+        );
+    }
+    let mut subcommands = Vec::new();
+    for (subcommand, about) in section("SUBCOMMANDS") {
+        if subcommand == "help" {
+            continue;
+        }
+        path.push(subcommand.to_string());
+        subcommands.push(command(app, path, subcommand, about));
+        path.pop();
+    }
+    // The first line gives the name and the version, and the next the
+    // authors, which come before the about.
+    let header: Vec<&str> = sections[0].1.iter().map(|line| line.trim()).collect();
+    let version = header
+        .first()
+        .and_then(|line| line.rsplit_once(' '))
+        .map(|(_, version)| version.to_string());
+    Command {
+        name: name.to_string(),
+        version,
+        about: about.or_else(|| Some(header.get(2..)?.join(" ")).filter(|a| !a.is_empty())),
+        args,
+        subcommands,
+    }
+}
+
+fn command_spec(command: &Command) -> Value {
+    let args = command
+        .args
+        .iter()
+        .map(|arg| {
+            let possible_values = || match &arg.possible_values {
+                Some(values) => {
+                    Value::Array(values.iter().map(|v| Value::from(v.as_str())).collect())
+                }
+                None => Value::Null,
+            };
+            let mut members = vec![
+                ("name".to_string(), Value::from(arg.name.as_str())),
+                ("kind".to_string(), Value::from(arg.kind)),
+            ];
+            if arg.kind != "positional" {
+                members.push(("short".to_string(), optional_string(arg.short.as_deref())));
+                members.push(("long".to_string(), optional_string(arg.long.as_deref())));
+            }
+            if arg.kind != "flag" {
+                members.push(("required".to_string(), Value::Bool(arg.required)));
+            }
+            members.push(("help".to_string(), optional_string(arg.help.as_deref())));
+            if arg.kind != "flag" {
+                members.push(("possibleValues".to_string(), possible_values()));
+            }
+            if arg.kind == "option" {
+                members.push((
+                    "default".to_string(),
+                    optional_string(arg.default.as_deref()),
+                ));
+            }
+            Value::Object(members)
+        })
+        .collect();
+    let mut spec = vec![
+        ("name".to_string(), Value::from(command.name.as_str())),
+        (
+            "about".to_string(),
+            optional_string(command.about.as_deref()),
+        ),
+        ("args".to_string(), Value::Array(args)),
+    ];
+    if !command.subcommands.is_empty() {
+        spec.push((
+            "subcommands".to_string(),
+            Value::Array(command.subcommands.iter().map(command_spec).collect()),
+        ));
+    }
+    Value::Object(spec)
+}
+
+/// Returns the full command and argument tree as a JSON document.
+pub fn cli_spec(app: &App) -> Value {
+    let command = command(app, &mut Vec::new(), app.get_name(), None);
+    let mut spec = match command_spec(&command) {
+        Value::Object(members) => members,
+        _ => unreachable!(),
+    };
+    spec.insert(
+        1,
+        (
+            "version".to_string(),
+            optional_string(command.version.as_deref()),
+        ),
+    );
+    Value::Object(spec)
+}
+
+/// Returns an indented, human readable outline of the command tree.
+pub fn cli_outline(app: &App) -> String {
+    fn outline(command: &Command, depth: usize, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        out.push_str(&format!(
+            "{}{}{}\n",
+            indent,
+            command.name,
+            command
+                .about
+                .as_ref()
+                .map(|a| format!(" - {}", a))
+                .unwrap_or_default()
+        ));
+        for (kind, arg) in command.args.iter().map(|arg| (arg.kind, arg)) {
+            match (kind, &arg.long) {
+                ("positional", _) => out.push_str(&format!("{}    <{}>\n", indent, arg.name)),
+                ("flag", Some(long)) => out.push_str(&format!("{}    --{}\n", indent, long)),
+                (_, Some(long)) => {
+                    out.push_str(&format!("{}    --{} <{}>\n", indent, long, arg.name))
+                }
+                _ => {}
+            }
+        }
+        for subcommand in &command.subcommands {
+            outline(subcommand, depth + 1, out);
+        }
+    }
+
+    let mut out = String::new();
+    outline(
+        &command(app, &mut Vec::new(), app.get_name(), None),
+        0,
+        &mut out,
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::clap::{Arg as ClapArg, SubCommand};
+
+    fn app() -> App<'static, 'static> {
+        App::new("tool").version("1.2.3").subcommand(
+            SubCommand::with_name("run")
+                .about("Runs it")
+                .arg(
+                    ClapArg::with_name("input")
+                        .required(true)
+                        .help("The input  file, with two spaces"),
+                )
+                .arg(ClapArg::with_name("extra").help("USAGE:"))
+                .arg(
+                    ClapArg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Where to write, as in --output <output>"),
+                )
+                .arg(
+                    ClapArg::with_name("mode")
+                        .long("mode")
+                        .takes_value(true)
+                        .default_value("fast")
+                        .possible_values(&["fast", "slow"])
+                        .help("How:\nFLAGS:"),
+                )
+                .arg(
+                    ClapArg::with_name("quiet")
+                        .short("q")
+                        .long("be-quiet")
+                        .help("Says nothing  at all"),
+                )
+                .arg(ClapArg::with_name("secret").long("secret").hidden(true)),
+        )
+    }
+
+    #[test]
+    fn descriptions_which_look_like_help_are_kept_whole() {
+        let spec = cli_spec(&app());
+        let run = &spec.get("subcommands").and_then(Value::as_array).unwrap()[0];
+        let args = run.get("args").and_then(Value::as_array).unwrap();
+        let arg = |name: &str| {
+            args.iter()
+                .find(|arg| arg.get("name").and_then(Value::as_str) == Some(name))
+                .unwrap_or_else(|| panic!("no {}", name))
+        };
+        let text =
+            |arg: &Value, member: &str| arg.get(member).and_then(Value::as_str).map(String::from);
+        let required = |arg: &Value| match arg.get("required") {
+            Some(Value::Bool(required)) => Some(*required),
+            _ => None,
+        };
+
+        assert_eq!(text(&spec, "version").as_deref(), Some("1.2.3"));
+        assert_eq!(text(run, "about").as_deref(), Some("Runs it"));
+        assert_eq!(args.len(), 5);
+        assert_eq!(
+            text(arg("input"), "help").as_deref(),
+            Some("The input  file, with two spaces")
+        );
+        assert_eq!(required(arg("input")), Some(true));
+        assert_eq!(text(arg("extra"), "help").as_deref(), Some("USAGE:"));
+        assert_eq!(required(arg("extra")), Some(false));
+
+        let output = arg("output");
+        assert_eq!(
+            (text(output, "short"), text(output, "long")),
+            (Some("o".to_string()), Some("output".to_string()))
+        );
+        assert_eq!(required(output), Some(true));
+        assert_eq!(
+            text(output, "help").as_deref(),
+            Some("Where to write, as in --output <output>")
+        );
+
+        let mode = arg("mode");
+        assert_eq!(text(mode, "help").as_deref(), Some("How:\nFLAGS:"));
+        assert_eq!(text(mode, "default").as_deref(), Some("fast"));
+        assert_eq!(
+            mode.get("possibleValues"),
+            Some(&Value::Array(vec![
+                Value::from("fast"),
+                Value::from("slow")
+            ]))
+        );
+        // With a default, it is never missing.
+        assert_eq!(required(mode), Some(false));
+
+        let quiet = arg("be_quiet");
+        assert_eq!(text(quiet, "short").as_deref(), Some("q"));
+        assert_eq!(text(quiet, "help").as_deref(), Some("Says nothing  at all"));
+        assert!(args
+            .iter()
+            .all(|arg| arg.get("name").and_then(Value::as_str) != Some("secret")));
+    }
+}
@@ -0,0 +1,457 @@
+// Defaults for the options of unpack, read from `slpkg.toml` files, so that a
+// project or a user can set once what they would otherwise pass on every
+// command. Files are looked for in the current folder, then in the folder of
+// the package, then in the configuration folder of the user, and a key set
+// in one is taken over those the later ones set. Tables and keys are the
+// groups and fields of `UnpackOptions`, and values are written as the flags
+// of unpack take them:
+//
+//     [output]
+//     overwrite = "merge"
+//
+//     [run]
+//     threads = 4
+//
+// Every key is checked when the file is read, so that a mistake in one is
+// reported, with its line and column, whichever command reads it.
+
+mod toml;
+
+use self::toml::TomlError;
+pub use self::toml::TomlValue;
+use crate::unpack::{ByteSize, UnpackOptions};
+use failure::Error;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the files configuration is read from.
+pub const CONFIG_FILE: &str = "slpkg.toml";
+
+/// Every key a configuration file may set, as `table.key`.
+pub const KEYS: [&str; 35] = [
+    "selection.max_level",
+    "selection.exclude_empty_nodes",
+    "selection.strip_prefix",
+    "selection.keep_unprefixed",
+    "output.output_dir",
+    "output.split_sublayers",
+    "output.overwrite",
+    "output.on_file_conflict",
+    "output.rename_collisions",
+    "output.portable_names",
+    "output.allow_symlinks",
+    "output.dedup",
+    "output.dedup_geometry",
+    "output.stage_files",
+    "output.atomic",
+    "output.write_buffer_size",
+    "content.trust_extensions",
+    "content.keep_gzip",
+    "content.json_formatting",
+    "content.sort_keys",
+    "content.sort_keys_max_size",
+    "content.restore_gzip_mtime",
+    "integrity.verify_after",
+    "integrity.verify_output",
+    "integrity.max_expansion_ratio",
+    "integrity.validate_json",
+    "integrity.strict_json",
+    "integrity.hardened",
+    "reports.semantic_manifest",
+    "reports.checksum_manifest",
+    "reports.emit_index",
+    "run.threads",
+    "run.extract_order",
+    "run.keep_going",
+    "run.mmap",
+];
+
+/// Why the configuration files can't be read.
+#[derive(Debug, Fail)]
+pub enum ConfigError {
+    /// A file isn't TOML, or sets a key which isn't one of `KEYS` or to a
+    /// value it can't take.
+    #[fail(display = "{}:{}:{}: {}", path, line, column, message)]
+    Invalid {
+        /// The file.
+        path: String,
+        /// The line of the file, counting from 1.
+        line: usize,
+        /// The column of the line, counting from 1.
+        column: usize,
+        /// What is wrong there.
+        message: String,
+    },
+    /// A file was found but can't be read.
+    #[fail(display = "{} can't be read: {}", path, error)]
+    Unreadable {
+        /// The file.
+        path: String,
+        /// Why it can't be read.
+        error: String,
+    },
+}
+
+/// A value set by a configuration file, and where.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setting {
+    /// The key, as `table.key`.
+    pub key: String,
+    /// The value it was set to.
+    pub value: TomlValue,
+    /// The file it was set in.
+    pub source: PathBuf,
+    /// The line of the file the key is on, counting from 1.
+    pub line: usize,
+    /// The column of the line the value starts at, counting from 1.
+    pub column: usize,
+}
+
+/// The settings of every configuration file found, with those of the files
+/// looked in first taken over those of the later ones.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Every file looked for, in order, and whether it was there.
+    pub files: Vec<(PathBuf, bool)>,
+    /// The value each key is set to, in the order of `KEYS`.
+    pub settings: Vec<Setting>,
+}
+
+impl Config {
+    /// Reads the configuration files for a command on `package`, or on no
+    /// package in particular: `slpkg.toml` in the current folder, in the
+    /// folder of the package, and in the configuration folder of the user.
+    pub fn discover(package: Option<&Path>) -> Result<Config, Error> {
+        let mut candidates = vec![PathBuf::from(CONFIG_FILE)];
+        if let Some(folder) = package.and_then(Path::parent) {
+            let folder = if folder.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                folder
+            };
+            let current = std::env::current_dir().and_then(|dir| dir.canonicalize());
+            if folder.canonicalize().ok() != current.ok() {
+                candidates.push(folder.join(CONFIG_FILE));
+            }
+        }
+        if let Some(folder) = user_config_dir() {
+            candidates.push(folder.join("slpkg").join(CONFIG_FILE));
+        }
+        Config::load(&candidates)
+    }
+
+    /// Reads whichever of `files` there are, taking the settings of each
+    /// over those of the files after it.
+    pub fn load(files: &[PathBuf]) -> Result<Config, Error> {
+        let mut config = Config::default();
+        for file in files {
+            let text = match std::fs::read_to_string(file) {
+                Ok(text) => text,
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    config.files.push((file.clone(), false));
+                    continue;
+                }
+                Err(e) => {
+                    return Err(ConfigError::Unreadable {
+                        path: file.to_string_lossy().into_owned(),
+                        error: e.to_string(),
+                    }
+                    .into())
+                }
+            };
+            config.files.push((file.clone(), true));
+            for setting in read_settings(file, &text)? {
+                if !config.settings.iter().any(|s| s.key == setting.key) {
+                    config.settings.push(setting);
+                }
+            }
+        }
+        config
+            .settings
+            .sort_by_key(|s| KEYS.iter().position(|key| *key == s.key));
+        Ok(config)
+    }
+
+    /// The setting of `key`, if a file sets it.
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.settings.iter().find(|s| s.key == key)
+    }
+
+    /// Sets the options the files set, but for the keys `given` says were
+    /// given on the command line, which are taken over the files.
+    pub fn apply(&self, options: &mut UnpackOptions, given: impl Fn(&str) -> bool) {
+        for setting in &self.settings {
+            if !given(&setting.key) {
+                // Values were checked when their file was read.
+                let _ = set(options, &setting.key, &setting.value);
+            }
+        }
+    }
+
+    /// The settings as one file, with the file each comes from beside it,
+    /// and the files looked for at the top.
+    pub fn to_toml(&self) -> String {
+        let mut text = String::new();
+        for (file, found) in &self.files {
+            let state = if *found { "read" } else { "not found" };
+            text.push_str(&format!("# {} ({})\n", file.to_string_lossy(), state));
+        }
+        let mut table = "";
+        for setting in &self.settings {
+            let (group, key) = setting.key.split_at(setting.key.find('.').unwrap_or(0));
+            if group != table {
+                text.push_str(&format!("\n[{}]\n", group));
+                table = group;
+            }
+            text.push_str(&format!(
+                "{} = {}  # {}:{}\n",
+                &key[1..],
+                setting.value,
+                setting.source.to_string_lossy(),
+                setting.line
+            ));
+        }
+        text
+    }
+}
+
+/// The folder the configuration of programs goes in for the user.
+fn user_config_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+    if cfg!(windows) {
+        var("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
+/// The settings of the file `path`, which holds `text`.
+fn read_settings(path: &Path, text: &str) -> Result<Vec<Setting>, ConfigError> {
+    let invalid = |line, column, message| ConfigError::Invalid {
+        path: path.to_string_lossy().into_owned(),
+        line,
+        column,
+        message,
+    };
+    let entries = toml::parse(text).map_err(
+        |TomlError {
+             line,
+             column,
+             message,
+         }| { invalid(line, column, message) },
+    )?;
+    let mut scratch = UnpackOptions::default();
+    let mut settings = Vec::new();
+    for entry in entries {
+        let key = format!("{}.{}", entry.table, entry.key);
+        if !KEYS.contains(&key.as_str()) {
+            let message = if entry.table.is_empty() {
+                format!("{} isn't in a table, such as [output]", entry.key)
+            } else {
+                format!("{} isn't an option of unpack", key)
+            };
+            return Err(invalid(entry.line, entry.key_column, message));
+        }
+        set(&mut scratch, &key, &entry.value).map_err(|message| {
+            invalid(entry.line, entry.column, format!("{}: {}", key, message))
+        })?;
+        settings.push(Setting {
+            key,
+            value: entry.value,
+            source: path.to_path_buf(),
+            line: entry.line,
+            column: entry.column,
+        });
+    }
+    Ok(settings)
+}
+
+fn boolean(value: &TomlValue) -> Result<bool, String> {
+    match value {
+        TomlValue::Boolean(b) => Ok(*b),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+fn count(value: &TomlValue) -> Result<u64, String> {
+    match value {
+        TomlValue::Integer(n) if *n >= 0 => Ok(*n as u64),
+        _ => Err("expected a whole number of at least 0".to_string()),
+    }
+}
+
+/// A value the flag of the key would take, from a string, or from a number
+/// for sizes.
+fn parsed<T>(value: &TomlValue) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        TomlValue::String(s) => s.parse().map_err(|e: T::Err| e.to_string()),
+        TomlValue::Integer(n) => n.to_string().parse().map_err(|e: T::Err| e.to_string()),
+        _ => Err("expected a string".to_string()),
+    }
+}
+
+/// Sets the option `key` of `options` to `value`.
+fn set(options: &mut UnpackOptions, key: &str, value: &TomlValue) -> Result<(), String> {
+    let (selection, output) = (&mut options.selection, &mut options.output);
+    let (content, integrity) = (&mut options.content, &mut options.integrity);
+    let (reports, run) = (&mut options.reports, &mut options.run);
+    match key {
+        "selection.max_level" => selection.max_level = Some(count(value)? as usize),
+        "selection.exclude_empty_nodes" => selection.exclude_empty_nodes = boolean(value)?,
+        "selection.strip_prefix" => selection.strip_prefix = Some(parsed(value)?),
+        "selection.keep_unprefixed" => selection.keep_unprefixed = boolean(value)?,
+        "output.output_dir" => match value {
+            TomlValue::String(s) => output.output_dir = Some(PathBuf::from(s)),
+            _ => return Err("expected a string".to_string()),
+        },
+        "output.split_sublayers" => output.split_sublayers = boolean(value)?,
+        "output.overwrite" => output.overwrite = parsed(value)?,
+        "output.on_file_conflict" => output.on_file_conflict = Some(parsed(value)?),
+        "output.rename_collisions" => output.rename_collisions = boolean(value)?,
+        "output.portable_names" => output.portable_names = boolean(value)?,
+        "output.allow_symlinks" => output.allow_symlinks = boolean(value)?,
+        "output.dedup" => output.dedup = Some(parsed(value)?),
+        "output.dedup_geometry" => output.dedup_geometry = boolean(value)?,
+        "output.stage_files" => output.stage_files = boolean(value)?,
+        "output.atomic" => output.atomic = boolean(value)?,
+        "output.write_buffer_size" => {
+            output.write_buffer_size = Some(parsed::<ByteSize>(value)?.0 as usize)
+        }
+        "content.trust_extensions" => content.trust_extensions = boolean(value)?,
+        "content.keep_gzip" => content.keep_gzip = boolean(value)?,
+        "content.json_formatting" => content.json_formatting = parsed(value)?,
+        "content.sort_keys" => content.sort_keys = boolean(value)?,
+        "content.sort_keys_max_size" => {
+            content.sort_keys_max_size = Some(parsed::<ByteSize>(value)?.0)
+        }
+        "content.restore_gzip_mtime" => content.restore_gzip_mtime = boolean(value)?,
+        "integrity.verify_after" => integrity.verify_after = boolean(value)?,
+        "integrity.verify_output" => integrity.verify_output = boolean(value)?,
+        "integrity.max_expansion_ratio" => integrity.max_expansion_ratio = Some(count(value)?),
+        "integrity.validate_json" => integrity.validate_json = boolean(value)?,
+        "integrity.strict_json" => integrity.strict_json = boolean(value)?,
+        "integrity.hardened" => integrity.hardened = boolean(value)?,
+        "reports.semantic_manifest" => reports.semantic_manifest = boolean(value)?,
+        "reports.checksum_manifest" => reports.checksum_manifest = boolean(value)?,
+        "reports.emit_index" => reports.emit_index = boolean(value)?,
+        "run.threads" => run.threads = Some(count(value)? as usize),
+        "run.extract_order" => run.extract_order = parsed(value)?,
+        "run.keep_going" => run.keep_going = boolean(value)?,
+        "run.mmap" => run.mmap = boolean(value)?,
+        _ => return Err(format!("{} isn't an option of unpack", key)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use crate::unpack::{JsonFormatting, OverwriteMode};
+
+    fn write(path: &Path, text: &str) -> PathBuf {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn every_key_sets_its_option() {
+        let mut options = UnpackOptions::default();
+        for key in KEYS.iter() {
+            let error = set(&mut options, key, &TomlValue::Array(Vec::new())).unwrap_err();
+            assert!(!error.contains("isn't an option"), "{}", key);
+        }
+    }
+
+    #[test]
+    fn earlier_files_are_taken_over_later_ones_and_flags_over_both() {
+        let dir = TempDir::new("config-precedence");
+        let project = write(
+            &dir.join("project/slpkg.toml"),
+            "[output]\noverwrite = \"merge\"\n\n[run]\nthreads = 4\n",
+        );
+        let user = write(
+            &dir.join("user/slpkg.toml"),
+            "[run]\nthreads = 8\nkeep_going = true\n\n[content]\njson_formatting = \"pretty\"\n",
+        );
+        let missing = dir.join("package/slpkg.toml");
+        let config = Config::load(&[project.clone(), missing.clone(), user.clone()]).unwrap();
+        assert_eq!(
+            config.files,
+            [
+                (project.clone(), true),
+                (missing, false),
+                (user.clone(), true)
+            ]
+        );
+        assert_eq!(config.get("run.threads").unwrap().source, project);
+        assert_eq!(config.get("run.keep_going").unwrap().source, user);
+
+        let mut options = UnpackOptions::new().threads(2);
+        config.apply(&mut options, |key| key == "run.threads");
+        assert_eq!(options.run.threads, Some(2));
+        assert!(options.run.keep_going);
+        assert_eq!(options.output.overwrite, OverwriteMode::Merge);
+        assert_eq!(
+            options.content.json_formatting,
+            "pretty".parse::<JsonFormatting>().unwrap()
+        );
+
+        let shown = config.to_toml();
+        assert!(shown.contains(&format!("threads = 4  # {}:5", project.to_string_lossy())));
+        assert!(shown.contains(&format!(
+            "keep_going = true  # {}:3",
+            user.to_string_lossy()
+        )));
+        assert!(shown.contains("not found"));
+        assert!(shown.find("[output]").unwrap() < shown.find("[run]").unwrap());
+    }
+
+    #[test]
+    fn mistakes_are_reported_where_they_are() {
+        let dir = TempDir::new("config-mistakes");
+        let error = |text: &str| {
+            let file = write(&dir.join("slpkg.toml"), text);
+            let error = Config::load(std::slice::from_ref(&file))
+                .unwrap_err()
+                .to_string();
+            let prefix = format!("{}:", file.to_string_lossy());
+            assert!(error.starts_with(&prefix), "{}", error);
+            error[prefix.len()..].to_string()
+        };
+        assert_eq!(
+            error("[run]\nthreads = \"four\"\n"),
+            "2:11: run.threads: expected a whole number of at least 0"
+        );
+        assert!(
+            error("[output]\noverwrite = \"sometimes\"\n").starts_with("2:13: output.overwrite: ")
+        );
+        assert_eq!(
+            error("[output]\nthreads = 4\n"),
+            "2:1: output.threads isn't an option of unpack"
+        );
+        assert_eq!(
+            error("threads = 4\n"),
+            "1:1: threads isn't in a table, such as [output]"
+        );
+        assert_eq!(
+            error("[run]\n  mmap = 1\n  bogus = 1\n"),
+            "2:10: run.mmap: expected true or false"
+        );
+        assert_eq!(
+            error("[run]\n  bogus = 1\n"),
+            "2:3: run.bogus isn't an option of unpack"
+        );
+        assert_eq!(error("[run\n"), "1:5: expected ']'");
+    }
+}
@@ -0,0 +1,498 @@
+// Reads the part of TOML which configuration files need: tables, keys set
+// to strings, integers, booleans or arrays of them, and comments. Anything
+// else, such as floats, dates, inline tables or strings over several lines,
+// is refused where it starts, with its line and column, as is a key set
+// twice. Keys and tables are bare or quoted names; dotted keys aren't read.
+
+use std::fmt;
+
+/// A value of a TOML file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TomlValue {
+    /// A basic string, in double quotes, with its escapes undone.
+    String(String),
+    /// An integer, with any underscores between its digits taken out.
+    Integer(i64),
+    /// `true` or `false`.
+    Boolean(bool),
+    /// An array of values, all on one line.
+    Array(Vec<TomlValue>),
+}
+
+impl fmt::Display for TomlValue {
+    /// Writes the value as TOML, so that it reads back the same.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TomlValue::String(s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        '\r' => f.write_str("\\r")?,
+                        c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            TomlValue::Integer(n) => write!(f, "{}", n),
+            TomlValue::Boolean(b) => write!(f, "{}", b),
+            TomlValue::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+/// A key set in a file, in the table it is in, with where it and its value
+/// start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TomlEntry {
+    /// The table the key is in, empty for the keys before any table.
+    pub table: String,
+    pub key: String,
+    pub value: TomlValue,
+    pub line: usize,
+    pub key_column: usize,
+    /// The column of the value.
+    pub column: usize,
+}
+
+/// Why a file isn't read, and where, counting lines and columns from 1 and
+/// columns in characters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TomlError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// A line being read, by character.
+struct Cursor {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl Cursor {
+    fn new(text: &str, line: usize) -> Cursor {
+        Cursor {
+            chars: text.chars().collect(),
+            position: 0,
+            line,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn column(&self) -> usize {
+        self.position + 1
+    }
+
+    fn error(&self, message: impl Into<String>) -> TomlError {
+        TomlError {
+            line: self.line,
+            column: self.column(),
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    /// Whether nothing but whitespace and a comment is left.
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    fn expect_end(&mut self) -> Result<(), TomlError> {
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(self.error("expected the end of the line"))
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), TomlError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", expected)))
+        }
+    }
+
+    /// A bare or quoted key or table name.
+    fn name(&mut self) -> Result<String, TomlError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.position;
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        self.position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                if self.position == start {
+                    return Err(self.error("expected a key"));
+                }
+                if self.peek() == Some('.') {
+                    return Err(self.error("dotted keys aren't supported"));
+                }
+                Ok(self.chars[start..self.position].iter().collect())
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        let start = self.column();
+        self.position += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(TomlError {
+                        line: self.line,
+                        column: start,
+                        message: "the string isn't closed".to_string(),
+                    })
+                }
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.position += 1;
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(kind @ 'u') | Some(kind @ 'U') => {
+                            let len = if kind == 'u' { 4 } else { 8 };
+                            let digits: String = self
+                                .chars
+                                .iter()
+                                .skip(self.position + 1)
+                                .take(len)
+                                .collect();
+                            let c = u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .filter(|_| digits.len() == len)
+                                .and_then(std::char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.position += len;
+                            c
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.push(escaped);
+                    self.position += 1;
+                }
+                Some(c) => {
+                    s.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        let start = self.column();
+        self.position += 1;
+        let from = self.position;
+        while let Some(c) = self.peek() {
+            if c == '\'' {
+                let s = self.chars[from..self.position].iter().collect();
+                self.position += 1;
+                return Ok(s);
+            }
+            self.position += 1;
+        }
+        Err(TomlError {
+            line: self.line,
+            column: start,
+            message: "the string isn't closed".to_string(),
+        })
+    }
+
+    fn value(&mut self) -> Result<TomlValue, TomlError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => {
+                if self.chars[self.position..].starts_with(&['"', '"', '"']) {
+                    return Err(self.error("strings over several lines aren't supported"));
+                }
+                self.basic_string().map(TomlValue::String)
+            }
+            Some('\'') => self.literal_string().map(TomlValue::String),
+            Some('[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.position += 1;
+                        return Ok(TomlValue::Array(items));
+                    }
+                    if self.peek().is_none() {
+                        return Err(self.error("arrays over several lines aren't supported"));
+                    }
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some(']') => {}
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some('{') => Err(self.error("inline tables aren't supported")),
+            _ => {
+                let start = self.position;
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_alphanumeric() || "+-_.:".contains(c) {
+                        self.position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let word: String = self.chars[start..self.position].iter().collect();
+                let error = |message: &str| TomlError {
+                    line: self.line,
+                    column: start + 1,
+                    message: message.to_string(),
+                };
+                match word.as_str() {
+                    "" => Err(error("expected a value")),
+                    "true" => Ok(TomlValue::Boolean(true)),
+                    "false" => Ok(TomlValue::Boolean(false)),
+                    _ => {
+                        let digits = word.replace('_', "");
+                        let leading_zero = digits.trim_start_matches(['+', '-']);
+                        if leading_zero.len() > 1 && leading_zero.starts_with('0') {
+                            return Err(error("integers can't have leading zeros"));
+                        }
+                        match digits.parse::<i64>() {
+                            Ok(n) if !word.starts_with('_') && !word.ends_with('_') => {
+                                Ok(TomlValue::Integer(n))
+                            }
+                            _ => Err(error(
+                                "expected a string, an integer, a boolean or an array",
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads every key a file sets, in order.
+pub fn parse(text: &str) -> Result<Vec<TomlEntry>, TomlError> {
+    let mut entries: Vec<TomlEntry> = Vec::new();
+    let mut tables: Vec<String> = Vec::new();
+    let mut table = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut cursor = Cursor::new(line, index + 1);
+        if cursor.at_end() {
+            continue;
+        }
+        if cursor.peek() == Some('[') {
+            cursor.position += 1;
+            if cursor.peek() == Some('[') {
+                return Err(cursor.error("arrays of tables aren't supported"));
+            }
+            let column = cursor.column();
+            let name = cursor.name()?;
+            cursor.expect(']')?;
+            cursor.expect_end()?;
+            if tables.contains(&name) {
+                return Err(TomlError {
+                    line: index + 1,
+                    column,
+                    message: format!("the table [{}] is given twice", name),
+                });
+            }
+            tables.push(name.clone());
+            table = name;
+            continue;
+        }
+        cursor.skip_whitespace();
+        let column = cursor.column();
+        let key = cursor.name()?;
+        cursor.expect('=')?;
+        cursor.skip_whitespace();
+        let value_column = cursor.column();
+        let value = cursor.value()?;
+        cursor.expect_end()?;
+        if entries.iter().any(|e| e.table == table && e.key == key) {
+            return Err(TomlError {
+                line: index + 1,
+                column,
+                message: format!("{} is set twice", key),
+            });
+        }
+        entries.push(TomlEntry {
+            table: table.clone(),
+            key,
+            value,
+            line: index + 1,
+            key_column: column,
+            column: value_column,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (usize, usize, String) {
+        let e = parse(text).unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn tables_keys_and_values_are_read() {
+        let entries = parse(
+            "# Shared defaults\n\
+             top = 1\n\
+             \n\
+             [output]\n\
+             overwrite = \"merge\"   # or error\n\
+             \"quoted key\" = 'C:\\path'\n\
+             [run]\n\
+             threads = +1_000\n\
+             keep_going = false\n\
+             only = [\"textures\", 'geometry',]\n\
+             escaped = \"a\\\"b\\u00e9\\n\"\n",
+        )
+        .unwrap();
+        let values: Vec<(&str, &str, &TomlValue, usize, usize)> = entries
+            .iter()
+            .map(|e| (&e.table[..], &e.key[..], &e.value, e.line, e.column))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("", "top", &TomlValue::Integer(1), 2, 7),
+                (
+                    "output",
+                    "overwrite",
+                    &TomlValue::String("merge".to_string()),
+                    5,
+                    13
+                ),
+                (
+                    "output",
+                    "quoted key",
+                    &TomlValue::String("C:\\path".to_string()),
+                    6,
+                    16
+                ),
+                ("run", "threads", &TomlValue::Integer(1000), 8, 11),
+                ("run", "keep_going", &TomlValue::Boolean(false), 9, 14),
+                (
+                    "run",
+                    "only",
+                    &TomlValue::Array(vec![
+                        TomlValue::String("textures".to_string()),
+                        TomlValue::String("geometry".to_string())
+                    ]),
+                    10,
+                    8
+                ),
+                (
+                    "run",
+                    "escaped",
+                    &TomlValue::String("a\"b\u{e9}\n".to_string()),
+                    11,
+                    11
+                ),
+            ]
+        );
+        let written = entries[6].value.to_string();
+        assert_eq!(written, "\"a\\\"b\u{e9}\\n\"");
+        assert_eq!(
+            parse(&format!("x = {}", written)).unwrap()[0].value,
+            entries[6].value
+        );
+    }
+
+    #[test]
+    fn malformed_files_give_the_line_and_column() {
+        assert_eq!(
+            error("[run]\nthreads = 4.5\n"),
+            (
+                2,
+                11,
+                "expected a string, an integer, a boolean or an array".to_string()
+            )
+        );
+        assert_eq!(
+            error("a = \"open\n"),
+            (1, 5, "the string isn't closed".to_string())
+        );
+        assert_eq!(
+            error("a = 1 2\n"),
+            (1, 7, "expected the end of the line".to_string())
+        );
+        assert_eq!(error("= 1\n"), (1, 1, "expected a key".to_string()));
+        assert_eq!(error("a 1\n"), (1, 3, "expected '='".to_string()));
+        assert_eq!(
+            error("run.threads = 1\n"),
+            (1, 4, "dotted keys aren't supported".to_string())
+        );
+        assert_eq!(
+            error("a = 1\na = 2\n"),
+            (2, 1, "a is set twice".to_string())
+        );
+        assert_eq!(
+            error("[a]\n[b]\n  [a]\n"),
+            (3, 4, "the table [a] is given twice".to_string())
+        );
+        assert_eq!(
+            error("a = [1,\n2]\n"),
+            (
+                1,
+                8,
+                "arrays over several lines aren't supported".to_string()
+            )
+        );
+        assert_eq!(
+            error("a = {b = 1}\n"),
+            (1, 5, "inline tables aren't supported".to_string())
+        );
+        assert_eq!(
+            error("a = 012\n"),
+            (1, 5, "integers can't have leading zeros".to_string())
+        );
+        assert_eq!(error("a = \"\\q\"\n"), (1, 7, "invalid escape".to_string()));
+    }
+}
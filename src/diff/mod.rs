@@ -0,0 +1,209 @@
+// Compares two packages without unpacking either, for telling what a
+// republished scene layer changed. Entries are matched by name, and an entry
+// in both is unchanged when its CRC and size in the zip directory are. Most
+// exporters stamp each gzip stream with the time it was written, so the
+// gzipped entries of a republished layer can differ in their CRCs alone.
+// Those are decompressed from both packages and compared by content, which
+// only costs a read of the entries whose CRCs differ.
+
+use crate::archive;
+use failure::Error;
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// An entry whose content differs between the packages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModifiedEntry {
+    /// The name of the entry.
+    pub name: String,
+    /// Its size in the left package.
+    pub left_size: u64,
+    /// Its size in the right package.
+    pub right_size: u64,
+}
+
+/// How the entries of two packages differ.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffReport {
+    /// Entries only the right package holds, in the order of their names.
+    pub added: Vec<String>,
+    /// Entries only the left package holds, in the order of their names.
+    pub removed: Vec<String>,
+    /// Entries whose content differs, in the order of their names.
+    pub modified: Vec<ModifiedEntry>,
+    /// Entries in both packages whose content is the same.
+    pub unchanged: usize,
+    /// Unchanged gzipped entries whose gzip streams differ, such as in the
+    /// time in their header.
+    pub regzipped: usize,
+}
+
+impl DiffReport {
+    /// Whether the packages hold the same entries with the same content.
+    pub fn identical(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// The CRC and size of each entry, by name. Later entries of the same name
+/// win, as they do for the zip library.
+fn entry_crcs<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<HashMap<String, (u32, u64)>, Error> {
+    let mut crcs = HashMap::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        crcs.insert(entry.name().to_string(), (entry.crc32(), entry.size()));
+    }
+    Ok(crcs)
+}
+
+/// The CRC and length of an entry once decompressed, or `None` when it
+/// isn't a gzip stream.
+fn content_crc<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<(u32, u64)>, Error> {
+    let mut decoder = GzDecoder::new(archive.by_name(name)?);
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut length = 0;
+    loop {
+        match decoder.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                length += n as u64;
+            }
+            Err(_) => return Ok(None),
+        }
+    }
+    Ok(Some((hasher.finalize(), length)))
+}
+
+/// Compares the entries of the package at `left_path` with those of the one
+/// at `right_path`.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::diff;
+/// use std::path::Path;
+///
+/// let package = Path::new("tests/fixtures/building.slpk");
+/// let report = diff::diff(package, package)?;
+/// assert!(report.identical());
+/// assert_eq!(report.unchanged, 13);
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn diff(left_path: &Path, right_path: &Path) -> Result<DiffReport, Error> {
+    let mut left = archive::open_slpk_archive(left_path)?;
+    let mut right = archive::open_slpk_archive(right_path)?;
+    let left_crcs = entry_crcs(&mut left)?;
+    let right_crcs = entry_crcs(&mut right)?;
+
+    let mut report = DiffReport::default();
+    for (name, &(left_crc, left_size)) in &left_crcs {
+        let (right_crc, right_size) = match right_crcs.get(name) {
+            Some(&right_entry) => right_entry,
+            None => {
+                report.removed.push(name.clone());
+                continue;
+            }
+        };
+        if (left_crc, left_size) == (right_crc, right_size) {
+            report.unchanged += 1;
+            continue;
+        }
+        if name.ends_with(".gz") {
+            let left_content = content_crc(&mut left, name)?;
+            if left_content.is_some() && left_content == content_crc(&mut right, name)? {
+                report.unchanged += 1;
+                report.regzipped += 1;
+                continue;
+            }
+        }
+        report.modified.push(ModifiedEntry {
+            name: name.clone(),
+            left_size,
+            right_size,
+        });
+    }
+    report.added = right_crcs
+        .keys()
+        .filter(|name| !left_crcs.contains_key(*name))
+        .cloned()
+        .collect();
+    report.added.sort();
+    report.removed.sort();
+    report.modified.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use flate2::{Compression, GzBuilder};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// A gzip stream of `contents`, stamped with `mtime`.
+    fn gzipped(contents: &[u8], mtime: u32) -> Vec<u8> {
+        let mut encoder = GzBuilder::new()
+            .mtime(mtime)
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(contents).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn package(path: &Path, entries: &[(&str, Vec<u8>)]) {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in entries {
+            writer.start_file(*name, stored).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[test]
+    fn entries_are_compared_by_content() {
+        let dir = TempDir::new("diff");
+        let (left, right) = (dir.join("old.slpk"), dir.join("new.slpk"));
+        package(
+            &left,
+            &[
+                ("3dSceneLayer.json.gz", gzipped(b"{\"id\":0}", 1)),
+                ("nodes/0/geometries/0.bin.gz", gzipped(b"old geometry", 1)),
+                ("nodes/0/textures/0.jpg", b"jpeg".to_vec()),
+                ("nodes/1/textures/0.jpg", b"jpeg".to_vec()),
+            ],
+        );
+        package(
+            &right,
+            &[
+                ("3dSceneLayer.json.gz", gzipped(b"{\"id\":0}", 2)),
+                ("nodes/0/geometries/0.bin.gz", gzipped(b"new geometry!", 2)),
+                ("nodes/0/textures/0.jpg", b"jpeg".to_vec()),
+                ("nodes/2/textures/0.jpg", b"jpeg".to_vec()),
+            ],
+        );
+        let report = diff(&left, &right).unwrap();
+        let same = diff(&left, &left).unwrap();
+
+        assert_eq!(report.added, vec!["nodes/2/textures/0.jpg"]);
+        assert_eq!(report.removed, vec!["nodes/1/textures/0.jpg"]);
+        assert_eq!(report.modified.len(), 1);
+        assert_eq!(report.modified[0].name, "nodes/0/geometries/0.bin.gz");
+        // The layer document only differs in the time in its gzip header.
+        assert_eq!((report.unchanged, report.regzipped), (2, 1));
+        assert!(!report.identical());
+        assert!(same.identical());
+        assert_eq!((same.unchanged, same.regzipped), (4, 0));
+    }
+}
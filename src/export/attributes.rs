@@ -0,0 +1,348 @@
+// Decodes the attribute buffers of a node, `attributes/<key>/0.bin`, by the
+// layout `attributeStorageInfo` declares for each key: a header of counts,
+// then the arrays `ordering` names, one element per feature. Numeric arrays
+// start at a multiple of the size of their values, so an array of Float64
+// values after a four byte header is preceded by four bytes of padding.
+// Strings are stored as an array of byte counts followed by the strings
+// themselves, each with its terminating zero counted. Nulls are a string of
+// no bytes at all, NaN, or for integers the value the specification reserves
+// for them: the minimum of a signed type and the maximum of an unsigned one.
+
+use crate::json::Value;
+use byteorder::{ByteOrder, LittleEndian};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+    String,
+}
+
+impl ValueType {
+    fn of_name(name: &str) -> Option<ValueType> {
+        Some(match name {
+            "Int8" => ValueType::Int8,
+            "UInt8" => ValueType::UInt8,
+            "Int16" => ValueType::Int16,
+            "UInt16" => ValueType::UInt16,
+            "Int32" => ValueType::Int32,
+            "UInt32" | "Oid32" => ValueType::UInt32,
+            "Int64" => ValueType::Int64,
+            "UInt64" | "Oid64" => ValueType::UInt64,
+            "Float32" => ValueType::Float32,
+            "Float64" => ValueType::Float64,
+            "String" => ValueType::String,
+            _ => return None,
+        })
+    }
+
+    /// The size of a value, or 1 for the bytes of a string.
+    fn size(self) -> usize {
+        match self {
+            ValueType::Int8 | ValueType::UInt8 | ValueType::String => 1,
+            ValueType::Int16 | ValueType::UInt16 => 2,
+            ValueType::Int32 | ValueType::UInt32 | ValueType::Float32 => 4,
+            ValueType::Int64 | ValueType::UInt64 | ValueType::Float64 => 8,
+        }
+    }
+
+    /// The value at the start of `bytes` as an integer, for the counts of
+    /// headers and the byte counts of strings.
+    fn count(self, bytes: &[u8]) -> u64 {
+        match self {
+            ValueType::Int8 | ValueType::UInt8 => u64::from(bytes[0]),
+            ValueType::Int16 | ValueType::UInt16 => u64::from(LittleEndian::read_u16(bytes)),
+            ValueType::Int32 | ValueType::UInt32 => u64::from(LittleEndian::read_u32(bytes)),
+            ValueType::Int64 | ValueType::UInt64 => LittleEndian::read_u64(bytes),
+            _ => 0,
+        }
+    }
+
+    /// The value at the start of `bytes` as a cell, or `None` when it is
+    /// null.
+    fn cell(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            ValueType::Int8 => Some(bytes[0] as i8)
+                .filter(|&v| v != i8::MIN)
+                .map(|v| v.to_string()),
+            ValueType::UInt8 => Some(bytes[0])
+                .filter(|&v| v != u8::MAX)
+                .map(|v| v.to_string()),
+            ValueType::Int16 => Some(LittleEndian::read_i16(bytes))
+                .filter(|&v| v != i16::MIN)
+                .map(|v| v.to_string()),
+            ValueType::UInt16 => Some(LittleEndian::read_u16(bytes))
+                .filter(|&v| v != u16::MAX)
+                .map(|v| v.to_string()),
+            ValueType::Int32 => Some(LittleEndian::read_i32(bytes))
+                .filter(|&v| v != i32::MIN)
+                .map(|v| v.to_string()),
+            ValueType::UInt32 => Some(LittleEndian::read_u32(bytes))
+                .filter(|&v| v != u32::MAX)
+                .map(|v| v.to_string()),
+            ValueType::Int64 => Some(LittleEndian::read_i64(bytes))
+                .filter(|&v| v != i64::MIN)
+                .map(|v| v.to_string()),
+            ValueType::UInt64 => Some(LittleEndian::read_u64(bytes))
+                .filter(|&v| v != u64::MAX)
+                .map(|v| v.to_string()),
+            ValueType::Float32 => Some(LittleEndian::read_f32(bytes))
+                .filter(|v| !v.is_nan())
+                .map(|v| v.to_string()),
+            ValueType::Float64 => Some(LittleEndian::read_f64(bytes))
+                .filter(|v| !v.is_nan())
+                .map(|v| v.to_string()),
+            ValueType::String => None,
+        }
+    }
+}
+
+/// An array of the buffer, as `ordering` names it.
+#[derive(Clone, Debug, PartialEq)]
+struct ArrayLayout {
+    name: String,
+    value_type: ValueType,
+    values_per_element: usize,
+}
+
+/// How the values of one attribute are laid out, from its entry in
+/// `attributeStorageInfo`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributeLayout {
+    pub key: String,
+    pub name: String,
+    header: Vec<(String, ValueType)>,
+    arrays: Vec<ArrayLayout>,
+}
+
+impl AttributeLayout {
+    /// The layout of each attribute of a layer, in the order of
+    /// `attributeStorageInfo`. Attributes whose layout can't be read are
+    /// left out, along with the reason.
+    pub fn of_layer(document: &Value) -> (Vec<AttributeLayout>, Vec<(String, String)>) {
+        let mut layouts = Vec::new();
+        let mut unreadable = Vec::new();
+        let storage = document
+            .get("attributeStorageInfo")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for info in storage {
+            let key = match info.get("key").and_then(Value::as_str) {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+            match AttributeLayout::of_storage_info(&key, info) {
+                Ok(layout) => layouts.push(layout),
+                Err(problem) => unreadable.push((key, problem)),
+            }
+        }
+        (layouts, unreadable)
+    }
+
+    fn of_storage_info(key: &str, info: &Value) -> Result<AttributeLayout, String> {
+        let value_type = |member: &Value| {
+            let name = member
+                .get("valueType")
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            ValueType::of_name(name).ok_or_else(|| format!("value type '{}' is unknown", name))
+        };
+        let mut header = Vec::new();
+        for property in info
+            .get("header")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = property
+                .get("property")
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            header.push((name.to_string(), value_type(property)?));
+        }
+        let mut arrays = Vec::new();
+        for name in info
+            .get("ordering")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = name.as_str().unwrap_or("");
+            let member = info
+                .get(name)
+                .ok_or_else(|| format!("'{}' is in ordering but isn't declared", name))?;
+            arrays.push(ArrayLayout {
+                name: name.to_string(),
+                value_type: value_type(member)?,
+                values_per_element: member
+                    .get("valuesPerElement")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(1)
+                    .max(1) as usize,
+            });
+        }
+        if !arrays.iter().any(|array| array.name == "attributeValues") {
+            return Err("ordering has no attributeValues".to_string());
+        }
+        Ok(AttributeLayout {
+            key: key.to_string(),
+            name: info
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or(key)
+                .to_string(),
+            header,
+            arrays,
+        })
+    }
+
+    /// The value of each feature in `buffer`, with `None` for nulls.
+    pub fn decode(&self, buffer: &[u8]) -> Result<Vec<Option<String>>, String> {
+        let truncated = || format!("the buffer ends early, after {} bytes", buffer.len());
+        let mut offset = 0;
+        let mut count = None;
+        for (property, value_type) in &self.header {
+            let bytes = buffer
+                .get(offset..offset + value_type.size())
+                .ok_or_else(truncated)?;
+            if property == "count" {
+                count = Some(value_type.count(bytes) as usize);
+            }
+            offset += value_type.size();
+        }
+        let count = count.ok_or("the header has no count")?;
+
+        let mut byte_counts = Vec::new();
+        let mut cells = Vec::new();
+        for array in &self.arrays {
+            let size = array.value_type.size();
+            if array.name == "attributeValues" && array.value_type == ValueType::String {
+                if byte_counts.len() != count {
+                    return Err("the strings have no attributeByteCounts".to_string());
+                }
+                for &length in &byte_counts {
+                    let bytes = buffer.get(offset..offset + length).ok_or_else(truncated)?;
+                    offset += length;
+                    // A string of no bytes is null, where an empty one
+                    // still has its terminating zero.
+                    cells.push(if length == 0 {
+                        None
+                    } else {
+                        let text = String::from_utf8_lossy(bytes);
+                        Some(text.trim_end_matches('\0').to_string())
+                    });
+                }
+                continue;
+            }
+
+            offset = offset.next_multiple_of(size);
+            let len = count
+                .checked_mul(size)
+                .and_then(|len| len.checked_mul(array.values_per_element))
+                .ok_or_else(truncated)?;
+            let values = buffer.get(offset..offset + len).ok_or_else(truncated)?;
+            offset += len;
+            match array.name.as_str() {
+                "attributeByteCounts" => {
+                    byte_counts = values
+                        .chunks(size * array.values_per_element)
+                        .map(|element| array.value_type.count(element) as usize)
+                        .collect();
+                }
+                "attributeValues" => {
+                    for element in values.chunks(size * array.values_per_element) {
+                        let parts: Vec<Option<String>> = element
+                            .chunks(size)
+                            .map(|value| array.value_type.cell(value))
+                            .collect();
+                        cells.push(if parts.iter().all(Option::is_none) {
+                            None
+                        } else {
+                            let parts: Vec<String> =
+                                parts.into_iter().map(Option::unwrap_or_default).collect();
+                            Some(parts.join(" "))
+                        });
+                    }
+                }
+                // Arrays such as the object ids some exporters add are
+                // skipped over.
+                _ => {}
+            }
+        }
+        Ok(cells)
+    }
+}
+
+/// A cell of a CSV file, quoted when it holds a separator, a quote or a
+/// line break.
+pub fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn decodes_numbers_and_strings_with_their_nulls() {
+        let document = json::parse_bytes(
+            br#"{"attributeStorageInfo":[
+                {"key":"f_0","name":"height",
+                 "header":[{"property":"count","valueType":"UInt32"}],
+                 "ordering":["attributeValues"],
+                 "attributeValues":{"valueType":"Float64","valuesPerElement":1}},
+                {"key":"f_1","name":"name",
+                 "header":[{"property":"count","valueType":"UInt32"},
+                           {"property":"attributeValuesByteCount","valueType":"UInt32"}],
+                 "ordering":["attributeByteCounts","attributeValues"],
+                 "attributeByteCounts":{"valueType":"UInt32","valuesPerElement":1},
+                 "attributeValues":{"valueType":"String","encoding":"UTF-8"}},
+                {"key":"f_2","ordering":["attributeValues"],
+                 "attributeValues":{"valueType":"Decimal"}}]}"#,
+        )
+        .unwrap();
+        let (layouts, unreadable) = AttributeLayout::of_layer(&document);
+        assert_eq!(
+            unreadable,
+            vec![(
+                "f_2".to_string(),
+                "value type 'Decimal' is unknown".to_string()
+            )]
+        );
+
+        // Two doubles after four bytes of padding.
+        let mut heights = vec![2, 0, 0, 0, 0, 0, 0, 0];
+        heights.extend_from_slice(&12.5f64.to_le_bytes());
+        heights.extend_from_slice(&f64::NAN.to_le_bytes());
+        assert_eq!(
+            layouts[0].decode(&heights).unwrap(),
+            vec![Some("12.5".to_string()), None]
+        );
+
+        let mut names = vec![3, 0, 0, 0, 10, 0, 0, 0];
+        for length in &[9u32, 1, 0] {
+            names.extend_from_slice(&length.to_le_bytes());
+        }
+        names.extend_from_slice(b"Caf\xc3\xa9, 1\0\0");
+        assert_eq!(
+            layouts[1].decode(&names).unwrap(),
+            vec![Some("Café, 1".to_string()), Some(String::new()), None]
+        );
+        assert_eq!(csv_field("Café, 1"), "\"Café, 1\"");
+        assert!(layouts[1].decode(&names[..20]).is_err());
+    }
+}
@@ -0,0 +1,559 @@
+// Decodes the uncompressed geometry buffers of I3S meshes. A buffer is a
+// header giving the vertex and feature counts, then each vertex attribute
+// for every vertex in turn, then each feature attribute for every feature.
+// Triangles are not indexed: every three vertices make one. Older layers
+// declare the layout in the `defaultGeometrySchema` of their layer document,
+// with attributes in the order it lists them. Layers with node pages declare
+// it in a geometry buffer of `geometryDefinitions`, with attributes in the
+// order the specification fixes and as many header bytes as its `offset`.
+
+use super::ExportError;
+use crate::json::Value;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The attributes of a buffer described by a geometry buffer of the node
+/// pages, in the order they are stored.
+const VERTEX_ATTRIBUTES: [&str; 5] = ["position", "normal", "uv0", "color", "uvRegion"];
+const FEATURE_ATTRIBUTES: [&str; 2] = ["featureId", "faceRange"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl ValueType {
+    fn parse(name: &str) -> Option<ValueType> {
+        Some(match name {
+            "Int8" => ValueType::Int8,
+            "UInt8" => ValueType::UInt8,
+            "Int16" => ValueType::Int16,
+            "UInt16" => ValueType::UInt16,
+            "Int32" => ValueType::Int32,
+            "UInt32" => ValueType::UInt32,
+            "Int64" => ValueType::Int64,
+            "UInt64" => ValueType::UInt64,
+            "Float32" => ValueType::Float32,
+            "Float64" => ValueType::Float64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ValueType::Int8 | ValueType::UInt8 => 1,
+            ValueType::Int16 | ValueType::UInt16 => 2,
+            ValueType::Int32 | ValueType::UInt32 | ValueType::Float32 => 4,
+            ValueType::Int64 | ValueType::UInt64 | ValueType::Float64 => 8,
+        }
+    }
+
+    fn read(self, bytes: &[u8]) -> f64 {
+        match self {
+            ValueType::Int8 => f64::from(bytes[0] as i8),
+            ValueType::UInt8 => f64::from(bytes[0]),
+            ValueType::Int16 => f64::from(LittleEndian::read_i16(bytes)),
+            ValueType::UInt16 => f64::from(LittleEndian::read_u16(bytes)),
+            ValueType::Int32 => f64::from(LittleEndian::read_i32(bytes)),
+            ValueType::UInt32 => f64::from(LittleEndian::read_u32(bytes)),
+            ValueType::Int64 => LittleEndian::read_i64(bytes) as f64,
+            ValueType::UInt64 => LittleEndian::read_u64(bytes) as f64,
+            ValueType::Float32 => f64::from(LittleEndian::read_f32(bytes)),
+            ValueType::Float64 => LittleEndian::read_f64(bytes),
+        }
+    }
+
+    /// Reads an integer exactly, which `read` can't for 64 bit ids.
+    fn read_u64(self, bytes: &[u8]) -> u64 {
+        match self {
+            ValueType::UInt64 | ValueType::Int64 => LittleEndian::read_u64(bytes),
+            _ => self.read(bytes) as u64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Attribute {
+    name: String,
+    value_type: ValueType,
+    components: usize,
+}
+
+impl Attribute {
+    fn element_size(&self) -> usize {
+        self.value_type.size() * self.components
+    }
+}
+
+/// How the vertex and feature counts are stored at the start of a buffer.
+#[derive(Clone, Debug, PartialEq)]
+enum Header {
+    /// The properties of `defaultGeometrySchema`, in order.
+    Properties(Vec<(String, ValueType)>),
+    /// Two UInt32 counts, vertices then features, padded to this many bytes.
+    Counts(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeometryLayout {
+    header: Header,
+    vertex_attributes: Vec<Attribute>,
+    feature_attributes: Vec<Attribute>,
+}
+
+/// The type and number of components of an attribute, as either kind of
+/// declaration names them.
+fn declared_attribute(name: &str, declaration: &Value) -> Result<Attribute, ExportError> {
+    let value_type = declaration
+        .get("valueType")
+        .or_else(|| declaration.get("type"))
+        .and_then(Value::as_str);
+    let components = declaration
+        .get("valuesPerElement")
+        .or_else(|| declaration.get("component"))
+        .and_then(Value::as_u64);
+    match (value_type.and_then(ValueType::parse), components) {
+        (Some(value_type), Some(components)) if components > 0 => Ok(Attribute {
+            name: name.to_string(),
+            value_type,
+            components: components as usize,
+        }),
+        _ => Err(ExportError::UnsupportedGeometry(format!(
+            "the {} attribute is declared with an unknown type",
+            name
+        ))),
+    }
+}
+
+impl GeometryLayout {
+    /// The layout of the `defaultGeometrySchema` of an older layer.
+    pub fn of_schema(schema: &Value) -> Result<GeometryLayout, ExportError> {
+        for (member, expected) in &[
+            ("geometryType", "triangles"),
+            ("topology", "PerAttributeArray"),
+        ] {
+            match schema.get(member).and_then(Value::as_str) {
+                Some(value) if value != *expected => {
+                    return Err(ExportError::UnsupportedGeometry(format!(
+                        "its {} is {}",
+                        member, value
+                    )))
+                }
+                _ => {}
+            }
+        }
+        let mut properties = Vec::new();
+        for property in schema
+            .get("header")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = property.get("property").and_then(Value::as_str);
+            let value_type = property
+                .get("type")
+                .and_then(Value::as_str)
+                .and_then(ValueType::parse);
+            match (name, value_type) {
+                (Some(name), Some(value_type)) => properties.push((name.to_string(), value_type)),
+                _ => {
+                    return Err(ExportError::UnsupportedGeometry(
+                        "its header is declared with an unknown type".to_string(),
+                    ))
+                }
+            }
+        }
+        let ordered = |order: &str, declarations: &str| -> Result<Vec<Attribute>, ExportError> {
+            let mut attributes = Vec::new();
+            for name in schema
+                .get(order)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                match schema.get(declarations).and_then(|d| d.get(name)) {
+                    Some(declaration) => attributes.push(declared_attribute(name, declaration)?),
+                    None => {
+                        return Err(ExportError::UnsupportedGeometry(format!(
+                            "the {} attribute isn't declared",
+                            name
+                        )))
+                    }
+                }
+            }
+            Ok(attributes)
+        };
+        Ok(GeometryLayout {
+            header: Header::Properties(properties),
+            vertex_attributes: ordered("ordering", "vertexAttributes")?,
+            feature_attributes: ordered("featureAttributeOrder", "featureAttributes")?,
+        })
+    }
+
+    /// The layout of a geometry buffer of `geometryDefinitions`, which
+    /// fails with `Compressed` for a compressed buffer.
+    pub fn of_buffer(buffer: &Value) -> Result<GeometryLayout, ExportError> {
+        if let Some(compressed) = buffer.get("compressedAttributes") {
+            let encoding = compressed.get("encoding").and_then(Value::as_str);
+            return Err(ExportError::Compressed(
+                encoding.unwrap_or("an unknown encoding").to_string(),
+            ));
+        }
+        let declared = |names: &[&str]| -> Result<Vec<Attribute>, ExportError> {
+            names
+                .iter()
+                .filter_map(|name| buffer.get(name).map(|d| declared_attribute(name, d)))
+                .collect()
+        };
+        Ok(GeometryLayout {
+            header: Header::Counts(
+                buffer.get("offset").and_then(Value::as_u64).unwrap_or(8) as usize
+            ),
+            vertex_attributes: declared(&VERTEX_ATTRIBUTES)?,
+            feature_attributes: declared(&FEATURE_ATTRIBUTES)?,
+        })
+    }
+
+    pub fn decode(&self, buffer: &[u8]) -> Result<Mesh, ExportError> {
+        let truncated = || ExportError::TruncatedGeometry(buffer.len());
+        let (vertex_count, feature_count, mut offset) = match &self.header {
+            Header::Counts(size) => {
+                if buffer.len() < (*size).max(8) {
+                    return Err(truncated());
+                }
+                (
+                    LittleEndian::read_u32(&buffer[0..4]) as usize,
+                    LittleEndian::read_u32(&buffer[4..8]) as usize,
+                    *size,
+                )
+            }
+            Header::Properties(properties) => {
+                let (mut vertices, mut features, mut offset) = (None, 0, 0);
+                for (name, value_type) in properties {
+                    let bytes = buffer
+                        .get(offset..offset + value_type.size())
+                        .ok_or_else(truncated)?;
+                    match name.as_str() {
+                        "vertexCount" => vertices = Some(value_type.read_u64(bytes) as usize),
+                        "featureCount" => features = value_type.read_u64(bytes) as usize,
+                        _ => {}
+                    }
+                    offset += value_type.size();
+                }
+                let vertices = vertices.ok_or_else(|| {
+                    ExportError::UnsupportedGeometry("its header has no vertexCount".to_string())
+                })?;
+                (vertices, features, offset)
+            }
+        };
+
+        let mut mesh = Mesh::default();
+        for (attributes, count) in &[
+            (&self.vertex_attributes, vertex_count),
+            (&self.feature_attributes, feature_count),
+        ] {
+            for attribute in attributes.iter() {
+                let size = count
+                    .checked_mul(attribute.element_size())
+                    .ok_or_else(truncated)?;
+                let values = buffer.get(offset..offset + size).ok_or_else(truncated)?;
+                offset += size;
+                mesh.add_attribute(attribute, values);
+            }
+        }
+        mesh.features.truncate(feature_count);
+        Ok(mesh)
+    }
+}
+
+/// A feature of a mesh, and the faces which draw it, first to last.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feature {
+    pub id: u64,
+    pub faces: (u32, u32),
+}
+
+/// The vertex attributes of a mesh which can be exported. Attributes the
+/// buffer doesn't hold are left empty.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// RGBA, with opaque alpha when the buffer only holds RGB.
+    pub colors: Vec<[u8; 4]>,
+    /// The rectangle of a texture atlas each vertex is textured from, as
+    /// its least u and v and its greatest, out of 65535.
+    pub uv_regions: Vec<[u16; 4]>,
+    pub features: Vec<Feature>,
+}
+
+impl Mesh {
+    fn add_attribute(&mut self, attribute: &Attribute, values: &[u8]) {
+        let size = attribute.value_type.size();
+        let elements = values.chunks(attribute.element_size()).map(|element| {
+            element
+                .chunks(size)
+                .map(|bytes| attribute.value_type.read(bytes))
+                .collect::<Vec<f64>>()
+        });
+        let components = attribute.components;
+        match attribute.name.as_str() {
+            "position" if components == 3 => self
+                .positions
+                .extend(elements.map(|e| [e[0] as f32, e[1] as f32, e[2] as f32])),
+            "normal" if components == 3 => self
+                .normals
+                .extend(elements.map(|e| [e[0] as f32, e[1] as f32, e[2] as f32])),
+            "uv0" if components == 2 => self
+                .uvs
+                .extend(elements.map(|e| [e[0] as f32, e[1] as f32])),
+            "uvRegion" | "region" if components == 4 => self
+                .uv_regions
+                .extend(elements.map(|e| [e[0] as u16, e[1] as u16, e[2] as u16, e[3] as u16])),
+            "color" if components == 3 || components == 4 => {
+                self.colors.extend(elements.map(|e| {
+                    let alpha = e.get(3).copied().unwrap_or(255.0);
+                    [e[0] as u8, e[1] as u8, e[2] as u8, alpha as u8]
+                }))
+            }
+            "id" | "featureId" => {
+                let ids = values
+                    .chunks(attribute.element_size())
+                    .map(|element| attribute.value_type.read_u64(element));
+                if self.features.is_empty() {
+                    self.features
+                        .extend(ids.map(|id| Feature { id, faces: (0, 0) }));
+                } else {
+                    for (feature, id) in self.features.iter_mut().zip(ids) {
+                        feature.id = id;
+                    }
+                }
+            }
+            "faceRange" if components == 2 => {
+                let ranges = elements.map(|e| (e[0] as u32, e[1] as u32));
+                if self.features.is_empty() {
+                    self.features
+                        .extend(ranges.map(|faces| Feature { id: 0, faces }));
+                } else {
+                    for (feature, faces) in self.features.iter_mut().zip(ranges) {
+                        feature.faces = faces;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The number of whole triangles.
+    pub fn triangles(&self) -> usize {
+        self.positions.len() / 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    fn layout_of_buffer(declaration: &str) -> Result<GeometryLayout, ExportError> {
+        GeometryLayout::of_buffer(&json::parse(declaration).unwrap())
+    }
+
+    fn layout_of_schema(schema: &str) -> Result<GeometryLayout, ExportError> {
+        GeometryLayout::of_schema(&json::parse(schema).unwrap())
+    }
+
+    fn bytes<T: Copy, const N: usize>(values: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+        values.iter().flat_map(|value| to_bytes(*value)).collect()
+    }
+
+    #[test]
+    fn buffers_of_the_node_pages_are_decoded_in_the_fixed_order() {
+        // Declared out of order, stored as the specification orders them,
+        // past a header padded to 12 bytes.
+        let layout = layout_of_buffer(
+            r#"{"offset":12,
+                "faceRange":{"type":"UInt32","component":2},
+                "color":{"type":"UInt8","component":3},
+                "normal":{"type":"Float32","component":3},
+                "position":{"type":"Float32","component":3},
+                "featureId":{"type":"UInt64","component":1}}"#,
+        )
+        .unwrap();
+        let mut buffer = bytes(&[3u32, 1, 0xdead_beef], u32::to_le_bytes);
+        buffer.extend(bytes(
+            &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            f32::to_le_bytes,
+        ));
+        buffer.extend(bytes(&[0.0f32, 0.0, 1.0].repeat(3), f32::to_le_bytes));
+        buffer.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        // An id too large for a double to hold exactly.
+        buffer.extend(bytes(&[u64::MAX - 1], u64::to_le_bytes));
+        buffer.extend(bytes(&[0u32, 0], u32::to_le_bytes));
+        let mesh = layout.decode(&buffer).unwrap();
+
+        assert_eq!(
+            mesh.positions,
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(mesh.normals, [[0.0, 0.0, 1.0]; 3]);
+        assert!(mesh.uvs.is_empty());
+        // RGB colors are made opaque.
+        assert_eq!(
+            mesh.colors,
+            [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+        );
+        assert_eq!(
+            mesh.features,
+            [Feature {
+                id: u64::MAX - 1,
+                faces: (0, 0)
+            }]
+        );
+        assert_eq!(mesh.triangles(), 1);
+    }
+
+    #[test]
+    fn schemas_give_the_header_and_the_order_of_attributes() {
+        // A header holding more than the counts, and attributes stored in
+        // the order the schema lists them, whatever their names.
+        let layout = layout_of_schema(
+            r#"{"geometryType":"triangles","topology":"PerAttributeArray",
+                "header":[{"property":"featureCount","type":"UInt16"},
+                          {"property":"reserved","type":"UInt8"},
+                          {"property":"vertexCount","type":"UInt64"}],
+                "ordering":["uv0","position","region"],
+                "vertexAttributes":{
+                    "position":{"valueType":"Int16","valuesPerElement":3},
+                    "uv0":{"valueType":"Float64","valuesPerElement":2},
+                    "region":{"valueType":"UInt16","valuesPerElement":4}},
+                "featureAttributeOrder":["faceRange","id"],
+                "featureAttributes":{
+                    "id":{"valueType":"UInt32","valuesPerElement":1},
+                    "faceRange":{"valueType":"UInt32","valuesPerElement":2}}}"#,
+        )
+        .unwrap();
+        let mut buffer = 2u16.to_le_bytes().to_vec();
+        buffer.push(0xff);
+        buffer.extend(6u64.to_le_bytes());
+        buffer.extend(bytes(&[0.5f64, 0.25].repeat(6), f64::to_le_bytes));
+        buffer.extend(bytes(
+            &[-1i16, 2, -3, 0, 0, 0, 1, 1, 1, 4, 5, 6, 7, 8, 9, 0, 0, 0],
+            i16::to_le_bytes,
+        ));
+        buffer.extend(bytes(&[1u16, 2, 65534, 65535].repeat(6), u16::to_le_bytes));
+        buffer.extend(bytes(&[0u32, 0, 1, 1], u32::to_le_bytes));
+        buffer.extend(bytes(&[11u32, 12], u32::to_le_bytes));
+        let mesh = layout.decode(&buffer).unwrap();
+
+        assert_eq!(mesh.uvs, [[0.5, 0.25]; 6]);
+        assert_eq!(mesh.positions[0], [-1.0, 2.0, -3.0]);
+        assert_eq!(mesh.positions[4], [7.0, 8.0, 9.0]);
+        assert_eq!(mesh.uv_regions, [[1, 2, 65534, 65535]; 6]);
+        assert_eq!(mesh.triangles(), 2);
+        assert_eq!(
+            mesh.features,
+            [
+                Feature {
+                    id: 11,
+                    faces: (0, 0)
+                },
+                Feature {
+                    id: 12,
+                    faces: (1, 1)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn buffers_shorter_than_their_counts_are_refused() {
+        let layout = layout_of_buffer(
+            r#"{"position":{"type":"Float32","component":3},
+                "featureId":{"type":"UInt64","component":1}}"#,
+        )
+        .unwrap();
+        let truncated = |buffer: &[u8]| match layout.decode(buffer) {
+            Err(ExportError::TruncatedGeometry(len)) => len,
+            other => panic!("{:?}", other),
+        };
+        let mut buffer = bytes(&[3u32, 1], u32::to_le_bytes);
+        buffer.extend(vec![0; 36 + 8]);
+        assert!(layout.decode(&buffer).is_ok());
+        assert_eq!(truncated(&buffer[..40]), 40);
+        assert_eq!(truncated(&buffer[..6]), 6);
+        // Counts claiming far more vertices than any buffer holds.
+        let huge = bytes(&[u32::MAX, u32::MAX], u32::to_le_bytes);
+        assert_eq!(truncated(&huge), 8);
+
+        let padded = layout_of_buffer(r#"{"offset":16}"#).unwrap();
+        assert_eq!(
+            match padded.decode(&[0; 12]) {
+                Err(ExportError::TruncatedGeometry(len)) => len,
+                other => panic!("{:?}", other),
+            },
+            12
+        );
+    }
+
+    #[test]
+    fn layouts_which_cant_be_decoded_are_refused() {
+        let message = |layout: Result<GeometryLayout, ExportError>| match layout {
+            Err(e) => e.to_string(),
+            Ok(layout) => panic!("{:?}", layout),
+        };
+        assert_eq!(
+            message(layout_of_buffer(
+                r#"{"compressedAttributes":{"encoding":"draco"}}"#
+            )),
+            "The geometry of the node is only stored compressed with draco, which can't be exported"
+        );
+        assert_eq!(
+            message(layout_of_buffer(
+                r#"{"position":{"type":"Float16","component":3}}"#
+            )),
+            "The geometry of the node can't be exported: the position attribute is declared with an unknown type"
+        );
+        assert_eq!(
+            message(layout_of_buffer(
+                r#"{"normal":{"type":"Float32","component":0}}"#
+            )),
+            "The geometry of the node can't be exported: the normal attribute is declared with an unknown type"
+        );
+        assert_eq!(
+            message(layout_of_schema(r#"{"geometryType":"lines"}"#)),
+            "The geometry of the node can't be exported: its geometryType is lines"
+        );
+        assert_eq!(
+            message(layout_of_schema(r#"{"topology":"InterleavedArray"}"#)),
+            "The geometry of the node can't be exported: its topology is InterleavedArray"
+        );
+        assert_eq!(
+            message(layout_of_schema(
+                r#"{"header":[{"property":"vertexCount","type":"Int128"}]}"#
+            )),
+            "The geometry of the node can't be exported: its header is declared with an unknown type"
+        );
+        assert_eq!(
+            message(layout_of_schema(
+                r#"{"ordering":["position"],"vertexAttributes":{}}"#
+            )),
+            "The geometry of the node can't be exported: the position attribute isn't declared"
+        );
+        let no_count = layout_of_schema(r#"{"header":[]}"#).unwrap();
+        assert_eq!(
+            no_count.decode(&[]).unwrap_err().to_string(),
+            "The geometry of the node can't be exported: its header has no vertexCount"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// Writes a mesh as glTF 2.0: a `.gltf` document, the vertex data in a
+// `.bin` buffer beside it, and the texture when there is one, all referred
+// to by file name. The mesh is a single primitive of unindexed triangles,
+// with a material only when it has a texture. I3S has the z axis up where
+// glTF has the y axis up, so the node holding the mesh is rotated rather
+// than every position being changed. A mesh whose positions are offsets
+// from an origin is moved there by the node too, so that the positions keep
+// the precision of small numbers.
+
+use super::geometry::Mesh;
+use crate::json::Value;
+
+const FLOAT: u64 = 5126;
+const UNSIGNED_BYTE: u64 = 5121;
+const ARRAY_BUFFER: u64 = 34962;
+const TRIANGLES: u64 = 4;
+
+/// A quarter turn about the x axis, from z up to y up.
+const Z_UP_TO_Y_UP: [f64; 4] = [
+    -std::f64::consts::FRAC_1_SQRT_2,
+    0.0,
+    0.0,
+    std::f64::consts::FRAC_1_SQRT_2,
+];
+
+fn object(members: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+fn numbers(values: &[f64]) -> Value {
+    Value::Array(values.iter().map(|&value| Value::from(value)).collect())
+}
+
+/// The accessors, buffer views and buffer of each vertex attribute, as
+/// they are added.
+#[derive(Default)]
+struct Buffers {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+    attributes: Vec<(String, Value)>,
+}
+
+impl Buffers {
+    fn add(&mut self, name: &str, data: Vec<u8>, count: usize, accessor: Vec<(&str, Value)>) {
+        self.views.push(object(vec![
+            ("buffer", Value::from(0u64)),
+            ("byteOffset", Value::from(self.data.len())),
+            ("byteLength", Value::from(data.len())),
+            ("target", Value::from(ARRAY_BUFFER)),
+        ]));
+        let mut members = vec![
+            ("bufferView", Value::from(self.views.len() - 1)),
+            ("count", Value::from(count)),
+        ];
+        members.extend(accessor);
+        self.accessors.push(object(members));
+        self.attributes
+            .push((name.to_string(), Value::from(self.accessors.len() - 1)));
+        // Every element is a multiple of four bytes long, so each view
+        // stays aligned.
+        self.data.extend(data);
+    }
+}
+
+fn floats<const N: usize>(elements: &[[f32; N]]) -> Vec<u8> {
+    elements
+        .iter()
+        .flat_map(|element| element.iter().flat_map(|value| value.to_le_bytes()))
+        .collect()
+}
+
+/// The glTF document for `mesh` and the buffer it refers to by
+/// `buffer_name`, along with the texture by `texture_name`, with the mesh
+/// moved to `origin` when its positions are offsets from one.
+pub fn to_gltf(
+    mesh: &Mesh,
+    node_id: &str,
+    buffer_name: &str,
+    texture_name: Option<&str>,
+    origin: Option<[f64; 3]>,
+) -> (Value, Vec<u8>) {
+    let vertices = mesh.triangles() * 3;
+    let positions = &mesh.positions[..vertices];
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+    for position in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(f64::from(position[axis]));
+            max[axis] = max[axis].max(f64::from(position[axis]));
+        }
+    }
+    if positions.is_empty() {
+        min = [0.0; 3];
+        max = [0.0; 3];
+    }
+
+    let mut buffers = Buffers::default();
+    buffers.add(
+        "POSITION",
+        floats(positions),
+        vertices,
+        vec![
+            ("componentType", Value::from(FLOAT)),
+            ("type", Value::from("VEC3")),
+            ("min", numbers(&min)),
+            ("max", numbers(&max)),
+        ],
+    );
+    if mesh.normals.len() >= vertices {
+        buffers.add(
+            "NORMAL",
+            floats(&mesh.normals[..vertices]),
+            vertices,
+            vec![
+                ("componentType", Value::from(FLOAT)),
+                ("type", Value::from("VEC3")),
+            ],
+        );
+    }
+    if mesh.uvs.len() >= vertices {
+        buffers.add(
+            "TEXCOORD_0",
+            floats(&mesh.uvs[..vertices]),
+            vertices,
+            vec![
+                ("componentType", Value::from(FLOAT)),
+                ("type", Value::from("VEC2")),
+            ],
+        );
+    }
+    if mesh.colors.len() >= vertices {
+        buffers.add(
+            "COLOR_0",
+            mesh.colors[..vertices].iter().flatten().copied().collect(),
+            vertices,
+            vec![
+                ("componentType", Value::from(UNSIGNED_BYTE)),
+                ("normalized", Value::Bool(true)),
+                ("type", Value::from("VEC4")),
+            ],
+        );
+    }
+
+    let mut node = vec![
+        ("name", Value::from(format!("node_{}", node_id))),
+        ("mesh", Value::from(0u64)),
+        ("rotation", numbers(&Z_UP_TO_Y_UP)),
+    ];
+    if let Some([x, y, z]) = origin {
+        // The translation applies after the rotation, so it is y up too.
+        node.push(("translation", numbers(&[x, z, -y])));
+    }
+    let mut primitive = vec![
+        ("attributes", Value::Object(buffers.attributes)),
+        ("mode", Value::from(TRIANGLES)),
+    ];
+    let mut document = vec![
+        (
+            "asset",
+            object(vec![
+                ("version", Value::from("2.0")),
+                ("generator", Value::from("slpkg")),
+            ]),
+        ),
+        ("scene", Value::from(0u64)),
+        (
+            "scenes",
+            Value::Array(vec![object(vec![("nodes", numbers(&[0.0]))])]),
+        ),
+        ("nodes", Value::Array(vec![object(node)])),
+    ];
+    if let Some(texture_name) = texture_name {
+        primitive.push(("material", Value::from(0u64)));
+        document.push((
+            "materials",
+            Value::Array(vec![object(vec![(
+                "pbrMetallicRoughness",
+                object(vec![
+                    (
+                        "baseColorTexture",
+                        object(vec![("index", Value::from(0u64))]),
+                    ),
+                    ("metallicFactor", Value::from(0.0)),
+                ]),
+            )])]),
+        ));
+        document.push((
+            "textures",
+            Value::Array(vec![object(vec![("source", Value::from(0u64))])]),
+        ));
+        document.push((
+            "images",
+            Value::Array(vec![object(vec![("uri", Value::from(texture_name))])]),
+        ));
+    }
+    document.push((
+        "meshes",
+        Value::Array(vec![object(vec![(
+            "primitives",
+            Value::Array(vec![object(primitive)]),
+        )])]),
+    ));
+    document.push(("accessors", Value::Array(buffers.accessors)));
+    document.push(("bufferViews", Value::Array(buffers.views)));
+    document.push((
+        "buffers",
+        Value::Array(vec![object(vec![
+            ("uri", Value::from(buffer_name)),
+            ("byteLength", Value::from(buffers.data.len())),
+        ])]),
+    ));
+    (object(document), buffers.data)
+}
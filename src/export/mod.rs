@@ -0,0 +1,1205 @@
+// Exports the mesh of a single node as a Wavefront OBJ or glTF 2.0 file,
+// for looking at how one node is drawn in any 3D viewer. The node is found
+// as `extract-node` finds it, and its geometry buffer is decoded by the
+// layout its layer declares. Only uncompressed buffers can be decoded: a
+// node whose geometry is only stored compressed with Draco fails rather
+// than being written as garbage. The texture is the first JPEG or PNG of
+// the node's material, and is written beside the mesh. Positions are
+// written as they are stored, as offsets from the center of the node in
+// the units of the layer's spatial reference, unless a target spatial
+// reference is given. They are then reprojected into it, and the glTF node
+// is moved to where the center of the node is in it, while OBJ, which has
+// no way to move a mesh, gets whole positions.
+//
+// The attributes of every node can be exported too, as CSV files with a row
+// per feature and a column per attribute of `attributeStorageInfo`, either a
+// file per node or a single file with a column for the node.
+
+pub(crate) mod attributes;
+pub(crate) mod geometry;
+mod gltf;
+mod obj;
+mod reproject;
+
+pub use self::reproject::TargetCrs;
+
+use self::attributes::{csv_field, AttributeLayout};
+use self::geometry::{GeometryLayout, Mesh};
+use self::reproject::Reprojection;
+
+use crate::archive;
+use crate::extract;
+use crate::i3s;
+use crate::i3s::{BoundingVolume, SceneLayer};
+use crate::json::Value;
+use failure::Error;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use zip::ZipArchive;
+
+/// Why a node or its attributes can't be exported.
+#[derive(Debug, Fail)]
+pub enum ExportError {
+    /// The format named isn't obj or gltf.
+    #[fail(display = "Unknown export format '{}', expected obj or gltf", _0)]
+    UnknownFormat(String),
+
+    /// No format was named, and the output file isn't named .obj or .gltf.
+    #[fail(
+        display = "Can't tell which format to export {} in from its extension, expected .obj or .gltf",
+        _0
+    )]
+    FormatOfPath(String),
+
+    /// The node has no geometry to export.
+    #[fail(display = "Node {} has no geometry", _0)]
+    NoGeometry(String),
+
+    /// A geometry buffer the node refers to isn't stored.
+    #[fail(display = "The package has no geometry buffer {}", _0)]
+    MissingGeometry(String),
+
+    /// The only geometry of the node is compressed with an encoding such as
+    /// Draco, which can't be decoded.
+    #[fail(
+        display = "The geometry of the node is only stored compressed with {}, which can't be exported",
+        _0
+    )]
+    Compressed(String),
+
+    /// The layout of the geometry buffer can't be decoded.
+    #[fail(display = "The geometry of the node can't be exported: {}", _0)]
+    UnsupportedGeometry(String),
+
+    /// The geometry buffer is shorter than its layout needs.
+    #[fail(display = "The geometry buffer ends early, after {} bytes", _0)]
+    TruncatedGeometry(usize),
+
+    /// The layer has no attributes to export.
+    #[fail(display = "The layer declares no attributeStorageInfo")]
+    NoAttributes,
+
+    /// The target spatial reference isn't ecef, wgs84 or `epsg:<code>`.
+    #[fail(
+        display = "Unknown target spatial reference '{}', expected ecef, wgs84 or epsg:<code>",
+        _0
+    )]
+    UnknownTargetCrs(String),
+
+    /// The target is an EPSG code positions can't be reprojected into.
+    #[fail(
+        display = "Positions can't be reprojected into EPSG:{}, only into ecef, wgs84, Web Mercator or a UTM zone",
+        _0
+    )]
+    UnsupportedTargetCrs(u32),
+
+    /// The spatial reference of the layer is one positions can't be
+    /// reprojected from.
+    #[fail(
+        display = "The spatial reference {} of the layer can't be reprojected from, only WGS84, Web Mercator or a UTM zone can",
+        _0
+    )]
+    UnsupportedSourceCrs(String),
+
+    /// The positions of the node are offsets from the center of a bounding
+    /// volume it doesn't have.
+    #[fail(
+        display = "Node {} has no bounding volume, whose center its positions are offsets from",
+        _0
+    )]
+    NoCenter(String),
+}
+
+/// The file format a node is exported in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// Wavefront OBJ, with an MTL material library for its texture.
+    Obj,
+    /// glTF 2.0, with its buffer in a separate .bin file.
+    Gltf,
+}
+
+impl ExportFormat {
+    /// The format an output file is named for.
+    pub fn of_path(path: &Path) -> Result<ExportFormat, ExportError> {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("obj") => Ok(ExportFormat::Obj),
+            Some("gltf") => Ok(ExportFormat::Gltf),
+            _ => Err(ExportError::FormatOfPath(
+                path.to_string_lossy().into_owned(),
+            )),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<ExportFormat, ExportError> {
+        match s {
+            "obj" => Ok(ExportFormat::Obj),
+            "gltf" => Ok(ExportFormat::Gltf),
+            _ => Err(ExportError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// What was written for an exported node.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportSummary {
+    /// The files written, the mesh first.
+    pub files: Vec<PathBuf>,
+    /// The triangles of the mesh.
+    pub triangles: usize,
+    /// The features the triangles belong to.
+    pub features: usize,
+}
+
+/// The mesh of a node, the texture image of its material, if it has one,
+/// and the center of its bounding volume.
+pub(crate) struct NodeMesh {
+    pub(crate) mesh: Mesh,
+    pub(crate) texture: Option<(Vec<u8>, &'static str)>,
+    /// The folder of the textures of the node's material, relative to the
+    /// layer root, with a trailing slash.
+    pub(crate) texture_folder: String,
+    center: Option<[f64; 3]>,
+}
+
+/// The extension of a JPEG or PNG image, from its first bytes.
+fn image_extension(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("jpg")
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else {
+        None
+    }
+}
+
+/// Reads the first of `names`, relative to the layer root, which the
+/// package holds, gzipped or not.
+fn read_first<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    names: &[String],
+) -> Result<Option<Vec<u8>>, Error> {
+    for name in names {
+        if let Some(contents) = archive::read_resource(slpk_archive, &layer.entry_name(name))? {
+            return Ok(Some(contents));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads a geometry buffer, which is stored as `<name>.bin` or `<name>`.
+fn read_geometry<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    name: &str,
+) -> Result<Vec<u8>, Error> {
+    let names = [format!("{}.bin", name), name.to_string()];
+    match read_first(slpk_archive, layer, &names)? {
+        Some(buffer) => Ok(buffer),
+        None => Err(Error::from(ExportError::MissingGeometry(
+            layer.entry_name(name),
+        ))),
+    }
+}
+
+/// Reads the first of the texture images at `names` which is a JPEG or PNG.
+fn read_texture<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    names: &[String],
+) -> Result<Option<(Vec<u8>, &'static str)>, Error> {
+    for name in names {
+        if let Some(image) = read_first(slpk_archive, layer, std::slice::from_ref(name))? {
+            if let Some(extension) = image_extension(&image) {
+                return Ok(Some((image, extension)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn member<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, name| value.get(name))
+}
+
+fn element(value: Option<&Value>, index: Option<u64>) -> Option<&Value> {
+    value?.as_array()?.get(index? as usize)
+}
+
+/// The mesh of a node of the node pages, from the geometry definitions
+/// and material definitions of its layer.
+fn paged_node_mesh<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node: &Value,
+    node_id: &str,
+) -> Result<NodeMesh, Error> {
+    let geometry = match member(node, &["mesh", "geometry"]) {
+        Some(geometry) => geometry,
+        None => return Err(Error::from(ExportError::NoGeometry(node_id.to_string()))),
+    };
+    let resource = geometry.get("resource").and_then(Value::as_u64);
+    let resource = resource.map_or_else(|| node_id.to_string(), |r| r.to_string());
+    let definition = element(
+        layer.document.get("geometryDefinitions"),
+        geometry.get("definition").and_then(Value::as_u64),
+    );
+    let buffers = definition
+        .and_then(|definition| definition.get("geometryBuffers"))
+        .and_then(Value::as_array)
+        .filter(|buffers| !buffers.is_empty())
+        .ok_or_else(|| {
+            ExportError::UnsupportedGeometry(
+                "its layer declares no geometry buffers for it".to_string(),
+            )
+        })?;
+    // The uncompressed buffer comes first when there is one, but the
+    // specification doesn't require it to.
+    let mut layout = Err(ExportError::UnsupportedGeometry(String::new()));
+    for (index, buffer) in buffers.iter().enumerate() {
+        match GeometryLayout::of_buffer(buffer) {
+            Ok(buffer_layout) => {
+                layout = Ok((index, buffer_layout));
+                break;
+            }
+            Err(e) if index == 0 => layout = Err(e),
+            Err(_) => {}
+        }
+    }
+    let (index, layout) = layout?;
+    let buffer = read_geometry(
+        slpk_archive,
+        layer,
+        &format!("nodes/{}/geometries/{}", resource, index),
+    )?;
+    let mesh = layout.decode(&buffer)?;
+
+    let material = member(node, &["mesh", "material"]);
+    let material_resource = material
+        .and_then(|material| material.get("resource"))
+        .and_then(Value::as_u64)
+        .map_or(resource, |r| r.to_string());
+    let material_definition = element(
+        layer.document.get("materialDefinitions"),
+        material
+            .and_then(|material| material.get("definition"))
+            .and_then(Value::as_u64),
+    );
+    let texture_set = element(
+        layer.document.get("textureSetDefinitions"),
+        material_definition
+            .and_then(|definition| {
+                member(
+                    definition,
+                    &[
+                        "pbrMetallicRoughness",
+                        "baseColorTexture",
+                        "textureSetDefinitionId",
+                    ],
+                )
+            })
+            .and_then(Value::as_u64),
+    );
+    let folder = format!("nodes/{}/textures/", material_resource);
+    let mut names: Vec<String> = texture_set
+        .and_then(|set| set.get("formats"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|format| {
+            let name = format.get("name").and_then(Value::as_str)?;
+            let extension = format.get("format").and_then(Value::as_str)?;
+            if extension == "jpg" || extension == "png" {
+                Some(format!("{}{}.{}", folder, name, extension))
+            } else {
+                None
+            }
+        })
+        .collect();
+    if names.is_empty() && material.is_some() {
+        names = vec![format!("{}0.jpg", folder), format!("{}0.png", folder)];
+    }
+    Ok(NodeMesh {
+        mesh,
+        texture: read_texture(slpk_archive, layer, &names)?,
+        texture_folder: folder,
+        center: BoundingVolume::of_node(node).map(|volume| volume.center()),
+    })
+}
+
+/// The hrefs of a member of a node index document, resolved against the
+/// node's folder.
+fn document_hrefs(document: &Value, member: &str, node_folder: &str) -> Vec<String> {
+    let hrefs = match document.get(member) {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(item) => vec![item],
+        None => Vec::new(),
+    };
+    hrefs
+        .into_iter()
+        .filter_map(|item| item.get("href").and_then(Value::as_str))
+        .filter_map(|href| extract::resolve_href(node_folder, href))
+        .collect()
+}
+
+/// The mesh of a node with a node index document, from the geometry
+/// schema of its layer.
+fn documented_node_mesh<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node_id: &str,
+) -> Result<NodeMesh, Error> {
+    let document = extract::read_node_document(slpk_archive, layer, node_id)?;
+    let node_folder = format!("nodes/{}", node_id);
+    let geometry = match document_hrefs(&document, "geometryData", &node_folder).first() {
+        Some(href) => href.clone(),
+        None => return Err(Error::from(ExportError::NoGeometry(node_id.to_string()))),
+    };
+    let schema = member(&layer.document, &["store", "defaultGeometrySchema"]).ok_or_else(|| {
+        ExportError::UnsupportedGeometry("its layer declares no defaultGeometrySchema".to_string())
+    })?;
+    let layout = GeometryLayout::of_schema(schema)?;
+    let mesh = layout.decode(&read_geometry(slpk_archive, layer, &geometry)?)?;
+    let names: Vec<String> = document_hrefs(&document, "textureData", &node_folder)
+        .into_iter()
+        .flat_map(|href| vec![format!("{}.jpg", href), format!("{}.png", href), href])
+        .collect();
+    Ok(NodeMesh {
+        mesh,
+        texture: read_texture(slpk_archive, layer, &names)?,
+        texture_folder: format!("{}/textures/", node_folder),
+        center: BoundingVolume::of_node(&document).map(|volume| volume.center()),
+    })
+}
+
+/// The mesh of a node of `layer`, by whichever kind of node it has.
+fn read_node_mesh<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node_id: &str,
+) -> Result<NodeMesh, Error> {
+    if layer.uses_node_pages() {
+        let page_nodes = i3s::read_page_nodes(slpk_archive, layer)?;
+        let node = extract::find_page_node(&page_nodes, node_id)?;
+        paged_node_mesh(slpk_archive, layer, node, node_id)
+    } else {
+        documented_node_mesh(slpk_archive, layer, node_id)
+    }
+}
+
+/// Hands the mesh of each node of `layer` which has geometry to `visit`
+/// in turn, with the id of the node, or the error reading it. The node
+/// pages are only read once.
+pub(crate) fn visit_node_meshes<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    mut visit: impl FnMut(&mut ZipArchive<R>, &str, Result<NodeMesh, Error>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let has_geometry = |mesh: &Result<NodeMesh, Error>| match mesh {
+        Err(e) => !matches!(e.downcast_ref(), Some(ExportError::NoGeometry(_))),
+        Ok(_) => true,
+    };
+    if layer.uses_node_pages() {
+        let page_nodes = i3s::read_page_nodes(slpk_archive, layer)?;
+        for (position, node) in page_nodes.iter().enumerate() {
+            let index = node.get("index").and_then(Value::as_u64);
+            let node_id = index.unwrap_or(position as u64).to_string();
+            let mesh = paged_node_mesh(slpk_archive, layer, node, &node_id);
+            if has_geometry(&mesh) {
+                visit(slpk_archive, &node_id, mesh)?;
+            }
+        }
+    } else {
+        for node in i3s::load_hierarchy(slpk_archive, layer)?.nodes {
+            let mesh = documented_node_mesh(slpk_archive, layer, &node.id);
+            if has_geometry(&mesh) {
+                visit(slpk_archive, &node.id, mesh)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Exports a node of the first layer of a package to `output`.
+pub fn export_node(
+    slpk_file_path: &Path,
+    node_id: &str,
+    output: &Path,
+) -> Result<ExportSummary, Error> {
+    export_layer_node(slpk_file_path, None, node_id, output, None, None)
+}
+
+/// Exports a node of the layer with the given id, or of the first layer
+/// when `layer` is `None`, to `output`, in `format` or the format its
+/// extension names, with its positions reprojected into `target_crs` when
+/// one is given. The buffer and texture are written beside it, named after
+/// it.
+pub fn export_layer_node(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    node_id: &str,
+    output: &Path,
+    format: Option<ExportFormat>,
+    target_crs: Option<TargetCrs>,
+) -> Result<ExportSummary, Error> {
+    let format = match format {
+        Some(format) => format,
+        None => ExportFormat::of_path(output)?,
+    };
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let layer = i3s::select_layer(&mut slpk_archive, layer)?;
+    let reprojection = match target_crs {
+        Some(target) => Some(Reprojection::new(layer.wkid, target)?),
+        None => None,
+    };
+    let mut node = read_node_mesh(&mut slpk_archive, &layer, node_id)?;
+    let origin = match (reprojection, node.center) {
+        (Some(reprojection), Some(center)) => Some(reprojection.apply(&mut node.mesh, center)),
+        (Some(_), None) => return Err(Error::from(ExportError::NoCenter(node_id.to_string()))),
+        (None, _) => None,
+    };
+
+    if let Some(folder) = output
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(folder)?;
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let beside = |extension: &str| output.with_file_name(format!("{}.{}", stem, extension));
+    let texture_name = node
+        .texture
+        .as_ref()
+        .map(|(_, extension)| format!("{}.{}", stem, extension));
+    let mut files = vec![output.to_path_buf()];
+    match format {
+        ExportFormat::Obj => {
+            let library_name = format!("{}.mtl", stem);
+            let (obj, library) = obj::to_obj(
+                &node.mesh,
+                node_id,
+                &library_name,
+                texture_name.as_deref(),
+                origin,
+            );
+            std::fs::write(output, obj)?;
+            if let Some(library) = library {
+                std::fs::write(beside("mtl"), library)?;
+                files.push(beside("mtl"));
+            }
+        }
+        ExportFormat::Gltf => {
+            let buffer_name = format!("{}.bin", stem);
+            let (document, buffer) = gltf::to_gltf(
+                &node.mesh,
+                node_id,
+                &buffer_name,
+                texture_name.as_deref(),
+                origin,
+            );
+            std::fs::write(output, document.to_pretty_string(2) + "\n")?;
+            std::fs::write(beside("bin"), buffer)?;
+            files.push(beside("bin"));
+        }
+    }
+    if let Some((image, extension)) = &node.texture {
+        std::fs::write(beside(extension), image)?;
+        files.push(beside(extension));
+    }
+    Ok(ExportSummary {
+        files,
+        triangles: node.mesh.triangles(),
+        features: node.mesh.features.len(),
+    })
+}
+
+/// What was written for the attributes of a layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttributesSummary {
+    /// The CSV files written.
+    pub files: Vec<PathBuf>,
+    /// The nodes which hold attributes.
+    pub nodes: usize,
+    /// The features of those nodes, a row for each.
+    pub features: usize,
+}
+
+/// The resource folders of the nodes of `layer` which hold attribute
+/// buffers, numbered ones in the order of their numbers.
+pub(crate) fn attribute_nodes<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Vec<String>, Error> {
+    let prefix = layer.entry_name("nodes/");
+    let mut nodes = Vec::new();
+    for i in 0..slpk_archive.len() {
+        let entry = slpk_archive.by_index(i)?;
+        let node = entry
+            .name()
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.split_once("/attributes/"))
+            .map(|(node, _)| node);
+        if let Some(node) = node.filter(|node| !node.contains('/')) {
+            nodes.push(node.to_string());
+        }
+    }
+    nodes.sort_by(|a, b| match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    });
+    nodes.dedup();
+    Ok(nodes)
+}
+
+/// The value of each attribute for each feature of a node, as rows. An
+/// attribute whose buffer is missing or can't be decoded is left empty,
+/// with a warning.
+fn node_attribute_rows<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    layouts: &[AttributeLayout],
+    node: &str,
+) -> Result<Vec<Vec<Option<String>>>, Error> {
+    let mut columns = Vec::with_capacity(layouts.len());
+    for layout in layouts {
+        let name = layer.entry_name(&format!("nodes/{}/attributes/{}/0.bin", node, layout.key));
+        let column = match archive::read_resource(slpk_archive, &name)? {
+            Some(buffer) => layout.decode(&buffer).unwrap_or_else(|problem| {
+                warn!(
+                    "Leaving {} of node {} empty: {}",
+                    layout.name, node, problem
+                );
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        columns.push(column);
+    }
+    let features = columns.iter().map(Vec::len).max().unwrap_or(0);
+    Ok((0..features)
+        .map(|feature| {
+            columns
+                .iter()
+                .map(|column| column.get(feature).cloned().flatten())
+                .collect()
+        })
+        .collect())
+}
+
+fn csv_line(cells: impl IntoIterator<Item = String>) -> String {
+    let cells: Vec<String> = cells.into_iter().map(|cell| csv_field(&cell)).collect();
+    cells.join(",") + "\n"
+}
+
+/// Exports the attributes of every node of the layer with the given id, or
+/// of the first layer when `layer` is `None`, as CSV files in `output_dir`:
+/// `<node>.csv` for each node, or with `merge` a single `attributes.csv`
+/// whose first column is the node. Nulls are written as empty cells.
+pub fn export_attributes(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    output_dir: &Path,
+    merge: bool,
+) -> Result<AttributesSummary, Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let layer = i3s::select_layer(&mut slpk_archive, layer)?;
+    let (layouts, unreadable) = AttributeLayout::of_layer(&layer.document);
+    for (key, problem) in &unreadable {
+        warn!("Leaving out attribute {}: {}", key, problem);
+    }
+    if layouts.is_empty() && unreadable.is_empty() {
+        return Err(Error::from(ExportError::NoAttributes));
+    }
+    std::fs::create_dir_all(output_dir)?;
+
+    let names = layouts.iter().map(|layout| layout.name.clone());
+    let mut summary = AttributesSummary::default();
+    let mut merged = csv_line(std::iter::once("node".to_string()).chain(names.clone()));
+    for node in attribute_nodes(&mut slpk_archive, &layer)? {
+        let rows = node_attribute_rows(&mut slpk_archive, &layer, &layouts, &node)?;
+        summary.nodes += 1;
+        summary.features += rows.len();
+        let cells = |row: Vec<Option<String>>| row.into_iter().map(Option::unwrap_or_default);
+        if merge {
+            for row in rows {
+                merged += &csv_line(std::iter::once(node.clone()).chain(cells(row)));
+            }
+        } else {
+            let mut csv = csv_line(names.clone());
+            for row in rows {
+                csv += &csv_line(cells(row));
+            }
+            let path = output_dir.join(format!("{}.csv", node));
+            std::fs::write(&path, csv)?;
+            summary.files.push(path);
+        }
+    }
+    if merge {
+        let path = output_dir.join("attributes.csv");
+        std::fs::write(&path, merged)?;
+        summary.files.push(path);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    fn buffer(
+        counts: &[u32],
+        floats: &[f32],
+        bytes: &[u8],
+        ids: &[u64],
+        ranges: &[u32],
+    ) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend(counts.iter().flat_map(|count| count.to_le_bytes()));
+        buffer.extend(floats.iter().flat_map(|value| value.to_le_bytes()));
+        buffer.extend_from_slice(bytes);
+        buffer.extend(ids.iter().flat_map(|id| id.to_le_bytes()));
+        buffer.extend(ranges.iter().flat_map(|face| face.to_le_bytes()));
+        buffer
+    }
+
+    #[test]
+    fn paged_nodes_are_exported_with_their_texture() {
+        let dir = TempDir::new("export");
+        let path = dir.join("paged.slpk");
+        let positions_and_uvs = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 2.5, 0.0, 0.0, 1.0, 0.0, 0.0, 0.25,
+        ];
+        let colors = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 128];
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"nodePages":{"nodesPerPage":64},
+                    "geometryDefinitions":[
+                        {"geometryBuffers":[
+                            {"offset":8,"position":{"type":"Float32","component":3},
+                             "uv0":{"type":"Float32","component":2},
+                             "color":{"type":"UInt8","component":4},
+                             "featureId":{"type":"UInt64","component":1},
+                             "faceRange":{"type":"UInt32","component":2}},
+                            {"compressedAttributes":{"encoding":"draco"}}]},
+                        {"geometryBuffers":[{"compressedAttributes":{"encoding":"draco"}}]}],
+                    "materialDefinitions":[
+                        {"pbrMetallicRoughness":{"baseColorTexture":{"textureSetDefinitionId":0}}}],
+                    "textureSetDefinitions":[
+                        {"formats":[{"name":"0_0_1","format":"dds"},{"name":"0","format":"jpg"}]}]}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[
+                    {"index":0,"mesh":{"geometry":{"definition":0,"resource":0},
+                                       "material":{"definition":0,"resource":0}}},
+                    {"index":1,"mesh":{"geometry":{"definition":1,"resource":1}}}]}"#,
+            )
+            .entry(
+                "nodes/0/geometries/0.bin.gz",
+                &buffer(&[3, 1], &positions_and_uvs, &colors, &[7], &[0, 0]),
+            )
+            .entry("nodes/0/geometries/1.bin.gz", b"draco")
+            .entry("nodes/0/textures/0.jpg", b"\xff\xd8\xff\xe0jpeg")
+            .entry("nodes/1/geometries/0.bin.gz", b"draco")
+            .write_to_file(&path)
+            .unwrap();
+        let obj_summary = export_node(&path, "0", &dir.join("obj/node.obj")).unwrap();
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        let (obj, library) = (read("obj/node.obj"), read("obj/node.mtl"));
+        let gltf_summary = export_node(&path, "0", &dir.join("node.gltf")).unwrap();
+        let gltf = crate::json::parse(&read("node.gltf")).unwrap();
+        let gltf_buffer = std::fs::read(dir.join("node.bin")).unwrap();
+        let draco = export_node(&path, "1", &dir.join("draco.obj")).unwrap_err();
+        let unknown = export_node(&path, "0", &dir.join("node.stl")).unwrap_err();
+
+        assert_eq!(
+            obj,
+            "# Node 0 of a scene layer package\n\
+             mtllib node.mtl\n\
+             o node_0\n\
+             v 0 0 0 1 0 0\n\
+             v 1 0 0 0 1 0\n\
+             v 0 1 2.5 0 0 1\n\
+             vt 0 1\n\
+             vt 1 1\n\
+             vt 0 0.75\n\
+             usemtl texture\n\
+             g feature_7\n\
+             f 1/1 2/2 3/3\n"
+        );
+        assert_eq!(
+            library,
+            "newmtl texture\nKa 1 1 1\nKd 1 1 1\nmap_Kd node.jpg\n"
+        );
+        assert_eq!(
+            obj_summary.files,
+            [
+                dir.join("obj/node.obj"),
+                dir.join("obj/node.mtl"),
+                dir.join("obj/node.jpg")
+            ]
+        );
+        assert_eq!((obj_summary.triangles, obj_summary.features), (1, 1));
+
+        assert_eq!(gltf_summary.files.len(), 3);
+        let attributes = member(&gltf, &["meshes"])
+            .and_then(|meshes| element(Some(meshes), Some(0)))
+            .and_then(|mesh| element(mesh.get("primitives"), Some(0)))
+            .and_then(|primitive| primitive.get("attributes"))
+            .unwrap();
+        let names: Vec<&str> = match attributes {
+            Value::Object(members) => members.iter().map(|(name, _)| name.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        assert_eq!(names, ["POSITION", "TEXCOORD_0", "COLOR_0"]);
+        assert_eq!(gltf_buffer.len(), 36 + 24 + 12);
+        assert_eq!(
+            element(gltf.get("images"), Some(0)).and_then(|image| image.get("uri")),
+            Some(&Value::from("node.jpg"))
+        );
+        assert_eq!(
+            draco.to_string(),
+            "The geometry of the node is only stored compressed with draco, which can't be exported"
+        );
+        assert!(unknown.to_string().starts_with("Can't tell which format"));
+    }
+
+    fn index(value: Option<&Value>, len: usize) -> usize {
+        let index = value.and_then(Value::as_u64).unwrap() as usize;
+        assert!(index < len, "{} refers past {} items", index, len);
+        index
+    }
+
+    fn items<'a>(document: &'a Value, name: &str) -> &'a [Value] {
+        document.get(name).and_then(Value::as_array).unwrap()
+    }
+
+    /// Checks that a glTF document and the files it refers to in `dir` are
+    /// consistent: every index refers to something, every view lies within
+    /// its buffer, every accessor fills its view, and the position bounds
+    /// are those of the positions. Returns the number of vertices.
+    fn check_gltf(dir: &Path, document: &Value) -> usize {
+        assert_eq!(
+            member(document, &["asset", "version"]),
+            Some(&Value::from("2.0"))
+        );
+        let (scenes, nodes, meshes) = (
+            items(document, "scenes"),
+            items(document, "nodes"),
+            items(document, "meshes"),
+        );
+        index(document.get("scene"), scenes.len());
+        for scene in scenes {
+            for node in items(scene, "nodes") {
+                let node = &nodes[index(Some(node), nodes.len())];
+                index(node.get("mesh"), meshes.len());
+            }
+        }
+
+        let buffers: Vec<Vec<u8>> = items(document, "buffers")
+            .iter()
+            .map(|buffer| {
+                let uri = buffer.get("uri").and_then(Value::as_str).unwrap();
+                let data = std::fs::read(dir.join(uri)).unwrap();
+                assert_eq!(
+                    buffer.get("byteLength").and_then(Value::as_u64),
+                    Some(data.len() as u64)
+                );
+                data
+            })
+            .collect();
+        let views: Vec<&[u8]> = items(document, "bufferViews")
+            .iter()
+            .map(|view| {
+                let buffer = &buffers[index(view.get("buffer"), buffers.len())];
+                let offset = view.get("byteOffset").and_then(Value::as_u64).unwrap() as usize;
+                let len = view.get("byteLength").and_then(Value::as_u64).unwrap() as usize;
+                assert_eq!(offset % 4, 0);
+                &buffer[offset..offset + len]
+            })
+            .collect();
+        let accessors = items(document, "accessors");
+
+        let mut vertices = None;
+        for mesh in meshes {
+            for primitive in items(mesh, "primitives") {
+                assert_eq!(primitive.get("mode").and_then(Value::as_u64), Some(4));
+                let attributes = match primitive.get("attributes") {
+                    Some(Value::Object(attributes)) => attributes,
+                    other => panic!("{:?}", other),
+                };
+                for (name, accessor) in attributes {
+                    let accessor = &accessors[index(Some(accessor), accessors.len())];
+                    let view = views[index(accessor.get("bufferView"), views.len())];
+                    let count = accessor.get("count").and_then(Value::as_u64).unwrap() as usize;
+                    let component_size = match accessor.get("componentType").and_then(Value::as_u64)
+                    {
+                        Some(5126) => 4,
+                        Some(5121) => 1,
+                        other => panic!("{:?}", other),
+                    };
+                    let components = match accessor.get("type").and_then(Value::as_str) {
+                        Some("VEC2") => 2,
+                        Some("VEC3") => 3,
+                        Some("VEC4") => 4,
+                        other => panic!("{:?}", other),
+                    };
+                    assert_eq!(view.len(), count * components * component_size, "{}", name);
+                    assert_eq!(*vertices.get_or_insert(count), count, "{}", name);
+                    if name == "POSITION" {
+                        let values: Vec<f64> = view
+                            .chunks(4)
+                            .map(|bytes| {
+                                f64::from(f32::from_le_bytes([
+                                    bytes[0], bytes[1], bytes[2], bytes[3],
+                                ]))
+                            })
+                            .collect();
+                        for axis in 0..3 {
+                            let axis_values = values.iter().skip(axis).step_by(3);
+                            let min = axis_values.clone().copied().fold(f64::MAX, f64::min);
+                            let max = axis_values.copied().fold(f64::MIN, f64::max);
+                            assert_eq!(
+                                element(accessor.get("min"), Some(axis as u64))
+                                    .and_then(Value::as_f64),
+                                Some(min)
+                            );
+                            assert_eq!(
+                                element(accessor.get("max"), Some(axis as u64))
+                                    .and_then(Value::as_f64),
+                                Some(max)
+                            );
+                        }
+                    }
+                }
+                if let Some(material) = primitive.get("material") {
+                    let materials = items(document, "materials");
+                    let material = &materials[index(Some(material), materials.len())];
+                    let textures = items(document, "textures");
+                    let texture = &textures[index(
+                        member(
+                            material,
+                            &["pbrMetallicRoughness", "baseColorTexture", "index"],
+                        ),
+                        textures.len(),
+                    )];
+                    let images = items(document, "images");
+                    let image = &images[index(texture.get("source"), images.len())];
+                    let uri = image.get("uri").and_then(Value::as_str).unwrap();
+                    assert!(dir.join(uri).is_file(), "{} is missing", uri);
+                }
+            }
+        }
+        let vertices = vertices.unwrap();
+        assert_eq!(vertices % 3, 0);
+        vertices
+    }
+
+    /// Checks that every face of an OBJ refers to vertices, texture
+    /// coordinates and normals it declares, and that its material library
+    /// and texture are in `dir`. Returns the number of faces of each
+    /// group.
+    fn check_obj(dir: &Path, obj: &str) -> Vec<(String, usize)> {
+        let count = |kind: &str| {
+            obj.lines()
+                .filter(|line| line.split(' ').next() == Some(kind))
+                .count()
+        };
+        let declared = [count("v"), count("vt"), count("vn")];
+        let mut groups: Vec<(String, usize)> = Vec::new();
+        let mut materials = Vec::new();
+        for line in obj.lines() {
+            let mut words = line.split(' ');
+            match words.next() {
+                Some("mtllib") => {
+                    let library = std::fs::read_to_string(dir.join(words.next().unwrap())).unwrap();
+                    for line in library.lines() {
+                        match line.split_once(' ') {
+                            Some(("newmtl", name)) => materials.push(name.to_string()),
+                            Some(("map_Kd", texture)) => {
+                                assert!(dir.join(texture).is_file(), "{} is missing", texture)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Some("usemtl") => assert!(materials.contains(&words.next().unwrap().to_string())),
+                Some("g") => groups.push((words.next().unwrap().to_string(), 0)),
+                Some("f") => {
+                    let corners: Vec<&str> = words.collect();
+                    assert_eq!(corners.len(), 3, "{}", line);
+                    for corner in corners {
+                        for (i, reference) in corner.split('/').enumerate() {
+                            if !reference.is_empty() {
+                                let reference: usize = reference.parse().unwrap();
+                                assert!((1..=declared[i]).contains(&reference), "{}", line);
+                            }
+                        }
+                    }
+                    groups.last_mut().unwrap().1 += 1;
+                }
+                _ => {}
+            }
+        }
+        groups
+    }
+
+    #[test]
+    fn exported_files_are_well_formed() {
+        let dir = TempDir::new("export-check");
+        let path = dir.join("features.slpk");
+        let mut floats = Vec::new();
+        for i in 0..6 {
+            floats.extend_from_slice(&[i as f32, -(i as f32), 0.5 * i as f32]);
+        }
+        floats.extend([0.0, 0.0, 1.0].repeat(6));
+        floats.extend([0.5, 0.5].repeat(6));
+        let colors = [200; 24];
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"nodePages":{"nodesPerPage":64},
+                    "geometryDefinitions":[{"geometryBuffers":[
+                        {"position":{"type":"Float32","component":3},
+                         "normal":{"type":"Float32","component":3},
+                         "uv0":{"type":"Float32","component":2},
+                         "color":{"type":"UInt8","component":4},
+                         "featureId":{"type":"UInt64","component":1},
+                         "faceRange":{"type":"UInt32","component":2}}]}],
+                    "materialDefinitions":[{}]}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"index":0,"mesh":{"geometry":{"definition":0,"resource":0},
+                                                  "material":{"definition":0,"resource":0}}}]}"#,
+            )
+            .entry(
+                "nodes/0/geometries/0.bin.gz",
+                &buffer(&[6, 2], &floats, &colors, &[40, 41], &[0, 0, 1, 1]),
+            )
+            .entry("nodes/0/textures/0.png", b"\x89PNG\r\n\x1a\npng")
+            .write_to_file(&path)
+            .unwrap();
+
+        let gltf_summary = export_node(&path, "0", &dir.join("gltf/node.gltf")).unwrap();
+        let document =
+            crate::json::parse(&std::fs::read_to_string(dir.join("gltf/node.gltf")).unwrap())
+                .unwrap();
+        assert_eq!(check_gltf(&dir.join("gltf"), &document), 6);
+        assert_eq!(gltf_summary.files.len(), 3);
+
+        let obj_summary = export_node(&path, "0", &dir.join("obj/node.obj")).unwrap();
+        let obj = std::fs::read_to_string(dir.join("obj/node.obj")).unwrap();
+        assert_eq!(
+            check_obj(&dir.join("obj"), &obj),
+            [("feature_40".to_string(), 1), ("feature_41".to_string(), 1)]
+        );
+        assert_eq!((obj_summary.triangles, obj_summary.features), (2, 2));
+        assert_eq!(obj_summary.files.last(), Some(&dir.join("obj/node.png")));
+    }
+
+    #[test]
+    fn positions_are_reprojected_into_the_target() {
+        let dir = TempDir::new("export-crs");
+        let layer = |spatial_reference: &str| {
+            format!(
+                r#"{{"id":0,"nodePages":{{"nodesPerPage":64}},{}
+                    "geometryDefinitions":[{{"geometryBuffers":[
+                        {{"position":{{"type":"Float32","component":3}},
+                         "normal":{{"type":"Float32","component":3}}}}]}}]}}"#,
+                spatial_reference
+            )
+        };
+        let nodes = br#"{"nodes":[
+            {"index":0,"obb":{"center":[490000,5455000,30],"halfSize":[1,1,1],
+                              "quaternion":[0,0,0,1]},
+             "mesh":{"geometry":{"definition":0,"resource":0}}},
+            {"index":1,"mesh":{"geometry":{"definition":0,"resource":0}}}]}"#;
+        let mut floats = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0];
+        floats.extend([0.0, 0.0, 1.0].repeat(3));
+        let geometry = buffer(&[3, 0], &floats, &[], &[], &[]);
+        let package = |name: &str, layer: &str| {
+            let path = dir.join(name);
+            SyntheticPackage::new()
+                .entry("3dSceneLayer.json.gz", layer.as_bytes())
+                .entry("nodepages/0.json.gz", nodes)
+                .entry("nodes/0/geometries/0.bin.gz", &geometry)
+                .write_to_file(&path)
+                .unwrap();
+            path
+        };
+        let utm = package("utm.slpk", &layer(r#""spatialReference":{"wkid":32610},"#));
+        let unknown = package("nzgd.slpk", &layer(r#""spatialReference":{"wkid":2193},"#));
+        let export = |path: &Path, node: &str, output: &str, target: &str| {
+            let output = dir.join(output);
+            export_layer_node(
+                path,
+                None,
+                node,
+                &output,
+                None,
+                Some(target.parse().unwrap()),
+            )
+            .map(|_| std::fs::read_to_string(&output).unwrap())
+        };
+
+        let gltf = crate::json::parse(&export(&utm, "0", "ecef.gltf", "ecef").unwrap()).unwrap();
+        assert_eq!(check_gltf(&dir, &gltf), 3);
+        let node = element(gltf.get("nodes"), Some(0)).unwrap();
+        let translation = node.get("translation").and_then(Value::as_f64_vec).unwrap();
+        let (lon, lat) = crate::geo::utm_to_wgs84(10, true, 490_000.0, 5_455_000.0);
+        let [x, y, z] = crate::geo::geodetic_to_ecef(lon, lat, 30.0);
+        assert_eq!(translation, [x, z, -y]);
+        let untouched = export_node(&utm, "0", &dir.join("stored.gltf")).unwrap();
+        let stored = std::fs::read_to_string(&untouched.files[0]).unwrap();
+        assert!(!stored.contains("translation"));
+
+        let obj = export(&utm, "0", "wgs84.obj", "wgs84").unwrap();
+        let first = obj.lines().find(|line| line.starts_with("v ")).unwrap();
+        let coordinates: Vec<f64> = first[2..].split(' ').map(|c| c.parse().unwrap()).collect();
+        assert!((coordinates[0] - lon).abs() < 1e-9 && (coordinates[1] - lat).abs() < 1e-9);
+        assert_eq!(coordinates[2], 30.0);
+
+        assert_eq!(
+            export(&unknown, "0", "nzgd.gltf", "ecef")
+                .unwrap_err()
+                .to_string(),
+            "The spatial reference 2193 of the layer can't be reprojected from, only WGS84, \
+             Web Mercator or a UTM zone can"
+        );
+        assert_eq!(
+            export(&utm, "1", "bare.gltf", "epsg:3857")
+                .unwrap_err()
+                .to_string(),
+            "Node 1 has no bounding volume, whose center its positions are offsets from"
+        );
+    }
+
+    #[test]
+    fn older_nodes_are_decoded_by_the_geometry_schema() {
+        let dir = TempDir::new("export-schema");
+        let path = dir.join("documented.slpk");
+        let mut floats = vec![0.0; 18];
+        floats.extend(vec![1.0; 18]);
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"store":{"rootNode":"./nodes/root",
+                    "defaultGeometrySchema":{
+                        "geometryType":"triangles","topology":"PerAttributeArray",
+                        "header":[{"property":"vertexCount","type":"UInt32"},
+                                  {"property":"featureCount","type":"UInt32"}],
+                        "ordering":["position","normal"],
+                        "vertexAttributes":{"position":{"valueType":"Float32","valuesPerElement":3},
+                                            "normal":{"valueType":"Float32","valuesPerElement":3}},
+                        "featureAttributeOrder":["id","faceRange"],
+                        "featureAttributes":{"id":{"valueType":"UInt64","valuesPerElement":1},
+                                             "faceRange":{"valueType":"UInt32","valuesPerElement":2}}}}}"#,
+            )
+            .entry("nodes/root/3dNodeIndexDocument.json.gz", b"{}")
+            .entry(
+                "nodes/5/3dNodeIndexDocument.json.gz",
+                br#"{"id":"5","geometryData":[{"href":"./geometries/0"}]}"#,
+            )
+            .entry(
+                "nodes/5/geometries/0.bin.gz",
+                &buffer(&[6, 2], &floats, &[], &[1, 2], &[0, 0, 1, 1]),
+            )
+            .entry("nodes/root/geometries/0.bin.gz", &buffer(&[6, 0], &[0.0; 3], &[], &[], &[]))
+            .write_to_file(&path)
+            .unwrap();
+        let summary = export_node(&path, "5", &dir.join("node.obj")).unwrap();
+        let obj = std::fs::read_to_string(dir.join("node.obj")).unwrap();
+        let missing = export_node(&path, "6", &dir.join("node.obj")).unwrap_err();
+        let root = crate::json::parse(r#"{"geometryData":[{"href":"./geometries/0"}]}"#).unwrap();
+        let mut archive = archive::open_slpk_archive(&path).unwrap();
+        let layer = i3s::read_scene_layer(&mut archive).unwrap();
+        let schema = member(&layer.document, &["store", "defaultGeometrySchema"]).unwrap();
+        let truncated = GeometryLayout::of_schema(schema).unwrap().decode(&buffer(
+            &[6, 0],
+            &[0.0; 3],
+            &[],
+            &[],
+            &[],
+        ));
+
+        assert_eq!((summary.triangles, summary.features), (2, 2));
+        assert_eq!(summary.files, [dir.join("node.obj")]);
+        assert!(obj.contains("g feature_1\nf 1//1 2//2 3//3\ng feature_2\nf 4//4 5//5 6//6\n"));
+        assert!(obj.contains("vn 1 1 1\n"));
+        assert_eq!(
+            missing.to_string(),
+            "The layer has no node 6, it holds 2 nodes"
+        );
+        assert_eq!(
+            document_hrefs(&root, "geometryData", "nodes/root"),
+            ["nodes/root/geometries/0"]
+        );
+        assert_eq!(
+            truncated.unwrap_err().to_string(),
+            "The geometry buffer ends early, after 20 bytes"
+        );
+    }
+
+    #[test]
+    fn attributes_are_exported_per_node_and_merged() {
+        let dir = TempDir::new("export-csv");
+        let path = dir.join("attributes.slpk");
+        let ids = |ids: &[u32]| {
+            let mut buffer = (ids.len() as u32).to_le_bytes().to_vec();
+            ids.iter()
+                .for_each(|id| buffer.extend_from_slice(&id.to_le_bytes()));
+            buffer
+        };
+        let mut names = vec![1, 0, 0, 0, 7, 0, 0, 0, 7, 0, 0, 0];
+        names.extend_from_slice(b"say \"a\"\0");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"attributeStorageInfo":[
+                    {"key":"f_0","name":"OBJECTID",
+                     "header":[{"property":"count","valueType":"UInt32"}],
+                     "ordering":["attributeValues"],
+                     "attributeValues":{"valueType":"Oid32","valuesPerElement":1}},
+                    {"key":"f_1","name":"label",
+                     "header":[{"property":"count","valueType":"UInt32"},
+                               {"property":"attributeValuesByteCount","valueType":"UInt32"}],
+                     "ordering":["attributeByteCounts","attributeValues"],
+                     "attributeByteCounts":{"valueType":"UInt32","valuesPerElement":1},
+                     "attributeValues":{"valueType":"String","valuesPerElement":1}}]}"#,
+            )
+            .entry("nodes/10/attributes/f_0/0.bin.gz", &ids(&[7]))
+            .entry("nodes/10/attributes/f_1/0.bin.gz", &names)
+            .entry("nodes/2/attributes/f_0/0.bin.gz", &ids(&[3, 4]))
+            .write_to_file(&path)
+            .unwrap();
+        let per_node = export_attributes(&path, None, &dir.join("nodes"), false).unwrap();
+        let node_2 = std::fs::read_to_string(dir.join("nodes/2.csv")).unwrap();
+        let merged = export_attributes(&path, None, &dir.join("merged"), true).unwrap();
+        let all = std::fs::read_to_string(dir.join("merged/attributes.csv")).unwrap();
+
+        assert_eq!(
+            (per_node.files.len(), per_node.nodes, per_node.features),
+            (2, 2, 3)
+        );
+        assert_eq!(node_2, "OBJECTID,label\n3,\n4,\n");
+        assert_eq!(merged.files, [dir.join("merged/attributes.csv")]);
+        assert_eq!(
+            all,
+            "node,OBJECTID,label\n2,3,\n2,4,\n10,7,\"say \"\"a\"\"\"\n"
+        );
+    }
+}
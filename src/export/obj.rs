@@ -0,0 +1,108 @@
+// Writes a mesh as a Wavefront OBJ, with a material library naming the
+// texture when there is one. Vertices are written as they are stored, so
+// each triangle refers to three vertices of its own, and vertex colors
+// follow the position on each `v` line, as most tools read them. OBJ puts
+// the origin of texture coordinates at the bottom left where I3S puts it at
+// the top left, so the second coordinate is flipped. The faces of each
+// feature are grouped under its id. Positions which are offsets from an
+// origin are written whole, since OBJ has no way to move a mesh.
+
+use super::geometry::Mesh;
+use std::fmt::Write;
+
+const MATERIAL: &str = "texture";
+
+/// The OBJ for `mesh`, and the material library to write beside it when the
+/// mesh has a texture, which refer to each other and to the texture by
+/// these file names, with `origin` added to each position when they are
+/// offsets from one.
+pub fn to_obj(
+    mesh: &Mesh,
+    node_id: &str,
+    library_name: &str,
+    texture_name: Option<&str>,
+    origin: Option<[f64; 3]>,
+) -> (String, Option<String>) {
+    let mut obj = format!("# Node {} of a scene layer package\n", node_id);
+    if texture_name.is_some() {
+        let _ = writeln!(obj, "mtllib {}", library_name);
+    }
+    let _ = writeln!(obj, "o node_{}", node_id);
+    let vertices = mesh.triangles() * 3;
+    for (i, [x, y, z]) in mesh.positions[..vertices].iter().enumerate() {
+        let position = match origin {
+            Some(origin) => format!(
+                "{} {} {}",
+                origin[0] + f64::from(*x),
+                origin[1] + f64::from(*y),
+                origin[2] + f64::from(*z)
+            ),
+            None => format!("{} {} {}", x, y, z),
+        };
+        match mesh.colors.get(i) {
+            Some([r, g, b, _]) => {
+                let _ = writeln!(
+                    obj,
+                    "v {} {} {} {}",
+                    position,
+                    f32::from(*r) / 255.0,
+                    f32::from(*g) / 255.0,
+                    f32::from(*b) / 255.0
+                );
+            }
+            None => {
+                let _ = writeln!(obj, "v {}", position);
+            }
+        }
+    }
+    let has_uvs = mesh.uvs.len() >= vertices;
+    let has_normals = mesh.normals.len() >= vertices;
+    if has_uvs {
+        for [u, v] in &mesh.uvs[..vertices] {
+            let _ = writeln!(obj, "vt {} {}", u, 1.0 - v);
+        }
+    }
+    if has_normals {
+        for [x, y, z] in &mesh.normals[..vertices] {
+            let _ = writeln!(obj, "vn {} {} {}", x, y, z);
+        }
+    }
+    if texture_name.is_some() {
+        let _ = writeln!(obj, "usemtl {}", MATERIAL);
+    }
+
+    let mut face_features = vec![None; mesh.triangles()];
+    for feature in &mesh.features {
+        let (first, last) = (feature.faces.0 as usize, feature.faces.1 as usize);
+        for face_feature in face_features.iter_mut().take(last + 1).skip(first) {
+            *face_feature = Some(feature.id);
+        }
+    }
+    let mut group = None;
+    for (face, &feature_id) in face_features.iter().enumerate() {
+        if feature_id != group {
+            if let Some(id) = feature_id {
+                let _ = writeln!(obj, "g feature_{}", id);
+            }
+            group = feature_id;
+        }
+        obj.push('f');
+        for vertex in face * 3 + 1..face * 3 + 4 {
+            let _ = match (has_uvs, has_normals) {
+                (true, true) => write!(obj, " {0}/{0}/{0}", vertex),
+                (true, false) => write!(obj, " {0}/{0}", vertex),
+                (false, true) => write!(obj, " {0}//{0}", vertex),
+                (false, false) => write!(obj, " {}", vertex),
+            };
+        }
+        obj.push('\n');
+    }
+
+    let library = texture_name.map(|texture_name| {
+        format!(
+            "newmtl {}\nKa 1 1 1\nKd 1 1 1\nmap_Kd {}\n",
+            MATERIAL, texture_name
+        )
+    });
+    (obj, library)
+}
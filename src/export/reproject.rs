@@ -0,0 +1,223 @@
+// Moves the positions of a mesh into another spatial reference, so that an
+// export lines up with data in it, such as a globe in earth-centered,
+// earth-fixed coordinates. Positions are stored as offsets from the center
+// of their node, so each is made whole by adding the center, taken through
+// WGS84 into the target, and made an offset again from where the center is
+// in the target, which the export then places the mesh at. Offsets are in
+// the units of their spatial reference: degrees of longitude and latitude
+// for geographic ones, meters for the others. Only the spatial references
+// `geo` knows can be reprojected, from or to.
+
+use super::geometry::Mesh;
+use super::ExportError;
+use crate::geo;
+use crate::geo::Crs;
+use std::fmt;
+use std::str::FromStr;
+
+/// A spatial reference an export can be reprojected into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetCrs {
+    /// Earth-centered, earth-fixed coordinates in meters, EPSG:4978.
+    Ecef,
+    /// Longitude and latitude in degrees, with heights in meters.
+    Wgs84,
+    /// Web Mercator or a UTM zone, by its EPSG code.
+    Projected(u32),
+}
+
+impl FromStr for TargetCrs {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<TargetCrs, ExportError> {
+        let lower = s.to_ascii_lowercase();
+        let code = match lower.as_str() {
+            "ecef" => return Ok(TargetCrs::Ecef),
+            "wgs84" => return Ok(TargetCrs::Wgs84),
+            _ => lower
+                .strip_prefix("epsg:")
+                .and_then(|code| code.parse::<u32>().ok())
+                .ok_or_else(|| ExportError::UnknownTargetCrs(s.to_string()))?,
+        };
+        match Crs::from_wkid(code) {
+            _ if code == 4978 => Ok(TargetCrs::Ecef),
+            Crs::Geographic => Ok(TargetCrs::Wgs84),
+            Crs::Unsupported(_) => Err(ExportError::UnsupportedTargetCrs(code)),
+            Crs::WebMercator | Crs::Utm { .. } => Ok(TargetCrs::Projected(code)),
+        }
+    }
+}
+
+impl fmt::Display for TargetCrs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetCrs::Ecef => f.write_str("ecef"),
+            TargetCrs::Wgs84 => f.write_str("wgs84"),
+            TargetCrs::Projected(code) => write!(f, "epsg:{}", code),
+        }
+    }
+}
+
+/// The transform from the spatial reference of a layer into a target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reprojection {
+    source: Crs,
+    target: TargetCrs,
+}
+
+impl Reprojection {
+    /// The reprojection of a layer with the spatial reference `wkid`, which
+    /// fails, naming it, when `geo` doesn't know it.
+    pub fn new(wkid: Option<u32>, target: TargetCrs) -> Result<Reprojection, ExportError> {
+        match Crs::from_wkid(wkid.unwrap_or(0)) {
+            Crs::Unsupported(_) => Err(ExportError::UnsupportedSourceCrs(
+                wkid.map_or_else(|| "(none given)".to_string(), |wkid| wkid.to_string()),
+            )),
+            source => Ok(Reprojection { source, target }),
+        }
+    }
+
+    fn wgs84(&self, x: f64, y: f64) -> (f64, f64) {
+        self.source.to_wgs84(x, y).unwrap_or((x, y))
+    }
+
+    /// A point of the layer, in the target.
+    fn point(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let (lon, lat) = self.wgs84(x, y);
+        match self.target {
+            TargetCrs::Ecef => geo::geodetic_to_ecef(lon, lat, z),
+            TargetCrs::Wgs84 => [lon, lat, z],
+            TargetCrs::Projected(code) => {
+                let (x, y) = Crs::from_wkid(code).project(lon, lat).unwrap_or((lon, lat));
+                [x, y, z]
+            }
+        }
+    }
+
+    /// Reprojects the positions of `mesh`, offsets from `center` in the
+    /// layer, into offsets from the center in the target, which is
+    /// returned. The axes of earth-centered coordinates aren't east, north
+    /// and up, as those of the others are near enough, so normals are
+    /// turned to match them.
+    pub fn apply(&self, mesh: &mut Mesh, center: [f64; 3]) -> [f64; 3] {
+        let origin = self.point(center);
+        for position in &mut mesh.positions {
+            let point = self.point([
+                center[0] + f64::from(position[0]),
+                center[1] + f64::from(position[1]),
+                center[2] + f64::from(position[2]),
+            ]);
+            *position = [
+                (point[0] - origin[0]) as f32,
+                (point[1] - origin[1]) as f32,
+                (point[2] - origin[2]) as f32,
+            ];
+        }
+        if self.target == TargetCrs::Ecef {
+            let (lon, lat) = self.wgs84(center[0], center[1]);
+            for normal in &mut mesh.normals {
+                let [x, y, z] = geo::enu_to_ecef(
+                    lon,
+                    lat,
+                    [
+                        f64::from(normal[0]),
+                        f64::from(normal[1]),
+                        f64::from(normal[2]),
+                    ],
+                );
+                *normal = [x as f32, y as f32, z as f32];
+            }
+        }
+        origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(positions: Vec<[f32; 3]>) -> Mesh {
+        Mesh {
+            normals: vec![[0.0, 0.0, 1.0]; positions.len()],
+            positions,
+            ..Mesh::default()
+        }
+    }
+
+    #[test]
+    fn targets_are_named_by_alias_or_epsg_code() {
+        let target = |s: &str| s.parse::<TargetCrs>().map_err(|e| e.to_string());
+        assert_eq!(target("ECEF"), Ok(TargetCrs::Ecef));
+        assert_eq!(target("epsg:4978"), Ok(TargetCrs::Ecef));
+        assert_eq!(target("wgs84"), Ok(TargetCrs::Wgs84));
+        assert_eq!(target("EPSG:4326"), Ok(TargetCrs::Wgs84));
+        assert_eq!(target("epsg:3857"), Ok(TargetCrs::Projected(3857)));
+        assert_eq!(target("epsg:32610").unwrap().to_string(), "epsg:32610");
+        assert_eq!(
+            target("epsg:2193"),
+            Err(
+                "Positions can't be reprojected into EPSG:2193, only into ecef, wgs84, \
+                 Web Mercator or a UTM zone"
+                    .to_string()
+            )
+        );
+        assert!(target("utm").unwrap_err().starts_with("Unknown target"));
+    }
+
+    #[test]
+    fn positions_are_offsets_from_the_center_in_the_target() {
+        // A meter east and a meter up from a point in UTM zone 10.
+        let center = [490_000.0, 5_455_000.0, 30.0];
+        let mut utm = mesh(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 1.0]]);
+        let reprojection = Reprojection::new(Some(32610), TargetCrs::Wgs84).unwrap();
+        let origin = reprojection.apply(&mut utm, center);
+        let (lon, lat) = geo::utm_to_wgs84(10, true, center[0], center[1]);
+        assert!((origin[0] - lon).abs() < 1e-9 && (origin[1] - lat).abs() < 1e-9);
+        assert_eq!(origin[2], 30.0);
+        assert_eq!(utm.positions[0], [0.0, 0.0, 0.0]);
+        // A meter is about 1.4e-5 degrees of longitude at 49 degrees north.
+        assert!((f64::from(utm.positions[1][0]) - 1.37e-5).abs() < 1e-7);
+        assert_eq!(utm.positions[1][2], 1.0);
+        assert_eq!(utm.normals[0], [0.0, 0.0, 1.0]);
+
+        let mut ecef = mesh(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 1.0]]);
+        let to_ecef = Reprojection::new(Some(32610), TargetCrs::Ecef).unwrap();
+        let origin = to_ecef.apply(&mut ecef, center);
+        let expected = geo::geodetic_to_ecef(lon, lat, 30.0);
+        for axis in 0..3 {
+            assert!((origin[axis] - expected[axis]).abs() < 1e-6);
+        }
+        let moved = ecef.positions[1];
+        let length = moved
+            .iter()
+            .map(|c| f64::from(*c).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        assert!((length - 2f64.sqrt()).abs() < 1e-3, "{}", length);
+        let up = geo::enu_to_ecef(lon, lat, [0.0, 0.0, 1.0]);
+        for (normal, up) in ecef.normals[0].iter().zip(&up) {
+            assert!((f64::from(*normal) - up).abs() < 1e-6);
+        }
+
+        let mut mercator = mesh(vec![[0.0, 0.0, 0.0]]);
+        let to_mercator = Reprojection::new(Some(32610), TargetCrs::Projected(3857)).unwrap();
+        let origin = to_mercator.apply(&mut mercator, center);
+        let back = geo::web_mercator_to_wgs84(origin[0], origin[1]);
+        assert!((back.0 - lon).abs() < 1e-9 && (back.1 - lat).abs() < 1e-9);
+    }
+
+    #[test]
+    fn layers_in_unknown_spatial_references_are_named() {
+        let error = |wkid| {
+            Reprojection::new(wkid, TargetCrs::Ecef)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(Some(2193)),
+            "The spatial reference 2193 of the layer can't be reprojected from, only WGS84, \
+             Web Mercator or a UTM zone can"
+        );
+        assert!(error(None).contains("(none given)"));
+    }
+}
@@ -0,0 +1,538 @@
+// Extracts the resources of a single node, for looking into how one node is
+// drawn without unpacking a whole package. Everything stored below the
+// node's folder is extracted, along with the resources it refers to in other
+// folders: those named by the node page for I3S 1.7 and later, where a node's
+// resources can be stored under the id of another node, or by the hrefs of
+// the node index document of older packages. Entries are written as an
+// unpack writes them, through the same folders and decompressed when they
+// are gzipped.
+//
+// A single entry can also be written to any writer, such as the standard
+// output for `slpkg cat`, decompressed and formatted as an unpack would
+// write it. It can be named with or without its `.gz` suffix.
+
+use crate::archive::{self, IndexedPackage};
+use crate::i3s;
+use crate::i3s::SceneLayer;
+use crate::json::Value;
+use crate::unpack::{self, JsonFormatting};
+use failure::Error;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// How many near misses are suggested for an entry which doesn't exist.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The members of a node index document which refer to resources, either by
+/// a single href or by an array of them.
+pub(crate) const RESOURCE_MEMBERS: [&str; 5] = [
+    "sharedResource",
+    "geometryData",
+    "textureData",
+    "attributeData",
+    "featureData",
+];
+
+/// The members of the mesh of a node in the node pages which name the folder
+/// holding a resource.
+const MESH_MEMBERS: [&str; 3] = ["geometry", "material", "attribute"];
+
+/// Why a node or entry can't be extracted.
+#[derive(Debug, Fail)]
+pub enum ExtractError {
+    /// No node of the layer has the id asked for.
+    #[fail(display = "The layer has no node {}, it holds {} nodes", id, nodes)]
+    NoSuchNode {
+        /// The id asked for.
+        id: String,
+        /// How many nodes the layer holds.
+        nodes: usize,
+    },
+
+    /// The package has no entry of the name asked for, with or without
+    /// `.gz`.
+    #[fail(display = "The package has no entry {}{}", name, suggestions)]
+    NoSuchEntry {
+        /// The name asked for.
+        name: String,
+        /// The entries which may have been meant, as a sentence to follow the
+        /// name, or empty when there are none.
+        suggestions: String,
+    },
+}
+
+/// What was extracted of a node.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExtractSummary {
+    /// The entries extracted, in order of name.
+    pub entries: Vec<String>,
+    /// The bytes of every file written, decompressed when the entry is
+    /// gzipped.
+    pub bytes_written: u64,
+}
+
+/// Extracts a node of the first layer of a package into `output_dir`.
+pub fn extract_node(
+    slpk_file_path: &Path,
+    node_id: &str,
+    output_dir: &Path,
+) -> Result<ExtractSummary, Error> {
+    extract_layer_node(slpk_file_path, None, node_id, output_dir)
+}
+
+/// Extracts a node of the layer with the given id, or of the first layer
+/// when `layer` is `None`, into `output_dir`, which is created if it doesn't
+/// exist.
+pub fn extract_layer_node(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+    node_id: &str,
+    output_dir: &Path,
+) -> Result<ExtractSummary, Error> {
+    let mut package = IndexedPackage::open(slpk_file_path)?;
+    // Which entries the node has is only known from the central directory,
+    // but they are read through the hash index, when there is one.
+    let slpk_archive = package.archive()?;
+    let layer = i3s::select_layer(slpk_archive, layer)?;
+    let mut entry_names = Vec::with_capacity(slpk_archive.len());
+    for i in 0..slpk_archive.len() {
+        let name = slpk_archive.by_index(i)?.name().to_string();
+        if layer.contains(&name) && !name.ends_with('/') {
+            entry_names.push(name);
+        }
+    }
+    let references = if layer.uses_node_pages() {
+        paged_node_references(slpk_archive, &layer, node_id)?
+    } else {
+        documented_node_references(slpk_archive, &layer, node_id)?
+    };
+    entry_names.retain(|name| {
+        name.strip_prefix(layer.root.as_str())
+            .is_some_and(|name| is_referenced(name, &references))
+    });
+    entry_names.sort();
+    let entry_names = entry_names
+        .into_iter()
+        .map(|name| {
+            let decoded_name = archive::names::decoded_name(&slpk_archive.by_name(&name)?);
+            Ok((name, decoded_name))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut summary = ExtractSummary::default();
+    for (entry_name, decoded_name) in entry_names {
+        let entry = package
+            .open_entry(&entry_name)?
+            .ok_or(ZipError::FileNotFound)?;
+        let mut target_path = output_dir.join(archive::names::sanitized_path(&decoded_name));
+        if entry_name.ends_with(".gz") {
+            let name = target_path.file_stem().unwrap_or_default().to_os_string();
+            target_path.set_file_name(name);
+        }
+        if let Some(folder) = target_path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        let mut contents: Box<dyn Read> = if entry_name.ends_with(".gz") {
+            Box::new(GzDecoder::new(entry))
+        } else {
+            Box::new(entry)
+        };
+        summary.bytes_written += std::io::copy(&mut contents, &mut File::create(&target_path)?)?;
+        summary.entries.push(entry_name);
+    }
+    Ok(summary)
+}
+
+/// Whether an entry, named relative to the layer root, holds one of the
+/// `references`. A reference ending with a slash is a folder, and any other
+/// names a resource, stored with or without a file name extension, or a
+/// folder of them.
+fn is_referenced(entry_name: &str, references: &[String]) -> bool {
+    let logical_name = entry_name.strip_suffix(".gz").unwrap_or(entry_name);
+    references.iter().any(|reference| {
+        if reference.ends_with('/') {
+            return logical_name.starts_with(reference.as_str());
+        }
+        match logical_name.strip_prefix(reference.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('/'),
+            None => false,
+        }
+    })
+}
+
+/// The node of the node pages whose id, its index there, is `node_id`.
+pub(crate) fn find_page_node<'a>(
+    page_nodes: &'a [Value],
+    node_id: &str,
+) -> Result<&'a Value, Error> {
+    let node = page_nodes.iter().enumerate().find(|(position, node)| {
+        let index = node
+            .get("index")
+            .and_then(Value::as_u64)
+            .unwrap_or(*position as u64);
+        index.to_string() == node_id
+    });
+    match node {
+        Some((_, node)) => Ok(node),
+        None => Err(Error::from(ExtractError::NoSuchNode {
+            id: node_id.to_string(),
+            nodes: page_nodes.len(),
+        })),
+    }
+}
+
+/// The folders holding the resources of a node of the node pages, whose id
+/// is its index there.
+fn paged_node_references<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node_id: &str,
+) -> Result<Vec<String>, Error> {
+    let page_nodes = i3s::read_page_nodes(slpk_archive, layer)?;
+    let node = find_page_node(&page_nodes, node_id)?;
+    let mesh = node.get("mesh");
+    let mut references = vec![format!("nodes/{}/", node_id)];
+    // The nodes of point clouds have no mesh, only the folder of their
+    // resources.
+    if let Some(resource) = node.get("resourceId").and_then(Value::as_u64) {
+        references.push(format!("nodes/{}/", resource));
+    }
+    for member in &MESH_MEMBERS {
+        if let Some(resource) = mesh
+            .and_then(|mesh| mesh.get(member))
+            .and_then(|member| member.get("resource"))
+            .and_then(Value::as_u64)
+        {
+            references.push(format!("nodes/{}/", resource));
+        }
+    }
+    Ok(references)
+}
+
+/// The node index document of a node of a layer without node pages.
+pub(crate) fn read_node_document<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node_id: &str,
+) -> Result<Value, Error> {
+    let document = i3s::read_document(
+        slpk_archive,
+        &layer.entry_name(&format!("nodes/{}/3dNodeIndexDocument.json", node_id)),
+    )?;
+    match document {
+        Some(document) if !node_id.contains('/') => Ok(document),
+        _ => {
+            let mut nodes = 0;
+            for i in 0..slpk_archive.len() {
+                let entry = slpk_archive.by_index(i)?;
+                let name = match entry.name().strip_prefix(layer.root.as_str()) {
+                    Some(name) if layer.contains(entry.name()) => name,
+                    _ => continue,
+                };
+                let name = name.strip_suffix(".gz").unwrap_or(name);
+                if name
+                    .strip_prefix("nodes/")
+                    .and_then(|name| name.strip_suffix("/3dNodeIndexDocument.json"))
+                    .is_some_and(|id| !id.contains('/'))
+                {
+                    nodes += 1;
+                }
+            }
+            Err(Error::from(ExtractError::NoSuchNode {
+                id: node_id.to_string(),
+                nodes,
+            }))
+        }
+    }
+}
+
+/// The resources of a node with a node index document, from the hrefs it
+/// holds.
+fn documented_node_references<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node_id: &str,
+) -> Result<Vec<String>, Error> {
+    let node_folder = format!("nodes/{}", node_id);
+    let document = read_node_document(slpk_archive, layer, node_id)?;
+    let mut references = vec![format!("{}/", node_folder)];
+    for member in &RESOURCE_MEMBERS {
+        let hrefs = match document.get(member) {
+            Some(Value::Array(items)) => items.iter().collect(),
+            Some(item) => vec![item],
+            None => Vec::new(),
+        };
+        references.extend(
+            hrefs
+                .into_iter()
+                .filter_map(|item| item.get("href").and_then(Value::as_str))
+                .filter_map(|href| resolve_href(&node_folder, href)),
+        );
+    }
+    Ok(references)
+}
+
+/// The name, relative to the layer root, of the resource an href of the node
+/// index document in `node_folder` refers to. Hrefs which lead out of the
+/// layer refer to nothing it holds.
+pub(crate) fn resolve_href(node_folder: &str, href: &str) -> Option<String> {
+    let mut components: Vec<&str> = if href.starts_with('/') {
+        Vec::new()
+    } else {
+        node_folder.split('/').collect()
+    };
+    for component in href.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.join("/"))
+}
+
+/// The entries which may be the one meant by `entry_name`: those with the
+/// same name in another case, or in the folder of another layer.
+fn near_misses<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    entry_name: &str,
+) -> Result<Vec<String>, Error> {
+    let logical = |name: &str| {
+        let name = name.trim_start_matches("./").trim_start_matches('/');
+        name.strip_suffix(".gz")
+            .unwrap_or(name)
+            .to_ascii_lowercase()
+    };
+    let wanted = logical(entry_name);
+    let nested = format!("/{}", wanted);
+    let mut suggestions = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let name = logical(entry.name());
+        if name == wanted || name.ends_with(&nested) {
+            suggestions.push(entry.name().to_string());
+        }
+    }
+    suggestions.sort();
+    suggestions.truncate(MAX_SUGGESTIONS);
+    Ok(suggestions)
+}
+
+/// Writes the contents of an entry to `writer` as an unpack would write its
+/// file: decompressed when it is gzipped, and a JSON document formatted as
+/// `formatting` says. The entry is named exactly or without its `.gz`
+/// suffix, and is found through the hash index of the package when it has
+/// one. Returns the number of bytes written.
+pub fn extract_entry_to_writer<W: Write>(
+    package: &mut IndexedPackage,
+    entry_name: &str,
+    writer: &mut W,
+    formatting: JsonFormatting,
+) -> Result<u64, Error> {
+    let alternative = match entry_name.strip_suffix(".gz") {
+        Some(name) => name.to_string(),
+        None => format!("{}.gz", entry_name),
+    };
+    let names = [entry_name, alternative.as_str()];
+    let entry_name = match package.find_any(&names)? {
+        Some(i) => names[i],
+        None => {
+            let suggestions = near_misses(package.archive()?, entry_name)?;
+            return Err(Error::from(ExtractError::NoSuchEntry {
+                name: entry_name.to_string(),
+                suggestions: if suggestions.is_empty() {
+                    String::new()
+                } else {
+                    format!(", did you mean {}?", suggestions.join(" or "))
+                },
+            }));
+        }
+    };
+    let mut entry = package
+        .open_entry(entry_name)?
+        .ok_or(ZipError::FileNotFound)?;
+    let sizes = (entry.compressed_size, entry.size);
+    let mut magic = Vec::with_capacity(archive::GZIP_MAGIC.len());
+    (&mut entry)
+        .take(archive::GZIP_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let logical_name = entry_name.strip_suffix(".gz");
+    let decompress = logical_name.is_some() && archive::is_gzip(&magic);
+    let contents = Cursor::new(magic).chain(entry);
+    let contents: Box<dyn Read> = if decompress {
+        Box::new(GzDecoder::new(contents))
+    } else {
+        Box::new(contents)
+    };
+    let target_name = logical_name.filter(|_| decompress).unwrap_or(entry_name);
+    let mut contents = unpack::written_contents(
+        contents,
+        entry_name,
+        Path::new(target_name),
+        sizes,
+        decompress,
+        None,
+        formatting,
+    );
+    Ok(std::io::copy(&mut contents, writer)?)
+}
+
+/// Writes the contents of an entry of the package at `slpk_file_path` to
+/// `writer`, as `extract_entry_to_writer` does.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::extract;
+/// use slpkg::unpack::JsonFormatting;
+/// use std::path::Path;
+///
+/// let mut metadata = Vec::new();
+/// extract::extract_entry(
+///     Path::new("tests/fixtures/building.slpk"),
+///     "metadata.json",
+///     &mut metadata,
+///     JsonFormatting::AsIs,
+/// )?;
+/// assert!(String::from_utf8(metadata)?.contains("I3SVersion"));
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn extract_entry<W: Write>(
+    slpk_file_path: &Path,
+    entry_name: &str,
+    writer: &mut W,
+    formatting: JsonFormatting,
+) -> Result<u64, Error> {
+    let mut package = IndexedPackage::open(slpk_file_path)?;
+    extract_entry_to_writer(&mut package, entry_name, writer, formatting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    #[test]
+    fn nodes_are_extracted_with_the_resources_they_refer_to() {
+        let dir = TempDir::new("extract-node");
+
+        // Node 1 of the node pages keeps its geometry under the id of node
+        // 2, and its texture under its own id.
+        let paged = dir.join("paged.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"nodePages":{"nodesPerPage":64}}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"index":0,"children":[1]},
+                    {"index":1,"mesh":{"geometry":{"resource":2},"material":{"resource":1}}},
+                    {"index":2}]}"#,
+            )
+            .entry("nodes/1/textures/0.jpg", b"jpeg")
+            .entry("nodes/2/geometries/0.bin.gz", &[1; 40])
+            .entry("nodes/21/geometries/0.bin.gz", &[2; 40])
+            .entry("nodes/0/geometries/0.bin.gz", &[3; 40])
+            .write_to_file(&paged)
+            .unwrap();
+        let paged_out = dir.join("paged");
+        let summary = extract_node(&paged, "1", &paged_out).unwrap();
+        let texture = std::fs::read(paged_out.join("nodes/1/textures/0.jpg")).unwrap();
+        let geometry = std::fs::read(paged_out.join("nodes/2/geometries/0.bin")).unwrap();
+        let missing = extract_node(&paged, "7", &dir.join("missing")).unwrap_err();
+
+        // Node 42 of an older package shares a texture with its parent, and
+        // its shared resources are stored together with the others.
+        let documented = dir.join("documented.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"store":{"rootNode":"./nodes/root"}}"#,
+            )
+            .entry("nodes/root/3dNodeIndexDocument.json.gz", b"{}")
+            .entry("nodes/4/3dNodeIndexDocument.json.gz", b"{}")
+            .entry("nodes/4/textures/0_0.jpg", b"parent jpeg")
+            .entry(
+                "nodes/42/3dNodeIndexDocument.json.gz",
+                br#"{"id":"42",
+                    "sharedResource":{"href":"../../shared/42"},
+                    "geometryData":[{"href":"./geometries/0"}],
+                    "textureData":[{"href":"../4/textures/0_0"}]}"#,
+            )
+            .entry("nodes/42/geometries/0.bin.gz", &[4; 40])
+            .entry("shared/42/sharedResource.json.gz", b"{}")
+            .entry("shared/420/sharedResource.json.gz", b"{}")
+            .write_to_file(&documented)
+            .unwrap();
+        let documented_summary = extract_node(&documented, "42", &dir.join("documented")).unwrap();
+        let documented_missing = extract_node(&documented, "43", &dir.join("missing")).unwrap_err();
+
+        assert_eq!(
+            summary.entries,
+            ["nodes/1/textures/0.jpg", "nodes/2/geometries/0.bin.gz"]
+        );
+        assert_eq!(summary.bytes_written, 44);
+        assert_eq!((texture, geometry), (b"jpeg".to_vec(), vec![1; 40]));
+        assert_eq!(
+            missing.to_string(),
+            "The layer has no node 7, it holds 3 nodes"
+        );
+
+        assert_eq!(
+            documented_summary.entries,
+            [
+                "nodes/4/textures/0_0.jpg",
+                "nodes/42/3dNodeIndexDocument.json.gz",
+                "nodes/42/geometries/0.bin.gz",
+                "shared/42/sharedResource.json.gz",
+            ]
+        );
+        assert_eq!(
+            documented_missing.to_string(),
+            "The layer has no node 43, it holds 3 nodes"
+        );
+    }
+
+    #[test]
+    fn single_entries_are_written_as_unpacked() {
+        let dir = TempDir::new("cat");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "nodes/0/3dNodeIndexDocument.json.gz",
+                br#"{"id":"0","level":1}"#,
+            )
+            .entry("nodes/0/geometries/0.bin.gz", &[1, 2, 3])
+            .write_to_file(&path)
+            .unwrap();
+        let mut package = IndexedPackage::open(&path).unwrap();
+        let mut cat = |name: &str, formatting: &str| {
+            let mut out = Vec::new();
+            extract_entry_to_writer(&mut package, name, &mut out, formatting.parse().unwrap())
+                .map(|_| out)
+        };
+        let pretty = cat("nodes/0/3dNodeIndexDocument.json.gz", "pretty").unwrap();
+        let geometry = cat("nodes/0/geometries/0.bin", "pretty").unwrap();
+        let as_is = cat("nodes/0/3dNodeIndexDocument.json", "as-is").unwrap();
+        let missing = cat("Nodes/0/Geometries/0.bin", "as-is").unwrap_err();
+
+        assert_eq!(pretty, b"{\n  \"id\": \"0\",\n  \"level\": 1\n}\n");
+        assert_eq!(geometry, [1, 2, 3]);
+        assert_eq!(as_is, br#"{"id":"0","level":1}"#);
+        assert_eq!(
+            missing.to_string(),
+            "The package has no entry Nodes/0/Geometries/0.bin, did you mean nodes/0/geometries/0.bin.gz?"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// The optional parts of slpkg: those which cargo features build in or leave
+// out, and the encodings of packages which no build decodes yet. A command
+// or flag whose part a build leaves out is still on its command line, and
+// fails with `FeatureError::FeatureNotCompiled`, naming the feature to build
+// with, rather than being unknown. Info and validate note what a layer holds
+// which the build can't decode.
+
+use crate::json::Value;
+use std::fmt;
+
+/// Why a command can't run in this build.
+#[derive(Debug, Fail)]
+pub enum FeatureError {
+    /// The command or flag needs a feature this build leaves out.
+    #[fail(
+        display = "{} needs the {} feature, which this build of slpkg leaves out; build it with `cargo build --features {}`",
+        flag, feature, feature
+    )]
+    FeatureNotCompiled {
+        /// The cargo feature, such as `remote`.
+        feature: String,
+        /// The command or flag which needs it, such as `mirror`.
+        flag: String,
+    },
+}
+
+/// A part of slpkg which a build may not have.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capability {
+    /// What the part is called, such as `mirror`.
+    pub name: &'static str,
+    /// The cargo feature which builds it in, or `None` for those which no
+    /// build has yet.
+    pub feature: Option<&'static str>,
+    /// Whether this build has it.
+    pub available: bool,
+    /// What it does, such as "mirroring a layer of a scene service".
+    pub description: &'static str,
+}
+
+impl Capability {
+    /// Why this build doesn't have it, or `None` when it does.
+    pub fn unavailable(&self) -> Option<String> {
+        match self.feature {
+            _ if self.available => None,
+            Some(feature) => Some(format!(
+                "{} needs the {} feature, which this build leaves out",
+                self.description, feature
+            )),
+            None => Some(format!(
+                "{} isn't in any build of slpkg yet",
+                self.description
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match (self.available, self.feature) {
+            (true, Some(feature)) => format!("built in, by the {} feature", feature),
+            (false, Some(feature)) => format!("left out, needs the {} feature", feature),
+            (_, None) => "not in any build yet".to_string(),
+        };
+        write!(f, "{}: {}, {}", self.name, state, self.description)
+    }
+}
+
+/// Every optional part of slpkg, and whether this build has it.
+pub fn capabilities() -> Vec<Capability> {
+    vec![
+        Capability {
+            name: "cli",
+            feature: Some("cli"),
+            available: cfg!(feature = "cli"),
+            description: "the slpkg command line, and asking for passwords on the terminal",
+        },
+        Capability {
+            name: "mirror",
+            feature: Some("remote"),
+            available: cfg!(feature = "remote"),
+            description: "mirroring a layer of a scene service into a package",
+        },
+        Capability {
+            name: "draco",
+            feature: None,
+            available: false,
+            description: "decoding Draco compressed geometry",
+        },
+        Capability {
+            name: "lepcc",
+            feature: None,
+            available: false,
+            description: "decoding LEPCC compressed point clouds",
+        },
+    ]
+}
+
+/// The capability called `name`.
+pub fn capability(name: &str) -> Option<Capability> {
+    capabilities().into_iter().find(|c| c.name == name)
+}
+
+/// Fails, naming `feature`, unless this build has it, for the command or
+/// flag `flag` which needs it.
+pub fn require(feature: &str, flag: &str) -> Result<(), FeatureError> {
+    if capabilities()
+        .iter()
+        .any(|c| c.feature == Some(feature) && c.available)
+    {
+        return Ok(());
+    }
+    Err(FeatureError::FeatureNotCompiled {
+        feature: feature.to_string(),
+        flag: flag.to_string(),
+    })
+}
+
+/// What the layer document `document` declares which this build can't
+/// decode, as a sentence for each.
+pub(crate) fn undecodable(document: &Value) -> Vec<String> {
+    let buffers = document
+        .get("geometryDefinitions")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|definition| definition.get("geometryBuffers").and_then(Value::as_array))
+        .flatten()
+        .filter_map(|buffer| buffer.get("compressedAttributes"))
+        .filter_map(|compressed| compressed.get("encoding").and_then(Value::as_str));
+    let attributes = document
+        .get("attributeStorageInfo")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|info| info.get("encoding").and_then(Value::as_str));
+    let encodings: Vec<String> = buffers.chain(attributes).map(str::to_lowercase).collect();
+    let mut notes = Vec::new();
+    let mut note = |name: &str, what: &str| {
+        if let Some(reason) = capability(name).and_then(|c| c.unavailable()) {
+            notes.push(format!("{} are present, but {}", what, reason));
+        }
+    };
+    if encodings.iter().any(|e| e == "draco") {
+        note("draco", "Draco compressed geometry buffers");
+    }
+    if encodings.iter().any(|e| e.starts_with("lepcc")) {
+        note("lepcc", "LEPCC compressed point cloud attributes");
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn capabilities_agree_with_the_errors_of_their_features() {
+        for capability in capabilities() {
+            let feature = match capability.feature {
+                Some(feature) => feature,
+                None => {
+                    assert!(!capability.available);
+                    continue;
+                }
+            };
+            match require(feature, capability.name) {
+                Ok(()) => assert!(capability.available, "{}", capability),
+                Err(e) => {
+                    assert!(!capability.available, "{}", capability);
+                    assert!(capability.to_string().contains("left out"));
+                    assert!(capability
+                        .unavailable()
+                        .unwrap()
+                        .contains(&format!("the {} feature", feature)));
+                    assert_eq!(
+                        e.to_string(),
+                        format!(
+                            "{} needs the {} feature, which this build of slpkg leaves out; \
+                             build it with `cargo build --features {}`",
+                            capability.name, feature, feature
+                        )
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            capability("mirror").unwrap().available,
+            cfg!(feature = "remote")
+        );
+        assert!(require("remote", "mirror").is_ok() == cfg!(feature = "remote"));
+        assert!(require("no-such-feature", "--flag").is_err());
+    }
+
+    // Run with `cargo test --lib --no-default-features`.
+    #[cfg(not(feature = "remote"))]
+    #[test]
+    fn mirror_names_the_feature_it_needs() {
+        assert_eq!(
+            require("remote", "mirror").unwrap_err().to_string(),
+            "mirror needs the remote feature, which this build of slpkg leaves out; \
+             build it with `cargo build --features remote`"
+        );
+        assert_eq!(
+            capability("mirror").unwrap().to_string(),
+            "mirror: left out, needs the remote feature, \
+             mirroring a layer of a scene service into a package"
+        );
+    }
+
+    #[test]
+    fn encodings_no_build_decodes_are_noted() {
+        let document = json::parse(
+            r#"{"geometryDefinitions":[{"geometryBuffers":[{"offset":8},
+                {"compressedAttributes":{"encoding":"draco","attributes":["position"]}}]}],
+                "attributeStorageInfo":[{"key":"2","encoding":"lepcc-intensity"},{"key":"4"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            undecodable(&document),
+            [
+                "Draco compressed geometry buffers are present, but decoding Draco compressed \
+                 geometry isn't in any build of slpkg yet",
+                "LEPCC compressed point cloud attributes are present, but decoding LEPCC \
+                 compressed point clouds isn't in any build of slpkg yet",
+            ]
+        );
+        let plain =
+            json::parse(r#"{"geometryDefinitions":[{"geometryBuffers":[{"offset":8}]}]}"#).unwrap();
+        assert!(undecodable(&plain).is_empty());
+    }
+}
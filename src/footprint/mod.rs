@@ -0,0 +1,396 @@
+use crate::archive;
+use crate::geo;
+use crate::geo::Crs;
+use crate::i3s;
+use crate::i3s::{BoundingVolume, Hierarchy, Node, SceneLayer};
+use crate::json::Value;
+use failure::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+use zip::ZipArchive;
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ResourceSizes {
+    entries: u64,
+    compressed: u64,
+    uncompressed: u64,
+}
+
+/// Projects points from the layer's spatial reference into WGS84, falling
+/// back to the untransformed coordinates for unsupported references.
+pub(crate) struct Projector {
+    crs: Crs,
+}
+
+impl Projector {
+    pub(crate) fn for_layer(layer: &SceneLayer) -> Projector {
+        Projector {
+            crs: Crs::from_wkid(layer.wkid.unwrap_or(0)),
+        }
+    }
+
+    pub(crate) fn is_supported(&self) -> bool {
+        !matches!(self.crs, Crs::Unsupported(_))
+    }
+
+    pub(crate) fn unsupported_warning(&self, layer: &SceneLayer) -> Option<String> {
+        if self.is_supported() {
+            return None;
+        }
+        Some(match layer.wkid {
+            Some(wkid) => format!(
+                "Warning: spatial reference {} is not supported, coordinates are not reprojected",
+                wkid
+            ),
+            None => "Warning: the layer has no spatial reference, coordinates are not reprojected"
+                .to_string(),
+        })
+    }
+
+    fn project(&self, x: f64, y: f64) -> (f64, f64) {
+        self.crs.to_wgs84(x, y).unwrap_or((x, y))
+    }
+
+    /// Returns the outline of a bounding volume as a closed WGS84 ring.
+    pub(crate) fn footprint(&self, volume: &BoundingVolume) -> Vec<(f64, f64)> {
+        let mut ring = match volume {
+            BoundingVolume::Obb {
+                center,
+                half_size,
+                quaternion,
+            } => {
+                let mut corners = Vec::with_capacity(8);
+                for &sx in &[-1.0, 1.0] {
+                    for &sy in &[-1.0, 1.0] {
+                        for &sz in &[-1.0, 1.0] {
+                            let offset = geo::rotate(
+                                *quaternion,
+                                [sx * half_size[0], sy * half_size[1], sz * half_size[2]],
+                            );
+                            corners.push(self.offset_point(center, offset));
+                        }
+                    }
+                }
+                geo::convex_hull(corners)
+            }
+            BoundingVolume::Mbs { center, radius } => (0..CIRCLE_SEGMENTS)
+                .map(|i| {
+                    let angle = 2.0 * std::f64::consts::PI * (i as f64) / (CIRCLE_SEGMENTS as f64);
+                    let east = radius * angle.cos();
+                    let north = radius * angle.sin();
+                    if self.crs.is_geographic() {
+                        geo::offset_geographic(center[0], center[1], east, north)
+                    } else {
+                        self.project(center[0] + east, center[1] + north)
+                    }
+                })
+                .collect(),
+        };
+        if let Some(&first) = ring.first() {
+            ring.push(first);
+        }
+        ring
+    }
+
+    fn offset_point(&self, center: &[f64; 3], offset: [f64; 3]) -> (f64, f64) {
+        if self.crs.is_geographic() {
+            // Global scenes orient their boxes relative to earth-centered,
+            // earth-fixed axes, while the center is geodetic.
+            let c = geo::geodetic_to_ecef(center[0], center[1], center[2]);
+            let (lon, lat, _) =
+                geo::ecef_to_geodetic([c[0] + offset[0], c[1] + offset[1], c[2] + offset[2]]);
+            (lon, lat)
+        } else {
+            self.project(center[0] + offset[0], center[1] + offset[1])
+        }
+    }
+
+    /// Returns the outline of a `[xmin, ymin, zmin, xmax, ymax, zmax]`
+    /// bounding box as a closed WGS84 ring.
+    fn rectangle(&self, mbb: &[f64]) -> Vec<(f64, f64)> {
+        let (xmin, ymin, xmax, ymax) = (mbb[0], mbb[1], mbb[3], mbb[4]);
+        vec![
+            self.project(xmin, ymin),
+            self.project(xmax, ymin),
+            self.project(xmax, ymax),
+            self.project(xmin, ymax),
+            self.project(xmin, ymin),
+        ]
+    }
+}
+
+pub(crate) fn polygon_geometry(ring: &[(f64, f64)]) -> Value {
+    let coordinates = ring
+        .iter()
+        .map(|&(x, y)| Value::Array(vec![Value::from(x), Value::from(y)]))
+        .collect();
+    Value::Object(vec![
+        ("type".to_string(), Value::from("Polygon")),
+        (
+            "coordinates".to_string(),
+            Value::Array(vec![Value::Array(coordinates)]),
+        ),
+    ])
+}
+
+pub(crate) fn feature(geometry: Value, properties: Vec<(String, Value)>) -> Value {
+    Value::Object(vec![
+        ("type".to_string(), Value::from("Feature")),
+        ("geometry".to_string(), geometry),
+        ("properties".to_string(), Value::Object(properties)),
+    ])
+}
+
+pub(crate) fn feature_collection(features: Vec<Value>) -> Value {
+    Value::Object(vec![
+        ("type".to_string(), Value::from("FeatureCollection")),
+        ("features".to_string(), Value::Array(features)),
+    ])
+}
+
+fn resource_sizes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<HashMap<String, ResourceSizes>, Error> {
+    let mut sizes: HashMap<String, ResourceSizes> = HashMap::new();
+    for entry_idx in 0..archive.len() {
+        let entry = archive.by_index(entry_idx)?;
+        let name = match entry.name().strip_prefix(layer.root.as_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut components = name.splitn(3, '/');
+        if let (Some("nodes"), Some(resource), Some(_)) =
+            (components.next(), components.next(), components.next())
+        {
+            let resource_sizes = sizes.entry(resource.to_string()).or_default();
+            resource_sizes.entries += 1;
+            resource_sizes.compressed += entry.compressed_size();
+            resource_sizes.uncompressed += entry.size();
+        }
+    }
+    Ok(sizes)
+}
+
+fn node_properties(node: &Node, sizes: ResourceSizes) -> Vec<(String, Value)> {
+    let mut properties = vec![
+        ("nodeId".to_string(), Value::from(node.id.as_str())),
+        ("level".to_string(), Value::from(node.level)),
+    ];
+    if let Some(lod_threshold) = node.lod_threshold {
+        properties.push(("lodThreshold".to_string(), Value::from(lod_threshold)));
+    }
+    properties.push(("entryCount".to_string(), Value::from(sizes.entries)));
+    properties.push(("compressedSize".to_string(), Value::from(sizes.compressed)));
+    properties.push((
+        "uncompressedSize".to_string(),
+        Value::from(sizes.uncompressed),
+    ));
+    properties
+}
+
+/// Reads per-feature bounding boxes from the legacy 3DObject feature
+/// documents of a node, if there are any.
+fn feature_boxes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    node: &Node,
+) -> Result<Vec<(Value, Vec<f64>)>, Error> {
+    let mut boxes = Vec::new();
+    for feature_idx in 0.. {
+        let document_name = layer.entry_name(&format!(
+            "nodes/{}/features/{}.json",
+            node.resource, feature_idx
+        ));
+        let document = match i3s::read_document(archive, &document_name)? {
+            Some(document) => document,
+            None => break,
+        };
+        let feature_data = document.get("featureData").and_then(Value::as_array);
+        for feature in feature_data.into_iter().flatten() {
+            let mbb = feature.get("mbb").and_then(Value::as_f64_vec);
+            if let Some(mbb) = mbb.filter(|mbb| mbb.len() == 6) {
+                let id = feature.get("id").cloned().unwrap_or(Value::Null);
+                boxes.push((id, mbb));
+            }
+        }
+    }
+    Ok(boxes)
+}
+
+/// Builds a GeoJSON feature collection with one polygon per top-level node,
+/// or one per feature for 3DObject layers which carry feature data.
+pub fn footprints<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Value, Error> {
+    let layer = i3s::read_scene_layer(archive)?;
+    let hierarchy: Hierarchy = i3s::load_hierarchy(archive, &layer)?;
+    let sizes = resource_sizes(archive, &layer)?;
+    let projector = Projector::for_layer(&layer);
+    if let Some(warning) = projector.unsupported_warning(&layer) {
+        warn!("{}", warning);
+    }
+
+    let is_3d_object = layer.layer_type.as_deref() == Some("3DObject");
+    let mut features = Vec::new();
+    for node_idx in hierarchy.top_level_nodes() {
+        let node = &hierarchy.nodes[node_idx];
+        let node_sizes = sizes.get(&node.resource).cloned().unwrap_or_default();
+
+        let boxes = if is_3d_object {
+            feature_boxes(archive, &layer, node)?
+        } else {
+            Vec::new()
+        };
+
+        if !boxes.is_empty() {
+            for (feature_id, mbb) in boxes {
+                let mut properties = node_properties(node, node_sizes);
+                properties.insert(0, ("featureId".to_string(), feature_id));
+                features.push(feature(
+                    polygon_geometry(&projector.rectangle(&mbb)),
+                    properties,
+                ));
+            }
+        } else if let Some(volume) = &node.volume {
+            features.push(feature(
+                polygon_geometry(&projector.footprint(volume)),
+                node_properties(node, node_sizes),
+            ));
+        } else {
+            warn!(
+                "Warning: node {} has no bounding volume and is omitted",
+                node.id
+            );
+        }
+    }
+
+    Ok(feature_collection(features))
+}
+
+/// Writes the footprints of a package to `output_path` as GeoJSON, as
+/// `footprints` builds them. Returns the number of features written.
+pub fn export_footprints(slpk_file_path: &Path, output_path: &Path) -> Result<usize, Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let collection = footprints(&mut slpk_archive)?;
+    let feature_count = collection
+        .get("features")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+
+    let mut output_file = File::create(output_path)?;
+    output_file.write_all(collection.to_pretty_string(2).as_bytes())?;
+    Ok(feature_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn build_package(entries: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn node_page_footprints_in_web_mercator() {
+        let mut archive = build_package(&[
+            (
+                "3dSceneLayer.json",
+                r#"{"layerType":"IntegratedMesh","spatialReference":{"wkid":102100,"latestWkid":3857},
+                   "nodePages":{"nodesPerPage":64}}"#,
+            ),
+            (
+                "nodepages/0.json",
+                r#"{"nodes":[
+                    {"index":0,"children":[1],"obb":{"center":[0,0,0],"halfSize":[2000,2000,10],"quaternion":[0,0,0,1]}},
+                    {"index":1,"parentIndex":0,"lodThreshold":50,"obb":{"center":[1000,1000,0],"halfSize":[500,500,10],"quaternion":[0,0,0,1]}}
+                ]}"#,
+            ),
+            ("nodes/1/geometries/0.bin", "0123456789"),
+        ]);
+
+        let collection = footprints(&mut archive).unwrap();
+        let features = collection.get("features").unwrap().as_array().unwrap();
+        assert_eq!(features.len(), 1);
+
+        let properties = features[0].get("properties").unwrap();
+        assert_eq!(properties.get("nodeId").unwrap().as_str(), Some("1"));
+        assert_eq!(properties.get("level").unwrap().as_u64(), Some(1));
+        assert_eq!(
+            properties.get("uncompressedSize").unwrap().as_u64(),
+            Some(10)
+        );
+
+        let ring = features[0]
+            .get("geometry")
+            .unwrap()
+            .get("coordinates")
+            .unwrap()
+            .as_array()
+            .unwrap()[0]
+            .as_array()
+            .unwrap()
+            .clone();
+        assert_eq!(ring.len(), 5);
+        assert_eq!(ring.first(), ring.last());
+        let (min_lon, _) = geo::web_mercator_to_wgs84(500.0, 500.0);
+        let corner = ring[0].as_f64_vec().unwrap();
+        assert!((corner[0] - min_lon).abs() < 1e-9);
+
+        // The collection must survive a round trip through the parser.
+        assert!(json::parse(&collection.to_pretty_string(2)).is_ok());
+    }
+
+    #[test]
+    fn unsupported_crs_keeps_coordinates() {
+        let mut archive = build_package(&[
+            (
+                "3dSceneLayer.json",
+                r#"{"layerType":"3DObject","spatialReference":{"wkid":2193},"store":{"rootNode":"./nodes/root"}}"#,
+            ),
+            (
+                "nodes/root/3dNodeIndexDocument.json",
+                r#"{"id":"root","mbs":[100,200,0,10],"children":[{"id":"0"}]}"#,
+            ),
+            (
+                "nodes/0/3dNodeIndexDocument.json",
+                r#"{"id":"0","mbs":[100,200,0,10],"featureData":[{"href":"./features/0"}]}"#,
+            ),
+            (
+                "nodes/0/features/0.json",
+                r#"{"featureData":[{"id":7,"mbb":[90,190,0,110,210,5]}]}"#,
+            ),
+        ]);
+
+        let collection = footprints(&mut archive).unwrap();
+        let features = collection.get("features").unwrap().as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        let properties = features[0].get("properties").unwrap();
+        assert_eq!(properties.get("featureId").unwrap().as_u64(), Some(7));
+        let first_point = features[0]
+            .get("geometry")
+            .unwrap()
+            .get("coordinates")
+            .unwrap()
+            .as_array()
+            .unwrap()[0]
+            .as_array()
+            .unwrap()[0]
+            .as_f64_vec()
+            .unwrap();
+        assert_eq!(first_point, vec![90.0, 190.0]);
+    }
+}
@@ -0,0 +1,239 @@
+// Mutation tests of the code which parses packages, in place of fuzz targets:
+// cargo-fuzz needs a library target and a nightly toolchain, and this crate
+// is a single binary. Each test corrupts a seed package or document in a
+// few thousand seeded ways and only checks that parsing returns, with an
+// error or without, instead of panicking, overflowing its stack or
+// allocating without bound. The inputs which once crashed are kept as
+// fixtures, so that they are tried on every run.
+
+use crate::archive::raw::RawArchive;
+use crate::i3s;
+use crate::image;
+use crate::json;
+use crate::synthetic::{SyntheticPackage, TempDir};
+use crate::unpack::{unpack_with_options, UnpackOptions};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// A xorshift generator, so that every run tries the same inputs and a
+/// failing one can be found again from its iteration.
+struct Mutator(u64);
+
+impl Mutator {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    /// Corrupts `input` in one of the ways which tend to find bugs in
+    /// parsers: flipped bits, lengths and offsets set to their extremes,
+    /// bytes of structure inserted or repeated, and truncation.
+    fn mutate(&mut self, input: &mut Vec<u8>, structure: &[u8]) {
+        let at = self.below(input.len());
+        match self.below(6) {
+            0 => {
+                if let Some(byte) = input.get_mut(at) {
+                    *byte ^= 1 << self.below(8);
+                }
+            }
+            1 => {
+                let extreme: [u8; 4] = match self.below(4) {
+                    0 => [0; 4],
+                    1 => [0xff; 4],
+                    2 => [0xff, 0xff, 0xff, 0x7f],
+                    _ => [0xff, 0xff, 0, 0],
+                };
+                for (offset, byte) in extreme.iter().enumerate() {
+                    if let Some(target) = input.get_mut(at + offset) {
+                        *target = *byte;
+                    }
+                }
+            }
+            2 => {
+                let byte = structure[self.below(structure.len())];
+                input.insert(at.min(input.len()), byte);
+            }
+            3 => {
+                let len = 1 + self.below(8).min(input.len().saturating_sub(at));
+                let chunk = input[at.min(input.len())..(at + len).min(input.len())].to_vec();
+                let repeats = 1 + self.below(20_000);
+                let tail = input.split_off(at.min(input.len()));
+                for _ in 0..repeats {
+                    input.extend_from_slice(&chunk);
+                }
+                input.extend_from_slice(&tail);
+            }
+            4 => input.truncate(at),
+            _ => {
+                if at < input.len() {
+                    input.remove(at);
+                }
+            }
+        }
+    }
+}
+
+/// Reads the zip directory and then every entry, the way unpacking and
+/// checking a package do.
+fn read_every_entry(package: &[u8]) {
+    let _ = RawArchive::read(&mut Cursor::new(package));
+    let mut archive = match ZipArchive::new(Cursor::new(package)) {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+    for i in 0..archive.len() {
+        if let Ok(mut entry) = archive.by_index(i) {
+            let _ = std::io::copy(&mut entry, &mut std::io::sink());
+        }
+    }
+    let _ = i3s::read_layers(&mut archive);
+}
+
+/// A package holding only a node page layer with `node_page` as its first
+/// page.
+fn node_page_package(node_page: &[u8]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    writer.start_file("3dSceneLayer.json", options).unwrap();
+    writer
+        .write_all(br#"{"layerType":"IntegratedMesh","nodePages":{}}"#)
+        .unwrap();
+    writer.start_file("nodepages/0.json", options).unwrap();
+    writer.write_all(node_page).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+/// Loads the hierarchy of a package and walks it from the top.
+fn walk_hierarchy(package: &[u8]) {
+    let mut archive = match ZipArchive::new(Cursor::new(package)) {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+    let layer = match i3s::read_scene_layer(&mut archive) {
+        Ok(layer) => layer,
+        Err(_) => return,
+    };
+    if let Ok(hierarchy) = i3s::load_hierarchy(&mut archive, &layer) {
+        for node in hierarchy.top_level_nodes() {
+            let _ = &hierarchy.nodes[node];
+        }
+    }
+}
+
+/// Inputs which crashed, by what they crashed.
+fn fixtures() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        // Each level of nesting was a level of recursion.
+        ("deeply nested document", "[".repeat(200_000).into_bytes()),
+        (
+            "deeply nested members",
+            r#"{"a":"#.repeat(100_000).into_bytes(),
+        ),
+    ]
+}
+
+#[test]
+fn fixtures_which_once_crashed_are_rejected() {
+    for (name, document) in fixtures() {
+        assert!(json::parse_bytes(&document).is_err(), "{}", name);
+        walk_hierarchy(&node_page_package(&document));
+    }
+}
+
+#[test]
+fn zip_directory_and_entries_survive_corruption() {
+    let seed = SyntheticPackage::standard().to_bytes().unwrap();
+    let mut mutator = Mutator(0x5eed_0001);
+    for _ in 0..2_000 {
+        let mut package = seed.clone();
+        for _ in 0..1 + mutator.below(4) {
+            mutator.mutate(&mut package, b"PK\x03\x04\x01\x02\x05\x06\x07\x08");
+        }
+        read_every_entry(&package);
+    }
+}
+
+#[test]
+fn node_page_walk_survives_corruption() {
+    let seed = br#"{"nodes":[{"index":0,"children":[1,2],"obb":{"center":[0,0,0],"halfSize":[1,1,1],"quaternion":[0,0,0,1]}},{"index":1,"children":[3],"lodThreshold":10,"mesh":{"geometry":{"resource":1,"vertexCount":3}}},{"index":2,"mbs":[0,0,0,1]},{"index":3,"children":[0]}]}"#;
+    let mut mutator = Mutator(0x5eed_0002);
+    for _ in 0..2_000 {
+        let mut page = seed.to_vec();
+        for _ in 0..1 + mutator.below(4) {
+            mutator.mutate(&mut page, b"[]{},:\"0123456789-.e");
+        }
+        walk_hierarchy(&node_page_package(&page));
+    }
+}
+
+#[test]
+fn unpacking_gzipped_documents_survives_corruption() {
+    let dir = TempDir::new("fuzz");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(br#"{"layerType":"IntegratedMesh","store":{"version":"1.7"}}"#)
+        .unwrap();
+    let seed = encoder.finish().unwrap();
+    let paranoid = UnpackOptions::new().paranoid().threads(1);
+    let trusting = UnpackOptions::new().threads(1);
+    let mut mutator = Mutator(0x5eed_0003);
+    for iteration in 0..300 {
+        let mut document = seed.clone();
+        for _ in 0..1 + mutator.below(2) {
+            mutator.mutate(&mut document, b"\x1f\x8b\x08\x00\xff[]{}");
+        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("3dSceneLayer.json.gz", options).unwrap();
+        writer.write_all(&document).unwrap();
+        let path = dir.join("package.slpk");
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+
+        let options = if iteration % 2 == 0 {
+            &paranoid
+        } else {
+            &trusting
+        };
+        let _ = unpack_with_options(&path, options);
+        // Whatever was unpacked has to parse, or fail to, without crashing.
+        if let Ok(contents) = std::fs::read(dir.join("package/3dSceneLayer.json")) {
+            let _ = json::parse_bytes(&contents);
+        }
+    }
+}
+
+#[test]
+fn texture_decoding_survives_corruption() {
+    let mut dds = b"DDS ".to_vec();
+    dds.resize(128, 0);
+    dds[12] = 8;
+    dds[16] = 8;
+    dds[84..88].copy_from_slice(b"DXT5");
+    dds.extend_from_slice(&[0x55; 4 * 16]);
+    let seeds = [
+        (
+            image::tests::gray_jpeg(),
+            &b"\xff\xc0\xc4\xda\xdb\xdd\xd0\x00\x01\x11"[..],
+        ),
+        (dds, &b"DDS DXT1DXT3DXT5\x00\xff"[..]),
+    ];
+    let mut mutator = Mutator(0x5eed_0004);
+    for (seed, structure) in &seeds {
+        for _ in 0..2_000 {
+            let mut texture = seed.clone();
+            for _ in 0..1 + mutator.below(4) {
+                mutator.mutate(&mut texture, structure);
+            }
+            let _ = image::decode(&texture);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+// Lightweight coordinate transforms for the handful of spatial references
+// which scene layer packages commonly use. This is not a general purpose
+// projection library; anything not listed in `Crs::from_wkid` is reported
+// as unsupported so callers can decide how to degrade.
+
+use std::f64::consts::PI;
+
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+const UTM_K0: f64 = 0.9996;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Crs {
+    /// Longitude/latitude in degrees, heights in meters.
+    Geographic,
+    WebMercator,
+    Utm {
+        zone: u8,
+        north: bool,
+    },
+    Unsupported(u32),
+}
+
+impl Crs {
+    pub fn from_wkid(wkid: u32) -> Crs {
+        match wkid {
+            4326 | 4979 | 4269 | 4258 => Crs::Geographic,
+            3857 | 102_100 | 102_113 | 900_913 => Crs::WebMercator,
+            32601..=32660 => Crs::Utm {
+                zone: (wkid - 32600) as u8,
+                north: true,
+            },
+            32701..=32760 => Crs::Utm {
+                zone: (wkid - 32700) as u8,
+                north: false,
+            },
+            // NAD83 and ETRS89 UTM zones. The GRS80 ellipsoid differs from
+            // WGS84 by far less than the precision needed here.
+            26901..=26923 => Crs::Utm {
+                zone: (wkid - 26900) as u8,
+                north: true,
+            },
+            25828..=25838 => Crs::Utm {
+                zone: (wkid - 25800) as u8,
+                north: true,
+            },
+            other => Crs::Unsupported(other),
+        }
+    }
+
+    pub fn is_geographic(self) -> bool {
+        self == Crs::Geographic
+    }
+
+    /// Converts a planar coordinate into WGS84 longitude/latitude degrees.
+    /// Returns `None` for unsupported spatial references.
+    pub fn to_wgs84(self, x: f64, y: f64) -> Option<(f64, f64)> {
+        match self {
+            Crs::Geographic => Some((x, y)),
+            Crs::WebMercator => Some(web_mercator_to_wgs84(x, y)),
+            Crs::Utm { zone, north } => Some(utm_to_wgs84(zone, north, x, y)),
+            Crs::Unsupported(_) => None,
+        }
+    }
+
+    /// Converts WGS84 longitude/latitude degrees into a planar coordinate,
+    /// the other way round from `to_wgs84`.
+    pub fn project(self, lon: f64, lat: f64) -> Option<(f64, f64)> {
+        match self {
+            Crs::Geographic => Some((lon, lat)),
+            Crs::WebMercator => Some(wgs84_to_web_mercator(lon, lat)),
+            Crs::Utm { zone, north } => Some(wgs84_to_utm(zone, north, lon, lat)),
+            Crs::Unsupported(_) => None,
+        }
+    }
+}
+
+pub fn web_mercator_to_wgs84(x: f64, y: f64) -> (f64, f64) {
+    let lon = (x / WGS84_A).to_degrees();
+    let lat = (2.0 * (y / WGS84_A).exp().atan() - PI / 2.0).to_degrees();
+    (lon, lat)
+}
+
+pub fn wgs84_to_web_mercator(lon: f64, lat: f64) -> (f64, f64) {
+    let x = WGS84_A * lon.to_radians();
+    let y = WGS84_A * (PI / 4.0 + lat.to_radians() / 2.0).tan().ln();
+    (x, y)
+}
+
+/// Forward transverse mercator, following Snyder's series expansion.
+pub fn wgs84_to_utm(zone: u8, north: bool, lon: f64, lat: f64) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let ep2 = e2 / (1.0 - e2);
+    let central_meridian = f64::from(zone) * 6.0 - 183.0;
+    let phi = lat.to_radians();
+    let (sin_phi, cos_phi) = (phi.sin(), phi.cos());
+
+    let n = WGS84_A / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+    let t = phi.tan().powi(2);
+    let c = ep2 * cos_phi * cos_phi;
+    let a = cos_phi * (lon - central_meridian).to_radians();
+    let m = WGS84_A
+        * ((1.0 - e2 / 4.0 - 3.0 * e2 * e2 / 64.0 - 5.0 * e2.powi(3) / 256.0) * phi
+            - (3.0 * e2 / 8.0 + 3.0 * e2 * e2 / 32.0 + 45.0 * e2.powi(3) / 1024.0)
+                * (2.0 * phi).sin()
+            + (15.0 * e2 * e2 / 256.0 + 45.0 * e2.powi(3) / 1024.0) * (4.0 * phi).sin()
+            - (35.0 * e2.powi(3) / 3072.0) * (6.0 * phi).sin());
+
+    let x = UTM_K0
+        * n
+        * (a + (1.0 - t + c) * a.powi(3) / 6.0
+            + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0);
+    let y = UTM_K0
+        * (m + n
+            * phi.tan()
+            * (a * a / 2.0
+                + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+    let northing = if north { y } else { y + 10_000_000.0 };
+    (x + 500_000.0, northing)
+}
+
+/// Inverse transverse mercator, following Snyder's series expansion.
+pub fn utm_to_wgs84(zone: u8, north: bool, easting: f64, northing: f64) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let ep2 = e2 / (1.0 - e2);
+    let x = easting - 500_000.0;
+    let y = if north {
+        northing
+    } else {
+        northing - 10_000_000.0
+    };
+
+    let m = y / UTM_K0;
+    let mu = m / (WGS84_A * (1.0 - e2 / 4.0 - 3.0 * e2 * e2 / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let sin_phi1 = phi1.sin();
+    let cos_phi1 = phi1.cos();
+    let c1 = ep2 * cos_phi1 * cos_phi1;
+    let t1 = phi1.tan().powi(2);
+    let n1 = WGS84_A / (1.0 - e2 * sin_phi1 * sin_phi1).sqrt();
+    let r1 = WGS84_A * (1.0 - e2) / (1.0 - e2 * sin_phi1 * sin_phi1).powf(1.5);
+    let d = x / (n1 * UTM_K0);
+
+    let lat = phi1
+        - (n1 * phi1.tan() / r1)
+            * (d * d / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1 - 252.0 * ep2 - 3.0 * c1 * c1)
+                    * d.powi(6)
+                    / 720.0);
+    let lon = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+        + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1) * d.powi(5)
+            / 120.0)
+        / cos_phi1;
+
+    let central_meridian = f64::from(zone) * 6.0 - 183.0;
+    (central_meridian + lon.to_degrees(), lat.to_degrees())
+}
+
+/// Converts longitude/latitude degrees and ellipsoidal height to
+/// earth-centered, earth-fixed coordinates.
+pub fn geodetic_to_ecef(lon: f64, lat: f64, height: f64) -> [f64; 3] {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let (lon, lat) = (lon.to_radians(), lat.to_radians());
+    let n = WGS84_A / (1.0 - e2 * lat.sin() * lat.sin()).sqrt();
+    [
+        (n + height) * lat.cos() * lon.cos(),
+        (n + height) * lat.cos() * lon.sin(),
+        (n * (1.0 - e2) + height) * lat.sin(),
+    ]
+}
+
+/// Inverse of `geodetic_to_ecef`, using Bowring's method.
+pub fn ecef_to_geodetic(p: [f64; 3]) -> (f64, f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let b = WGS84_A * (1.0 - WGS84_F);
+    let ep2 = (WGS84_A * WGS84_A - b * b) / (b * b);
+    let r = (p[0] * p[0] + p[1] * p[1]).sqrt();
+    let theta = (p[2] * WGS84_A).atan2(r * b);
+    let lat = (p[2] + ep2 * b * theta.sin().powi(3)).atan2(r - e2 * WGS84_A * theta.cos().powi(3));
+    let n = WGS84_A / (1.0 - e2 * lat.sin() * lat.sin()).sqrt();
+    let height = if lat.cos().abs() > 1e-10 {
+        r / lat.cos() - n
+    } else {
+        p[2].abs() - b
+    };
+    (p[1].atan2(p[0]).to_degrees(), lat.to_degrees(), height)
+}
+
+/// Turns a vector given in east, north and up components at a geographic
+/// point into earth-centered, earth-fixed axes.
+pub fn enu_to_ecef(lon: f64, lat: f64, v: [f64; 3]) -> [f64; 3] {
+    let (sin_lon, cos_lon) = lon.to_radians().sin_cos();
+    let (sin_lat, cos_lat) = lat.to_radians().sin_cos();
+    let [east, north, up] = v;
+    [
+        -sin_lon * east - sin_lat * cos_lon * north + cos_lat * cos_lon * up,
+        cos_lon * east - sin_lat * sin_lon * north + cos_lat * sin_lon * up,
+        cos_lat * north + sin_lat * up,
+    ]
+}
+
+/// Moves a geographic point by an east/north offset in meters, using a
+/// spherical approximation which is adequate for node-sized distances.
+pub fn offset_geographic(lon: f64, lat: f64, east: f64, north: f64) -> (f64, f64) {
+    let dlat = (north / WGS84_A).to_degrees();
+    let dlon = (east / (WGS84_A * lat.to_radians().cos().max(1e-12))).to_degrees();
+    (lon + dlon, lat + dlat)
+}
+
+/// Rotates a vector by a unit quaternion given as `[x, y, z, w]`, the
+/// order used by I3S oriented bounding boxes.
+pub fn rotate(q: [f64; 4], v: [f64; 3]) -> [f64; 3] {
+    let [qx, qy, qz, qw] = q;
+    // t = 2 * cross(q.xyz, v)
+    let tx = 2.0 * (qy * v[2] - qz * v[1]);
+    let ty = 2.0 * (qz * v[0] - qx * v[2]);
+    let tz = 2.0 * (qx * v[1] - qy * v[0]);
+    [
+        v[0] + qw * tx + (qy * tz - qz * ty),
+        v[1] + qw * ty + (qz * tx - qx * tz),
+        v[2] + qw * tz + (qx * ty - qy * tx),
+    ]
+}
+
+/// Returns the convex hull of a point set in counter-clockwise order,
+/// using Andrew's monotone chain algorithm.
+pub fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    fn cross(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    }
+
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() * 2);
+    for &p in points.iter() {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    // The upper hull must not pop points that belong to the lower hull.
+    let lower_len = hull.len() + 1;
+    for &p in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+        {
+            hull.pop();
+        }
+        hull.push(p);
+    }
+    hull.pop();
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64), tolerance: f64) {
+        assert!(
+            (actual.0 - expected.0).abs() < tolerance && (actual.1 - expected.1).abs() < tolerance,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn web_mercator_extents() {
+        assert_close(web_mercator_to_wgs84(0.0, 0.0), (0.0, 0.0), 1e-12);
+        assert_close(
+            web_mercator_to_wgs84(20_037_508.342_789_244, 0.0),
+            (180.0, 0.0),
+            1e-9,
+        );
+        assert_close(
+            web_mercator_to_wgs84(0.0, 20_037_508.342_789_244),
+            (0.0, 85.051_128_779_806_6),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn utm_central_meridian() {
+        assert_close(utm_to_wgs84(33, true, 500_000.0, 0.0), (15.0, 0.0), 1e-9);
+        assert_close(
+            utm_to_wgs84(32, true, 500_000.0, 4_982_950.4),
+            (9.0, 45.0),
+            1e-5,
+        );
+        assert_close(
+            Crs::from_wkid(32719)
+                .to_wgs84(500_000.0, 10_000_000.0)
+                .unwrap(),
+            (-69.0, 0.0),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn projections_round_trip() {
+        assert_close(
+            wgs84_to_web_mercator(180.0, 85.051_128_779_806_6),
+            (20_037_508.342_789_244, 20_037_508.342_789_244),
+            1e-6,
+        );
+        assert_close(
+            wgs84_to_utm(32, true, 9.0, 45.0),
+            (500_000.0, 4_982_950.4),
+            0.1,
+        );
+        for &(zone, north, lon, lat) in &[(10, true, -123.1, 49.25), (19, false, -70.2, -33.4)] {
+            let crs = Crs::Utm { zone, north };
+            let (x, y) = crs.project(lon, lat).unwrap();
+            assert_close(crs.to_wgs84(x, y).unwrap(), (lon, lat), 1e-8);
+        }
+        let (x, y) = Crs::WebMercator.project(-123.1, 49.25).unwrap();
+        assert_close(web_mercator_to_wgs84(x, y), (-123.1, 49.25), 1e-9);
+        assert_eq!(Crs::Unsupported(2193).project(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn up_points_away_from_the_center_of_the_earth() {
+        let up = enu_to_ecef(-123.1, 49.25, [0.0, 0.0, 1.0]);
+        let point = geodetic_to_ecef(-123.1, 49.25, 0.0);
+        let above = geodetic_to_ecef(-123.1, 49.25, 1.0);
+        for axis in 0..3 {
+            assert!((above[axis] - point[axis] - up[axis]).abs() < 1e-6);
+        }
+        let east = enu_to_ecef(90.0, 0.0, [1.0, 0.0, 0.0]);
+        assert!((east[0] + 1.0).abs() < 1e-12 && east[1].abs() < 1e-12);
+    }
+
+    #[test]
+    fn ecef_round_trip() {
+        let ecef = geodetic_to_ecef(-123.1, 49.25, 120.0);
+        let (lon, lat, height) = ecef_to_geodetic(ecef);
+        assert_close((lon, lat), (-123.1, 49.25), 1e-9);
+        assert!((height - 120.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn hull_of_square_with_interior_point() {
+        let hull = convex_hull(vec![
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 0.0),
+            (0.0, 2.0),
+            (2.0, 2.0),
+        ]);
+        assert_eq!(hull, vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+    }
+}
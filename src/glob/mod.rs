@@ -0,0 +1,244 @@
+// Shell-style wildcard matching over `/`-separated paths. Supported syntax:
+// `*` matches within a single path component, `**` matches across any
+// number of components, `?` matches one character, and `[abc]`/`[a-z]`
+// match a character class (`[!abc]` negates it).
+
+use failure::Error;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    Star,
+    DoubleStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Pattern {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 2;
+                    // "**/" also matches zero components, so swallow the slash.
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                    }
+                    tokens.push(Token::DoubleStar);
+                    continue;
+                }
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::AnyChar),
+                '[' => {
+                    if let Some(end) = chars[i + 1..].iter().position(|&c| c == ']') {
+                        let mut class = &chars[i + 1..i + 1 + end];
+                        let negated = class.first() == Some(&'!');
+                        if negated {
+                            class = &class[1..];
+                        }
+                        let mut ranges = Vec::new();
+                        let mut j = 0;
+                        while j < class.len() {
+                            if j + 2 < class.len() && class[j + 1] == '-' {
+                                ranges.push((class[j], class[j + 2]));
+                                j += 3;
+                            } else {
+                                ranges.push((class[j], class[j]));
+                                j += 1;
+                            }
+                        }
+                        tokens.push(Token::Class { negated, ranges });
+                        i += end + 2;
+                        continue;
+                    }
+                    tokens.push(Token::Literal('['));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+            i += 1;
+        }
+        Pattern { tokens }
+    }
+
+    pub fn has_wildcards(text: &str) -> bool {
+        text.contains(['*', '?', '['])
+    }
+
+    /// Matches the whole of `path`, which must use `/` as its separator.
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        matches_from(&self.tokens, &path)
+    }
+
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.matches(&path.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+fn matches_from(tokens: &[Token], path: &[char]) -> bool {
+    match tokens.split_first() {
+        None => path.is_empty(),
+        Some((Token::Literal(c), rest)) => {
+            path.first() == Some(c) && matches_from(rest, &path[1..])
+        }
+        Some((Token::AnyChar, rest)) => {
+            matches!(path.first(), Some(&c) if c != '/') && matches_from(rest, &path[1..])
+        }
+        Some((Token::Class { negated, ranges }, rest)) => match path.first() {
+            Some(&c) if c != '/' => {
+                let in_class = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                in_class != *negated && matches_from(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Some((Token::Star, rest)) => {
+            let component_len = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+            (0..=component_len).any(|n| matches_from(rest, &path[n..]))
+        }
+        Some((Token::DoubleStar, rest)) => (0..=path.len()).any(|n| {
+            // Only resume matching at a component boundary.
+            (n == 0 || path[n - 1] == '/' || n == path.len()) && matches_from(rest, &path[n..])
+        }),
+    }
+}
+
+/// Collects the files under `dir`, descending at most `depth` levels into
+/// subfolders, or without limit when `depth` is `None`.
+pub fn walk(dir: &Path, depth: Option<usize>, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            match depth {
+                Some(0) => {}
+                Some(d) => walk(&path, Some(d - 1), files)?,
+                None => walk(&path, None, files)?,
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expands a wildcard pattern into the files which match it, sorted by
+/// path. A pattern without wildcards is returned as-is when it exists.
+/// This doesn't rely on the shell, which doesn't expand patterns on Windows.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let pattern = pattern.replace('\\', "/");
+    if !Pattern::has_wildcards(&pattern) {
+        let path = PathBuf::from(&pattern);
+        return Ok(if path.exists() {
+            vec![path]
+        } else {
+            Vec::new()
+        });
+    }
+
+    // Walk from the deepest directory which doesn't contain wildcards.
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal_len = components
+        .iter()
+        .position(|c| Pattern::has_wildcards(c))
+        .unwrap_or(components.len());
+    let base = components[..literal_len].join("/");
+    let base_dir = if literal_len == 0 {
+        PathBuf::from(".")
+    } else if base.is_empty() {
+        PathBuf::from("/")
+    } else {
+        PathBuf::from(&base)
+    };
+
+    let remaining = &components[literal_len..];
+    let depth = if remaining.contains(&"**") || remaining.iter().any(|c| c.contains("**")) {
+        None
+    } else {
+        Some(remaining.len() - 1)
+    };
+
+    let mut files = Vec::new();
+    if base_dir.is_dir() {
+        walk(&base_dir, depth, &mut files)?;
+    }
+
+    let relative_pattern = Pattern::new(&remaining.join("/"));
+    let mut matched: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|path| {
+            path.strip_prefix(&base_dir)
+                .map(|relative| relative_pattern.matches_path(relative))
+                .unwrap_or(false)
+        })
+        .collect();
+    matched.sort();
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn single_star_stays_within_a_component() {
+        let pattern = Pattern::new("nodes/*/features/*");
+        assert!(pattern.matches("nodes/12/features/0.json.gz"));
+        assert!(!pattern.matches("nodes/12/textures/0.jpg"));
+        assert!(!pattern.matches("nodes/1/2/features/0"));
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        let pattern = Pattern::new("**/*.json.gz");
+        assert!(pattern.matches("3dSceneLayer.json.gz"));
+        assert!(pattern.matches("nodes/0/3dNodeIndexDocument.json.gz"));
+        assert!(!pattern.matches("nodes/0/geometries/0.bin.gz"));
+        assert!(Pattern::new("nodes/**").matches("nodes/0/textures/0.jpg"));
+    }
+
+    #[test]
+    fn character_classes() {
+        let pattern = Pattern::new("f_[0-9]?.bin");
+        assert!(pattern.matches("f_12.bin"));
+        assert!(!pattern.matches("f_a2.bin"));
+        assert!(Pattern::new("[!x]y").matches("ay"));
+        assert!(!Pattern::new("[!x]y").matches("xy"));
+    }
+
+    #[test]
+    fn expand_walks_directories() {
+        let root = TempDir::new("glob");
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        for name in &[
+            "one.slpk",
+            "two.slpk",
+            "notes.txt",
+            "a/three.slpk",
+            "a/b/four.slpk",
+        ] {
+            std::fs::write(root.join(name), b"").unwrap();
+        }
+        let root_pattern = root.to_string_lossy().replace('\\', "/");
+
+        let top_level = expand(&format!("{}/*.slpk", root_pattern)).unwrap();
+        assert_eq!(
+            top_level,
+            vec![root.join("one.slpk"), root.join("two.slpk")]
+        );
+
+        let recursive = expand(&format!("{}/**/*.slpk", root_pattern)).unwrap();
+        assert_eq!(recursive.len(), 4);
+    }
+}
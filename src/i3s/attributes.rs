@@ -0,0 +1,153 @@
+// The attributes of a layer are declared twice: `fields` says what each one
+// is, and `attributeStorageInfo` says where its values are, under the key
+// which also names its folder below each node (`attributes/f_0/`). Field
+// definitions carry no key, so the two arrays are matched up by name.
+
+use crate::json::Value;
+
+/// One attribute, from either or both of the arrays declaring it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    /// The type from `fields`, or `None` when no field defines it.
+    pub field_type: Option<String>,
+    /// The key from `attributeStorageInfo`, or `None` when nothing stores it.
+    pub key: Option<String>,
+    pub field_position: Option<usize>,
+    pub storage_position: Option<usize>,
+    /// Whether it is declared in a different place relative to the other
+    /// attributes in each array.
+    pub out_of_order: bool,
+}
+
+impl Attribute {
+    /// What is wrong with how the attribute is declared, if anything.
+    pub fn problem(&self) -> Option<&'static str> {
+        match (&self.field_type, &self.key) {
+            (_, None) => Some("has no attributeStorageInfo"),
+            (None, _) => Some("has no field definition"),
+            _ if self.out_of_order => {
+                Some("is in a different order in fields and attributeStorageInfo")
+            }
+            _ => None,
+        }
+    }
+}
+
+fn string_member(value: &Value, name: &str) -> Option<String> {
+    value.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+/// The attributes in the order of `fields`, followed by those which only
+/// `attributeStorageInfo` declares. Storage entries without a name are
+/// known by their key.
+pub fn declared_attributes(document: &Value) -> Vec<Attribute> {
+    let empty = Vec::new();
+    let fields = document
+        .get("fields")
+        .and_then(Value::as_array)
+        .unwrap_or(&empty);
+    let storage = document
+        .get("attributeStorageInfo")
+        .and_then(Value::as_array)
+        .unwrap_or(&empty);
+
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for (position, field) in fields.iter().enumerate() {
+        let name = match string_member(field, "name") {
+            Some(name) => name,
+            None => continue,
+        };
+        attributes.push(Attribute {
+            field_type: Some(string_member(field, "type").unwrap_or_default()),
+            name,
+            key: None,
+            field_position: Some(position),
+            storage_position: None,
+            out_of_order: false,
+        });
+    }
+    for (position, info) in storage.iter().enumerate() {
+        let key = string_member(info, "key");
+        let name = match string_member(info, "name").or_else(|| key.clone()) {
+            Some(name) => name,
+            None => continue,
+        };
+        let joined = attributes
+            .iter_mut()
+            .find(|a| a.name == name && a.storage_position.is_none());
+        match joined {
+            Some(attribute) => {
+                attribute.key = key;
+                attribute.storage_position = Some(position);
+            }
+            None => attributes.push(Attribute {
+                name,
+                field_type: None,
+                key,
+                field_position: None,
+                storage_position: Some(position),
+                out_of_order: false,
+            }),
+        }
+    }
+
+    // A missing attribute shifts the positions of the rest, so only the
+    // order of the attributes in both arrays is compared.
+    let mut in_both: Vec<(usize, usize, usize)> = attributes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, a)| match (a.field_position, a.storage_position) {
+            (Some(field), Some(storage)) => Some((i, field, storage)),
+            _ => None,
+        })
+        .collect();
+    let by_field: Vec<usize> = in_both.iter().map(|&(i, _, _)| i).collect();
+    in_both.sort_by_key(|&(_, _, storage)| storage);
+    for (rank, &(i, _, _)) in in_both.iter().enumerate() {
+        if by_field[rank] != i {
+            attributes[i].out_of_order = true;
+        }
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn joins_fields_with_their_storage() {
+        let document = json::parse_bytes(
+            br#"{"fields":[
+                    {"name":"OBJECTID","type":"esriFieldTypeOID"},
+                    {"name":"height","type":"esriFieldTypeDouble"},
+                    {"name":"roof","type":"esriFieldTypeString"},
+                    {"name":"color","type":"esriFieldTypeString"}],
+                "attributeStorageInfo":[
+                    {"key":"f_0","name":"OBJECTID"},
+                    {"key":"f_1","name":"roof"},
+                    {"key":"f_2","name":"height"},
+                    {"key":"f_3","name":"usage"}]}"#,
+        )
+        .unwrap();
+        let attributes = declared_attributes(&document);
+        let summary: Vec<(&str, Option<&str>, Option<&str>)> = attributes
+            .iter()
+            .map(|a| (a.name.as_str(), a.key.as_deref(), a.problem()))
+            .collect();
+        let swapped = Some("is in a different order in fields and attributeStorageInfo");
+        assert_eq!(
+            summary,
+            vec![
+                ("OBJECTID", Some("f_0"), None),
+                ("height", Some("f_2"), swapped),
+                ("roof", Some("f_1"), swapped),
+                ("color", None, Some("has no attributeStorageInfo")),
+                ("usage", Some("f_3"), Some("has no field definition")),
+            ]
+        );
+        assert!(declared_attributes(&json::parse_bytes(b"{}").unwrap()).is_empty());
+    }
+}
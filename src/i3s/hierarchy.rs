@@ -0,0 +1,378 @@
+use super::{read_document, SceneLayer};
+use crate::json::Value;
+use failure::Error;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::io::Seek;
+use zip::ZipArchive;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoundingVolume {
+    Obb {
+        center: [f64; 3],
+        half_size: [f64; 3],
+        quaternion: [f64; 4],
+    },
+    Mbs {
+        center: [f64; 3],
+        radius: f64,
+    },
+}
+
+impl BoundingVolume {
+    fn from_obb(obb: &Value) -> Option<BoundingVolume> {
+        let center = obb.get("center")?.as_f64_vec()?;
+        let half_size = obb.get("halfSize")?.as_f64_vec()?;
+        let quaternion = obb.get("quaternion")?.as_f64_vec()?;
+        if center.len() != 3 || half_size.len() != 3 || quaternion.len() != 4 {
+            return None;
+        }
+        Some(BoundingVolume::Obb {
+            center: [center[0], center[1], center[2]],
+            half_size: [half_size[0], half_size[1], half_size[2]],
+            quaternion: [quaternion[0], quaternion[1], quaternion[2], quaternion[3]],
+        })
+    }
+
+    fn from_mbs(mbs: &Value) -> Option<BoundingVolume> {
+        match mbs.as_f64_vec()?.as_slice() {
+            [x, y, z, r] => Some(BoundingVolume::Mbs {
+                center: [*x, *y, *z],
+                radius: *r,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The volume of a node of the node pages, or of a node index
+    /// document, preferring its oriented bounding box.
+    pub(crate) fn of_node(node: &Value) -> Option<BoundingVolume> {
+        node.get("obb")
+            .and_then(BoundingVolume::from_obb)
+            .or_else(|| node.get("mbs").and_then(BoundingVolume::from_mbs))
+    }
+
+    pub fn center(&self) -> [f64; 3] {
+        match self {
+            BoundingVolume::Obb { center, .. } | BoundingVolume::Mbs { center, .. } => *center,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    /// The node id as used by the package: a node page index for I3S 1.7+
+    /// packages, or the id from the node index document for older ones.
+    pub id: String,
+    /// Name of the folder under `nodes/` holding this node's resources.
+    pub resource: String,
+    /// The folders of the material and attributes of the node's mesh,
+    /// where they aren't that of its geometry. Only node pages record this.
+    pub other_resources: Vec<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Depth from the root, where the root node is level 0.
+    pub level: usize,
+    pub lod_threshold: Option<f64>,
+    pub volume: Option<BoundingVolume>,
+    /// Vertex count of the node's mesh. Only node pages record this.
+    pub vertex_count: Option<u64>,
+    /// Feature count of the node's mesh. Only node pages record this.
+    pub feature_count: Option<u64>,
+    /// Point count of a node of a point cloud, in place of its vertex and
+    /// feature counts.
+    pub point_count: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Hierarchy {
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+}
+
+impl Hierarchy {
+    /// Visits the nodes breadth-first from the roots, assigning levels.
+    /// Nodes unreachable from any root keep level 0.
+    fn assign_levels(&mut self) {
+        let mut queue: VecDeque<(usize, usize)> = self.roots.iter().map(|&r| (r, 0)).collect();
+        let mut visited = vec![false; self.nodes.len()];
+        while let Some((idx, level)) = queue.pop_front() {
+            if visited[idx] {
+                continue;
+            }
+            visited[idx] = true;
+            self.nodes[idx].level = level;
+            for &child in &self.nodes[idx].children {
+                queue.push_back((child, level + 1));
+            }
+        }
+    }
+
+    /// Nodes immediately below the roots, or the roots themselves when
+    /// they have no children.
+    pub fn top_level_nodes(&self) -> Vec<usize> {
+        let mut top_level = Vec::new();
+        for &root in &self.roots {
+            if self.nodes[root].children.is_empty() {
+                top_level.push(root);
+            } else {
+                top_level.extend(self.nodes[root].children.iter().cloned());
+            }
+        }
+        top_level
+    }
+}
+
+/// Reads the node hierarchy of a package, from node pages when the layer
+/// document declares them and from per-node index documents otherwise.
+pub fn load_hierarchy<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Hierarchy, Error> {
+    let mut hierarchy = if layer.uses_node_pages() {
+        load_from_node_pages(archive, layer)?
+    } else {
+        load_from_node_documents(archive, layer)?
+    };
+    hierarchy.assign_levels();
+    Ok(hierarchy)
+}
+
+/// The nodes of every node page, in order, up to the first missing page.
+pub fn read_page_nodes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Vec<Value>, Error> {
+    let mut page_nodes = Vec::new();
+    for page_idx in 0.. {
+        match read_document(
+            archive,
+            &layer.entry_name(&format!("nodepages/{}.json", page_idx)),
+        )? {
+            Some(page) => {
+                if let Some(nodes) = page.get("nodes").and_then(Value::as_array) {
+                    page_nodes.extend(nodes.iter().cloned());
+                }
+            }
+            None => break,
+        }
+    }
+    Ok(page_nodes)
+}
+
+fn load_from_node_pages<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Hierarchy, Error> {
+    let page_nodes = read_page_nodes(archive, layer)?;
+
+    let mut hierarchy = Hierarchy::default();
+    let mut position_of_index = HashMap::new();
+    for (position, node) in page_nodes.iter().enumerate() {
+        let index = node
+            .get("index")
+            .and_then(Value::as_u64)
+            .unwrap_or(position as u64);
+        position_of_index.insert(index, position);
+
+        let geometry = node.get("mesh").and_then(|m| m.get("geometry"));
+        let resource = geometry
+            .and_then(|g| g.get("resource"))
+            .or_else(|| node.get("resourceId"))
+            .and_then(Value::as_u64)
+            .unwrap_or(index);
+        let mut other_resources: Vec<String> = Vec::new();
+        for member in ["material", "attribute"] {
+            let other = node
+                .get("mesh")
+                .and_then(|m| m.get(member))
+                .and_then(|m| m.get("resource"))
+                .and_then(Value::as_u64)
+                .filter(|&other| other != resource)
+                .map(|other| other.to_string());
+            if let Some(other) = other.filter(|other| !other_resources.contains(other)) {
+                other_resources.push(other);
+            }
+        }
+        let point_count = if layer.is_point_cloud() {
+            node.get("vertexCount").and_then(Value::as_u64)
+        } else {
+            None
+        };
+
+        hierarchy.nodes.push(Node {
+            id: index.to_string(),
+            resource: resource.to_string(),
+            other_resources,
+            parent: None,
+            children: Vec::new(),
+            level: 0,
+            lod_threshold: node.get("lodThreshold").and_then(Value::as_f64),
+            volume: BoundingVolume::of_node(node),
+            vertex_count: geometry
+                .and_then(|g| g.get("vertexCount"))
+                .and_then(Value::as_u64),
+            feature_count: geometry
+                .and_then(|g| g.get("featureCount"))
+                .and_then(Value::as_u64),
+            point_count,
+        });
+    }
+
+    for (position, node) in page_nodes.iter().enumerate() {
+        for child_index in page_node_children(node) {
+            if let Some(&child) = position_of_index.get(&child_index) {
+                hierarchy.nodes[position].children.push(child);
+                hierarchy.nodes[child].parent = Some(position);
+            }
+        }
+    }
+    // Some exporters only record the parent of a node, not its children.
+    for (position, node) in page_nodes.iter().enumerate() {
+        if hierarchy.nodes[position].parent.is_some() {
+            continue;
+        }
+        let parent = node
+            .get("parentIndex")
+            .and_then(Value::as_u64)
+            .and_then(|index| position_of_index.get(&index).copied())
+            .filter(|&parent| parent != position);
+        if let Some(parent) = parent {
+            hierarchy.nodes[parent].children.push(position);
+            hierarchy.nodes[position].parent = Some(parent);
+        }
+    }
+
+    hierarchy.roots = (0..hierarchy.nodes.len())
+        .filter(|&n| hierarchy.nodes[n].parent.is_none())
+        .take(1)
+        .collect();
+    Ok(hierarchy)
+}
+
+/// The indices of the children of a node of the node pages. Point cloud
+/// nodes give the range of their children, which are stored one after the
+/// other, as `firstChild` and `childCount` rather than listing them.
+fn page_node_children(node: &Value) -> Vec<u64> {
+    if let Some(children) = node.get("children").and_then(Value::as_array) {
+        return children.iter().filter_map(Value::as_u64).collect();
+    }
+    match (
+        node.get("firstChild").and_then(Value::as_u64),
+        node.get("childCount").and_then(Value::as_u64),
+    ) {
+        (Some(first), Some(count)) => (first..first.saturating_add(count)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The id of the root node of a package without node pages. Older packages
+/// name it in the store, usually "./nodes/root".
+fn root_node_id(layer: &SceneLayer) -> String {
+    layer
+        .document
+        .get("store")
+        .and_then(|s| s.get("rootNode"))
+        .and_then(Value::as_str)
+        .and_then(|r| r.rsplit('/').next())
+        .unwrap_or("root")
+        .to_string()
+}
+
+/// The entry holding the root node: the first node page, or the root's node
+/// index document.
+pub fn root_node_entry(layer: &SceneLayer) -> String {
+    if layer.uses_node_pages() {
+        layer.entry_name("nodepages/0.json")
+    } else {
+        layer.entry_name(&format!(
+            "nodes/{}/3dNodeIndexDocument.json",
+            root_node_id(layer)
+        ))
+    }
+}
+
+/// Reads only the root node, without walking the rest of the tree. Returns
+/// `None` when its entry is missing or holds no nodes.
+pub fn read_root_node<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Option<Value>, Error> {
+    let document = match read_document(archive, &root_node_entry(layer))? {
+        Some(document) => document,
+        None => return Ok(None),
+    };
+    if !layer.uses_node_pages() {
+        return Ok(Some(document));
+    }
+    Ok(document
+        .get("nodes")
+        .and_then(Value::as_array)
+        .and_then(|nodes| nodes.first())
+        .cloned())
+}
+
+fn load_from_node_documents<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<Hierarchy, Error> {
+    let root_id = root_node_id(layer);
+
+    let mut hierarchy = Hierarchy::default();
+    let mut position_of_id: HashMap<String, usize> = HashMap::new();
+    let mut queue: VecDeque<(String, Option<usize>)> = VecDeque::new();
+    queue.push_back((root_id, None));
+
+    while let Some((id, parent)) = queue.pop_front() {
+        if position_of_id.contains_key(&id) {
+            continue;
+        }
+        let document = match read_document(
+            archive,
+            &layer.entry_name(&format!("nodes/{}/3dNodeIndexDocument.json", id)),
+        )? {
+            Some(document) => document,
+            None => continue,
+        };
+
+        let position = hierarchy.nodes.len();
+        position_of_id.insert(id.clone(), position);
+        if let Some(parent) = parent {
+            hierarchy.nodes[parent].children.push(position);
+        } else {
+            hierarchy.roots.push(position);
+        }
+
+        let lod_threshold = document
+            .get("lodSelection")
+            .and_then(Value::as_array)
+            .and_then(|metrics| metrics.first())
+            .and_then(|m| m.get("maxError"))
+            .and_then(Value::as_f64);
+
+        hierarchy.nodes.push(Node {
+            id: id.clone(),
+            resource: id,
+            other_resources: Vec::new(),
+            parent,
+            children: Vec::new(),
+            level: 0,
+            lod_threshold,
+            volume: BoundingVolume::of_node(&document),
+            vertex_count: None,
+            feature_count: None,
+            point_count: None,
+        });
+
+        if let Some(children) = document.get("children").and_then(Value::as_array) {
+            for child in children {
+                if let Some(child_id) = child.get("id").and_then(Value::as_str) {
+                    queue.push_back((child_id.to_string(), Some(position)));
+                }
+            }
+        }
+    }
+
+    Ok(hierarchy)
+}
@@ -0,0 +1,170 @@
+// The package-level metadata of `metadata.json`, which older exports leave
+// out. Without it the metadata is inferred instead: the version from the
+// store of the layer document, or when that gives none from how the layer is
+// laid out, with node pages first appearing in 1.7, and whether resources
+// are gzipped from the name of the layer document. A single warning says the
+// metadata was inferred, so that commands go on with what they can tell
+// rather than failing or leaving the fields blank. Counting the nodes means
+// walking the whole hierarchy, so it is only done when asked for.
+
+use super::{load_hierarchy, read_document, root_node_entry, SceneLayer, SCENE_LAYER_DOCUMENT};
+use super::{SlpkVersion, METADATA_DOCUMENT};
+use crate::json::Value;
+use failure::Error;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// The version of the point cloud profile, which numbers its versions apart.
+const POINT_CLOUD_VERSION: &str = "2.0";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageMetadata {
+    /// The document, as read or as inferred.
+    pub document: Value,
+    /// Whether the package has no `metadata.json`, so that `document` was
+    /// inferred from its layer.
+    pub inferred: bool,
+}
+
+impl PackageMetadata {
+    pub fn node_count(&self) -> Option<u64> {
+        self.document.get("nodeCount").and_then(Value::as_u64)
+    }
+
+    /// Adds the number of nodes of `layer` to inferred metadata which has
+    /// none, walking its hierarchy. A hierarchy which can't be read leaves
+    /// the count out.
+    pub fn count_nodes<R: Read + Seek>(&mut self, archive: &mut ZipArchive<R>, layer: &SceneLayer) {
+        if !self.inferred || self.node_count().is_some() {
+            return;
+        }
+        if let (Ok(hierarchy), Value::Object(members)) =
+            (load_hierarchy(archive, layer), &mut self.document)
+        {
+            members.push(("nodeCount".to_string(), Value::from(hierarchy.nodes.len())));
+        }
+    }
+}
+
+/// The version a layer is laid out for, when its store doesn't say.
+fn version_of_layout<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Option<SlpkVersion> {
+    if layer.is_point_cloud() {
+        return Some(SlpkVersion::parse(POINT_CLOUD_VERSION));
+    }
+    if layer.uses_node_pages() {
+        return Some(SlpkVersion::V1_7);
+    }
+    let root = root_node_entry(layer);
+    if archive.by_name(&root).is_ok() || archive.by_name(&format!("{}.gz", root)).is_ok() {
+        return Some(SlpkVersion::V1_6);
+    }
+    None
+}
+
+/// The metadata of the package, from `metadata.json`, or inferred from
+/// `layer` with a warning when the package has none.
+pub fn read_metadata<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: Option<&SceneLayer>,
+) -> Result<PackageMetadata, Error> {
+    if let Some(document) = read_document(archive, METADATA_DOCUMENT)? {
+        return Ok(PackageMetadata {
+            document,
+            inferred: false,
+        });
+    }
+    let version = match layer {
+        Some(layer) => SlpkVersion::of_layer(layer).or_else(|| version_of_layout(archive, layer)),
+        None => None,
+    };
+    let gzipped = layer.map(|layer| {
+        let document = layer.entry_name(SCENE_LAYER_DOCUMENT);
+        archive.by_name(&format!("{}.gz", document)).is_ok()
+    });
+    let mut members = vec![
+        ("folderPattern".to_string(), Value::from("basic")),
+        ("ArchiveCompressionType".to_string(), Value::from("STORE")),
+    ];
+    if let Some(gzipped) = gzipped {
+        members.push((
+            "ResourceCompressionType".to_string(),
+            Value::from(if gzipped { "GZIP" } else { "NONE" }),
+        ));
+    }
+    if let Some(version) = &version {
+        members.push(("I3SVersion".to_string(), Value::from(version.to_string())));
+    }
+    warn!(
+        "The package has no {}, so its metadata was inferred from its layer, with I3S version {}",
+        METADATA_DOCUMENT,
+        version.map_or_else(|| "unknown".to_string(), |version| version.to_string())
+    );
+    Ok(PackageMetadata {
+        document: Value::Object(members),
+        inferred: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i3s::read_scene_layer;
+    use crate::synthetic::SyntheticPackage;
+    use std::io::Cursor;
+
+    #[test]
+    fn missing_metadata_is_inferred_from_the_layer() {
+        let read = |package: SyntheticPackage| {
+            let bytes = package.to_bytes().unwrap();
+            let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+            let layer = read_scene_layer(&mut archive).unwrap();
+            let mut metadata = read_metadata(&mut archive, Some(&layer)).unwrap();
+            metadata.count_nodes(&mut archive, &layer);
+            metadata
+        };
+        let old = read(
+            SyntheticPackage::new()
+                .entry("3dSceneLayer.json.gz", br#"{"id":0}"#)
+                .entry(
+                    "nodes/root/3dNodeIndexDocument.json.gz",
+                    br#"{"id":"root","children":[{"id":"1"}]}"#,
+                )
+                .entry("nodes/1/3dNodeIndexDocument.json.gz", br#"{"id":"1"}"#),
+        );
+        assert!(old.inferred);
+        assert_eq!(
+            SlpkVersion::of_package(Some(&old.document), None),
+            SlpkVersion::V1_6
+        );
+        assert_eq!(old.node_count(), Some(2));
+        assert_eq!(
+            old.document.get("ResourceCompressionType"),
+            Some(&Value::from("GZIP"))
+        );
+
+        let paged = read(
+            SyntheticPackage::new()
+                .entry("3dSceneLayer.json", br#"{"id":0,"nodePages":{}}"#)
+                .entry("nodepages/0.json", br#"{"nodes":[{"index":0}]}"#),
+        );
+        assert_eq!(
+            SlpkVersion::of_package(Some(&paged.document), None),
+            SlpkVersion::V1_7
+        );
+        assert_eq!(
+            paged.document.get("ResourceCompressionType"),
+            Some(&Value::from("NONE"))
+        );
+
+        let stored = read(
+            SyntheticPackage::new()
+                .entry("3dSceneLayer.json.gz", br#"{"store":{"version":"1.8"}}"#)
+                .entry("metadata.json", br#"{"I3SVersion":"1.8"}"#),
+        );
+        assert!(!stored.inferred);
+        assert_eq!(stored.node_count(), None);
+    }
+}
@@ -0,0 +1,302 @@
+// Typed views over the parts of the I3S specification which slpkg needs to
+// understand. Only the fields actually used are extracted; the full JSON
+// document is kept alongside for anything else.
+
+mod attributes;
+mod hierarchy;
+mod metadata;
+mod version;
+
+pub use self::attributes::{declared_attributes, Attribute};
+pub use self::hierarchy::{
+    load_hierarchy, read_page_nodes, read_root_node, root_node_entry, BoundingVolume, Hierarchy,
+    Node,
+};
+pub use self::metadata::read_metadata;
+pub use self::version::{detect_version, SlpkVersion, METADATA_DOCUMENT};
+
+use crate::archive;
+use crate::json;
+use crate::json::Value;
+use failure::Error;
+use std::io::Read;
+use std::io::Seek;
+use zip::ZipArchive;
+
+pub const SCENE_LAYER_DOCUMENT: &str = "3dSceneLayer.json";
+
+/// Packages holding several layers, and some newer single layer packages,
+/// store each layer below `layers/<n>/` instead of at the root.
+const LAYERS_FOLDER: &str = "layers/";
+
+#[derive(Debug, Fail)]
+pub enum I3sError {
+    #[fail(display = "The package does not contain a scene layer document (3dSceneLayer.json)")]
+    MissingSceneLayerDocument,
+
+    #[fail(display = "Invalid JSON in {}: {}", document, error)]
+    InvalidDocument {
+        document: String,
+        error: json::JsonError,
+    },
+
+    #[fail(
+        display = "The package has no layer {}, its layers are: {}",
+        id, available
+    )]
+    NoSuchLayer { id: usize, available: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneLayer {
+    /// The `<n>` of `layers/<n>/`, or for a layer at the root of the
+    /// package, the id from its layer document.
+    pub id: usize,
+    /// The folder holding the layer, either empty or `layers/<n>/`.
+    pub root: String,
+    pub layer_type: Option<String>,
+    pub wkid: Option<u32>,
+    pub document: Value,
+}
+
+impl SceneLayer {
+    /// The layer stored at `root`, from its parsed layer document.
+    pub fn from_document(root: &str, document: Value) -> SceneLayer {
+        let id = match root
+            .strip_prefix(LAYERS_FOLDER)
+            .and_then(|rest| rest.trim_end_matches('/').parse().ok())
+        {
+            Some(n) => n,
+            None => document.get("id").and_then(Value::as_u64).unwrap_or(0) as usize,
+        };
+
+        let spatial_reference = document.get("spatialReference");
+        // latestWkid is preferred since it is the code which the EPSG registry
+        // uses, where wkid may be an older Esri-specific code.
+        let wkid = spatial_reference
+            .and_then(|sr| sr.get("latestWkid").or_else(|| sr.get("wkid")))
+            .and_then(Value::as_u64)
+            .map(|wkid| wkid as u32);
+
+        SceneLayer {
+            id,
+            root: root.to_string(),
+            layer_type: document
+                .get("layerType")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            wkid,
+            document,
+        }
+    }
+
+    /// Whether the layer is a point cloud, whose layer type is written
+    /// either way by different exporters.
+    pub fn is_point_cloud(&self) -> bool {
+        matches!(
+            self.layer_type.as_deref(),
+            Some("PointCloud") | Some("Point Cloud")
+        )
+    }
+
+    /// Whether the nodes of the layer are in node pages. Point cloud layers
+    /// always have them, declared in the `index` of their store rather than
+    /// in `nodePages`.
+    pub fn uses_node_pages(&self) -> bool {
+        self.document.get("nodePages").is_some() || self.is_point_cloud()
+    }
+
+    /// The name of an entry of this layer, from its name relative to the
+    /// layer root.
+    pub fn entry_name(&self, name: &str) -> String {
+        format!("{}{}", self.root, name)
+    }
+
+    /// Whether an entry is stored below this layer's root.
+    pub fn contains(&self, entry_name: &str) -> bool {
+        if self.root.is_empty() {
+            !entry_name.starts_with(LAYERS_FOLDER)
+        } else {
+            entry_name.starts_with(&self.root)
+        }
+    }
+}
+
+/// Finds the folders holding a layer document, with the root of the package
+/// first and then the `layers/<n>/` folders in order of `<n>`.
+pub fn layer_roots<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<String>, Error> {
+    let mut names = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        names.push(archive.by_index(i)?.name().to_string());
+    }
+    Ok(layer_roots_of(names.iter().map(String::as_str)))
+}
+
+/// The layer roots among the names of the entries of a package, in the
+/// order `layer_roots` gives them.
+pub fn layer_roots_of<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut roots: Vec<(Option<usize>, String)> = Vec::new();
+    for name in names {
+        let folder = match name
+            .trim_end_matches(".gz")
+            .strip_suffix(SCENE_LAYER_DOCUMENT)
+        {
+            Some(folder) => folder,
+            None => continue,
+        };
+        if folder.is_empty() {
+            roots.push((None, String::new()));
+        } else if let Some(n) = folder
+            .strip_prefix(LAYERS_FOLDER)
+            .and_then(|rest| rest.strip_suffix('/'))
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            roots.push((Some(n), folder.to_string()));
+        }
+    }
+    roots.sort();
+    roots.dedup();
+    roots.into_iter().map(|(_, root)| root).collect()
+}
+
+/// Reads and parses a JSON resource by its logical (un-gzipped) name.
+pub fn read_document<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    resource_name: &str,
+) -> Result<Option<Value>, Error> {
+    match archive::read_resource(archive, resource_name)? {
+        Some(contents) => json::parse_bytes(&contents).map(Some).map_err(|error| {
+            Error::from(I3sError::InvalidDocument {
+                document: resource_name.to_string(),
+                error,
+            })
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Reads the first layer of the package, which for single layer packages is
+/// the only one.
+pub fn read_scene_layer<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<SceneLayer, Error> {
+    match layer_roots(archive)?.first() {
+        Some(root) => read_layer_at(archive, root),
+        None => Err(Error::from(I3sError::MissingSceneLayerDocument)),
+    }
+}
+
+/// Reads the same layer as `read_scene_layer`, but looks for its document
+/// by name first. Listing the entries of a package means reading the local
+/// header of every one, which adds up for large packages.
+pub fn find_scene_layer<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<SceneLayer, Error> {
+    for root in &["", "layers/0/"] {
+        let document_name = format!("{}{}", root, SCENE_LAYER_DOCUMENT);
+        if archive.by_name(&document_name).is_ok()
+            || archive.by_name(&format!("{}.gz", document_name)).is_ok()
+        {
+            return read_layer_at(archive, root);
+        }
+    }
+    read_scene_layer(archive)
+}
+
+pub fn read_layers<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<SceneLayer>, Error> {
+    layer_roots(archive)?
+        .iter()
+        .map(|root| read_layer_at(archive, root))
+        .collect()
+}
+
+/// Reads the layer with the given id, or the first layer when `id` is
+/// `None`.
+pub fn select_layer<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    id: Option<usize>,
+) -> Result<SceneLayer, Error> {
+    let id = match id {
+        Some(id) => id,
+        None => return read_scene_layer(archive),
+    };
+    let mut layers = read_layers(archive)?;
+    match layers.iter().position(|layer| layer.id == id) {
+        Some(position) => Ok(layers.swap_remove(position)),
+        None if layers.is_empty() => Err(Error::from(I3sError::MissingSceneLayerDocument)),
+        None => Err(Error::from(I3sError::NoSuchLayer {
+            id,
+            available: layers
+                .iter()
+                .map(|layer| layer.id.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        })),
+    }
+}
+
+fn read_layer_at<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    root: &str,
+) -> Result<SceneLayer, Error> {
+    let document_name = format!("{}{}", root, SCENE_LAYER_DOCUMENT);
+    let document =
+        read_document(archive, &document_name)?.ok_or(I3sError::MissingSceneLayerDocument)?;
+    Ok(SceneLayer::from_document(root, document))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn package(entries: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    const NODE_PAGE: &str = r#"{"nodes":[{"index":0,"children":[1]},{"index":1}]}"#;
+
+    #[test]
+    fn reads_layers_at_the_root() {
+        let mut archive = package(&[
+            (
+                "3dSceneLayer.json",
+                r#"{"id":3,"layerType":"3DObject","nodePages":{}}"#,
+            ),
+            ("nodepages/0.json", NODE_PAGE),
+        ]);
+        let layer = select_layer(&mut archive, Some(3)).unwrap();
+        assert_eq!((layer.id, layer.root.as_str()), (3, ""));
+        assert!(layer.contains("nodes/1/geometries/0.bin"));
+        assert_eq!(load_hierarchy(&mut archive, &layer).unwrap().nodes.len(), 2);
+        assert!(select_layer(&mut archive, Some(0)).is_err());
+    }
+
+    #[test]
+    fn reads_layers_below_layers_folders() {
+        let mut archive = package(&[
+            ("layers/10/3dSceneLayer.json", r#"{"layerType":"Point"}"#),
+            (
+                "layers/2/3dSceneLayer.json",
+                r#"{"layerType":"3DObject","nodePages":{}}"#,
+            ),
+            ("layers/2/nodepages/0.json", NODE_PAGE),
+            ("layers/20/3dSceneLayer.json.txt", "{}"),
+        ]);
+        assert_eq!(
+            layer_roots(&mut archive).unwrap(),
+            vec!["layers/2/", "layers/10/"]
+        );
+        let layer = read_scene_layer(&mut archive).unwrap();
+        assert_eq!(layer.id, 2);
+        assert_eq!(layer.entry_name("nodes/0"), "layers/2/nodes/0");
+        assert!(!layer.contains("layers/10/nodes/0"));
+        assert_eq!(load_hierarchy(&mut archive, &layer).unwrap().nodes.len(), 2);
+
+        let layer = select_layer(&mut archive, Some(10)).unwrap();
+        assert_eq!(layer.layer_type.as_deref(), Some("Point"));
+    }
+}
@@ -0,0 +1,139 @@
+// The version of I3S a package was written for, which decides how its nodes
+// are laid out: each node of an I3S 1.6 layer has a node index document in
+// its folder, where layers of 1.7 and later list their nodes in node pages.
+// Packages of 1.7 and later also carry `metadata.json` at their root, giving
+// the version along with the number of nodes, and as it speaks for the
+// package as a whole it is taken over the layer documents. Older packages
+// only give the `version` of the store of their layer document, and the
+// metadata they lack is inferred as `read_metadata` does. The point
+// cloud profile numbers its versions apart, from 2.0, and those are kept as
+// they are given.
+
+use super::{read_metadata, read_scene_layer, SceneLayer};
+use crate::json::Value;
+use failure::Error;
+use std::fmt;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// The document at the root of packages of I3S 1.7 and later.
+pub const METADATA_DOCUMENT: &str = "metadata.json";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SlpkVersion {
+    V1_6,
+    V1_7,
+    V1_8,
+    /// Any other version, as it is given, or empty when none is.
+    Unknown(String),
+}
+
+impl SlpkVersion {
+    /// The version named by `version`, whatever its patch number.
+    pub fn parse(version: &str) -> SlpkVersion {
+        let mut parts = version.trim().split('.');
+        match (parts.next(), parts.next()) {
+            (Some("1"), Some("6")) => SlpkVersion::V1_6,
+            (Some("1"), Some("7")) => SlpkVersion::V1_7,
+            (Some("1"), Some("8")) => SlpkVersion::V1_8,
+            _ => SlpkVersion::Unknown(version.to_string()),
+        }
+    }
+
+    /// The version the store of a layer document gives, if it gives one.
+    pub fn of_layer(layer: &SceneLayer) -> Option<SlpkVersion> {
+        layer
+            .document
+            .get("store")
+            .and_then(|store| store.get("version"))
+            .and_then(Value::as_str)
+            .map(SlpkVersion::parse)
+    }
+
+    /// The version `metadata.json` gives, and otherwise the one the layer
+    /// document of `layer` gives.
+    pub fn of_package(metadata: Option<&Value>, layer: Option<&SceneLayer>) -> SlpkVersion {
+        metadata
+            .and_then(|metadata| metadata.get("I3SVersion"))
+            .and_then(Value::as_str)
+            .map(SlpkVersion::parse)
+            .or_else(|| layer.and_then(SlpkVersion::of_layer))
+            .unwrap_or_else(|| SlpkVersion::Unknown(String::new()))
+    }
+
+    /// Whether layers of this version list their nodes in node pages, if
+    /// that is known.
+    pub fn has_node_pages(&self) -> Option<bool> {
+        match self {
+            SlpkVersion::V1_6 => Some(false),
+            SlpkVersion::V1_7 | SlpkVersion::V1_8 => Some(true),
+            SlpkVersion::Unknown(_) => None,
+        }
+    }
+
+    /// Whether no version was given at all.
+    pub fn is_missing(&self) -> bool {
+        matches!(self, SlpkVersion::Unknown(version) if version.is_empty())
+    }
+}
+
+impl fmt::Display for SlpkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlpkVersion::V1_6 => write!(f, "1.6"),
+            SlpkVersion::V1_7 => write!(f, "1.7"),
+            SlpkVersion::V1_8 => write!(f, "1.8"),
+            SlpkVersion::Unknown(version) if version.is_empty() => write!(f, "unknown"),
+            SlpkVersion::Unknown(version) => write!(f, "{}", version),
+        }
+    }
+}
+
+/// The version of I3S of a package, from its `metadata.json` when it has
+/// one, and otherwise from the layer document of its first layer, with a
+/// warning when the package has no `metadata.json`.
+pub fn detect_version<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<SlpkVersion, Error> {
+    let layer = read_scene_layer(archive).ok();
+    let metadata = read_metadata(archive, layer.as_ref())?;
+    Ok(SlpkVersion::of_package(
+        Some(&metadata.document),
+        layer.as_ref(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::SyntheticPackage;
+    use std::io::Cursor;
+
+    #[test]
+    fn metadata_is_taken_over_the_layer_document() {
+        let detect = |package: SyntheticPackage| {
+            let bytes = package.to_bytes().unwrap();
+            detect_version(&mut ZipArchive::new(Cursor::new(bytes)).unwrap()).unwrap()
+        };
+        let layer = br#"{"store":{"version":"1.6.2"}}"#;
+        assert_eq!(
+            detect(
+                SyntheticPackage::new()
+                    .entry("3dSceneLayer.json.gz", layer)
+                    .entry("metadata.json", br#"{"I3SVersion":"1.8","nodeCount":7}"#)
+            ),
+            SlpkVersion::V1_8
+        );
+        assert_eq!(
+            detect(SyntheticPackage::new().entry("3dSceneLayer.json.gz", layer)),
+            SlpkVersion::V1_6
+        );
+        let point_cloud = detect(
+            SyntheticPackage::new()
+                .entry("3dSceneLayer.json.gz", br#"{"store":{"version":"2.0"}}"#)
+                .entry("metadata.json", b"{}"),
+        );
+        assert_eq!(point_cloud, SlpkVersion::Unknown("2.0".to_string()));
+        let missing = detect(SyntheticPackage::new().entry("3dSceneLayer.json.gz", b"{}"));
+        assert_eq!(missing.to_string(), "unknown");
+        assert_eq!(SlpkVersion::V1_7.has_node_pages(), Some(true));
+    }
+}
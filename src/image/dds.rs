@@ -0,0 +1,270 @@
+// Decodes the first level of the DDS textures scene layers carry for
+// desktop GPUs, which are compressed as DXT1, DXT3 or DXT5 in blocks of four
+// by four pixels. Each block holds two RGB565 colors and a two bit index per
+// pixel into a palette made from them; DXT3 adds four bits of alpha per
+// pixel, and DXT5 two alpha values with a three bit index into the eight
+// interpolated from them.
+
+use super::{DecodeError, RgbaImage};
+use byteorder::{ByteOrder, LittleEndian};
+
+const HEADER_LEN: usize = 128;
+
+/// The four character code of a DDS texture, such as `DXT1`, or `None` for
+/// an uncompressed one.
+pub fn four_cc(dds: &[u8]) -> Option<String> {
+    let code = dds.get(84..88)?;
+    if code == [0; 4] {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(code)
+            .trim_end_matches('\0')
+            .to_string(),
+    )
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let (r, g, b) = ((color >> 11) & 0x1f, (color >> 5) & 0x3f, color & 0x1f);
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+/// Mixes `a` and `b` as `(a * wa + b * wb) / (wa + wb)`, for each channel.
+fn mix(a: [u8; 3], b: [u8; 3], wa: u16, wb: u16) -> [u8; 3] {
+    let channel = |i: usize| ((u16::from(a[i]) * wa + u16::from(b[i]) * wb) / (wa + wb)) as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+/// The colors of the sixteen pixels of a color block. With `has_alpha`
+/// cleared, as for DXT1, a block whose first color isn't the greater uses
+/// its fourth color for transparent black.
+fn color_block(block: &[u8], has_alpha: bool) -> [[u8; 4]; 16] {
+    let (c0, c1) = (
+        LittleEndian::read_u16(&block[0..2]),
+        LittleEndian::read_u16(&block[2..4]),
+    );
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let opaque = |[r, g, b]: [u8; 3]| [r, g, b, 255];
+    let palette = if c0 > c1 || has_alpha {
+        [
+            opaque(a),
+            opaque(b),
+            opaque(mix(a, b, 2, 1)),
+            opaque(mix(a, b, 1, 2)),
+        ]
+    } else {
+        [opaque(a), opaque(b), opaque(mix(a, b, 1, 1)), [0, 0, 0, 0]]
+    };
+    let indices = LittleEndian::read_u32(&block[4..8]);
+    let mut pixels = [[0u8; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 3) as usize];
+    }
+    pixels
+}
+
+/// The alpha of the sixteen pixels of a DXT5 alpha block.
+fn interpolated_alpha(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (u16::from(block[0]), u16::from(block[1]));
+    let mut palette = [a0 as u8, a1 as u8, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1) / 5) as u8;
+        }
+    }
+    let indices = LittleEndian::read_u48(&block[2..8]);
+    let mut alpha = [0u8; 16];
+    for (i, value) in alpha.iter_mut().enumerate() {
+        *value = palette[((indices >> (i * 3)) & 7) as usize];
+    }
+    alpha
+}
+
+pub fn decode(dds: &[u8]) -> Result<RgbaImage, DecodeError> {
+    if dds.len() < HEADER_LEN {
+        return Err(DecodeError::Invalid("DDS header ends early".to_string()));
+    }
+    let height = LittleEndian::read_u32(&dds[12..16]);
+    let width = LittleEndian::read_u32(&dds[16..20]);
+    let code = four_cc(dds);
+    let block_len = match code.as_deref() {
+        Some("DXT1") => 8,
+        Some("DXT3") | Some("DXT5") => 16,
+        Some("DX10") => {
+            return Err(DecodeError::Unsupported(
+                "DDS with a DX10 header".to_string(),
+            ))
+        }
+        Some(code) => {
+            return Err(DecodeError::Unsupported(format!(
+                "DDS compressed as {}",
+                code
+            )))
+        }
+        None => return Err(DecodeError::Unsupported("uncompressed DDS".to_string())),
+    };
+    let (blocks_x, blocks_y) = (width.div_ceil(4) as usize, height.div_ceil(4) as usize);
+    let data_len = blocks_x
+        .checked_mul(blocks_y)
+        .and_then(|blocks| blocks.checked_mul(block_len));
+    let data = dds
+        .get(HEADER_LEN..)
+        .filter(|data| data_len.is_some_and(|data_len| data.len() >= data_len))
+        .ok_or_else(|| DecodeError::Invalid("DDS data ends early".to_string()))?;
+
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = vec![0u8; width * height * 4];
+    for (i, block) in data.chunks(block_len).take(blocks_x * blocks_y).enumerate() {
+        let (block_x, block_y) = (i % blocks_x, i / blocks_x);
+        let mut colors = match code.as_deref() {
+            Some("DXT1") => color_block(block, false),
+            _ => color_block(&block[8..], true),
+        };
+        match code.as_deref() {
+            Some("DXT3") => {
+                let alpha = LittleEndian::read_u64(&block[0..8]);
+                for (j, color) in colors.iter_mut().enumerate() {
+                    color[3] = ((alpha >> (j * 4)) & 0x0f) as u8 * 17;
+                }
+            }
+            Some("DXT5") => {
+                for (color, alpha) in colors.iter_mut().zip(&interpolated_alpha(block)) {
+                    color[3] = *alpha;
+                }
+            }
+            _ => {}
+        }
+        for (j, color) in colors.iter().enumerate() {
+            let (x, y) = (block_x * 4 + j % 4, block_y * 4 + j / 4);
+            if x < width && y < height {
+                let start = (y * width + x) * 4;
+                pixels[start..start + 4].copy_from_slice(color);
+            }
+        }
+    }
+    Ok(RgbaImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds(code: &[u8; 4], width: u32, height: u32, blocks: &[u8]) -> Vec<u8> {
+        let mut dds = b"DDS ".to_vec();
+        dds.resize(HEADER_LEN, 0);
+        dds[12..16].copy_from_slice(&height.to_le_bytes());
+        dds[16..20].copy_from_slice(&width.to_le_bytes());
+        dds[84..88].copy_from_slice(code);
+        dds.extend_from_slice(blocks);
+        dds
+    }
+
+    const RED: u16 = 0xf800;
+    const BLUE: u16 = 0x001f;
+
+    /// A color block of `c0` and `c1` whose pixels use the four colors of
+    /// its palette in turn along each row.
+    fn color_block(c0: u16, c1: u16) -> Vec<u8> {
+        let mut block = c0.to_le_bytes().to_vec();
+        block.extend_from_slice(&c1.to_le_bytes());
+        block.extend_from_slice(&[0b1110_0100; 4]);
+        block
+    }
+
+    fn pixel(image: &RgbaImage, x: usize, y: usize) -> &[u8] {
+        let start = (y * image.width as usize + x) * 4;
+        &image.pixels[start..start + 4]
+    }
+
+    #[test]
+    fn decodes_dxt1_with_the_interpolated_colors() {
+        let image = decode(&dds(b"DXT1", 4, 4, &color_block(RED, BLUE))).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        for y in 0..4 {
+            assert_eq!(pixel(&image, 0, y), [255, 0, 0, 255]);
+            assert_eq!(pixel(&image, 1, y), [0, 0, 255, 255]);
+            assert_eq!(pixel(&image, 2, y), [170, 0, 85, 255]);
+            assert_eq!(pixel(&image, 3, y), [85, 0, 170, 255]);
+        }
+    }
+
+    #[test]
+    fn decodes_dxt1_with_transparent_black() {
+        // The first color not being the greater makes the fourth
+        // transparent.
+        let image = decode(&dds(b"DXT1", 4, 4, &color_block(BLUE, RED))).unwrap();
+        assert_eq!(pixel(&image, 0, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 2, 0), [127, 0, 127, 255]);
+        assert_eq!(pixel(&image, 3, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decodes_dxt5_with_the_interpolated_alpha() {
+        // Alpha from 255 to 0, with the pixels using the eight values in
+        // turn, and the same colors as the DXT1 block.
+        let mut block = vec![255, 0];
+        let indices: u64 = (0..16).map(|i| (i % 8) << (i * 3)).sum();
+        block.extend_from_slice(&indices.to_le_bytes()[..6]);
+        block.extend_from_slice(&color_block(RED, BLUE));
+        let image = decode(&dds(b"DXT5", 4, 4, &block)).unwrap();
+        let alpha: Vec<u8> = image.pixels.chunks(4).map(|pixel| pixel[3]).collect();
+        let palette = [255, 0, 218, 182, 145, 109, 72, 36];
+        assert_eq!(&alpha[..8], palette);
+        assert_eq!(&alpha[8..], palette);
+        // DXT5 colors never use transparent black.
+        assert_eq!(pixel(&image, 3, 0), [85, 0, 170, 182]);
+        assert_eq!(pixel(&image, 3, 1), [85, 0, 170, 36]);
+    }
+
+    #[test]
+    fn crops_the_blocks_to_the_image() {
+        let mut blocks = color_block(RED, BLUE);
+        blocks.extend_from_slice(&color_block(BLUE, BLUE));
+        let image = decode(&dds(b"DXT1", 5, 3, &blocks)).unwrap();
+        assert_eq!((image.width, image.height), (5, 3));
+        assert_eq!(image.pixels.len(), 5 * 3 * 4);
+        assert_eq!(pixel(&image, 3, 2), [85, 0, 170, 255]);
+        assert_eq!(pixel(&image, 4, 2), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn refuses_truncated_and_unsupported_textures() {
+        let message = |result: Result<RgbaImage, DecodeError>| match result {
+            Err(DecodeError::Invalid(message)) => message,
+            other => panic!("{:?}", other),
+        };
+        let complete = dds(b"DXT1", 8, 4, &[0; 16]);
+        assert_eq!(message(decode(&complete[..100])), "DDS header ends early");
+        assert_eq!(
+            message(decode(&complete[..complete.len() - 1])),
+            "DDS data ends early"
+        );
+        // A header claiming more blocks than memory could hold.
+        let huge = dds(b"DXT5", u32::MAX, u32::MAX, &[0; 16]);
+        assert_eq!(message(decode(&huge)), "DDS data ends early");
+
+        for (code, format) in &[
+            (b"DX10", "DDS with a DX10 header"),
+            (b"ATI2", "DDS compressed as ATI2"),
+            (&[0; 4], "uncompressed DDS"),
+        ] {
+            match decode(&dds(code, 4, 4, &[0; 16])) {
+                Err(DecodeError::Unsupported(message)) => assert_eq!(&message, format),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,711 @@
+// Decodes baseline and extended sequential JPEGs, the kind every exporter
+// writes for scene layer textures: Huffman coded, 8 bits per sample, with
+// one component or three in YCbCr and any chroma subsampling. Progressive
+// and arithmetic coded JPEGs are refused rather than decoded.
+
+use super::{DecodeError, RgbaImage};
+use byteorder::{BigEndian, ByteOrder};
+
+/// The most pixels an image may have, so that a header can't claim a size
+/// which takes more memory than there is.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// The order of the coefficients of a block in the stream.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+fn invalid(message: &str) -> DecodeError {
+    DecodeError::Invalid(format!("JPEG {}", message))
+}
+
+/// A Huffman table, decoded as the specification's `DECODE` procedure does.
+#[derive(Clone, Default)]
+struct HuffmanTable {
+    /// The largest code of each length, or -1 when there are none.
+    max_code: [i32; 17],
+    /// What to add to a code of each length to find its value.
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8], values: &[u8]) -> HuffmanTable {
+        let mut table = HuffmanTable {
+            max_code: [-1; 17],
+            offset: [0; 17],
+            values: values.to_vec(),
+        };
+        let (mut code, mut index) = (0i32, 0i32);
+        for length in 1..=16 {
+            let count = i32::from(counts[length - 1]);
+            table.offset[length] = index - code;
+            if count > 0 {
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+/// Reads the entropy coded data of a scan, a bit at a time.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, DecodeError> {
+        if self.count == 0 {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("data ends early"))?;
+            if byte == 0xff {
+                match self.data.get(self.pos + 1) {
+                    Some(0) => self.pos += 1,
+                    // A marker ends the data, and what follows reads as
+                    // zeros, as decoders do for truncated scans.
+                    _ => {
+                        self.bits = 0;
+                        self.count = 8;
+                        return self.bit();
+                    }
+                }
+            }
+            self.pos += 1;
+            self.bits = u32::from(byte);
+            self.count = 8;
+        }
+        self.count -= 1;
+        Ok((self.bits >> self.count) & 1)
+    }
+
+    fn bits(&mut self, count: u8) -> Result<i32, DecodeError> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()? as i32;
+        }
+        Ok(value)
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> Result<u8, DecodeError> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit()? as i32;
+            if code <= table.max_code[length] {
+                let index = (code + table.offset[length]) as usize;
+                return table
+                    .values
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| invalid("Huffman code is out of range"));
+            }
+        }
+        Err(invalid("Huffman code is invalid"))
+    }
+
+    /// Skips to the restart marker which must come next.
+    fn restart(&mut self) -> Result<(), DecodeError> {
+        self.count = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xff && (0xd0..=0xd7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+        Err(invalid("restart marker is missing"))
+    }
+}
+
+/// Extends the `size` bits of a coefficient to its signed value.
+fn extend(value: i32, size: u8) -> i32 {
+    if size == 0 {
+        0
+    } else if value < 1 << (size - 1) {
+        value - (1 << size) + 1
+    } else {
+        value
+    }
+}
+
+#[derive(Clone)]
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    predictor: i32,
+    /// The samples of every block, in rows of `stride`.
+    samples: Vec<u8>,
+    stride: usize,
+}
+
+/// The inverse DCT of a block of dequantized coefficients, into `samples`.
+fn inverse_dct(coefficients: &[f32; 64], cosines: &[[f32; 8]; 8], out: &mut [u8; 64]) {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            let mut sum = 0.0;
+            for u in 0..8 {
+                sum += cosines[x][u] * coefficients[y * 8 + u];
+            }
+            rows[y * 8 + x] = sum;
+        }
+    }
+    for x in 0..8 {
+        for y in 0..8 {
+            let mut sum = 0.0;
+            for v in 0..8 {
+                sum += cosines[y][v] * rows[v * 8 + x];
+            }
+            out[y * 8 + x] = (sum / 4.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+struct Decoder {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    quantization: [[u16; 64]; 4],
+    dc_tables: [HuffmanTable; 4],
+    ac_tables: [HuffmanTable; 4],
+    restart_interval: usize,
+    cosines: [[f32; 8]; 8],
+}
+
+impl Decoder {
+    fn block(
+        &mut self,
+        reader: &mut BitReader,
+        component: usize,
+        block_x: usize,
+        block_y: usize,
+    ) -> Result<(), DecodeError> {
+        let (dc_table, ac_table, quantization) = {
+            let c = &self.components[component];
+            (c.dc_table, c.ac_table, c.quantization)
+        };
+        let mut coefficients = [0f32; 64];
+        let size = reader.decode(&self.dc_tables[dc_table])?;
+        if size > 11 {
+            return Err(invalid("DC coefficient is too large"));
+        }
+        let difference = extend(reader.bits(size)?, size);
+        let dc = self.components[component].predictor + difference;
+        self.components[component].predictor = dc;
+        let table = &self.quantization[quantization];
+        coefficients[0] = dc as f32 * f32::from(table[0]);
+        let mut k = 1;
+        while k < 64 {
+            let symbol = reader.decode(&self.ac_tables[ac_table])?;
+            let (run, size) = (usize::from(symbol >> 4), symbol & 0x0f);
+            if size == 0 {
+                if run == 15 {
+                    k += 16;
+                    continue;
+                }
+                break;
+            }
+            k += run;
+            if k > 63 {
+                return Err(invalid("block has too many coefficients"));
+            }
+            let value = extend(reader.bits(size)?, size);
+            coefficients[ZIGZAG[k]] = value as f32 * f32::from(table[k]);
+            k += 1;
+        }
+        let mut samples = [0u8; 64];
+        inverse_dct(&coefficients, &self.cosines, &mut samples);
+        let c = &mut self.components[component];
+        for y in 0..8 {
+            let start = (block_y * 8 + y) * c.stride + block_x * 8;
+            c.samples[start..start + 8].copy_from_slice(&samples[y * 8..y * 8 + 8]);
+        }
+        Ok(())
+    }
+
+    /// Decodes a scan of `scan_components`, given by index, from `data`,
+    /// returning how many bytes it took.
+    fn scan(&mut self, data: &[u8], scan_components: &[usize]) -> Result<usize, DecodeError> {
+        let max_h = self
+            .components
+            .iter()
+            .map(|c| c.horizontal)
+            .max()
+            .unwrap_or(1);
+        let max_v = self
+            .components
+            .iter()
+            .map(|c| c.vertical)
+            .max()
+            .unwrap_or(1);
+        let mut reader = BitReader {
+            data,
+            pos: 0,
+            bits: 0,
+            count: 0,
+        };
+        for component in self.components.iter_mut() {
+            component.predictor = 0;
+        }
+        // A scan of one component codes its blocks one at a time, covering
+        // only the component's own samples.
+        let (mcus_x, mcus_y) = if scan_components.len() == 1 {
+            let c = &self.components[scan_components[0]];
+            (
+                (self.width * c.horizontal).div_ceil(max_h * 8),
+                (self.height * c.vertical).div_ceil(max_v * 8),
+            )
+        } else {
+            (
+                self.width.div_ceil(max_h * 8),
+                self.height.div_ceil(max_v * 8),
+            )
+        };
+        let mut mcus = 0;
+        for mcu_y in 0..mcus_y {
+            for mcu_x in 0..mcus_x {
+                if self.restart_interval > 0 && mcus > 0 && mcus % self.restart_interval == 0 {
+                    reader.restart()?;
+                    for component in self.components.iter_mut() {
+                        component.predictor = 0;
+                    }
+                }
+                mcus += 1;
+                if scan_components.len() == 1 {
+                    self.block(&mut reader, scan_components[0], mcu_x, mcu_y)?;
+                    continue;
+                }
+                for &component in scan_components {
+                    let (horizontal, vertical) = {
+                        let c = &self.components[component];
+                        (c.horizontal, c.vertical)
+                    };
+                    for v in 0..vertical {
+                        for h in 0..horizontal {
+                            self.block(
+                                &mut reader,
+                                component,
+                                mcu_x * horizontal + h,
+                                mcu_y * vertical + v,
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(reader.pos)
+    }
+
+    fn image(&self) -> RgbaImage {
+        let max_h = self
+            .components
+            .iter()
+            .map(|c| c.horizontal)
+            .max()
+            .unwrap_or(1);
+        let max_v = self
+            .components
+            .iter()
+            .map(|c| c.vertical)
+            .max()
+            .unwrap_or(1);
+        let sample = |c: &Component, x: usize, y: usize| {
+            let (x, y) = (x * c.horizontal / max_h, y * c.vertical / max_v);
+            f32::from(c.samples[y * c.stride + x])
+        };
+        let mut pixels = Vec::with_capacity(self.width * self.height * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let luma = sample(&self.components[0], x, y);
+                if self.components.len() == 1 {
+                    let luma = luma as u8;
+                    pixels.extend_from_slice(&[luma, luma, luma, 255]);
+                    continue;
+                }
+                let cb = sample(&self.components[1], x, y) - 128.0;
+                let cr = sample(&self.components[2], x, y) - 128.0;
+                let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                pixels.extend_from_slice(&[
+                    channel(luma + 1.402 * cr),
+                    channel(luma - 0.344_136 * cb - 0.714_136 * cr),
+                    channel(luma + 1.772 * cb),
+                    255,
+                ]);
+            }
+        }
+        RgbaImage {
+            width: self.width as u32,
+            height: self.height as u32,
+            pixels,
+        }
+    }
+}
+
+pub fn decode(jpeg: &[u8]) -> Result<RgbaImage, DecodeError> {
+    let mut cosines = [[0f32; 8]; 8];
+    for (x, row) in cosines.iter_mut().enumerate() {
+        for (u, cosine) in row.iter_mut().enumerate() {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            *cosine = scale * (((2 * x + 1) * u) as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    let mut decoder = Decoder {
+        width: 0,
+        height: 0,
+        components: Vec::new(),
+        quantization: [[0; 64]; 4],
+        dc_tables: Default::default(),
+        ac_tables: Default::default(),
+        restart_interval: 0,
+        cosines,
+    };
+    let mut scanned = false;
+    let mut pos = 2;
+    loop {
+        // Markers may be padded with any number of 0xff bytes.
+        while jpeg.get(pos) == Some(&0xff) && jpeg.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        let marker = match jpeg.get(pos..pos + 2) {
+            Some([0xff, marker]) => *marker,
+            _ if scanned => break,
+            _ => return Err(invalid("marker is missing")),
+        };
+        if marker == 0xd9 {
+            break;
+        }
+        let length = jpeg
+            .get(pos + 2..pos + 4)
+            .map(BigEndian::read_u16)
+            .ok_or_else(|| invalid("segment ends early"))? as usize;
+        let segment = jpeg
+            .get(pos + 4..pos + 2 + length)
+            .filter(|_| length >= 2)
+            .ok_or_else(|| invalid("segment ends early"))?;
+        pos += 2 + length;
+        match marker {
+            0xc0 | 0xc1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err(DecodeError::Unsupported(
+                        "JPEG with more than 8 bits per sample".to_string(),
+                    ));
+                }
+                decoder.height = BigEndian::read_u16(&segment[1..3]) as usize;
+                decoder.width = BigEndian::read_u16(&segment[3..5]) as usize;
+                let count = segment[5] as usize;
+                if count != 1 && count != 3 {
+                    return Err(DecodeError::Unsupported(format!(
+                        "JPEG with {} components",
+                        count
+                    )));
+                }
+                if decoder.width == 0 || decoder.height == 0 || segment.len() < 6 + count * 3 {
+                    return Err(invalid("frame header is invalid"));
+                }
+                if decoder.width * decoder.height > MAX_PIXELS {
+                    return Err(DecodeError::Unsupported(format!(
+                        "JPEG of {}x{} pixels",
+                        decoder.width, decoder.height
+                    )));
+                }
+                for c in segment[6..6 + count * 3].chunks(3) {
+                    let (horizontal, vertical) = (usize::from(c[1] >> 4), usize::from(c[1] & 0x0f));
+                    if !(1..=4).contains(&horizontal) || !(1..=4).contains(&vertical) || c[2] > 3 {
+                        return Err(invalid("frame header is invalid"));
+                    }
+                    decoder.components.push(Component {
+                        id: c[0],
+                        horizontal,
+                        vertical,
+                        quantization: usize::from(c[2]),
+                        dc_table: 0,
+                        ac_table: 0,
+                        predictor: 0,
+                        samples: Vec::new(),
+                        stride: 0,
+                    });
+                }
+                let max_h = decoder
+                    .components
+                    .iter()
+                    .map(|c| c.horizontal)
+                    .max()
+                    .unwrap_or(1);
+                let max_v = decoder
+                    .components
+                    .iter()
+                    .map(|c| c.vertical)
+                    .max()
+                    .unwrap_or(1);
+                let (mcus_x, mcus_y) = (
+                    decoder.width.div_ceil(max_h * 8),
+                    decoder.height.div_ceil(max_v * 8),
+                );
+                for c in decoder.components.iter_mut() {
+                    c.stride = mcus_x * c.horizontal * 8;
+                    c.samples = vec![0; c.stride * mcus_y * c.vertical * 8];
+                }
+            }
+            0xc2 | 0xc6 | 0xca | 0xce => {
+                return Err(DecodeError::Unsupported("progressive JPEG".to_string()))
+            }
+            0xc3 | 0xc5 | 0xc7 | 0xcb | 0xcd | 0xcf => {
+                return Err(DecodeError::Unsupported("lossless JPEG".to_string()))
+            }
+            0xc9 => {
+                return Err(DecodeError::Unsupported(
+                    "arithmetic coded JPEG".to_string(),
+                ))
+            }
+            0xc4 => {
+                let mut rest = segment;
+                while rest.len() >= 17 {
+                    let (class, id) = (rest[0] >> 4, usize::from(rest[0] & 0x0f));
+                    let counts = &rest[1..17];
+                    let total: usize = counts.iter().map(|&count| usize::from(count)).sum();
+                    let values = rest
+                        .get(17..17 + total)
+                        .ok_or_else(|| invalid("Huffman table ends early"))?;
+                    if id > 3 {
+                        return Err(invalid("Huffman table is invalid"));
+                    }
+                    let table = HuffmanTable::new(counts, values);
+                    if class == 0 {
+                        decoder.dc_tables[id] = table;
+                    } else {
+                        decoder.ac_tables[id] = table;
+                    }
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xdb => {
+                let mut rest = segment;
+                while !rest.is_empty() {
+                    let (precision, id) = (rest[0] >> 4, usize::from(rest[0] & 0x0f));
+                    let size = if precision == 0 { 64 } else { 128 };
+                    let values = rest
+                        .get(1..1 + size)
+                        .filter(|_| id < 4)
+                        .ok_or_else(|| invalid("quantization table is invalid"))?;
+                    for (k, value) in decoder.quantization[id].iter_mut().enumerate() {
+                        *value = if precision == 0 {
+                            u16::from(values[k])
+                        } else {
+                            BigEndian::read_u16(&values[k * 2..k * 2 + 2])
+                        };
+                    }
+                    rest = &rest[1 + size..];
+                }
+            }
+            0xdd => {
+                decoder.restart_interval = segment
+                    .get(0..2)
+                    .map(BigEndian::read_u16)
+                    .ok_or_else(|| invalid("restart interval is invalid"))?
+                    as usize;
+            }
+            0xda => {
+                if decoder.components.is_empty() {
+                    return Err(invalid("scan comes before the frame header"));
+                }
+                let count = usize::from(*segment.first().unwrap_or(&0));
+                let mut scan_components = Vec::with_capacity(count);
+                for selector in segment.get(1..1 + count * 2).unwrap_or(&[]).chunks(2) {
+                    let index = decoder
+                        .components
+                        .iter()
+                        .position(|c| c.id == selector[0])
+                        .ok_or_else(|| invalid("scan names an unknown component"))?;
+                    let (dc, ac) = (
+                        usize::from(selector[1] >> 4),
+                        usize::from(selector[1] & 0x0f),
+                    );
+                    if dc > 3 || ac > 3 {
+                        return Err(invalid("scan header is invalid"));
+                    }
+                    decoder.components[index].dc_table = dc;
+                    decoder.components[index].ac_table = ac;
+                    scan_components.push(index);
+                }
+                if scan_components.is_empty() {
+                    return Err(invalid("scan header is invalid"));
+                }
+                pos += decoder.scan(&jpeg[pos..], &scan_components)?;
+                scanned = true;
+                // Skip to the next marker, past any padding.
+                while pos + 1 < jpeg.len()
+                    && !(jpeg[pos] == 0xff
+                        && jpeg[pos + 1] != 0
+                        && !(0xd0..=0xd7).contains(&jpeg[pos + 1]))
+                {
+                    pos += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if !scanned {
+        return Err(invalid("image has no scan"));
+    }
+    Ok(decoder.image())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs a string of bits into entropy coded data, padded with ones
+    /// and with each 0xff byte stuffed.
+    fn entropy_coded(bits: &str) -> Vec<u8> {
+        let bits: Vec<u8> = bits.bytes().filter(|bit| *bit != b' ').collect();
+        let mut data = Vec::new();
+        for byte in bits.chunks(8) {
+            let mut value = 0u8;
+            for i in 0..8 {
+                value = (value << 1) | u8::from(byte.get(i).map_or(true, |bit| *bit == b'1'));
+            }
+            data.push(value);
+            if value == 0xff {
+                data.push(0);
+            }
+        }
+        data
+    }
+
+    /// A JPEG of 16 by 16 pixels in YCbCr with 4:2:0 subsampling, under
+    /// the start of frame `marker`. Every coefficient is quantized by 8 and
+    /// the DC table codes a difference of 0 as `0` and of seven bits as
+    /// `1`, so that a block's samples are its DC value plus 128. The top
+    /// half of the luma is 192 and the bottom 128, with a Cb of 128 and a
+    /// Cr of 192.
+    fn color_jpeg(marker: u8) -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xdb, 0x00, 0x43, 0x00];
+        jpeg.extend_from_slice(&[8; 64]);
+        jpeg.extend_from_slice(&[
+            0xff, marker, 0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x22, 0x00, 0x02,
+            0x11, 0x00, 0x03, 0x11, 0x00,
+        ]);
+        jpeg.extend_from_slice(&[0xff, 0xc4, 0x00, 0x15, 0x00, 0x02]);
+        jpeg.extend_from_slice(&[0; 15]);
+        jpeg.extend_from_slice(&[0, 7]);
+        jpeg.extend_from_slice(&[0xff, 0xc4, 0x00, 0x14, 0x10, 0x01]);
+        jpeg.extend_from_slice(&[0; 16]);
+        jpeg.extend_from_slice(&[
+            0xff, 0xda, 0x00, 0x0c, 0x03, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, 0x3f, 0x00,
+        ]);
+        // The four luma blocks, with DC differences of 64, 0, -64 and 0,
+        // then Cb and Cr, each block ending at once.
+        jpeg.extend_from_slice(&entropy_coded(
+            "1 1000000 0  0 0  1 0111111 0  0 0  0 0  1 1000000 0",
+        ));
+        jpeg.extend_from_slice(&[0xff, 0xd9]);
+        jpeg
+    }
+
+    #[test]
+    fn decodes_baseline_jpeg_with_subsampled_chroma() {
+        let image = decode(&color_jpeg(0xc0)).unwrap();
+        assert_eq!((image.width, image.height), (16, 16));
+        for (i, pixel) in image.pixels.chunks(4).enumerate() {
+            let expected = if i / 16 < 8 {
+                [255, 146, 192, 255]
+            } else {
+                [218, 82, 128, 255]
+            };
+            assert_eq!(pixel, expected, "pixel {}", i);
+        }
+        // Extended sequential JPEGs decode the same.
+        assert_eq!(decode(&color_jpeg(0xc1)).unwrap(), image);
+    }
+
+    #[test]
+    fn refuses_progressive_lossless_and_arithmetic_coded_jpeg() {
+        for (marker, kind) in &[
+            (0xc2, "progressive JPEG"),
+            (0xc3, "lossless JPEG"),
+            (0xc9, "arithmetic coded JPEG"),
+        ] {
+            match decode(&color_jpeg(*marker)) {
+                Err(DecodeError::Unsupported(message)) => assert_eq!(&message, kind),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_truncated_jpeg() {
+        let jpeg = color_jpeg(0xc0);
+        // Every prefix short of the last byte of the scan misses something
+        // the image needs.
+        for len in 0..jpeg.len() - 2 {
+            match decode(&jpeg[..len]) {
+                Err(DecodeError::Invalid(_)) => {}
+                other => panic!("{} bytes: {:?}", len, other.map(|_| ())),
+            }
+        }
+        // Only the end of image marker is missing.
+        assert!(decode(&jpeg[..jpeg.len() - 2]).is_ok());
+    }
+
+    #[test]
+    fn refuses_corrupt_jpeg() {
+        let message = |jpeg: &[u8]| match decode(jpeg) {
+            Err(DecodeError::Invalid(message)) => message,
+            other => panic!("{:?}", other.map(|_| ())),
+        };
+        let jpeg = color_jpeg(0xc0);
+        let scan = jpeg.len() - 2 - 5;
+
+        // A luma block whose AC code is sixteen ones, which no code of the
+        // table starts with.
+        let mut corrupt = jpeg[..scan].to_vec();
+        corrupt.extend_from_slice(&entropy_coded("0 1111111111111111 0"));
+        assert_eq!(message(&corrupt), "JPEG Huffman code is invalid");
+
+        // The scan before the frame header.
+        let sof = jpeg.windows(2).position(|w| w == [0xff, 0xc0]).unwrap();
+        let mut corrupt = jpeg[..sof].to_vec();
+        corrupt.extend_from_slice(&jpeg[sof + 19..]);
+        assert_eq!(message(&corrupt), "JPEG scan comes before the frame header");
+
+        // A scan naming a component the frame doesn't have.
+        let mut corrupt = jpeg.clone();
+        corrupt[scan - 9] = 9;
+        assert_eq!(message(&corrupt), "JPEG scan names an unknown component");
+
+        // A scan using an AC table beyond the four there can be.
+        let mut corrupt = jpeg.clone();
+        corrupt[scan - 8] = 9;
+        assert_eq!(message(&corrupt), "JPEG scan header is invalid");
+
+        // Components sampled five times as often as the others.
+        let mut corrupt = jpeg.clone();
+        corrupt[sof + 11] = 0x55;
+        assert_eq!(message(&corrupt), "JPEG frame header is invalid");
+
+        // A segment longer than what is left.
+        let mut corrupt = jpeg[..sof + 4].to_vec();
+        corrupt[sof + 3] = 0xff;
+        assert_eq!(message(&corrupt), "JPEG segment ends early");
+
+        assert_eq!(message(&[0xff, 0xd8, 0xff, 0xd9]), "JPEG image has no scan");
+        assert_eq!(message(&[0xff, 0xd8, 0x00]), "JPEG marker is missing");
+    }
+}
@@ -0,0 +1,300 @@
+// Reads the format and size of texture images from their headers, without
+// decoding them, and decodes the few formats which can be turned into PNGs
+// for previewing: JPEG and the DXT compressed DDS textures.
+
+mod dds;
+mod jpeg;
+mod png;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fmt;
+
+#[derive(Debug, Fail)]
+pub enum DecodeError {
+    #[fail(display = "decoding {} isn't supported", _0)]
+    Unsupported(String),
+
+    #[fail(display = "{}", _0)]
+    Invalid(String),
+}
+
+/// The format of a texture image, from its first bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    /// JPEG.
+    Jpeg,
+    /// PNG.
+    Png,
+    /// DDS, with the four character code of its compression, if it is
+    /// compressed.
+    Dds(Option<String>),
+    /// KTX, version 1.
+    Ktx,
+    /// KTX2, and whether it holds Basis Universal data rather than a GPU
+    /// format.
+    Ktx2 {
+        /// Whether the texture holds Basis Universal data.
+        basis: bool,
+    },
+    /// A bare Basis Universal file.
+    Basis,
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageFormat::Jpeg => f.write_str("JPEG"),
+            ImageFormat::Png => f.write_str("PNG"),
+            ImageFormat::Dds(Some(code)) => write!(f, "DDS/{}", code),
+            ImageFormat::Dds(None) => f.write_str("DDS"),
+            ImageFormat::Ktx => f.write_str("KTX"),
+            ImageFormat::Ktx2 { basis: true } => f.write_str("KTX2/Basis"),
+            ImageFormat::Ktx2 { basis: false } => f.write_str("KTX2"),
+            ImageFormat::Basis => f.write_str("Basis"),
+        }
+    }
+}
+
+/// Tells the format of an image from its first bytes, whatever its name
+/// says.
+pub fn image_format(image: &[u8]) -> Option<ImageFormat> {
+    if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(ImageFormat::Jpeg)
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if image.starts_with(b"DDS ") {
+        Some(ImageFormat::Dds(dds::four_cc(image)))
+    } else if image.starts_with(b"\xabKTX 11\xbb\r\n\x1a\n") {
+        Some(ImageFormat::Ktx)
+    } else if image.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+        // Basis Universal data has no Vulkan format of its own.
+        let vk_format = image.get(12..16).map(LittleEndian::read_u32);
+        Some(ImageFormat::Ktx2 {
+            basis: vk_format == Some(0),
+        })
+    } else if image.starts_with(b"sB") {
+        Some(ImageFormat::Basis)
+    } else {
+        None
+    }
+}
+
+/// An image of 8 bit RGBA pixels, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode(self)
+    }
+
+    /// The pixels of a rectangle of the image, from `x`, `y` at its top
+    /// left, cut down to the part of it within the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> RgbaImage {
+        let (x, y) = (x.min(self.width), y.min(self.height));
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in y..y + height {
+            let start = (row as usize * self.width as usize + x as usize) * 4;
+            pixels.extend_from_slice(&self.pixels[start..start + width as usize * 4]);
+        }
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Decodes a JPEG, or a DDS compressed as DXT1, DXT3 or DXT5.
+pub fn decode(image: &[u8]) -> Result<RgbaImage, DecodeError> {
+    match image_format(image) {
+        Some(ImageFormat::Jpeg) => jpeg::decode(image),
+        Some(ImageFormat::Dds(_)) => dds::decode(image),
+        Some(format) => Err(DecodeError::Unsupported(format.to_string())),
+        None => Err(DecodeError::Unsupported("an unknown format".to_string())),
+    }
+}
+
+/// Returns the width and height of a PNG, JPEG, DDS or KTX2 image.
+pub fn image_dimensions(image: &[u8]) -> Option<(u32, u32)> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") && image.len() >= 24 {
+        return Some((
+            BigEndian::read_u32(&image[16..20]),
+            BigEndian::read_u32(&image[20..24]),
+        ));
+    }
+    if image.starts_with(b"DDS ") && image.len() >= 20 {
+        return Some((
+            LittleEndian::read_u32(&image[16..20]),
+            LittleEndian::read_u32(&image[12..16]),
+        ));
+    }
+    if image.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") && image.len() >= 28 {
+        return Some((
+            LittleEndian::read_u32(&image[20..24]),
+            LittleEndian::read_u32(&image[24..28]),
+        ));
+    }
+    if image.starts_with(&[0xff, 0xd8]) {
+        // Walk the marker segments up to the start of frame, which holds the
+        // image size.
+        let mut pos = 2;
+        while pos + 9 <= image.len() && image[pos] == 0xff {
+            let marker = image[pos + 1];
+            let segment_len = BigEndian::read_u16(&image[pos + 2..pos + 4]) as usize;
+            let is_start_of_frame =
+                (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker);
+            if is_start_of_frame {
+                return Some((
+                    BigEndian::read_u16(&image[pos + 7..pos + 9]) as u32,
+                    BigEndian::read_u16(&image[pos + 5..pos + 7]) as u32,
+                ));
+            }
+            pos += 2 + segment_len;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut image = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        image.extend_from_slice(&width.to_be_bytes());
+        image.extend_from_slice(&height.to_be_bytes());
+        image
+    }
+
+    #[test]
+    fn reads_image_dimensions() {
+        assert_eq!(image_dimensions(&png(1, 1)), Some((1, 1)));
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x00,
+            0x20, 0x00, 0x40, 0x03,
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((64, 32)));
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn reads_the_dimensions_of_any_kind_of_png() {
+        // The size comes before the bit depth, color type, compression,
+        // filter method and interlacing, whichever they are.
+        for (color_type, interlace) in &[(0, 0), (2, 1), (3, 0), (6, 1)] {
+            let mut image = png(640, 480);
+            image.extend_from_slice(&[8, *color_type, 0, 0, *interlace]);
+            assert_eq!(image_dimensions(&image), Some((640, 480)));
+            assert_eq!(image_format(&image), Some(ImageFormat::Png));
+            match decode(&image) {
+                Err(DecodeError::Unsupported(format)) => assert_eq!(format, "PNG"),
+                other => panic!("{:?}", other),
+            }
+        }
+        // A header cut short has no size.
+        assert_eq!(image_dimensions(&png(640, 480)[..20]), None);
+    }
+
+    #[test]
+    fn stops_at_truncated_or_corrupt_headers() {
+        let mut dds = b"DDS ".to_vec();
+        dds.resize(16, 0);
+        assert_eq!(image_dimensions(&dds), None);
+        let ktx2 = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+        assert_eq!(image_dimensions(&ktx2), None);
+        // A JPEG segment claiming to run past the end, and one whose
+        // length loops back onto itself.
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 0xff, 0xff, 0, 0, 0, 0, 0];
+        assert_eq!(image_dimensions(&jpeg), None);
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 0x00, 0x00, 0, 0, 0, 0, 0];
+        assert_eq!(image_dimensions(&jpeg), None);
+        match decode(&jpeg) {
+            Err(DecodeError::Invalid(_)) => {}
+            other => panic!("{:?}", other),
+        }
+        match decode(b"DDS ") {
+            Err(DecodeError::Invalid(message)) => assert_eq!(message, "DDS header ends early"),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    /// A baseline JPEG of 8 by 8 gray pixels, coded with one Huffman code
+    /// in each table.
+    pub(crate) fn gray_jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xdb, 0x00, 0x43, 0x00];
+        jpeg.extend_from_slice(&[1; 64]);
+        jpeg.extend_from_slice(&[
+            0xff, 0xc0, 0x00, 0x0b, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00,
+        ]);
+        for class in &[0x00, 0x10] {
+            jpeg.extend_from_slice(&[0xff, 0xc4, 0x00, 0x14, *class, 0x01]);
+            jpeg.extend_from_slice(&[0; 16]);
+        }
+        jpeg.extend_from_slice(&[0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00]);
+        // A DC difference of zero and the end of the block.
+        jpeg.extend_from_slice(&[0x3f, 0xff, 0xd9]);
+        jpeg
+    }
+
+    #[test]
+    fn crops_keep_the_pixels_within_the_image() {
+        let image = RgbaImage {
+            width: 3,
+            height: 2,
+            pixels: (0..24).collect(),
+        };
+        let crop = image.crop(1, 0, 2, 2);
+        assert_eq!((crop.width, crop.height), (2, 2));
+        assert_eq!(
+            crop.pixels,
+            [4, 5, 6, 7, 8, 9, 10, 11, 16, 17, 18, 19, 20, 21, 22, 23]
+        );
+        let past = image.crop(2, 1, 5, 5);
+        assert_eq!((past.width, past.height), (1, 1));
+        assert_eq!(past.pixels, [20, 21, 22, 23]);
+        assert_eq!(image.crop(3, 2, 1, 1).pixels, []);
+    }
+
+    #[test]
+    fn decodes_jpeg() {
+        let image = decode(&gray_jpeg()).unwrap();
+        assert_eq!((image.width, image.height), (8, 8));
+        assert!(image
+            .pixels
+            .chunks(4)
+            .all(|pixel| pixel == [128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn detects_formats_by_their_magic_bytes() {
+        assert_eq!(image_format(&png(1, 1)), Some(ImageFormat::Png));
+        assert_eq!(
+            image_format(&[0xff, 0xd8, 0xff, 0xe0]),
+            Some(ImageFormat::Jpeg)
+        );
+        let mut dds = b"DDS ".to_vec();
+        dds.resize(128, 0);
+        dds[84..88].copy_from_slice(b"DXT5");
+        let format = image_format(&dds).unwrap();
+        assert_eq!(format.to_string(), "DDS/DXT5");
+        let mut ktx2 = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+        ktx2.extend_from_slice(&37u32.to_le_bytes());
+        assert_eq!(
+            image_format(&ktx2),
+            Some(ImageFormat::Ktx2 { basis: false })
+        );
+        assert_eq!(image_format(b"sB\x13\0"), Some(ImageFormat::Basis));
+        assert_eq!(image_format(b"not an image"), None);
+        match decode(b"\xabKTX 11\xbb\r\n\x1a\n") {
+            Err(DecodeError::Unsupported(format)) => assert_eq!(format, "KTX"),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Writes RGBA images as PNG, without filtering the scanlines, which leaves
+// the compression to zlib.
+
+use super::RgbaImage;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+pub fn encode(image: &RgbaImage) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    // Eight bits per sample, RGBA, deflate, no filtering, no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let row_len = image.width as usize * 4;
+    for row in image.pixels.chunks(row_len.max(1)) {
+        // Writing to a Vec can't fail.
+        let _ = encoder.write_all(&[0]);
+        let _ = encoder.write_all(row);
+    }
+    let data = encoder.finish().unwrap_or_default();
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &data);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{BigEndian, ByteOrder};
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    /// The kind and data of each chunk of a PNG, checking their CRCs.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = BigEndian::read_u32(&rest[..4]) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = BigEndian::read_u32(&rest[8 + len..12 + len]);
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(kind);
+            hasher.update(data);
+            assert_eq!(hasher.finalize(), crc);
+            chunks.push(([kind[0], kind[1], kind[2], kind[3]], data));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn writes_unfiltered_scanlines_without_interlacing() {
+        let image = RgbaImage {
+            width: 3,
+            height: 2,
+            pixels: (0..24).collect(),
+        };
+        let png = encode(&image);
+        let chunks = chunks(&png);
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        let header = chunks[0].1;
+        assert_eq!(BigEndian::read_u32(&header[0..4]), 3);
+        assert_eq!(BigEndian::read_u32(&header[4..8]), 2);
+        // Eight bit RGBA, with the adaptive filters and no interlacing.
+        assert_eq!(&header[8..], [8, 6, 0, 0, 0]);
+
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(chunks[1].1)
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        // Each row has the filter type None, then its pixels as they are.
+        assert_eq!(scanlines.len(), 2 * (1 + 12));
+        for (row, scanline) in scanlines.chunks(13).enumerate() {
+            assert_eq!(scanline[0], 0);
+            assert_eq!(&scanline[1..], &image.pixels[row * 12..row * 12 + 12]);
+        }
+        assert_eq!(super::super::image_dimensions(&png), Some((3, 2)));
+    }
+
+    #[test]
+    fn writes_empty_images() {
+        let png = encode(&RgbaImage {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        });
+        let chunks = chunks(&png);
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(chunks[1].1)
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert!(scanlines.is_empty());
+    }
+}
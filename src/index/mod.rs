@@ -0,0 +1,203 @@
+// Checks the hash table index of a package against its central directory.
+// Readers which trust the index go straight to the recorded offset, so an
+// index which is stale or incomplete after a package was edited by another
+// tool makes resources silently unreachable for them.
+
+use crate::archive;
+use crate::archive::encryption::{self, Decryption, PackageView};
+pub use crate::archive::hash_index::HASH_INDEX_ENTRY;
+use crate::archive::hash_index::{self, HashIndex};
+use crate::md5;
+use failure::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// How the hash index of a package agrees with its zip directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IndexReport {
+    /// The records of the index.
+    pub records: usize,
+    /// Whether the records are sorted by key, as readers which search the
+    /// index need them to be.
+    pub sorted: bool,
+    /// Keys which appear in more than one record.
+    pub duplicate_keys: usize,
+    /// Groups of entry names which share a key, such as names which only
+    /// differ in case.
+    pub colliding_names: Vec<Vec<String>>,
+    /// The entries of the zip directory.
+    pub entries: usize,
+    /// Entries whose record points at their local header.
+    pub covered: usize,
+    /// Entries which have no record in the index.
+    pub missing: Vec<String>,
+    /// Entries whose record doesn't point at their local header.
+    pub stale: Vec<String>,
+    /// Records whose key matches no entry.
+    pub dangling: usize,
+    /// Every record as (key, offset, name of the entry at the offset).
+    pub listing: Vec<(String, u64, Option<String>)>,
+}
+
+impl IndexReport {
+    /// Whether every entry can be found through the index as it is.
+    pub fn is_consistent(&self) -> bool {
+        self.sorted
+            && self.duplicate_keys == 0
+            && self.missing.is_empty()
+            && self.stale.is_empty()
+            && self.dangling == 0
+    }
+}
+
+/// Checks the hash index of a package against its zip directory. Returns
+/// `None` if the package has no hash index.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::index;
+/// use std::path::Path;
+///
+/// // The fixture was written without a hash index.
+/// let report = index::check_hash_index(Path::new("tests/fixtures/building.slpk"))?;
+/// assert!(report.is_none());
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn check_hash_index(slpk_file_path: &Path) -> Result<Option<IndexReport>, Error> {
+    check_decrypted_hash_index(slpk_file_path, None)
+}
+
+/// Checks the hash index of a package as `check_hash_index` does, reading
+/// the package through a view which decrypts it when `decryption` is given.
+pub fn check_decrypted_hash_index(
+    slpk_file_path: &Path,
+    decryption: Option<&Decryption>,
+) -> Result<Option<IndexReport>, Error> {
+    let mut slpk_archive = encryption::open_archive(slpk_file_path, decryption)?;
+    let index = match archive::read_entry(&mut slpk_archive, HASH_INDEX_ENTRY)? {
+        Some(bytes) => HashIndex::parse(&bytes)?,
+        None => return Ok(None),
+    };
+    let mut raw_package = PackageView::new(BufReader::new(File::open(slpk_file_path)?), decryption);
+
+    let mut report = IndexReport {
+        records: index.records.len(),
+        sorted: index.is_sorted(),
+        ..IndexReport::default()
+    };
+
+    let mut record_keys = HashSet::new();
+    for record in &index.records {
+        if !record_keys.insert(record.key) {
+            report.duplicate_keys += 1;
+        }
+        report.listing.push((
+            md5::to_hex(&record.key),
+            record.offset,
+            hash_index::local_header_name(&mut raw_package, record.offset),
+        ));
+    }
+
+    let mut names_by_key: HashMap<[u8; 16], Vec<String>> = HashMap::new();
+    for i in 0..slpk_archive.len() {
+        let name = slpk_archive.by_index(i)?.name().to_string();
+        // The index doesn't list itself.
+        if name == HASH_INDEX_ENTRY {
+            continue;
+        }
+        report.entries += 1;
+        names_by_key
+            .entry(hash_index::key_of(&name))
+            .or_default()
+            .push(name.clone());
+
+        match index.lookup(&name) {
+            Some(offset) => {
+                if hash_index::local_header_name(&mut raw_package, offset).as_deref()
+                    == Some(name.as_str())
+                {
+                    report.covered += 1;
+                } else {
+                    report.stale.push(name);
+                }
+            }
+            None => report.missing.push(name),
+        }
+    }
+
+    report.dangling = record_keys
+        .iter()
+        .filter(|key| !names_by_key.contains_key(*key))
+        .count();
+    report.colliding_names = names_by_key
+        .into_values()
+        .filter(|names| names.len() > 1)
+        .collect();
+    report.colliding_names.sort();
+    Ok(Some(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn write_package(path: &Path, index: &[(&str, u64)]) {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in &[
+            "3dSceneLayer.json.gz",
+            "nodes/0/3dNodeIndexDocument.json.gz",
+        ] {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(b"{}").unwrap();
+        }
+        let mut records: Vec<([u8; 16], u64)> = index
+            .iter()
+            .map(|(name, offset)| (hash_index::key_of(name), *offset))
+            .collect();
+        records.sort();
+        writer
+            .start_file(HASH_INDEX_ENTRY, FileOptions::default())
+            .unwrap();
+        for (key, offset) in records {
+            writer.write_all(&key).unwrap();
+            writer.write_all(&offset.to_le_bytes()).unwrap();
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[test]
+    fn stale_and_missing_records_are_reported() {
+        let dir = TempDir::new("index");
+        let path = dir.join("package.slpk");
+        // The first entry's local header is at the start of the package. The
+        // second record points at the first entry's header too, and a third
+        // names an entry which doesn't exist.
+        write_package(
+            &path,
+            &[
+                ("3dSceneLayer.json.gz", 0),
+                ("nodes/0/3dNodeIndexDocument.json.gz", 0),
+                ("gone.json", 0),
+            ],
+        );
+        let report = check_hash_index(&path).unwrap().unwrap();
+        write_package(&path, &[]);
+        let empty = check_hash_index(&path).unwrap().unwrap();
+
+        assert_eq!(report.entries, 2);
+        assert_eq!(report.covered, 1);
+        assert_eq!(report.stale, vec!["nodes/0/3dNodeIndexDocument.json.gz"]);
+        assert_eq!(report.dangling, 1);
+        assert!(!report.is_consistent());
+        assert_eq!(report.listing[0].2.as_deref(), Some("3dSceneLayer.json.gz"));
+
+        assert_eq!(empty.missing.len(), 2);
+    }
+}
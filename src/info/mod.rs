@@ -0,0 +1,339 @@
+// Describes the layers of a package from their layer documents alone, for a
+// look at what a package holds before deciding what to do with it.
+
+use crate::archive;
+pub use crate::archive::PackageFlavor;
+use crate::features;
+use crate::i3s;
+pub use crate::i3s::METADATA_DOCUMENT;
+use crate::i3s::{Attribute, SceneLayer, SlpkVersion};
+use crate::json::Value;
+use failure::Error;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// The names of the vertex attributes a geometry buffer may hold, in the
+/// order the I3S specification lists them.
+const BUFFER_ATTRIBUTES: [&str; 7] = [
+    "position",
+    "normal",
+    "uv0",
+    "color",
+    "uvRegion",
+    "featureId",
+    "faceRange",
+];
+
+/// What the layer document of a layer says about it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerInfo {
+    /// The id of the layer.
+    pub id: usize,
+    /// The folder holding the layer, either empty or `layers/<n>/`.
+    pub root: String,
+    /// 3DObject, IntegratedMesh, Point, PointCloud or Building.
+    pub layer_type: Option<String>,
+    /// The name of the layer.
+    pub name: Option<String>,
+    /// The well-known id of the spatial reference of the layer.
+    pub wkid: Option<u32>,
+    /// The I3S version from the store of the layer document, or that of
+    /// the package when the store has none.
+    pub version: SlpkVersion,
+    /// The formats of the texture sets, such as jpg or dds, or for layers
+    /// without texture sets the MIME types of their store.
+    pub texture_encodings: Vec<String>,
+    /// A description of each buffer of each geometry definition.
+    pub geometry_buffers: Vec<String>,
+    /// What the layer declares which this build of slpkg can't decode, as
+    /// `features::capabilities` says, with why.
+    pub undecodable: Vec<String>,
+    /// The nodes of the node pages, or the count from `metadata.json` for
+    /// layers without node pages.
+    pub nodes: Option<u64>,
+    /// For a point cloud, the points of all of its nodes, where each level
+    /// of detail holds points of its own.
+    pub points: Option<u64>,
+    /// Each attribute of the layer, its field definition joined with its
+    /// storage info.
+    pub attributes: Vec<Attribute>,
+}
+
+/// What a package holds, from its layer documents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlpkInfo {
+    /// Whether the package gzips its resources, as a `.slpk` does, or
+    /// stores them as they are, as an `.eslpk` does.
+    pub flavor: PackageFlavor,
+    /// The I3S version of the package, as `i3s::detect_version` gives it.
+    pub version: SlpkVersion,
+    /// Whether the package has no `metadata.json`, so that its version and
+    /// node count were inferred from its layer.
+    pub metadata_inferred: bool,
+    /// Each layer of the package.
+    pub layers: Vec<LayerInfo>,
+}
+
+fn string_member(value: &Value, name: &str) -> Option<String> {
+    value.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Pushes each string of `values` which isn't already in `list`.
+fn push_distinct<'a>(list: &mut Vec<String>, values: impl Iterator<Item = &'a str>) {
+    for value in values {
+        if !list.iter().any(|listed| listed == value) {
+            list.push(value.to_string());
+        }
+    }
+}
+
+fn texture_encodings(document: &Value) -> Vec<String> {
+    let mut encodings = Vec::new();
+    let texture_sets = document
+        .get("textureSetDefinitions")
+        .and_then(Value::as_array);
+    for texture_set in texture_sets.into_iter().flatten() {
+        let formats = texture_set.get("formats").and_then(Value::as_array);
+        push_distinct(
+            &mut encodings,
+            formats
+                .into_iter()
+                .flatten()
+                .filter_map(|format| format.get("format").and_then(Value::as_str)),
+        );
+    }
+    if encodings.is_empty() {
+        let store_encodings = document
+            .get("store")
+            .and_then(|store| store.get("textureEncoding"))
+            .and_then(Value::as_array);
+        push_distinct(
+            &mut encodings,
+            store_encodings
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str),
+        );
+    }
+    encodings
+}
+
+/// Describes each buffer as `<definition>.<buffer>:` followed by the
+/// attributes it holds, which for Draco compressed buffers are listed in
+/// the buffer's `compressedAttributes`.
+fn geometry_buffers(document: &Value) -> Vec<String> {
+    let mut buffers = Vec::new();
+    let definitions = document
+        .get("geometryDefinitions")
+        .and_then(Value::as_array);
+    for (definition_idx, definition) in definitions.into_iter().flatten().enumerate() {
+        let definition_buffers = definition.get("geometryBuffers").and_then(Value::as_array);
+        for (buffer_idx, buffer) in definition_buffers.into_iter().flatten().enumerate() {
+            let description = match buffer.get("compressedAttributes") {
+                Some(compressed) => format!(
+                    "{} compressed {}",
+                    compressed
+                        .get("encoding")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown"),
+                    compressed
+                        .get("attributes")
+                        .and_then(Value::as_array)
+                        .map(|attributes| attributes
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .collect::<Vec<_>>()
+                            .join(", "))
+                        .unwrap_or_default()
+                ),
+                None => BUFFER_ATTRIBUTES
+                    .iter()
+                    .filter(|attribute| buffer.get(attribute).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            buffers.push(format!(
+                "{}.{}: {}",
+                definition_idx, buffer_idx, description
+            ));
+        }
+    }
+    buffers
+}
+
+fn layer_info<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: SceneLayer,
+    metadata: Option<&Value>,
+    version: &SlpkVersion,
+) -> Result<LayerInfo, Error> {
+    let document = &layer.document;
+    let mut points = None;
+    let nodes = if layer.uses_node_pages() {
+        let page_nodes = i3s::read_page_nodes(archive, &layer)?;
+        if layer.is_point_cloud() {
+            points = Some(
+                page_nodes
+                    .iter()
+                    .filter_map(|node| node.get("vertexCount").and_then(Value::as_u64))
+                    .sum(),
+            );
+        }
+        Some(page_nodes.len() as u64)
+    } else {
+        metadata.and_then(|metadata| metadata.get("nodeCount").and_then(Value::as_u64))
+    };
+    Ok(LayerInfo {
+        id: layer.id,
+        root: layer.root.clone(),
+        layer_type: layer.layer_type.clone(),
+        name: string_member(document, "name"),
+        wkid: layer.wkid,
+        version: SlpkVersion::of_layer(&layer).unwrap_or_else(|| version.clone()),
+        texture_encodings: texture_encodings(document),
+        geometry_buffers: geometry_buffers(document),
+        undecodable: features::undecodable(document),
+        nodes,
+        points,
+        attributes: i3s::declared_attributes(document),
+    })
+}
+
+/// Summarizes each layer of a package from its layer document and node
+/// pages, and from `metadata.json` where the layer document leaves
+/// something out.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::info;
+/// use std::path::Path;
+///
+/// let info = info::info(Path::new("tests/fixtures/building.slpk"))?;
+/// assert_eq!(info.version.to_string(), "1.7");
+/// let layer = &info.layers[0];
+/// assert_eq!(layer.name.as_deref(), Some("Synthetic"));
+/// assert_eq!(layer.nodes, Some(4));
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn info(slpk_file_path: &Path) -> Result<SlpkInfo, Error> {
+    let mut archive = archive::open_slpk_archive(slpk_file_path)?;
+    info_of(&mut archive, &slpk_file_path.to_string_lossy())
+}
+
+/// Summarizes the layers of a package as `info` does, where `name` is the
+/// name of the package, which tells an `.eslpk` whose resources are all
+/// stored from an `.slpk`.
+pub fn info_of<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<SlpkInfo, Error> {
+    let flavor = PackageFlavor::of_package(archive, name)?;
+    let layers = i3s::read_layers(archive)?;
+    if layers.is_empty() {
+        return Err(Error::from(i3s::I3sError::MissingSceneLayerDocument));
+    }
+    let mut metadata = i3s::read_metadata(archive, layers.first())?;
+    // Layers with node pages are counted from them.
+    if let Some(first) = layers.first().filter(|layer| !layer.uses_node_pages()) {
+        metadata.count_nodes(archive, first);
+    }
+    let version = SlpkVersion::of_package(Some(&metadata.document), layers.first());
+    let layers = layers
+        .into_iter()
+        .map(|layer| layer_info(archive, layer, Some(&metadata.document), &version))
+        .collect::<Result<_, Error>>()?;
+    Ok(SlpkInfo {
+        flavor,
+        version,
+        metadata_inferred: metadata.inferred,
+        layers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    #[test]
+    fn layers_are_summarized_from_their_documents() {
+        let dir = TempDir::new("info");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"name":"Buildings","layerType":"3DObject","store":{},
+                    "spatialReference":{"wkid":102100,"latestWkid":3857},"nodePages":{},
+                    "textureSetDefinitions":[{"formats":[{"name":"0","format":"jpg"},{"name":"0_0_1","format":"dds"}]},
+                        {"formats":[{"name":"0","format":"jpg"}]}],
+                    "geometryDefinitions":[{"geometryBuffers":[
+                        {"offset":8,"position":{},"normal":{},"uv0":{},"color":{}},
+                        {"compressedAttributes":{"encoding":"draco","attributes":["position","uv0"]}}]}]}"#,
+            )
+            .entry("metadata.json", br#"{"I3SVersion":"1.8","nodeCount":7}"#)
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"index":0,"children":[1]},{"index":1}]}"#,
+            )
+            .write_to_file(&path)
+            .unwrap();
+        let without_layer = dir.join("without-layer.slpk");
+        SyntheticPackage::new()
+            .entry("metadata.json", b"{}")
+            .write_to_file(&without_layer)
+            .unwrap();
+        let summary = info(&path).unwrap();
+        let missing_layer = info(&without_layer).unwrap_err();
+
+        assert_eq!(
+            summary.layers,
+            vec![LayerInfo {
+                id: 0,
+                root: String::new(),
+                layer_type: Some("3DObject".to_string()),
+                name: Some("Buildings".to_string()),
+                wkid: Some(3857),
+                // From metadata.json, as the store gives none.
+                version: SlpkVersion::V1_8,
+                texture_encodings: vec!["jpg".to_string(), "dds".to_string()],
+                geometry_buffers: vec![
+                    "0.0: position, normal, uv0, color".to_string(),
+                    "0.1: draco compressed position, uv0".to_string(),
+                ],
+                undecodable: vec![
+                    "Draco compressed geometry buffers are present, but decoding Draco \
+                     compressed geometry isn't in any build of slpkg yet"
+                        .to_string()
+                ],
+                // From the node pages rather than metadata.json.
+                nodes: Some(2),
+                points: None,
+                attributes: Vec::new(),
+            }]
+        );
+        assert_eq!(summary.version, SlpkVersion::V1_8);
+        assert!(missing_layer.to_string().contains("3dSceneLayer.json"));
+    }
+
+    #[test]
+    fn point_clouds_count_their_points() {
+        let dir = TempDir::new("info-points");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"PointCloud","store":{"version":"2.0","index":{}}}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"resourceId":0,"firstChild":1,"childCount":1,"vertexCount":30},
+                    {"resourceId":1,"vertexCount":20}]}"#,
+            )
+            .write_to_file(&path)
+            .unwrap();
+        let summary = info(&path).unwrap();
+
+        assert_eq!(summary.layers[0].nodes, Some(2));
+        assert_eq!(summary.layers[0].points, Some(50));
+    }
+}
@@ -0,0 +1,601 @@
+// A small JSON reader/writer, sufficient for the I3S documents found inside
+// a scene layer package. Object members are kept in document order so
+// that documents can be written back out without reshuffling their keys.
+// Numbers keep the text they were written as too, as writing their value
+// back would round integers beyond 2^53, such as large object ids, and spell
+// out exponents, so that `1e300` would take 301 digits.
+
+use std::fmt::{self, Write};
+
+/// How deeply arrays and objects may nest. Each level is a level of
+/// recursion, and I3S documents nest a handful of levels at most.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Invalid JSON at line {}, column {}: {}",
+    line, column, message
+)]
+pub struct JsonError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// A number, compared by its value, and written as it was parsed.
+#[derive(Clone, Debug)]
+pub struct Number {
+    value: f64,
+    /// The text the number was parsed from, unless writing `value` gives it.
+    text: Option<Box<str>>,
+}
+
+impl Number {
+    pub fn as_f64(&self) -> f64 {
+        self.value
+    }
+
+    /// The number parsed from `text`. The text is kept unless it is an
+    /// integer short enough to be written back the same from its value, as
+    /// most numbers in a document are, or isn't a JSON number, such as `1.`
+    /// or `01`, which are read for the documents of lenient exporters.
+    fn parse(text: &str) -> Option<Number> {
+        let value = text.parse::<f64>().ok()?;
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let short_integer = digits.len() <= 15 && digits.bytes().all(|b| b.is_ascii_digit());
+        Some(Number {
+            value,
+            text: if short_integer || !is_json_number(text) {
+                None
+            } else {
+                Some(text.into())
+            },
+        })
+    }
+}
+
+/// Whether `text` is a number as JSON writes them, such as `-0.5e+3`.
+fn is_json_number(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let mut pos = usize::from(bytes.first() == Some(&b'-'));
+    let digits = |pos: &mut usize| {
+        let start = *pos;
+        while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+            *pos += 1;
+        }
+        *pos - start
+    };
+    let integer_start = pos;
+    let integer = digits(&mut pos);
+    if integer == 0 || (integer > 1 && bytes[integer_start] == b'0') {
+        return false;
+    }
+    if bytes.get(pos) == Some(&b'.') {
+        pos += 1;
+        if digits(&mut pos) == 0 {
+            return false;
+        }
+    }
+    if let Some(b'e') | Some(b'E') = bytes.get(pos) {
+        pos += 1;
+        if let Some(b'+') | Some(b'-') = bytes.get(pos) {
+            pos += 1;
+        }
+        if digits(&mut pos) == 0 {
+            return false;
+        }
+    }
+    pos == bytes.len()
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.value == other.value
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.text {
+            Some(text) => f.write_str(text),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Number {
+        Number { value, text: None }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(n.value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if n.value >= 0.0 && n.value.fract() == 0.0 => Some(n.value as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Reads an array of numbers, such as an MBS or OBB center.
+    pub fn as_f64_vec(&self) -> Option<Vec<f64>> {
+        self.as_array()?.iter().map(Value::as_f64).collect()
+    }
+
+    pub fn to_pretty_string(&self, indent: usize) -> String {
+        self.to_indented_string(&" ".repeat(indent))
+    }
+
+    /// Writes each member and item on a line of its own, indented by `unit`
+    /// for each level, such as four spaces or a tab.
+    pub fn to_indented_string(&self, unit: &str) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, Some(unit), 0);
+        out
+    }
+
+    /// Writes the document without any whitespace.
+    pub fn to_compact_string(&self) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, None, 0);
+        out
+    }
+
+    /// Sorts the members of every object by their keys, at every level.
+    /// Members with the same key keep their order.
+    pub fn sort_keys(&mut self) {
+        match self {
+            Value::Object(members) => {
+                members.sort_by(|(a, _), (b, _)| a.cmp(b));
+                for (_, value) in members {
+                    value.sort_keys();
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(Value::sort_keys),
+            _ => {}
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(Number::from(n))
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::from(n as f64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::from(n as f64)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, JsonError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+pub fn parse_bytes(bytes: &[u8]) -> Result<Value, JsonError> {
+    // Some producers write a UTF-8 byte order mark at the start of documents.
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => parse(text),
+        Err(e) => Err(JsonError {
+            line: 1,
+            column: 1,
+            message: format!("Document is not UTF-8: {}", e),
+        }),
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> JsonError {
+        let consumed = &self.bytes[..self.pos.min(self.bytes.len())];
+        let line = consumed.iter().filter(|&&b| b == b'\n').count() + 1;
+        let line_start = consumed
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |p| p + 1);
+        JsonError {
+            line,
+            column: self.pos - line_start + 1,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str, value: Value) -> Result<Value, JsonError> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("Unexpected character"))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, JsonError> {
+        match self.peek() {
+            Some(b'{') => self.parse_nested(Parser::parse_object),
+            Some(b'[') => self.parse_nested(Parser::parse_array),
+            Some(b'"') => Ok(Value::String(self.parse_string()?)),
+            Some(b't') => self.expect_literal("true", Value::Bool(true)),
+            Some(b'f') => self.expect_literal("false", Value::Bool(false)),
+            Some(b'n') => self.expect_literal("null", Value::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of document")),
+        }
+    }
+
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut Parser<'a>) -> Result<Value, JsonError>,
+    ) -> Result<Value, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Arrays and objects nest too deeply"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<Value, JsonError> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected an object key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("Expected ':' after object key"));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("Expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, JsonError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("Expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // The input came from a &str and we only stop on ASCII bytes, so
+            // this slice is always on a character boundary.
+            out.push_str(std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default());
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("Unterminated string"))?;
+                    self.pos += 1;
+                    match escaped {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.bytes[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            out.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    }
+                }
+                Some(_) => return Err(self.error("Control character in string")),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, JsonError> {
+        let start = self.pos;
+        while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+        | Some(b'0'..=b'9') = self.peek()
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(Number::parse)
+            .map(Value::Number)
+            .ok_or_else(|| self.error("Invalid number"))
+    }
+}
+
+pub fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_number(out: &mut String, n: &Number) {
+    // A number written out of range, such as 1e400, is still valid JSON.
+    if n.value.is_finite() || n.text.is_some() {
+        let _ = write!(out, "{}", n);
+    } else {
+        // JSON has no representation for NaN or infinity.
+        out.push_str("null");
+    }
+}
+
+fn write_newline(out: &mut String, indent: Option<&str>, depth: usize) {
+    if let Some(unit) = indent {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str(unit);
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &Value, indent: Option<&str>, depth: usize) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            if items.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_newline(out, indent, depth + 1);
+                write_value(out, item, indent, depth + 1);
+            }
+            write_newline(out, indent, depth);
+            out.push(']');
+        }
+        Value::Object(members) => {
+            if members.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push('{');
+            for (i, (key, member)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_newline(out, indent, depth + 1);
+                write_string(out, key);
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_value(out, member, indent, depth + 1);
+            }
+            write_newline(out, indent, depth);
+            out.push('}');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nested_document() {
+        let doc =
+            parse(r#"{"a": [1, 2.5, -3e2], "b": {"c": null, "d": true}, "e": "xé\n"}"#).unwrap();
+        assert_eq!(
+            doc.get("a").unwrap().as_f64_vec(),
+            Some(vec![1.0, 2.5, -300.0])
+        );
+        assert_eq!(doc.get("b").unwrap().get("c"), Some(&Value::Null));
+        assert_eq!(doc.get("e").unwrap().as_str(), Some("x\u{e9}\n"));
+    }
+
+    #[test]
+    fn round_trip_preserves_member_order() {
+        let text = "{\n  \"z\": 1,\n  \"a\": [\n    true,\n    false\n  ],\n  \"m\": \"s\"\n}";
+        assert_eq!(parse(text).unwrap().to_pretty_string(2), text);
+    }
+
+    #[test]
+    fn numbers_are_written_as_they_were_parsed() {
+        let text = r#"[12345678901234567890,1e300,1.5E-10,-0,2.50,1e400,42]"#;
+        let doc = parse(text).unwrap();
+        assert_eq!(doc.to_compact_string(), text);
+        assert_eq!(parse("[1.,01.5]").unwrap().to_compact_string(), "[1,1.5]");
+        assert_eq!(parse("2.50").unwrap(), Value::from(2.5));
+        assert!(is_json_number("-0.5e+3") && !is_json_number("01") && !is_json_number("1."));
+    }
+
+    #[test]
+    fn keys_are_sorted_at_every_level() {
+        let mut doc = parse(r#"{"z": [{"b": 1, "a": 2}], "m": {"y": 0, "x": 0}, "a": 3}"#).unwrap();
+        doc.sort_keys();
+        assert_eq!(
+            doc.to_compact_string(),
+            r#"{"a":3,"m":{"x":0,"y":0},"z":[{"a":2,"b":1}]}"#
+        );
+    }
+
+    #[test]
+    fn error_reports_position() {
+        let err = parse("{\n  \"a\": tru\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        let err = parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert_eq!(err.column, MAX_DEPTH + 1);
+    }
+}
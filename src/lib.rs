@@ -0,0 +1,120 @@
+//! The packages slpkg reads and writes, and the commands of the slpkg binary,
+//! which is a thin command line over this library. Most programs want
+//! `unpack::unpack_with_options`, with options built by `UnpackOptions::new`.
+//! Everything the binary needs only for its command line, which is the parsing
+//! of its arguments, the description of them in `cli_spec` and the prompt for
+//! a password, is behind the `cli` feature, so a program depending on the
+//! library with `default-features = false` doesn't build the argument parser.
+//! The library prints nothing itself: each command returns what it found, or
+//! hands it to a callback as it goes, and the text for the terminal is laid
+//! out by the binary, in `src/cli`.
+//!
+//! # Examples
+//!
+//! ```
+//! use slpkg::{info, unpack};
+//! use std::path::Path;
+//!
+//! let package = Path::new("tests/fixtures/building.slpk");
+//! let info = info::info(package)?;
+//! assert_eq!(info.layers[0].layer_type.as_deref(), Some("Building"));
+//!
+//! # let output_dir = std::env::temp_dir().join(format!("slpkg-doc-crate-{}", std::process::id()));
+//! let options = unpack::UnpackOptions::new().output_dir(output_dir.clone());
+//! let summary = unpack::unpack_with_options(package, &options)?;
+//! assert_eq!(summary.entries_unpacked, 13);
+//! # std::fs::remove_dir_all(&output_dir)?;
+//! # Ok::<(), failure::Error>(())
+//! ```
+
+// The Fail derive from failure_derive 0.1 generates its impls inside an
+// anonymous const, which newer compilers flag as non-local.
+#![allow(non_local_definitions)]
+#![warn(missing_docs)]
+
+#[macro_use]
+extern crate failure;
+extern crate zip;
+
+// First, so that the logging macros are in scope in every other module.
+/// The messages of the library, which go to the logger a program installs.
+#[macro_use]
+pub mod log;
+
+mod archive;
+/// Runs a command over many packages, sharing one budget of worker threads.
+pub mod batch;
+/// Exports the bounding volume of each node as GeoJSON or KML.
+pub mod bounds;
+/// Quick checks of whether a package can be read, without unpacking it.
+pub mod check;
+/// A description of the command line, for shells and GUI wrappers.
+#[cfg(feature = "cli")]
+pub mod cli_spec;
+/// Defaults for the options of unpack, read from `slpkg.toml` files.
+pub mod config;
+/// Compares two packages entry by entry, without unpacking either.
+pub mod diff;
+/// Exports the mesh of a node as OBJ or glTF, and attributes as CSV.
+pub mod export;
+/// Extracts the resources of one node, or writes out a single entry.
+pub mod extract;
+/// The optional parts of slpkg, and which of them a build has.
+pub mod features;
+/// Exports the footprint of each top-level node or feature as GeoJSON.
+pub mod footprint;
+#[cfg(test)]
+mod fuzz;
+mod geo;
+mod glob;
+mod http;
+mod i3s;
+mod image;
+/// Checks the hash index of a package against its zip directory.
+pub mod index;
+/// Describes the layers of a package from their layer documents.
+pub mod info;
+mod json;
+/// Heuristic checks for export bugs which only show up in a viewer.
+pub mod lint;
+/// Lists the entries of a package from its zip directory.
+pub mod list;
+mod md5;
+/// Mirrors a layer of a scene service into a package.
+#[cfg(feature = "remote")]
+pub mod mirror;
+/// Packs an unpacked folder into a package again.
+pub mod pack;
+/// Asks for the password of an encrypted package on the terminal.
+#[cfg(feature = "cli")]
+pub mod password;
+/// Patches which turn one version of a package into the next.
+pub mod patch;
+/// Gzips the entries of a package again, at another level.
+pub mod recompress;
+/// Finds and fixes entries whose content doesn't match their name.
+pub mod repair;
+mod schema;
+/// Checks that packages can be unpacked on this machine.
+pub mod self_test;
+/// Serves a package over the I3S REST API.
+pub mod serve;
+mod sha256;
+/// Where the bytes of a package go, by the kind of resource.
+pub mod stats;
+// Public for the integration tests, which build their packages with it, but
+// not part of the library's API.
+#[doc(hidden)]
+pub mod synthetic;
+/// Lists the textures of a package, and converts them to PNG.
+pub mod textures;
+/// The node hierarchy of a layer.
+pub mod tree;
+/// Unpacks a package into a folder, a tar stream or memory.
+pub mod unpack;
+/// Validates the structure of a package against what its layers declare.
+pub mod validate;
+/// Reads every entry of a package to its end, checking its CRC.
+pub mod verify;
+/// Unpacks a package again each time its file is replaced.
+pub mod watch;
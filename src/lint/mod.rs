@@ -0,0 +1,894 @@
+// Heuristic checks for common export bugs which only show up when someone
+// views the layer: placeholder textures, nearly empty geometry, level of
+// detail thresholds which get coarser further down the tree, and attributes
+// which popups and filters can't find. Only the layer document, node pages,
+// node index documents, and the headers of resources are read. The nodes are
+// shared out between worker threads, each with its own handle on the package.
+
+use crate::archive;
+use crate::i3s;
+use crate::i3s::{Hierarchy, Node, SceneLayer};
+use crate::image;
+use byteorder::{ByteOrder, LittleEndian};
+use failure::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zip::ZipArchive;
+
+/// JPEG images can carry metadata in front of the frame header holding
+/// their size, so a fair amount of each texture is read.
+const TEXTURE_HEADER_LEN: usize = 64 * 1024;
+
+/// Why the options of a lint couldn't be parsed.
+#[derive(Debug, Fail)]
+pub enum LintError {
+    /// A `--severity` which isn't `<rule>=<severity>`.
+    #[fail(
+        display = "Invalid severity '{}', expected <rule>=<off|info|warning|error> where rule is small-texture, low-vertex-count, inverted-lod or attribute-mismatch",
+        _0
+    )]
+    InvalidSeverity(String),
+    /// A rule name which isn't one of the four rules.
+    #[fail(
+        display = "Unknown rule '{}', expected small-texture, low-vertex-count, inverted-lod or attribute-mismatch",
+        _0
+    )]
+    UnknownRule(String),
+    /// A `--fail-on` which isn't error, warning or never.
+    #[fail(
+        display = "Invalid --fail-on '{}', expected error, warning or never",
+        _0
+    )]
+    InvalidFailOn(String),
+}
+
+/// A check the lint runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    /// Textures so small they are likely placeholders.
+    SmallTexture,
+    /// Meshes below the root with next to no vertices.
+    LowVertexCount,
+    /// LOD thresholds which get coarser further down the tree.
+    InvertedLod,
+    /// Attributes whose storage info and field definitions don't agree.
+    AttributeMismatch,
+}
+
+const RULES: [Rule; 4] = [
+    Rule::SmallTexture,
+    Rule::LowVertexCount,
+    Rule::InvertedLod,
+    Rule::AttributeMismatch,
+];
+
+impl Rule {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for Rule {
+    type Err = LintError;
+
+    fn from_str(s: &str) -> Result<Rule, LintError> {
+        match s {
+            "small-texture" => Ok(Rule::SmallTexture),
+            "low-vertex-count" => Ok(Rule::LowVertexCount),
+            "inverted-lod" => Ok(Rule::InvertedLod),
+            "attribute-mismatch" => Ok(Rule::AttributeMismatch),
+            _ => Err(LintError::UnknownRule(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Rule::SmallTexture => "small-texture",
+            Rule::LowVertexCount => "low-vertex-count",
+            Rule::InvertedLod => "inverted-lod",
+            Rule::AttributeMismatch => "attribute-mismatch",
+        })
+    }
+}
+
+/// How much a finding matters, from least to most.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// The rule doesn't run.
+    Off,
+    /// Reported, but never fails the lint.
+    Info,
+    /// Fails the lint with `--fail-on warning`, which is the default.
+    Warning,
+    /// Always fails the lint, unless it's `--fail-on never`.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Off => "off",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Overrides the severity of one rule, as `<rule>=<severity>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleSeverity {
+    /// The rule to override.
+    pub rule: Rule,
+    /// Its severity from now on.
+    pub severity: Severity,
+}
+
+impl FromStr for RuleSeverity {
+    type Err = LintError;
+
+    fn from_str(s: &str) -> Result<RuleSeverity, LintError> {
+        let invalid = || LintError::InvalidSeverity(s.to_string());
+        let mut parts = s.splitn(2, '=');
+        let rule = match parts.next().map(str::parse) {
+            Some(Ok(rule)) => rule,
+            _ => return Err(invalid()),
+        };
+        let severity = match parts.next() {
+            Some("off") => Severity::Off,
+            Some("info") => Severity::Info,
+            Some("warning") => Severity::Warning,
+            Some("error") => Severity::Error,
+            _ => return Err(invalid()),
+        };
+        Ok(RuleSeverity { rule, severity })
+    }
+}
+
+/// What the lint checks, and how strictly.
+#[derive(Clone, Debug)]
+pub struct LintOptions {
+    /// Textures with fewer pixels than this are flagged.
+    pub min_texture_area: u64,
+    /// Meshes below the root with fewer vertices than this are flagged.
+    pub min_vertices: u64,
+    /// Every rule is a warning unless overridden here. A later override of
+    /// the same rule wins.
+    pub severities: Vec<RuleSeverity>,
+    /// The rules to run, or all of them when empty.
+    pub checks: Vec<Rule>,
+    /// The number of worker threads, or one per core when `None`.
+    pub threads: Option<usize>,
+}
+
+impl Default for LintOptions {
+    fn default() -> LintOptions {
+        LintOptions {
+            min_texture_area: 16,
+            // Two triangles which share no vertices.
+            min_vertices: 7,
+            severities: Vec::new(),
+            checks: Vec::new(),
+            threads: None,
+        }
+    }
+}
+
+impl LintOptions {
+    fn severity(&self, rule: Rule) -> Severity {
+        if !self.checks.is_empty() && !self.checks.contains(&rule) {
+            return Severity::Off;
+        }
+        self.severities
+            .iter()
+            .rev()
+            .find(|s| s.rule == rule)
+            .map(|s| s.severity)
+            .unwrap_or(Severity::Warning)
+    }
+}
+
+/// Something a rule found wrong with a layer or one of its nodes.
+#[derive(Debug)]
+pub struct Finding {
+    /// The rule which found it.
+    pub rule: Rule,
+    /// The severity the options give the rule.
+    pub severity: Severity,
+    /// Empty for findings about the layer rather than one of its nodes.
+    pub node_id: String,
+    /// The entry the finding is about, or the node index for rules about
+    /// the node as a whole.
+    pub resource: String,
+    /// What is wrong, for a person to read.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.node_id.is_empty() {
+            write!(
+                f,
+                "{} {} layer {}: {}",
+                self.severity, self.rule, self.resource, self.message
+            )
+        } else {
+            write!(
+                f,
+                "{} {} node {} {}: {}",
+                self.severity, self.rule, self.node_id, self.resource, self.message
+            )
+        }
+    }
+}
+
+/// The entries below each node resource folder, such as `nodes/12/`.
+fn entries_by_resource<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+) -> Result<BTreeMap<String, Vec<String>>, Error> {
+    let mut entries: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)?.name().to_string();
+        let relative_name = match name.strip_prefix(layer.root.as_str()) {
+            Some(relative_name) => relative_name,
+            None => continue,
+        };
+        let mut parts = relative_name.splitn(3, '/');
+        if let (Some("nodes"), Some(resource), Some(_)) = (parts.next(), parts.next(), parts.next())
+        {
+            entries.entry(resource.to_string()).or_default().push(name);
+        }
+    }
+    Ok(entries)
+}
+
+/// The vertex count from the node page, or for older packages from the
+/// header of the geometry buffer. The nodes of point clouds have no mesh,
+/// and so no vertex count.
+fn vertex_count<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    node: &Node,
+    geometry: Option<&String>,
+) -> Result<Option<u64>, Error> {
+    if node.point_count.is_some() {
+        return Ok(None);
+    }
+    if node.vertex_count.is_some() {
+        return Ok(node.vertex_count);
+    }
+    let geometry = match geometry {
+        Some(geometry) => geometry,
+        None => return Ok(None),
+    };
+    let header = archive::read_entry_prefix(archive, geometry, 4)?;
+    if header.len() < 4 {
+        return Ok(None);
+    }
+    Ok(Some(u64::from(LittleEndian::read_u32(&header))))
+}
+
+/// Which findings make the lint fail.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FailOn {
+    /// Only errors fail the lint.
+    Error,
+    /// Warnings and errors fail the lint.
+    Warning,
+    /// The lint never fails.
+    Never,
+}
+
+impl FailOn {
+    /// Whether findings of these severities fail the lint.
+    pub fn fails(self, counts: &SeverityCounts) -> bool {
+        match self {
+            FailOn::Error => counts.errors > 0,
+            FailOn::Warning => counts.errors + counts.warnings > 0,
+            FailOn::Never => false,
+        }
+    }
+}
+
+impl FromStr for FailOn {
+    type Err = LintError;
+
+    fn from_str(s: &str) -> Result<FailOn, LintError> {
+        match s {
+            "error" => Ok(FailOn::Error),
+            "warning" => Ok(FailOn::Warning),
+            "never" => Ok(FailOn::Never),
+            _ => Err(LintError::InvalidFailOn(s.to_string())),
+        }
+    }
+}
+
+/// The number of findings of each severity so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SeverityCounts {
+    /// Findings with the error severity.
+    pub errors: usize,
+    /// Findings with the warning severity.
+    pub warnings: usize,
+    /// Findings with the info severity.
+    pub info: usize,
+}
+
+impl SeverityCounts {
+    pub(crate) fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Info => self.info += 1,
+            Severity::Off => {}
+        }
+    }
+}
+
+/// What a lint found, besides the findings themselves.
+#[derive(Debug)]
+pub struct LintSummary {
+    /// The findings of each severity.
+    pub counts: SeverityCounts,
+    /// The time spent in each rule which ran, summed over the worker
+    /// threads.
+    pub timings: Vec<(Rule, Duration)>,
+}
+
+/// What the layer is made of, read once and shared by the workers.
+struct Layer {
+    layer: SceneLayer,
+    hierarchy: Hierarchy,
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+/// The findings of one node, in the order its rules ran.
+struct NodeFindings {
+    position: usize,
+    findings: Vec<Finding>,
+    timings: [Duration; RULES.len()],
+}
+
+fn lint_node<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    shared: &Layer,
+    position: usize,
+    options: &LintOptions,
+) -> Result<NodeFindings, Error> {
+    let Layer {
+        layer,
+        hierarchy,
+        entries,
+    } = shared;
+    let node = &hierarchy.nodes[position];
+    let mut findings = Vec::new();
+    let mut timings = [Duration::default(); RULES.len()];
+    let mut report = |rule: Rule, node: &Node, resource: String, message: String| {
+        let severity = options.severity(rule);
+        if severity != Severity::Off {
+            findings.push(Finding {
+                rule,
+                severity,
+                node_id: node.id.clone(),
+                resource,
+                message,
+            });
+        }
+    };
+
+    let node_entries = entries.get(&node.resource).cloned().unwrap_or_default();
+    let in_folder = |folder: &str| {
+        let prefix = layer.entry_name(&format!("nodes/{}/{}/", node.resource, folder));
+        node_entries
+            .iter()
+            .filter(|name| name.starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    if options.severity(Rule::SmallTexture) != Severity::Off {
+        let start = Instant::now();
+        for texture in in_folder("textures") {
+            let header = archive::read_entry_prefix(archive, &texture, TEXTURE_HEADER_LEN)?;
+            if let Some((width, height)) = image::image_dimensions(&header) {
+                let area = u64::from(width) * u64::from(height);
+                if area < options.min_texture_area {
+                    report(
+                        Rule::SmallTexture,
+                        node,
+                        texture,
+                        format!("texture is {}x{} pixels", width, height),
+                    );
+                }
+            }
+        }
+        timings[Rule::SmallTexture.index()] += start.elapsed();
+    }
+
+    // The root can legitimately be coarse, and nodes without any
+    // vertices are empty rather than under-detailed.
+    if node.level > 0 && options.severity(Rule::LowVertexCount) != Severity::Off {
+        let start = Instant::now();
+        let geometries = in_folder("geometries");
+        if let Some(count) = vertex_count(archive, node, geometries.first())? {
+            if count > 0 && count < options.min_vertices {
+                report(
+                    Rule::LowVertexCount,
+                    node,
+                    geometries
+                        .first()
+                        .cloned()
+                        .unwrap_or_else(|| layer.entry_name(&format!("nodes/{}", node.resource))),
+                    format!("mesh has only {} vertices at level {}", count, node.level),
+                );
+            }
+        }
+        timings[Rule::LowVertexCount.index()] += start.elapsed();
+    }
+
+    if let Some(threshold) = node.lod_threshold {
+        let start = Instant::now();
+        for &child in &node.children {
+            let child = &hierarchy.nodes[child];
+            if let Some(child_threshold) = child.lod_threshold {
+                if child_threshold > threshold {
+                    report(
+                        Rule::InvertedLod,
+                        child,
+                        layer.entry_name(&format!("nodes/{}", child.resource)),
+                        format!(
+                            "lodThreshold {} is larger than the parent's ({}, node {})",
+                            child_threshold, threshold, node.id
+                        ),
+                    );
+                }
+            }
+        }
+        timings[Rule::InvertedLod.index()] += start.elapsed();
+    }
+
+    Ok(NodeFindings {
+        position,
+        findings,
+        timings,
+    })
+}
+
+/// Compares the `fields` of the layer with its `attributeStorageInfo`, and
+/// the attribute folders of each node with the declared keys. Folders are
+/// reported once, at the first node in hierarchy order which has them.
+fn lint_layer(shared: &Layer, options: &LintOptions) -> Vec<Finding> {
+    let Layer {
+        layer,
+        hierarchy,
+        entries,
+    } = shared;
+    let severity = options.severity(Rule::AttributeMismatch);
+    if severity == Severity::Off {
+        return Vec::new();
+    }
+
+    // The folders below `attributes/` of each node, and the first node and
+    // number of nodes holding each one.
+    let mut folders: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (position, node) in hierarchy.nodes.iter().enumerate() {
+        let prefix = layer.entry_name(&format!("nodes/{}/attributes/", node.resource));
+        let mut node_folders: Vec<&str> = entries
+            .get(&node.resource)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.strip_prefix(prefix.as_str()))
+            .filter_map(|rest| rest.split('/').next())
+            .filter(|folder| !folder.is_empty())
+            .collect();
+        node_folders.sort_unstable();
+        node_folders.dedup();
+        for folder in node_folders {
+            folders.entry(folder.to_string()).or_insert((position, 0)).1 += 1;
+        }
+    }
+
+    let document = layer.entry_name(i3s::SCENE_LAYER_DOCUMENT);
+    let mut findings = Vec::new();
+    let attributes = i3s::declared_attributes(&layer.document);
+    for attribute in &attributes {
+        let problem = match attribute.problem() {
+            Some(problem) => problem,
+            None => continue,
+        };
+        let stored_by = attribute
+            .key
+            .as_ref()
+            .and_then(|key| folders.get(key))
+            .map(|&(position, _)| &hierarchy.nodes[position]);
+        let mut message = format!("field '{}' {}", attribute.name, problem);
+        if let (Some(key), Some(node)) = (&attribute.key, stored_by) {
+            message = format!(
+                "{}, its values are first stored in {} by node {}",
+                message, key, node.id
+            );
+        }
+        findings.push(Finding {
+            rule: Rule::AttributeMismatch,
+            severity,
+            node_id: stored_by.map(|node| node.id.clone()).unwrap_or_default(),
+            resource: document.clone(),
+            message,
+        });
+    }
+    for (folder, &(position, nodes)) in &folders {
+        if attributes.iter().any(|a| a.key.as_ref() == Some(folder)) {
+            continue;
+        }
+        let node = &hierarchy.nodes[position];
+        findings.push(Finding {
+            rule: Rule::AttributeMismatch,
+            severity,
+            node_id: node.id.clone(),
+            resource: layer.entry_name(&format!("nodes/{}/attributes/{}", node.resource, folder)),
+            message: format!(
+                "attribute folder {} is not a key in attributeStorageInfo, and {} nodes have it",
+                folder, nodes
+            ),
+        });
+    }
+    findings
+}
+
+/// Passes each finding to `on_finding` as soon as it is found, rather than
+/// holding on to them, so that packages with hundreds of thousands of
+/// findings can be linted in constant memory. Linting waits while
+/// `on_finding` runs, so a slow consumer simply slows it down.
+///
+/// `open` is called once up front and once per worker thread, each of
+/// which takes the next node to lint until there are none left. Nodes are
+/// reported in hierarchy order whatever the number of threads, and the
+/// findings of each node come in the order small-texture, low-vertex-count,
+/// then inverted-lod for its children, so the output is the same from run to
+/// run. Findings about the layer as a whole come before those of any node.
+pub fn lint_streaming<R, O, F>(
+    open: O,
+    options: &LintOptions,
+    mut on_finding: F,
+) -> Result<LintSummary, Error>
+where
+    R: Read + Seek,
+    O: Fn() -> Result<ZipArchive<R>, Error> + Sync,
+    F: FnMut(Finding),
+{
+    let mut archive = open()?;
+    let layer = i3s::read_scene_layer(&mut archive)?;
+    let hierarchy: Hierarchy = i3s::load_hierarchy(&mut archive, &layer)?;
+    let entries = entries_by_resource(&mut archive, &layer)?;
+    drop(archive);
+    let shared = Layer {
+        layer,
+        hierarchy,
+        entries,
+    };
+
+    let mut counts = SeverityCounts::default();
+    let mut timings = [Duration::default(); RULES.len()];
+    let start = Instant::now();
+    for finding in lint_layer(&shared, options) {
+        counts.add(finding.severity);
+        on_finding(finding);
+    }
+    timings[Rule::AttributeMismatch.index()] += start.elapsed();
+
+    let num_nodes = shared.hierarchy.nodes.len();
+    let num_threads = options
+        .threads
+        .unwrap_or_else(num_cpus::get)
+        .clamp(1, num_nodes.max(1));
+    let next_node = AtomicUsize::new(0);
+
+    thread::scope(|scope| -> Result<(), Error> {
+        // Bounded, so that the workers wait for a slow consumer rather than
+        // queueing up findings.
+        let (sender, receiver) = mpsc::sync_channel(num_threads * 4);
+        for _ in 0..num_threads {
+            let sender = sender.clone();
+            let (open, shared, next_node) = (&open, &shared, &next_node);
+            scope.spawn(move || {
+                let mut archive = match open() {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        return;
+                    }
+                };
+                loop {
+                    let position = next_node.fetch_add(1, Ordering::SeqCst);
+                    if position >= num_nodes {
+                        break;
+                    }
+                    let result = lint_node(&mut archive, shared, position, options);
+                    let failed = result.is_err();
+                    // The consumer hangs up when it stops on an error.
+                    if sender.send(result).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Nodes are handed out in order, so only the few which overtake a
+        // slower worker wait here.
+        let mut pending = BTreeMap::new();
+        let mut next_to_report = 0;
+        for result in receiver {
+            let node: NodeFindings = result?;
+            for rule in &RULES {
+                timings[rule.index()] += node.timings[rule.index()];
+            }
+            pending.insert(node.position, node.findings);
+            while let Some(findings) = pending.remove(&next_to_report) {
+                for finding in findings {
+                    counts.add(finding.severity);
+                    on_finding(finding);
+                }
+                next_to_report += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(LintSummary {
+        counts,
+        timings: RULES
+            .iter()
+            .filter(|&&rule| options.severity(rule) != Severity::Off)
+            .map(|&rule| (rule, timings[rule.index()]))
+            .collect(),
+    })
+}
+
+/// Collects every finding, for callers which want them all at once.
+// The command line streams its findings, so only the tests use this.
+#[allow(dead_code)]
+pub fn lint<R, O>(open: O, options: &LintOptions) -> Result<Vec<Finding>, Error>
+where
+    R: Read + Seek,
+    O: Fn() -> Result<ZipArchive<R>, Error> + Sync,
+{
+    let mut findings = Vec::new();
+    lint_streaming(open, options, |finding| findings.push(finding))?;
+    Ok(findings)
+}
+
+/// Lints the package at `slpk_file_path` as `lint_streaming` does, with a
+/// reader of the file for each worker thread.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::lint::{self, FailOn, LintOptions};
+/// use std::path::Path;
+///
+/// let mut findings = Vec::new();
+/// let summary = lint::lint_package(
+///     Path::new("tests/fixtures/building.slpk"),
+///     &LintOptions::default(),
+///     |finding| findings.push(finding.to_string()),
+/// )?;
+/// assert!(findings.is_empty());
+/// assert!(!FailOn::Warning.fails(&summary.counts));
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn lint_package<F: FnMut(Finding)>(
+    slpk_file_path: &Path,
+    options: &LintOptions,
+    on_finding: F,
+) -> Result<LintSummary, Error> {
+    lint_streaming(
+        || archive::open_slpk_archive(slpk_file_path),
+        options,
+        on_finding,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut image = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        image.extend_from_slice(&width.to_be_bytes());
+        image.extend_from_slice(&height.to_be_bytes());
+        image
+    }
+
+    #[test]
+    fn flags_placeholders_and_inverted_lod() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, contents: &[u8]| {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        };
+        add("3dSceneLayer.json", br#"{"nodePages":{}}"#);
+        add(
+            "nodepages/0.json",
+            br#"{"nodes":[
+                {"index":0,"lodThreshold":100,"children":[1,2],
+                    "mesh":{"geometry":{"resource":0,"vertexCount":3}}},
+                {"index":1,"lodThreshold":50,"mesh":{"geometry":{"resource":1,"vertexCount":6}}},
+                {"index":2,"lodThreshold":400,"mesh":{"geometry":{"resource":2,"vertexCount":900}}}]}"#,
+        );
+        add("nodes/1/textures/0.png", &png(1, 1));
+        add("nodes/2/textures/0.png", &png(256, 256));
+        let package = writer.finish().unwrap();
+
+        let open = || Ok(ZipArchive::new(package.clone())?);
+        let findings = lint(open, &LintOptions::default()).unwrap();
+        let summary: Vec<(Rule, &str, &str)> = findings
+            .iter()
+            .map(|f| (f.rule, f.node_id.as_str(), f.resource.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Rule::InvertedLod, "2", "nodes/2"),
+                (Rule::SmallTexture, "1", "nodes/1/textures/0.png"),
+                (Rule::LowVertexCount, "1", "nodes/1"),
+            ]
+        );
+
+        let options = LintOptions {
+            severities: vec![
+                "inverted-lod=off".parse().unwrap(),
+                "small-texture=error".parse().unwrap(),
+            ],
+            ..LintOptions::default()
+        };
+        let mut streamed = Vec::new();
+        let summary = lint_streaming(open, &options, |finding| {
+            streamed.push((finding.rule, finding.severity))
+        })
+        .unwrap();
+        assert_eq!(
+            streamed,
+            vec![
+                (Rule::SmallTexture, Severity::Error),
+                (Rule::LowVertexCount, Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            summary.counts,
+            SeverityCounts {
+                errors: 1,
+                warnings: 1,
+                info: 0
+            }
+        );
+        assert!("inverted-lod=fatal".parse::<RuleSeverity>().is_err());
+        assert!(FailOn::Warning.fails(&summary.counts));
+        assert!(!FailOn::Never.fails(&summary.counts));
+    }
+
+    #[test]
+    fn names_fields_and_folders_without_a_match() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, contents: &[u8]| {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        };
+        add(
+            "3dSceneLayer.json",
+            br#"{"nodePages":{},
+                "fields":[{"name":"OBJECTID","type":"esriFieldTypeOID"},
+                    {"name":"height","type":"esriFieldTypeDouble"}],
+                "attributeStorageInfo":[{"key":"f_0","name":"OBJECTID"},
+                    {"key":"f_2","name":"usage"}]}"#,
+        );
+        add(
+            "nodepages/0.json",
+            br#"{"nodes":[{"index":0,"children":[1,2]},{"index":1},{"index":2}]}"#,
+        );
+        for node in 0..3 {
+            add(&format!("nodes/{}/attributes/f_0/0.bin", node), b"");
+        }
+        add("nodes/2/attributes/f_2/0.bin", b"");
+        add("nodes/1/attributes/f_3/0.bin", b"");
+        add("nodes/2/attributes/f_3/0.bin", b"");
+        let package = writer.finish().unwrap();
+
+        let options = LintOptions {
+            checks: vec![Rule::AttributeMismatch],
+            ..LintOptions::default()
+        };
+        let findings = lint(|| Ok(ZipArchive::new(package.clone())?), &options).unwrap();
+        let summary: Vec<(&str, &str, &str)> = findings
+            .iter()
+            .map(|f| (f.node_id.as_str(), f.resource.as_str(), f.message.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "",
+                    "3dSceneLayer.json",
+                    "field 'height' has no attributeStorageInfo"
+                ),
+                (
+                    "2",
+                    "3dSceneLayer.json",
+                    "field 'usage' has no field definition, its values are first stored in f_2 by node 2"
+                ),
+                (
+                    "1",
+                    "nodes/1/attributes/f_3",
+                    "attribute folder f_3 is not a key in attributeStorageInfo, and 2 nodes have it"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_the_same_with_any_number_of_threads() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, contents: &[u8]| {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        };
+        add("3dSceneLayer.json", br#"{"nodePages":{}}"#);
+        let mut nodes = Vec::new();
+        for index in 0..200 {
+            let children: Vec<String> = [2 * index + 1, 2 * index + 2]
+                .iter()
+                .filter(|&&child| child < 200)
+                .map(|child| child.to_string())
+                .collect();
+            nodes.push(format!(
+                r#"{{"index":{},"lodThreshold":{},"children":[{}],"mesh":{{"geometry":{{"resource":{},"vertexCount":{}}}}}}}"#,
+                index,
+                (index * 37) % 101,
+                children.join(","),
+                index,
+                index % 9
+            ));
+            add(
+                &format!("nodes/{}/textures/0.png", index),
+                &png(index % 5 + 1, 3),
+            );
+        }
+        add(
+            "nodepages/0.json",
+            format!(r#"{{"nodes":[{}]}}"#, nodes.join(",")).as_bytes(),
+        );
+        let package = writer.finish().unwrap();
+
+        let findings = |threads, checks: Vec<Rule>| {
+            let options = LintOptions {
+                threads: Some(threads),
+                checks,
+                ..LintOptions::default()
+            };
+            lint(|| Ok(ZipArchive::new(package.clone())?), &options)
+                .unwrap()
+                .iter()
+                .map(|f| format!("{} {} {}", f.rule, f.node_id, f.resource))
+                .collect::<Vec<_>>()
+        };
+        let serial = findings(1, Vec::new());
+        assert!(serial.len() > 200);
+        assert_eq!(findings(8, Vec::new()), serial);
+
+        let only_lod = findings(8, vec![Rule::InvertedLod]);
+        assert!(!only_lod.is_empty());
+        assert!(only_lod.iter().all(|f| f.starts_with("inverted-lod")));
+    }
+}
@@ -0,0 +1,278 @@
+// Lists the entries of a package from its zip directory alone, without
+// reading any entry data, so that a package can be looked over before
+// committing the disk space to unpack it. The size and date filters of
+// unpack can narrow the listing, as they go by the headers alone too. The
+// entries of an encrypted package are listed without its password, as their
+// headers aren't encrypted.
+
+use crate::archive;
+use crate::archive::{encryption, raw, EntryMeta};
+use crate::json::Value;
+use crate::unpack::{EntryFilters, Filter};
+use failure::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use zip::{DateTime, ZipArchive};
+
+/// An entry of a package, as its zip headers describe it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryInfo {
+    /// The name of the entry in the zip directory.
+    pub name: String,
+    /// The size of its data in the package.
+    pub compressed_size: u64,
+    /// The size of its data once inflated, which for a gzipped entry is
+    /// that of the gzip stream.
+    pub size: u64,
+    /// Whether the entry is named `.gz`, so that unpacking decompresses it.
+    pub gzipped: bool,
+    /// What the start of the entry's data says about it, when the entries
+    /// were listed deeply.
+    pub meta: Option<EntryMeta>,
+}
+
+impl EntryInfo {
+    /// The entry as a JSON object.
+    pub fn to_json(&self) -> Value {
+        let mut members = vec![
+            ("name".to_string(), Value::from(self.name.as_str())),
+            (
+                "compressedSize".to_string(),
+                Value::from(self.compressed_size),
+            ),
+            ("size".to_string(), Value::from(self.size)),
+            ("gzipped".to_string(), Value::Bool(self.gzipped)),
+        ];
+        if let Some(gzip) = self.meta.as_ref().and_then(|meta| meta.gzip.as_ref()) {
+            members.push(("gzipHeader".to_string(), gzip.to_json()));
+        }
+        Value::Object(members)
+    }
+}
+
+/// The entries of a package, in the order of its zip directory. The entries
+/// of a package whose entries are encrypted are listed without decrypting
+/// them, as they would be once decrypted.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::list;
+/// use std::path::Path;
+///
+/// let entries = list::list(Path::new("tests/fixtures/building.slpk"))?;
+/// assert_eq!(entries.len(), 13);
+/// assert_eq!(entries[0].name, "3dSceneLayer.json.gz");
+/// assert!(entries[0].gzipped);
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn list(slpk_file_path: &Path) -> Result<Vec<EntryInfo>, Error> {
+    list_filtered(slpk_file_path, &EntryFilters::default()).map(|(entries, _)| entries)
+}
+
+/// The entries of a package which pass the size and date filters of
+/// `filters`, and how many entries each filter excluded.
+pub fn list_filtered(
+    slpk_file_path: &Path,
+    filters: &EntryFilters,
+) -> Result<(Vec<EntryInfo>, HashMap<Filter, usize>), Error> {
+    let (entries, excluded) = listed(slpk_file_path, filters)?;
+    Ok((
+        entries.into_iter().map(|(_, entry)| entry).collect(),
+        excluded,
+    ))
+}
+
+/// The entries of a package which pass `filters`, as `list_filtered` gives
+/// them, each with what the start of its data says about it too. This reads
+/// the gzip header of every gzipped entry, decrypting it with `password`
+/// when the package is encrypted.
+pub fn list_deep(
+    slpk_file_path: &Path,
+    password: Option<&str>,
+    filters: &EntryFilters,
+) -> Result<(Vec<EntryInfo>, HashMap<Filter, usize>), Error> {
+    let (entries, excluded) = listed(slpk_file_path, filters)?;
+    let decryption = encryption::decryption(slpk_file_path, password)?;
+    let mut archive = encryption::open_archive(slpk_file_path, decryption.as_ref())?;
+    let entries = entries
+        .into_iter()
+        .map(|(index, entry)| {
+            Ok(EntryInfo {
+                meta: Some(archive::entry_meta(&mut archive, index)?),
+                ..entry
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok((entries, excluded))
+}
+
+/// Entries, each with its index in the zip directory, and how many entries
+/// each filter excluded.
+type Listing = (Vec<(usize, EntryInfo)>, HashMap<Filter, usize>);
+
+/// The entries `list_filtered` gives, each with its index in the zip
+/// directory.
+fn listed(slpk_file_path: &Path, filters: &EntryFilters) -> Result<Listing, Error> {
+    let mut reader = BufReader::new(File::open(slpk_file_path)?);
+    let directory = match raw::central_directory_entries(&mut reader) {
+        Ok(directory) => directory,
+        // A zip directory the zip library can't read either fails with the
+        // error it gives.
+        Err(e) => {
+            ZipArchive::new(reader)?;
+            return Err(e);
+        }
+    };
+    let mut entries = Vec::with_capacity(directory.len());
+    let mut excluded = HashMap::new();
+    for (index, entry) in directory.iter().enumerate() {
+        let modified = DateTime::from_msdos(entry.last_modified_date, entry.last_modified_time);
+        if let Some(filter) = filters.excludes(entry.size, modified) {
+            *excluded.entry(filter).or_insert(0) += 1;
+            continue;
+        }
+        let name = archive::names::decoded_raw_name(entry);
+        entries.push((
+            index,
+            EntryInfo {
+                gzipped: name.ends_with(".gz"),
+                name,
+                compressed_size: entry.compressed_size - encryption::header_len(entry),
+                size: entry.size,
+                meta: None,
+            },
+        ));
+    }
+    Ok((entries, excluded))
+}
+
+/// The entries as a JSON array of their `to_json` objects.
+pub fn entries_to_json(entries: &[EntryInfo]) -> Value {
+    Value::Array(entries.iter().map(EntryInfo::to_json).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::GzipProvenance;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use flate2::write::GzEncoder;
+    use flate2::{Compression, GzBuilder};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    #[test]
+    fn lists_entries_without_unpacking_them() {
+        let dir = TempDir::new("list");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodes/0/geometries/0.bin", &[7; 100])
+            .write_to_file(&path)
+            .unwrap();
+        let entries = list(&path).unwrap();
+        let unpacked = dir.join("package").exists();
+
+        assert!(!unpacked);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "3dSceneLayer.json.gz");
+        assert!(entries[0].gzipped);
+        // The size of a gzipped entry is that of the gzip stream it holds.
+        assert!(entries[0].size > 2);
+        assert_eq!(
+            entries[1],
+            EntryInfo {
+                name: "nodes/0/geometries/0.bin".to_string(),
+                compressed_size: 100,
+                size: 100,
+                gzipped: false,
+                meta: None,
+            }
+        );
+    }
+
+    #[test]
+    fn entries_are_listed_by_size() {
+        let dir = TempDir::new("list-size");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("nodes/0/geometries/0.bin", &[7; 100])
+            .entry("nodes/0/textures/0.jpg", &[7; 2000])
+            .entry("metadata.json", b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        let filters = EntryFilters {
+            min_size: Some("100".parse().unwrap()),
+            max_size: Some("1KB".parse().unwrap()),
+            ..EntryFilters::default()
+        };
+        let (entries, excluded) = list_filtered(&path, &filters).unwrap();
+
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["nodes/0/geometries/0.bin"]);
+        assert_eq!(excluded.get(&Filter::MinSize), Some(&1));
+        assert_eq!(excluded.get(&Filter::MaxSize), Some(&1));
+    }
+
+    #[test]
+    fn deep_listings_read_the_gzip_headers() {
+        let dir = TempDir::new("list-deep");
+        let path = dir.join("package.slpk");
+        let mut encoder = GzBuilder::new()
+            .filename("0.bin")
+            .comment("exported by a tool")
+            .mtime(1_551_443_410)
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        let stamped = encoder.finish().unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{}").unwrap();
+        let zeroed = encoder.finish().unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in [
+            ("nodes/0/geometries/0.bin.gz", &stamped[..]),
+            ("3dSceneLayer.json.gz", &zeroed[..]),
+            ("nodes/0/textures/0.jpg", b"jpeg"),
+        ] {
+            writer.start_file(name, stored).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+        let (entries, _) = list_deep(&path, None, &EntryFilters::default()).unwrap();
+        let shallow = list(&path).unwrap();
+
+        let gzip: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.meta.clone().unwrap().gzip)
+            .collect();
+        assert_eq!(
+            gzip,
+            [
+                Some(GzipProvenance {
+                    mtime: Some(1_551_443_410),
+                    filename: Some("0.bin".to_string()),
+                    comment: Some("exported by a tool".to_string()),
+                }),
+                None,
+                None,
+            ]
+        );
+        assert!(shallow.iter().all(|entry| entry.meta.is_none()));
+        assert_eq!(
+            entries[0].to_json().to_compact_string(),
+            format!(
+                concat!(
+                    r#"{{"name":"nodes/0/geometries/0.bin.gz","compressedSize":{0},"#,
+                    r#""size":{0},"gzipped":true,"gzipHeader":{{"mtime":1551443410,"#,
+                    r#""filename":"0.bin","comment":"exported by a tool"}}}}"#
+                ),
+                stamped.len()
+            )
+        );
+    }
+}
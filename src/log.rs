@@ -0,0 +1,130 @@
+// What the library has to say about its work, such as the folder it is
+// deleting or each file it unpacks, goes through the macros here rather than
+// to standard output, so that a program using slpkg decides where it goes.
+// Nothing is written until a logger is installed with `set_logger`, which
+// the slpkg binary does at start up. Results go in the return values of the
+// API, never through the logger.
+//
+// This follows the shape of the `log` crate, which slpkg can't depend on,
+// with a single logger for the whole process and a level past which
+// messages aren't formatted at all.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// `set_logger` was called again, after a logger was installed.
+#[derive(Debug, Fail)]
+#[fail(display = "A logger has already been installed")]
+pub struct LoggerAlreadySet;
+
+/// How much a message matters, from most to least.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Failures which end a run, or the entry they happened on.
+    Error = 1,
+    /// Entries which failed or couldn't be checked, and work left undone.
+    Warn,
+    /// What a run did, such as the folder it deleted and the files it
+    /// unpacked.
+    Info,
+    /// A line per entry.
+    Debug,
+}
+
+/// Where messages go once they pass the level.
+pub trait Log: Send + Sync {
+    /// Writes `message`, which is at `level`.
+    fn log(&self, level: Level, message: &fmt::Arguments);
+}
+
+static LOGGER: OnceLock<Box<dyn Log>> = OnceLock::new();
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+
+/// Installs the logger for the rest of the process, which can only be done
+/// once.
+pub fn set_logger(logger: Box<dyn Log>) -> Result<(), LoggerAlreadySet> {
+    LOGGER.set(logger).map_err(|_| LoggerAlreadySet)
+}
+
+/// Drops messages less important than `level`, which is `Level::Info` until
+/// it is set.
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Whether a message at `level` would be written, for callers which would
+/// rather not build a costly message for nothing.
+pub fn enabled(level: Level) -> bool {
+    level as usize <= MAX_LEVEL.load(Ordering::Relaxed) && LOGGER.get().is_some()
+}
+
+#[doc(hidden)]
+pub fn log(level: Level, message: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    if let Some(logger) = LOGGER.get() {
+        logger.log(level, &message);
+    }
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<(Level, String)>>>);
+
+    impl Log for Recorder {
+        fn log(&self, level: Level, message: &fmt::Arguments) {
+            self.0.lock().unwrap().push((level, message.to_string()));
+        }
+    }
+
+    #[test]
+    fn messages_reach_the_logger_up_to_the_level() {
+        // Other tests may log while this one runs, so only the messages
+        // naming this test are looked at.
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        set_logger(Box::new(Recorder(recorded.clone()))).unwrap();
+        warn!("{} warn", "reach-the-logger");
+        info!("{} info", "reach-the-logger");
+        debug!("{} debug", "reach-the-logger");
+        let second = set_logger(Box::new(Recorder(recorded.clone())));
+
+        let recorded: Vec<_> = recorded
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, message)| message.starts_with("reach-the-logger"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            recorded,
+            vec![
+                (Level::Warn, "reach-the-logger warn".to_string()),
+                (Level::Info, "reach-the-logger info".to_string()),
+            ]
+        );
+        assert!(second.is_err());
+    }
+}
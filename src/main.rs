@@ -1,41 +1,2060 @@
-#[macro_use]
-extern crate failure;
 extern crate structopt;
-extern crate zip;
 
-use std::path::PathBuf;
-use structopt::StructOpt;
+mod cli;
 
-mod unpack;
+#[cfg(feature = "remote")]
+use slpkg::mirror;
+use slpkg::{
+    batch, bounds, cli_spec, config, export, features, footprint, lint, log, pack, patch,
+    recompress, repair, serve, unpack, validate, watch,
+};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use structopt::clap::{ArgMatches, Shell};
+use structopt::StructOpt;
 
+// Parsed once per run, so the size of the unpack options doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 enum Settings {
-    // Packing a directory into a .slpk file is not yet implemented.
-    /*
+    /// Packs an unpacked folder into a .slpk file
     #[structopt(name = "pack")]
     Pack {
+        /// The folder to pack
         #[structopt(parse(from_os_str))]
-        src_dir: PathBuf
+        src_dir: PathBuf,
+
+        /// The package to write, the folder name with .slpk added by default
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The timestamp of every entry, in seconds since 1970-01-01 UTC,
+        /// or mtime for the modification time of each file. By default it
+        /// is SOURCE_DATE_EPOCH, or 1980-01-01 when that isn't set
+        #[structopt(long = "timestamp")]
+        timestamp: Option<pack::Timestamps>,
+
+        /// The gzip level of JSON resources, from 1 for the fastest to 9
+        /// for the smallest package, 6 by default
+        #[structopt(long = "compression-level", raw(conflicts_with = r#""no_compress""#))]
+        compression_level: Option<u32>,
+
+        /// Store JSON resources as they are, without .gz in their names, as
+        /// an ESLPK does
+        #[structopt(long = "no-compress")]
+        no_compress: bool,
+
+        /// Pack the junk files which are ignored by default, such as
+        /// .DS_Store, Thumbs.db, editor backups and .git/
+        #[structopt(long = "no-default-ignores")]
+        no_default_ignores: bool,
+
+        /// An ignore file in .gitignore syntax, read after the .slpkignore
+        /// of the folder
+        #[structopt(long = "ignore-file", parse(from_os_str), raw(number_of_values = "1"))]
+        ignore_files: Vec<PathBuf>,
+
+        /// List every ignored file
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
     },
-    */
     /// Unpacks a .slpk file into a directory
     #[structopt(name = "unpack")]
     Unpack {
-        /// The .slpk file which will be unpacked
+        /// The .slpk file which will be unpacked, an http:// URL of one, or -
+        /// to read one from standard input
         #[structopt(parse(from_os_str))]
         src_file: PathBuf,
 
-        #[structopt(short = "v", long = "verbose")]
-        verbose: bool,
+        /// A header to send with every request when unpacking from a URL,
+        /// such as "Authorization: Bearer <token>"
+        #[structopt(long = "header", raw(number_of_values = "1"))]
+        headers: Vec<unpack::HttpHeader>,
+
+        /// Log every file unpacked, and with -vv also how long each stage
+        /// of unpacking took
+        #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
+        verbose: u8,
+
+        /// Only print warnings and errors
+        #[structopt(short = "q", long = "quiet", conflicts_with = "verbose")]
+        quiet: bool,
+
+        /// Draw a progress bar of the entries unpacked and bytes written
+        #[structopt(long = "progress")]
+        progress: bool,
+
+        /// The number of worker threads, one per core by default. 0 or 1
+        /// unpacks one entry at a time without starting any threads
+        #[structopt(long = "threads")]
+        threads: Option<usize>,
+
+        /// The name of the folder in --output-dir a package read from
+        /// standard input is unpacked into
+        #[structopt(long = "name", default_value = "package", parse(from_os_str))]
+        name: PathBuf,
+
+        /// Unpack again each time the package file is replaced, until Ctrl-C
+        #[structopt(long = "watch", raw(conflicts_with = r#""dry_run""#))]
+        watch: bool,
+
+        #[structopt(flatten)]
+        flags: UnpackFlags,
+
+        /// Write a timeline of the entries each worker thread unpacked, in
+        /// the Chrome trace format
+        #[structopt(long = "trace-json", parse(from_os_str))]
+        trace_json: Option<PathBuf>,
+
+        /// Write the files as a tar stream to this file, or to standard
+        /// output with -, instead of into the output folder
+        #[structopt(
+            long = "to-tar",
+            parse(from_os_str),
+            raw(
+                conflicts_with_all = r#"&["output_dir", "watch", "routes", "dedup", "retries", "retry_failed", "on_file_conflict", "semantic_manifest", "manifest", "emit_index", "verify_after", "verify_output", "paranoid", "stage_files", "dry_run", "timeout", "resume", "incremental", "atomic"]"#
+            )
+        )]
+        to_tar: Option<unpack::TarTarget>,
+
+        /// Print a JSON summary on standard output once the unpack is done,
+        /// and every other message on standard error
+        #[structopt(long = "json", conflicts_with = "watch")]
+        json: bool,
+    },
+    /// Writes a GeoJSON footprint of the top-level nodes of a .slpk file
+    #[structopt(name = "footprints")]
+    Footprints {
+        /// The .slpk file to summarize
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The GeoJSON file to write
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Exports node bounding volumes of a .slpk file as GeoJSON or KML
+    #[structopt(name = "bounds", raw(alias = r#""export-bounds""#))]
+    Bounds {
+        /// The .slpk file to read
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The file to write
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+
+        /// Only export nodes at this level, where the root node is level 0
+        #[structopt(long = "level")]
+        level: Option<usize>,
+
+        /// Output format
+        #[structopt(
+            long = "format",
+            default_value = "geojson",
+            raw(possible_values = r#"&["geojson", "kml"]"#)
+        )]
+        format: bounds::BoundsFormat,
+
+        /// Sample at most this many nodes, spread across the layer
+        #[structopt(long = "max-features")]
+        max_features: Option<usize>,
+    },
+    /// Checks the hash table index of a .slpk file against its contents
+    #[structopt(name = "index")]
+    Index {
+        /// The .slpk file to check
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Print every record of the index
+        #[structopt(long = "list")]
+        list: bool,
+    },
+    /// Checks that a package can be read, without unpacking it
+    #[structopt(name = "check")]
+    Check {
+        /// The .slpk file to check
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Only read the zip directory, the layer document and the root node,
+        /// rather than every entry
+        #[structopt(long = "quick")]
+        quick: bool,
+
+        #[structopt(flatten)]
+        filters: SizeFilters,
+    },
+    /// Reads every entry of a package to check its CRC, and decompresses every
+    /// gzipped one, without unpacking it
+    #[structopt(name = "verify")]
+    Verify {
+        /// The .slpk file to verify
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The number of worker threads, one per core by default
+        #[structopt(long = "threads")]
+        threads: Option<usize>,
+
+        /// The password of a package whose entries are encrypted
+        #[structopt(
+            long = "password",
+            raw(env = r#""SLPKG_PASSWORD""#, hide_env_values = "true")
+        )]
+        password: Option<String>,
+
+        /// Ask for the password of an encrypted package on the terminal,
+        /// without showing it
+        #[structopt(long = "ask-password")]
+        ask_password: bool,
+    },
+    /// Checks the files of an unpacked folder against the manifest.sha256
+    /// written by unpack --manifest
+    #[structopt(name = "check-manifest")]
+    CheckManifest {
+        /// The folder to check
+        #[structopt(parse(from_os_str))]
+        folder: PathBuf,
+    },
+    /// Lists the entries added, removed and modified from one .slpk file to
+    /// another, without unpacking either
+    #[structopt(name = "diff")]
+    Diff {
+        /// The old package
+        #[structopt(parse(from_os_str))]
+        left: PathBuf,
+
+        /// The new package
+        #[structopt(parse(from_os_str))]
+        right: PathBuf,
+
+        /// List every entry added, removed or modified
+        #[structopt(long = "detailed")]
+        detailed: bool,
+    },
+    /// Checks that every resource the node pages refer to is in a package, and
+    /// that nothing below nodes/ is left unreferenced, without unpacking it
+    #[structopt(name = "validate")]
+    Validate {
+        /// The .slpk file to validate
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Also check the JSON resources of the package against their
+        /// schemas
+        #[structopt(long = "validate-json")]
+        validate_json: bool,
+
+        /// The password of a package whose entries are encrypted
+        #[structopt(
+            long = "password",
+            raw(env = r#""SLPKG_PASSWORD""#, hide_env_values = "true")
+        )]
+        password: Option<String>,
+
+        /// Ask for the password of an encrypted package on the terminal,
+        /// without showing it
+        #[structopt(long = "ask-password")]
+        ask_password: bool,
+
+        /// Runs only these checks, such as gzip,refs,schema, out of refs,
+        /// naming, gzip, json, schema and index
+        #[structopt(long = "checks", raw(use_delimiter = "true"))]
+        checks: Vec<validate::Check>,
+
+        /// Fails on findings of this severity or worse
+        #[structopt(
+            long = "fail-on",
+            default_value = "warning",
+            raw(possible_values = r#"&["error", "warning", "never"]"#)
+        )]
+        fail_on: lint::FailOn,
+
+        /// The number of worker threads, one per core by default
+        #[structopt(long = "threads")]
+        threads: Option<usize>,
+    },
+    /// Extracts the entries of one node, and the resources it refers to
+    #[structopt(name = "extract-node")]
+    ExtractNode {
+        /// The .slpk file to extract from
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The id of the node, its index in the node pages for I3S 1.7 and
+        /// later
+        node_id: String,
+
+        /// The folder to extract into, which is created if it doesn't exist
+        #[structopt(short = "o", long = "output-dir", parse(from_os_str))]
+        output_dir: PathBuf,
+
+        /// Extract from layer <n> of a package which stores its layers below
+        /// layers/<n>/, rather than the first
+        #[structopt(long = "layer")]
+        layer: Option<usize>,
+    },
+    /// Prints the node hierarchy of a package, each node indented below its
+    /// parent
+    #[structopt(name = "tree")]
+    Tree {
+        /// The .slpk file to read
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Only print nodes down to this depth, where the root is at depth 0
+        #[structopt(long = "max-depth")]
+        max_depth: Option<usize>,
+
+        /// Only print the number of nodes at each level
+        #[structopt(long = "counts-only")]
+        counts_only: bool,
+    },
+    /// Writes the mesh of a node as a Wavefront OBJ or glTF 2.0 file, with
+    /// its texture beside it
+    #[structopt(name = "export")]
+    Export {
+        /// The .slpk file to export from
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The id of the node, its index in the node pages for I3S 1.7 and
+        /// later
+        node_id: String,
+
+        /// The file to write, in the format its extension names unless
+        /// --format is given
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+
+        /// Write OBJ or glTF whatever the extension of the output file
+        #[structopt(long = "format", raw(possible_values = r#"&["obj", "gltf"]"#))]
+        format: Option<export::ExportFormat>,
+
+        /// Export from layer <n> of a package which stores its layers below
+        /// layers/<n>/, rather than the first
+        #[structopt(long = "layer")]
+        layer: Option<usize>,
+
+        /// Reproject the positions into ecef, wgs84 or epsg:<code>, for Web
+        /// Mercator or a UTM zone, rather than writing them as they are
+        /// stored
+        #[structopt(long = "target-crs")]
+        target_crs: Option<export::TargetCrs>,
+    },
+    /// Writes the attributes of every node as CSV, a row per feature
+    #[structopt(name = "export-attributes")]
+    ExportAttributes {
+        /// The .slpk file to export from
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The folder to write the CSV files to, which is created if it
+        /// doesn't exist
+        #[structopt(short = "o", long = "output-dir", parse(from_os_str))]
+        output_dir: PathBuf,
+
+        /// Write a single attributes.csv with a column for the node, rather
+        /// than a file per node
+        #[structopt(long = "merge")]
+        merge: bool,
+
+        /// Export from layer <n> of a package which stores its layers below
+        /// layers/<n>/, rather than the first
+        #[structopt(long = "layer")]
+        layer: Option<usize>,
+    },
+    /// Lists the textures of a package with their format and size, and
+    /// converts them to PNG
+    #[structopt(name = "textures")]
+    Textures {
+        /// The .slpk file to list the textures of
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Decode the JPEG and DXT compressed DDS textures into PNGs in the
+        /// folder the package unpacks into
+        #[structopt(long = "convert", raw(possible_values = r#"&["png"]"#))]
+        convert: Option<String>,
+
+        /// Crop the texture atlas of each node into a PNG for each UV region
+        /// of its features, in the folder the package unpacks into
+        #[structopt(long = "split-atlas")]
+        split_atlas: bool,
+
+        /// Convert into a folder named after the package in this folder,
+        /// instead of next to the package
+        #[structopt(long = "output-dir", parse(from_os_str))]
+        output_dir: Option<PathBuf>,
+    },
+    /// Writes a single entry to the standard output, decompressed
+    #[structopt(name = "cat")]
+    Cat {
+        /// The .slpk file holding the entry
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The name of the entry, with or without its .gz suffix
+        entry: String,
+
+        /// Write a JSON document pretty-printed with two spaces (pretty,
+        /// the default), with <n> spaces or a tab, minified, or as-is
+        #[structopt(long = "json-format")]
+        json_format: Option<unpack::JsonFormatting>,
+    },
+    /// Lists the entries of a package, without unpacking them
+    #[structopt(name = "list")]
+    List {
+        /// The .slpk file to list
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// List the largest entries first
+        #[structopt(long = "sort-by-size")]
+        sort_by_size: bool,
+
+        /// Print the entries as a JSON array
+        #[structopt(long = "json")]
+        json: bool,
+
+        /// Also read the gzip header of every gzipped entry, and list the
+        /// original name, time and comment it records
+        #[structopt(long = "deep")]
+        deep: bool,
+
+        /// The password of a package whose entries are encrypted, which
+        /// --deep needs to read them
+        #[structopt(
+            long = "password",
+            raw(env = r#""SLPKG_PASSWORD""#, hide_env_values = "true")
+        )]
+        password: Option<String>,
+
+        /// Ask for the password of an encrypted package on the terminal,
+        /// without showing it
+        #[structopt(long = "ask-password")]
+        ask_password: bool,
+
+        #[structopt(flatten)]
+        filters: SizeFilters,
+    },
+    /// Shows where the bytes of a package go, by kind of resource, and its
+    /// largest entries
+    #[structopt(name = "stats")]
+    Stats {
+        /// The .slpk file to summarize
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Print the breakdown as a JSON object
+        #[structopt(long = "json")]
+        json: bool,
+
+        /// Also count the features holding each value of the attributes with
+        /// a coded-value domain, labelled by the domain
+        #[structopt(long = "coded-values")]
+        coded_values: bool,
+
+        /// Count the coded values of layer <n> of a package which stores its
+        /// layers below layers/<n>/, rather than of the first
+        #[structopt(long = "layer", requires = "coded_values")]
+        layer: Option<usize>,
+    },
+    /// Describes each layer of a package from its layer document
+    #[structopt(name = "info")]
+    Info {
+        /// The .slpk file to describe
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Lists the attributes, joining fields with attributeStorageInfo
+        #[structopt(long = "attributes")]
+        attributes: bool,
+    },
+    /// Fixes entries whose .gz suffix doesn't match whether they are gzipped
+    #[structopt(name = "repair")]
+    Repair {
+        /// The .slpk file to repair
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The repaired copy of the package to write
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            raw(required_unless = r#""check""#)
+        )]
+        output: Option<PathBuf>,
+
+        /// Only list the entries which need repairing
+        #[structopt(long = "check")]
+        check: bool,
+
+        /// Add the metadata.json inferred from the layer to a copy of a
+        /// package which has none
+        #[structopt(long = "write-metadata", conflicts_with = "check")]
+        write_metadata: bool,
+    },
+    /// Writes a smaller copy of a package, gzipping its .gz entries again
+    #[structopt(name = "recompress")]
+    Recompress {
+        /// The .slpk file to recompress
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// The recompressed copy of the package to write
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+
+        /// The gzip level of the .gz entries, from 1 for the fastest to 9
+        /// for the smallest package, 9 by default
+        #[structopt(long = "compression-level", default_value = "9")]
+        compression_level: u32,
+
+        /// Minify the JSON inside .json.gz entries before gzipping it again
+        #[structopt(long = "minify-json")]
+        minify_json: bool,
+    },
+    /// Reports tiny textures, near-empty meshes and inverted LOD thresholds
+    #[structopt(name = "lint")]
+    Lint {
+        /// The .slpk file to lint
+        #[structopt(parse(from_os_str))]
+        src_file: PathBuf,
+
+        /// Flag textures with fewer pixels than this
+        #[structopt(long = "min-texture-area", default_value = "16")]
+        min_texture_area: u64,
+
+        /// Flag meshes below the root with fewer vertices than this
+        #[structopt(long = "min-vertices", default_value = "7")]
+        min_vertices: u64,
+
+        /// Sets the severity of a rule, such as small-texture=error or inverted-lod=off
+        #[structopt(long = "severity", number_of_values = 1)]
+        severities: Vec<lint::RuleSeverity>,
+
+        /// Runs only these rules, such as small-texture,inverted-lod
+        #[structopt(long = "checks", raw(use_delimiter = "true"))]
+        checks: Vec<lint::Rule>,
+
+        /// Fails on findings of this severity or worse
+        #[structopt(
+            long = "fail-on",
+            default_value = "error",
+            raw(possible_values = r#"&["error", "warning", "never"]"#)
+        )]
+        fail_on: lint::FailOn,
+
+        /// The number of worker threads, one per core by default
+        #[structopt(long = "threads")]
+        threads: Option<usize>,
+    },
+    /// Makes and applies patches which turn one version of a package into the next
+    #[structopt(name = "patch")]
+    Patch {
+        #[structopt(subcommand)]
+        command: PatchCommand,
+    },
+    /// Runs a command over many packages, sharing the machine between them
+    #[structopt(name = "batch")]
+    Batch {
+        /// The packages to process: .slpk files, patterns such as
+        /// "exports/*.slpk", or folders, whose .slpk and .eslpk files are
+        /// processed
+        #[structopt(raw(required = "true", min_values = "1"))]
+        packages: Vec<String>,
+
+        /// The command to run on each package
+        #[structopt(
+            long = "command",
+            default_value = "unpack",
+            raw(possible_values = r#"&["unpack", "check", "verify", "validate"]"#)
+        )]
+        command: batch::BatchCommand,
+
+        /// How many packages to process at once
+        #[structopt(short = "j", long = "jobs", default_value = "1")]
+        jobs: usize,
+
+        /// The worker threads shared by the packages processed at once, by
+        /// default the number of cores
+        #[structopt(long = "threads")]
+        threads: Option<usize>,
+
+        /// Don't start any more packages once one has failed
+        #[structopt(long = "fail-fast")]
+        fail_fast: bool,
+
+        /// The JSON report to write
+        #[structopt(
+            long = "report",
+            default_value = "slpkg-batch-report.json",
+            parse(from_os_str)
+        )]
+        report: PathBuf,
+
+        #[structopt(flatten)]
+        flags: UnpackFlags,
+    },
+    /// Mirrors a layer of a scene service into a .slpk file
+    #[structopt(name = "mirror")]
+    Mirror {
+        /// The http:// URL of the layer, ending in /SceneServer/layers/<id>
+        url: String,
+
+        /// The package to write, named after the service by default
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// The deepest level of the node tree to mirror, where the root is
+        /// level 0, for a quick preview of a large layer
+        #[structopt(long = "max-level")]
+        max_level: Option<usize>,
+
+        /// How many resources to fetch at once
+        #[structopt(long = "concurrency", default_value = "8")]
+        concurrency: usize,
+
+        /// How many times to send a request again when it fails on the way
+        /// or the server is busy
+        #[structopt(long = "retries", default_value = "3")]
+        retries: usize,
+
+        /// A header to send with every request, such as
+        /// "Authorization: Bearer <token>"
+        #[structopt(long = "header", raw(number_of_values = "1"))]
+        headers: Vec<unpack::HttpHeader>,
+    },
+    /// Serves a package over the I3S REST API of a scene service, for
+    /// previewing it in a viewer
+    #[structopt(name = "serve")]
+    Serve {
+        /// The .slpk file, or the folder it was unpacked into
+        #[structopt(parse(from_os_str))]
+        src: PathBuf,
+
+        /// The port to listen on
+        #[structopt(long = "port", default_value = "8080")]
+        port: u16,
+
+        /// The address to listen on, by default one only this machine can
+        /// connect to
+        #[structopt(long = "bind", default_value = "127.0.0.1")]
+        bind: String,
+    },
+    /// Checks that packages can be unpacked on this machine
+    #[structopt(name = "self-test")]
+    SelfTest {
+        /// The folder to run the checks in, instead of the temp folder
+        #[structopt(parse(from_os_str))]
+        dir: Option<PathBuf>,
+    },
+    /// Prints the defaults the slpkg.toml files set for unpack
+    #[structopt(name = "config")]
+    Config {
+        /// Print every value the files set, with the file it comes from,
+        /// which is needed for now
+        #[structopt(long = "show")]
+        show: bool,
+
+        /// The package whose folder is also looked in for a slpkg.toml
+        #[structopt(parse(from_os_str))]
+        package: Option<PathBuf>,
+    },
+    /// Prints which optional parts of slpkg this build has
+    #[structopt(name = "features")]
+    Features,
+    /// Prints a shell completion script to stdout
+    #[structopt(name = "completions")]
+    Completions {
+        /// The shell to generate completions for
+        #[structopt(raw(possible_values = r#"&["bash", "zsh", "fish", "powershell"]"#))]
+        shell: Shell,
+    },
+    /// Prints a description of every command and argument
+    #[structopt(name = "cli-spec")]
+    CliSpec {
+        /// Print the description as JSON instead of an outline
+        #[structopt(long = "json")]
+        json: bool,
+    },
+}
+
+/// The options of an unpack which apply to any package, for unpack and
+/// batch.
+#[derive(Debug, StructOpt)]
+struct UnpackFlags {
+    /// Unpack into a folder named after the package in this folder,
+    /// instead of next to the package
+    #[structopt(long = "output-dir", parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+
+    /// Unpack each sublayer of a Building Scene Layer into its own folder
+    #[structopt(long = "split-sublayers")]
+    split_sublayers: bool,
+
+    /// Write one class of resource (metadata, geometry, textures,
+    /// attributes or other) below another folder, as <class>=<folder>
+    #[structopt(long = "route", raw(number_of_values = "1"))]
+    routes: Vec<unpack::ClassRoute>,
+
+    /// Link texture payloads identical to one already unpacked instead
+    /// of writing them again, or with "copy" only report them
+    #[structopt(
+        long = "dedup",
+        raw(possible_values = r#"&["hardlink", "symlink", "copy"]"#)
+    )]
+    dedup: Option<unpack::DedupMode>,
+
+    /// Deduplicate geometry payloads as well as textures
+    #[structopt(long = "dedup-geometry", raw(requires = r#""dedup""#))]
+    dedup_geometry: bool,
+
+    /// Skip the resources of nodes with no vertices, no features and
+    /// only placeholder textures
+    #[structopt(long = "exclude-empty-nodes")]
+    exclude_empty_nodes: bool,
+
+    /// Only unpack the resources of nodes down to this level below the
+    /// root, which is at level 0
+    #[structopt(long = "max-level")]
+    max_level: Option<usize>,
+
+    /// Only unpack the resources of nodes meeting this box, given as
+    /// xmin,ymin,xmax,ymax in the spatial reference of the layer, and of
+    /// their ancestors
+    #[structopt(long = "bbox")]
+    bbox: Option<unpack::BoundingBox>,
+
+    /// Give the --bbox in WGS84 longitude and latitude
+    #[structopt(long = "bbox-wgs84", requires = "bbox")]
+    bbox_wgs84: bool,
+
+    /// Only unpack layer <n> of a package which stores its layers below
+    /// layers/<n>/
+    #[structopt(long = "layer")]
+    layer: Option<usize>,
+
+    /// Retry an entry which fails up to this many times, then set it
+    /// aside with a .failed marker file and carry on
+    #[structopt(long = "retries")]
+    retries: Option<usize>,
+
+    /// Milliseconds to wait before the first retry, doubling each time
+    #[structopt(long = "retry-backoff-ms", default_value = "200")]
+    retry_backoff_ms: u64,
+
+    /// Carry on past entries which fail, listing them at the end, rather
+    /// than stopping at the first
+    #[structopt(long = "keep-going", raw(conflicts_with = r#""paranoid""#))]
+    keep_going: bool,
+
+    /// Only unpack the entries set aside by an earlier run, into its
+    /// output folder
+    #[structopt(long = "retry-failed")]
+    retry_failed: bool,
+
+    /// When the output folder already exists, fail, delete it first, or
+    /// unpack into it, leaving any files the package doesn't have
+    #[structopt(
+        long = "if-exists",
+        default_value = "overwrite",
+        raw(possible_values = r#"&["error", "overwrite", "merge"]"#)
+    )]
+    if_exists: unpack::OverwriteMode,
+
+    /// Unpack into the existing output folder, and resolve each file
+    /// which already exists by overwriting it, skipping the entry,
+    /// overwriting it only when the entry is newer, keeping it when it
+    /// is complete, or failing
+    #[structopt(
+        long = "on-file-conflict",
+        raw(possible_values = r#"&["overwrite", "skip", "newer", "complete", "error"]"#)
+    )]
+    on_file_conflict: Option<unpack::ConflictPolicy>,
+
+    /// Unpack entries whose files would collide with others where case
+    /// is ignored under names suffixed ~1, ~2 and so on, rather than
+    /// failing
+    #[structopt(long = "rename-collisions")]
+    rename_collisions: bool,
+
+    /// Escape the characters of file names which Windows doesn't allow,
+    /// as is always done on Windows
+    #[structopt(long = "portable-names")]
+    portable_names: bool,
+
+    /// Strip this folder from the start of every entry, or with auto
+    /// the one folder every entry is in, leaving out entries which
+    /// aren't in it
+    #[structopt(long = "strip-prefix")]
+    strip_prefix: Option<unpack::StripPrefix>,
+
+    /// Unpack entries which aren't in the folder --strip-prefix strips
+    /// as they are, rather than leaving them out
+    #[structopt(long = "keep-unprefixed", requires = "strip_prefix")]
+    keep_unprefixed: bool,
+
+    /// Recreate symbolic links whose target is inside the output
+    /// folder, on Unix, rather than leaving them out
+    #[structopt(long = "allow-symlinks", raw(conflicts_with = r#""paranoid""#))]
+    allow_symlinks: bool,
+
+    /// Entries are decompressed when they start like a gzip stream
+    /// whatever their names say, which this used to turn on
+    #[structopt(long = "sniff-compression", raw(hidden = "true"))]
+    sniff_compression: bool,
+
+    /// Decompress entries named .gz and copy the rest, without looking
+    /// at what they hold
+    #[structopt(long = "trust-extensions", raw(conflicts_with = r#""paranoid""#))]
+    trust_extensions: bool,
+
+    /// Write gzipped entries exactly as they are stored, keeping their
+    /// .gz names, rather than decompressing them
+    #[structopt(long = "keep-gzip", raw(conflicts_with = r#""restore_gzip_mtime""#))]
+    keep_gzip: bool,
+
+    /// Write .json and .geojson files as they are stored (as-is, the
+    /// default), minified, or pretty-printed with two spaces, <n> spaces
+    /// or a tab
+    #[structopt(
+        long = "json-format",
+        raw(conflicts_with_all = r#"&["verify_after", "paranoid"]"#)
+    )]
+    json_format: Option<unpack::JsonFormatting>,
+
+    /// Sort the keys of every object in the documents --json-format
+    /// reformats, so that unpacked packages diff cleanly
+    #[structopt(long = "sort-keys", requires = "json_format")]
+    sort_keys: bool,
+
+    /// Leave the keys of documents larger than this in their order with
+    /// --sort-keys, with a warning (64MiB by default)
+    #[structopt(long = "sort-keys-max-size", requires = "sort_keys")]
+    sort_keys_max_size: Option<unpack::ByteSize>,
+
+    /// Only unpack resources of these kinds: textures, geometry,
+    /// attributes or metadata
+    #[structopt(long = "only", raw(number_of_values = "1"))]
+    only: Vec<unpack::ResourceKind>,
+
+    /// Only unpack entries whose path matches one of these patterns, such
+    /// as "*.json.gz" or "nodes/*/features/*"
+    #[structopt(
+        long = "include",
+        parse(from_str = "unpack::PathPattern::new"),
+        raw(number_of_values = "1")
+    )]
+    include: Vec<unpack::PathPattern>,
+
+    /// Don't unpack entries whose path matches one of these patterns
+    #[structopt(
+        long = "exclude",
+        parse(from_str = "unpack::PathPattern::new"),
+        raw(number_of_values = "1")
+    )]
+    exclude: Vec<unpack::PathPattern>,
+
+    /// Only unpack entries at least this large, such as 10MB or 1GiB
+    #[structopt(long = "min-size")]
+    min_size: Option<unpack::ByteSize>,
+
+    /// Only unpack entries at most this large
+    #[structopt(long = "max-size")]
+    max_size: Option<unpack::ByteSize>,
+
+    /// Only unpack entries modified after this date, as YYYY-MM-DD or
+    /// YYYY-MM-DDTHH:MM:SS
+    #[structopt(long = "newer-than")]
+    newer_than: Option<unpack::EntryDate>,
+
+    /// Write manifest.json, giving the node, level and role of every
+    /// unpacked file
+    #[structopt(long = "semantic-manifest")]
+    semantic_manifest: bool,
+
+    /// Write manifest.sha256, giving the SHA-256 of every unpacked file
+    /// in the format of sha256sum
+    #[structopt(long = "manifest")]
+    manifest: bool,
+
+    /// Write index.html, a page summarizing the package with its node tree
+    /// and links to its documents, and its figures in index.json
+    #[structopt(long = "emit-index")]
+    emit_index: bool,
+
+    /// Give files from gzipped entries the modification time recorded in
+    /// their gzip header
+    #[structopt(long = "restore-gzip-mtime")]
+    restore_gzip_mtime: bool,
+
+    /// Check every unpacked file against the package once unpacking is
+    /// done, failing if any differ
+    #[structopt(long = "verify-after")]
+    verify_after: bool,
+
+    /// Read every unpacked file back from disk once unpacking is done,
+    /// failing if any doesn't hold what was unpacked to it
+    #[structopt(long = "verify-output")]
+    verify_output: bool,
+
+    /// Keep the memory held for entry contents below this, such as 512MiB
+    #[structopt(long = "max-memory")]
+    max_memory: Option<unpack::ByteSize>,
+
+    /// The size of the buffer each file is written through, 128KiB by
+    /// default
+    #[structopt(long = "write-buffer")]
+    write_buffer: Option<unpack::ByteSize>,
+
+    /// Fail on entries which expand to more than this many times their
+    /// compressed size
+    #[structopt(long = "max-expansion-ratio")]
+    max_expansion_ratio: Option<u64>,
+
+    /// Turn on every integrity check, and fail on the first violation,
+    /// for packages which can't be trusted
+    #[structopt(long = "paranoid", raw(conflicts_with = r#""retries""#))]
+    paranoid: bool,
+
+    /// Check the layer documents, node pages, node index documents,
+    /// shared resources and statistics against their schemas, warning
+    /// of the first violation in each
+    #[structopt(long = "validate-json")]
+    validate_json: bool,
+
+    /// Fail on JSON resources which don't match their schemas, rather
+    /// than warning of them
+    #[structopt(long = "strict", raw(requires = r#""validate_json""#))]
+    strict: bool,
+
+    /// Refuse packages with more entries, or entries with longer names,
+    /// than real packages have
+    #[structopt(long = "hardened")]
+    hardened: bool,
+
+    /// Write each file under a temporary name, and rename it into place
+    /// once it is complete
+    #[structopt(long = "stage-files")]
+    stage_files: bool,
+
+    /// Read the package through a memory mapping of it, rather than
+    /// through a file handle for each thread
+    #[structopt(long = "mmap")]
+    mmap: bool,
+
+    /// The password of a package whose entries are encrypted
+    #[structopt(
+        long = "password",
+        raw(env = r#""SLPKG_PASSWORD""#, hide_env_values = "true")
+    )]
+    password: Option<String>,
+
+    /// Ask for the password of an encrypted package on the terminal,
+    /// without showing it
+    #[structopt(long = "ask-password")]
+    ask_password: bool,
+
+    /// Print how many files and bytes unpacking would write, without
+    /// writing anything
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Write the names of the entries to this file, one per line, in
+    /// the order they were written to the package
+    #[structopt(long = "restore-order-file", parse(from_os_str))]
+    restore_order_file: Option<PathBuf>,
+
+    /// The order entries are unpacked in: archive, that of the zip
+    /// directory, or metadata-first, the layer documents and node pages
+    /// before the resources
+    #[structopt(long = "extract-order")]
+    extract_order: Option<unpack::ExtractOrder>,
+
+    /// Stop after this long, such as 90s or 20m, leaving a resume file
+    /// listing the entries which are left
+    #[structopt(long = "timeout")]
+    timeout: Option<unpack::TimeLimit>,
+
+    /// Only unpack the entries left by a run which timed out, into its
+    /// output folder
+    #[structopt(long = "resume", conflicts_with = "retry_failed")]
+    resume: bool,
+
+    /// Only unpack the entries which changed since the last unpack into
+    /// the output folder, deleting the files of those which are gone
+    #[structopt(long = "incremental")]
+    incremental: bool,
+
+    /// Unpack into a temporary folder next to the output folder, which
+    /// only replaces it once every entry has been unpacked
+    #[structopt(
+        long = "atomic",
+        raw(
+            conflicts_with_all = r#"&["resume", "retry_failed", "on_file_conflict", "timeout", "incremental"]"#
+        )
+    )]
+    atomic: bool,
+}
+
+/// The filters of unpack which go by the zip headers of an entry, for list
+/// and check.
+#[derive(Debug, StructOpt)]
+struct SizeFilters {
+    /// Only take entries at least this large, such as 10MB or 1GiB
+    #[structopt(long = "min-size")]
+    min_size: Option<unpack::ByteSize>,
+
+    /// Only take entries at most this large
+    #[structopt(long = "max-size")]
+    max_size: Option<unpack::ByteSize>,
+
+    /// Only take entries modified after this date, as YYYY-MM-DD or
+    /// YYYY-MM-DDTHH:MM:SS
+    #[structopt(long = "newer-than")]
+    newer_than: Option<unpack::EntryDate>,
+}
+
+impl SizeFilters {
+    fn filters(self) -> unpack::EntryFilters {
+        unpack::EntryFilters {
+            min_size: self.min_size,
+            max_size: self.max_size,
+            newer_than: self.newer_than,
+            ..unpack::EntryFilters::default()
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum PatchCommand {
+    /// Writes a patch which turns one package into another
+    #[structopt(name = "create")]
+    Create {
+        /// The package the patch applies to
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// The package applying the patch makes
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+
+        /// The patch to write
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Applies a patch to the package it was made for
+    #[structopt(name = "apply")]
+    Apply {
+        /// The package to patch
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        /// The patch to apply
+        #[structopt(parse(from_os_str))]
+        patch: PathBuf,
+
+        /// The patched package to write
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: PathBuf,
     },
 }
 
+/// Prints what the library logs, warnings and errors to standard error and
+/// everything else to standard output, unless that is kept for a JSON
+/// summary.
+struct ConsoleLogger {
+    info_to_stderr: bool,
+}
+
+impl log::Log for ConsoleLogger {
+    fn log(&self, level: log::Level, message: &fmt::Arguments) {
+        if level <= log::Level::Warn || self.info_to_stderr {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+/// The password given for an encrypted package, asked for on the terminal
+/// with `--ask-password`.
+fn package_password(password: Option<String>, ask_password: bool) -> Option<String> {
+    if !ask_password {
+        return password;
+    }
+    match slpkg::password::ask_password("Password: ") {
+        Ok(password) => Some(password),
+        Err(e) => {
+            eprintln!("The password can't be read from the terminal: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The flag of unpack which gives each key of a configuration file, by its
+/// name in `Settings`.
+const CONFIG_FLAGS: [(&str, &str); 35] = [
+    ("selection.max_level", "max_level"),
+    ("selection.exclude_empty_nodes", "exclude_empty_nodes"),
+    ("selection.strip_prefix", "strip_prefix"),
+    ("selection.keep_unprefixed", "keep_unprefixed"),
+    ("output.output_dir", "output_dir"),
+    ("output.split_sublayers", "split_sublayers"),
+    ("output.overwrite", "if_exists"),
+    ("output.on_file_conflict", "on_file_conflict"),
+    ("output.rename_collisions", "rename_collisions"),
+    ("output.portable_names", "portable_names"),
+    ("output.allow_symlinks", "allow_symlinks"),
+    ("output.dedup", "dedup"),
+    ("output.dedup_geometry", "dedup_geometry"),
+    ("output.stage_files", "stage_files"),
+    ("output.atomic", "atomic"),
+    ("output.write_buffer_size", "write_buffer"),
+    ("content.trust_extensions", "trust_extensions"),
+    ("content.keep_gzip", "keep_gzip"),
+    ("content.json_formatting", "json_format"),
+    ("content.sort_keys", "sort_keys"),
+    ("content.sort_keys_max_size", "sort_keys_max_size"),
+    ("content.restore_gzip_mtime", "restore_gzip_mtime"),
+    ("integrity.verify_after", "verify_after"),
+    ("integrity.verify_output", "verify_output"),
+    ("integrity.max_expansion_ratio", "max_expansion_ratio"),
+    ("integrity.validate_json", "validate_json"),
+    ("integrity.strict_json", "strict"),
+    ("integrity.hardened", "hardened"),
+    ("reports.semantic_manifest", "semantic_manifest"),
+    ("reports.checksum_manifest", "manifest"),
+    ("reports.emit_index", "emit_index"),
+    ("run.threads", "threads"),
+    ("run.extract_order", "extract_order"),
+    ("run.keep_going", "keep_going"),
+    ("run.mmap", "mmap"),
+];
+
+/// Whether the flag which gives the configuration `key` is on the command
+/// line of the subcommand `matches` are of, which it is then taken from.
+fn flag_given(matches: &ArgMatches, key: &str) -> bool {
+    CONFIG_FLAGS
+        .iter()
+        .any(|(config_key, flag)| *config_key == key && matches.occurrences_of(flag) > 0)
+}
+
+/// The configuration files for a command on `package`, leaving when one
+/// can't be read.
+fn read_config(package: Option<&Path>) -> config::Config {
+    match config::Config::discover(package) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+impl UnpackFlags {
+    /// The unpack options the flags give, over the defaults `config` sets
+    /// for the flags which aren't in `matches`, asking for the password on
+    /// the terminal if they say to.
+    fn options(self, config: &config::Config, matches: &ArgMatches) -> unpack::UnpackOptions {
+        if self.sniff_compression {
+            log::log(
+                log::Level::Warn,
+                format_args!("--sniff-compression is the default now, and can be left out"),
+            );
+        }
+        let retry_backoff = std::time::Duration::from_millis(self.retry_backoff_ms);
+        let mut options = unpack::UnpackOptions {
+            filters: unpack::EntryFilters {
+                only: self.only,
+                include: self.include,
+                exclude: self.exclude,
+                min_size: self.min_size,
+                max_size: self.max_size,
+                newer_than: self.newer_than,
+            },
+            selection: unpack::SelectionOptions {
+                layer: self.layer,
+                max_level: self.max_level,
+                bbox: self.bbox,
+                bbox_wgs84: self.bbox_wgs84,
+                exclude_empty_nodes: self.exclude_empty_nodes,
+                strip_prefix: self.strip_prefix,
+                keep_unprefixed: self.keep_unprefixed,
+                retry_failed: self.retry_failed,
+                resume: self.resume,
+                incremental: self.incremental,
+            },
+            output: unpack::OutputOptions {
+                output_dir: self.output_dir,
+                split_sublayers: self.split_sublayers,
+                routes: self.routes,
+                overwrite: self.if_exists,
+                on_file_conflict: self.on_file_conflict,
+                rename_collisions: self.rename_collisions,
+                portable_names: self.portable_names,
+                allow_symlinks: self.allow_symlinks,
+                dedup: self.dedup,
+                dedup_geometry: self.dedup_geometry,
+                stage_files: self.stage_files,
+                atomic: self.atomic,
+                dry_run: self.dry_run,
+                write_buffer_size: self.write_buffer.map(|size| size.0 as usize),
+                ..unpack::OutputOptions::default()
+            },
+            content: unpack::ContentOptions {
+                trust_extensions: self.trust_extensions,
+                keep_gzip: self.keep_gzip,
+                json_formatting: self.json_format.unwrap_or_default(),
+                sort_keys: self.sort_keys,
+                sort_keys_max_size: self.sort_keys_max_size.map(|size| size.0),
+                restore_gzip_mtime: self.restore_gzip_mtime,
+            },
+            integrity: unpack::IntegrityOptions {
+                verify_after: self.verify_after,
+                verify_output: self.verify_output,
+                max_expansion_ratio: self.max_expansion_ratio,
+                validate_json: self.validate_json,
+                strict_json: self.strict,
+                hardened: self.hardened,
+                ..unpack::IntegrityOptions::default()
+            },
+            reports: unpack::ReportOptions {
+                semantic_manifest: self.semantic_manifest,
+                checksum_manifest: self.manifest,
+                emit_index: self.emit_index,
+                order_file: self.restore_order_file,
+                ..unpack::ReportOptions::default()
+            },
+            run: unpack::RunOptions {
+                extract_order: self.extract_order.unwrap_or_default(),
+                keep_going: self.keep_going,
+                retry: self.retries.map(|retries| unpack::RetryPolicy {
+                    retries,
+                    backoff: retry_backoff,
+                }),
+                memory_budget: self
+                    .max_memory
+                    .map(|size| std::sync::Arc::new(unpack::MemoryBudget::new(size.0))),
+                timeout: self.timeout.map(|timeout| timeout.0),
+                mmap: self.mmap,
+                ..unpack::RunOptions::default()
+            },
+            encryption: unpack::EncryptionOptions {
+                password: package_password(self.password, self.ask_password),
+            },
+        };
+        config.apply(&mut options, |key| flag_given(matches, key));
+        if self.paranoid {
+            options.paranoid()
+        } else {
+            options
+        }
+    }
+}
+
+/// Whether standard output is the tar stream, which messages stay out of.
+fn tar_on_stdout(to_tar: &Option<unpack::TarTarget>) -> bool {
+    *to_tar == Some(unpack::TarTarget::Stdout)
+}
+
+#[cfg(feature = "remote")]
+fn run_mirror(
+    url: &str,
+    output: Option<PathBuf>,
+    max_level: Option<usize>,
+    concurrency: usize,
+    retries: usize,
+    headers: Vec<unpack::HttpHeader>,
+) {
+    let output = match output.map_or_else(|| mirror::default_output(url), Ok) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let options = mirror::MirrorOptions {
+        headers,
+        max_level,
+        concurrency,
+        retries,
+        ..mirror::MirrorOptions::default()
+    };
+    match mirror::mirror(url, &output, &options) {
+        Ok(summary) => {
+            println!(
+                "{} nodes, {} resources fetched ({} bytes), {} already fetched, packed into {}",
+                summary.nodes,
+                summary.fetched,
+                summary.bytes_fetched,
+                summary.resumed,
+                output.to_string_lossy()
+            );
+            if summary.left_out_nodes > 0 {
+                println!(
+                    "{} nodes below level {} were left out",
+                    summary.left_out_nodes,
+                    max_level.unwrap_or_default()
+                );
+            }
+            if summary.retries > 0 {
+                println!("{} requests were sent again", summary.retries);
+            }
+            if !summary.missing.is_empty() {
+                println!(
+                    "{} resources the nodes refer to aren't on the server:",
+                    summary.missing.len()
+                );
+                for path in &summary.missing {
+                    println!("  {}", path);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Running the same command again picks up from what was fetched, in {}",
+                mirror::staging_folder(&output).to_string_lossy()
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+fn run_mirror(
+    _url: &str,
+    _output: Option<PathBuf>,
+    _max_level: Option<usize>,
+    _concurrency: usize,
+    _retries: usize,
+    _headers: Vec<unpack::HttpHeader>,
+) {
+    if let Err(e) = features::require("remote", "mirror") {
+        eprintln!("{}", e);
+    }
+    std::process::exit(1);
+}
+
 fn main() {
-    let params = Settings::from_args();
+    let matches = Settings::clap().get_matches();
+    let params = Settings::from_clap(&matches);
+    let subcommand = matches.subcommand().1.cloned().unwrap_or_default();
+    let (level, info_to_stderr) = match &params {
+        Settings::Unpack { quiet: true, .. } => (log::Level::Warn, false),
+        Settings::Unpack {
+            verbose,
+            json,
+            to_tar,
+            ..
+        } if *verbose >= 1 => (log::Level::Debug, *json || tar_on_stdout(to_tar)),
+        Settings::Unpack { json, to_tar, .. } => (log::Level::Info, *json || tar_on_stdout(to_tar)),
+        Settings::Pack { verbose: true, .. } => (log::Level::Debug, false),
+        _ => (log::Level::Info, false),
+    };
+    log::set_max_level(level);
+    // Nothing else installs one.
+    let _ = log::set_logger(Box::new(ConsoleLogger { info_to_stderr }));
     match params {
-        Settings::Unpack { src_file, verbose } => {
-            if let Err(e) = unpack::unpack(&src_file, verbose) {
+        Settings::Pack {
+            src_dir,
+            output,
+            timestamp,
+            compression_level,
+            no_compress,
+            no_default_ignores,
+            ignore_files,
+            verbose: _,
+        } => {
+            let output = output.unwrap_or_else(|| {
+                let mut name = src_dir.file_name().unwrap_or_default().to_os_string();
+                name.push(".slpk");
+                src_dir.with_file_name(name)
+            });
+            let options = pack::PackOptions {
+                timestamps: timestamp,
+                compression: match compression_level {
+                    _ if no_compress => None,
+                    Some(level) => Some(pack::Compression::new(level)),
+                    None => Some(pack::Compression::default()),
+                },
+                default_ignores: !no_default_ignores,
+                ignore_files,
+            };
+            match pack::pack_with_options(&src_dir, &output, &options) {
+                Ok(summary) => {
+                    println!(
+                        "{} entries, {} of them gzipped JSON, packed into {}",
+                        summary.entries,
+                        summary.gzipped,
+                        output.to_string_lossy()
+                    );
+                    if summary.left_out > 0 {
+                        println!("{} files left by unpacking were left out", summary.left_out);
+                    }
+                    if !summary.ignored.is_empty() {
+                        println!("{} ignored files were left out", summary.ignored.len());
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::Unpack {
+            src_file,
+            headers,
+            verbose,
+            quiet: _,
+            progress,
+            threads,
+            name,
+            watch,
+            flags,
+            trace_json,
+            to_tar,
+            json,
+        } => {
+            let url = src_file.to_str().filter(|src| unpack::is_url(src));
+            let from_stdin = src_file.as_os_str() == "-";
+            let package = Some(src_file.as_path()).filter(|_| url.is_none() && !from_stdin);
+            let mut options = flags.options(&read_config(package), &subcommand);
+            options.reports.show_timings = verbose >= 2;
+            options.reports.trace_path = trace_json;
+            options.output.tar = to_tar;
+            options.run.threads = threads.or(options.run.threads);
+            let progress_bar = cli::progress::ProgressBar::new();
+            if progress {
+                options.run.progress = Some(progress_bar.progress());
+            }
+            if json && tar_on_stdout(&options.output.tar) {
+                eprintln!(
+                    "--json prints on standard output, where --to-tar - writes the tar stream"
+                );
+                std::process::exit(1);
+            }
+            if watch && url.is_some() {
+                eprintln!("--watch needs a package file rather than a URL");
+                std::process::exit(1);
+            }
+            if watch && from_stdin {
+                eprintln!("--watch needs a package file rather than standard input");
+                std::process::exit(1);
+            }
+            // Each cycle only writes what changed since the one before, which
+            // an atomic unpack can't do, as it writes a new folder.
+            if watch && !options.output.atomic {
+                options.selection.incremental = true;
+            }
+            let unpack = || {
+                // Progress goes where the library's messages do, so that
+                // --quiet and --json treat it the same way.
+                let source = url.map_or_else(|| src_file.to_string_lossy(), Into::into);
+                log::log(
+                    log::Level::Info,
+                    format_args!("Unpacking archive: {}", source),
+                );
+                let result = match url {
+                    Some(url) => unpack::unpack_url(url, &headers, &options),
+                    None if from_stdin => {
+                        unpack::unpack_stream(std::io::stdin().lock(), &name, &options)
+                    }
+                    None => unpack::unpack_with_options(&src_file, &options),
+                };
+                progress_bar.finish();
+                if let Ok(summary) = &result {
+                    match &summary.dry_run {
+                        Some(_) if json => {}
+                        Some(planned) => println!("{}", planned),
+                        None => log::log(log::Level::Info, format_args!("{}", summary)),
+                    }
+                    if json {
+                        println!("{}", summary.to_json().to_pretty_string(2));
+                    }
+                }
+                result
+            };
+            let result = if watch {
+                watch::watch(&src_file, watch::WatchTiming::default(), unpack, |event| {
+                    cli::watch::print_event(&src_file, event)
+                })
+            } else {
+                match unpack() {
+                    // The failed entries are listed in the summary.
+                    Ok(summary) if !summary.failed_entries.is_empty() => {
+                        std::process::exit(unpack::ErrorKind::EntryFailed.exit_code())
+                    }
+                    result => result.map(|_| ()),
+                }
+            };
+            if let Err(e) = result {
+                if json {
+                    eprintln!("{}", unpack::error_to_json(&e).to_pretty_string(2));
+                } else {
+                    eprintln!("{}", e);
+                }
+                std::process::exit(unpack::error_kind(&e).exit_code());
+            }
+        }
+        Settings::Footprints { src_file, output } => {
+            match footprint::export_footprints(&src_file, &output) {
+                Ok(n) => println!("{} footprints written to {}", n, output.to_string_lossy()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::Bounds {
+            src_file,
+            output,
+            level,
+            format,
+            max_features,
+        } => match bounds::export_bounds(&src_file, &output, format, level, max_features) {
+            Ok(n) => println!("{} node bounds written to {}", n, output.to_string_lossy()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Settings::Index { src_file, list } => match cli::index::print_hash_index(&src_file, list) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Settings::Check {
+            src_file,
+            quick,
+            filters,
+        } => match cli::check::print_check(&src_file, quick, &filters.filters()) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Settings::Verify {
+            src_file,
+            threads,
+            password,
+            ask_password,
+        } => match cli::verify::print_verify(
+            &src_file,
+            threads,
+            package_password(password, ask_password).as_deref(),
+        ) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Settings::CheckManifest { folder } => match cli::checksums::print_check_manifest(&folder) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        Settings::Diff {
+            left,
+            right,
+            detailed,
+        } => match cli::diff::print_diff(&left, &right, detailed) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        Settings::Mirror {
+            url,
+            output,
+            max_level,
+            concurrency,
+            retries,
+            headers,
+        } => run_mirror(&url, output, max_level, concurrency, retries, headers),
+        Settings::Serve { src, port, bind } => {
+            if let Err(e) = serve::serve(&src, (bind.as_str(), port)) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Validate {
+            src_file,
+            validate_json,
+            password,
+            ask_password,
+            checks,
+            fail_on,
+            threads,
+        } => {
+            let mut options = validate::ValidateOptions {
+                password: package_password(password, ask_password),
+                threads,
+                ..validate::ValidateOptions::default()
+            };
+            if !checks.is_empty() {
+                options.checks = checks;
+            }
+            if validate_json && !options.runs(validate::Check::Schema) {
+                options.checks.push(validate::Check::Schema);
+            }
+            match cli::validate::print_validate(&src_file, &options, fail_on) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::ExtractNode {
+            src_file,
+            node_id,
+            output_dir,
+            layer,
+        } => {
+            if let Err(e) =
+                cli::extract::print_extract_node(&src_file, layer, &node_id, &output_dir)
+            {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Tree {
+            src_file,
+            max_depth,
+            counts_only,
+        } => {
+            if let Err(e) = cli::tree::print_tree(&src_file, max_depth, counts_only) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Export {
+            src_file,
+            node_id,
+            output,
+            format,
+            layer,
+            target_crs,
+        } => {
+            if let Err(e) = cli::export::print_export_node(
+                &src_file, layer, &node_id, &output, format, target_crs,
+            ) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::ExportAttributes {
+            src_file,
+            output_dir,
+            merge,
+            layer,
+        } => {
+            if let Err(e) =
+                cli::export::print_export_attributes(&src_file, layer, &output_dir, merge)
+            {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Textures {
+            src_file,
+            convert,
+            split_atlas,
+            output_dir,
+        } => {
+            if let Err(e) = cli::textures::print_textures(
+                &src_file,
+                convert.is_some(),
+                split_atlas,
+                output_dir.as_deref(),
+            ) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Cat {
+            src_file,
+            entry,
+            json_format,
+        } => {
+            if let Err(e) = cli::extract::print_entry(&src_file, &entry, json_format) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::List {
+            src_file,
+            sort_by_size,
+            json,
+            deep,
+            password,
+            ask_password,
+            filters,
+        } => {
+            let password = if deep {
+                Some(package_password(password, ask_password))
+            } else {
+                None
+            };
+            if let Err(e) = cli::list::print_list(
+                &src_file,
+                sort_by_size,
+                json,
+                password.as_ref().map(Option::as_deref),
+                &filters.filters(),
+            ) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Stats {
+            src_file,
+            json,
+            coded_values,
+            layer,
+        } => {
+            let coded_values = if coded_values { Some(layer) } else { None };
+            if let Err(e) = cli::stats::print_stats(&src_file, json, coded_values) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Info {
+            src_file,
+            attributes,
+        } => {
+            if let Err(e) = cli::info::print_info(&src_file, attributes) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Repair {
+            src_file,
+            output,
+            check,
+            write_metadata,
+        } => {
+            let result = match output {
+                Some(output) if !check => {
+                    repair::repair_package(&src_file, &output, write_metadata).map(|summary| {
+                        println!(
+                            "{} entries renamed and {} entries gzipped, written to {}",
+                            summary.renamed,
+                            summary.gzipped,
+                            output.to_string_lossy()
+                        );
+                        if summary.dropped_hash_index {
+                            println!("The hash index was left out, since the offsets it records no longer hold");
+                        }
+                        if summary.wrote_metadata {
+                            println!("metadata.json was inferred from the layer and added");
+                        } else if write_metadata {
+                            println!("The package already has a metadata.json, which was kept");
+                        }
+                        true
+                    })
+                }
+                _ => cli::repair::check_naming(&src_file),
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::Recompress {
+            src_file,
+            output,
+            compression_level,
+            minify_json,
+        } => {
+            let options = recompress::RecompressOptions {
+                compression: pack::Compression::new(compression_level),
+                minify_json,
+            };
+            match recompress::recompress_package(&src_file, &output, &options) {
+                Ok(summary) => {
+                    println!(
+                        "{} entries, {} gzipped entries recompressed and {} minified, written to {}",
+                        summary.entries,
+                        summary.recompressed,
+                        summary.minified,
+                        output.to_string_lossy()
+                    );
+                    if summary.unreadable > 0 {
+                        println!(
+                            "{} entries named .gz aren't gzipped, and were copied as they are",
+                            summary.unreadable
+                        );
+                    }
+                    if summary.hash_index {
+                        println!("The hash index was written again for the new offsets");
+                    }
+                    println!(
+                        "{} bytes before, {} bytes after ({:.1}% smaller)",
+                        summary.bytes_before,
+                        summary.bytes_after,
+                        100.0
+                            * (1.0
+                                - summary.bytes_after as f64 / summary.bytes_before.max(1) as f64)
+                    );
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::Lint {
+            src_file,
+            min_texture_area,
+            min_vertices,
+            severities,
+            checks,
+            fail_on,
+            threads,
+        } => {
+            let options = lint::LintOptions {
+                min_texture_area,
+                min_vertices,
+                severities,
+                checks,
+                threads,
+            };
+            match cli::lint::print_lint(&src_file, &options, fail_on) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Settings::Patch { command } => {
+            let result = match &command {
+                PatchCommand::Create { old, new, output } => patch::create_patch(old, new, output)
+                    .map(|summary| {
+                        println!(
+                            "{} entries copied, {} carried in the patch and {} removed, {} bytes written to {}",
+                            summary.copied,
+                            summary.carried,
+                            summary.removed,
+                            summary.patch_bytes,
+                            output.to_string_lossy()
+                        )
+                    }),
+                PatchCommand::Apply { old, patch, output } => patch::apply_patch(old, patch, output)
+                    .map(|summary| {
+                        println!(
+                            "{} entries copied, {} taken from the patch and {} removed, written to {}",
+                            summary.copied,
+                            summary.carried,
+                            summary.removed,
+                            output.to_string_lossy()
+                        )
+                    }),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Settings::Batch {
+            packages,
+            command,
+            jobs,
+            threads,
+            fail_fast,
+            report,
+            flags,
+        } => {
+            let unpack_options = flags.options(&read_config(None), &subcommand);
+            let options = batch::BatchOptions {
+                jobs,
+                threads: threads.or(unpack_options.run.threads),
+                fail_fast,
+            };
+            let result = batch::batch(&packages, command, &options, move |path, threads| {
+                batch::run_command(command, path, threads, &unpack_options)
+            });
+            let batch_report = match result {
+                Ok(batch_report) => batch_report,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) =
+                std::fs::write(&report, batch_report.to_json().to_pretty_string(2) + "\n")
+            {
                 eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            for package in &batch_report.packages {
+                println!("{}", package);
+            }
+            let succeeded =
+                batch_report.packages.len() - batch_report.failed() - batch_report.skipped();
+            if command == batch::BatchCommand::Unpack {
+                println!(
+                    "{} files, {} bytes unpacked from {} packages in {:.1}s",
+                    batch_report.entries(),
+                    batch_report.bytes_written(),
+                    succeeded,
+                    batch_report.seconds
+                );
+            } else {
+                println!(
+                    "{} entries of {} packages passed in {:.1}s",
+                    batch_report.entries(),
+                    succeeded,
+                    batch_report.seconds
+                );
+            }
+            if batch_report.skipped() > 0 {
+                println!(
+                    "{} packages skipped after a failure",
+                    batch_report.skipped()
+                );
+            }
+            println!(
+                "{} of {} packages failed, report written to {}",
+                batch_report.failed(),
+                batch_report.packages.len(),
+                report.to_string_lossy()
+            );
+            if batch_report.failed() > 0 {
+                std::process::exit(1);
+            }
+        }
+        Settings::SelfTest { dir } => match cli::self_test::print_self_test(dir.as_deref()) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Settings::Config { show, package } => {
+            if !show {
+                structopt::clap::Error::with_description(
+                    "The following required arguments were not provided:\n    --show",
+                    structopt::clap::ErrorKind::MissingRequiredArgument,
+                )
+                .exit();
+            }
+            print!("{}", read_config(package.as_deref()).to_toml());
+        }
+        Settings::Features => {
+            for capability in features::capabilities() {
+                println!("{}", capability);
+            }
+        }
+        Settings::Completions { shell } => {
+            Settings::clap().gen_completions_to("slpkg", shell, &mut std::io::stdout());
+        }
+        Settings::CliSpec { json } => {
+            let app = Settings::clap();
+            if json {
+                println!("{}", cli_spec::cli_spec(&app).to_pretty_string(2));
+            } else {
+                print!("{}", cli_spec::cli_outline(&app));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    // Compares generated output against the files in tests/golden. Run the
+    // tests with UPDATE_GOLDEN=1 to rewrite them after changing the CLI.
+    fn check_golden(file_name: &str, actual: &str) {
+        let golden_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join(file_name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden_path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&golden_path).unwrap_or_default();
+        assert!(
+            expected == actual,
+            "{} is out of date, rerun the tests with UPDATE_GOLDEN=1",
+            golden_path.to_string_lossy()
+        );
+    }
+
+    fn completions(shell: Shell) -> String {
+        let mut buffer = Vec::new();
+        Settings::clap().gen_completions_to("slpkg", shell, &mut buffer);
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn completions_match_golden_files() {
+        check_golden("slpkg.bash", &completions(Shell::Bash));
+        check_golden("_slpkg", &completions(Shell::Zsh));
+        check_golden("slpkg.fish", &completions(Shell::Fish));
+        check_golden("_slpkg.ps1", &completions(Shell::PowerShell));
+    }
+
+    #[test]
+    fn cli_spec_matches_golden_file() {
+        let spec = cli_spec::cli_spec(&Settings::clap()).to_pretty_string(2);
+        check_golden("cli-spec.json", &spec);
+    }
+
+    #[test]
+    fn every_config_key_has_a_flag_of_unpack_and_batch() {
+        let keys: Vec<&str> = CONFIG_FLAGS.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, config::KEYS);
+        let spec = cli_spec::cli_spec(&Settings::clap());
+        for command in &["unpack", "batch"] {
+            let subcommand = spec
+                .get("subcommands")
+                .and_then(|s| s.as_array())
+                .and_then(|s| {
+                    s.iter()
+                        .find(|c| c.get("name").and_then(|n| n.as_str()) == Some(command))
+                })
+                .unwrap();
+            let args = subcommand.get("args").and_then(|a| a.as_array()).unwrap();
+            for (key, flag) in CONFIG_FLAGS.iter() {
+                assert!(
+                    args.iter()
+                        .any(|a| a.get("name").and_then(|n| n.as_str()) == Some(*flag)),
+                    "{} has no flag {} in {}",
+                    key,
+                    flag,
+                    command
+                );
             }
         }
     }
@@ -0,0 +1,158 @@
+// MD5, as used by the hash table index in packages. It isn't used for
+// anything security related, only to reproduce the index's keys and to
+// check that patches are whole and apply to the package they were made for.
+
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+fn round_constants() -> [u32; 64] {
+    let mut constants = [0u32; 64];
+    for (i, constant) in constants.iter_mut().enumerate() {
+        *constant = ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32;
+    }
+    constants
+}
+
+fn process_block(state: &mut [u32; 4], constants: &[u32; 64], block: &[u8]) {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let rotated = a
+            .wrapping_add(f)
+            .wrapping_add(constants[i])
+            .wrapping_add(words[g])
+            .rotate_left(SHIFTS[i]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+}
+
+/// The MD5 of data which is given a piece at a time, for what is too large
+/// to hold in memory.
+pub struct Md5 {
+    state: [u32; 4],
+    constants: [u32; 64],
+    /// The start of a block, until the rest of it is given.
+    partial: Vec<u8>,
+    len: u64,
+}
+
+impl Default for Md5 {
+    fn default() -> Md5 {
+        Md5 {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            constants: round_constants(),
+            partial: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+}
+
+impl Md5 {
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+        if !self.partial.is_empty() {
+            let wanted = (64 - self.partial.len()).min(data.len());
+            self.partial.extend_from_slice(&data[..wanted]);
+            data = &data[wanted..];
+            if self.partial.len() < 64 {
+                return;
+            }
+            let block = std::mem::take(&mut self.partial);
+            process_block(&mut self.state, &self.constants, &block);
+        }
+        let whole = data.len() / 64 * 64;
+        for block in data[..whole].chunks(64) {
+            process_block(&mut self.state, &self.constants, block);
+        }
+        self.partial.extend_from_slice(&data[whole..]);
+    }
+
+    pub fn finish(mut self) -> [u8; 16] {
+        let bits = self.len.wrapping_mul(8);
+        let mut padding = vec![0x80];
+        while (self.partial.len() + padding.len()) % 64 != 56 {
+            padding.push(0);
+        }
+        padding.extend_from_slice(&bits.to_le_bytes());
+        // The length is already counted.
+        let len = self.len;
+        self.update(&padding);
+        self.len = len;
+
+        let mut digest = [0u8; 16];
+        for (bytes, word) in digest.chunks_mut(4).zip(&self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+impl std::io::Write for Md5 {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut hasher = Md5::default();
+    hasher.update(data);
+    hasher.finish()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(to_hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(to_hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            to_hex(&md5(
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            )),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+
+    #[test]
+    fn digests_of_pieces_match() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        for piece in [1, 3, 63, 64, 65, 200] {
+            let mut hasher = Md5::default();
+            for chunk in data.chunks(piece) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finish(), md5(&data), "{}", piece);
+        }
+    }
+}
@@ -0,0 +1,1203 @@
+// Mirrors a layer of a scene service into a package, the other way round
+// from serving one. The layer document is fetched from the URL of the layer,
+// such as `.../SceneServer/layers/0`, then its node pages, or for older layers
+// the node index document of each node from the root down, and then every
+// resource the nodes refer to: geometry buffers, textures and attributes, and
+// for older layers features and shared resources too. Resources are fetched
+// by a few workers at once, each over a connection of its own, and requests
+// which fail on the way are sent again a few times. What is fetched is kept
+// as it is sent, gzipped or not, in a folder beside the package, which is
+// packed once everything is there. A mirror which is interrupted can be run
+// again, and only fetches the resources it didn't finish; the documents of
+// the node tree are always fetched again, as they are few and say which
+// resources there are. A maximum level stops at a depth of the node tree, for
+// a quick preview of a large layer: the nodes below it are left out of the
+// node pages, which are numbered again to match, or out of the children of
+// the node index documents. Point cloud layers and Building Scene Layers,
+// whose resources are laid out differently, aren't mirrored.
+
+use crate::extract::{resolve_href, RESOURCE_MEMBERS};
+use crate::http::{self, Client, HttpHeader, Resource};
+use crate::i3s::{self, SceneLayer, SlpkVersion, METADATA_DOCUMENT, SCENE_LAYER_DOCUMENT};
+use crate::json::{self, Value};
+use crate::pack::{self, PackOptions, PackSummary};
+use crate::serve::routes;
+use crate::validate;
+use failure::Error;
+use flate2::read::GzDecoder;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The folder below the staging folder which holds the layer as it is
+/// packed.
+const LAYER_FOLDER: &str = "layer";
+
+/// The file in the staging folder recording the URL of the layer, so that
+/// a mirror of another layer to the same package starts over.
+const URL_FILE: &str = "url";
+
+/// How long to wait before sending a request again, doubled after each
+/// failure.
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// How many nodes a node page holds when the layer document doesn't say.
+const DEFAULT_NODES_PER_PAGE: usize = 64;
+
+/// Why a layer couldn't be mirrored.
+#[derive(Debug, Fail)]
+pub enum MirrorError {
+    /// A URL which doesn't end in `/SceneServer/layers/<id>`.
+    #[fail(
+        display = "{} isn't the URL of a layer of a scene service, expected one ending in /SceneServer/layers/<id>",
+        _0
+    )]
+    NotALayerUrl(String),
+
+    /// A layer the service doesn't have.
+    #[fail(display = "The service has no layer at {}", _0)]
+    NoSuchLayer(String),
+
+    /// A layer of a type other than those with nodes to mirror.
+    #[fail(
+        display = "The layer at {} is a {} layer, which can't be mirrored",
+        _0, _1
+    )]
+    UnsupportedLayer(String, String),
+
+    /// A document the service sent which doesn't parse.
+    #[fail(display = "Invalid JSON in {}: {}", _0, _1)]
+    InvalidDocument(String, json::JsonError),
+
+    /// A root node the service doesn't have.
+    #[fail(display = "The service has no root node at {}", _0)]
+    MissingRootNode(String),
+
+    /// A node page the service doesn't have.
+    #[fail(display = "The service has no node page at {}", _0)]
+    MissingNodePage(String),
+}
+
+/// How a layer is mirrored.
+#[derive(Clone, Debug)]
+pub struct MirrorOptions {
+    /// Headers sent with every request to the server of the layer, such as
+    /// one which authenticates it.
+    pub headers: Vec<HttpHeader>,
+    /// The deepest level of the node tree mirrored, where the root is level
+    /// 0, or `None` for every level.
+    pub max_level: Option<usize>,
+    /// How many resources are fetched at once.
+    pub concurrency: usize,
+    /// How many times a request which fails on the way, or which the server
+    /// is too busy for, is sent again.
+    pub retries: usize,
+    /// How the mirrored resources are packed.
+    pub pack: PackOptions,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> MirrorOptions {
+        MirrorOptions {
+            headers: Vec::new(),
+            max_level: None,
+            concurrency: 8,
+            retries: 3,
+            pack: PackOptions::default(),
+        }
+    }
+}
+
+/// What mirroring a layer did.
+#[derive(Debug, Default)]
+pub struct MirrorSummary {
+    /// The nodes mirrored.
+    pub nodes: usize,
+    /// The nodes left out below the maximum level. Of layers without node
+    /// pages, only those right below it are known of.
+    pub left_out_nodes: usize,
+    /// The resources fetched by this run.
+    pub fetched: usize,
+    /// The resources which an earlier run had fetched.
+    pub resumed: usize,
+    /// The bytes fetched by this run, as they were sent.
+    pub bytes_fetched: u64,
+    /// The requests which were sent again after failing.
+    pub retries: usize,
+    /// The REST paths of the resources nodes refer to which the service
+    /// doesn't have, in order.
+    pub missing: Vec<String>,
+    /// What packing the mirrored resources did.
+    pub pack: PackSummary,
+}
+
+/// The URL of a layer, split into where its paths are and the query sent
+/// with each of them, such as a token.
+struct LayerUrl {
+    base: String,
+    query: String,
+}
+
+impl LayerUrl {
+    fn parse(url: &str) -> Result<LayerUrl, MirrorError> {
+        let (base, query) = match url.split_once('?') {
+            Some((base, query)) => (base, format!("?{}", query)),
+            None => (url, String::new()),
+        };
+        let base = base.trim_end_matches('/');
+        let mut parts = base.rsplit('/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(id), Some("layers"), Some(service))
+                if !id.is_empty()
+                    && id.bytes().all(|b| b.is_ascii_digit())
+                    && service.eq_ignore_ascii_case("SceneServer") =>
+            {
+                Ok(LayerUrl {
+                    base: base.to_string(),
+                    query,
+                })
+            }
+            _ => Err(MirrorError::NotALayerUrl(url.to_string())),
+        }
+    }
+
+    /// The URL of a path relative to the layer.
+    fn of(&self, path: &str) -> String {
+        if path.is_empty() {
+            format!("{}{}", self.base, self.query)
+        } else {
+            format!("{}/{}{}", self.base, path, self.query)
+        }
+    }
+}
+
+/// The package a layer is mirrored to unless another is asked for, named
+/// after its service, such as `City.slpk` for
+/// `.../services/City/SceneServer/layers/0`.
+pub fn default_output(url: &str) -> Result<PathBuf, Error> {
+    let layer_url = LayerUrl::parse(url)?;
+    let mut parts = layer_url.base.rsplit('/').skip(3);
+    let name = match (parts.next(), parts.next()) {
+        (Some(name), Some(before)) if !name.is_empty() && !before.is_empty() => name,
+        // A service at the root of a server, as `slpkg serve` puts it.
+        _ => "layer",
+    };
+    Ok(PathBuf::from(format!("{}.slpk", name)))
+}
+
+/// The folder what is fetched for `output` is kept in until it is packed.
+pub fn staging_folder(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".mirror");
+    output.with_file_name(name)
+}
+
+/// The body of a resource, decompressed.
+fn decoded(resource: &Resource) -> io::Result<Vec<u8>> {
+    if !resource.gzipped {
+        return Ok(resource.body.clone());
+    }
+    let mut body = Vec::new();
+    GzDecoder::new(&resource.body[..]).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+fn parse_document(resource: &Resource, url: &str) -> Result<Value, Error> {
+    json::parse_bytes(&decoded(resource)?)
+        .map_err(|e| Error::from(MirrorError::InvalidDocument(url.to_string(), e)))
+}
+
+/// Fetches `url`, sending the request again up to `retries` times when it
+/// fails in a way which may pass.
+fn fetch(
+    client: &mut Client,
+    url: &str,
+    retries: usize,
+    retried: &AtomicUsize,
+) -> Result<Option<Resource>, Error> {
+    let mut delay = RETRY_DELAY;
+    for _ in 0..retries {
+        match client.get(url) {
+            Err(e) if http::is_transient(&e) => {
+                debug!("Fetching {} again, {}", url, e);
+                retried.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    client.get(url)
+}
+
+/// How the file a resource is kept in is named, relative to the layer.
+#[derive(Clone, Debug, PartialEq)]
+enum FileName {
+    Exact(String),
+    /// A name whose extension is that of the content type it is sent as,
+    /// for the hrefs of node index documents, which leave it off.
+    Stem(String),
+}
+
+/// A resource to fetch.
+#[derive(Clone, Debug, PartialEq)]
+struct Download {
+    /// The REST path, relative to the layer.
+    path: String,
+    name: FileName,
+}
+
+/// Whether `name` is a file of the staging folder, gzipped or not, and
+/// if it is its name and whether it is gzipped. Files of partial downloads
+/// don't count.
+fn staged(folder: &Path, name: &FileName) -> Option<String> {
+    match name {
+        FileName::Exact(name) => [format!("{}.gz", name), name.clone()]
+            .iter()
+            .find(|name| folder.join(name).is_file())
+            .cloned(),
+        FileName::Stem(stem) => {
+            let (parent, file_stem) = stem.rsplit_once('/').unwrap_or(("", stem));
+            let prefix = format!("{}.", file_stem);
+            let mut names: Vec<String> = std::fs::read_dir(folder.join(parent))
+                .ok()?
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| name.starts_with(&prefix) && !name.ends_with(".part"))
+                .collect();
+            names.sort();
+            let name = names.into_iter().next()?;
+            Some(if parent.is_empty() {
+                name
+            } else {
+                format!("{}/{}", parent, name)
+            })
+        }
+    }
+}
+
+/// Writes `contents` to the file `name` of the staging folder, through a
+/// partial file which is only renamed into place once it is whole, and
+/// removes the file of the other name, gzipped or not.
+fn stage(folder: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let path = folder.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut partial = path.file_name().unwrap_or_default().to_os_string();
+    partial.push(".part");
+    let partial = path.with_file_name(partial);
+    std::fs::write(&partial, contents)?;
+    std::fs::rename(&partial, &path)?;
+    let other = match name.strip_suffix(".gz") {
+        Some(name) => name.to_string(),
+        None => format!("{}.gz", name),
+    };
+    match std::fs::remove_file(folder.join(other)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Stages a resource under `name` as it was sent.
+fn stage_resource(folder: &Path, name: &str, resource: &Resource) -> io::Result<String> {
+    let name = if resource.gzipped {
+        format!("{}.gz", name)
+    } else {
+        name.to_string()
+    };
+    stage(folder, &name, &resource.body)?;
+    Ok(name)
+}
+
+/// Replaces the member `key` of an object, or removes it for `None`.
+fn set_member(object: &mut Value, key: &str, value: Option<Value>) {
+    if let Value::Object(members) = object {
+        match (members.iter().position(|(k, _)| k == key), value) {
+            (Some(at), Some(value)) => members[at].1 = value,
+            (Some(at), None) => {
+                members.remove(at);
+            }
+            (None, Some(value)) => members.push((key.to_string(), value)),
+            (None, None) => {}
+        }
+    }
+}
+
+fn child_indices(node: &Value) -> Vec<u64> {
+    node.get("children")
+        .and_then(Value::as_array)
+        .map(|children| children.iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default()
+}
+
+/// The nodes of the node pages down to `max_level`, in the order they
+/// were in, numbered again from 0 and with their children and parents
+/// numbered to match. Nodes at `max_level` lose their children.
+fn prune_nodes(nodes: &[Value], max_level: usize) -> Vec<Value> {
+    let position_of_index: HashMap<u64, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(position, node)| {
+            let index = node.get("index").and_then(Value::as_u64);
+            (index.unwrap_or(position as u64), position)
+        })
+        .collect();
+    let mut levels = vec![None; nodes.len()];
+    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
+    if !nodes.is_empty() {
+        queue.push_back((0, 0));
+    }
+    while let Some((position, level)) = queue.pop_front() {
+        if levels[position].is_some() || level > max_level {
+            continue;
+        }
+        levels[position] = Some(level);
+        for child in child_indices(&nodes[position]) {
+            if let Some(&child) = position_of_index.get(&child) {
+                queue.push_back((child, level + 1));
+            }
+        }
+    }
+    let mut new_index = HashMap::new();
+    for (position, node) in nodes.iter().enumerate() {
+        if levels[position].is_some() {
+            let index = node.get("index").and_then(Value::as_u64);
+            new_index.insert(index.unwrap_or(position as u64), new_index.len() as u64);
+        }
+    }
+    let renumbered = |index: Option<u64>| index.and_then(|index| new_index.get(&index).copied());
+    let mut pruned = Vec::with_capacity(new_index.len());
+    for (position, node) in nodes.iter().enumerate() {
+        let level = match levels[position] {
+            Some(level) => level,
+            None => continue,
+        };
+        let mut node = node.clone();
+        set_member(&mut node, "index", Some(Value::from(pruned.len())));
+        let parent = renumbered(node.get("parentIndex").and_then(Value::as_u64));
+        if node.get("parentIndex").is_some() {
+            set_member(&mut node, "parentIndex", parent.map(Value::from));
+        }
+        if node.get("children").is_some() {
+            let children: Vec<Value> = child_indices(&node)
+                .into_iter()
+                .filter_map(|child| renumbered(Some(child)))
+                .map(Value::from)
+                .collect();
+            let children = Some(children)
+                .filter(|children| level < max_level && !children.is_empty())
+                .map(Value::Array);
+            set_member(&mut node, "children", children);
+        }
+        pruned.push(node);
+    }
+    pruned
+}
+
+/// What the requests of a mirror share.
+struct Mirror<'a> {
+    url: &'a str,
+    layer_url: LayerUrl,
+    /// The folder below the staging folder holding the layer.
+    folder: PathBuf,
+    options: &'a MirrorOptions,
+    /// How many requests were sent again.
+    retried: AtomicUsize,
+}
+
+impl Mirror<'_> {
+    /// Fetches the resource at `path`, relative to the layer, giving its URL
+    /// too.
+    fn fetch(&self, client: &mut Client, path: &str) -> Result<(String, Option<Resource>), Error> {
+        let url = self.layer_url.of(path);
+        let resource = fetch(client, &url, self.options.retries, &self.retried)?;
+        Ok((url, resource))
+    }
+}
+
+/// What crawling the node tree of a layer found.
+#[derive(Default)]
+struct Crawl {
+    downloads: Vec<Download>,
+    /// The files of the staging folder the documents of the tree were
+    /// written to.
+    staged: BTreeSet<String>,
+    nodes: usize,
+    left_out_nodes: usize,
+    missing: Vec<String>,
+}
+
+/// Fetches the node pages of a layer, and stages them, or when only the
+/// levels down to `max_level` are mirrored, what is left of them.
+fn crawl_node_pages(
+    mirror: &Mirror,
+    client: &mut Client,
+    layer: &SceneLayer,
+) -> Result<Crawl, Error> {
+    let folder = mirror.folder.as_path();
+    let nodes_per_page = layer
+        .document
+        .get("nodePages")
+        .and_then(|pages| pages.get("nodesPerPage"))
+        .and_then(Value::as_u64)
+        .filter(|&n| n > 0)
+        .map_or(DEFAULT_NODES_PER_PAGE, |n| n as usize);
+    let mut crawl = Crawl::default();
+    let mut pages = Vec::new();
+    let mut nodes = Vec::new();
+    loop {
+        let (url, page) = mirror.fetch(client, &format!("nodepages/{}", pages.len()))?;
+        let page = match page {
+            Some(page) => page,
+            None if pages.is_empty() => return Err(Error::from(MirrorError::MissingNodePage(url))),
+            None => break,
+        };
+        let document = parse_document(&page, &url)?;
+        let page_nodes = document
+            .get("nodes")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let full = page_nodes.len() >= nodes_per_page;
+        nodes.extend(page_nodes);
+        pages.push(page);
+        if !full {
+            break;
+        }
+    }
+    let all_nodes = nodes.len();
+    match mirror.options.max_level {
+        None => {
+            for (i, page) in pages.iter().enumerate() {
+                let name = stage_resource(folder, &format!("nodepages/{}.json", i), page)?;
+                crawl.staged.insert(name);
+            }
+        }
+        Some(max_level) => {
+            nodes = prune_nodes(&nodes, max_level);
+            for (i, page) in nodes.chunks(nodes_per_page).enumerate() {
+                let document =
+                    Value::Object(vec![("nodes".to_string(), Value::Array(page.to_vec()))]);
+                let name = format!("nodepages/{}.json", i);
+                stage(folder, &name, document.to_compact_string().as_bytes())?;
+                crawl.staged.insert(name);
+            }
+        }
+    }
+    crawl.nodes = nodes.len();
+    crawl.left_out_nodes = all_nodes - nodes.len();
+
+    let attribute_keys: Vec<String> = i3s::declared_attributes(&layer.document)
+        .into_iter()
+        .filter_map(|attribute| attribute.key)
+        .collect();
+    for node in &nodes {
+        for name in validate::expected_entries(layer, node, &attribute_keys) {
+            if let Some(path) = routes::rest_path(&name) {
+                let download = Download {
+                    path,
+                    name: FileName::Exact(name),
+                };
+                if !crawl.downloads.contains(&download) {
+                    crawl.downloads.push(download);
+                }
+            }
+        }
+    }
+    Ok(crawl)
+}
+
+/// Whether a node id can name a folder of the package.
+fn is_node_id(id: &str) -> bool {
+    !id.is_empty() && id != "." && id != ".." && !id.contains(['/', '\\'])
+}
+
+/// Fetches the node index document of every node from the root down, to
+/// `max_level`, and stages them.
+fn crawl_node_documents(
+    mirror: &Mirror,
+    client: &mut Client,
+    layer: &SceneLayer,
+) -> Result<Crawl, Error> {
+    let folder = mirror.folder.as_path();
+    let root_entry = i3s::root_node_entry(layer);
+    let root = root_entry
+        .strip_prefix("nodes/")
+        .and_then(|entry| entry.strip_suffix("/3dNodeIndexDocument.json"))
+        .unwrap_or("root")
+        .to_string();
+    let mut crawl = Crawl::default();
+    let mut visited = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((root, 0));
+    while let Some((id, level)) = queue.pop_front() {
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        let node_folder = format!("nodes/{}", id);
+        let (url, resource) = mirror.fetch(client, &node_folder)?;
+        let resource = match resource {
+            Some(resource) => resource,
+            None if level == 0 => return Err(Error::from(MirrorError::MissingRootNode(url))),
+            None => {
+                warn!("The service has no node {}, at {}", id, url);
+                crawl.missing.push(node_folder);
+                continue;
+            }
+        };
+        let mut document = parse_document(&resource, &url)?;
+        crawl.nodes += 1;
+        let children: Vec<String> = document
+            .get("children")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|child| child.get("id").and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        let name = format!("{}/3dNodeIndexDocument.json", node_folder);
+        if mirror.options.max_level == Some(level) && !children.is_empty() {
+            crawl.left_out_nodes += children.len();
+            set_member(&mut document, "children", None);
+            stage(folder, &name, document.to_compact_string().as_bytes())?;
+            crawl.staged.insert(name);
+        } else {
+            crawl
+                .staged
+                .insert(stage_resource(folder, &name, &resource)?);
+            for child in children {
+                if is_node_id(&child) {
+                    queue.push_back((child, level + 1));
+                } else {
+                    warn!(
+                        "Node {} has a child whose id {:?} can't be mirrored",
+                        id, child
+                    );
+                }
+            }
+        }
+
+        for member in &RESOURCE_MEMBERS {
+            let hrefs = match document.get(member) {
+                Some(Value::Array(items)) => items.iter().collect(),
+                Some(item) => vec![item],
+                None => Vec::new(),
+            };
+            let paths = hrefs
+                .into_iter()
+                .filter_map(|item| item.get("href").and_then(Value::as_str))
+                .filter_map(|href| resolve_href(&node_folder, href));
+            for path in paths {
+                let name = if *member == "sharedResource" {
+                    FileName::Exact(format!("{}/sharedResource.json", path))
+                } else {
+                    FileName::Stem(path.clone())
+                };
+                let download = Download { path, name };
+                if !crawl.downloads.contains(&download) {
+                    crawl.downloads.push(download);
+                }
+            }
+        }
+    }
+    Ok(crawl)
+}
+
+/// The statistics documents the layer document lists.
+fn statistics_downloads(layer: &SceneLayer) -> Vec<Download> {
+    layer
+        .document
+        .get("statisticsInfo")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|info| info.get("key").and_then(Value::as_str))
+        .filter(|key| is_node_id(key))
+        .map(|key| Download {
+            path: format!("statistics/{}", key),
+            name: FileName::Exact(format!("statistics/{}/0.json", key)),
+        })
+        .collect()
+}
+
+/// What fetching a resource came to.
+enum Outcome {
+    /// Fetched by this run, as the file named and of the bytes sent.
+    Fetched(String, u64),
+    /// Fetched by an earlier one.
+    Resumed(String),
+    Missing(String),
+}
+
+fn download(mirror: &Mirror, client: &mut Client, download: &Download) -> Result<Outcome, Error> {
+    if let Some(name) = staged(&mirror.folder, &download.name) {
+        return Ok(Outcome::Resumed(name));
+    }
+    let resource = match mirror.fetch(client, &download.path)?.1 {
+        Some(resource) => resource,
+        None => return Ok(Outcome::Missing(download.path.clone())),
+    };
+    let name = match &download.name {
+        FileName::Exact(name) => name.clone(),
+        FileName::Stem(stem) => {
+            let content_type = resource.content_type.as_deref().unwrap_or_default();
+            format!("{}.{}", stem, routes::extension_of(content_type))
+        }
+    };
+    let name = stage_resource(&mirror.folder, &name, &resource)?;
+    Ok(Outcome::Fetched(name, resource.body.len() as u64))
+}
+
+/// Fetches every resource, with `concurrency` workers, each with a client
+/// of its own, and adds the files they are staged as to `staged`. The first
+/// error stops the others.
+fn download_all(
+    mirror: &Mirror,
+    downloads: Vec<Download>,
+    summary: &mut MirrorSummary,
+    staged: &mut BTreeSet<String>,
+) -> Result<(), Error> {
+    let workers = mirror.options.concurrency.clamp(1, downloads.len().max(1));
+    let queue = Mutex::new(downloads.into_iter());
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new(Vec::new());
+    let errors = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<(), Error> {
+                    let mut client = Client::new(mirror.url, &mirror.options.headers)?;
+                    while !failed.load(Ordering::SeqCst) {
+                        let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                        let next = match next {
+                            Some(next) => next,
+                            None => break,
+                        };
+                        match download(mirror, &mut client, &next) {
+                            Ok(fetched) => results
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .push(fetched),
+                            Err(e) => {
+                                failed.store(true, Ordering::SeqCst);
+                                return Err(e);
+                            }
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().expect("a mirror worker panicked").err())
+            .collect::<Vec<Error>>()
+    });
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error);
+    }
+    for fetched in results.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        match fetched {
+            Outcome::Fetched(name, bytes) => {
+                summary.fetched += 1;
+                summary.bytes_fetched += bytes;
+                staged.insert(name);
+            }
+            Outcome::Resumed(name) => {
+                summary.resumed += 1;
+                staged.insert(name);
+            }
+            Outcome::Missing(path) => {
+                warn!(
+                    "The service has no resource at {}",
+                    mirror.layer_url.of(&path)
+                );
+                summary.missing.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gets the staging folder ready for mirroring `url`, keeping what an
+/// earlier mirror of the same layer fetched and clearing anything else.
+fn prepare_staging(staging: &Path, url: &str) -> io::Result<()> {
+    let url_file = staging.join(URL_FILE);
+    match std::fs::read_to_string(&url_file) {
+        Ok(staged_url) if staged_url == url => return Ok(()),
+        Ok(_) => {
+            info!(
+                "Starting over, {} holds a mirror of another layer",
+                staging.display()
+            );
+            std::fs::remove_dir_all(staging)?;
+        }
+        Err(_) if staging.exists() => std::fs::remove_dir_all(staging)?,
+        Err(_) => {}
+    }
+    std::fs::create_dir_all(staging.join(LAYER_FOLDER))?;
+    std::fs::write(url_file, url)
+}
+
+/// Removes the files of the staging folder which this run didn't stage,
+/// such as partial downloads and the resources of nodes which are left out.
+fn remove_unstaged(folder: &Path, staged: &BTreeSet<String>) -> Result<(), Error> {
+    let mut files = Vec::new();
+    crate::glob::walk(folder, None, &mut files)?;
+    for file in files {
+        if !staged.contains(&pack::name_in_folder(folder, &file)?) {
+            debug!("Removing {}", file.display());
+            std::fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+/// The package metadata of a mirrored layer.
+fn metadata(layer: &SceneLayer, nodes: usize) -> Value {
+    let version = SlpkVersion::of_layer(layer).unwrap_or(if layer.uses_node_pages() {
+        SlpkVersion::V1_7
+    } else {
+        SlpkVersion::V1_6
+    });
+    Value::Object(vec![
+        ("folderPattern".to_string(), Value::from("1")),
+        ("archiveCompressionType".to_string(), Value::from("STORE")),
+        ("resourceCompressionType".to_string(), Value::from("GZIP")),
+        ("I3SVersion".to_string(), Value::from(version.to_string())),
+        ("nodeCount".to_string(), Value::from(nodes)),
+    ])
+}
+
+/// Mirrors the layer at `url`, such as
+/// `http://host/arcgis/rest/services/City/SceneServer/layers/0`, into a
+/// package at `output`. What is fetched is kept in `staging_folder(output)`
+/// until the package is written, and a mirror to the same package picks up
+/// from there.
+pub fn mirror(url: &str, output: &Path, options: &MirrorOptions) -> Result<MirrorSummary, Error> {
+    let staging = staging_folder(output);
+    let mirror = Mirror {
+        url,
+        layer_url: LayerUrl::parse(url)?,
+        folder: staging.join(LAYER_FOLDER),
+        options,
+        retried: AtomicUsize::new(0),
+    };
+    prepare_staging(&staging, url)?;
+    let mut client = Client::new(url, &options.headers)?;
+
+    let (document_url, resource) = mirror.fetch(&mut client, "")?;
+    let resource = resource.ok_or_else(|| MirrorError::NoSuchLayer(document_url.clone()))?;
+    let layer = SceneLayer::from_document("", parse_document(&resource, &document_url)?);
+    if layer.is_point_cloud() || layer.document.get("sublayers").is_some() {
+        let layer_type = layer.layer_type.clone().unwrap_or_default();
+        return Err(Error::from(MirrorError::UnsupportedLayer(
+            document_url,
+            layer_type,
+        )));
+    }
+    let mut staged = BTreeSet::new();
+    staged.insert(stage_resource(
+        &mirror.folder,
+        SCENE_LAYER_DOCUMENT,
+        &resource,
+    )?);
+
+    let mut crawl = if layer.uses_node_pages() {
+        crawl_node_pages(&mirror, &mut client, &layer)?
+    } else {
+        crawl_node_documents(&mirror, &mut client, &layer)?
+    };
+    crawl.downloads.extend(statistics_downloads(&layer));
+    info!(
+        "{} nodes, with {} resources to fetch",
+        crawl.nodes,
+        crawl.downloads.len()
+    );
+    let mut summary = MirrorSummary {
+        nodes: crawl.nodes,
+        left_out_nodes: crawl.left_out_nodes,
+        missing: crawl.missing,
+        ..MirrorSummary::default()
+    };
+    staged.extend(crawl.staged);
+    download_all(&mirror, crawl.downloads, &mut summary, &mut staged)?;
+    summary.missing.sort();
+    summary.retries = mirror.retried.load(Ordering::SeqCst);
+
+    let metadata = metadata(&layer, summary.nodes).to_compact_string();
+    stage(&mirror.folder, METADATA_DOCUMENT, metadata.as_bytes())?;
+    staged.insert(METADATA_DOCUMENT.to_string());
+    remove_unstaged(&mirror.folder, &staged)?;
+    summary.pack = pack::pack_with_options(&mirror.folder, output, &options.pack)?;
+    std::fs::remove_dir_all(&staging)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serve::Server;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves `package` with `slpkg serve`, giving the URL of its layer.
+    fn serve(dir: &TempDir, package: SyntheticPackage) -> String {
+        let path = dir.join("served.slpk");
+        package.write_to_file(&path).unwrap();
+        let server = Server::bind(&path, "127.0.0.1:0").unwrap();
+        let url = server.layer_urls().unwrap().remove(0);
+        std::thread::spawn(move || server.run());
+        url
+    }
+
+    /// The entries of a package, as they are stored, in order of name.
+    fn entries(path: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut entries: Vec<(String, Vec<u8>)> = (0..archive.len())
+            .map(|i| {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (entry.name().to_string(), contents)
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn names(entries: &[(String, Vec<u8>)]) -> Vec<&str> {
+        entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn document(entries: &[(String, Vec<u8>)], name: &str) -> Value {
+        let (_, contents) = entries.iter().find(|(n, _)| n == name).unwrap();
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&contents[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        json::parse_bytes(&decompressed).unwrap()
+    }
+
+    /// A layer of four nodes in two node pages, where node 0 has nodes 2
+    /// and 3 as its children and node 2 has node 1. Node 3 has no second
+    /// geometry buffer.
+    fn node_page_layer() -> SyntheticPackage {
+        let mut package = SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"3DObject","store":{"version":"1.8"},
+                    "nodePages":{"nodesPerPage":2},
+                    "geometryDefinitions":[{"geometryBuffers":[{"offset":8},{"compressedAttributes":{}}]}],
+                    "materialDefinitions":[{"pbrMetallicRoughness":{"baseColorTexture":{"textureSetDefinitionId":0}}}],
+                    "textureSetDefinitions":[{"formats":[{"name":"0","format":"jpg"},{"name":"0_0_1","format":"dds"}]}],
+                    "attributeStorageInfo":[{"key":"f_0","name":"OBJECTID"}],
+                    "statisticsInfo":[{"key":"f_0","name":"OBJECTID","href":"./statistics/f_0/0"}]}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[
+                    {"index":0,"children":[2,3],"mesh":{"geometry":{"resource":0},"material":{"resource":0},"attribute":{"resource":0}}},
+                    {"index":1,"parentIndex":2,"mesh":{"geometry":{"resource":1},"attribute":{"resource":1}}}]}"#,
+            )
+            .entry(
+                "nodepages/1.json.gz",
+                br#"{"nodes":[
+                    {"index":2,"parentIndex":0,"children":[1],"mesh":{"geometry":{"resource":2},"attribute":{"resource":2}}},
+                    {"index":3,"parentIndex":0,"mesh":{"geometry":{"resource":3}}}]}"#,
+            )
+            .entry("nodes/0/textures/0.jpg", &[0xff, 0xd8, 0xff, 0xe0])
+            .entry("nodes/0/textures/0_0_1.bin.dds.gz", b"DDS texture")
+            .entry("statistics/f_0/0.json.gz", br#"{"stats":{"min":1}}"#);
+        for node in 0..4 {
+            package = package.entry(&format!("nodes/{}/geometries/0.bin.gz", node), &[node; 100]);
+            if node < 3 {
+                package = package
+                    .entry(&format!("nodes/{}/geometries/1.bin.gz", node), b"draco")
+                    .entry(&format!("nodes/{}/attributes/f_0/0.bin.gz", node), &[node]);
+            }
+        }
+        package
+    }
+
+    #[test]
+    fn layers_with_node_pages_are_mirrored_as_they_are_served() {
+        let dir = TempDir::new("mirror");
+        let original = node_page_layer();
+        let url = serve(&dir, original.clone());
+        let output = dir.join("mirror.slpk");
+        let options = MirrorOptions {
+            concurrency: 3,
+            ..MirrorOptions::default()
+        };
+        let summary = mirror(&url, &output, &options).unwrap();
+        let mirrored = entries(&output);
+        let mut expected = entries(&dir.join("served.slpk"));
+        let (_, metadata) = mirrored
+            .iter()
+            .find(|(name, _)| name == METADATA_DOCUMENT)
+            .unwrap();
+        let metadata = json::parse_bytes(metadata).unwrap();
+        expected.push((METADATA_DOCUMENT.to_string(), Vec::new()));
+        expected.sort();
+
+        assert_eq!(names(&mirrored), names(&expected));
+        for ((name, contents), (_, mirrored)) in expected.iter().zip(&mirrored) {
+            if name != METADATA_DOCUMENT {
+                assert_eq!(mirrored, contents, "{}", name);
+            }
+        }
+        assert_eq!(metadata.get("nodeCount").and_then(Value::as_u64), Some(4));
+        assert_eq!(
+            metadata.get("I3SVersion").and_then(Value::as_str),
+            Some("1.8")
+        );
+        assert_eq!(summary.nodes, 4);
+        assert_eq!((summary.fetched, summary.resumed), (13, 0));
+        assert_eq!(summary.missing, ["nodes/3/geometries/1"]);
+        assert!(!staging_folder(&output).exists());
+    }
+
+    #[test]
+    fn interrupted_mirrors_pick_up_where_they_left_off() {
+        let dir = TempDir::new("mirror-resume");
+        let url = serve(&dir, node_page_layer());
+        let output = dir.join("mirror.slpk");
+        let staging = staging_folder(&output);
+        let layer = staging.join(LAYER_FOLDER);
+        std::fs::create_dir_all(layer.join("nodes/0/geometries")).unwrap();
+        std::fs::create_dir_all(layer.join("nodes/9/geometries")).unwrap();
+        std::fs::write(staging.join(URL_FILE), &url).unwrap();
+        std::fs::write(layer.join("nodes/0/geometries/0.bin.gz"), b"kept").unwrap();
+        std::fs::write(layer.join("nodes/0/geometries/1.bin.gz.part"), b"dr").unwrap();
+        std::fs::write(layer.join("nodes/9/geometries/0.bin"), b"stale").unwrap();
+        let summary = mirror(&url, &output, &MirrorOptions::default()).unwrap();
+        let resumed = entries(&output);
+
+        let other = dir.join("other.slpk");
+        let other_layer = staging_folder(&other).join(LAYER_FOLDER);
+        std::fs::create_dir_all(other_layer.join("nodes/0/geometries")).unwrap();
+        std::fs::write(staging_folder(&other).join(URL_FILE), "http://elsewhere/").unwrap();
+        std::fs::write(other_layer.join("nodes/0/geometries/0.bin.gz"), b"other").unwrap();
+        mirror(&url, &other, &MirrorOptions::default()).unwrap();
+        let started_over = entries(&other);
+
+        assert_eq!((summary.fetched, summary.resumed), (12, 1));
+        let geometry = |entries: &[(String, Vec<u8>)]| {
+            let name = "nodes/0/geometries/0.bin.gz";
+            entries.iter().find(|(n, _)| n == name).unwrap().1.clone()
+        };
+        assert_eq!(geometry(&resumed), b"kept");
+        assert!(crate::archive::is_gzip(&geometry(&started_over)));
+        assert!(names(&resumed).contains(&"nodes/0/geometries/1.bin.gz"));
+        assert!(!names(&resumed)
+            .iter()
+            .any(|name| name.starts_with("nodes/9/") || name.ends_with(".part")));
+    }
+
+    #[test]
+    fn levels_below_the_maximum_are_left_out() {
+        let dir = TempDir::new("mirror-max-level");
+        let url = serve(&dir, node_page_layer());
+        let output = dir.join("preview.slpk");
+        let options = MirrorOptions {
+            max_level: Some(1),
+            ..MirrorOptions::default()
+        };
+        let summary = mirror(&url, &output, &options).unwrap();
+        let mirrored = entries(&output);
+        let nodes = |page: &str| {
+            document(&mirrored, page)
+                .get("nodes")
+                .and_then(Value::as_array)
+                .unwrap()
+                .iter()
+                .map(|node| {
+                    (
+                        node.get("index").and_then(Value::as_u64),
+                        node.get("parentIndex").and_then(Value::as_u64),
+                        node.get("children").map(|children| {
+                            child_indices(&Value::Object(vec![(
+                                "children".to_string(),
+                                children.clone(),
+                            )]))
+                        }),
+                        node.get("mesh")
+                            .and_then(|mesh| mesh.get("geometry"))
+                            .and_then(|geometry| geometry.get("resource"))
+                            .and_then(Value::as_u64),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Nodes 0, 2 and 3 are kept, as 0, 1 and 2, and node 2 loses its
+        // child.
+        assert_eq!(
+            nodes("nodepages/0.json.gz"),
+            [
+                (Some(0), None, Some(vec![1, 2]), Some(0)),
+                (Some(1), Some(0), None, Some(2)),
+            ]
+        );
+        assert_eq!(
+            nodes("nodepages/1.json.gz"),
+            [(Some(2), Some(0), None, Some(3))]
+        );
+        assert!(!names(&mirrored)
+            .iter()
+            .any(|name| name.starts_with("nodes/1/")));
+        assert_eq!((summary.nodes, summary.left_out_nodes), (3, 1));
+    }
+
+    #[test]
+    fn layers_with_node_index_documents_are_mirrored_from_the_root_down() {
+        let dir = TempDir::new("mirror-documents");
+        let original = SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"3DObject","store":{"rootNode":"./nodes/root"}}"#,
+            )
+            .entry(
+                "nodes/root/3dNodeIndexDocument.json.gz",
+                br#"{"id":"root","children":[{"id":"0","href":"../0"}],"sharedResource":{"href":"./shared"}}"#,
+            )
+            .entry("nodes/root/shared/sharedResource.json.gz", b"{}")
+            .entry(
+                "nodes/0/3dNodeIndexDocument.json.gz",
+                br#"{"id":"0","children":[{"id":"1"},{"id":"../x"}],
+                    "geometryData":[{"href":"./geometries/0"}],
+                    "textureData":[{"href":"./textures/0_0"}],
+                    "featureData":[{"href":"./features/0"}],
+                    "attributeData":[{"href":"./attributes/f_0/0"}]}"#,
+            )
+            .entry("nodes/0/geometries/0.bin.gz", &[0; 64])
+            .entry("nodes/0/textures/0_0.jpg", &[0xff, 0xd8, 0xff, 0xe0])
+            .entry("nodes/0/features/0.json.gz", br#"{"featureData":[]}"#)
+            .entry("nodes/0/attributes/f_0/0.bin.gz", &[1; 8])
+            .entry(
+                "nodes/1/3dNodeIndexDocument.json.gz",
+                br#"{"id":"1","geometryData":[{"href":"./geometries/0"}]}"#,
+            )
+            .entry("nodes/1/geometries/0.bin.gz", &[1; 64]);
+        let url = serve(&dir, original);
+        let output = dir.join("mirror.slpk");
+        let summary = mirror(&url, &output, &MirrorOptions::default()).unwrap();
+        let mirrored = entries(&output);
+        let mut expected = entries(&dir.join("served.slpk"));
+        expected.push((METADATA_DOCUMENT.to_string(), Vec::new()));
+        expected.sort();
+        let preview = dir.join("preview.slpk");
+        let options = MirrorOptions {
+            max_level: Some(1),
+            ..MirrorOptions::default()
+        };
+        let preview_summary = mirror(&url, &preview, &options).unwrap();
+        let previewed = entries(&preview);
+
+        assert_eq!(names(&mirrored), names(&expected));
+        for ((name, contents), (_, mirrored)) in expected.iter().zip(&mirrored) {
+            if name != METADATA_DOCUMENT {
+                assert_eq!(mirrored, contents, "{}", name);
+            }
+        }
+        assert_eq!(summary.nodes, 3);
+        assert!(summary.missing.is_empty());
+        assert!(!names(&previewed)
+            .iter()
+            .any(|name| name.starts_with("nodes/1/")));
+        let node = document(&previewed, "nodes/0/3dNodeIndexDocument.json.gz");
+        assert!(node.get("children").is_none());
+        assert!(node.get("geometryData").is_some());
+        assert_eq!(
+            (preview_summary.nodes, preview_summary.left_out_nodes),
+            (2, 2)
+        );
+    }
+
+    #[test]
+    fn point_clouds_and_urls_of_anything_but_a_layer_are_refused() {
+        let dir = TempDir::new("mirror-refused");
+        let url = serve(
+            &dir,
+            SyntheticPackage::new().entry("3dSceneLayer.json.gz", br#"{"layerType":"PointCloud"}"#),
+        );
+        let refused = mirror(&url, &dir.join("points.slpk"), &MirrorOptions::default());
+
+        assert!(matches!(
+            refused.unwrap_err().downcast_ref::<MirrorError>(),
+            Some(MirrorError::UnsupportedLayer(_, layer_type)) if layer_type == "PointCloud"
+        ));
+        for url in &[
+            "http://host/arcgis/rest/services/City/SceneServer",
+            "http://host/SceneServer/layers/x",
+            "http://host/MapServer/layers/0",
+        ] {
+            assert!(LayerUrl::parse(url).is_err(), "{}", url);
+        }
+        let layer = LayerUrl::parse("http://host/s/City/SceneServer/layers/1/?token=t").unwrap();
+        assert_eq!(
+            layer.of("nodepages/0"),
+            "http://host/s/City/SceneServer/layers/1/nodepages/0?token=t"
+        );
+        let output = |url: &str| default_output(url).unwrap();
+        assert_eq!(
+            output("http://host/arcgis/rest/services/City/SceneServer/layers/0"),
+            PathBuf::from("City.slpk")
+        );
+        assert_eq!(
+            output("http://127.0.0.1:8080/SceneServer/layers/0"),
+            PathBuf::from("layer.slpk")
+        );
+    }
+
+    /// Answers the first `failures` requests with 503 Service Unavailable
+    /// and the rest with `body`, a request to a connection.
+    fn flaky(failures: usize, body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/resource", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let mut stream = BufReader::new(stream.unwrap());
+                loop {
+                    let mut line = String::new();
+                    if stream.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let response = if i < failures {
+                    b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n".to_vec()
+                } else {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(body);
+                    response
+                };
+                let _ = stream.get_mut().write_all(&response);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn failed_requests_are_sent_again() {
+        let retried = AtomicUsize::new(0);
+        let url = flaky(2, b"body");
+        let mut client = Client::new(&url, &[]).unwrap();
+        let resource = fetch(&mut client, &url, 3, &retried).unwrap().unwrap();
+        let url = flaky(2, b"body");
+        let mut client = Client::new(&url, &[]).unwrap();
+        let error = fetch(&mut client, &url, 1, &AtomicUsize::new(0)).unwrap_err();
+
+        assert_eq!(resource.body, b"body");
+        assert!(!resource.gzipped);
+        assert_eq!(retried.load(Ordering::SeqCst), 2);
+        assert!(error.to_string().contains("503 Service Unavailable"));
+    }
+}
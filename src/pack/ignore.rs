@@ -0,0 +1,200 @@
+// Files which must never end up in a package, such as editor backups, the
+// files file managers leave in every folder they open, and version control
+// metadata. They are named with the syntax of `.gitignore`: a pattern without
+// a slash matches a name in any folder, one with a slash matches from the
+// root of the folder being packed, a trailing slash only matches folders,
+// and a leading `!` takes a file back which an earlier pattern ignored. As
+// with git, a file below an ignored folder can't be taken back. The last
+// pattern which matches decides, so the patterns of an ignore file override
+// the defaults, which come first.
+
+use crate::glob::Pattern;
+use failure::Error;
+use std::path::Path;
+
+/// The ignore file read from the root of the folder being packed.
+pub const IGNORE_FILE: &str = ".slpkignore";
+
+/// The junk left in folders by editors, operating systems and version
+/// control, which is ignored unless the defaults are turned off.
+const DEFAULT_IGNORES: &[&str] = &[
+    ".DS_Store",
+    "._*",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    "*~",
+    "*.bak",
+    "*.swp",
+    "*.swo",
+    ".#*",
+    "#*#",
+    ".git/",
+    ".svn/",
+    ".hg/",
+];
+
+#[derive(Clone, Debug)]
+struct Rule {
+    /// The line the rule was read from.
+    text: String,
+    /// Where the rule came from, for warnings.
+    origin: String,
+    pattern: Pattern,
+    negated: bool,
+    folders_only: bool,
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str, origin: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            // `\#` and `\!` start patterns which begin with those characters.
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (folders_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(Rule {
+            text: line.to_string(),
+            origin: origin.to_string(),
+            pattern: Pattern::new(pattern),
+            negated,
+            folders_only,
+            anchored,
+        })
+    }
+
+    /// Whether the rule matches the file or folder at `name`, relative to
+    /// the root and with forward slashes.
+    fn matches(&self, name: &str, is_folder: bool) -> bool {
+        if self.folders_only && !is_folder {
+            return false;
+        }
+        if self.anchored {
+            return self.pattern.matches(name);
+        }
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        self.pattern.matches(file_name)
+    }
+}
+
+/// The rules deciding which files of a folder are left out of its package.
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+/// What ignored a file: the pattern, as it was written, and where it was
+/// written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IgnoredBy {
+    /// The pattern.
+    pub pattern: String,
+    /// The ignore file it's from, or the built-in rules.
+    pub origin: String,
+}
+
+impl IgnoreRules {
+    /// The built-in rules for common junk files.
+    pub fn defaults() -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        rules.add(&DEFAULT_IGNORES.join("\n"), "the default ignores");
+        rules
+    }
+
+    /// Adds the rules of an ignore file, which take precedence over those
+    /// added before.
+    pub fn add(&mut self, text: &str, origin: &str) {
+        self.rules
+            .extend(text.lines().filter_map(|line| Rule::parse(line, origin)));
+    }
+
+    /// Adds the rules of the ignore file at `path`.
+    pub fn add_file(&mut self, path: &Path) -> Result<(), Error> {
+        let text = std::fs::read_to_string(path)?;
+        self.add(&text, &path.to_string_lossy());
+        Ok(())
+    }
+
+    /// The last rule which matches `name`, if it ignores it.
+    fn decide(&self, name: &str, is_folder: bool) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(name, is_folder))
+            .filter(|rule| !rule.negated)
+    }
+
+    /// The rule which leaves the file at `name` out of the package, if any
+    /// does, either by ignoring it or one of the folders it is in. `name` is
+    /// relative to the root of the folder, with forward slashes.
+    pub fn ignored_by(&self, name: &str) -> Option<IgnoredBy> {
+        let folders = name.match_indices('/').map(|(end, _)| (&name[..end], true));
+        folders
+            .chain(std::iter::once((name, false)))
+            .find_map(|(name, is_folder)| self.decide(name, is_folder))
+            .map(|rule| IgnoredBy {
+                pattern: rule.text.clone(),
+                origin: rule.origin.clone(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_gitignore_syntax() {
+        let mut rules = IgnoreRules::defaults();
+        rules.add(
+            "# scratch work\n*.tmp\n!keep.tmp\n/notes/\nbuild/\nnodes/*/textures/*.psd\n",
+            ".slpkignore",
+        );
+        let ignored = |name: &str| rules.ignored_by(name).is_some();
+
+        assert!(ignored(".DS_Store"));
+        assert!(ignored("nodes/0/geometries/Thumbs.db"));
+        assert!(ignored(".git/HEAD"));
+        assert!(ignored("nodes/0/3dNodeIndexDocument.json~"));
+        assert!(ignored("a.tmp"));
+        assert!(!ignored("keep.tmp"));
+        // Only the folder at the root, and only folders.
+        assert!(ignored("notes/todo.txt"));
+        assert!(!ignored("nodes/notes/todo.txt"));
+        assert!(!ignored("notes"));
+        assert!(ignored("nodes/0/build/x"));
+        assert!(ignored("nodes/0/textures/0.psd"));
+        assert!(!ignored("nodes/0/textures/0.jpg"));
+        assert!(!ignored("3dSceneLayer.json"));
+        assert_eq!(
+            rules.ignored_by("a.tmp"),
+            Some(IgnoredBy {
+                pattern: "*.tmp".to_string(),
+                origin: ".slpkignore".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn files_below_ignored_folders_stay_ignored() {
+        let mut rules = IgnoreRules::default();
+        rules.add("cache/\n!cache/keep.json\n!*.bak\n", "test");
+        assert!(rules.ignored_by("cache/keep.json").is_some());
+        let mut defaults = IgnoreRules::defaults();
+        defaults.add("!*.bak", "test");
+        assert!(defaults.ignored_by("0.bak").is_none());
+    }
+}
@@ -0,0 +1,669 @@
+// Packs an unpacked folder into a package again, so that a package can be
+// edited as files and put back together. JSON resources are gzipped into
+// `.json.gz` entries, as unpacking took them out of, at the level ArcGIS
+// uses unless another is asked for, and everything else is added as it is. Every entry is stored rather than deflated, as the I3S
+// specification asks of packages. Packing the same folder twice gives the
+// same package, byte for byte, so that packages can be content-addressed:
+// entries are written in order of name, every entry has the same timestamp
+// rather than the modification time of its file, the gzip headers record no
+// time, name or operating system, and no attributes of the files are
+// recorded, such as their Unix permissions. A hash index
+// left by unpacking records offsets into the original package, so it is left
+// out, as are the files which unpacking writes beside the entries. So are
+// the files the ignore rules name, such as editor backups and version
+// control metadata, which are warned about when they look like resources.
+
+mod ignore;
+
+pub use self::ignore::{IgnoreRules, IgnoredBy, IGNORE_FILE};
+use crate::archive::hash_index::HASH_INDEX_ENTRY;
+use crate::archive::raw::{RawEntry, RawWriter};
+use crate::i3s::SCENE_LAYER_DOCUMENT;
+use crate::unpack;
+use failure::Error;
+pub use flate2::Compression;
+use flate2::GzBuilder;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bit 11 of the flags: the name is UTF-8 rather than code page 437.
+const UTF8_NAME_FLAG: u16 = 0x0800;
+/// Version 2.0 of the zip specification, which any reader handles.
+const VERSION: u16 = 20;
+/// The operating system a gzip header records when it is unknown.
+const UNKNOWN_OS: u8 = 255;
+/// 1980-01-01, the earliest time an MS-DOS date can record, in seconds
+/// since the Unix epoch.
+const ZIP_EPOCH: u64 = 315_532_800;
+
+/// Why a folder couldn't be packed.
+#[derive(Debug, Fail)]
+pub enum PackError {
+    /// A folder with no layer document where a package keeps one.
+    #[fail(
+        display = "{} is not an unpacked package, it has no {} at its root or below layers/<n>/",
+        _0, _1
+    )]
+    MissingSceneLayerDocument(String, &'static str),
+
+    /// A file whose name can't be an entry name.
+    #[fail(display = "{} has a name which isn't valid Unicode", _0)]
+    NonUnicodeName(String),
+
+    /// Two files which would be packed under the same entry name.
+    #[fail(display = "{} and {} would both be packed as {}", _0, _1, _2)]
+    NameCollision(String, String, String),
+
+    /// A `--timestamp` which is neither seconds nor mtime.
+    #[fail(
+        display = "Invalid timestamp '{}', expected seconds since 1970-01-01 UTC or mtime",
+        _0
+    )]
+    InvalidTimestamp(String),
+
+    /// A compression level above 9.
+    #[fail(display = "Invalid compression level {}, expected 0 to 9", _0)]
+    InvalidCompressionLevel(u32),
+}
+
+/// The timestamp each entry is given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timestamps {
+    /// The same time for every entry.
+    Fixed(SystemTime),
+    /// The modification time of each file, which makes the package depend
+    /// on when its files were written.
+    Modified,
+}
+
+impl Timestamps {
+    /// The time `SOURCE_DATE_EPOCH` gives, as reproducible builds set it,
+    /// or otherwise 1980-01-01.
+    pub fn from_environment() -> Result<Timestamps, PackError> {
+        match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(seconds) => match seconds.trim().parse::<u64>() {
+                Ok(seconds) => Ok(Timestamps::Fixed(UNIX_EPOCH + Duration::from_secs(seconds))),
+                Err(_) => Err(PackError::InvalidTimestamp(seconds)),
+            },
+            Err(_) => Ok(Timestamps::Fixed(
+                UNIX_EPOCH + Duration::from_secs(ZIP_EPOCH),
+            )),
+        }
+    }
+}
+
+/// How a folder is packed.
+#[derive(Clone, Debug)]
+pub struct PackOptions {
+    /// The timestamp of every entry, or `None` for those of
+    /// `Timestamps::from_environment`.
+    pub timestamps: Option<Timestamps>,
+    /// The level JSON resources are gzipped at, from 0 to 9, or `None` to
+    /// store them as they are, without `.gz` in their names, as an ESLPK
+    /// does. Level 6 by default, as ArcGIS writes packages.
+    pub compression: Option<Compression>,
+    /// Whether common junk files, such as `.DS_Store` and `.git/`, are
+    /// ignored.
+    pub default_ignores: bool,
+    /// Ignore files read after the `.slpkignore` of the folder, whose
+    /// rules take precedence over it.
+    pub ignore_files: Vec<PathBuf>,
+}
+
+impl Default for PackOptions {
+    fn default() -> PackOptions {
+        PackOptions {
+            timestamps: None,
+            compression: Some(Compression::default()),
+            default_ignores: true,
+            ignore_files: Vec::new(),
+        }
+    }
+}
+
+impl PackOptions {
+    /// The rules for packing `folder`: the defaults unless they are turned
+    /// off, then the `.slpkignore` of the folder, then `ignore_files`.
+    pub fn ignore_rules(&self, folder: &Path) -> Result<IgnoreRules, Error> {
+        let mut rules = if self.default_ignores {
+            IgnoreRules::defaults()
+        } else {
+            IgnoreRules::default()
+        };
+        let ignore_file = folder.join(IGNORE_FILE);
+        if ignore_file.is_file() {
+            rules.add(&std::fs::read_to_string(ignore_file)?, IGNORE_FILE);
+        }
+        for path in &self.ignore_files {
+            rules.add_file(path)?;
+        }
+        Ok(rules)
+    }
+}
+
+impl FromStr for Timestamps {
+    type Err = PackError;
+
+    fn from_str(s: &str) -> Result<Timestamps, PackError> {
+        if s == "mtime" {
+            return Ok(Timestamps::Modified);
+        }
+        s.trim()
+            .parse::<u64>()
+            .map(|seconds| Timestamps::Fixed(UNIX_EPOCH + Duration::from_secs(seconds)))
+            .map_err(|_| PackError::InvalidTimestamp(s.to_string()))
+    }
+}
+
+/// What packing a folder did.
+#[derive(Debug, Default)]
+pub struct PackSummary {
+    /// The entries written.
+    pub entries: usize,
+    /// JSON resources which were gzipped.
+    pub gzipped: usize,
+    /// Files which slpkg itself left in the folder, and a hash index whose
+    /// offsets would be wrong, which were left out.
+    pub left_out: usize,
+    /// The entry names of the files the ignore rules left out, in order of
+    /// name.
+    pub ignored: Vec<String>,
+}
+
+/// Whether a file is a JSON resource, which packages hold gzipped. Only the
+/// metadata of the package as a whole is stored as plain JSON.
+fn is_json_resource(name_in_folder: &str) -> bool {
+    name_in_folder.ends_with(".json") && name_in_folder != "metadata.json"
+}
+
+/// Whether the folder holds a layer, either at its root or below
+/// `layers/<n>/`, gzipped or not.
+fn has_scene_layer_document(folder: &Path) -> Result<bool, Error> {
+    let is_document = |path: &Path| {
+        path.is_file()
+            || path
+                .with_file_name(format!("{}.gz", SCENE_LAYER_DOCUMENT))
+                .is_file()
+    };
+    if is_document(&folder.join(SCENE_LAYER_DOCUMENT)) {
+        return Ok(true);
+    }
+    let layers = folder.join("layers");
+    if !layers.is_dir() {
+        return Ok(false);
+    }
+    for layer in std::fs::read_dir(layers)? {
+        if is_document(&layer?.path().join(SCENE_LAYER_DOCUMENT)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The name of the file below `folder`, with forward slashes whatever the
+/// host uses.
+pub(crate) fn name_in_folder(folder: &Path, file: &Path) -> Result<String, Error> {
+    let relative = file.strip_prefix(folder)?;
+    let mut parts = Vec::new();
+    for part in relative.components() {
+        match part.as_os_str().to_str() {
+            Some(part) => parts.push(part),
+            None => {
+                return Err(Error::from(PackError::NonUnicodeName(
+                    relative.to_string_lossy().into_owned(),
+                )))
+            }
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+/// The MS-DOS time and date of a modification time, taken as UTC, and
+/// clamped to the years which it can record.
+fn dos_time_and_date(modified: SystemTime) -> (u16, u16) {
+    const LATEST: u64 = 4_354_819_199; // 2107-12-31 23:59:59
+    let seconds = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+        .clamp(ZIP_EPOCH, LATEST);
+    let (days, time_of_day) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // The date in the proleptic Gregorian calendar of a day since the epoch.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = ((time_of_day / 3_600) << 11)
+        | ((time_of_day % 3_600 / 60) << 5)
+        | ((time_of_day % 60) / 2);
+    let date = (((year - 1980) << 9) | (month << 5) | day) as u16;
+    (time as u16, date)
+}
+
+/// A file to pack, by the name of its entry.
+struct Source {
+    entry_name: String,
+    path: PathBuf,
+    gzip: bool,
+}
+
+/// Finds the files to pack, leaving out the package being written, the
+/// files slpkg leaves in the folders it unpacks to, the ignore file and the
+/// files it ignores.
+fn sources(
+    folder: &Path,
+    output: &Path,
+    gzip_json: bool,
+    ignore_rules: &IgnoreRules,
+    summary: &mut PackSummary,
+) -> Result<Vec<Source>, Error> {
+    let mut files = Vec::new();
+    crate::glob::walk(folder, None, &mut files)?;
+    let output = output.canonicalize().ok();
+    let junk = IgnoreRules::defaults();
+    let mut sources: Vec<Source> = Vec::with_capacity(files.len());
+    for path in files {
+        if output.is_some() && path.canonicalize().ok() == output {
+            continue;
+        }
+        let name = name_in_folder(folder, &path)?;
+        if unpack::is_bookkeeping_file(&name) || name == HASH_INDEX_ENTRY {
+            summary.left_out += 1;
+            continue;
+        }
+        if name == IGNORE_FILE {
+            continue;
+        }
+        if let Some(ignored_by) = ignore_rules.ignored_by(&name) {
+            // Backups in node folders are junk even though they are where
+            // resources are.
+            let is_resource =
+                unpack::ResourceKind::of_entry(Path::new(&name)) != unpack::ResourceKind::Other;
+            if is_resource && junk.ignored_by(&name).is_none() {
+                warn!(
+                    "{} looks like an I3S resource, but {} in {} ignores it",
+                    name, ignored_by.pattern, ignored_by.origin
+                );
+            }
+            debug!("Ignored: {}", name);
+            summary.ignored.push(name);
+            continue;
+        }
+        let gzip = gzip_json && is_json_resource(&name);
+        let entry_name = if gzip { format!("{}.gz", name) } else { name };
+        sources.push(Source {
+            entry_name,
+            path,
+            gzip,
+        });
+    }
+    sources.sort_by(|a, b| a.entry_name.cmp(&b.entry_name));
+    for pair in sources.windows(2) {
+        if pair[0].entry_name == pair[1].entry_name {
+            return Err(Error::from(PackError::NameCollision(
+                pair[0].path.to_string_lossy().into_owned(),
+                pair[1].path.to_string_lossy().into_owned(),
+                pair[0].entry_name.clone(),
+            )));
+        }
+    }
+    Ok(sources)
+}
+
+fn crc32_of(reader: &mut dyn Read) -> std::io::Result<(u32, u64)> {
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok((hasher.finalize(), bytes));
+        }
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+}
+
+fn pack_to<W: Write>(
+    sources: &[Source],
+    timestamps: Timestamps,
+    compression: Compression,
+    writer: W,
+) -> Result<W, Error> {
+    let mut writer = RawWriter::new(writer);
+    for source in sources {
+        let (time, date) = dos_time_and_date(match timestamps {
+            Timestamps::Fixed(time) => time,
+            Timestamps::Modified => std::fs::metadata(&source.path)?.modified()?,
+        });
+        // Version 2.0 made by MS-DOS, and external attributes of zero, so
+        // nothing of the host or the file's permissions is recorded.
+        let mut entry = RawEntry::default();
+        entry.name = source.entry_name.as_bytes().to_vec();
+        entry.version_made_by = VERSION;
+        entry.version_needed = VERSION;
+        if !source.entry_name.is_ascii() {
+            entry.flags = UTF8_NAME_FLAG;
+        }
+        entry.last_modified_time = time;
+        entry.last_modified_date = date;
+        if source.gzip {
+            // JSON resources are small enough to gzip in memory, which the
+            // CRC in the local header has to be known before.
+            let mut encoder = GzBuilder::new()
+                .mtime(0)
+                .operating_system(UNKNOWN_OS)
+                .write(Vec::new(), compression);
+            std::io::copy(&mut File::open(&source.path)?, &mut encoder)?;
+            let data = encoder.finish()?;
+            let (crc32, bytes) = crc32_of(&mut data.as_slice())?;
+            entry.crc32 = crc32;
+            entry.size = bytes;
+            entry.compressed_size = bytes;
+            writer.write_entry(&entry, &mut data.as_slice())?;
+        } else {
+            let (crc32, bytes) = crc32_of(&mut BufReader::new(File::open(&source.path)?))?;
+            entry.crc32 = crc32;
+            entry.size = bytes;
+            entry.compressed_size = bytes;
+            writer.write_entry(&entry, &mut BufReader::new(File::open(&source.path)?))?;
+        }
+    }
+    Ok(writer.finish(b"")?)
+}
+
+/// Packs the files below `folder_path` into a package at `output`, with
+/// the default options.
+pub fn pack(folder_path: &Path, output: &Path) -> Result<PackSummary, Error> {
+    pack_with_options(folder_path, output, &PackOptions::default())
+}
+
+/// Packs the files below `folder_path` into a package at `output`. The
+/// package is written under a temporary name and renamed into place, so a
+/// failure never leaves a partial package at `output`.
+pub fn pack_with_options(
+    folder_path: &Path,
+    output: &Path,
+    options: &PackOptions,
+) -> Result<PackSummary, Error> {
+    let timestamps = match options.timestamps {
+        Some(timestamps) => timestamps,
+        None => Timestamps::from_environment()?,
+    };
+    if let Some(compression) = options.compression.filter(|c| c.level() > 9) {
+        return Err(Error::from(PackError::InvalidCompressionLevel(
+            compression.level(),
+        )));
+    }
+    if !has_scene_layer_document(folder_path)? {
+        return Err(Error::from(PackError::MissingSceneLayerDocument(
+            folder_path.to_string_lossy().into_owned(),
+            SCENE_LAYER_DOCUMENT,
+        )));
+    }
+    let mut summary = PackSummary::default();
+    let sources = sources(
+        folder_path,
+        output,
+        options.compression.is_some(),
+        &options.ignore_rules(folder_path)?,
+        &mut summary,
+    )?;
+    summary.ignored.sort();
+    summary.entries = sources.len();
+    summary.gzipped = sources.iter().filter(|source| source.gzip).count();
+
+    let mut partial = output.file_name().unwrap_or_default().to_os_string();
+    partial.push(".partial");
+    let partial = output.with_file_name(partial);
+    let written = File::create(&partial)
+        .map_err(Error::from)
+        .and_then(|file| {
+            pack_to(
+                &sources,
+                timestamps,
+                options.compression.unwrap_or_default(),
+                BufWriter::new(file),
+            )
+        })
+        .and_then(|writer| Ok(writer.into_inner().map_err(|e| e.into_error())?));
+    match written {
+        Ok(_) => {
+            std::fs::rename(&partial, output)?;
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use zip::{CompressionMethod, ZipArchive};
+
+    fn files_below(folder: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
+        crate::glob::walk(folder, None, &mut files).unwrap();
+        let mut contents: Vec<(String, Vec<u8>)> = files
+            .iter()
+            .map(|file| {
+                (
+                    name_in_folder(folder, file).unwrap(),
+                    std::fs::read(file).unwrap(),
+                )
+            })
+            .filter(|(name, _)| !unpack::is_bookkeeping_file(name))
+            .collect();
+        contents.sort();
+        contents
+    }
+
+    #[test]
+    fn packed_folders_unpack_to_the_same_files() {
+        let dir = TempDir::new("pack");
+        let original = dir.join("original.slpk");
+        SyntheticPackage::standard()
+            .write_to_file(&original)
+            .unwrap();
+        unpack_with_options(&original, &UnpackOptions::default()).unwrap();
+        let unpacked = files_below(&dir.join("original"));
+
+        let repacked = dir.join("repacked.slpk");
+        let summary = pack(&dir.join("original"), &repacked).unwrap();
+        assert_eq!(summary.entries, unpacked.len());
+        let mut archive = ZipArchive::new(File::open(&repacked).unwrap()).unwrap();
+        let mut names = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i).unwrap();
+            assert_eq!(entry.compression(), CompressionMethod::Stored);
+            names.push(entry.name().to_string());
+        }
+        assert!(names.contains(&"3dSceneLayer.json.gz".to_string()));
+        assert!(names.contains(&"metadata.json".to_string()));
+        assert!(names.contains(&"sublayers/1/nodes/0/geometries/0.bin".to_string()));
+        unpack_with_options(&repacked, &UnpackOptions::default()).unwrap();
+        let round_tripped = files_below(&dir.join("repacked"));
+
+        std::fs::create_dir_all(dir.join("empty/nodes")).unwrap();
+        let refused = pack(&dir.join("empty"), &dir.join("empty.slpk"));
+        let left_behind = dir.join("empty.slpk").exists();
+        assert_eq!(round_tripped, unpacked);
+        assert!(refused.is_err());
+        assert!(!left_behind);
+    }
+
+    #[test]
+    fn packing_the_same_folder_gives_the_same_bytes() {
+        let dir = TempDir::new("pack-reproducible");
+        let original = dir.join("original.slpk");
+        SyntheticPackage::standard()
+            .write_to_file(&original)
+            .unwrap();
+        unpack_with_options(&original, &UnpackOptions::default()).unwrap();
+        let folder = dir.join("original");
+        let first = dir.join("first.slpk");
+        pack(&folder, &first).unwrap();
+        let second = dir.join("second.slpk");
+        pack(&folder, &second).unwrap();
+        let mut files = Vec::new();
+        crate::glob::walk(&folder, None, &mut files).unwrap();
+        let touched = SystemTime::now() - Duration::from_secs(86_400 * 400);
+        for file in &files {
+            File::options()
+                .write(true)
+                .open(file)
+                .unwrap()
+                .set_modified(touched)
+                .unwrap();
+        }
+        let after_touching = dir.join("touched.slpk");
+        pack(&folder, &after_touching).unwrap();
+        let with_mtimes = dir.join("mtimes.slpk");
+        let options = PackOptions {
+            timestamps: Some(Timestamps::Modified),
+            ..PackOptions::default()
+        };
+        pack_with_options(&folder, &with_mtimes, &options).unwrap();
+        let read = |path: &Path| std::fs::read(path).unwrap();
+        let (first, second, after_touching, with_mtimes) = (
+            read(&first),
+            read(&second),
+            read(&after_touching),
+            read(&with_mtimes),
+        );
+
+        assert_eq!(first, second);
+        assert_eq!(first, after_touching);
+        assert_ne!(first, with_mtimes);
+        assert_eq!("mtime".parse::<Timestamps>().unwrap(), Timestamps::Modified);
+        assert!("yesterday".parse::<Timestamps>().is_err());
+    }
+
+    #[test]
+    fn every_compression_level_unpacks_to_the_same_files() {
+        let dir = TempDir::new("pack-levels");
+        let original = dir.join("original.slpk");
+        SyntheticPackage::standard()
+            .write_to_file(&original)
+            .unwrap();
+        unpack_with_options(&original, &UnpackOptions::default()).unwrap();
+        let unpacked = files_below(&dir.join("original"));
+        let mut results = Vec::new();
+        for (name, compression) in &[
+            ("fast", Some(Compression::fast())),
+            ("best", Some(Compression::best())),
+            ("stored", None),
+        ] {
+            let package = dir.join(format!("{}.slpk", name));
+            let options = PackOptions {
+                compression: *compression,
+                ..PackOptions::default()
+            };
+            let summary = pack_with_options(&dir.join("original"), &package, &options).unwrap();
+            let mut archive = ZipArchive::new(File::open(&package).unwrap()).unwrap();
+            let has_gz = archive.by_name("3dSceneLayer.json.gz").is_ok();
+            unpack_with_options(&package, &UnpackOptions::default()).unwrap();
+            results.push((summary.gzipped > 0, has_gz, files_below(&dir.join(name))));
+        }
+        let too_high = PackOptions {
+            compression: Some(Compression::new(10)),
+            ..PackOptions::default()
+        };
+        let refused = pack_with_options(&dir.join("original"), &dir.join("x.slpk"), &too_high);
+
+        assert_eq!(results[0], (true, true, unpacked.clone()));
+        assert_eq!(results[1], (true, true, unpacked.clone()));
+        assert_eq!(results[2], (false, false, unpacked));
+        assert!(refused.is_err());
+    }
+
+    #[test]
+    fn ignored_files_are_left_out() {
+        let dir = TempDir::new("pack-ignore");
+        let folder = dir.join("layer");
+        std::fs::create_dir_all(folder.join(".git")).unwrap();
+        std::fs::create_dir_all(folder.join("nodes/0/geometries")).unwrap();
+        for (name, contents) in &[
+            ("3dSceneLayer.json", &b"{}"[..]),
+            (".DS_Store", b"junk"),
+            (".git/HEAD", b"ref: refs/heads/main"),
+            ("nodes/0/geometries/0.bin", b"vertices"),
+            ("nodes/0/geometries/0.bin.bak", b"vertices"),
+            ("notes.txt", b"todo"),
+            (IGNORE_FILE, b"*.txt\n"),
+        ] {
+            std::fs::write(folder.join(name), contents).unwrap();
+        }
+        let extra = dir.join("extra-ignores");
+        std::fs::write(&extra, "!notes.txt\n").unwrap();
+
+        let package = dir.join("layer.slpk");
+        let summary = pack(&folder, &package).unwrap();
+        let names = |package: &Path| {
+            let mut archive = ZipArchive::new(File::open(package).unwrap()).unwrap();
+            let mut names: Vec<String> = (0..archive.len())
+                .map(|i| archive.by_index(i).unwrap().name().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        let packed = names(&package);
+        let options = PackOptions {
+            default_ignores: false,
+            ignore_files: vec![extra],
+            ..PackOptions::default()
+        };
+        let without_defaults = pack_with_options(&folder, &package, &options).unwrap();
+        let packed_without_defaults = names(&package);
+
+        assert_eq!(packed, ["3dSceneLayer.json.gz", "nodes/0/geometries/0.bin"]);
+        assert_eq!(
+            summary.ignored,
+            [
+                ".DS_Store",
+                ".git/HEAD",
+                "nodes/0/geometries/0.bin.bak",
+                "notes.txt"
+            ]
+        );
+        assert!(without_defaults.ignored.is_empty());
+        assert_eq!(packed_without_defaults.len(), 6);
+        assert!(packed_without_defaults.contains(&"notes.txt".to_string()));
+    }
+
+    #[test]
+    fn modification_times_convert_to_dos_dates() {
+        // 2019-03-01 12:30:10 UTC.
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1_551_443_410);
+        let (time, date) = dos_time_and_date(modified);
+        assert_eq!(
+            (time >> 11, (time >> 5) & 0x3f, (time & 0x1f) * 2),
+            (12, 30, 10)
+        );
+        assert_eq!(
+            (1980 + (date >> 9), (date >> 5) & 0xf, date & 0x1f),
+            (2019, 3, 1)
+        );
+        assert_eq!(dos_time_and_date(UNIX_EPOCH), (0, 1 << 5 | 1));
+    }
+}
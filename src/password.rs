@@ -0,0 +1,56 @@
+// Asks for the password of an encrypted package on the terminal, for users
+// who would rather not leave it in their shell history or the environment.
+// On Unix the terminal is opened as /dev/tty, so that standard input can still
+// be the package being unpacked, and echo is turned off while the password is
+// typed. There is no portable way to do that elsewhere, so other systems read
+// the password from standard input as it is typed, after saying it will show.
+
+use std::io::{self, BufRead, Write};
+
+/// The password typed in answer to `prompt`, without its line ending.
+#[cfg(unix)]
+pub fn ask_password(prompt: &str) -> io::Result<String> {
+    use std::fs::OpenOptions;
+    use std::io::BufReader;
+    use std::os::unix::io::AsRawFd;
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let echoing = termios;
+    termios.c_lflag &= !libc::ECHO;
+    termios.c_lflag |= libc::ECHONL;
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut line = String::new();
+    let read = BufReader::new(&tty).read_line(&mut line);
+    // Echo comes back on whether or not the password could be read.
+    unsafe {
+        libc::tcsetattr(fd, libc::TCSANOW, &echoing);
+    }
+    read?;
+    Ok(trimmed(line))
+}
+
+/// The password typed in answer to `prompt`, without its line ending.
+#[cfg(not(unix))]
+pub fn ask_password(prompt: &str) -> io::Result<String> {
+    eprint!("{} (it will show as it is typed) ", prompt.trim_end());
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(trimmed(line))
+}
+
+fn trimmed(mut line: String) -> String {
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+    line
+}
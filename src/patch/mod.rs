@@ -0,0 +1,631 @@
+// Patches which turn one version of a package into the next, for exports
+// which only change a few hundred of their entries from week to week. A
+// patch lists the entries of the new package in order: an entry which is
+// unchanged is copied from the old package by name, and the rest are carried
+// in the patch as their zip record and compressed data, so that neither
+// making nor applying a patch recompresses anything.
+//
+// The layout, little endian throughout:
+//   "SLPKPATCH" and the format version, a byte
+//   the MD5 of the old package's directory
+//   the removed entries: a u32 count, then a u16 length and name for each
+//   the comment of the new package: a u16 length and the comment
+//   the entries of the new package: a u64 count, then for each either
+//     0, a u16 length and name: copied from the old package
+//     1, its zip record, a u64 length and its compressed data
+//   the MD5 of the new package's entries
+//   the MD5 of everything before it
+
+use crate::archive::raw::{raw_data, RawArchive, RawEntry, RawWriter};
+use crate::md5::Md5;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use failure::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8] = b"SLPKPATCH";
+const FORMAT_VERSION: u8 = 1;
+const DIGEST_LEN: u64 = 16;
+
+const COPIED: u8 = 0;
+const CARRIED: u8 = 1;
+
+/// Why a patch couldn't be made or applied.
+#[derive(Debug, Fail)]
+pub enum PatchError {
+    /// A file which doesn't start as a patch does.
+    #[fail(display = "{} isn't a package patch", _0)]
+    NotAPatch(String),
+
+    /// A patch of a format version this build can't apply.
+    #[fail(
+        display = "The patch is of format version {}, but only version {} can be applied",
+        _0, _1
+    )]
+    UnsupportedVersion(u8, u8),
+
+    /// A patch which doesn't match its checksum, or is cut short.
+    #[fail(display = "The patch is corrupt: {}", _0)]
+    Corrupt(String),
+
+    /// A package other than the one the patch was made for.
+    #[fail(display = "The patch was made for another package than {}", _0)]
+    WrongPackage(String),
+
+    /// An entry the patch copies which the package doesn't hold.
+    #[fail(display = "The patch copies {}, which the package doesn't hold", _0)]
+    MissingEntry(String),
+
+    /// A patched package which doesn't match its digest.
+    #[fail(display = "The patched package doesn't match the package the patch was made from")]
+    WrongResult,
+}
+
+/// What a patch holds, or what applying it did.
+#[derive(Debug, Default)]
+pub struct PatchSummary {
+    /// Entries copied from the old package.
+    pub copied: usize,
+    /// Entries whose data the patch carries.
+    pub carried: usize,
+    /// Entries of the old package which the new one doesn't hold.
+    pub removed: usize,
+    /// The size of the patch.
+    pub patch_bytes: u64,
+}
+
+/// The zip record of an entry, as it is written to patches and digested.
+/// Only where its data happens to be is left out.
+fn write_record(out: &mut Vec<u8>, entry: &RawEntry) -> std::io::Result<()> {
+    for field in [
+        entry.version_made_by,
+        entry.version_needed,
+        entry.flags,
+        entry.compression_method,
+        entry.last_modified_time,
+        entry.last_modified_date,
+        entry.internal_attributes,
+    ] {
+        out.write_u16::<LittleEndian>(field)?;
+    }
+    out.write_u32::<LittleEndian>(entry.crc32)?;
+    out.write_u32::<LittleEndian>(entry.external_attributes)?;
+    out.write_u64::<LittleEndian>(entry.compressed_size)?;
+    out.write_u64::<LittleEndian>(entry.size)?;
+    for bytes in [
+        &entry.name,
+        &entry.extra,
+        &entry.local_extra,
+        &entry.comment,
+    ] {
+        write_bytes(out, bytes)?;
+    }
+    Ok(())
+}
+
+fn read_record(reader: &mut dyn Read) -> std::io::Result<RawEntry> {
+    let mut fields = [0u16; 7];
+    for field in fields.iter_mut() {
+        *field = reader.read_u16::<LittleEndian>()?;
+    }
+    let mut entry = RawEntry::default();
+    entry.version_made_by = fields[0];
+    entry.version_needed = fields[1];
+    entry.flags = fields[2];
+    entry.compression_method = fields[3];
+    entry.last_modified_time = fields[4];
+    entry.last_modified_date = fields[5];
+    entry.internal_attributes = fields[6];
+    entry.crc32 = reader.read_u32::<LittleEndian>()?;
+    entry.external_attributes = reader.read_u32::<LittleEndian>()?;
+    entry.compressed_size = reader.read_u64::<LittleEndian>()?;
+    entry.size = reader.read_u64::<LittleEndian>()?;
+    entry.name = read_bytes(reader)?;
+    entry.extra = read_bytes(reader)?;
+    entry.local_extra = read_bytes(reader)?;
+    entry.comment = read_bytes(reader)?;
+    Ok(entry)
+}
+
+/// Names, comments and extra fields are at most 64 KiB in a zip, so a u16
+/// length is enough.
+fn write_bytes(out: &mut dyn Write, bytes: &[u8]) -> std::io::Result<()> {
+    out.write_u16::<LittleEndian>(bytes.len() as u16)?;
+    out.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut dyn Read) -> std::io::Result<Vec<u8>> {
+    let len = reader.read_u16::<LittleEndian>()? as usize;
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// The digest of a package's directory, which is all a patch needs to know
+/// that it was made for the package.
+fn directory_digest(archive: &RawArchive) -> std::io::Result<[u8; 16]> {
+    let mut digest = Md5::default();
+    let mut record = Vec::new();
+    for entry in &archive.entries {
+        record.clear();
+        write_record(&mut record, entry)?;
+        digest.update(&record);
+    }
+    digest.update(&archive.comment);
+    Ok(digest.finish())
+}
+
+/// Digests the entries of a package from their records and the CRC of
+/// their compressed data, so that the data is covered wherever it came
+/// from.
+#[derive(Default)]
+struct EntriesDigest(Md5);
+
+impl EntriesDigest {
+    fn add(&mut self, entry: &RawEntry, data_crc32: u32) -> std::io::Result<()> {
+        let mut record = Vec::new();
+        write_record(&mut record, entry)?;
+        record.write_u32::<LittleEndian>(data_crc32)?;
+        self.0.update(&record);
+        Ok(())
+    }
+
+    fn finish(mut self, comment: &[u8]) -> [u8; 16] {
+        self.0.update(comment);
+        self.0.finish()
+    }
+}
+
+/// Takes the CRC of what is read through it.
+struct CrcReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> CrcReader<R> {
+    fn new(inner: R) -> CrcReader<R> {
+        CrcReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+
+    fn crc32(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Takes the MD5 of what is written through it.
+struct DigestWriter<W> {
+    inner: W,
+    digest: Md5,
+    written: u64,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.digest.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn data_crc32<R: Read + Seek>(reader: &mut R, entry: &RawEntry) -> std::io::Result<u32> {
+    let mut data = CrcReader::new(raw_data(reader, entry)?);
+    std::io::copy(&mut data, &mut std::io::sink())?;
+    Ok(data.crc32())
+}
+
+/// Where output is written until it is complete, so that a failure never
+/// leaves a partial file at the output path.
+fn partial_path(output_path: &Path) -> PathBuf {
+    let mut name = output_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    output_path.with_file_name(name)
+}
+
+/// Runs `write` on the partial file for `output_path`, and moves it into
+/// place if that succeeds.
+fn write_output<T>(
+    output_path: &Path,
+    write: impl FnOnce(BufWriter<File>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let partial = partial_path(output_path);
+    match write(BufWriter::new(File::create(&partial)?)) {
+        Ok(result) => {
+            std::fs::rename(&partial, output_path)?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Names which only one entry of the package has, by the position of that
+/// entry. Only they can be copied by name.
+fn unique_names(archive: &RawArchive) -> HashMap<&[u8], usize> {
+    let mut positions = HashMap::new();
+    let mut repeated = HashSet::new();
+    for (position, entry) in archive.entries.iter().enumerate() {
+        if positions.insert(entry.name.as_slice(), position).is_some() {
+            repeated.insert(entry.name.as_slice());
+        }
+    }
+    positions.retain(|name, _| !repeated.contains(name));
+    positions
+}
+
+/// Writes a patch which turns the package at `old_path` into the package at
+/// `new_path`. Both are read through once, and the entries which changed
+/// once more.
+pub fn create_patch(
+    old_path: &Path,
+    new_path: &Path,
+    patch_path: &Path,
+) -> Result<PatchSummary, Error> {
+    let mut old_reader = BufReader::new(File::open(old_path)?);
+    let old = RawArchive::read(&mut old_reader)?;
+    let mut new_reader = BufReader::new(File::open(new_path)?);
+    let new = RawArchive::read(&mut new_reader)?;
+
+    let old_names = unique_names(&old);
+    let mut old_crcs = HashMap::new();
+    for (&name, &position) in &old_names {
+        old_crcs.insert(name, data_crc32(&mut old_reader, &old.entries[position])?);
+    }
+    let new_names: HashSet<&[u8]> = new.entries.iter().map(|e| e.name.as_slice()).collect();
+    let removed: Vec<&RawEntry> = old
+        .entries
+        .iter()
+        .filter(|entry| !new_names.contains(entry.name.as_slice()))
+        .collect();
+
+    write_output(patch_path, |writer| {
+        let mut out = DigestWriter {
+            inner: writer,
+            digest: Md5::default(),
+            written: 0,
+        };
+        out.write_all(MAGIC)?;
+        out.write_u8(FORMAT_VERSION)?;
+        out.write_all(&directory_digest(&old)?)?;
+        out.write_u32::<LittleEndian>(removed.len() as u32)?;
+        for entry in &removed {
+            write_bytes(&mut out, &entry.name)?;
+        }
+        write_bytes(&mut out, &new.comment)?;
+
+        let mut summary = PatchSummary {
+            removed: removed.len(),
+            ..PatchSummary::default()
+        };
+        let mut digest = EntriesDigest::default();
+        out.write_u64::<LittleEndian>(new.entries.len() as u64)?;
+        for entry in &new.entries {
+            let crc32 = data_crc32(&mut new_reader, entry)?;
+            digest.add(entry, crc32)?;
+            let unchanged = old_names
+                .get(entry.name.as_slice())
+                .map(|&position| &old.entries[position])
+                .filter(|old_entry| {
+                    old_crcs.get(old_entry.name.as_slice()) == Some(&crc32)
+                        && records_match(old_entry, entry)
+                })
+                .is_some();
+            if unchanged {
+                out.write_u8(COPIED)?;
+                write_bytes(&mut out, &entry.name)?;
+                summary.copied += 1;
+            } else {
+                out.write_u8(CARRIED)?;
+                let mut record = Vec::new();
+                write_record(&mut record, entry)?;
+                out.write_all(&record)?;
+                out.write_u64::<LittleEndian>(entry.compressed_size)?;
+                std::io::copy(&mut raw_data(&mut new_reader, entry)?, &mut out)?;
+                summary.carried += 1;
+            }
+        }
+        out.write_all(&digest.finish(&new.comment))?;
+
+        let checksum = out.digest.finish();
+        let mut writer = out.inner;
+        writer.write_all(&checksum)?;
+        writer.flush()?;
+        summary.patch_bytes = out.written + DIGEST_LEN;
+        Ok(summary)
+    })
+}
+
+fn records_match(a: &RawEntry, b: &RawEntry) -> bool {
+    let (mut a_record, mut b_record) = (Vec::new(), Vec::new());
+    write_record(&mut a_record, a).is_ok()
+        && write_record(&mut b_record, b).is_ok()
+        && a_record == b_record
+}
+
+/// An entry of the patched package, as the patch describes it.
+enum Step {
+    Copy(Vec<u8>),
+    /// The record of the entry, and where its data is in the patch.
+    Carry(RawEntry, u64),
+}
+
+struct Patch {
+    directory_digest: [u8; 16],
+    removed: Vec<Vec<u8>>,
+    comment: Vec<u8>,
+    steps: Vec<Step>,
+    entries_digest: [u8; 16],
+}
+
+fn corrupt(e: std::io::Error) -> Error {
+    Error::from(PatchError::Corrupt(e.to_string()))
+}
+
+/// Checks the patch against its checksum, and reads all of it but the data
+/// it carries.
+fn read_patch<R: Read + Seek>(reader: &mut R, patch_path: &Path) -> Result<Patch, Error> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < MAGIC.len() as u64 + 1 + DIGEST_LEN {
+        return Err(Error::from(PatchError::NotAPatch(
+            patch_path.to_string_lossy().into_owned(),
+        )));
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    let mut magic = vec![0u8; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(Error::from(PatchError::NotAPatch(
+            patch_path.to_string_lossy().into_owned(),
+        )));
+    }
+    let version = reader.read_u8()?;
+    if version != FORMAT_VERSION {
+        return Err(Error::from(PatchError::UnsupportedVersion(
+            version,
+            FORMAT_VERSION,
+        )));
+    }
+
+    let body_len = len - DIGEST_LEN;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut digest = Md5::default();
+    std::io::copy(&mut reader.by_ref().take(body_len), &mut digest)?;
+    let mut checksum = [0u8; 16];
+    reader.read_exact(&mut checksum)?;
+    if digest.finish() != checksum {
+        return Err(Error::from(PatchError::Corrupt(
+            "its checksum doesn't match its contents".to_string(),
+        )));
+    }
+
+    reader.seek(SeekFrom::Start(MAGIC.len() as u64 + 1))?;
+    let mut directory_digest = [0u8; 16];
+    reader.read_exact(&mut directory_digest).map_err(corrupt)?;
+    let mut removed = Vec::new();
+    for _ in 0..reader.read_u32::<LittleEndian>().map_err(corrupt)? {
+        removed.push(read_bytes(reader).map_err(corrupt)?);
+    }
+    let comment = read_bytes(reader).map_err(corrupt)?;
+    let mut steps = Vec::new();
+    for _ in 0..reader.read_u64::<LittleEndian>().map_err(corrupt)? {
+        match reader.read_u8().map_err(corrupt)? {
+            COPIED => steps.push(Step::Copy(read_bytes(reader).map_err(corrupt)?)),
+            CARRIED => {
+                let entry = read_record(reader).map_err(corrupt)?;
+                let data_len = reader.read_u64::<LittleEndian>().map_err(corrupt)?;
+                let data_start = reader.stream_position()?;
+                if data_len != entry.compressed_size || data_start + data_len > body_len {
+                    return Err(Error::from(PatchError::Corrupt(format!(
+                        "the data of {} is cut short",
+                        entry.name_lossy()
+                    ))));
+                }
+                reader.seek(SeekFrom::Current(data_len as i64))?;
+                steps.push(Step::Carry(entry, data_start));
+            }
+            step => {
+                return Err(Error::from(PatchError::Corrupt(format!(
+                    "{} isn't a kind of entry",
+                    step
+                ))))
+            }
+        }
+    }
+    let mut entries_digest = [0u8; 16];
+    reader.read_exact(&mut entries_digest).map_err(corrupt)?;
+    if reader.stream_position()? != body_len {
+        return Err(Error::from(PatchError::Corrupt(
+            "it goes on after its last entry".to_string(),
+        )));
+    }
+    Ok(Patch {
+        directory_digest,
+        removed,
+        comment,
+        steps,
+        entries_digest,
+    })
+}
+
+/// Applies the patch at `patch_path` to the package at `old_path`, writing
+/// the patched package to `output_path`. The patch is checked in full, and
+/// against the package, before anything is written; the patched package is
+/// checked against the digest in the patch before it is moved into place.
+pub fn apply_patch(
+    old_path: &Path,
+    patch_path: &Path,
+    output_path: &Path,
+) -> Result<PatchSummary, Error> {
+    let mut patch_reader = BufReader::new(File::open(patch_path)?);
+    let patch = read_patch(&mut patch_reader, patch_path)?;
+    let mut old_reader = BufReader::new(File::open(old_path)?);
+    let old = RawArchive::read(&mut old_reader)?;
+    if directory_digest(&old)? != patch.directory_digest {
+        return Err(Error::from(PatchError::WrongPackage(
+            old_path.to_string_lossy().into_owned(),
+        )));
+    }
+    let old_names = unique_names(&old);
+    for name in patch
+        .removed
+        .iter()
+        .chain(patch.steps.iter().filter_map(|step| match step {
+            Step::Copy(name) => Some(name),
+            Step::Carry(..) => None,
+        }))
+    {
+        if !old_names.contains_key(name.as_slice()) {
+            return Err(Error::from(PatchError::MissingEntry(
+                String::from_utf8_lossy(name).into_owned(),
+            )));
+        }
+    }
+
+    write_output(output_path, |output| {
+        let mut writer = RawWriter::new(output);
+        let mut digest = EntriesDigest::default();
+        let mut summary = PatchSummary {
+            removed: patch.removed.len(),
+            ..PatchSummary::default()
+        };
+        for step in &patch.steps {
+            let (entry, crc32) = match step {
+                Step::Copy(name) => {
+                    let entry = &old.entries[old_names[name.as_slice()]];
+                    let mut data = CrcReader::new(raw_data(&mut old_reader, entry)?);
+                    writer.write_entry(entry, &mut data)?;
+                    summary.copied += 1;
+                    (entry, data.crc32())
+                }
+                Step::Carry(entry, data_start) => {
+                    patch_reader.seek(SeekFrom::Start(*data_start))?;
+                    let mut data = CrcReader::new((&mut patch_reader).take(entry.compressed_size));
+                    writer.write_entry(entry, &mut data)?;
+                    summary.carried += 1;
+                    (entry, data.crc32())
+                }
+            };
+            digest.add(entry, crc32)?;
+        }
+        writer.finish(&patch.comment)?.flush()?;
+        if digest.finish(&patch.comment) != patch.entries_digest {
+            return Err(Error::from(PatchError::WrongResult));
+        }
+        Ok(summary)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    /// A package of stored entries, each given as its name, contents and
+    /// extra field.
+    fn package(path: &Path, entries: &[(&str, &[u8], &[u8])], comment: &[u8]) {
+        let mut writer = RawWriter::new(Vec::new());
+        for (name, contents, extra) in entries {
+            let mut entry = RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            entry.extra = extra.to_vec();
+            let data = entry.replace_contents(contents).unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        std::fs::write(path, writer.finish(comment).unwrap()).unwrap();
+    }
+
+    /// The record and data of every entry, in order.
+    fn entries(path: &Path) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        let archive = RawArchive::read(&mut reader).unwrap();
+        archive
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut record = Vec::new();
+                write_record(&mut record, entry).unwrap();
+                let mut data = Vec::new();
+                raw_data(&mut reader, entry)
+                    .unwrap()
+                    .read_to_end(&mut data)
+                    .unwrap();
+                (record, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn applying_a_patch_recreates_the_new_package() {
+        let dir = TempDir::new("patch");
+        let (old, new) = (dir.join("old.slpk"), dir.join("new.slpk"));
+        let big = vec![7u8; 64 * 1024];
+        package(
+            &old,
+            &[
+                ("3dSceneLayer.json.gz", b"layer", b""),
+                ("nodes/0/geometries/0.bin.gz", &big, b""),
+                ("nodes/1/textures/0.jpg", b"old texture", b""),
+                ("nodes/2/features/0.json.gz", b"gone", b""),
+                ("metadata.json", b"{}", b""),
+            ],
+            b"week 1",
+        );
+        package(
+            &new,
+            &[
+                ("3dSceneLayer.json.gz", b"layer", b""),
+                ("nodes/1/textures/0.jpg", b"new texture", b""),
+                ("nodes/0/geometries/0.bin.gz", &big, b""),
+                ("nodes/3/geometries/0.bin.gz", b"added", b""),
+                ("metadata.json", b"{}", b"\x0a\x00\x00\x00"),
+            ],
+            b"week 2",
+        );
+
+        let patch = dir.join("update.slpkpatch");
+        let made = create_patch(&old, &new, &patch).unwrap();
+        assert_eq!((made.copied, made.carried, made.removed), (2, 3, 1));
+        assert!(made.patch_bytes < big.len() as u64);
+        let patched = dir.join("patched.slpk");
+        let applied = apply_patch(&old, &patch, &patched).unwrap();
+        assert_eq!((applied.copied, applied.carried), (2, 3));
+        assert_eq!(entries(&patched), entries(&new));
+        let comment = RawArchive::read(&mut File::open(&patched).unwrap())
+            .unwrap()
+            .comment;
+        assert_eq!(comment, b"week 2");
+
+        // A patch for the wrong package, or one which is damaged, fails
+        // before anything is written.
+        let output = dir.join("output.slpk");
+        let wrong = apply_patch(&new, &patch, &output).unwrap_err();
+        assert!(wrong.to_string().contains("another package"), "{}", wrong);
+        let mut damaged = std::fs::read(&patch).unwrap();
+        let middle = damaged.len() / 2;
+        damaged[middle] ^= 1;
+        std::fs::write(&patch, &damaged).unwrap();
+        let corrupt = apply_patch(&old, &patch, &output).unwrap_err();
+        assert!(corrupt.to_string().contains("checksum"), "{}", corrupt);
+        assert!(!output.exists());
+        assert!(!partial_path(&output).exists());
+    }
+}
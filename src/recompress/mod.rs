@@ -0,0 +1,289 @@
+// Shrinks a package by gzipping its `.gz` entries again at another level,
+// for packages exported at a low level, or with pretty-printed JSON inside
+// their gzip members, which can also be minified on the way. Every entry is
+// written in the order of the original, with its name, times, extra fields
+// and comment, and everything which isn't gzipped is copied as it is, so
+// that only the contents of the gzip members change. A member is only
+// replaced when that makes it smaller, and members which can't be read as
+// gzip are copied as they are, as `slpkg repair` is the place to fix those.
+//
+// A hash index records the offsets of the entries, which move as they
+// shrink, so a package which has one gets a new one, written as its last
+// entry once every other entry is where it will stay. The copy is written
+// under a temporary name and renamed into place, and is never the package
+// being read.
+
+use crate::archive::hash_index::{HashIndex, HASH_INDEX_ENTRY};
+use crate::archive::raw::{raw_data, RawArchive, RawEntry, RawWriter};
+use crate::json;
+use failure::Error;
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use flate2::{Compression, GzBuilder};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+/// The operating system a gzip header records when it is unknown.
+const UNKNOWN_OS: u8 = 255;
+
+/// Why a package couldn't be recompressed.
+#[derive(Debug, Fail)]
+pub enum RecompressError {
+    /// The output path is the package itself.
+    #[fail(display = "The recompressed package would replace the package being recompressed")]
+    SameFile,
+
+    /// A compression level above 9.
+    #[fail(display = "Invalid compression level {}, expected 0 to 9", _0)]
+    InvalidCompressionLevel(u32),
+}
+
+/// How the entries of a package are gzipped again.
+#[derive(Clone, Debug)]
+pub struct RecompressOptions {
+    /// The level the gzip members are compressed at, 9 by default.
+    pub compression: Compression,
+    /// Minify the JSON documents of `.json.gz` entries before gzipping them
+    /// again. Documents which don't parse are left as they are.
+    pub minify_json: bool,
+}
+
+impl Default for RecompressOptions {
+    fn default() -> RecompressOptions {
+        RecompressOptions {
+            compression: Compression::best(),
+            minify_json: false,
+        }
+    }
+}
+
+/// What recompressing a package did.
+#[derive(Debug, Default, PartialEq)]
+pub struct RecompressSummary {
+    /// The entries of the package.
+    pub entries: usize,
+    /// Gzip members which were replaced by smaller ones.
+    pub recompressed: usize,
+    /// JSON documents which were minified, of those replaced.
+    pub minified: usize,
+    /// Entries named `.gz` which couldn't be read as gzip, and were copied.
+    pub unreadable: usize,
+    /// Whether the package had a hash index, which was written again.
+    pub hash_index: bool,
+    /// The size of the package.
+    pub bytes_before: u64,
+    /// The size of the recompressed copy.
+    pub bytes_after: u64,
+}
+
+/// What gzipping a member again gave.
+enum Regzipped {
+    /// A smaller member, and whether its JSON document was minified.
+    Smaller(Vec<u8>, bool),
+    NotSmaller,
+    NotGzip,
+}
+
+/// The gzip member `member` of the entry `name` compressed again.
+fn regzip(name: &str, member: &[u8], options: &RecompressOptions) -> std::io::Result<Regzipped> {
+    let mut contents = Vec::new();
+    if MultiGzDecoder::new(member)
+        .read_to_end(&mut contents)
+        .is_err()
+    {
+        return Ok(Regzipped::NotGzip);
+    }
+    let mut minified = false;
+    if options.minify_json && name.ends_with(".json.gz") {
+        if let Ok(document) = json::parse_bytes(&contents) {
+            let compact = document.to_compact_string().into_bytes();
+            if compact.len() < contents.len() {
+                contents = compact;
+                minified = true;
+            }
+        }
+    }
+    let mut encoder = GzBuilder::new()
+        .mtime(0)
+        .operating_system(UNKNOWN_OS)
+        .write(Vec::new(), options.compression);
+    encoder.write_all(&contents)?;
+    let gzipped = encoder.finish()?;
+    if gzipped.len() >= member.len() {
+        return Ok(Regzipped::NotSmaller);
+    }
+    Ok(Regzipped::Smaller(gzipped, minified))
+}
+
+/// Writes a copy of the package `reader` holds, with its gzip members
+/// compressed again.
+pub fn recompress<R: Read + Seek, W: Write>(
+    mut reader: R,
+    output: W,
+    options: &RecompressOptions,
+) -> Result<(RecompressSummary, W), Error> {
+    if options.compression.level() > 9 {
+        return Err(Error::from(RecompressError::InvalidCompressionLevel(
+            options.compression.level(),
+        )));
+    }
+    let raw = RawArchive::read(&mut reader)?;
+    let mut summary = RecompressSummary::default();
+    let mut writer = RawWriter::new(output);
+    let mut index_entry: Option<RawEntry> = None;
+    let mut offsets: Vec<(String, u64)> = Vec::with_capacity(raw.entries.len());
+    for entry in &raw.entries {
+        let name = entry.name_lossy();
+        if name == HASH_INDEX_ENTRY {
+            index_entry = Some(entry.clone());
+            continue;
+        }
+        summary.entries += 1;
+        offsets.push((name.clone(), writer.offset()));
+        let readable = entry.is_stored() || entry.is_deflated();
+        if !name.ends_with(".gz") || entry.size == 0 || !readable {
+            let mut data = raw_data(&mut reader, entry)?;
+            writer.write_entry(entry, &mut data)?;
+            continue;
+        }
+        // The gzip member is what the zip entry holds once decompressed.
+        let mut member = Vec::with_capacity(entry.size as usize);
+        let data = raw_data(&mut reader, entry)?;
+        if entry.is_stored() {
+            BufReader::new(data).read_to_end(&mut member)?;
+        } else {
+            DeflateDecoder::new(data).read_to_end(&mut member)?;
+        }
+        match regzip(&name, &member, options)? {
+            Regzipped::Smaller(gzipped, minified) => {
+                let mut entry = entry.clone();
+                let data = entry.replace_contents(&gzipped)?;
+                writer.write_entry(&entry, &mut data.as_slice())?;
+                summary.recompressed += 1;
+                if minified {
+                    summary.minified += 1;
+                }
+            }
+            regzipped => {
+                if let Regzipped::NotGzip = regzipped {
+                    summary.unreadable += 1;
+                }
+                let mut data = raw_data(&mut reader, entry)?;
+                writer.write_entry(entry, &mut data)?;
+            }
+        }
+    }
+    if let Some(mut entry) = index_entry {
+        let index = HashIndex::of_entries(
+            offsets
+                .iter()
+                .map(|(name, offset)| (name.as_str(), *offset)),
+        );
+        let data = entry.replace_contents(&index.to_bytes())?;
+        writer.write_entry(&entry, &mut data.as_slice())?;
+        summary.hash_index = true;
+    }
+    let output = writer.finish(&raw.comment)?;
+    Ok((summary, output))
+}
+
+/// Writes a recompressed copy of the package at `slpk_file_path` to
+/// `output_path`, giving the sizes of both.
+pub fn recompress_package(
+    slpk_file_path: &Path,
+    output_path: &Path,
+    options: &RecompressOptions,
+) -> Result<RecompressSummary, Error> {
+    if output_path.exists()
+        && std::fs::canonicalize(output_path)? == std::fs::canonicalize(slpk_file_path)?
+    {
+        return Err(Error::from(RecompressError::SameFile));
+    }
+    let reader = BufReader::new(File::open(slpk_file_path)?);
+    let mut partial = output_path.file_name().unwrap_or_default().to_os_string();
+    partial.push(".partial");
+    let partial = output_path.with_file_name(partial);
+    let written = File::create(&partial)
+        .map_err(Error::from)
+        .and_then(|file| recompress(reader, BufWriter::new(file), options))
+        .and_then(|(summary, writer)| {
+            writer.into_inner().map_err(|e| e.into_error())?;
+            Ok(summary)
+        });
+    match written {
+        Ok(mut summary) => {
+            std::fs::rename(&partial, output_path)?;
+            summary.bytes_before = std::fs::metadata(slpk_file_path)?.len();
+            summary.bytes_after = std::fs::metadata(output_path)?.len();
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use flate2::read::GzDecoder;
+    use zip::ZipArchive;
+
+    #[test]
+    fn recompressed_packages_are_smaller_and_still_pass_checks() {
+        let dir = TempDir::new("recompress");
+        let original = dir.join("original.slpk");
+        let pretty = format!(
+            "{{\n  \"id\": \"0\",\n  \"children\": [\n    1,\n    2\n  ]{}\n}}\n",
+            " ".repeat(4000)
+        );
+        SyntheticPackage::standard()
+            .entry(
+                "sublayers/1/nodes/0/3dNodeIndexDocument.json.gz",
+                pretty.as_bytes(),
+            )
+            .with_hash_index()
+            .write_to_file(&original)
+            .unwrap();
+        let recompressed = dir.join("recompressed.slpk");
+        let options = RecompressOptions {
+            minify_json: true,
+            ..RecompressOptions::default()
+        };
+        let summary = recompress_package(&original, &recompressed, &options).unwrap();
+        let same_file = recompress_package(&original, &original, &options);
+        let index = crate::index::check_hash_index(&recompressed)
+            .unwrap()
+            .unwrap();
+        let verified = crate::verify::verify(&recompressed).unwrap();
+        let validated = crate::validate::validate(&recompressed, false).unwrap();
+        let original_validated = crate::validate::validate(&original, false).unwrap();
+        let mut archive = ZipArchive::new(File::open(&recompressed).unwrap()).unwrap();
+        let mut document = String::new();
+        GzDecoder::new(
+            archive
+                .by_name("sublayers/1/nodes/0/3dNodeIndexDocument.json.gz")
+                .unwrap(),
+        )
+        .read_to_string(&mut document)
+        .unwrap();
+        let last = archive
+            .by_index(archive.len() - 1)
+            .unwrap()
+            .name()
+            .to_string();
+
+        assert!(summary.recompressed >= 1);
+        assert!(summary.minified >= 1);
+        assert!(summary.hash_index);
+        assert!(summary.bytes_after < summary.bytes_before);
+        assert!(same_file.is_err());
+        assert!(index.is_consistent());
+        assert!(verified.passed());
+        assert_eq!(validated.passed(), original_validated.passed());
+        assert_eq!(document, r#"{"id":"0","children":[1,2]}"#);
+        assert_eq!(last, HASH_INDEX_ENTRY);
+    }
+}
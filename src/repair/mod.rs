@@ -0,0 +1,407 @@
+// Some tools write packages where the .gz suffix of an entry doesn't match
+// its content: gzipped geometry buffers named plain .bin, or raw JSON named
+// .json.gz. Consumers go by the name, so they serve or unpack garbage. The
+// content is sniffed to find these entries, and a repaired copy of the
+// package makes the names and content agree again. The copy can also be
+// given the `metadata.json` which older exports leave out, as it is inferred
+// from the layer when it is missing.
+
+use crate::archive;
+use crate::archive::hash_index::HASH_INDEX_ENTRY;
+use crate::archive::raw::{RawArchive, RawEntry, RawWriter};
+use crate::i3s;
+use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, Write};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Why a package couldn't be repaired.
+#[derive(Debug, Fail)]
+pub enum RepairError {
+    /// The output path is the package itself.
+    #[fail(display = "The repaired package would replace the package being repaired")]
+    SameFile,
+
+    /// An entry whose new name another entry already has.
+    #[fail(
+        display = "Renaming {} to {} would collide with an existing entry",
+        _0, _1
+    )]
+    NameCollision(String, String),
+
+    /// A zip directory which disagrees with the entries stored.
+    #[fail(
+        display = "The zip directory lists {} entries, but {} were read",
+        _0, _1
+    )]
+    EntryCountMismatch(usize, usize),
+}
+
+/// How the content of an entry disagrees with its name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mismatch {
+    /// The content is a gzip stream, but the name has no .gz suffix.
+    GzipWithoutSuffix,
+    /// The name ends with .gz, but the content isn't a gzip stream.
+    SuffixWithoutGzip,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mismatch::GzipWithoutSuffix => "is gzipped but not named .gz",
+            Mismatch::SuffixWithoutGzip => "is named .gz but not gzipped",
+        })
+    }
+}
+
+/// An entry whose content doesn't match its name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamingProblem {
+    /// The name of the entry.
+    pub name: String,
+    /// How the content disagrees with it.
+    pub mismatch: Mismatch,
+}
+
+/// Whether an entry needs its first bytes compared against its name: empty
+/// entries and folders have no content to go by, and are skipped.
+pub fn has_content_to_check(name: &str, size: u64) -> bool {
+    size != 0 && !name.ends_with('/') && name != HASH_INDEX_ENTRY
+}
+
+/// How the first bytes of an entry, `magic`, disagree with its name, if
+/// they do.
+pub fn naming_mismatch(name: &str, magic: &[u8]) -> Option<Mismatch> {
+    match (name.ends_with(".gz"), archive::is_gzip(magic)) {
+        (false, true) => Some(Mismatch::GzipWithoutSuffix),
+        (true, false) => Some(Mismatch::SuffixWithoutGzip),
+        _ => None,
+    }
+}
+
+/// Compares the first bytes of every entry against what its name implies.
+/// Empty entries and folders have no content to go by, and are skipped.
+pub fn find_naming_problems<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Vec<NamingProblem>, Error> {
+    let mut problems = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        if !has_content_to_check(&name, entry.size()) {
+            continue;
+        }
+        let mut magic = Vec::with_capacity(archive::GZIP_MAGIC.len());
+        entry
+            .take(archive::GZIP_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if let Some(mismatch) = naming_mismatch(&name, &magic) {
+            problems.push(NamingProblem { name, mismatch });
+        }
+    }
+    Ok(problems)
+}
+
+/// The naming problems of a package, and the number of entries it has.
+pub fn naming_problems(slpk_file_path: &Path) -> Result<(Vec<NamingProblem>, usize), Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let problems = find_naming_problems(&mut slpk_archive)?;
+    Ok((problems, slpk_archive.len()))
+}
+
+/// What a repair fixed.
+#[derive(Debug, Default)]
+pub struct RepairSummary {
+    /// Gzipped entries which were given a .gz suffix.
+    pub renamed: usize,
+    /// Entries named .gz whose content was gzipped.
+    pub gzipped: usize,
+    /// Whether the package had a hash index, which is left out of the copy.
+    pub dropped_hash_index: bool,
+    /// Whether `metadata.json` was inferred and added to the copy, as the
+    /// package had none.
+    pub wrote_metadata: bool,
+}
+
+/// Writes a copy of the package with every naming problem fixed. Entries are
+/// copied as they are in the package, compressed data, extra fields and
+/// comments included, so a package with nothing to fix is copied byte for
+/// byte. Gzipped entries are renamed rather than decompressed, so their bytes
+/// aren't touched, though the Unicode path field of a renamed entry is left
+/// out as it holds the old name; entries wrongly named .gz are recompressed.
+/// The hash index records the offsets of entries in the original package,
+/// which no longer hold, so it isn't copied. With `write_metadata`, a package
+/// without `metadata.json` is given the metadata inferred from its layer, as
+/// a stored entry after the others.
+pub fn repair<R: Read + Seek, W: Write>(
+    mut archive: ZipArchive<R>,
+    output: W,
+    write_metadata: bool,
+) -> Result<RepairSummary, Error> {
+    let problems = find_naming_problems(&mut archive)?;
+    let metadata = if write_metadata {
+        let layer = i3s::find_scene_layer(&mut archive)?;
+        let mut metadata = i3s::read_metadata(&mut archive, Some(&layer))?;
+        metadata.count_nodes(&mut archive, &layer);
+        Some(metadata).filter(|metadata| metadata.inferred)
+    } else {
+        None
+    };
+    let names: Vec<String> = (0..archive.len())
+        .map(|i| Ok(archive.by_index(i)?.name().to_string()))
+        .collect::<Result<_, Error>>()?;
+    for problem in &problems {
+        if problem.mismatch == Mismatch::GzipWithoutSuffix {
+            let renamed = format!("{}.gz", problem.name);
+            if names.contains(&renamed) {
+                return Err(Error::from(RepairError::NameCollision(
+                    problem.name.clone(),
+                    renamed,
+                )));
+            }
+        }
+    }
+
+    // Gzipped while the zip library can still decompress the content.
+    let mut gzipped = HashMap::new();
+    for problem in &problems {
+        if problem.mismatch == Mismatch::SuffixWithoutGzip {
+            let mut contents = Vec::new();
+            archive.by_name(&problem.name)?.read_to_end(&mut contents)?;
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&contents)?;
+            gzipped.insert(problem.name.clone(), encoder.finish()?);
+        }
+    }
+
+    let mut reader = archive.into_inner();
+    let raw = RawArchive::read(&mut reader)?;
+    if raw.entries.len() != names.len() {
+        return Err(Error::from(RepairError::EntryCountMismatch(
+            names.len(),
+            raw.entries.len(),
+        )));
+    }
+    let mut summary = RepairSummary::default();
+    let mut writer = RawWriter::new(output);
+    // The zip library lists the entries in the order of the zip directory,
+    // and decodes their names.
+    for (entry, name) in raw.entries.iter().zip(&names) {
+        if name == HASH_INDEX_ENTRY {
+            summary.dropped_hash_index = true;
+            continue;
+        }
+        let mut entry = entry.clone();
+        if let Some(contents) = gzipped.get(name) {
+            let data = entry.replace_contents(contents)?;
+            writer.write_entry(&entry, &mut data.as_slice())?;
+            summary.gzipped += 1;
+            continue;
+        }
+        let mismatch = problems
+            .iter()
+            .find(|p| &p.name == name)
+            .map(|p| p.mismatch);
+        if mismatch == Some(Mismatch::GzipWithoutSuffix) {
+            entry.rename(&format!("{}.gz", name));
+            summary.renamed += 1;
+        }
+        let mut data = archive::raw::raw_data(&mut reader, &entry)?;
+        writer.write_entry(&entry, &mut data)?;
+    }
+    if let Some(metadata) = metadata {
+        let mut entry = RawEntry::default();
+        entry.name = i3s::METADATA_DOCUMENT.as_bytes().to_vec();
+        // Dated as the entries the exporter wrote.
+        if let Some(first) = raw.entries.first() {
+            entry.last_modified_time = first.last_modified_time;
+            entry.last_modified_date = first.last_modified_date;
+        }
+        let contents = metadata.document.to_compact_string();
+        let data = entry.replace_contents(contents.as_bytes())?;
+        writer.write_entry(&entry, &mut data.as_slice())?;
+        summary.wrote_metadata = true;
+    }
+    writer.finish(&raw.comment)?;
+    Ok(summary)
+}
+
+/// Repairs the package at `slpk_file_path` as `repair` does, writing the
+/// copy to `output_path`, which can't be the package itself.
+pub fn repair_package(
+    slpk_file_path: &Path,
+    output_path: &Path,
+    write_metadata: bool,
+) -> Result<RepairSummary, Error> {
+    if output_path.exists()
+        && std::fs::canonicalize(output_path)? == std::fs::canonicalize(slpk_file_path)?
+    {
+        return Err(Error::from(RepairError::SameFile));
+    }
+    let slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    repair(
+        slpk_archive,
+        BufWriter::new(File::create(output_path)?),
+        write_metadata,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn gzip(contents: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn repairs_names_which_disagree_with_content() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let entries: Vec<(&str, Vec<u8>)> = vec![
+            ("3dSceneLayer.json.gz", gzip(b"{}")),
+            ("nodes/0/geometries/0.bin", gzip(b"vertices")),
+            (
+                "nodes/0/3dNodeIndexDocument.json.gz",
+                b"{\"id\":\"0\"}".to_vec(),
+            ),
+            ("metadata.json", b"{}".to_vec()),
+        ];
+        for (name, contents) in &entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(
+            find_naming_problems(&mut archive).unwrap(),
+            vec![
+                NamingProblem {
+                    name: "nodes/0/geometries/0.bin".to_string(),
+                    mismatch: Mismatch::GzipWithoutSuffix,
+                },
+                NamingProblem {
+                    name: "nodes/0/3dNodeIndexDocument.json.gz".to_string(),
+                    mismatch: Mismatch::SuffixWithoutGzip,
+                },
+            ]
+        );
+
+        let mut repaired = Cursor::new(Vec::new());
+        let summary = repair(archive, &mut repaired, false).unwrap();
+        assert_eq!((summary.renamed, summary.gzipped), (1, 1));
+        let mut repaired = ZipArchive::new(repaired).unwrap();
+        assert!(find_naming_problems(&mut repaired).unwrap().is_empty());
+
+        let mut document = String::new();
+        GzDecoder::new(
+            repaired
+                .by_name("nodes/0/3dNodeIndexDocument.json.gz")
+                .unwrap(),
+        )
+        .read_to_string(&mut document)
+        .unwrap();
+        assert_eq!(document, "{\"id\":\"0\"}");
+        assert!(repaired.by_name("nodes/0/geometries/0.bin.gz").is_ok());
+    }
+
+    #[test]
+    fn copies_extra_fields_and_comments() {
+        let unix_owner = [0x75, 0x78, 11, 0, 1, 4, 0xe8, 3, 0, 0, 4, 0xe8, 3, 0, 0];
+        let unicode_path = [0x75, 0x70, 5, 0, 1, 0, 0, 0, 0];
+        let mut writer = RawWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in &[
+            ("3dSceneLayer.json.gz", gzip(b"{}")),
+            ("nodes/0/geometries/0.bin", gzip(b"vertices")),
+        ] {
+            let mut entry = crate::archive::raw::RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            entry.compression_method = 8;
+            entry.extra = [&unix_owner[..], &unicode_path[..]].concat();
+            entry.local_extra = unix_owner.to_vec();
+            entry.comment = format!("{} from the exporter", name).into_bytes();
+            let data = entry.replace_contents(contents).unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        let package = writer.finish(b"exported 2019-01-02").unwrap().into_inner();
+
+        // Renaming the geometry is the only change, so everything else is
+        // copied as it was.
+        let mut repaired = Cursor::new(Vec::new());
+        repair(
+            ZipArchive::new(Cursor::new(package.clone())).unwrap(),
+            &mut repaired,
+            false,
+        )
+        .unwrap();
+        let original = RawArchive::read(&mut Cursor::new(package)).unwrap();
+        let repaired_bytes = repaired.into_inner();
+
+        // With nothing left to fix, the copy is byte for byte the same.
+        let mut copy = Cursor::new(Vec::new());
+        repair(
+            ZipArchive::new(Cursor::new(repaired_bytes.clone())).unwrap(),
+            &mut copy,
+            false,
+        )
+        .unwrap();
+        assert_eq!(copy.into_inner(), repaired_bytes);
+
+        let repaired = RawArchive::read(&mut Cursor::new(repaired_bytes)).unwrap();
+        assert_eq!(repaired.comment, original.comment);
+        assert_eq!(repaired.entries[0], original.entries[0]);
+        let geometry = &repaired.entries[1];
+        assert_eq!(geometry.name, b"nodes/0/geometries/0.bin.gz");
+        assert_eq!(geometry.extra, unix_owner);
+        assert_eq!(geometry.local_extra, unix_owner);
+        assert_eq!(geometry.comment, original.entries[1].comment);
+        assert_eq!(geometry.crc32, original.entries[1].crc32);
+    }
+
+    #[test]
+    fn adds_the_inferred_metadata() {
+        let package = crate::synthetic::SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"store":{"version":"1.6"}}"#)
+            .entry(
+                "nodes/root/3dNodeIndexDocument.json.gz",
+                br#"{"id":"root"}"#,
+            )
+            .to_bytes()
+            .unwrap();
+        let mut repaired = Cursor::new(Vec::new());
+        let summary = repair(
+            ZipArchive::new(Cursor::new(package)).unwrap(),
+            &mut repaired,
+            true,
+        )
+        .unwrap();
+        assert!(summary.wrote_metadata);
+        let mut repaired = ZipArchive::new(Cursor::new(repaired.into_inner())).unwrap();
+        let mut metadata = String::new();
+        repaired
+            .by_name("metadata.json")
+            .unwrap()
+            .read_to_string(&mut metadata)
+            .unwrap();
+        assert_eq!(
+            metadata,
+            r#"{"folderPattern":"basic","ArchiveCompressionType":"STORE","ResourceCompressionType":"GZIP","I3SVersion":"1.6","nodeCount":1}"#
+        );
+
+        // A package which has its metadata keeps it.
+        let mut again = Cursor::new(Vec::new());
+        let summary = repair(repaired, &mut again, true).unwrap();
+        assert!(!summary.wrote_metadata);
+    }
+}
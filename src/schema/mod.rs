@@ -0,0 +1,656 @@
+// Structural checks of the JSON resources of a package against the parts of
+// the I3S schemas which readers rely on: the scene layer document, node
+// pages, node index documents, shared resources and attribute statistics.
+// The schemas are written out here rather than bundled as JSON Schema, as
+// only a small part of JSON Schema is needed: the type of each value, which
+// members an object must have, and the values a few strings may take.
+// Members the schemas don't name are allowed, since the specification grows
+// with every version and exporters add members of their own. Only the first
+// violation in document order is reported, with the JSON pointer of the
+// value at fault.
+
+use crate::json::Value;
+use std::fmt;
+
+/// What a value must be.
+pub enum Schema {
+    Any,
+    Bool,
+    Number,
+    Integer,
+    String,
+    /// A string which is one of these.
+    OneOf(&'static [&'static str]),
+    Array(&'static Schema),
+    /// An object, which may have members other than these.
+    Object(&'static [Member]),
+}
+
+pub struct Member {
+    name: &'static str,
+    required: bool,
+    schema: &'static Schema,
+}
+
+const fn required(name: &'static str, schema: &'static Schema) -> Member {
+    Member {
+        name,
+        required: true,
+        schema,
+    }
+}
+
+const fn optional(name: &'static str, schema: &'static Schema) -> Member {
+    Member {
+        name,
+        required: false,
+        schema,
+    }
+}
+
+static ANY: Schema = Schema::Any;
+static INTEGER: Schema = Schema::Integer;
+static NUMBER: Schema = Schema::Number;
+static STRING: Schema = Schema::String;
+static BOOL: Schema = Schema::Bool;
+static INTEGERS: Schema = Schema::Array(&INTEGER);
+static NUMBERS: Schema = Schema::Array(&NUMBER);
+static STRINGS: Schema = Schema::Array(&STRING);
+static OBJECT: Schema = Schema::Object(&[]);
+
+static LAYER_TYPE: Schema = Schema::OneOf(&[
+    "3DObject",
+    "IntegratedMesh",
+    "Point",
+    "PointCloud",
+    "Building",
+]);
+
+static SPATIAL_REFERENCE: Schema = Schema::Object(&[
+    optional("wkid", &INTEGER),
+    optional("latestWkid", &INTEGER),
+    optional("vcsWkid", &INTEGER),
+    optional("latestVcsWkid", &INTEGER),
+    optional("wkt", &STRING),
+]);
+
+static STORE: Schema = Schema::Object(&[
+    optional("profile", &STRING),
+    optional("version", &STRING),
+    optional("rootNode", &STRING),
+    optional("extent", &NUMBERS),
+    optional("resourcePattern", &STRINGS),
+    optional("normalReferenceFrame", &STRING),
+]);
+
+static NODE_PAGE_DEFINITION: Schema = Schema::Object(&[
+    required("nodesPerPage", &INTEGER),
+    optional("lodSelectionMetricType", &STRING),
+    optional("rootIndex", &INTEGER),
+]);
+
+static GEOMETRY_BUFFER: Schema = Schema::Object(&[
+    optional("offset", &INTEGER),
+    optional("compressedAttributes", &OBJECT),
+]);
+static GEOMETRY_BUFFERS: Schema = Schema::Array(&GEOMETRY_BUFFER);
+static GEOMETRY_DEFINITION: Schema =
+    Schema::Object(&[required("geometryBuffers", &GEOMETRY_BUFFERS)]);
+static GEOMETRY_DEFINITIONS: Schema = Schema::Array(&GEOMETRY_DEFINITION);
+
+static MATERIAL_DEFINITIONS: Schema = Schema::Array(&OBJECT);
+
+static TEXTURE_FORMAT: Schema =
+    Schema::Object(&[required("name", &STRING), required("format", &STRING)]);
+static TEXTURE_FORMATS: Schema = Schema::Array(&TEXTURE_FORMAT);
+static TEXTURE_SET_DEFINITION: Schema = Schema::Object(&[
+    required("formats", &TEXTURE_FORMATS),
+    optional("atlas", &BOOL),
+]);
+static TEXTURE_SET_DEFINITIONS: Schema = Schema::Array(&TEXTURE_SET_DEFINITION);
+
+static HEADER_PROPERTY: Schema = Schema::Object(&[
+    required("property", &STRING),
+    required("valueType", &STRING),
+]);
+static HEADER_PROPERTIES: Schema = Schema::Array(&HEADER_PROPERTY);
+static ATTRIBUTE_STORAGE: Schema = Schema::Object(&[
+    required("key", &STRING),
+    required("name", &STRING),
+    optional("header", &HEADER_PROPERTIES),
+    optional("ordering", &STRINGS),
+]);
+static ATTRIBUTE_STORAGE_INFO: Schema = Schema::Array(&ATTRIBUTE_STORAGE);
+
+static FIELD: Schema = Schema::Object(&[
+    required("name", &STRING),
+    required("type", &STRING),
+    optional("alias", &STRING),
+]);
+static FIELDS: Schema = Schema::Array(&FIELD);
+
+static HEIGHT_MODEL_INFO: Schema = Schema::Object(&[
+    optional("heightModel", &STRING),
+    optional("vertCRS", &STRING),
+    optional("heightUnit", &STRING),
+]);
+
+/// The document of a layer other than a building scene layer.
+pub static SCENE_LAYER: Schema = Schema::Object(&[
+    required("id", &INTEGER),
+    required("layerType", &LAYER_TYPE),
+    optional("name", &STRING),
+    optional("href", &STRING),
+    optional("version", &STRING),
+    optional("spatialReference", &SPATIAL_REFERENCE),
+    optional("heightModelInfo", &HEIGHT_MODEL_INFO),
+    optional("store", &STORE),
+    optional("nodePages", &NODE_PAGE_DEFINITION),
+    optional("geometryDefinitions", &GEOMETRY_DEFINITIONS),
+    optional("materialDefinitions", &MATERIAL_DEFINITIONS),
+    optional("textureSetDefinitions", &TEXTURE_SET_DEFINITIONS),
+    optional("attributeStorageInfo", &ATTRIBUTE_STORAGE_INFO),
+    optional("fields", &FIELDS),
+]);
+
+static SUBLAYER: Schema = Schema::Object(&[
+    required("id", &INTEGER),
+    required("layerType", &STRING),
+    optional("name", &STRING),
+    optional("sublayers", &SUBLAYERS),
+]);
+static SUBLAYERS: Schema = Schema::Array(&SUBLAYER);
+
+/// The document of a building scene layer, which groups sublayers.
+pub static BUILDING_SCENE_LAYER: Schema = Schema::Object(&[
+    optional("id", &INTEGER),
+    required("layerType", &LAYER_TYPE),
+    optional("name", &STRING),
+    optional("spatialReference", &SPATIAL_REFERENCE),
+    optional("sublayers", &SUBLAYERS),
+]);
+
+static OBB: Schema = Schema::Object(&[
+    required("center", &NUMBERS),
+    required("halfSize", &NUMBERS),
+    required("quaternion", &NUMBERS),
+]);
+
+static MESH_RESOURCE: Schema = Schema::Object(&[
+    optional("definition", &INTEGER),
+    optional("resource", &INTEGER),
+    optional("vertexCount", &INTEGER),
+    optional("featureCount", &INTEGER),
+    optional("texelCountHint", &INTEGER),
+]);
+static MESH: Schema = Schema::Object(&[
+    optional("material", &MESH_RESOURCE),
+    optional("geometry", &MESH_RESOURCE),
+    optional("attribute", &MESH_RESOURCE),
+]);
+static NODE: Schema = Schema::Object(&[
+    required("index", &INTEGER),
+    optional("lodThreshold", &NUMBER),
+    optional("obb", &OBB),
+    optional("parentIndex", &INTEGER),
+    optional("children", &INTEGERS),
+    optional("mesh", &MESH),
+]);
+static NODES: Schema = Schema::Array(&NODE);
+
+/// A page of nodes, `nodepages/<n>.json`.
+pub static NODE_PAGE: Schema = Schema::Object(&[required("nodes", &NODES)]);
+
+static POINT_CLOUD_NODE: Schema = Schema::Object(&[
+    required("resourceId", &INTEGER),
+    optional("firstChild", &INTEGER),
+    optional("childCount", &INTEGER),
+    optional("vertexCount", &INTEGER),
+    optional("lodThreshold", &NUMBER),
+    optional("obb", &OBB),
+]);
+static POINT_CLOUD_NODES: Schema = Schema::Array(&POINT_CLOUD_NODE);
+
+/// A page of the nodes of a point cloud, which refer to their resources
+/// and children differently.
+pub static POINT_CLOUD_NODE_PAGE: Schema = Schema::Object(&[required("nodes", &POINT_CLOUD_NODES)]);
+
+static NODE_REFERENCE: Schema = Schema::Object(&[
+    required("id", &STRING),
+    optional("href", &STRING),
+    optional("mbs", &NUMBERS),
+    optional("obb", &OBB),
+]);
+static NODE_REFERENCES: Schema = Schema::Array(&NODE_REFERENCE);
+static RESOURCE_REFERENCE: Schema = Schema::Object(&[optional("href", &STRING)]);
+static RESOURCE_REFERENCES: Schema = Schema::Array(&RESOURCE_REFERENCE);
+static LOD_SELECTION: Schema = Schema::Object(&[
+    required("metricType", &STRING),
+    optional("maxError", &NUMBER),
+]);
+static LOD_SELECTIONS: Schema = Schema::Array(&LOD_SELECTION);
+
+/// The document of a node in the layout before node pages,
+/// `nodes/<id>/3dNodeIndexDocument.json`.
+pub static NODE_INDEX_DOCUMENT: Schema = Schema::Object(&[
+    required("id", &STRING),
+    optional("level", &INTEGER),
+    optional("version", &STRING),
+    optional("mbs", &NUMBERS),
+    optional("obb", &OBB),
+    optional("parentNode", &NODE_REFERENCE),
+    optional("children", &NODE_REFERENCES),
+    optional("neighbors", &NODE_REFERENCES),
+    optional("sharedResource", &RESOURCE_REFERENCE),
+    optional("featureData", &RESOURCE_REFERENCES),
+    optional("geometryData", &RESOURCE_REFERENCES),
+    optional("textureData", &RESOURCE_REFERENCES),
+    optional("attributeData", &RESOURCE_REFERENCES),
+    optional("lodSelection", &LOD_SELECTIONS),
+]);
+
+/// The materials and textures of a node, `nodes/<id>/shared/sharedResource.json`.
+pub static SHARED_RESOURCE: Schema = Schema::Object(&[
+    optional("materialDefinitions", &OBJECT),
+    optional("textureDefinitions", &OBJECT),
+]);
+
+static FREQUENT_VALUE: Schema =
+    Schema::Object(&[required("value", &ANY), required("count", &INTEGER)]);
+static FREQUENT_VALUES: Schema = Schema::Array(&FREQUENT_VALUE);
+static HISTOGRAM: Schema = Schema::Object(&[
+    optional("minimum", &NUMBER),
+    optional("maximum", &NUMBER),
+    optional("counts", &INTEGERS),
+]);
+static STATS: Schema = Schema::Object(&[
+    optional("min", &NUMBER),
+    optional("max", &NUMBER),
+    optional("avg", &NUMBER),
+    optional("stddev", &NUMBER),
+    optional("count", &NUMBER),
+    optional("sum", &NUMBER),
+    optional("totalValuesCount", &INTEGER),
+    optional("mostFrequentValues", &FREQUENT_VALUES),
+    optional("histogram", &HISTOGRAM),
+]);
+
+/// The statistics of an attribute, `statistics/<key>/0.json`.
+pub static STATISTICS: Schema = Schema::Object(&[required("stats", &STATS)]);
+
+/// The first value of a document which doesn't match its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The JSON pointer of the value, such as `/nodes/3/index`, or the empty
+    /// pointer for the document itself.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pointer.is_empty() {
+            write!(f, "{} at the root", self.message)
+        } else {
+            write!(f, "{} at {}", self.message, self.pointer)
+        }
+    }
+}
+
+/// A pointer with one more reference token, escaped as RFC 6901 asks.
+fn child_pointer(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl Schema {
+    /// The first violation of the schema in `value`, depth first.
+    pub fn check(&self, value: &Value) -> Option<Violation> {
+        self.check_at(value, "")
+    }
+
+    fn check_at(&self, value: &Value, pointer: &str) -> Option<Violation> {
+        let violation = |message: String| {
+            Some(Violation {
+                pointer: pointer.to_string(),
+                message,
+            })
+        };
+        let expected =
+            |what: &str| violation(format!("expected {}, found {}", what, type_name(value)));
+        match (self, value) {
+            (Schema::Any, _)
+            | (Schema::Bool, Value::Bool(_))
+            | (Schema::Number, Value::Number(_))
+            | (Schema::String, Value::String(_)) => None,
+            (Schema::Integer, Value::Number(n)) if n.as_f64().fract() == 0.0 => None,
+            (Schema::Integer, Value::Number(n)) => {
+                violation(format!("expected an integer, found {}", n))
+            }
+            (Schema::OneOf(allowed), Value::String(s)) if allowed.contains(&s.as_str()) => None,
+            (Schema::OneOf(allowed), Value::String(s)) => violation(format!(
+                "expected one of {}, found '{}'",
+                allowed.join(", "),
+                s
+            )),
+            (Schema::Array(items), Value::Array(values)) => {
+                values.iter().enumerate().find_map(|(i, item)| {
+                    items.check_at(item, &child_pointer(pointer, &i.to_string()))
+                })
+            }
+            (Schema::Object(members), Value::Object(_)) => {
+                members
+                    .iter()
+                    .find_map(|member| match value.get(member.name) {
+                        Some(child) => member
+                            .schema
+                            .check_at(child, &child_pointer(pointer, member.name)),
+                        None if member.required => {
+                            violation(format!("missing the required member '{}'", member.name))
+                        }
+                        None => None,
+                    })
+            }
+            (Schema::Bool, _) => expected("a boolean"),
+            (Schema::Number, _) | (Schema::Integer, _) => expected("a number"),
+            (Schema::String, _) | (Schema::OneOf(_), _) => expected("a string"),
+            (Schema::Array(_), _) => expected("an array"),
+            (Schema::Object(_), _) => expected("an object"),
+        }
+    }
+}
+
+/// The schema of the resource an entry holds, by its name with or without
+/// `.gz`, or `None` when it isn't one of the documents checked.
+pub fn schema_of_entry(entry_name: &str) -> Option<&'static Schema> {
+    let name = entry_name.strip_suffix(".gz").unwrap_or(entry_name);
+    let mut parts = name.rsplit('/');
+    let file_name = parts.next()?;
+    let parent = parts.next();
+    let grandparent = parts.next();
+    Some(match (file_name, parent, grandparent) {
+        ("3dSceneLayer.json", _, _) => &SCENE_LAYER,
+        ("3dNodeIndexDocument.json", _, _) => &NODE_INDEX_DOCUMENT,
+        ("sharedResource.json", Some("shared"), _) => &SHARED_RESOURCE,
+        ("0.json", Some(_), Some("statistics")) => &STATISTICS,
+        (page, Some("nodepages"), _)
+            if page
+                .strip_suffix(".json")
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())) =>
+        {
+            &NODE_PAGE
+        }
+        _ => return None,
+    })
+}
+
+/// Checks a document against the schema of its entry. A scene layer
+/// document of a building scene layer is checked against the schema of
+/// those, as is a node page whose first node has a `resourceId`, as the
+/// nodes of point clouds do, and a document which doesn't parse is a
+/// violation of its own.
+pub fn check_entry(entry_name: &str, document: &[u8]) -> Option<Violation> {
+    schema_of_entry(entry_name)?;
+    match crate::json::parse_bytes(document) {
+        Ok(value) => check_value(entry_name, &value),
+        Err(e) => Some(Violation {
+            pointer: String::new(),
+            message: format!("not valid JSON, {}", e),
+        }),
+    }
+}
+
+/// Checks a document which has already been parsed against the schema of
+/// its entry, as `check_entry` does.
+pub fn check_value(entry_name: &str, value: &Value) -> Option<Violation> {
+    let schema = schema_of_entry(entry_name)?;
+    let schema = if std::ptr::eq(schema, &SCENE_LAYER)
+        && value.get("layerType").and_then(Value::as_str) == Some("Building")
+    {
+        &BUILDING_SCENE_LAYER
+    } else if std::ptr::eq(schema, &NODE_PAGE)
+        && value
+            .get("nodes")
+            .and_then(Value::as_array)
+            .and_then(|nodes| nodes.first())
+            .is_some_and(|node| node.get("resourceId").is_some())
+    {
+        &POINT_CLOUD_NODE_PAGE
+    } else {
+        schema
+    };
+    schema.check(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_pointer_of_the_first_violation() {
+        let check = |name: &str, document: &str| check_entry(name, document.as_bytes());
+        assert_eq!(
+            check(
+                "layers/0/3dSceneLayer.json.gz",
+                r#"{"id":0,"layerType":"3DObject","nodePages":{"nodesPerPage":64}}"#
+            ),
+            None
+        );
+        assert_eq!(
+            check(
+                "3dSceneLayer.json.gz",
+                r#"{"id":0,"layerType":"Mesh","nodePages":{}}"#
+            )
+            .unwrap()
+            .to_string(),
+            "expected one of 3DObject, IntegratedMesh, Point, PointCloud, Building, found 'Mesh' at /layerType"
+        );
+        assert_eq!(
+            check(
+                "nodepages/12.json.gz",
+                r#"{"nodes":[{"index":0},{"index":1,"obb":{"center":[0,0,"0"]}}]}"#
+            ),
+            Some(Violation {
+                pointer: "/nodes/1/obb/center/2".to_string(),
+                message: "expected a number, found a string".to_string(),
+            })
+        );
+        assert_eq!(
+            check("nodes/root/3dNodeIndexDocument.json.gz", r#"{"level":1.5}"#)
+                .unwrap()
+                .to_string(),
+            "missing the required member 'id' at the root"
+        );
+        assert_eq!(
+            check(
+                "3dSceneLayer.json.gz",
+                r#"{"layerType":"Building","sublayers":[{"id":1}]}"#
+            )
+            .unwrap()
+            .pointer,
+            "/sublayers/0"
+        );
+        // The nodes of point clouds have no index.
+        assert_eq!(
+            check(
+                "nodepages/0.json.gz",
+                r#"{"nodes":[{"resourceId":0,"firstChild":1,"childCount":2,"vertexCount":10}]}"#
+            ),
+            None
+        );
+        assert!(check("statistics/f_1/0.json", "{").is_some());
+        assert_eq!(check("nodes/0/features/0.json", "[]"), None);
+        assert_eq!(child_pointer("/a", "b/c~d"), "/a/b~1c~0d");
+    }
+
+    fn violation(schema: &Schema, document: &str) -> Option<String> {
+        schema
+            .check(&crate::json::parse(document).unwrap())
+            .map(|violation| violation.to_string())
+    }
+
+    #[test]
+    fn each_kind_of_schema_fails_on_what_it_doesnt_allow() {
+        let fails = |schema: &Schema, document: &str, expected: &str| {
+            assert_eq!(
+                violation(schema, document).as_deref(),
+                Some(expected),
+                "{}",
+                document
+            )
+        };
+        for document in &["null", "true", "1.5", "\"s\"", "[]", "{}"] {
+            assert_eq!(violation(&ANY, document), None);
+        }
+
+        assert_eq!(violation(&BOOL, "false"), None);
+        fails(&BOOL, "0", "expected a boolean, found a number at the root");
+
+        assert_eq!(violation(&NUMBER, "-2.5e3"), None);
+        fails(
+            &NUMBER,
+            "\"1\"",
+            "expected a number, found a string at the root",
+        );
+        fails(&NUMBER, "null", "expected a number, found null at the root");
+
+        assert_eq!(violation(&INTEGER, "3"), None);
+        assert_eq!(violation(&INTEGER, "3.0"), None);
+        fails(
+            &INTEGER,
+            "3.5",
+            "expected an integer, found 3.5 at the root",
+        );
+        fails(
+            &INTEGER,
+            "true",
+            "expected a number, found a boolean at the root",
+        );
+
+        assert_eq!(violation(&STRING, "\"\""), None);
+        fails(
+            &STRING,
+            "[]",
+            "expected a string, found an array at the root",
+        );
+
+        assert_eq!(violation(&LAYER_TYPE, "\"Point\""), None);
+        fails(
+            &LAYER_TYPE,
+            "\"point\"",
+            "expected one of 3DObject, IntegratedMesh, Point, PointCloud, Building, found 'point' at the root",
+        );
+        fails(
+            &LAYER_TYPE,
+            "1",
+            "expected a string, found a number at the root",
+        );
+
+        assert_eq!(violation(&NUMBERS, "[]"), None);
+        fails(
+            &NUMBERS,
+            "{}",
+            "expected an array, found an object at the root",
+        );
+        fails(
+            &NUMBERS,
+            "[1, 2, false]",
+            "expected a number, found a boolean at /2",
+        );
+
+        assert_eq!(
+            violation(&TEXTURE_FORMAT, r#"{"name":"0","format":"jpg","size":1}"#),
+            None
+        );
+        fails(
+            &TEXTURE_FORMAT,
+            "[]",
+            "expected an object, found an array at the root",
+        );
+        fails(
+            &TEXTURE_FORMAT,
+            r#"{"name":"0"}"#,
+            "missing the required member 'format' at the root",
+        );
+        fails(
+            &TEXTURE_FORMAT,
+            r#"{"name":0,"format":"jpg"}"#,
+            "expected a string, found a number at /name",
+        );
+        // Optional members may be left out, but not be of the wrong type.
+        assert_eq!(
+            violation(&TEXTURE_SET_DEFINITION, r#"{"formats":[]}"#),
+            None
+        );
+        fails(
+            &TEXTURE_SET_DEFINITION,
+            r#"{"formats":[],"atlas":"yes"}"#,
+            "expected a boolean, found a string at /atlas",
+        );
+    }
+
+    #[test]
+    fn each_kind_of_document_fails_on_its_own_schema() {
+        for (name, document, expected) in &[
+            (
+                "3dSceneLayer.json",
+                r#"{"id":"0","layerType":"3DObject"}"#,
+                "expected a number, found a string at /id",
+            ),
+            (
+                "3dSceneLayer.json",
+                r#"{"id":0,"layerType":"3DObject","nodePages":{"nodesPerPage":64},
+                    "geometryDefinitions":[{"geometryBuffers":[{"offset":8.5}]}]}"#,
+                "expected an integer, found 8.5 at /geometryDefinitions/0/geometryBuffers/0/offset",
+            ),
+            (
+                "3dSceneLayer.json",
+                r#"{"layerType":"Building","sublayers":[{"id":1,"layerType":"group",
+                    "sublayers":[{"id":2,"layerType":3}]}]}"#,
+                "expected a string, found a number at /sublayers/0/sublayers/0/layerType",
+            ),
+            (
+                "nodepages/3.json",
+                r#"{"nodes":[{"index":0,"children":[1,"2"]}]}"#,
+                "expected a number, found a string at /nodes/0/children/1",
+            ),
+            (
+                "nodepages/0.json",
+                r#"{"nodes":[{"resourceId":0},{"firstChild":1}]}"#,
+                "missing the required member 'resourceId' at /nodes/1",
+            ),
+            (
+                "nodes/4/3dNodeIndexDocument.json",
+                r#"{"id":"4","children":[{"id":"5","mbs":"0,0,0,1"}]}"#,
+                "expected an array, found a string at /children/0/mbs",
+            ),
+            (
+                "nodes/4/shared/sharedResource.json",
+                r#"{"materialDefinitions":[]}"#,
+                "expected an object, found an array at /materialDefinitions",
+            ),
+            (
+                "statistics/f_0/0.json",
+                r#"{"stats":{"mostFrequentValues":[{"value":"a"}]}}"#,
+                "missing the required member 'count' at /stats/mostFrequentValues/0",
+            ),
+        ] {
+            let violation = check_entry(name, document.as_bytes()).map(|v| v.to_string());
+            assert_eq!(violation.as_deref(), Some(*expected), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// Checks that packages can be unpacked in the current environment. Problems
+// reported by users are often caused by the machine rather than the package:
+// antivirus software locking files, missing permissions, or path length
+// limits. Running the same unpacks on a known good synthetic package tells
+// the two apart.
+
+use crate::glob;
+use crate::synthetic::SyntheticPackage;
+use crate::unpack;
+use crate::unpack::UnpackOptions;
+use failure::Error;
+use std::path::{Path, PathBuf};
+
+/// How a check went, with why it failed or was skipped.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The check passed.
+    Pass,
+    /// The check failed, for this reason.
+    Fail(String),
+    /// The check couldn't be run here, for this reason.
+    Skip(String),
+}
+
+/// The outcome of each check, by name, in the order they ran.
+#[derive(Debug)]
+pub struct SelfTestReport {
+    /// Each check, by name, and how it went.
+    pub results: Vec<(&'static str, Outcome)>,
+    /// The scratch folder the checks ran in, which is removed afterwards.
+    pub work_dir: PathBuf,
+    /// Why the scratch folder couldn't be removed, if it couldn't.
+    pub cleanup_error: Option<std::io::Error>,
+}
+
+impl SelfTestReport {
+    /// The number of checks which failed.
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Fail(_)))
+            .count()
+    }
+}
+
+fn verify_unpacked(folder: &Path, expected: &[(PathBuf, Vec<u8>)]) -> Result<Outcome, Error> {
+    let mut files = Vec::new();
+    glob::walk(folder, None, &mut files)?;
+    let mut actual: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|path| path.strip_prefix(folder).ok().map(Path::to_path_buf))
+        .filter(|path| !unpack::is_bookkeeping_file(&path.to_string_lossy()))
+        .collect();
+    actual.sort();
+
+    for (path, _) in expected {
+        if actual.binary_search(path).is_err() {
+            return Ok(Outcome::Fail(format!(
+                "{} is missing",
+                path.to_string_lossy()
+            )));
+        }
+    }
+    if actual.len() != expected.len() {
+        let unexpected = actual
+            .iter()
+            .find(|path| !expected.iter().any(|(p, _)| p == *path))
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        return Ok(Outcome::Fail(format!("unexpected file {}", unexpected)));
+    }
+    for (path, contents) in expected {
+        if &std::fs::read(folder.join(path))? != contents {
+            return Ok(Outcome::Fail(format!(
+                "{} has the wrong contents",
+                path.to_string_lossy()
+            )));
+        }
+    }
+    Ok(Outcome::Pass)
+}
+
+/// Unpacks a fresh copy of the package and compares the output against it.
+fn round_trip(
+    work_dir: &Path,
+    name: &str,
+    split_sublayers: bool,
+    threads: Option<usize>,
+) -> Result<Outcome, Error> {
+    let package = SyntheticPackage::standard();
+    let package_path = work_dir.join(format!("{}.slpk", name));
+    package.write_to_file(&package_path)?;
+    let mut options = UnpackOptions::new().split_sublayers(split_sublayers);
+    options.run.threads = threads;
+    unpack::unpack_with_options(&package_path, &options)?;
+    verify_unpacked(
+        &work_dir.join(name),
+        &package.expected_files(split_sublayers),
+    )
+}
+
+fn replaces_existing_folder(work_dir: &Path) -> Result<Outcome, Error> {
+    let package = SyntheticPackage::standard();
+    let package_path = work_dir.join("existing.slpk");
+    package.write_to_file(&package_path)?;
+    unpack::unpack_with_options(&package_path, &UnpackOptions::default())?;
+    std::fs::write(work_dir.join("existing").join("stale.txt"), b"stale")?;
+    unpack::unpack_with_options(&package_path, &UnpackOptions::default())?;
+    verify_unpacked(&work_dir.join("existing"), &package.expected_files(false))
+}
+
+fn refuses_to_replace_a_file(work_dir: &Path) -> Result<Outcome, Error> {
+    let package_path = work_dir.join("blocked.slpk");
+    SyntheticPackage::standard().write_to_file(&package_path)?;
+    let blocking_file = work_dir.join("blocked");
+    std::fs::write(&blocking_file, b"keep me")?;
+
+    if unpack::unpack_with_options(&package_path, &UnpackOptions::default()).is_ok() {
+        return Ok(Outcome::Fail("the unpack succeeded".to_string()));
+    }
+    if std::fs::read(&blocking_file)? != b"keep me" {
+        return Ok(Outcome::Fail("the existing file was modified".to_string()));
+    }
+    Ok(Outcome::Pass)
+}
+
+#[cfg(unix)]
+fn fails_cleanly_when_read_only(work_dir: &Path) -> Result<Outcome, Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let read_only_dir = work_dir.join("read-only");
+    std::fs::create_dir(&read_only_dir)?;
+    let package_path = read_only_dir.join("package.slpk");
+    SyntheticPackage::standard().write_to_file(&package_path)?;
+    std::fs::set_permissions(&read_only_dir, std::fs::Permissions::from_mode(0o555))?;
+
+    // Administrators can write to read-only folders, so check whether the
+    // permissions actually took effect before blaming the unpack.
+    let probe = read_only_dir.join("probe");
+    let outcome = if std::fs::write(&probe, b"").is_ok() {
+        std::fs::remove_file(&probe)?;
+        Outcome::Skip("read-only folders are writable by this user".to_string())
+    } else if unpack::unpack_with_options(&package_path, &UnpackOptions::default()).is_ok() {
+        Outcome::Fail("the unpack succeeded".to_string())
+    } else {
+        Outcome::Pass
+    };
+
+    std::fs::set_permissions(&read_only_dir, std::fs::Permissions::from_mode(0o755))?;
+    Ok(outcome)
+}
+
+#[cfg(not(unix))]
+fn fails_cleanly_when_read_only(_: &Path) -> Result<Outcome, Error> {
+    Ok(Outcome::Skip(
+        "folder permissions can't be restricted on this platform".to_string(),
+    ))
+}
+
+type Check = Box<dyn Fn(&Path) -> Result<Outcome, Error>>;
+
+fn run_checks(work_dir: &Path) -> Vec<(&'static str, Outcome)> {
+    let checks: Vec<(&'static str, Check)> = vec![
+        (
+            "unpack with one thread",
+            Box::new(|dir| round_trip(dir, "single-thread", false, Some(1))),
+        ),
+        (
+            "unpack with all cores",
+            Box::new(|dir| round_trip(dir, "all-cores", false, None)),
+        ),
+        (
+            "unpack split into sublayers",
+            Box::new(|dir| round_trip(dir, "split", true, None)),
+        ),
+        (
+            "replace an existing output folder",
+            Box::new(replaces_existing_folder),
+        ),
+        (
+            "refuse to replace a file with the output folder",
+            Box::new(refuses_to_replace_a_file),
+        ),
+        (
+            "fail cleanly in a read-only folder",
+            Box::new(fails_cleanly_when_read_only),
+        ),
+    ];
+
+    checks
+        .into_iter()
+        .map(|(name, check)| {
+            let outcome = check(work_dir).unwrap_or_else(|e| Outcome::Fail(e.to_string()));
+            (name, outcome)
+        })
+        .collect()
+}
+
+/// Runs every check in a scratch folder inside `dir`, or the system temp
+/// folder.
+pub fn self_test(dir: Option<&Path>) -> Result<SelfTestReport, Error> {
+    let work_dir = dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("slpkg-self-test-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir)?;
+
+    let results = run_checks(&work_dir);
+    let cleanup_error = std::fs::remove_dir_all(&work_dir).err();
+    Ok(SelfTestReport {
+        results,
+        work_dir,
+        cleanup_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn all_checks_pass() {
+        let work_dir = TempDir::new("self-test-checks");
+        let results = run_checks(&work_dir);
+
+        for (name, outcome) in results {
+            assert!(
+                !matches!(outcome, Outcome::Fail(_)),
+                "{}: {:?}",
+                name,
+                outcome
+            );
+        }
+    }
+
+    #[test]
+    fn the_scratch_folder_is_removed_afterwards() {
+        let dir = TempDir::new("self-test");
+        let report = self_test(Some(&dir)).unwrap();
+
+        assert_eq!(report.failures(), 0);
+        assert!(report.cleanup_error.is_none());
+        assert!(!report.work_dir.exists());
+    }
+
+    #[test]
+    fn corrupted_output_is_detected() {
+        let folder = TempDir::new("self-test-verify");
+        std::fs::write(folder.join("a.json"), b"{}").unwrap();
+
+        let expected = vec![(PathBuf::from("a.json"), b"[]".to_vec())];
+        let outcome = verify_unpacked(&folder, &expected).unwrap();
+        let missing = verify_unpacked(&folder, &[]).unwrap();
+
+        assert_eq!(
+            outcome,
+            Outcome::Fail("a.json has the wrong contents".to_string())
+        );
+        assert_eq!(missing, Outcome::Fail("unexpected file a.json".to_string()));
+    }
+}
@@ -0,0 +1,917 @@
+// Serves a package over the I3S REST API, as an ArcGIS scene service would,
+// so that a viewer such as the ArcGIS API for JavaScript can show it from
+// localhost without a web server set up to mimic one. Packages are served
+// straight from the archive, with an index from each REST path to the entry
+// behind it built when the server starts, or from the folder a package was
+// unpacked into. Entries are read at the offset the hash index of the
+// package records for them, when it has one. Gzipped entries are sent as they are stored, with
+// `Content-Encoding: gzip`, to clients which accept it, and decompressed for
+// those which don't. Every response allows any origin, as the viewer is
+// usually served from somewhere else.
+//
+// The server speaks just enough HTTP/1.1 to serve files: GET and HEAD, with
+// keep-alive and single byte ranges, and OPTIONS for CORS preflight
+// requests. Each connection gets a thread of its own, up to a limit, and
+// every thread reads the archive through the one handle, which is enough for
+// previewing a layer on the machine it's on. Requests are read within limits
+// on the length of each line, the number of headers and the size of the
+// whole request, so that a client can't make a thread hold on to more than
+// a few kilobytes, or to its connection for longer than the keep-alive.
+
+pub(crate) mod routes;
+
+use crate::archive::IndexedPackage;
+use crate::glob;
+use crate::i3s::{self, SceneLayer};
+use crate::json::{self, Value};
+use failure::Error;
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The path the scene service is served at.
+const SERVICE_PATH: &str = "SceneServer";
+
+/// How long a connection is kept open waiting for its next request, and
+/// how long a read or a write may take.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// The longest request line or header which is read.
+const MAX_LINE: u64 = 8 * 1024;
+
+/// The most headers a request may have.
+const MAX_HEADERS: usize = 100;
+
+/// The longest a request may be, from its request line to the end of its
+/// body.
+const MAX_REQUEST: u64 = 64 * 1024;
+
+/// The most connections answered at once. Further connections are told
+/// to come back later.
+const MAX_CONNECTIONS: usize = 64;
+
+/// Where the entries of a package are read from.
+enum Source {
+    Package(Box<Mutex<IndexedPackage>>),
+    /// A folder a package was unpacked into.
+    Folder(PathBuf),
+}
+
+/// A layer of the package, with the entries behind its REST paths.
+struct ServedLayer {
+    layer: SceneLayer,
+    routes: HashMap<String, usize>,
+}
+
+/// A package, indexed for serving.
+struct ServedPackage {
+    source: Source,
+    /// The names of the entries, or of the files below the folder, with `/`
+    /// between folders.
+    names: Vec<String>,
+    by_name: HashMap<String, usize>,
+    layers: Vec<ServedLayer>,
+    /// The document of the scene service, listing the layers.
+    service: Vec<u8>,
+}
+
+impl ServedPackage {
+    fn open(path: &Path) -> Result<ServedPackage, Error> {
+        let (source, names) = if path.is_dir() {
+            let mut files = Vec::new();
+            glob::walk(path, None, &mut files)?;
+            let names = files
+                .iter()
+                .filter_map(|file| file.strip_prefix(path).ok())
+                .map(|name| {
+                    name.components()
+                        .map(|part| part.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                })
+                .collect();
+            (Source::Folder(path.to_path_buf()), names)
+        } else {
+            let mut package = IndexedPackage::open(path)?;
+            let archive = package.archive()?;
+            let mut names = Vec::with_capacity(archive.len());
+            for i in 0..archive.len() {
+                names.push(archive.by_index(i)?.name().to_string());
+            }
+            (Source::Package(Box::new(Mutex::new(package))), names)
+        };
+        let by_name: HashMap<String, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect();
+        let mut package = ServedPackage {
+            source,
+            names,
+            by_name,
+            layers: Vec::new(),
+            service: Vec::new(),
+        };
+        for root in i3s::layer_roots_of(package.names.iter().map(String::as_str)) {
+            let document_name = format!("{}{}", root, i3s::SCENE_LAYER_DOCUMENT);
+            let index = package
+                .find(&format!("{}.gz", document_name))
+                .or_else(|| package.find(&document_name))
+                .ok_or(i3s::I3sError::MissingSceneLayerDocument)?;
+            let document = json::parse_bytes(&package.decompressed(index)?).map_err(|error| {
+                i3s::I3sError::InvalidDocument {
+                    document: document_name.clone(),
+                    error,
+                }
+            })?;
+            let layer = SceneLayer::from_document(&root, document);
+            let mut routes = HashMap::new();
+            for (i, name) in package.names.iter().enumerate() {
+                let path = name
+                    .strip_prefix(&root)
+                    .filter(|_| layer.contains(name))
+                    .and_then(routes::rest_path);
+                if let Some(path) = path {
+                    routes.entry(path).or_insert(i);
+                }
+            }
+            package.layers.push(ServedLayer { layer, routes });
+        }
+        if package.layers.is_empty() {
+            return Err(Error::from(i3s::I3sError::MissingSceneLayerDocument));
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        package.service = service_document(&name, &package.layers)
+            .to_compact_string()
+            .into_bytes();
+        Ok(package)
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.by_name.get(name).copied()
+    }
+
+    /// The entry as it is stored.
+    fn read(&self, index: usize) -> io::Result<Vec<u8>> {
+        match &self.source {
+            Source::Package(package) => {
+                let mut package = package.lock().unwrap_or_else(|e| e.into_inner());
+                let mut entry = package
+                    .open_entry(&self.names[index])
+                    .map_err(io::Error::other)?
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                Ok(contents)
+            }
+            Source::Folder(folder) => std::fs::read(folder.join(&self.names[index])),
+        }
+    }
+
+    fn is_gzipped(&self, index: usize, contents: &[u8]) -> bool {
+        self.names[index].ends_with(".gz") && crate::archive::is_gzip(contents)
+    }
+
+    fn decompressed(&self, index: usize) -> io::Result<Vec<u8>> {
+        let contents = self.read(index)?;
+        if !self.is_gzipped(index, &contents) {
+            return Ok(contents);
+        }
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&contents[..]).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// The entry behind a path of the scene service, such as
+    /// `layers/0/nodes/4/geometries/0`.
+    fn route(&self, path: &str) -> Option<usize> {
+        let rest = path.strip_prefix("layers/")?;
+        let (id, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        let id: usize = id.parse().ok()?;
+        let served = self.layers.iter().find(|served| served.layer.id == id)?;
+        // Entries can be asked for by name too, with or without `.gz`.
+        let name = served.layer.entry_name(rest);
+        served
+            .routes
+            .get(rest)
+            .copied()
+            .or_else(|| self.find(&format!("{}.gz", name)))
+            .or_else(|| self.find(&name).filter(|_| !rest.is_empty()))
+    }
+}
+
+/// The document of the scene service, as `/SceneServer` gives it, holding
+/// the document of each layer. Layers whose document has no id get the one
+/// they are served at.
+fn service_document(name: &str, layers: &[ServedLayer]) -> Value {
+    let layers = layers
+        .iter()
+        .map(|served| {
+            let mut document = served.layer.document.clone();
+            if let Value::Object(members) = &mut document {
+                if !members.iter().any(|(key, _)| key == "id") {
+                    members.insert(0, ("id".to_string(), Value::from(served.layer.id)));
+                }
+            }
+            document
+        })
+        .collect();
+    Value::Object(vec![
+        ("serviceName".to_string(), Value::from(name)),
+        ("name".to_string(), Value::from(name)),
+        ("serviceVersion".to_string(), Value::from("1.8")),
+        (
+            "supportedBindings".to_string(),
+            Value::Array(vec![Value::from("REST")]),
+        ),
+        ("layers".to_string(), Value::Array(layers)),
+    ])
+}
+
+struct Request {
+    method: String,
+    /// The path, without its query or the leading and trailing `/`.
+    path: String,
+    accepts_gzip: bool,
+    keep_alive: bool,
+    /// The value of the `Range` header, if there is one.
+    range: Option<String>,
+}
+
+fn invalid_request(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a line of a request, up to `MAX_LINE` long and within what is
+/// left of the `MAX_REQUEST` bytes of the request.
+fn read_line(reader: &mut impl BufRead, left: &mut u64) -> io::Result<String> {
+    let limit = MAX_LINE.min(*left);
+    let mut line = String::new();
+    let read = reader.take(limit).read_line(&mut line)? as u64;
+    if read == limit && !line.ends_with('\n') {
+        return Err(invalid_request(if limit < MAX_LINE {
+            format!("the request is longer than {} bytes", MAX_REQUEST)
+        } else {
+            format!("a line of the request is longer than {} bytes", MAX_LINE)
+        }));
+    }
+    *left -= read;
+    Ok(line)
+}
+
+/// Whether an `Accept-Encoding` header accepts gzip: whether it names gzip
+/// or `*` with a quality other than 0, gzip taking precedence.
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    let mut gzip = None;
+    let mut any = None;
+    for coding in accept_encoding.split(',') {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let quality = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim() == "q")
+            .map_or(1.0, |(_, value)| value.trim().parse().unwrap_or(0.0));
+        match name {
+            "gzip" | "x-gzip" => gzip = Some(quality > 0.0),
+            "*" => any = Some(quality > 0.0),
+            _ => {}
+        }
+    }
+    gzip.or(any).unwrap_or(false)
+}
+
+/// Reads the next request of a connection, or `None` once it is closed.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut left = MAX_REQUEST;
+    let line = read_line(reader, &mut left)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => {
+            return Err(invalid_request(format!(
+                "invalid request line: {}",
+                line.trim_end()
+            )))
+        }
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: target
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_matches('/')
+            .to_string(),
+        accepts_gzip: false,
+        keep_alive: version != "HTTP/1.0",
+        range: None,
+    };
+    let mut body_length = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_line(reader, &mut left)?;
+        if header.trim_end().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(invalid_request(format!(
+                "the request has more than {} headers",
+                MAX_HEADERS
+            )));
+        }
+        let (name, value) = match header.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        let value = value.to_ascii_lowercase();
+        match name.as_str() {
+            "accept-encoding" => request.accepts_gzip = accepts_gzip(&value),
+            "connection" if value == "close" => request.keep_alive = false,
+            "connection" if value == "keep-alive" => request.keep_alive = true,
+            "content-length" => body_length = value.parse().unwrap_or(0),
+            "range" => request.range = Some(value),
+            _ => {}
+        }
+    }
+    if body_length > left {
+        return Err(invalid_request(format!(
+            "the request is longer than {} bytes",
+            MAX_REQUEST
+        )));
+    }
+    // Requests with a body aren't served, but the body is skipped so that
+    // the next request can be read.
+    io::copy(&mut reader.take(body_length), &mut io::sink())?;
+    Ok(Some(request))
+}
+
+/// The bytes a `Range` header asks for of a body `len` bytes long, as
+/// `Some(Ok(range))`, or `Some(Err(()))` when none of them are in the body.
+/// Headers which ask for more than one range, or which don't parse, are
+/// ignored, and the whole body is sent.
+fn byte_range(range: &str, len: usize) -> Option<Result<std::ops::Range<usize>, ()>> {
+    let spec = range.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len)
+        }
+        (start, "") => (start.parse().ok()?, len),
+        (start, end) => {
+            let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.saturating_add(1).min(len))
+        }
+    };
+    Some(if start < end { Ok(start..end) } else { Err(()) })
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &str, body: Vec<u8>) -> Response {
+        Response {
+            status: 200,
+            headers: vec![("Content-Type", content_type.to_string())],
+            body,
+        }
+    }
+
+    /// An error, in the form scene services give them.
+    fn error(status: u16, message: &str) -> Response {
+        let body = Value::Object(vec![(
+            "error".to_string(),
+            Value::Object(vec![
+                ("code".to_string(), Value::from(u64::from(status))),
+                ("message".to_string(), Value::from(message)),
+            ]),
+        )]);
+        let mut response = Response::ok(
+            "application/json; charset=utf-8",
+            body.to_compact_string().into_bytes(),
+        );
+        response.status = status;
+        response
+    }
+
+    fn write_to(&self, stream: &mut impl Write, head_only: bool) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            204 => "No Content",
+            206 => "Partial Content",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            416 => "Range Not Satisfiable",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        };
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("Access-Control-Allow-Origin: *\r\n");
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+        stream.write_all(head.as_bytes())?;
+        if !head_only {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()
+    }
+}
+
+fn respond(package: &ServedPackage, request: &Request) -> Response {
+    match request.method.as_str() {
+        "GET" | "HEAD" => {}
+        "OPTIONS" => {
+            return Response {
+                status: 204,
+                headers: vec![
+                    (
+                        "Access-Control-Allow-Methods",
+                        "GET, HEAD, OPTIONS".to_string(),
+                    ),
+                    ("Access-Control-Allow-Headers", "*".to_string()),
+                    ("Access-Control-Max-Age", "86400".to_string()),
+                ],
+                body: Vec::new(),
+            }
+        }
+        _ => {
+            let mut response = Response::error(405, "Only GET and HEAD are supported");
+            response
+                .headers
+                .push(("Allow", "GET, HEAD, OPTIONS".to_string()));
+            return response;
+        }
+    }
+    let path = match request.path.strip_prefix(SERVICE_PATH) {
+        Some("") => {
+            return Response::ok("application/json; charset=utf-8", package.service.clone())
+        }
+        Some(path) if path.starts_with('/') => &path[1..],
+        _ => return Response::error(404, "Not found"),
+    };
+    let index = match package.route(path) {
+        Some(index) => index,
+        None => return Response::error(404, "Not found"),
+    };
+    let contents = match package.read(index) {
+        Ok(contents) => contents,
+        Err(e) => return Response::error(500, &e.to_string()),
+    };
+    let name = &package.names[index];
+    let mut response = Response::ok(routes::content_type(name), Vec::new());
+    if !package.is_gzipped(index, &contents) {
+        response.body = contents;
+    } else if request.accepts_gzip {
+        response
+            .headers
+            .push(("Content-Encoding", "gzip".to_string()));
+        response.body = contents;
+    } else if let Err(e) = GzDecoder::new(&contents[..]).read_to_end(&mut response.body) {
+        return Response::error(500, &e.to_string());
+    }
+    // Ranges are of the body as it is sent, gzipped or not.
+    response
+        .headers
+        .push(("Accept-Ranges", "bytes".to_string()));
+    let len = response.body.len();
+    match request
+        .range
+        .as_deref()
+        .and_then(|range| byte_range(range, len))
+    {
+        None => {}
+        Some(Ok(range)) => {
+            response.status = 206;
+            response.headers.push((
+                "Content-Range",
+                format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+            ));
+            response.body = response.body[range].to_vec();
+        }
+        Some(Err(())) => {
+            let mut unsatisfiable = Response::error(416, "The range isn't in the resource");
+            unsatisfiable
+                .headers
+                .push(("Content-Range", format!("bytes */{}", len)));
+            return unsatisfiable;
+        }
+    }
+    response
+}
+
+fn handle_connection(stream: TcpStream, package: &ServedPackage) -> io::Result<()> {
+    stream.set_read_timeout(Some(KEEP_ALIVE))?;
+    stream.set_write_timeout(Some(KEEP_ALIVE))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                // What is left of the request can't be told from the next
+                // one, so the connection is closed after saying why.
+                Response::error(400, &e.to_string()).write_to(&mut stream, false)?;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        let response = respond(package, &request);
+        debug!("{} /{} {}", request.method, request.path, response.status);
+        response.write_to(&mut stream, request.method == "HEAD")?;
+        if !request.keep_alive {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// A server of one package, listening but not yet answering.
+pub struct Server {
+    listener: TcpListener,
+    package: Arc<ServedPackage>,
+    /// The connections being answered.
+    connections: Arc<AtomicUsize>,
+}
+
+/// Counts a connection as being answered while it is held.
+struct Connection(Arc<AtomicUsize>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    /// Indexes the package at `path`, a `.slpk` file or the folder it was
+    /// unpacked into, and listens on `address`.
+    pub fn bind(path: &Path, address: impl ToSocketAddrs) -> Result<Server, Error> {
+        let package = Arc::new(ServedPackage::open(path)?);
+        Ok(Server {
+            listener: TcpListener::bind(address)?,
+            package,
+            connections: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// The address the server listens on, with the port it was given.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The URL of each layer.
+    pub fn layer_urls(&self) -> io::Result<Vec<String>> {
+        let address = self.local_addr()?;
+        Ok(self
+            .package
+            .layers
+            .iter()
+            .map(|served| {
+                format!(
+                    "http://{}/{}/layers/{}",
+                    address, SERVICE_PATH, served.layer.id
+                )
+            })
+            .collect())
+    }
+
+    /// Answers requests until the process ends.
+    pub fn run(self) -> Result<(), Error> {
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            if self.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                self.connections.fetch_sub(1, Ordering::SeqCst);
+                let mut response = Response::error(503, "Too many connections");
+                response.headers.push(("Retry-After", "1".to_string()));
+                let mut stream = stream;
+                let _ = stream.set_write_timeout(Some(KEEP_ALIVE));
+                let _ = response.write_to(&mut stream, false);
+                continue;
+            }
+            let connection = Connection(Arc::clone(&self.connections));
+            let package = Arc::clone(&self.package);
+            std::thread::spawn(move || {
+                let _connection = connection;
+                if let Err(e) = handle_connection(stream, &package) {
+                    debug!("Connection closed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Serves the package at `path` on `address` until the process ends, after
+/// logging the URL of each layer.
+pub fn serve(path: &Path, address: impl ToSocketAddrs) -> Result<(), Error> {
+    let server = Server::bind(path, address)?;
+    for url in server.layer_urls()? {
+        info!("Serving {}", url);
+    }
+    server.run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    /// Sends a request for `path` and reads the response, as its status
+    /// line, headers and body.
+    fn get(
+        address: SocketAddr,
+        method: &str,
+        path: &str,
+        accept_gzip: bool,
+    ) -> (String, String, Vec<u8>) {
+        let encoding = if accept_gzip {
+            "Accept-Encoding: gzip, deflate\r\n"
+        } else {
+            ""
+        };
+        send(
+            address,
+            &format!(
+                "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+                method, path, encoding
+            ),
+        )
+    }
+
+    /// Sends `request` as it is and reads the response, as `get` does.
+    fn send(address: SocketAddr, request: &str) -> (String, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        let (status, headers) = head.split_once("\r\n").unwrap_or((&head, ""));
+        (
+            status.to_string(),
+            headers.to_string(),
+            response[end + 4..].to_vec(),
+        )
+    }
+
+    #[test]
+    fn serves_rest_paths_from_the_archive_and_the_unpacked_folder() {
+        let dir = TempDir::new("serve");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"layerType":"3DObject"}"#)
+            .entry("nodepages/0.json.gz", br#"{"nodes":[{"index":0}]}"#)
+            .entry("nodes/0/textures/0_0_1.bin.dds", b"DDS ")
+            .write_to_file(&path)
+            .unwrap();
+        let server = Server::bind(&path, "127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let urls = server.layer_urls().unwrap();
+        std::thread::spawn(move || server.run());
+
+        let (status, headers, gzipped) = get(
+            address,
+            "GET",
+            "/SceneServer/layers/0/nodepages/0?f=json",
+            true,
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(headers.contains("Content-Encoding: gzip"));
+        assert!(headers.contains("Access-Control-Allow-Origin: *"));
+        assert!(crate::archive::is_gzip(&gzipped));
+        let (_, headers, plain) = get(address, "GET", "/SceneServer/layers/0/nodepages/0", false);
+        assert!(!headers.contains("Content-Encoding"));
+        assert_eq!(plain, br#"{"nodes":[{"index":0}]}"#);
+        let (_, headers, texture) = get(
+            address,
+            "GET",
+            "/SceneServer/layers/0/nodes/0/textures/0_0_1",
+            true,
+        );
+        assert!(headers.contains("Content-Type: image/vnd-ms.dds"));
+        assert_eq!(texture, b"DDS ");
+        let (_, _, service) = get(address, "GET", "/SceneServer", true);
+        let service = json::parse_bytes(&service).unwrap();
+        let layers = service.get("layers").and_then(Value::as_array).unwrap();
+        assert_eq!(layers[0].get("id").and_then(Value::as_u64), Some(0));
+        let (status, _, _) = get(address, "GET", "/SceneServer/layers/1", true);
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        let (status, headers, _) = get(address, "OPTIONS", "/SceneServer/layers/0", true);
+        assert_eq!(status, "HTTP/1.1 204 No Content");
+        assert!(headers.contains("Access-Control-Allow-Methods"));
+        let (status, _, body) = get(address, "HEAD", "/SceneServer/layers/0", true);
+        assert_eq!((status.as_str(), body.len()), ("HTTP/1.1 200 OK", 0));
+        assert_eq!(urls, [format!("http://{}/SceneServer/layers/0", address)]);
+
+        crate::unpack::unpack_with_options(&path, &crate::unpack::UnpackOptions::new()).unwrap();
+        let folder = Server::bind(&dir.join("package"), "127.0.0.1:0").unwrap();
+        let address = folder.local_addr().unwrap();
+        std::thread::spawn(move || folder.run());
+        let (status, headers, page) =
+            get(address, "GET", "/SceneServer/layers/0/nodepages/0", true);
+
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(!headers.contains("Content-Encoding"));
+        assert_eq!(page, br#"{"nodes":[{"index":0}]}"#);
+    }
+
+    fn requests(bytes: &[u8]) -> Vec<io::Result<Option<Request>>> {
+        let mut reader = io::Cursor::new(bytes);
+        let mut requests = Vec::new();
+        loop {
+            let request = read_request(&mut reader);
+            let done = !matches!(request, Ok(Some(_)));
+            requests.push(request);
+            if done {
+                return requests;
+            }
+        }
+    }
+
+    #[test]
+    fn requests_are_parsed_one_after_another() {
+        let parsed = requests(
+            b"GET /SceneServer/layers/0/?f=json HTTP/1.1\r\nAccept-Encoding: gzip;q=0.5\r\n\
+              Range: bytes=0-3\r\n\r\n\
+              POST /SceneServer HTTP/1.0\r\nContent-Length: 5\r\n\r\nhello\
+              HEAD / HTTP/1.1\r\nConnection: close\r\nNot a header\r\n\r\n",
+        );
+
+        let summary: Vec<_> = parsed
+            .iter()
+            .map(|request| {
+                request.as_ref().unwrap().as_ref().map(|r| {
+                    (
+                        r.method.as_str(),
+                        r.path.as_str(),
+                        r.accepts_gzip,
+                        r.keep_alive,
+                        r.range.as_deref(),
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                Some(("GET", "SceneServer/layers/0", true, true, Some("bytes=0-3"))),
+                Some(("POST", "SceneServer", false, false, None)),
+                Some(("HEAD", "", false, false, None)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn requests_beyond_the_limits_are_refused() {
+        let error = |bytes: Vec<u8>| match requests(&bytes).pop().unwrap() {
+            Err(e) => (e.kind(), e.to_string()),
+            Ok(_) => panic!("the request was read"),
+        };
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "x".repeat(MAX_LINE as usize));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let long_body = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_REQUEST);
+        let long_request = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            format!("A: {}\r\n", "b".repeat(4000)).repeat(20)
+        );
+
+        assert_eq!(
+            error(long_line.into_bytes()),
+            (
+                io::ErrorKind::InvalidData,
+                "a line of the request is longer than 8192 bytes".to_string()
+            )
+        );
+        assert_eq!(
+            error(many_headers.into_bytes()).1,
+            "the request has more than 100 headers"
+        );
+        assert_eq!(
+            error(long_body.into_bytes()).1,
+            "the request is longer than 65536 bytes"
+        );
+        assert_eq!(
+            error(long_request.into_bytes()).1,
+            "the request is longer than 65536 bytes"
+        );
+        assert_eq!(
+            error(b"GET\r\n\r\n".to_vec()).1,
+            "invalid request line: GET"
+        );
+    }
+
+    #[test]
+    fn codings_are_accepted_by_their_quality() {
+        let accepted: Vec<bool> = [
+            "gzip",
+            "deflate, gzip;q=0.8",
+            "gzip;q=0",
+            "gzip; q=0.000",
+            "*",
+            "*;q=0",
+            "gzip;q=0, *",
+            "br, *;q=0.1",
+            "deflate",
+            "gzipped",
+            "",
+        ]
+        .iter()
+        .map(|header| accepts_gzip(header))
+        .collect();
+
+        assert_eq!(
+            accepted,
+            [true, true, false, false, true, false, false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn byte_ranges_are_clamped_to_the_body() {
+        assert_eq!(byte_range("bytes=0-3", 10), Some(Ok(0..4)));
+        assert_eq!(byte_range("bytes=4-", 10), Some(Ok(4..10)));
+        assert_eq!(byte_range("bytes=-3", 10), Some(Ok(7..10)));
+        assert_eq!(byte_range("bytes=5-100", 10), Some(Ok(5..10)));
+        assert_eq!(byte_range("bytes=10-", 10), Some(Err(())));
+        assert_eq!(byte_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(byte_range("bytes=3-1", 10), None);
+        assert_eq!(byte_range("items=0-1", 10), None);
+    }
+
+    #[test]
+    fn ranges_and_encodings_are_of_the_body_as_sent() {
+        let dir = TempDir::new("serve-ranges");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"layerType":"3DObject"}"#)
+            .entry("nodepages/0.json.gz", br#"{"nodes":[{"index":0}]}"#)
+            .write_to_file(&path)
+            .unwrap();
+        let server = Server::bind(&path, "127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+        let request = |headers: &str| {
+            send(
+                address,
+                &format!(
+                    "GET /SceneServer/layers/0/nodepages/0 HTTP/1.1\r\n{}Connection: close\r\n\r\n",
+                    headers
+                ),
+            )
+        };
+
+        let (status, headers, body) = request("Accept-Encoding: gzip;q=0\r\nRange: bytes=2-6\r\n");
+        assert_eq!(status, "HTTP/1.1 206 Partial Content");
+        assert!(!headers.contains("Content-Encoding"));
+        assert!(headers.contains("Content-Range: bytes 2-6/23"));
+        assert_eq!(body, b"nodes");
+        let (_, headers, gzipped) = request("Accept-Encoding: *\r\n");
+        assert!(headers.contains("Content-Encoding: gzip"));
+        assert!(headers.contains("Accept-Ranges: bytes"));
+        let (status, headers, tail) = request("Accept-Encoding: gzip\r\nRange: bytes=-8\r\n");
+        assert_eq!(status, "HTTP/1.1 206 Partial Content");
+        assert!(headers.contains("Content-Encoding: gzip"));
+        assert_eq!(tail, &gzipped[gzipped.len() - 8..]);
+        let (status, headers, _) = request("Range: bytes=100-\r\n");
+        assert_eq!(status, "HTTP/1.1 416 Range Not Satisfiable");
+        assert!(headers.contains("Content-Range: bytes */23"));
+        let (status, _, body) = send(address, "GARBAGE\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert!(String::from_utf8_lossy(&body).contains("invalid request line"));
+    }
+}
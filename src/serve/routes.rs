@@ -0,0 +1,124 @@
+// The paths of the I3S REST API, as a scene service lays out each layer, and
+// the entries of a package which hold what they return. Services leave the
+// file name extensions off: `nodes/4/geometries/0` is the entry
+// `nodes/4/geometries/0.bin.gz`, and `nodes/4/textures/0_0_1` the entry
+// `nodes/4/textures/0_0_1.bin.dds`. A few documents are served at the path
+// of the folder they are in, such as the node index document of a node at
+// `nodes/<id>`.
+
+use crate::i3s::SCENE_LAYER_DOCUMENT;
+
+/// The REST path of an entry, relative to its layer, from the name of the
+/// entry below the layer root. Entries which the API has no path for, such
+/// as `metadata.json`, have none.
+pub fn rest_path(name: &str) -> Option<String> {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    if name == SCENE_LAYER_DOCUMENT {
+        return Some(String::new());
+    }
+    let parts: Vec<&str> = name.split('/').collect();
+    match parts.as_slice() {
+        ["nodepages", page] => page
+            .strip_suffix(".json")
+            .map(|page| format!("nodepages/{}", page)),
+        ["statistics", key, "0.json"] => Some(format!("statistics/{}", key)),
+        ["nodes", id, "3dNodeIndexDocument.json"] => Some(format!("nodes/{}", id)),
+        ["nodes", id, "shared", "sharedResource.json"] => Some(format!("nodes/{}/shared", id)),
+        ["nodes", id, folders @ .., file] if !folders.is_empty() => {
+            let stem = file.split('.').next().unwrap_or(file);
+            Some(format!("nodes/{}/{}/{}", id, folders.join("/"), stem))
+        }
+        _ => None,
+    }
+}
+
+/// The media type of an entry, from its name without any `.gz` suffix.
+pub fn content_type(name: &str) -> &'static str {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    match name.rsplit('.').next().unwrap_or_default() {
+        "json" => "application/json; charset=utf-8",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "dds" => "image/vnd-ms.dds",
+        "ktx2" => "image/ktx2",
+        "ktx" => "image/ktx",
+        "pbf" => "application/x-protobuf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The file name extension an entry served as `content_type` is stored
+/// with, the other way round from `content_type`. Anything which isn't JSON
+/// or an image is a binary buffer.
+#[cfg(feature = "remote")]
+pub fn extension_of(content_type: &str) -> &'static str {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    match media_type.to_ascii_lowercase().as_str() {
+        "application/json" => "json",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/png" => "png",
+        "image/vnd-ms.dds" | "image/vnd.ms-dds" => "bin.dds",
+        "image/ktx2" => "ktx2",
+        "image/ktx" => "ktx",
+        "application/x-protobuf" => "pbf",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_have_the_paths_of_the_rest_api() {
+        let path = |name: &str| rest_path(name);
+        assert_eq!(path("3dSceneLayer.json.gz").as_deref(), Some(""));
+        assert_eq!(
+            path("nodepages/12.json.gz").as_deref(),
+            Some("nodepages/12")
+        );
+        assert_eq!(
+            path("statistics/f_1/0.json").as_deref(),
+            Some("statistics/f_1")
+        );
+        assert_eq!(
+            path("nodes/root/3dNodeIndexDocument.json.gz").as_deref(),
+            Some("nodes/root")
+        );
+        assert_eq!(
+            path("nodes/4/shared/sharedResource.json.gz").as_deref(),
+            Some("nodes/4/shared")
+        );
+        assert_eq!(
+            path("nodes/4/textures/0_0_1.bin.dds.gz").as_deref(),
+            Some("nodes/4/textures/0_0_1")
+        );
+        assert_eq!(
+            path("nodes/4/attributes/f_0/0.bin.gz").as_deref(),
+            Some("nodes/4/attributes/f_0/0")
+        );
+        assert_eq!(path("metadata.json"), None);
+        assert_eq!(path("nodes/4"), None);
+
+        assert_eq!(content_type("nodes/4/textures/0.jpg"), "image/jpeg");
+        assert_eq!(
+            content_type("nodepages/0.json.gz"),
+            "application/json; charset=utf-8"
+        );
+        assert_eq!(
+            content_type("nodes/4/geometries/0.bin.gz"),
+            "application/octet-stream"
+        );
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn content_types_give_back_the_extensions() {
+        for name in &["0.json", "0.jpg", "0.png", "0.bin.dds", "0.ktx2", "0.bin"] {
+            let extension = name.split_once('.').unwrap().1;
+            assert_eq!(extension_of(content_type(name)), extension);
+        }
+        assert_eq!(extension_of("Application/JSON"), "json");
+        assert_eq!(extension_of("text/html"), "bin");
+    }
+}
@@ -0,0 +1,332 @@
+// Reports where the bytes of a package go, from its zip directory alone.
+// Entries are counted by the kind of resource they hold, and the size each
+// gzipped resource unpacks to is read from the ISIZE field of its gzip
+// trailer when it is stored, as the I3S specification has them. Deflated
+// gzipped entries would have to be decompressed for it, so their size in the
+// zip directory is taken instead.
+
+mod values;
+
+pub use self::values::{coded_values, grouped, CodedValue, CountSource, FieldValues};
+
+use crate::json::Value;
+use crate::list::EntryInfo;
+use crate::unpack::ResourceKind;
+use byteorder::{LittleEndian, ReadBytesExt};
+use failure::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::{CompressionMethod, ZipArchive};
+
+/// How many of the largest entries are reported.
+const LARGEST_ENTRIES: usize = 10;
+
+/// The smallest gzip stream: a header and a trailer around an empty block.
+const MIN_GZIP_LEN: u64 = 18;
+
+/// The kinds of entry whose sizes are added up.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Category {
+    /// The texture resources of the nodes.
+    Textures,
+    /// The geometry buffers of the nodes.
+    Geometry,
+    /// The attribute buffers of the nodes.
+    Attributes,
+    /// The JSON documents of the nodes, below `nodes/` or in the node pages.
+    NodeDocuments,
+    /// Everything else, from the layer document to the hash index.
+    Other,
+}
+
+const CATEGORIES: [Category; 5] = [
+    Category::Textures,
+    Category::Geometry,
+    Category::Attributes,
+    Category::NodeDocuments,
+    Category::Other,
+];
+
+impl Category {
+    /// The category of an entry, from its path within the package.
+    pub fn of_entry(entry_path: &Path) -> Category {
+        match ResourceKind::of_entry(entry_path) {
+            ResourceKind::Textures => Category::Textures,
+            ResourceKind::Geometry => Category::Geometry,
+            ResourceKind::Attributes => Category::Attributes,
+            ResourceKind::Metadata
+                if entry_path.components().any(|component| {
+                    component.as_os_str() == "nodes" || component.as_os_str() == "nodepages"
+                }) =>
+            {
+                Category::NodeDocuments
+            }
+            _ => Category::Other,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::Textures => "textures",
+            Category::Geometry => "geometry",
+            Category::Attributes => "attributes",
+            Category::NodeDocuments => "node documents",
+            Category::Other => "other",
+        })
+    }
+}
+
+/// The sizes of the entries of a category.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryStats {
+    /// The category the sizes are of.
+    pub category: Category,
+    /// The number of entries.
+    pub entries: usize,
+    /// The size of the entries in the package.
+    pub compressed_size: u64,
+    /// The size of the entries once decompressed by the zip reader.
+    pub size: u64,
+    /// The size once unpacked, which for gzipped entries is that of the
+    /// resource they hold.
+    pub unpacked_size: u64,
+}
+
+impl CategoryStats {
+    fn new(category: Category) -> CategoryStats {
+        CategoryStats {
+            category,
+            entries: 0,
+            compressed_size: 0,
+            size: 0,
+            unpacked_size: 0,
+        }
+    }
+
+    /// The sizes, without the category, which callers name themselves.
+    fn sizes_to_json(&self) -> Vec<(String, Value)> {
+        vec![
+            ("entries".to_string(), Value::from(self.entries)),
+            (
+                "compressedSize".to_string(),
+                Value::from(self.compressed_size),
+            ),
+            ("size".to_string(), Value::from(self.size)),
+            ("unpackedSize".to_string(), Value::from(self.unpacked_size)),
+        ]
+    }
+
+    fn add(&mut self, other: &CategoryStats) {
+        self.entries += other.entries;
+        self.compressed_size += other.compressed_size;
+        self.size += other.size;
+        self.unpacked_size += other.unpacked_size;
+    }
+}
+
+/// The sizes of the entries of a package, by category.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageStats {
+    /// Every category, including those with no entries, in the order they
+    /// are printed.
+    pub categories: Vec<CategoryStats>,
+    /// The largest entries by size, largest first.
+    pub largest: Vec<EntryInfo>,
+}
+
+impl PackageStats {
+    /// The sizes of the whole package, whose category is `Other`.
+    pub fn total(&self) -> CategoryStats {
+        let mut total = CategoryStats::new(Category::Other);
+        for category in &self.categories {
+            total.add(category);
+        }
+        total
+    }
+
+    /// The sizes as JSON, for `slpkg stats --json`.
+    pub fn to_json(&self) -> Value {
+        let categories = self
+            .categories
+            .iter()
+            .map(|category| {
+                let mut members = vec![(
+                    "category".to_string(),
+                    Value::from(category.category.to_string()),
+                )];
+                members.extend(category.sizes_to_json());
+                Value::Object(members)
+            })
+            .collect();
+        Value::Object(vec![
+            ("categories".to_string(), Value::Array(categories)),
+            (
+                "total".to_string(),
+                Value::Object(self.total().sizes_to_json()),
+            ),
+            (
+                "largest".to_string(),
+                Value::Array(self.largest.iter().map(EntryInfo::to_json).collect()),
+            ),
+        ])
+    }
+
+    /// `to_json`, with the features holding each value of the coded-value
+    /// attributes `fields` under `codedValues`.
+    pub fn to_json_with_coded_values(&self, fields: &[FieldValues]) -> Value {
+        let mut document = self.to_json();
+        if let Value::Object(members) = &mut document {
+            let fields = fields.iter().map(FieldValues::to_json).collect();
+            members.push(("codedValues".to_string(), Value::Array(fields)));
+        }
+        document
+    }
+}
+
+/// The size a stored gzip stream unpacks to, from its trailer. ISIZE holds
+/// the size modulo 4 GiB, which no resource of a real package reaches.
+fn gzip_isize<R: Read + Seek>(
+    package: &mut R,
+    data_start: u64,
+    compressed_size: u64,
+) -> Result<u64, Error> {
+    package.seek(SeekFrom::Start(data_start + compressed_size - 4))?;
+    Ok(u64::from(package.read_u32::<LittleEndian>()?))
+}
+
+/// Breaks down the sizes of the entries of a package by category, and
+/// finds its largest entries.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::stats;
+/// use std::path::Path;
+///
+/// let stats = stats::stats(Path::new("tests/fixtures/building.slpk"))?;
+/// let total = stats.total();
+/// assert_eq!(total.entries, 13);
+/// assert!(total.unpacked_size > total.size);
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn stats(slpk_file_path: &Path) -> Result<PackageStats, Error> {
+    let mut archive = crate::archive::open_slpk_archive(slpk_file_path)?;
+    let mut package = BufReader::new(File::open(slpk_file_path)?);
+    stats_of(&mut archive, &mut package)
+}
+
+/// The breakdown of a package by its zip directory, with `package` reading
+/// the same package for the gzip trailers.
+pub fn stats_of<R: Read + Seek, P: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    package: &mut P,
+) -> Result<PackageStats, Error> {
+    let mut categories: Vec<CategoryStats> =
+        CATEGORIES.iter().map(|&c| CategoryStats::new(c)).collect();
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let info = EntryInfo {
+            name: entry.name().to_string(),
+            compressed_size: entry.compressed_size(),
+            size: entry.size(),
+            gzipped: entry.name().ends_with(".gz"),
+            meta: None,
+        };
+        let unpacked_size = if info.gzipped
+            && entry.compression() == CompressionMethod::Stored
+            && info.compressed_size >= MIN_GZIP_LEN
+        {
+            gzip_isize(package, entry.data_start(), info.compressed_size)?
+        } else {
+            info.size
+        };
+        let category = Category::of_entry(&entry.sanitized_name());
+        if let Some(stats) = categories.iter_mut().find(|c| c.category == category) {
+            stats.add(&CategoryStats {
+                category,
+                entries: 1,
+                compressed_size: info.compressed_size,
+                size: info.size,
+                unpacked_size,
+            });
+        }
+        entries.push(info);
+    }
+    // Entries of the same size stay in the order of the zip directory.
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.size));
+    entries.truncate(LARGEST_ENTRIES);
+    Ok(PackageStats {
+        categories,
+        largest: entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    #[test]
+    fn sizes_are_added_up_by_category() {
+        let dir = TempDir::new("stats");
+        let path = dir.join("package.slpk");
+        let mut package = SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodepages/0.json.gz", b"{\"nodes\":[]}")
+            .entry("nodes/0/3dNodeIndexDocument.json.gz", b"{}")
+            .entry("nodes/0/geometries/0.bin.gz", &[0; 5000])
+            .entry("nodes/0/attributes/f_0/0.bin.gz", &[1; 30]);
+        for i in 0..12 {
+            package = package.entry(&format!("nodes/{}/textures/0.jpg", i), &vec![2; 100 + i]);
+        }
+        package.write_to_file(&path).unwrap();
+        let stats = stats(&path).unwrap();
+        let json = stats.to_json();
+
+        let category = |category| {
+            stats
+                .categories
+                .iter()
+                .find(|c| c.category == category)
+                .unwrap()
+                .clone()
+        };
+        let textures = category(Category::Textures);
+        assert_eq!((textures.entries, textures.size), (12, 1266));
+        assert_eq!(textures.unpacked_size, textures.size);
+        let geometry = category(Category::Geometry);
+        assert_eq!(geometry.entries, 1);
+        // The gzip stream is far smaller than the buffer it holds.
+        assert!(geometry.size < 100);
+        assert_eq!(geometry.unpacked_size, 5000);
+        assert_eq!(category(Category::Attributes).unpacked_size, 30);
+        assert_eq!(category(Category::NodeDocuments).entries, 2);
+        assert_eq!(category(Category::Other).entries, 1);
+        assert_eq!(stats.total().entries, 17);
+
+        assert_eq!(stats.largest.len(), 10);
+        assert_eq!(stats.largest[0].name, "nodes/11/textures/0.jpg");
+        assert_eq!(stats.largest[9].name, "nodes/2/textures/0.jpg");
+        let total = json.get("total").unwrap();
+        assert_eq!(total.get("entries").and_then(Value::as_u64), Some(17));
+        assert_eq!(
+            json.get("categories")
+                .and_then(Value::as_array)
+                .map(Vec::len),
+            Some(5)
+        );
+        let with_values = stats.to_json_with_coded_values(&[]);
+        assert_eq!(
+            with_values
+                .get("codedValues")
+                .and_then(Value::as_array)
+                .map(Vec::len),
+            Some(0)
+        );
+    }
+}
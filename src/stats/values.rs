@@ -0,0 +1,391 @@
+// Counts the features holding each value of the attributes whose field has a
+// coded-value domain, with the label the domain gives each code, so that the
+// values read as "RESIDENTIAL (code 3)" rather than as bare codes. The counts
+// come from the statistics document of the attribute when it lists its most
+// frequent values. Otherwise the attribute buffer of every node is decoded,
+// numbers and strings alike, since domains of string fields have string
+// codes. Nodes of every level of detail are counted, so a feature is counted
+// once for each level which holds it. A buffer which is missing or can't be
+// decoded is left out of the counts of its field, with a warning, rather than
+// failing the others.
+
+use crate::archive;
+use crate::export::attribute_nodes;
+use crate::export::attributes::AttributeLayout;
+use crate::i3s::{self, SceneLayer};
+use crate::json::{self, Value};
+use failure::Error;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Where the counts of a field were taken from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountSource {
+    /// The most frequent values of its statistics document.
+    Statistics,
+    /// The attribute buffers of the nodes.
+    Buffers,
+}
+
+/// A value of a coded-value domain, and the features which hold it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodedValue {
+    /// The code, as the attribute stores it.
+    pub code: String,
+    /// `None` for a code which the domain doesn't list.
+    pub label: Option<String>,
+    /// The features holding the code.
+    pub features: u64,
+}
+
+/// The values of one attribute with a coded-value domain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldValues {
+    /// The name of the field.
+    pub name: String,
+    /// The key of the attribute, which names its folder of attribute buffers.
+    pub key: Option<String>,
+    /// Where the counts were taken from.
+    pub source: CountSource,
+    /// The most frequent first.
+    pub values: Vec<CodedValue>,
+    /// Why some or all of the values couldn't be counted.
+    pub problem: Option<String>,
+}
+
+impl FieldValues {
+    /// The values as JSON, for `slpkg stats --json`.
+    pub fn to_json(&self) -> Value {
+        let values = self.values.iter().map(|value| {
+            Value::Object(vec![
+                ("code".to_string(), Value::from(value.code.as_str())),
+                (
+                    "label".to_string(),
+                    value.label.as_deref().map_or(Value::Null, Value::from),
+                ),
+                ("features".to_string(), Value::from(value.features)),
+            ])
+        });
+        Value::Object(vec![
+            ("name".to_string(), Value::from(self.name.as_str())),
+            (
+                "key".to_string(),
+                self.key.as_deref().map_or(Value::Null, Value::from),
+            ),
+            (
+                "source".to_string(),
+                Value::from(match self.source {
+                    CountSource::Statistics => "statistics",
+                    CountSource::Buffers => "buffers",
+                }),
+            ),
+            ("values".to_string(), Value::Array(values.collect())),
+            (
+                "problem".to_string(),
+                self.problem.as_deref().map_or(Value::Null, Value::from),
+            ),
+        ])
+    }
+}
+
+/// A code of a domain or a value of a statistics document, as the cells of a
+/// decoded buffer give it.
+fn code_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(text) => Some(text.clone()),
+        _ => None,
+    }
+}
+
+/// The code and label of each value of a field's coded-value domain, or
+/// `None` when it has none.
+fn coded_domain(field: &Value) -> Option<Vec<(String, String)>> {
+    let domain = field.get("domain")?;
+    if domain.get("type").and_then(Value::as_str) != Some("codedValue") {
+        return None;
+    }
+    let values = domain.get("codedValues").and_then(Value::as_array)?;
+    Some(
+        values
+            .iter()
+            .filter_map(|value| {
+                let code = value.get("code").and_then(code_text)?;
+                let label = value.get("name").and_then(Value::as_str)?;
+                Some((code, label.to_string()))
+            })
+            .collect(),
+    )
+}
+
+/// The statistics document of the attribute with `key`, by the `href` of
+/// `statisticsInfo`, or where the specification puts it.
+fn statistics_entry(layer: &SceneLayer, key: &str) -> String {
+    let href = layer
+        .document
+        .get("statisticsInfo")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .find(|info| info.get("key").and_then(Value::as_str) == Some(key))
+        .and_then(|info| info.get("href"))
+        .and_then(Value::as_str);
+    match href {
+        Some(href) => layer.entry_name(&format!(
+            "{}.json",
+            href.trim_start_matches("./").trim_end_matches(".json")
+        )),
+        None => layer.entry_name(&format!("statistics/{}/0.json", key)),
+    }
+}
+
+/// The number of features holding each code.
+type Counts = Vec<(String, u64)>;
+
+/// The counts of the most frequent values of a statistics document.
+fn statistics_counts(document: &Value) -> Option<Counts> {
+    let values = document
+        .get("stats")
+        .and_then(|stats| stats.get("mostFrequentValues"))
+        .and_then(Value::as_array)?;
+    Some(
+        values
+            .iter()
+            .filter_map(|value| {
+                let code = value.get("value").and_then(code_text)?;
+                Some((code, value.get("count").and_then(Value::as_u64)?))
+            })
+            .collect(),
+    )
+}
+
+/// Counts the values of an attribute by decoding its buffer in every node,
+/// with what went wrong for the nodes which couldn't be counted.
+fn buffer_counts<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    layout: &AttributeLayout,
+    nodes: &[String],
+) -> Result<(Counts, Option<String>), Error> {
+    let mut counts: Counts = Vec::new();
+    let mut failed = 0;
+    let mut first_problem = None;
+    for node in nodes {
+        let name = layer.entry_name(&format!("nodes/{}/attributes/{}/0.bin", node, layout.key));
+        let cells = match archive::read_resource(slpk_archive, &name)? {
+            Some(buffer) => layout.decode(&buffer),
+            None => Err("it has no buffer".to_string()),
+        };
+        match cells {
+            Ok(cells) => {
+                for code in cells.into_iter().flatten() {
+                    match counts.iter_mut().find(|(counted, _)| *counted == code) {
+                        Some((_, features)) => *features += 1,
+                        None => counts.push((code, 1)),
+                    }
+                }
+            }
+            Err(problem) => {
+                failed += 1;
+                first_problem.get_or_insert_with(|| format!("node {}: {}", node, problem));
+            }
+        }
+    }
+    let problem = first_problem.map(|first| {
+        format!(
+            "{} of {} nodes weren't counted, the first of them {}",
+            failed,
+            nodes.len(),
+            first
+        )
+    });
+    Ok((counts, problem))
+}
+
+/// The values of every attribute of the layer with the given id, or of the
+/// first layer when `layer` is `None`, whose field has a coded-value domain,
+/// in the order of `fields`.
+pub fn coded_values(
+    slpk_file_path: &Path,
+    layer: Option<usize>,
+) -> Result<Vec<FieldValues>, Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let layer = i3s::select_layer(&mut slpk_archive, layer)?;
+    let (layouts, unreadable) = AttributeLayout::of_layer(&layer.document);
+    let fields = layer
+        .document
+        .get("fields")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let mut nodes = None;
+    let mut coded = Vec::new();
+    for field in &fields {
+        let (name, domain) = match (
+            field.get("name").and_then(Value::as_str),
+            coded_domain(field),
+        ) {
+            (Some(name), Some(domain)) => (name.to_string(), domain),
+            _ => continue,
+        };
+        let key = i3s::declared_attributes(&layer.document)
+            .into_iter()
+            .find(|attribute| attribute.name == name)
+            .and_then(|attribute| attribute.key);
+        let mut values = FieldValues {
+            name,
+            key: key.clone(),
+            source: CountSource::Statistics,
+            values: Vec::new(),
+            problem: None,
+        };
+        let key = match key {
+            Some(key) => key,
+            None => {
+                values.problem = Some("the field has no attributeStorageInfo".to_string());
+                coded.push(values);
+                continue;
+            }
+        };
+
+        let statistics =
+            archive::read_resource(&mut slpk_archive, &statistics_entry(&layer, &key))?
+                .and_then(|document| json::parse_bytes(&document).ok());
+        let counts = match statistics.as_ref().and_then(statistics_counts) {
+            Some(counts) => counts,
+            None => {
+                values.source = CountSource::Buffers;
+                match layouts.iter().find(|layout| layout.key == key) {
+                    Some(layout) => {
+                        if nodes.is_none() {
+                            nodes = Some(attribute_nodes(&mut slpk_archive, &layer)?);
+                        }
+                        let nodes = nodes.as_deref().unwrap_or_default();
+                        let (counts, problem) =
+                            buffer_counts(&mut slpk_archive, &layer, layout, nodes)?;
+                        values.problem = problem;
+                        counts
+                    }
+                    None => {
+                        let problem = unreadable
+                            .iter()
+                            .find(|(unreadable, _)| *unreadable == key)
+                            .map_or("it isn't declared", |(_, problem)| problem.as_str());
+                        values.problem = Some(format!("its buffers can't be decoded, {}", problem));
+                        Vec::new()
+                    }
+                }
+            }
+        };
+        values.values = counts
+            .into_iter()
+            .map(|(code, features)| CodedValue {
+                label: domain
+                    .iter()
+                    .find(|(coded, _)| *coded == code)
+                    .map(|(_, label)| label.clone()),
+                code,
+                features,
+            })
+            .collect();
+        // Values with the same count stay in the order they were found.
+        values
+            .values
+            .sort_by_key(|value| std::cmp::Reverse(value.features));
+        coded.push(values);
+    }
+    Ok(coded)
+}
+
+/// A count with a comma between each group of three digits.
+pub fn grouped(count: u64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use byteorder::{ByteOrder, LittleEndian};
+
+    #[test]
+    fn codes_are_counted_with_their_labels() {
+        let dir = TempDir::new("stats-values");
+        let path = dir.join("package.slpk");
+        let layer = br#"{"layerType":"3DObject","store":{"version":"1.8"},
+            "fields":[{"name":"USE","type":"esriFieldTypeInteger","domain":{"type":"codedValue",
+                "codedValues":[{"name":"RESIDENTIAL","code":3},{"name":"COMMERCIAL","code":4}]}},
+                {"name":"ZONE","type":"esriFieldTypeString","domain":{"type":"codedValue",
+                "codedValues":[{"name":"Downtown","code":"D"}]}},
+                {"name":"HEIGHT","type":"esriFieldTypeDouble"}],
+            "attributeStorageInfo":[
+                {"key":"f_0","name":"USE","header":[{"property":"count","valueType":"UInt32"}],
+                    "ordering":["attributeValues"],"attributeValues":{"valueType":"Int32"}},
+                {"key":"f_1","name":"ZONE","header":[{"property":"count","valueType":"UInt32"},
+                    {"property":"attributeValuesByteCount","valueType":"UInt32"}],
+                    "ordering":["attributeByteCounts","attributeValues"],
+                    "attributeByteCounts":{"valueType":"UInt32"},"attributeValues":{"valueType":"String"}},
+                {"key":"f_2","name":"HEIGHT"}]}"#;
+        let mut zones = vec![0; 8];
+        LittleEndian::write_u32(&mut zones[..4], 3);
+        LittleEndian::write_u32(&mut zones[4..], 6);
+        for length in &[2u32, 2, 2] {
+            zones.extend_from_slice(&length.to_le_bytes());
+        }
+        zones.extend_from_slice(b"D\0E\0D\0");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", layer)
+            .entry(
+                "statistics/f_0/0.json.gz",
+                br#"{"stats":{"mostFrequentValues":[{"value":4,"count":17},{"value":3,"count":1204},{"value":9,"count":1}]}}"#,
+            )
+            .entry("nodes/0/attributes/f_1/0.bin.gz", &zones)
+            .entry("nodes/1/attributes/f_1/0.bin.gz", &zones[..10])
+            .write_to_file(&path)
+            .unwrap();
+        let values = coded_values(&path, None).unwrap();
+
+        assert_eq!(values.len(), 2);
+        let uses = &values[0];
+        assert_eq!(
+            (uses.source, uses.problem.as_ref()),
+            (CountSource::Statistics, None)
+        );
+        let labelled: Vec<(&str, Option<&str>, u64)> = uses
+            .values
+            .iter()
+            .map(|v| (v.code.as_str(), v.label.as_deref(), v.features))
+            .collect();
+        assert_eq!(
+            labelled,
+            [
+                ("3", Some("RESIDENTIAL"), 1204),
+                ("4", Some("COMMERCIAL"), 17),
+                ("9", None, 1)
+            ]
+        );
+        // The buffer of the second node is cut short, and left out.
+        let zones = &values[1];
+        assert_eq!(zones.source, CountSource::Buffers);
+        assert_eq!(zones.values[0].label.as_deref(), Some("Downtown"));
+        assert_eq!(zones.values[0].features, 2);
+        assert_eq!(zones.values[1].features, 1);
+        assert!(zones
+            .problem
+            .as_ref()
+            .unwrap()
+            .starts_with("1 of 2 nodes weren't counted, the first of them node 1"));
+        assert_eq!(grouped(1204), "1,204");
+        assert_eq!(grouped(1_234_567), "1,234,567");
+        assert_eq!(grouped(17), "17");
+    }
+}
@@ -0,0 +1,282 @@
+// Builds small synthetic packages, for the self-test command and for the unit
+// and integration tests.
+// The standard package is a Building Scene Layer, so that it can be unpacked
+// both as-is and split into sublayers, and it mixes plain JSON, gzipped JSON,
+// binary buffers, and entry names which tend to upset filesystems and tools.
+
+mod temp_dir;
+
+pub use self::temp_dir::TempDir;
+
+use crate::archive::hash_index::{self, HASH_INDEX_ENTRY};
+use failure::Error;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+#[derive(Clone, Debug)]
+pub struct SyntheticEntry {
+    /// The entry name inside the package, including any `.gz` extension.
+    pub name: String,
+    /// The uncompressed payload.
+    pub contents: Vec<u8>,
+}
+
+impl SyntheticEntry {
+    fn is_gzipped(&self) -> bool {
+        self.name.ends_with(".gz")
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SyntheticPackage {
+    entries: Vec<SyntheticEntry>,
+    /// Sublayer folder names by sublayer id, for Building Scene Layers.
+    sublayers: Vec<(u32, String)>,
+    /// Whether a hash index is written after the entries, as ArcGIS Pro
+    /// writes one, and the offsets it records for entries instead of their
+    /// own, as in the stale index of a package edited by another tool.
+    hash_index: Option<Vec<(String, u64)>>,
+}
+
+fn binary_payload(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+impl SyntheticPackage {
+    pub fn new() -> SyntheticPackage {
+        SyntheticPackage::default()
+    }
+
+    /// Adds an entry. Entries whose name ends in `.gz` are gzipped when the
+    /// package is written.
+    pub fn entry(mut self, name: &str, contents: &[u8]) -> SyntheticPackage {
+        self.entries.push(SyntheticEntry {
+            name: name.to_string(),
+            contents: contents.to_vec(),
+        });
+        self
+    }
+
+    pub fn sublayer(mut self, id: u32, name: &str) -> SyntheticPackage {
+        self.sublayers.push((id, name.to_string()));
+        self
+    }
+
+    pub fn with_hash_index(mut self) -> SyntheticPackage {
+        self.hash_index.get_or_insert_with(Vec::new);
+        self
+    }
+
+    /// Adds a hash index whose record of `name` points at `offset`.
+    pub fn with_hash_index_record(mut self, name: &str, offset: u64) -> SyntheticPackage {
+        self.hash_index
+            .get_or_insert_with(Vec::new)
+            .push((name.to_string(), offset));
+        self
+    }
+
+    /// A two sublayer building with a few nodes each.
+    pub fn standard() -> SyntheticPackage {
+        let long_name = format!("sublayers/2/nodes/1/{}.bin", "x".repeat(120));
+        SyntheticPackage::new()
+            .sublayer(1, "Doors")
+            .sublayer(2, "Walls")
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"layerType":"Building","name":"Synthetic","sublayers":[
+                    {"id":1,"name":"Doors","layerType":"3DObject"},
+                    {"id":2,"name":"Walls","layerType":"3DObject"}]}"#,
+            )
+            .entry(
+                "metadata.json",
+                br#"{"folderPattern":"1","archiveCompressionType":"STORE","resourceCompressionType":"GZIP","I3SVersion":"1.7","nodeCount":4}"#,
+            )
+            .entry(
+                "sublayers/1/3dSceneLayer.json.gz",
+                br#"{"id":1,"layerType":"3DObject","store":{"rootNode":"./nodes/root"}}"#,
+            )
+            .entry(
+                "sublayers/1/nodes/root/3dNodeIndexDocument.json.gz",
+                br#"{"id":"root","level":0,"children":[{"id":"0"}]}"#,
+            )
+            .entry(
+                "sublayers/1/nodes/0/geometries/0.bin.gz",
+                &binary_payload(4096, 1),
+            )
+            .entry(
+                "sublayers/1/nodes/0/textures/0 copy (1).jpg",
+                &binary_payload(1024, 2),
+            )
+            .entry(
+                "sublayers/1/nodes/0/attributes/f_0/0.bin.gz",
+                &binary_payload(64, 3),
+            )
+            .entry(
+                "sublayers/2/3dSceneLayer.json.gz",
+                br#"{"id":2,"layerType":"3DObject","store":{"rootNode":"./nodes/root"}}"#,
+            )
+            .entry(
+                "sublayers/2/nodes/root/features/\u{fc}n\u{ef}c\u{f8}d\u{e9} name.json.gz",
+                br#"{"featureData":[]}"#,
+            )
+            .entry(&long_name, &binary_payload(16, 4))
+            .entry("sublayers/2/nodes/1/README", b"no extension")
+            .entry(".hidden/0..json", b"{}")
+            .entry("statistics/a/b/c/d/e/f/deep.json.gz", b"[1,2,3]")
+    }
+
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<W, Error> {
+        // The index records where each local header ends up, which is the
+        // same whether or not the index follows the entries.
+        let index = if let Some(offsets) = &self.hash_index {
+            let without_index = SyntheticPackage {
+                hash_index: None,
+                ..self.clone()
+            };
+            let mut archive = ZipArchive::new(Cursor::new(without_index.to_bytes()?))?;
+            let mut records = Vec::with_capacity(archive.len());
+            for i in 0..archive.len() {
+                let entry = archive.by_index(i)?;
+                // The writer leaves the extra field of local headers empty.
+                let offset = offsets
+                    .iter()
+                    .find(|(name, _)| name == entry.name())
+                    .map(|(_, offset)| *offset)
+                    .unwrap_or(entry.data_start() - 30 - entry.name_raw().len() as u64);
+                records.push((hash_index::key_of(entry.name()), offset));
+            }
+            records.sort();
+            Some(records)
+        } else {
+            None
+        };
+        let mut zip = ZipWriter::new(writer);
+        // Packages are stored rather than deflated, like real exports.
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for entry in &self.entries {
+            zip.start_file(entry.name.as_str(), options)?;
+            if entry.is_gzipped() {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&entry.contents)?;
+                zip.write_all(&encoder.finish()?)?;
+            } else {
+                zip.write_all(&entry.contents)?;
+            }
+        }
+        if let Some(records) = index {
+            zip.start_file(HASH_INDEX_ENTRY, options)?;
+            for (key, offset) in records {
+                zip.write_all(&key)?;
+                zip.write_all(&offset.to_le_bytes())?;
+            }
+        }
+        Ok(zip.finish()?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.write(Cursor::new(Vec::new()))?.into_inner())
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Returns the files, relative to the output folder, and their contents
+    /// that unpacking the package should produce.
+    pub fn expected_files(&self, split_sublayers: bool) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files: Vec<(PathBuf, Vec<u8>)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut name = entry.name.as_str();
+                if entry.is_gzipped() {
+                    name = &name[..name.len() - 3];
+                }
+                let mut path = PathBuf::from(name);
+                if split_sublayers {
+                    for (id, folder) in &self.sublayers {
+                        let prefix = format!("sublayers/{}", id);
+                        if let Ok(rest) = path.strip_prefix(&prefix) {
+                            path = Path::new(folder).join(rest);
+                            break;
+                        }
+                    }
+                }
+                (path, entry.contents.clone())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive;
+    use zip::ZipArchive;
+
+    #[test]
+    fn gzipped_entries_round_trip() {
+        let package = SyntheticPackage::standard();
+        let bytes = package.to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), package.entries.len());
+
+        let document = archive::read_entry(&mut archive, "3dSceneLayer.json.gz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(document, package.entries[0].contents);
+    }
+
+    // The package the examples in the documentation unpack. Run the tests
+    // with UPDATE_GOLDEN=1 to write it again after changing the standard
+    // package.
+    #[test]
+    fn the_fixture_is_the_standard_package() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("building.slpk");
+        let package = SyntheticPackage::standard();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(fixture.parent().unwrap()).unwrap();
+            package.write_to_file(&fixture).unwrap();
+            return;
+        }
+        let mut archive = ZipArchive::new(std::fs::File::open(&fixture).unwrap()).unwrap();
+        let mut entries = Vec::new();
+        for entry in &package.entries {
+            let contents = archive::read_entry(&mut archive, &entry.name)
+                .unwrap()
+                .unwrap_or_else(|| panic!("{} is missing from the fixture", entry.name));
+            entries.push((entry.name.clone(), contents));
+        }
+
+        assert_eq!(archive.len(), package.entries.len());
+        assert_eq!(
+            entries,
+            package
+                .entries
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.contents.clone()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn expected_files_follow_sublayer_split() {
+        let files = SyntheticPackage::standard().expected_files(true);
+        assert!(files
+            .iter()
+            .any(|(path, _)| path == Path::new("Doors/nodes/0/geometries/0.bin")));
+        assert!(files.iter().all(|(path, _)| !path.starts_with("sublayers")));
+    }
+}
@@ -0,0 +1,69 @@
+// Folders of their own for the tests, each below the temporary folder of the
+// system and named after its test and this process, so that tests running at
+// once and test runs of other checkouts keep apart. The folder is removed when
+// it is dropped, which also happens when an assertion fails before the test
+// gets to the end.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A folder created empty for a test, and removed with everything in it when
+/// this is dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates the folder `slpkg-<name>-<pid>` in the temporary folder,
+    /// replacing any left by an earlier process with the same id.
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("slpkg-{}-{}", name, std::process::id()));
+        if path.exists() {
+            std::fs::remove_dir_all(&path).unwrap();
+        }
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_folder_is_removed_when_dropped() {
+        let dir = TempDir::new("temp-dir");
+        std::fs::write(dir.join("file"), b"contents").unwrap();
+        let path = dir.path().to_path_buf();
+        let existed = path.join("file").is_file();
+        drop(dir);
+
+        assert!(existed);
+        assert!(!path.exists());
+    }
+}
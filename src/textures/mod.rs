@@ -0,0 +1,430 @@
+// Lists the textures of a package by what their bytes hold rather than what
+// their names say, since exporters mix JPEGs, DDS files named `*.bin.dds`
+// and KTX2 or Basis files freely, and only the first bytes of each entry are
+// read for it. The JPEGs and the DXT compressed DDS textures can also be
+// decoded into PNGs, for looking at in any image viewer. They are written
+// into the folder the package unpacks into, each named after its texture
+// with `.png` added, so that they sit beside the textures once unpacked.
+// Texture formats which can't be decoded, such as ETC2 or Basis, are skipped
+// with a warning.
+//
+// The atlas of a node, a texture shared by many features, can be split too.
+// The geometry buffer of the node gives each vertex the region of the atlas
+// it is textured from, and the atlas is cropped by the region of the first
+// vertex of each feature, into `atlas/<feature id>.png` in the folder of the
+// node's textures. Features which share a region share the crop, named after
+// the first of them, and `atlas/features.json` gives the crop of each.
+
+use crate::archive;
+use crate::export;
+use crate::export::geometry::Mesh;
+use crate::i3s;
+use crate::image;
+pub use crate::image::ImageFormat;
+use crate::image::RgbaImage;
+use crate::json::Value;
+use crate::unpack::{unpack_folder_path, ResourceKind};
+use failure::Error;
+use std::path::{Path, PathBuf};
+
+/// How much of a texture is read to tell its format and size.
+const TEXTURE_HEADER_LEN: usize = 64 * 1024;
+
+/// The folder below the textures of a node which its crops are written to.
+const ATLAS_FOLDER: &str = "atlas";
+
+/// A texture of a node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureInfo {
+    /// The name of the entry.
+    pub name: String,
+    /// The path of the entry, as it is unpacked below a folder.
+    pub path: PathBuf,
+    /// The format of the texture, or `None` when it's one slpkg doesn't
+    /// know.
+    pub format: Option<ImageFormat>,
+    /// The width and height of the texture, when its header holds them.
+    pub dimensions: Option<(u32, u32)>,
+    /// The size of the entry in the zip directory.
+    pub size: u64,
+}
+
+/// What converting the textures of a package did.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConvertSummary {
+    /// Textures which were written as PNGs.
+    pub converted: usize,
+    /// Textures which were PNGs already.
+    pub already_png: usize,
+    /// Textures which couldn't be decoded, with a warning for each.
+    pub skipped: usize,
+}
+
+/// The crops written from the atlas of a node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeCrops {
+    /// The id of the node.
+    pub node_id: String,
+    /// The folder the crops are in.
+    pub folder: PathBuf,
+    /// The number of crops, one for each region.
+    pub crops: usize,
+    /// The number of features, which may share regions.
+    pub features: usize,
+}
+
+/// What splitting the atlases of a package did.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SplitSummary {
+    /// The nodes whose atlas was split, in the order of their layers.
+    pub nodes: Vec<NodeCrops>,
+    /// Nodes whose mesh, or whose atlas, couldn't be decoded.
+    pub skipped: usize,
+}
+
+/// Every entry below the `textures` folder of a node, in the order of the
+/// zip directory.
+pub fn textures(slpk_file_path: &Path) -> Result<Vec<TextureInfo>, Error> {
+    let mut archive = archive::open_slpk_archive(slpk_file_path)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let path = archive::names::entry_path(&entry);
+        if ResourceKind::of_entry(&path) == ResourceKind::Textures {
+            entries.push((entry.name().to_string(), path, entry.size()));
+        }
+    }
+
+    let mut textures = Vec::with_capacity(entries.len());
+    for (name, path, size) in entries {
+        let header = archive::read_entry_prefix(&mut archive, &name, TEXTURE_HEADER_LEN)?;
+        textures.push(TextureInfo {
+            format: image::image_format(&header),
+            dimensions: image::image_dimensions(&header),
+            name,
+            path,
+            size,
+        });
+    }
+    Ok(textures)
+}
+
+/// The path below the unpack folder the PNG of a texture is written to.
+fn png_path(texture_path: &Path) -> PathBuf {
+    let path = texture_path.to_string_lossy();
+    let path = path.strip_suffix(".gz").unwrap_or(&path);
+    PathBuf::from(format!("{}.png", path))
+}
+
+/// Decodes each texture which can be decoded into a PNG below
+/// `output_folder`, warning about the others.
+pub fn convert_textures(
+    slpk_file_path: &Path,
+    textures: &[TextureInfo],
+    output_folder: &Path,
+) -> Result<ConvertSummary, Error> {
+    let mut archive = archive::open_slpk_archive(slpk_file_path)?;
+    let mut summary = ConvertSummary::default();
+    for texture in textures {
+        if texture.format == Some(ImageFormat::Png) {
+            summary.already_png += 1;
+            continue;
+        }
+        let contents = archive::read_entry(&mut archive, &texture.name)?.unwrap_or_default();
+        let decoded = match image::decode(&contents) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!("Skipping {}: {}", texture.name, e);
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        let target = output_folder.join(png_path(&texture.path));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, decoded.to_png())?;
+        summary.converted += 1;
+    }
+    Ok(summary)
+}
+
+/// The pixels a UV region covers in an image of `width` by `height`
+/// pixels, as left, top, width and height, rounded outwards to whole
+/// pixels and at least one wide and high.
+fn region_pixels(region: [u16; 4], width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let scale = |value: u16, size: u32| f64::from(value) / f64::from(u16::MAX) * f64::from(size);
+    let (left, top) = (
+        scale(region[0], width).floor() as u32,
+        scale(region[1], height).floor() as u32,
+    );
+    let right = (scale(region[2], width).ceil() as u32).max(left + 1);
+    let bottom = (scale(region[3], height).ceil() as u32).max(top + 1);
+    (left, top, right - left, bottom - top)
+}
+
+/// The region of each feature of a mesh, by its id, from the first vertex
+/// of its first face.
+fn feature_regions(mesh: &Mesh) -> Vec<(u64, [u16; 4])> {
+    mesh.features
+        .iter()
+        .filter_map(|feature| {
+            let vertex = feature.faces.0 as usize * 3;
+            Some((feature.id, *mesh.uv_regions.get(vertex)?))
+        })
+        .collect()
+}
+
+/// Decodes the atlas of a node: the texture of its material, or else the
+/// first of `candidates` which decodes.
+fn read_atlas<R: std::io::Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    texture: Option<&[u8]>,
+    candidates: &[&TextureInfo],
+) -> Result<Option<RgbaImage>, Error> {
+    if let Some(Ok(atlas)) = texture.map(image::decode) {
+        return Ok(Some(atlas));
+    }
+    for candidate in candidates {
+        let contents = archive::read_entry(archive, &candidate.name)?.unwrap_or_default();
+        if let Ok(atlas) = image::decode(&contents) {
+            return Ok(Some(atlas));
+        }
+    }
+    Ok(None)
+}
+
+/// Crops the atlas of each node whose geometry gives its features UV
+/// regions, writing a PNG for each region into the folder of the node's
+/// textures below `output_folder`. Nodes whose mesh or atlas can't be
+/// decoded are skipped with a warning.
+pub fn split_atlases(
+    slpk_file_path: &Path,
+    textures: &[TextureInfo],
+    output_folder: &Path,
+) -> Result<SplitSummary, Error> {
+    let mut archive = archive::open_slpk_archive(slpk_file_path)?;
+    let mut summary = SplitSummary::default();
+    for layer in i3s::read_layers(&mut archive)? {
+        if layer.is_point_cloud() {
+            continue;
+        }
+        export::visit_node_meshes(&mut archive, &layer, |archive, node_id, mesh| {
+            let mesh = match mesh {
+                Ok(mesh) => mesh,
+                Err(e) => {
+                    warn!("Skipping the atlas of node {}: {}", node_id, e);
+                    summary.skipped += 1;
+                    return Ok(());
+                }
+            };
+            let regions = feature_regions(&mesh.mesh);
+            if regions.is_empty() {
+                return Ok(());
+            }
+            let folder = layer.entry_name(&mesh.texture_folder);
+            let candidates: Vec<&TextureInfo> = textures
+                .iter()
+                .filter(|texture| texture.name.starts_with(&folder))
+                .collect();
+            let texture = mesh.texture.as_ref().map(|(image, _)| &image[..]);
+            let atlas = match read_atlas(archive, texture, &candidates)? {
+                Some(atlas) => atlas,
+                None => {
+                    warn!(
+                        "Skipping the atlas of node {}: none of its textures can be decoded",
+                        node_id
+                    );
+                    summary.skipped += 1;
+                    return Ok(());
+                }
+            };
+
+            let target = output_folder.join(&folder).join(ATLAS_FOLDER);
+            std::fs::create_dir_all(&target)?;
+            let mut crops: Vec<([u16; 4], String)> = Vec::new();
+            let mut features = Vec::with_capacity(regions.len());
+            for (id, region) in &regions {
+                let file = match crops.iter().find(|(cropped, _)| cropped == region) {
+                    Some((_, file)) => file.clone(),
+                    None => {
+                        let file = format!("{}.png", id);
+                        let (x, y, width, height) =
+                            region_pixels(*region, atlas.width, atlas.height);
+                        let crop = atlas.crop(x, y, width, height);
+                        std::fs::write(target.join(&file), crop.to_png())?;
+                        crops.push((*region, file.clone()));
+                        file
+                    }
+                };
+                features.push((id.to_string(), Value::from(file)));
+            }
+            let index = Value::Object(features).to_pretty_string(2) + "\n";
+            std::fs::write(target.join("features.json"), index)?;
+            summary.nodes.push(NodeCrops {
+                node_id: node_id.to_string(),
+                folder: target,
+                crops: crops.len(),
+                features: regions.len(),
+            });
+            Ok(())
+        })?;
+    }
+    Ok(summary)
+}
+
+/// The folder which textures are converted and atlases split into: the one
+/// the package unpacks into, in `output_dir` when it is given.
+pub fn output_folder(slpk_file_path: &Path, output_dir: Option<&Path>) -> Result<PathBuf, Error> {
+    unpack_folder_path(slpk_file_path.to_path_buf(), output_dir, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    /// A DDS header for a DXT1 texture of `width` by `height` pixels.
+    fn dxt1_header(width: u32, height: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 128];
+        header[..4].copy_from_slice(b"DDS ");
+        header[4..8].copy_from_slice(&124u32.to_le_bytes());
+        header[12..16].copy_from_slice(&height.to_le_bytes());
+        header[16..20].copy_from_slice(&width.to_le_bytes());
+        header[84..88].copy_from_slice(b"DXT1");
+        header
+    }
+
+    #[test]
+    fn lists_textures_by_their_contents_and_converts_them() {
+        let dir = TempDir::new("textures");
+        let path = dir.join("package.slpk");
+        // A single block, red against transparent black.
+        let mut dds = dxt1_header(4, 4);
+        dds.extend_from_slice(&[0x00, 0x00, 0x00, 0xf8, 0x55, 0x55, 0x55, 0x55]);
+        let mut ktx2 = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+        ktx2.extend_from_slice(&[0; 64]);
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodes/0/textures/0_0_1.bin.dds.gz", &dds)
+            .entry("nodes/0/textures/0.jpg", &ktx2)
+            .entry("nodes/0/geometries/0.bin", &[0; 16])
+            .write_to_file(&path)
+            .unwrap();
+
+        let textures = textures(&path).unwrap();
+        let output = dir.join("package");
+        let summary = convert_textures(&path, &textures, &output).unwrap();
+        let png = std::fs::read(output.join("nodes/0/textures/0_0_1.bin.dds.png"));
+
+        let formats: Vec<_> = textures.iter().map(|t| t.format.clone()).collect();
+        assert_eq!(
+            formats,
+            vec![
+                Some(ImageFormat::Dds(Some("DXT1".to_string()))),
+                Some(ImageFormat::Ktx2 { basis: true }),
+            ]
+        );
+        assert_eq!(textures[0].dimensions, Some((4, 4)));
+        assert_eq!(
+            summary,
+            ConvertSummary {
+                converted: 1,
+                already_png: 0,
+                skipped: 1,
+            }
+        );
+        assert!(png.unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn atlases_are_cropped_by_the_regions_of_their_features() {
+        let dir = TempDir::new("atlas");
+        let path = dir.join("atlas.slpk");
+        // A red block, then a blue one.
+        let mut dds = dxt1_header(8, 4);
+        dds.extend_from_slice(&[0x00, 0x00, 0x00, 0xf8, 0x55, 0x55, 0x55, 0x55]);
+        dds.extend_from_slice(&[0x00, 0x00, 0x1f, 0x00, 0x55, 0x55, 0x55, 0x55]);
+        let (left, right) = ([0u16, 0, 32767, 65535], [32768u16, 0, 65535, 65535]);
+        let mut geometry = [9u32, 3]
+            .iter()
+            .flat_map(|n| n.to_le_bytes())
+            .collect::<Vec<u8>>();
+        geometry.extend(std::iter::repeat(0u8).take(9 * 12));
+        for region in [left, right, left].iter() {
+            for _ in 0..3 {
+                geometry.extend(region.iter().flat_map(|value| value.to_le_bytes()));
+            }
+        }
+        geometry.extend([7u64, 8, 9].iter().flat_map(|id| id.to_le_bytes()));
+        geometry.extend(
+            [0u32, 0, 1, 1, 2, 2]
+                .iter()
+                .flat_map(|face| face.to_le_bytes()),
+        );
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"nodePages":{"nodesPerPage":64},
+                    "geometryDefinitions":[
+                        {"geometryBuffers":[
+                            {"position":{"type":"Float32","component":3},
+                             "uvRegion":{"type":"UInt16","component":4},
+                             "featureId":{"type":"UInt64","component":1},
+                             "faceRange":{"type":"UInt32","component":2}}]},
+                        {"geometryBuffers":[{"compressedAttributes":{"encoding":"draco"}}]}],
+                    "materialDefinitions":[
+                        {"pbrMetallicRoughness":{"baseColorTexture":{"textureSetDefinitionId":0}}}],
+                    "textureSetDefinitions":[{"formats":[{"name":"0","format":"dds"}]}]}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[
+                    {"index":0,"mesh":{"geometry":{"definition":0,"resource":0},
+                                       "material":{"definition":0,"resource":0}}},
+                    {"index":1,"mesh":{"geometry":{"definition":1,"resource":1}}},
+                    {"index":2}]}"#,
+            )
+            .entry("nodes/0/geometries/0.bin.gz", &geometry)
+            .entry("nodes/0/textures/0.bin.dds.gz", &dds)
+            .entry("nodes/1/geometries/0.bin.gz", b"draco")
+            .write_to_file(&path)
+            .unwrap();
+
+        let output = dir.join("atlas");
+        let summary = split_atlases(&path, &textures(&path).unwrap(), &output).unwrap();
+        let folder = output.join("nodes/0/textures/atlas");
+        assert_eq!(
+            summary,
+            SplitSummary {
+                nodes: vec![NodeCrops {
+                    node_id: "0".to_string(),
+                    folder: folder.clone(),
+                    crops: 2,
+                    features: 3,
+                }],
+                skipped: 1,
+            }
+        );
+        let mut files: Vec<String> = std::fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["7.png", "8.png", "features.json"]);
+        let index = std::fs::read_to_string(folder.join("features.json")).unwrap();
+        assert_eq!(
+            crate::json::parse(&index).unwrap().to_compact_string(),
+            r#"{"7":"7.png","8":"8.png","9":"7.png"}"#
+        );
+
+        let atlas = image::decode(&dds).unwrap();
+        for (file, region) in &[("7.png", left), ("8.png", right)] {
+            let (x, y, width, height) = region_pixels(*region, 8, 4);
+            let png = std::fs::read(folder.join(file)).unwrap();
+            assert_eq!(png, atlas.crop(x, y, width, height).to_png());
+            assert_eq!(image::image_dimensions(&png), Some((4, 4)));
+        }
+        assert_eq!(region_pixels([0, 0, 0, 0], 8, 4), (0, 0, 1, 1));
+    }
+}
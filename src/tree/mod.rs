@@ -0,0 +1,262 @@
+// Reads the node hierarchy of a layer, for looking into how its levels of
+// detail were built without opening node pages one by one. The hierarchy is
+// read as the other commands read it, from the node pages of I3S 1.7 and
+// later, linking nodes by their `children` and `parentIndex`, or from the
+// node index document of each node in older layers. Whether a node has
+// geometry or textures comes from the folders stored below its resource
+// folder, which is what a viewer would load, rather than from what the node
+// says it has. Nodes of point clouds give their number of points where mesh
+// nodes give their features.
+//
+// Nodes are listed depth first from the root, each with its depth below the
+// root. Meshes with millions of nodes are cut off at `max_depth`, where the
+// number of nodes left out below each node is counted instead.
+
+use crate::archive;
+use crate::i3s::{self, Hierarchy, SceneLayer};
+use failure::Error;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// A node of the tree, with what is shown for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeNode {
+    /// The id of the node.
+    pub id: String,
+    /// The depth of the node, where the root is at depth 0.
+    pub depth: usize,
+    /// The LOD threshold of the node, when it has one.
+    pub lod_threshold: Option<f64>,
+    /// Only node pages record the feature count of a node.
+    pub feature_count: Option<u64>,
+    /// The number of points of a node of a point cloud, which has no
+    /// features.
+    pub point_count: Option<u64>,
+    /// Whether the node has a geometry resource.
+    pub has_geometry: bool,
+    /// Whether the node has a texture resource.
+    pub has_texture: bool,
+    /// How many nodes below this one are left out because they are deeper
+    /// than the maximum depth.
+    pub hidden_descendants: usize,
+}
+
+/// The node hierarchy of a layer, as `tree` reads it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeTree {
+    /// The nodes down to the maximum depth, depth first from the root.
+    pub nodes: Vec<TreeNode>,
+    /// The number of nodes at each depth, however deep.
+    pub nodes_per_level: Vec<usize>,
+    /// Nodes which can't be reached from the root.
+    pub unreachable: usize,
+}
+
+/// Reads the node hierarchy of the first layer of a package, down to
+/// `max_depth` below the root, or all of it when that is `None`.
+pub fn tree(slpk_file_path: &Path, max_depth: Option<usize>) -> Result<NodeTree, Error> {
+    let mut slpk_archive = archive::open_slpk_archive(slpk_file_path)?;
+    let layer = i3s::read_scene_layer(&mut slpk_archive)?;
+    tree_of(&mut slpk_archive, &layer, max_depth)
+}
+
+/// Reads the node hierarchy of `layer`, as `tree` does.
+pub fn tree_of<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    max_depth: Option<usize>,
+) -> Result<NodeTree, Error> {
+    let hierarchy = i3s::load_hierarchy(slpk_archive, layer)?;
+
+    // The folders, below `nodes/`, holding geometry or textures.
+    let mut geometry_folders = HashSet::new();
+    let mut texture_folders = HashSet::new();
+    for i in 0..slpk_archive.len() {
+        let entry = slpk_archive.by_index(i)?;
+        let name = match entry.name().strip_prefix(layer.root.as_str()) {
+            Some(name) if layer.contains(entry.name()) => name,
+            _ => continue,
+        };
+        let mut parts = name.split('/');
+        if let (Some("nodes"), Some(resource), Some(folder)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            match folder {
+                "geometries" => geometry_folders.insert(resource.to_string()),
+                "textures" => texture_folders.insert(resource.to_string()),
+                _ => false,
+            };
+        }
+    }
+
+    let mut tree = NodeTree::default();
+    let mut visited = vec![false; hierarchy.nodes.len()];
+    let mut stack: Vec<(usize, usize)> = hierarchy.roots.iter().rev().map(|&r| (r, 0)).collect();
+    while let Some((position, depth)) = stack.pop() {
+        if visited[position] {
+            continue;
+        }
+        visited[position] = true;
+        if tree.nodes_per_level.len() <= depth {
+            tree.nodes_per_level.resize(depth + 1, 0);
+        }
+        tree.nodes_per_level[depth] += 1;
+        let node = &hierarchy.nodes[position];
+        if max_depth.map_or(true, |max_depth| depth <= max_depth) {
+            let hidden_descendants = if max_depth == Some(depth) {
+                descendants(&hierarchy, position)
+            } else {
+                0
+            };
+            tree.nodes.push(TreeNode {
+                id: node.id.clone(),
+                depth,
+                lod_threshold: node.lod_threshold,
+                feature_count: node.feature_count,
+                point_count: node.point_count,
+                has_geometry: geometry_folders.contains(&node.resource),
+                has_texture: texture_folders.contains(&node.resource),
+                hidden_descendants,
+            });
+        }
+        stack.extend(node.children.iter().rev().map(|&child| (child, depth + 1)));
+    }
+    tree.unreachable = visited.iter().filter(|visited| !**visited).count();
+    Ok(tree)
+}
+
+/// The number of nodes below a node, each counted once.
+fn descendants(hierarchy: &Hierarchy, position: usize) -> usize {
+    let mut seen = HashSet::new();
+    let mut stack = hierarchy.nodes[position].children.clone();
+    while let Some(child) = stack.pop() {
+        if child != position && seen.insert(child) {
+            stack.extend(hierarchy.nodes[child].children.iter().copied());
+        }
+    }
+    seen.len()
+}
+
+/// The line for the node, without its indentation.
+impl fmt::Display for TreeNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(lod_threshold) = self.lod_threshold {
+            write!(f, ", LOD threshold {}", lod_threshold)?;
+        }
+        if let Some(feature_count) = self.feature_count {
+            write!(f, ", {} features", feature_count)?;
+        }
+        if let Some(point_count) = self.point_count {
+            write!(f, ", {} points", point_count)?;
+        }
+        if self.has_geometry {
+            write!(f, ", geometry")?;
+        }
+        if self.has_texture {
+            write!(f, ", texture")?;
+        }
+        if self.hidden_descendants > 0 {
+            write!(f, ", {} nodes below not shown", self.hidden_descendants)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    #[test]
+    fn nodes_are_nested_below_their_parents() {
+        let dir = TempDir::new("tree");
+        let path = dir.join("package.slpk");
+        // Node 3 is only linked to its parent by its parentIndex, and node 4
+        // to nothing.
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"nodePages":{"nodesPerPage":64}}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"index":0,"children":[1,2]},
+                    {"index":1,"lodThreshold":100,"children":[]},
+                    {"index":2,"lodThreshold":200.5,"mesh":{"geometry":{"resource":2,"featureCount":7}}},
+                    {"index":3,"parentIndex":2,"mesh":{"geometry":{"resource":3}}},
+                    {"index":4}]}"#,
+            )
+            .entry("nodes/2/geometries/0.bin.gz", &[0; 8])
+            .entry("nodes/2/textures/0.jpg", b"jpeg")
+            .entry("nodes/3/geometries/0.bin.gz", &[0; 8])
+            .write_to_file(&path)
+            .unwrap();
+        let full = tree(&path, None).unwrap();
+        let shallow = tree(&path, Some(1)).unwrap();
+
+        let lines: Vec<(usize, String)> = full
+            .nodes
+            .iter()
+            .map(|node| (node.depth, node.to_string()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, "0".to_string()),
+                (1, "1, LOD threshold 100".to_string()),
+                (
+                    1,
+                    "2, LOD threshold 200.5, 7 features, geometry, texture".to_string()
+                ),
+                (2, "3, geometry".to_string()),
+            ]
+        );
+        assert_eq!(full.nodes_per_level, [1, 2, 1]);
+        assert_eq!(full.unreachable, 1);
+
+        assert_eq!(shallow.nodes.len(), 3);
+        assert_eq!(shallow.nodes[2].hidden_descendants, 1);
+        assert_eq!(shallow.nodes_per_level, full.nodes_per_level);
+    }
+
+    #[test]
+    fn point_cloud_nodes_give_their_points() {
+        let dir = TempDir::new("tree-points");
+        let path = dir.join("package.slpk");
+        // The nodes of point clouds have no index, and give the range of
+        // their children.
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"PointCloud","store":{"version":"2.0","index":{"nodesPerPage":64}}}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"resourceId":0,"firstChild":1,"childCount":2,"vertexCount":30},
+                    {"resourceId":7,"vertexCount":20},{"resourceId":8,"vertexCount":10}]}"#,
+            )
+            .entry("nodes/0/geometries/0.bin.pccxyz", &[0; 8])
+            .entry("nodes/7/geometries/0.bin.pccxyz", &[0; 8])
+            .write_to_file(&path)
+            .unwrap();
+        let tree = tree(&path, None).unwrap();
+
+        let lines: Vec<(usize, String)> = tree
+            .nodes
+            .iter()
+            .map(|node| (node.depth, node.to_string()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, "0, 30 points, geometry".to_string()),
+                (1, "1, 20 points, geometry".to_string()),
+                (1, "2, 10 points".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// An atomic unpack writes into a folder next to the output folder, named
+// `.<name>.tmp-<pid>`, and only once every entry has been unpacked is the
+// old output folder replaced by it. A run which fails or is killed leaves the
+// old output as it was, and a folder under the output name is always one
+// which a run finished.
+//
+// Neither Windows nor Unix renames a folder over one which holds files, so
+// the old folder is first moved aside to `.<name>.old-<pid>`, and moved back
+// if the new one can't take its place.
+
+use super::UnpackError;
+use failure::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// A folder beside `target`, named after it and this process.
+fn sibling(target: &Path, kind: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    name.push(format!(".{}-{}", kind, std::process::id()));
+    target.with_file_name(name)
+}
+
+/// The folder an atomic unpack writes into, which is removed when it is
+/// dropped without being committed.
+pub struct AtomicFolder {
+    temporary: PathBuf,
+    target: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFolder {
+    /// Creates the folder to unpack into in place of `target`. With
+    /// `refuse_existing`, an existing `target` is an error, rather than
+    /// something to replace.
+    pub fn create(target: &Path, refuse_existing: bool) -> Result<AtomicFolder, Error> {
+        if target.exists() && !target.is_dir() {
+            return Err(Error::from(UnpackError::OutputFolderIsAFile));
+        }
+        if refuse_existing && target.is_dir() {
+            return Err(Error::from(UnpackError::OutputFolderExists(
+                target.to_string_lossy().into_owned(),
+            )));
+        }
+        let temporary = sibling(target, "tmp");
+        // Only a process with the same id, which has since died, can have
+        // left it.
+        if temporary.is_dir() {
+            std::fs::remove_dir_all(&temporary)?;
+        }
+        std::fs::create_dir(&temporary)?;
+        Ok(AtomicFolder {
+            temporary,
+            target: target.to_path_buf(),
+            committed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.temporary
+    }
+
+    /// The path `path` below the temporary folder has once it is committed.
+    pub fn committed_path(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.temporary) {
+            Ok(rest) => self.target.join(rest),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Puts the unpacked folder in place of the target, removing the old one.
+    pub fn commit(mut self) -> Result<(), Error> {
+        if !self.target.is_dir() {
+            std::fs::rename(&self.temporary, &self.target)?;
+            self.committed = true;
+            return Ok(());
+        }
+        info!("Replacing folder: {}", self.target.to_string_lossy());
+        let old = sibling(&self.target, "old");
+        std::fs::rename(&self.target, &old)?;
+        if let Err(e) = std::fs::rename(&self.temporary, &self.target) {
+            std::fs::rename(&old, &self.target)?;
+            return Err(Error::from(e));
+        }
+        self.committed = true;
+        std::fs::remove_dir_all(&old)?;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFolder {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_dir_all(&self.temporary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn old_output_survives_until_the_unpack_succeeds() {
+        let dir = TempDir::new("atomic");
+        let path = dir.join("package.slpk");
+        let broken = dir.join("broken.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodes/0/geometries/0.bin", b"new")
+            .write_to_file(&path)
+            .unwrap();
+        // The second entry has a name which is refused.
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodes/0/con/0.bin", b"new")
+            .write_to_file(&broken)
+            .unwrap();
+        let old_output = |folder: &Path| {
+            std::fs::create_dir_all(folder.join("nodes")).unwrap();
+            std::fs::write(folder.join("old.txt"), b"old").unwrap();
+        };
+        old_output(&dir.join("package"));
+        old_output(&dir.join("broken"));
+        let options = UnpackOptions::new().atomic(true).strict_paths(true);
+        let unpacked = unpack_with_options(&path, &options);
+        let failed = unpack_with_options(&broken, &options);
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with('.'))
+            .collect();
+        let read = |name: &str| std::fs::read(dir.join(name)).ok();
+        let files = (
+            read("package/nodes/0/geometries/0.bin"),
+            read("package/old.txt"),
+            read("broken/old.txt"),
+            read("broken/3dSceneLayer.json"),
+        );
+
+        assert_eq!(unpacked.unwrap().entries_unpacked, 2);
+        assert!(failed.is_err());
+        assert_eq!(files.0, Some(b"new".to_vec()));
+        assert_eq!(files.1, None);
+        assert_eq!(files.2, Some(b"old".to_vec()));
+        assert_eq!(files.3, None);
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+    }
+}
@@ -0,0 +1,103 @@
+// Cancelling an unpack from another thread, such as when the user of a GUI
+// clicks cancel. Workers check the token between entries, and reading an
+// entry fails once it is cancelled, so that a large entry doesn't hold the
+// unpack up either. Files are staged while an unpack can be cancelled, so
+// the file of an entry cut short is removed rather than left in the output.
+// The entries unpacked before the cancel are kept, unless the unpack is
+// atomic, in which case the temporary folder goes with them.
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag to cancel an unpack with, shared by every clone of it.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// A token which hasn't been cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Asks the unpacks holding a clone of the token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Fails reads once the token is cancelled.
+pub struct CancellableReader<'a, R> {
+    inner: R,
+    token: Option<&'a CancelToken>,
+}
+
+impl<'a, R: Read> CancellableReader<'a, R> {
+    pub fn new(inner: R, token: Option<&'a CancelToken>) -> CancellableReader<'a, R> {
+        CancellableReader { inner, token }
+    }
+}
+
+impl<R: Read> Read for CancellableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.token.is_some_and(CancelToken::is_cancelled) {
+            return Err(std::io::Error::other("the unpack was cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{cancelled_after, unpack_with_options, Progress, UnpackOptions};
+
+    #[test]
+    fn cancelling_stops_between_entries_and_keeps_only_whole_files() {
+        let dir = TempDir::new("cancel");
+        let path = dir.join("package.slpk");
+        let mut package = SyntheticPackage::new();
+        for i in 0..10 {
+            package = package.entry(&format!("nodes/{}/features/0.json", i), b"{}");
+        }
+        package.write_to_file(&path).unwrap();
+        let unpack_cancelled_after_two = |atomic: bool| {
+            let token = CancelToken::new();
+            let cancel = token.clone();
+            let options = UnpackOptions::new()
+                .threads(1)
+                .atomic(atomic)
+                .cancel(token)
+                .progress(Progress::new(move |done, _, _| {
+                    if done == 2 {
+                        cancel.cancel();
+                    }
+                }));
+            let error = unpack_with_options(&path, &options).unwrap_err();
+            let mut files = Vec::new();
+            crate::glob::walk(&dir.join("package"), None, &mut files).unwrap();
+            (cancelled_after(&error), files.len())
+        };
+        let kept = unpack_cancelled_after_two(false);
+        let atomic = unpack_cancelled_after_two(true);
+        let folders = std::fs::read_dir(&dir).unwrap().count();
+
+        assert_eq!(kept, (Some(2), 2));
+        // The output of the first run is left as it was, and the temporary
+        // folder of the atomic run is gone.
+        assert_eq!(atomic, (Some(2), 2));
+        assert_eq!(folders, 2);
+
+        let token = CancelToken::new();
+        let mut reader = CancellableReader::new(&b"data"[..], Some(&token));
+        assert_eq!(reader.read(&mut [0; 2]).unwrap(), 2);
+        token.cancel();
+        assert!(reader.read(&mut [0; 2]).is_err());
+    }
+}
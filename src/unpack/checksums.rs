@@ -0,0 +1,220 @@
+// A checksum manifest lists the SHA-256 of every file an unpack wrote, in
+// the format of `sha256sum`, so that the output can be checked later by
+// slpkg or by `sha256sum -c`. The files are hashed on their way to disk, and
+// the manifest is written once every worker has finished, in the order of
+// the paths. Paths are relative to the output folder and use forward
+// slashes, except for files routed outside of it, which are given in full.
+// A run which merges into an earlier output keeps the lines of the files it
+// didn't write, so that the manifest still lists all of them.
+
+use crate::md5::to_hex;
+use crate::sha256::Sha256;
+use failure::Error;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// The manifest of checksums written to the output folder.
+pub const CHECKSUM_FILE: &str = "manifest.sha256";
+
+#[derive(Debug, Fail)]
+pub enum ChecksumError {
+    #[fail(display = "{} has no {}", _0, _1)]
+    NoManifest(String, &'static str),
+
+    #[fail(display = "Line {} of the checksum manifest can't be read", _0)]
+    Malformed(usize),
+}
+
+/// The path of `file` as the manifest in `folder` lists it.
+pub fn listed_path(folder: &Path, file: &Path) -> String {
+    match file.strip_prefix(folder) {
+        Ok(relative) => relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => file.to_string_lossy().into_owned(),
+    }
+}
+
+/// A line of the manifest. As `sha256sum` does, a path holding a backslash or
+/// a newline is escaped and the line starts with a backslash.
+fn manifest_line(digest: &[u8; 32], path: &str) -> String {
+    if path.contains('\\') || path.contains('\n') {
+        let escaped = path.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{}  {}\n", to_hex(digest), escaped)
+    } else {
+        format!("{}  {}\n", to_hex(digest), path)
+    }
+}
+
+/// Writes the manifest of `files` into `folder`, along with the files an
+/// earlier manifest there lists which `files` doesn't.
+pub fn write_manifest(folder: &Path, files: &[(PathBuf, [u8; 32])]) -> Result<PathBuf, Error> {
+    let manifest_path = folder.join(CHECKSUM_FILE);
+    let mut lines: BTreeMap<String, String> = BTreeMap::new();
+    if let Ok(earlier) = std::fs::read_to_string(&manifest_path) {
+        for line in earlier.lines() {
+            if let Some((_, path)) = parse_line(line) {
+                lines.insert(path, format!("{}\n", line));
+            }
+        }
+    }
+    for (path, digest) in files {
+        let path = listed_path(folder, path);
+        let line = manifest_line(digest, &path);
+        lines.insert(path, line);
+    }
+    let manifest: String = lines.into_values().collect();
+    std::fs::write(&manifest_path, manifest)?;
+    Ok(manifest_path)
+}
+
+/// Parses a line of a manifest into the digest and path it lists.
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let digest = line.get(..64)?;
+    // A `*` marks a file hashed in binary mode, which is the same on Unix.
+    let path = line
+        .get(64..)?
+        .strip_prefix("  ")
+        .or_else(|| line.get(64..)?.strip_prefix(" *"))?;
+    if !digest.bytes().all(|b| b.is_ascii_hexdigit()) || path.is_empty() {
+        return None;
+    }
+    let path = if escaped {
+        path.replace("\\n", "\n").replace("\\\\", "\\")
+    } else {
+        path.to_string()
+    };
+    Some((digest.to_ascii_lowercase(), path))
+}
+
+fn file_digest(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::default();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// A file of the manifest which isn't as it was unpacked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChecksumMismatch {
+    /// The path of the file, relative to the output folder.
+    pub path: String,
+    /// Whether the file is missing or changed.
+    pub problem: String,
+}
+
+/// What checking an output folder against its manifest found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChecksumReport {
+    /// The files of the manifest.
+    pub files: usize,
+    /// The files which are missing or changed, in the order of the manifest.
+    pub mismatches: Vec<ChecksumMismatch>,
+}
+
+/// Checks every file the manifest in `folder` lists against its digest.
+pub fn check_manifest(folder: &Path) -> Result<ChecksumReport, Error> {
+    let manifest = match std::fs::read_to_string(folder.join(CHECKSUM_FILE)) {
+        Ok(manifest) => manifest,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::from(ChecksumError::NoManifest(
+                folder.to_string_lossy().into_owned(),
+                CHECKSUM_FILE,
+            )))
+        }
+        Err(e) => return Err(Error::from(e)),
+    };
+    let mut report = ChecksumReport::default();
+    for (i, line) in manifest.lines().enumerate() {
+        let (digest, path) = parse_line(line).ok_or(ChecksumError::Malformed(i + 1))?;
+        report.files += 1;
+        let problem = match file_digest(&folder.join(&path)) {
+            Ok(actual) if to_hex(&actual) == digest => continue,
+            Ok(_) => "the file has changed since it was unpacked".to_string(),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                "the file is missing".to_string()
+            }
+            Err(e) => format!("the file can't be read: {}", e),
+        };
+        report.mismatches.push(ChecksumMismatch { path, problem });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn manifests_list_every_file_and_catch_changes() {
+        let dir = TempDir::new("checksums");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry("nodes/0/geometries/0.bin", b"abc")
+            .entry("nodes/0/back\\slash.bin", b"")
+            .write_to_file(&path)
+            .unwrap();
+        unpack_with_options(&path, &UnpackOptions::new().checksum_manifest(true)).unwrap();
+        let folder = dir.join("package");
+        let manifest = std::fs::read_to_string(folder.join(CHECKSUM_FILE)).unwrap();
+        let unchanged = check_manifest(&folder).unwrap();
+        std::fs::write(folder.join("3dSceneLayer.json"), b"{ }").unwrap();
+        std::fs::remove_file(folder.join("nodes/0/geometries/0.bin")).unwrap();
+        let changed = check_manifest(&folder).unwrap();
+
+        let lines: Vec<&str> = manifest.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a  3dSceneLayer.json"
+        );
+        // The zip library reads the backslash as a separator.
+        assert!(lines[1].ends_with("  nodes/0/back/slash.bin"));
+        assert_eq!(
+            lines[2],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  nodes/0/geometries/0.bin"
+        );
+        assert_eq!((unchanged.files, unchanged.mismatches.len()), (3, 0));
+        assert_eq!(
+            changed.mismatches,
+            vec![
+                ChecksumMismatch {
+                    path: "3dSceneLayer.json".to_string(),
+                    problem: "the file has changed since it was unpacked".to_string(),
+                },
+                ChecksumMismatch {
+                    path: "nodes/0/geometries/0.bin".to_string(),
+                    problem: "the file is missing".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_line(
+                "\\e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  a\\\\b"
+            ),
+            Some((
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string(),
+                "a\\b".to_string()
+            ))
+        );
+    }
+}
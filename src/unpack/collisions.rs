@@ -0,0 +1,186 @@
+// Finds entries which would be unpacked to the same file on a filesystem
+// which ignores case, as those of Windows and macOS do by default, before
+// anything is written. Two such entries, such as `nodes/0/textures/0.JPG`
+// and `nodes/0/textures/0.jpg`, would otherwise be written one over the
+// other there, leaving whichever worker finished last, and on Linux both
+// would be written, so the same package would unpack differently from one
+// machine to the next. The check is the same on every platform for that
+// reason. Files are compared by the path they are written to, so an entry
+// named `.gz` collides with the plain entry its name gives once the suffix
+// is dropped. Entries with exactly the same name aren't collisions, as the
+// last one in the package has always been the one unpacked, unless the name
+// of one of them was escaped to be valid on Windows, which the package
+// didn't ask for.
+//
+// Collisions are renamed in the order of their paths, so the same package is
+// renamed the same way however its entries are stored: the first keeps its
+// name, and each after it gets `~1`, `~2` and so on before its extensions,
+// as in `0~1.jpg`, passing over any name another entry already has.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// The entries of a package whose files collide when case is ignored.
+#[derive(Debug, Default)]
+pub struct CaseCollisions {
+    /// Each entry whose file collides with that of an entry with a path
+    /// before it, with that entry.
+    pub pairs: Vec<(PathBuf, PathBuf)>,
+    /// How many of the pairs have an entry whose name was escaped, which
+    /// are renamed whether or not other collisions are.
+    pub escaped_pairs: usize,
+    /// The path each colliding entry is unpacked to when collisions are
+    /// renamed, by the position of the entry in the package.
+    renamed: HashMap<usize, PathBuf>,
+}
+
+impl CaseCollisions {
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// The path an entry is unpacked to in place of its own, if it is
+    /// renamed.
+    pub fn renamed(&self, entry_idx: usize) -> Option<&Path> {
+        self.renamed.get(&entry_idx).map(PathBuf::as_path)
+    }
+
+    /// The positions of the entries which are renamed.
+    pub fn renamed_entries(&self) -> impl Iterator<Item = usize> + '_ {
+        self.renamed.keys().copied()
+    }
+
+    /// The colliding pairs, one to a line.
+    pub fn describe(&self) -> String {
+        self.pairs
+            .iter()
+            .map(|(path, other)| format!("{} and {}", other.display(), path.display()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The path of the file an entry is written to, below the output folder, in
+/// lower case.
+fn folded_target(path: &Path, keep_gzip: bool) -> Option<String> {
+    let name = super::target_file_name(path, keep_gzip).1?;
+    let target = path.with_file_name(name);
+    Some(target.to_string_lossy().to_lowercase())
+}
+
+/// `path` with `~<n>` before the extensions of its file name.
+fn suffixed(path: &Path, n: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    // A leading dot starts the name rather than its extensions.
+    let split = name
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '.')
+        .map_or(name.len(), |(i, _)| i);
+    path.with_file_name(format!("{}~{}{}", &name[..split], n, &name[split..]))
+}
+
+/// Finds the collisions between the files of `entries`, given by their
+/// position in the package and their sanitized path, where the names of
+/// those in `escaped` were escaped for Windows.
+pub fn find_case_collisions(
+    entries: Vec<(usize, PathBuf)>,
+    escaped: &HashSet<usize>,
+    keep_gzip: bool,
+) -> CaseCollisions {
+    let mut entries: Vec<(usize, PathBuf, String)> = entries
+        .into_iter()
+        .filter_map(|(entry_idx, path)| {
+            let folded = folded_target(&path, keep_gzip)?;
+            Some((entry_idx, path, folded))
+        })
+        .collect();
+    entries.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+
+    let mut collisions = CaseCollisions::default();
+    let taken: HashSet<&str> = entries
+        .iter()
+        .map(|(_, _, folded)| folded.as_str())
+        .collect();
+    let mut first_of: HashMap<&str, (usize, &Path)> = HashMap::new();
+    let mut renames: HashSet<String> = HashSet::new();
+    for (entry_idx, path, folded) in &entries {
+        let (first_idx, first) = *first_of
+            .entry(folded.as_str())
+            .or_insert((*entry_idx, path.as_path()));
+        let escape = escaped.contains(entry_idx) || escaped.contains(&first_idx);
+        if first_idx == *entry_idx || (first == path.as_path() && !escape) {
+            continue;
+        }
+        collisions.pairs.push((path.clone(), first.to_path_buf()));
+        if escape {
+            collisions.escaped_pairs += 1;
+        }
+        let renamed = (1..)
+            .map(|n| suffixed(path, n))
+            .find(|renamed| {
+                let folded = folded_target(renamed, keep_gzip).unwrap_or_default();
+                !taken.contains(folded.as_str()) && !renames.contains(&folded)
+            })
+            .unwrap();
+        renames.insert(folded_target(&renamed, keep_gzip).unwrap_or_default());
+        collisions.renamed.insert(*entry_idx, renamed);
+    }
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collisions_are_renamed_in_the_order_of_their_paths() {
+        let entries = [
+            "textures/0.jpg",
+            "Textures/0.JPG",
+            "textures/0~1.jpg",
+            "TEXTURES/0.jpg",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .chain(vec![
+            PathBuf::from("nodes/0/3dNodeIndexDocument.json.gz"),
+            PathBuf::from("nodes/0/3dNodeIndexDocument.json"),
+            PathBuf::from("nodes/0/.hidden"),
+            PathBuf::from("nodes/0/.HIDDEN"),
+            PathBuf::from("nodes/0/same.bin"),
+            PathBuf::from("nodes/0/same.bin"),
+        ])
+        .enumerate()
+        .collect();
+        let collisions = find_case_collisions(entries, &HashSet::new(), false);
+
+        let renamed: Vec<(usize, &Path)> = (0..10)
+            .filter_map(|i| collisions.renamed(i).map(|renamed| (i, renamed)))
+            .collect();
+        assert_eq!(
+            renamed,
+            vec![
+                (0, Path::new("textures/0~3.jpg")),
+                (1, Path::new("Textures/0~2.JPG")),
+                (4, Path::new("nodes/0/3dNodeIndexDocument~1.json.gz")),
+                (6, Path::new("nodes/0/.hidden~1")),
+            ]
+        );
+        assert_eq!(
+            collisions.pairs[0],
+            (
+                PathBuf::from("Textures/0.JPG"),
+                PathBuf::from("TEXTURES/0.jpg")
+            )
+        );
+        assert_eq!(collisions.pairs.len(), 4);
+
+        // Kept gzipped, the document and its plain copy don't collide.
+        let gzipped = vec![
+            (0, PathBuf::from("a.json.gz")),
+            (1, PathBuf::from("a.json")),
+        ];
+        assert!(find_case_collisions(gzipped, &HashSet::new(), true).is_empty());
+    }
+}
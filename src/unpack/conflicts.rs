@@ -0,0 +1,246 @@
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::DateTime;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Unknown conflict policy '{}', expected overwrite, skip, newer, complete or error",
+    _0
+)]
+pub struct UnknownConflictPolicy(String);
+
+/// What to do about an entry whose output file already exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    /// Write over the existing file.
+    Overwrite,
+    /// Leave the existing file as it is.
+    Skip,
+    /// Overwrite only when the entry was modified after the existing file.
+    Newer,
+    /// Keep the existing file when it is complete, for carrying on with an
+    /// unpack which was interrupted, and overwrite it otherwise.
+    Complete,
+    /// Write nothing for conflicting entries, and fail once every entry has
+    /// been checked.
+    Error,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = UnknownConflictPolicy;
+
+    fn from_str(s: &str) -> Result<ConflictPolicy, UnknownConflictPolicy> {
+        match s {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "newer" => Ok(ConflictPolicy::Newer),
+            "complete" => Ok(ConflictPolicy::Complete),
+            "error" => Ok(ConflictPolicy::Error),
+            _ => Err(UnknownConflictPolicy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    /// There is no existing file.
+    Write,
+    Overwrite,
+    Skip,
+    /// The policy is `Complete`, and the existing file is.
+    AlreadyUnpacked,
+    /// The policy is `Error`, so the conflict is reported instead.
+    Refuse,
+}
+
+/// Converts a zip timestamp to a system time. Zip timestamps carry no time
+/// zone; they are taken to be UTC, as most packaging tools write them.
+fn entry_time(modified: DateTime) -> SystemTime {
+    // Days since the epoch of a date in the proleptic Gregorian calendar.
+    let (year, month, day) = (
+        i64::from(modified.year()),
+        i64::from(modified.month()),
+        i64::from(modified.day()),
+    );
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400
+        + i64::from(modified.hour()) * 3_600
+        + i64::from(modified.minute()) * 60
+        + i64::from(modified.second());
+    // Zip timestamps start in 1980, so this is never before the epoch.
+    UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64)
+}
+
+/// The time an entry was modified, for its output file, or `None` when the
+/// zip header holds no real time: a zeroed DOS timestamp, the start of the
+/// zip epoch which some tools write for "unknown", or an impossible date.
+pub fn recorded_time(modified: DateTime) -> Option<SystemTime> {
+    let midnight = (modified.hour(), modified.minute(), modified.second()) == (0, 0, 0);
+    let zip_epoch = modified.year() == 1980 && modified.month() <= 1 && modified.day() <= 1;
+    let impossible = !(1..=12).contains(&modified.month())
+        || !(1..=31).contains(&modified.day())
+        || modified.hour() > 23
+        || modified.minute() > 59
+        || modified.second() > 60;
+    if (zip_epoch && midnight) || impossible {
+        None
+    } else {
+        Some(entry_time(modified))
+    }
+}
+
+/// What an entry's output file holds when it is written, for telling
+/// whether an existing one is complete.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExpectedFile {
+    /// The entry byte for byte, with its size and CRC-32.
+    Copy { size: u64, crc32: u32 },
+    /// The entry decompressed or reformatted, whose size isn't known until
+    /// it is written.
+    Transformed,
+}
+
+/// Whether the file at `path` is the whole of what was to be written. A
+/// transformed file can only be checked for having been written at all,
+/// which since files are staged when unpacking into an existing folder
+/// means it was written to its end.
+fn is_complete(path: &Path, length: u64, expected: ExpectedFile) -> std::io::Result<bool> {
+    let (size, crc32) = match expected {
+        ExpectedFile::Copy { size, crc32 } => (size, crc32),
+        ExpectedFile::Transformed => return Ok(length > 0),
+    };
+    if length != size {
+        return Ok(false);
+    }
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hasher.finalize() == crc32)
+}
+
+/// Decides what to do about the output file of an entry. This costs a
+/// single stat of the target when it doesn't exist yet.
+pub fn resolve(
+    policy: ConflictPolicy,
+    target_file_path: &Path,
+    entry_modified: DateTime,
+    expected: ExpectedFile,
+) -> std::io::Result<Resolution> {
+    let metadata = match std::fs::symlink_metadata(target_file_path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Resolution::Write),
+        Err(e) => return Err(e),
+    };
+    Ok(match policy {
+        ConflictPolicy::Overwrite => Resolution::Overwrite,
+        ConflictPolicy::Skip => Resolution::Skip,
+        ConflictPolicy::Error => Resolution::Refuse,
+        ConflictPolicy::Newer => {
+            if entry_time(entry_modified) > metadata.modified()? {
+                Resolution::Overwrite
+            } else {
+                Resolution::Skip
+            }
+        }
+        ConflictPolicy::Complete => {
+            if metadata.is_file() && is_complete(target_file_path, metadata.len(), expected)? {
+                Resolution::AlreadyUnpacked
+            } else {
+                Resolution::Overwrite
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn zip_times_convert_to_system_times() {
+        let modified = DateTime::from_date_and_time(2019, 3, 1, 12, 30, 10).unwrap();
+        assert_eq!(
+            entry_time(modified),
+            UNIX_EPOCH + Duration::from_secs(1_551_443_410)
+        );
+        assert_eq!(recorded_time(modified), Some(entry_time(modified)));
+        let epoch = DateTime::from_date_and_time(1980, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(recorded_time(epoch), None);
+        assert_eq!(recorded_time(DateTime::from_msdos(0, 0)), None);
+    }
+
+    #[test]
+    fn newer_compares_against_the_existing_file() {
+        let dir = TempDir::new("conflicts");
+        let path = dir.join("0.bin");
+        let old = DateTime::from_date_and_time(1990, 1, 1, 0, 0, 0).unwrap();
+        let future = DateTime::from_date_and_time(2100, 1, 1, 0, 0, 0).unwrap();
+
+        let any = ExpectedFile::Transformed;
+        let missing = resolve(ConflictPolicy::Error, &path, old, any).unwrap();
+        std::fs::write(&path, b"existing").unwrap();
+        let resolutions = [
+            resolve(ConflictPolicy::Newer, &path, old, any).unwrap(),
+            resolve(ConflictPolicy::Newer, &path, future, any).unwrap(),
+            resolve(ConflictPolicy::Error, &path, old, any).unwrap(),
+        ];
+
+        assert_eq!(missing, Resolution::Write);
+        assert_eq!(
+            resolutions,
+            [Resolution::Skip, Resolution::Overwrite, Resolution::Refuse]
+        );
+    }
+
+    #[test]
+    fn complete_checks_the_size_and_checksum_of_copies() {
+        let dir = TempDir::new("complete");
+        let path = dir.join("0.bin");
+        let modified = DateTime::from_date_and_time(2019, 3, 1, 12, 30, 10).unwrap();
+        let copy = |contents: &[u8]| ExpectedFile::Copy {
+            size: contents.len() as u64,
+            crc32: {
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(contents);
+                hasher.finalize()
+            },
+        };
+        let resolve = |expected| resolve(ConflictPolicy::Complete, &path, modified, expected);
+
+        std::fs::write(&path, b"existing").unwrap();
+        let resolutions = [
+            resolve(copy(b"existing")).unwrap(),
+            resolve(copy(b"existin")).unwrap(),
+            resolve(copy(b"EXISTING")).unwrap(),
+            resolve(ExpectedFile::Transformed).unwrap(),
+        ];
+        std::fs::write(&path, b"").unwrap();
+        let empty = resolve(ExpectedFile::Transformed).unwrap();
+
+        assert_eq!(
+            resolutions,
+            [
+                Resolution::AlreadyUnpacked,
+                Resolution::Overwrite,
+                Resolution::Overwrite,
+                Resolution::AlreadyUnpacked,
+            ]
+        );
+        assert_eq!(empty, Resolution::Overwrite);
+    }
+}
@@ -0,0 +1,208 @@
+// A time limit on unpacking, for jobs with a hard wall-clock budget. Workers
+// check it between entries, and reading an entry fails once it has passed,
+// so that a huge entry doesn't overrun it either. Files are staged while a
+// time limit applies, so the one being written when time runs out is removed
+// rather than left cut short. What is left to unpack is recorded in a resume
+// file in the output folder, which a later run with --resume picks up.
+
+use crate::json;
+use crate::json::Value;
+use failure::Error;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const RESUME_FILE: &str = "slpkg-resume.json";
+
+#[derive(Debug, Fail)]
+pub enum DeadlineError {
+    #[fail(
+        display = "Invalid duration '{}', expected a number with an optional unit of ms, s, m or h",
+        _0
+    )]
+    InvalidDuration(String),
+
+    #[fail(display = "The resume file {} is not valid", _0)]
+    InvalidResumeFile(String),
+}
+
+/// A duration written as a number with an optional unit, seconds by default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeLimit(pub Duration);
+
+impl FromStr for TimeLimit {
+    type Err = DeadlineError;
+
+    fn from_str(s: &str) -> Result<TimeLimit, DeadlineError> {
+        let invalid = || DeadlineError::InvalidDuration(s.to_string());
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        let seconds = match unit.trim().to_ascii_lowercase().as_str() {
+            "ms" => number / 1000.0,
+            "" | "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 3600.0,
+            _ => return Err(invalid()),
+        };
+        Ok(TimeLimit(Duration::from_secs_f64(seconds)))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    pub fn passed(self) -> bool {
+        Instant::now() >= self.0
+    }
+}
+
+/// Fails reads once the deadline has passed.
+pub struct DeadlineReader<R> {
+    inner: R,
+    deadline: Option<Deadline>,
+}
+
+impl<R: Read> DeadlineReader<R> {
+    pub fn new(inner: R, deadline: Option<Deadline>) -> DeadlineReader<R> {
+        DeadlineReader { inner, deadline }
+    }
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.deadline.is_some_and(Deadline::passed) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "the time limit has passed",
+            ));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Records what a timed out run left to unpack, with how far it got.
+pub fn write_resume_file(
+    unpack_folder: &Path,
+    elapsed: Duration,
+    entries_unpacked: usize,
+    remaining: &[String],
+) -> Result<(), Error> {
+    let report = Value::Object(vec![
+        ("status".to_string(), Value::from("timed out")),
+        ("seconds".to_string(), Value::from(elapsed.as_secs_f64())),
+        ("entriesUnpacked".to_string(), Value::from(entries_unpacked)),
+        (
+            "remaining".to_string(),
+            Value::Array(
+                remaining
+                    .iter()
+                    .map(|name| Value::from(name.as_str()))
+                    .collect(),
+            ),
+        ),
+    ]);
+    std::fs::write(
+        unpack_folder.join(RESUME_FILE),
+        report.to_pretty_string(2) + "\n",
+    )?;
+    Ok(())
+}
+
+/// The entries a timed out run left to unpack, removing its resume file so
+/// that only a run which times out again leaves one. `None` when there is no
+/// resume file, as after a run which finished.
+pub fn take_resume_file(unpack_folder: &Path) -> Result<Option<HashSet<String>>, Error> {
+    let remaining = read_resume_file(unpack_folder)?;
+    if remaining.is_some() {
+        std::fs::remove_file(unpack_folder.join(RESUME_FILE))?;
+    }
+    Ok(remaining)
+}
+
+/// The entries a timed out run left to unpack, leaving its resume file.
+pub fn read_resume_file(unpack_folder: &Path) -> Result<Option<HashSet<String>>, Error> {
+    let path = unpack_folder.join(RESUME_FILE);
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::from(e)),
+    };
+    let invalid = || DeadlineError::InvalidResumeFile(path.to_string_lossy().into_owned());
+    let report = json::parse_bytes(&contents).map_err(|_| invalid())?;
+    let remaining = report
+        .get("remaining")
+        .and_then(Value::as_array)
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|name| name.as_str().map(str::to_string).ok_or_else(invalid))
+        .collect::<Result<HashSet<String>, DeadlineError>>()?;
+    Ok(Some(remaining))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{is_timeout, unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn timed_out_unpacks_resume_where_they_stopped() {
+        let dir = TempDir::new("deadline");
+        let mut package = SyntheticPackage::new();
+        for i in 0..200 {
+            package = package.entry(
+                &format!("nodes/{}/geometries/0.bin", i),
+                &vec![i as u8; 32 * 1024],
+            );
+        }
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let folder = dir.join("package");
+        let expected = package.expected_files(false);
+
+        for timeout in [Duration::ZERO, Duration::from_micros(500)] {
+            let options = UnpackOptions::new().threads(2).timeout(timeout);
+            let result = unpack_with_options(&path, &options);
+            if timeout == Duration::ZERO {
+                assert!(is_timeout(result.as_ref().unwrap_err()));
+            }
+            // Whatever was unpacked is complete, and the rest is listed for
+            // resuming.
+            let remaining: Vec<String> = match std::fs::read(folder.join(RESUME_FILE)) {
+                Ok(report) => json::parse_bytes(&report)
+                    .unwrap()
+                    .get("remaining")
+                    .and_then(Value::as_array)
+                    .unwrap()
+                    .iter()
+                    .map(|name| name.as_str().unwrap().to_string())
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            assert_eq!(result.is_err(), !remaining.is_empty());
+            if timeout == Duration::ZERO {
+                assert_eq!(remaining.len(), expected.len());
+            }
+            for (file, contents) in &expected {
+                match std::fs::read(folder.join(file)) {
+                    Ok(written) => assert_eq!(&written, contents),
+                    Err(_) => assert!(remaining.contains(&file.to_string_lossy().into_owned())),
+                }
+            }
+
+            let resumed = UnpackOptions::new().resume(true);
+            unpack_with_options(&path, &resumed).unwrap();
+            assert!(!folder.join(RESUME_FILE).exists());
+            for (file, contents) in &expected {
+                assert_eq!(&std::fs::read(folder.join(file)).unwrap(), contents);
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+// Links payloads identical to one already unpacked to its file, rather than
+// writing them again. Payloads are known by their SHA-256, as the checksum
+// manifest gives it, so a duplicate is found without reading the file it
+// duplicates back from disk. A file which is written over by a later entry
+// of the same name stops standing for its earlier payload.
+
+use super::staging;
+use crate::sha256::Sha256;
+use failure::Error;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Unknown dedup mode '{}', expected hardlink, symlink or copy",
+    _0
+)]
+pub struct UnknownDedupMode(String);
+
+/// What to write in place of a payload which was already extracted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DedupMode {
+    /// Link the file to the first one with the same payload.
+    Hardlink,
+    /// Point the file at the first one with the same payload.
+    Symlink,
+    /// Write the duplicate out again, only reporting the space it takes.
+    Copy,
+}
+
+impl FromStr for DedupMode {
+    type Err = UnknownDedupMode;
+
+    fn from_str(s: &str) -> Result<DedupMode, UnknownDedupMode> {
+        match s {
+            "hardlink" => Ok(DedupMode::Hardlink),
+            "symlink" => Ok(DedupMode::Symlink),
+            "copy" => Ok(DedupMode::Copy),
+            _ => Err(UnknownDedupMode(s.to_string())),
+        }
+    }
+}
+
+/// What deduplicating payloads saved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DedupStats {
+    /// Payloads which duplicated one already written.
+    pub duplicates: usize,
+    /// Bytes not written because a duplicate was linked.
+    pub bytes_saved: u64,
+    /// Duplicates written as copies because linking failed, for example
+    /// because the two paths are on different filesystems.
+    pub link_failures: usize,
+}
+
+impl fmt::Display for DedupStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} duplicate payloads, {} bytes saved",
+            self.duplicates, self.bytes_saved
+        )?;
+        if self.link_failures > 0 {
+            write!(
+                f,
+                ", {} could not be linked and were copied",
+                self.link_failures
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Shared between the worker threads, so that a payload written by one
+/// thread is linked to by the others.
+#[derive(Debug)]
+pub struct Deduplicator {
+    mode: DedupMode,
+    written: Mutex<Written>,
+    stats: Mutex<DedupStats>,
+}
+
+/// The files written, by the SHA-256 of their payload and the other way
+/// round.
+#[derive(Debug, Default)]
+struct Written {
+    by_digest: HashMap<[u8; 32], PathBuf>,
+    digests: HashMap<PathBuf, [u8; 32]>,
+}
+
+/// The path to `target` relative to the folder `from_dir`. Both paths must
+/// be relative to the same folder, or both absolute.
+fn relative_path(from_dir: &Path, target: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = target.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component.as_os_str());
+    }
+    relative
+}
+
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+impl Deduplicator {
+    pub fn new(mode: DedupMode) -> Deduplicator {
+        Deduplicator {
+            mode,
+            written: Mutex::new(Written::default()),
+            stats: Mutex::new(DedupStats::default()),
+        }
+    }
+
+    fn link(&self, original: &Path, target: &Path) -> std::io::Result<()> {
+        match self.mode {
+            DedupMode::Hardlink => std::fs::hard_link(original, target),
+            DedupMode::Symlink => {
+                let target_dir = target.parent().unwrap_or_else(|| Path::new(""));
+                symlink(&relative_path(target_dir, original), target)
+            }
+            DedupMode::Copy => Err(std::io::Error::other("copy mode doesn't link")),
+        }
+    }
+
+    /// Writes `contents` to `target`, or links `target` to an identical
+    /// payload written earlier. Written files are staged by `staging`'s
+    /// worker, if any.
+    pub fn write(
+        &self,
+        contents: &[u8],
+        target: &Path,
+        staging: Option<usize>,
+    ) -> Result<(), Error> {
+        let write = |target: &Path| {
+            staging::write_staged(target, staging, |path| Ok(std::fs::write(path, contents)?))
+        };
+        let digest = sha256(contents);
+        self.forget(target);
+        let original = self.written.lock().unwrap().by_digest.get(&digest).cloned();
+
+        // Routed folders aren't cleared before unpacking, and writing through
+        // a link left there by an earlier run would change the original too.
+        if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(target)?;
+        }
+
+        if let Some(original) = original {
+            let linked = self.mode != DedupMode::Copy && self.link(&original, target).is_ok();
+            if !linked {
+                write(target)?;
+            }
+
+            let mut stats = self.stats.lock().unwrap();
+            stats.duplicates += 1;
+            if linked {
+                stats.bytes_saved += contents.len() as u64;
+            } else if self.mode != DedupMode::Copy {
+                stats.link_failures += 1;
+            }
+            return Ok(());
+        }
+
+        write(target)?;
+        let mut written = self.written.lock().unwrap();
+        written.by_digest.insert(digest, target.to_path_buf());
+        written.digests.insert(target.to_path_buf(), digest);
+        Ok(())
+    }
+
+    /// Stops linking to `target`, which is being written over with a
+    /// payload which isn't deduplicated.
+    pub fn forget(&self, target: &Path) {
+        let mut written = self.written.lock().unwrap();
+        if let Some(earlier) = written.digests.remove(target) {
+            if written.by_digest.get(&earlier).map(PathBuf::as_path) == Some(target) {
+                written.by_digest.remove(&earlier);
+            }
+        }
+    }
+
+    pub fn into_stats(self) -> DedupStats {
+        self.stats.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    fn scratch_dir(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("dedup-{}", name));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        dir
+    }
+
+    fn dedup_in(mode: DedupMode, name: &str) -> (DedupStats, TempDir) {
+        let dir = scratch_dir(name);
+        let dedup = Deduplicator::new(mode);
+        dedup.write(b"texture", &dir.join("a/0.jpg"), None).unwrap();
+        dedup
+            .write(b"texture", &dir.join("b/0.jpg"), Some(1))
+            .unwrap();
+        dedup
+            .write(b"other", &dir.join("b/1.jpg"), Some(1))
+            .unwrap();
+        assert_eq!(std::fs::read(dir.join("b/0.jpg")).unwrap(), b"texture");
+        (dedup.into_stats(), dir)
+    }
+
+    #[test]
+    fn hardlinks_duplicates() {
+        let (stats, _dir) = dedup_in(DedupMode::Hardlink, "hardlink");
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.bytes_saved, 7);
+        assert_eq!(stats.link_failures, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_relative() {
+        let (stats, dir) = dedup_in(DedupMode::Symlink, "symlink");
+        let link = std::fs::read_link(dir.join("b/0.jpg")).unwrap();
+        assert_eq!(link, Path::new("../a/0.jpg"));
+        assert_eq!(stats.bytes_saved, 7);
+    }
+
+    #[test]
+    fn copy_mode_only_reports() {
+        let (stats, _dir) = dedup_in(DedupMode::Copy, "copy");
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.bytes_saved, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_across_filesystems_fall_back_to_copies() {
+        use std::os::unix::fs::MetadataExt;
+
+        // Hard links can't cross filesystems, which happens when a class of
+        // resource is routed to another disk. /dev/shm is usually a tmpfs.
+        let dir = scratch_dir("cross-device");
+        let other_fs = Path::new("/dev/shm").join(format!("slpkg-dedup-{}", std::process::id()));
+        let device = |path: &Path| std::fs::metadata(path).map(|m| m.dev()).ok();
+        if std::fs::create_dir_all(&other_fs).is_err() || device(&dir) == device(&other_fs) {
+            let _ = std::fs::remove_dir_all(&other_fs);
+            return;
+        }
+
+        let dedup = Deduplicator::new(DedupMode::Hardlink);
+        dedup.write(b"texture", &dir.join("a/0.jpg"), None).unwrap();
+        dedup
+            .write(b"texture", &other_fs.join("0.jpg"), None)
+            .unwrap();
+        let copied = std::fs::read(other_fs.join("0.jpg")).unwrap();
+        std::fs::remove_dir_all(&other_fs).unwrap();
+
+        assert_eq!(copied, b"texture");
+        let stats = dedup.into_stats();
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.bytes_saved, 0);
+        assert_eq!(stats.link_failures, 1);
+    }
+
+    #[test]
+    fn files_written_over_stop_standing_for_their_payload() {
+        let dir = scratch_dir("written-over");
+        let dedup = Deduplicator::new(DedupMode::Hardlink);
+        dedup.write(b"texture", &dir.join("a/0.jpg"), None).unwrap();
+        dedup.write(b"other", &dir.join("a/0.jpg"), None).unwrap();
+        dedup.write(b"texture", &dir.join("b/0.jpg"), None).unwrap();
+        let contents = std::fs::read(dir.join("b/0.jpg")).unwrap();
+
+        assert_eq!(contents, b"texture");
+        assert_eq!(dedup.into_stats().duplicates, 0);
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("out/b/c"), Path::new("out/a/0.jpg")),
+            Path::new("../../a/0.jpg")
+        );
+    }
+}
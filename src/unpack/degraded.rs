@@ -0,0 +1,147 @@
+// Entries named .gz whose gzip member can't be decoded, such as one cut short
+// by an exporter which crashed, would fail the whole unpack, and with
+// `keep_going` would still be lost. Instead, such an entry is copied as it is,
+// under its .gz name, with a warning giving the decode error, and listed
+// among the degraded entries of the summary, so the bytes the package holds
+// can still be looked at or repaired. With `strict_content` they fail as
+// before.
+//
+// Only errors of the decoder itself lead to a copy. An error of the stream
+// beneath it, such as a failed read, a wrong CRC, a cancelled unpack or the
+// time limit, fails the entry as any other error does. Whatever the decoder
+// had already written is removed before the copy, so that no file is left
+// which holds the start of a document and looks complete.
+
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, Read};
+
+#[derive(Debug, Fail)]
+#[fail(display = "{} isn't a valid gzip member: {}", entry, cause)]
+pub struct MalformedGzip {
+    pub entry: String,
+    pub cause: String,
+}
+
+/// Watches a gzip decoder, through `source` under it and `decoded` over it,
+/// for the first error which came from the decoder rather than from the
+/// stream it reads.
+#[derive(Default)]
+pub struct DecodeWatch {
+    source_failed: Cell<bool>,
+    decode_error: RefCell<Option<String>>,
+}
+
+impl DecodeWatch {
+    /// The stream the decoder reads, whose errors aren't decode errors.
+    pub fn source<R: Read>(&self, inner: R) -> Watched<'_, R> {
+        Watched {
+            inner,
+            watch: self,
+            decoder: false,
+        }
+    }
+
+    /// What the decoder gives.
+    pub fn decoded<R: Read>(&self, inner: R) -> Watched<'_, R> {
+        Watched {
+            inner,
+            watch: self,
+            decoder: true,
+        }
+    }
+
+    /// The error the decoder failed with, unless it was passed up from the
+    /// stream under it.
+    pub fn decode_error(&self) -> Option<String> {
+        self.decode_error.borrow().clone()
+    }
+
+    /// Records an error of the decoder, or of the stream under it.
+    fn failed(&self, decoder: bool, e: io::Error) -> io::Error {
+        if !decoder {
+            self.source_failed.set(true);
+        } else if !self.source_failed.get() {
+            self.decode_error
+                .borrow_mut()
+                .get_or_insert_with(|| e.to_string());
+        }
+        e
+    }
+}
+
+pub struct Watched<'a, R> {
+    inner: R,
+    watch: &'a DecodeWatch,
+    decoder: bool,
+}
+
+impl<R: Read> Read for Watched<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (watch, decoder) = (self.watch, self.decoder);
+        self.inner.read(buf).map_err(|e| watch.failed(decoder, e))
+    }
+}
+
+// The decoder reads the stream beneath it through its buffer.
+impl<R: BufRead> BufRead for Watched<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let (watch, decoder) = (self.watch, self.decoder);
+        self.inner.fill_buf().map_err(|e| watch.failed(decoder, e))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::raw::{RawEntry, RawWriter};
+    use crate::synthetic::TempDir;
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn truncated_members_are_copied_as_they_are() {
+        let dir = TempDir::new("degraded");
+        let path = dir.join("package.slpk");
+        let document = format!("{{\"id\":\"0\",\"children\":[{}0]}}", "1,".repeat(50_000));
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(document.as_bytes()).unwrap();
+        let member = encoder.finish().unwrap();
+        let truncated = &member[..member.len() / 2];
+        let mut writer = RawWriter::new(io::Cursor::new(Vec::new()));
+        let entries: [(&str, &[u8]); 2] = [
+            ("nodes/0/3dNodeIndexDocument.json.gz", truncated),
+            ("nodes/1/3dNodeIndexDocument.json.gz", &member),
+        ];
+        for (name, contents) in &entries {
+            let mut entry = RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            let data = entry.replace_contents(contents).unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        std::fs::write(&path, writer.finish(b"").unwrap().into_inner()).unwrap();
+        let unpacked = dir.join("package/nodes");
+
+        let summary = unpack_with_options(&path, &UnpackOptions::new().threads(1)).unwrap();
+        let copied = std::fs::read(unpacked.join("0/3dNodeIndexDocument.json.gz")).unwrap();
+        let partial = unpacked.join("0/3dNodeIndexDocument.json").exists();
+        let decoded = std::fs::read_to_string(unpacked.join("1/3dNodeIndexDocument.json"));
+        let strict =
+            unpack_with_options(&path, &UnpackOptions::new().threads(1).strict_content(true));
+
+        assert_eq!(summary.entries_unpacked, 2);
+        assert_eq!(
+            summary.degraded_entries,
+            vec!["nodes/0/3dNodeIndexDocument.json.gz".to_string()]
+        );
+        assert_eq!(copied, truncated);
+        assert!(!partial);
+        assert_eq!(decoded.unwrap(), document);
+        assert!(strict.is_err());
+    }
+}
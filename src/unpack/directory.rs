@@ -0,0 +1,133 @@
+// The zip directory of a package is read once, before the workers start,
+// rather than once more by each of them. Reading it is most of the time an
+// unpack takes to start on a package of hundreds of thousands of entries,
+// as every entry has a record of its own to read and parse. The zip library
+// keeps the parsed directory along with the reader it was read through, and
+// has no way to swap the reader, so the directory can't be lent to workers
+// with readers of their own. An archive can be cloned, though, when its
+// reader can be, so the package is read through a `PackageReader`, which is
+// only opened once it is first read from, and whose clones start out
+// unopened. Cloning the archive then copies its directory in memory, and
+// each worker's clone opens a reader of its own on its first entry.
+
+use failure::Error;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A reader of the package for an archive, opened with `open` when it is
+/// first read from or seeked.
+pub struct PackageReader<'a, R> {
+    open: &'a (dyn Fn() -> Result<R, Error> + Sync),
+    reader: Option<R>,
+}
+
+impl<'a, R> PackageReader<'a, R> {
+    pub fn new(open: &'a (dyn Fn() -> Result<R, Error> + Sync)) -> PackageReader<'a, R> {
+        PackageReader { open, reader: None }
+    }
+
+    fn reader(&mut self) -> std::io::Result<&mut R> {
+        let reader = match self.reader.take() {
+            Some(reader) => reader,
+            None => (self.open)().map_err(|e| std::io::Error::other(e.compat()))?,
+        };
+        Ok(self.reader.insert(reader))
+    }
+}
+
+/// A clone opens a reader of its own.
+impl<'a, R> Clone for PackageReader<'a, R> {
+    fn clone(&self) -> PackageReader<'a, R> {
+        PackageReader::new(self.open)
+    }
+}
+
+impl<R: Read> Read for PackageReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader()?.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for PackageReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.reader()?.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use zip::ZipArchive;
+
+    #[test]
+    fn clones_read_the_directory_once_and_open_readers_of_their_own() {
+        let package = SyntheticPackage::standard().to_bytes().unwrap();
+        let opened = AtomicUsize::new(0);
+        let open = || {
+            opened.fetch_add(1, Ordering::SeqCst);
+            Ok(Cursor::new(&package[..]))
+        };
+        let archive = ZipArchive::new(PackageReader::new(&open)).unwrap();
+        let mut clones = vec![archive.clone(), archive.clone()];
+        assert_eq!(opened.load(Ordering::SeqCst), 1);
+
+        let last = archive.len() - 1;
+        let mut contents = Vec::new();
+        for clone in &mut clones {
+            let mut entry = clone.by_index(last).unwrap();
+            let mut buf = Vec::new();
+            entry.read_to_end(&mut buf).unwrap();
+            contents.push(buf);
+        }
+        assert_eq!(opened.load(Ordering::SeqCst), 3);
+        assert_eq!(contents[0], contents[1]);
+    }
+
+    /// Compares reading the zip directory of a large package in each of
+    /// eight workers with reading it once and cloning it for each. The zip
+    /// library only writes directories of up to 65,535 entries, without
+    /// zip64, so the package stays below that. Run it with
+    /// `cargo test --release -- --ignored --nocapture directory`.
+    #[test]
+    #[ignore]
+    fn benchmark_reading_and_cloning_the_directory() {
+        let mut package = SyntheticPackage::new().entry("3dSceneLayer.json.gz", b"{}");
+        for i in 0..60_000 {
+            package = package.entry(&format!("nodes/{}/features/0.json", i), b"{}");
+        }
+        let dir = TempDir::new("directory-bench");
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let open = || Ok(std::io::BufReader::new(std::fs::File::open(&path)?));
+        let time = |start_workers: &dyn Fn()| {
+            let mut best = Duration::MAX;
+            for _ in 0..3 {
+                let start = Instant::now();
+                start_workers();
+                best = best.min(start.elapsed());
+            }
+            best
+        };
+        let entries = ZipArchive::new(PackageReader::new(&open)).unwrap().len();
+        assert_eq!(entries, 60_001);
+        let read = time(&|| {
+            for _ in 0..8 {
+                ZipArchive::new(PackageReader::new(&open)).unwrap();
+            }
+        });
+        let cloned = time(&|| {
+            let archive = ZipArchive::new(PackageReader::new(&open)).unwrap();
+            for _ in 0..8 {
+                drop(archive.clone());
+            }
+        });
+
+        println!(
+            "60000 entries, 8 workers: {:?} reading the directory in each, {:?} reading it once",
+            read, cloned
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// A dry run goes through the entries just as an unpack does, working out the
+// file each one would be written to and how large it would be, but creates
+// nothing. The size of a gzipped entry is read from the ISIZE field in its
+// gzip trailer when the entry is stored, as entries of real packages are, and
+// otherwise found by decompressing it.
+
+use crate::archive;
+use crate::json::Value;
+use failure::Error;
+use flate2::bufread::GzDecoder;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use zip::{CompressionMethod, ZipArchive};
+
+/// The smallest gzip stream: a header and a trailer around an empty block.
+const MIN_GZIP_LEN: u64 = 18;
+
+/// What an unpack would write.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DryRunSummary {
+    /// The files which would be written.
+    pub files: usize,
+    /// The bytes of every file, decompressed when the entry is gzipped.
+    pub bytes: u64,
+    /// The files which would be decompressed from gzipped entries, and the
+    /// bytes they would hold.
+    pub gzipped_files: usize,
+    /// The bytes of the files decompressed from gzipped entries.
+    pub gzipped_bytes: u64,
+    /// The folders which would hold files, or which directory entries would
+    /// create.
+    pub folders: usize,
+    /// Files which already exist, and which the conflict policy would keep.
+    pub files_kept: usize,
+}
+
+impl DryRunSummary {
+    /// The summary as JSON, for `slpkg unpack --dry-run --json`.
+    pub fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("files".to_string(), Value::from(self.files)),
+            ("bytes".to_string(), Value::from(self.bytes)),
+            ("gzippedFiles".to_string(), Value::from(self.gzipped_files)),
+            ("gzippedBytes".to_string(), Value::from(self.gzipped_bytes)),
+            ("folders".to_string(), Value::from(self.folders)),
+            ("filesKept".to_string(), Value::from(self.files_kept)),
+        ])
+    }
+}
+
+impl fmt::Display for DryRunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Would unpack {} files in {} folders, {} bytes in all, of which {} files decompressed from gzip hold {} bytes",
+            self.files, self.folders, self.bytes, self.gzipped_files, self.gzipped_bytes
+        )?;
+        if self.files_kept > 0 {
+            write!(f, ", and would keep {} existing files", self.files_kept)?;
+        }
+        Ok(())
+    }
+}
+
+/// What the workers found, before folders counted by several of them are
+/// counted once.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    files: usize,
+    bytes: u64,
+    gzipped_files: usize,
+    gzipped_bytes: u64,
+    folders: HashSet<PathBuf>,
+    pub files_kept: usize,
+}
+
+impl DryRun {
+    pub fn add(&mut self, target_file_path: &Path, gzipped: bool, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        if gzipped {
+            self.gzipped_files += 1;
+            self.gzipped_bytes += bytes;
+        }
+        if let Some(folder) = target_file_path.parent() {
+            self.folders.insert(folder.to_path_buf());
+        }
+    }
+
+    pub fn add_folder(&mut self, folder: &Path) {
+        self.folders.insert(folder.to_path_buf());
+    }
+
+    pub fn merge(&mut self, other: DryRun) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.gzipped_files += other.gzipped_files;
+        self.gzipped_bytes += other.gzipped_bytes;
+        self.folders.extend(other.folders);
+        self.files_kept += other.files_kept;
+    }
+
+    pub fn summary(&self) -> DryRunSummary {
+        DryRunSummary {
+            files: self.files,
+            bytes: self.bytes,
+            gzipped_files: self.gzipped_files,
+            gzipped_bytes: self.gzipped_bytes,
+            folders: self.folders.len(),
+            files_kept: self.files_kept,
+        }
+    }
+}
+
+/// Reads `len` bytes of the package at `offset`.
+fn read_at<P: Read + Seek>(package: &mut P, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buffer = vec![0; len];
+    package.seek(SeekFrom::Start(offset))?;
+    package.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Whether an entry would be decompressed, and the size of the file it would
+/// be unpacked to. Entries are taken to be gzipped when `named_gzip` says so,
+/// or when `sniff` is set, when they start like a gzip stream. `package` is
+/// a reader of its own over the package, for reading stored entries
+/// directly. ISIZE holds the
+/// size modulo 4 GiB, which no resource of a real package reaches.
+pub fn planned_file<R: Read + Seek, P: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    package: &mut P,
+    entry_idx: usize,
+    named_gzip: bool,
+    sniff: bool,
+) -> Result<(bool, u64), Error> {
+    let entry = archive.by_index(entry_idx)?;
+    let stored = entry.compression() == CompressionMethod::Stored;
+    let (data_start, compressed_size, size) =
+        (entry.data_start(), entry.compressed_size(), entry.size());
+    drop(entry);
+    let is_gzip = if !sniff {
+        named_gzip
+    } else if stored {
+        archive::is_gzip(&read_at(
+            package,
+            data_start,
+            archive::GZIP_MAGIC.len().min(compressed_size as usize),
+        )?)
+    } else {
+        let mut magic = Vec::new();
+        archive
+            .by_index(entry_idx)?
+            .take(archive::GZIP_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        archive::is_gzip(&magic)
+    };
+    if !is_gzip {
+        return Ok((false, size));
+    }
+    if stored && compressed_size >= MIN_GZIP_LEN {
+        let isize = read_at(package, data_start + compressed_size - 4, 4)?;
+        return Ok((
+            true,
+            u64::from(u32::from_le_bytes([isize[0], isize[1], isize[2], isize[3]])),
+        ));
+    }
+    let entry = archive.by_index(entry_idx)?;
+    let mut decoder = GzDecoder::new(BufReader::new(entry));
+    Ok((true, std::io::copy(&mut decoder, &mut std::io::sink())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, ClassRoute, UnpackOptions};
+
+    #[test]
+    fn dry_runs_count_what_unpacking_writes() {
+        let dir = TempDir::new("dry-run");
+        let path = dir.join("package.slpk");
+        let package = SyntheticPackage::standard();
+        package.write_to_file(&path).unwrap();
+        let routed = dir.join("textures");
+        let options = UnpackOptions::new()
+            .routes(vec![format!("textures={}", routed.to_string_lossy())
+                .parse::<ClassRoute>()
+                .unwrap()])
+            .output_dir(dir.join("out"))
+            .dry_run(true);
+        let planned = unpack_with_options(&path, &options)
+            .unwrap()
+            .dry_run
+            .unwrap();
+        let created = std::fs::read_dir(&dir).unwrap().count();
+
+        let unpacked = unpack_with_options(&path, &options.dry_run(false)).unwrap();
+        let mut files = Vec::new();
+        for folder in [dir.join("out/package"), routed] {
+            crate::glob::walk(&folder, None, &mut files).unwrap();
+        }
+        files.retain(|file| !file.ends_with(crate::unpack::incremental::RECORD_FILE));
+        let bytes: u64 = files
+            .iter()
+            .map(|file| file.metadata().unwrap().len())
+            .sum();
+        let mut folders: Vec<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        folders.sort();
+        folders.dedup();
+
+        // Nothing but the package was there after the dry run.
+        assert_eq!(created, 1);
+        assert_eq!(planned.files, unpacked.entries_unpacked);
+        assert_eq!(planned.files, files.len());
+        assert_eq!(planned.bytes, bytes);
+        assert_eq!(planned.folders, folders.len());
+        assert!(planned.gzipped_files > 0 && planned.gzipped_bytes < planned.bytes);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::archive;
+use crate::i3s;
+use crate::image;
+use byteorder::{ByteOrder, LittleEndian};
+use failure::Error;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// Resource folders of a node which are dropped when it is empty. The node
+/// index document is kept, so that the hierarchy can still be walked.
+const RESOURCE_FOLDERS: [&str; 4] = ["geometries", "textures", "features", "attributes"];
+
+/// Textures no larger than this are placeholders.
+const PLACEHOLDER_SIZE: u32 = 1;
+
+/// Nodes whose resources aren't worth unpacking: zero vertex geometry, no
+/// features, and no textures other than placeholders.
+#[derive(Debug, Default)]
+pub struct EmptyNodes {
+    pub ids: Vec<String>,
+    /// Entry name prefixes to skip, such as `nodes/12/geometries/`.
+    prefixes: Vec<String>,
+}
+
+impl EmptyNodes {
+    pub fn skips(&self, entry_name: &str) -> bool {
+        self.prefixes
+            .iter()
+            .any(|prefix| entry_name.starts_with(prefix.as_str()))
+    }
+}
+
+/// Finds the empty nodes of one layer, adding them to `empty_nodes`. The
+/// ids of nodes outside of the package root are prefixed with their layer
+/// folder.
+pub fn find_empty_nodes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &i3s::SceneLayer,
+    empty_nodes: &mut EmptyNodes,
+) -> Result<(), Error> {
+    let hierarchy = i3s::load_hierarchy(archive, layer)?;
+
+    // Group the resource entries by node resource folder.
+    let mut entries_of_resource: HashMap<String, Vec<String>> = HashMap::new();
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)?.name().to_string();
+        let mut parts = match name.strip_prefix(layer.root.as_str()) {
+            Some(relative_name) => relative_name.splitn(3, '/'),
+            None => continue,
+        };
+        if let (Some("nodes"), Some(resource), Some(_)) = (parts.next(), parts.next(), parts.next())
+        {
+            entries_of_resource
+                .entry(resource.to_string())
+                .or_default()
+                .push(name);
+        }
+    }
+
+    for node in &hierarchy.nodes {
+        let entries = match entries_of_resource.get(&node.resource) {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let in_folder = |folder: &str| {
+            let prefix = layer.entry_name(&format!("nodes/{}/{}/", node.resource, folder));
+            entries
+                .iter()
+                .filter(move |name| name.starts_with(&prefix))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        // Node pages record the counts, for a point cloud only of its
+        // points. Older packages store them in the header of the geometry
+        // buffer: a vertex count then a feature count.
+        let (vertex_count, feature_count) = match node.vertex_count.or(node.point_count) {
+            Some(vertex_count) => (vertex_count, node.feature_count.unwrap_or(0)),
+            None => match in_folder("geometries").first() {
+                Some(geometry) => {
+                    let header = archive::read_entry_prefix(archive, geometry, 8)?;
+                    if header.len() < 8 {
+                        continue;
+                    }
+                    (
+                        LittleEndian::read_u32(&header[0..4]) as u64,
+                        LittleEndian::read_u32(&header[4..8]) as u64,
+                    )
+                }
+                None => continue,
+            },
+        };
+        if vertex_count != 0 || feature_count != 0 {
+            continue;
+        }
+
+        let mut placeholders_only = true;
+        for texture in in_folder("textures") {
+            let image = archive::read_entry(archive, &texture)?.unwrap_or_default();
+            match image::image_dimensions(&image) {
+                Some((width, height))
+                    if width <= PLACEHOLDER_SIZE && height <= PLACEHOLDER_SIZE => {}
+                _ => {
+                    placeholders_only = false;
+                    break;
+                }
+            }
+        }
+        if !placeholders_only {
+            continue;
+        }
+
+        empty_nodes.ids.push(layer.entry_name(&node.id));
+        for folder in &RESOURCE_FOLDERS {
+            empty_nodes
+                .prefixes
+                .push(layer.entry_name(&format!("nodes/{}/{}/", node.resource, folder)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut image = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        image.extend_from_slice(&width.to_be_bytes());
+        image.extend_from_slice(&height.to_be_bytes());
+        image
+    }
+
+    #[test]
+    fn finds_zero_vertex_nodes_with_placeholder_textures() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, contents: &[u8]| {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        };
+        add("3dSceneLayer.json", br#"{"nodePages":{}}"#);
+        add(
+            "nodepages/0.json",
+            br#"{"nodes":[
+                {"index":0,"children":[1,2,3]},
+                {"index":1,"mesh":{"geometry":{"resource":1,"vertexCount":0,"featureCount":0}}},
+                {"index":2,"mesh":{"geometry":{"resource":2,"vertexCount":0}}},
+                {"index":3,"mesh":{"geometry":{"resource":3,"vertexCount":12}}}]}"#,
+        );
+        add("nodes/1/geometries/0.bin", b"");
+        add("nodes/1/textures/0.png", &png(1, 1));
+        add("nodes/2/geometries/0.bin", b"");
+        add("nodes/2/textures/0.png", &png(256, 256));
+        add("nodes/3/geometries/0.bin", &[0; 64]);
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let layer = i3s::read_scene_layer(&mut archive).unwrap();
+        let mut empty_nodes = EmptyNodes::default();
+        find_empty_nodes(&mut archive, &layer, &mut empty_nodes).unwrap();
+        assert_eq!(empty_nodes.ids, vec!["1"]);
+        assert!(empty_nodes.skips("nodes/1/textures/0.png"));
+        assert!(!empty_nodes.skips("nodes/2/geometries/0.bin"));
+        assert!(!empty_nodes.skips("nodepages/0.json"));
+    }
+}
@@ -0,0 +1,234 @@
+// Classifies the errors an unpack fails with, so that scripts can tell a
+// package which isn't there from one which is corrupt, or from an output
+// folder in the way, without matching on messages. The class comes from the
+// first error in the chain which says something about it: an entry which
+// failed is an entry failure whatever it failed with, while an I/O error
+// anywhere else is one of reading the package or writing the output. Each
+// class has an exit code of its own, and a name which stays the same from
+// release to release, for the error object `--json` prints.
+
+use super::{EncryptionError, EntryError, UnpackError, TIMED_OUT_EXIT_CODE};
+use crate::http::HttpError;
+use crate::i3s::I3sError;
+use crate::json::Value;
+use failure::Error;
+use zip::result::ZipError;
+
+/// What kind of failure an unpack ended with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The package couldn't be read, such as when it doesn't exist, or the
+    /// output couldn't be written.
+    Io,
+    /// The package isn't a zip archive, or isn't one slpkg can read.
+    InvalidArchive,
+    /// The output folder, or files in it, are in the way.
+    OutputConflict,
+    /// An entry couldn't be unpacked, or didn't pass a check.
+    EntryFailed,
+    /// The package is encrypted, and no password was given, or the wrong
+    /// one.
+    Password,
+    /// The unpack was cancelled.
+    Cancelled,
+    /// The unpack took longer than its timeout.
+    TimedOut,
+    /// Anything else, such as options which can't be combined.
+    Other,
+}
+
+impl ErrorKind {
+    /// The name of the kind in error objects, which doesn't change.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io",
+            ErrorKind::InvalidArchive => "invalid-archive",
+            ErrorKind::OutputConflict => "output-conflict",
+            ErrorKind::EntryFailed => "entry-failed",
+            ErrorKind::Password => "password",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::TimedOut => "timed-out",
+            ErrorKind::Other => "other",
+        }
+    }
+
+    /// The exit code of `slpkg unpack` for the kind. Cancelling is the code
+    /// of a process stopped by Ctrl-C, and timing out that of `timeout`.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Io => 2,
+            ErrorKind::InvalidArchive => 3,
+            ErrorKind::OutputConflict => 4,
+            ErrorKind::EntryFailed => 5,
+            ErrorKind::Password => 6,
+            ErrorKind::Cancelled => 130,
+            ErrorKind::TimedOut => TIMED_OUT_EXIT_CODE,
+        }
+    }
+}
+
+fn unpack_error_kind(error: &UnpackError) -> ErrorKind {
+    match error {
+        UnpackError::NoFolderForPackage => ErrorKind::Io,
+        UnpackError::OutputFolderIsAFile
+        | UnpackError::OutputFolderExists(_)
+        | UnpackError::FileConflicts(..) => ErrorKind::OutputConflict,
+        UnpackError::PackageEntryHasAbsolutePath
+        | UnpackError::PackageEntryOutsideFolder(_)
+        | UnpackError::VerificationFailed(_)
+        | UnpackError::UnsafeEntryPath(..)
+        | UnpackError::NotGzipped(_)
+        | UnpackError::UnexpectedlyGzipped(_)
+        | UnpackError::DataAfterGzipStream(_) => ErrorKind::EntryFailed,
+        UnpackError::TooManyEntries(..)
+        | UnpackError::NameTooLong(..)
+        | UnpackError::CaseCollisions(..) => ErrorKind::InvalidArchive,
+        UnpackError::TimedOut(..) => ErrorKind::TimedOut,
+        UnpackError::Cancelled(_) => ErrorKind::Cancelled,
+        UnpackError::NoFolderToRetryIn
+        | UnpackError::NoOutputDirForStream
+        | UnpackError::ReformattedVerification
+        | UnpackError::AtomicWith(_)
+        | UnpackError::TarWith(_)
+        | UnpackError::WorkerPanicked(_) => ErrorKind::Other,
+    }
+}
+
+/// The kind of the error an unpack failed with.
+pub fn error_kind(error: &Error) -> ErrorKind {
+    for fail in error.iter_chain() {
+        if let Some(error) = fail.downcast_ref::<UnpackError>() {
+            return unpack_error_kind(error);
+        }
+        if fail.downcast_ref::<EntryError>().is_some() {
+            return ErrorKind::EntryFailed;
+        }
+        match fail.downcast_ref::<ZipError>() {
+            Some(ZipError::Io(_)) => return ErrorKind::Io,
+            Some(_) => return ErrorKind::InvalidArchive,
+            None => {}
+        }
+        match fail.downcast_ref::<EncryptionError>() {
+            Some(EncryptionError::Unsupported(..)) => return ErrorKind::InvalidArchive,
+            Some(_) => return ErrorKind::Password,
+            None => {}
+        }
+        if fail.downcast_ref::<I3sError>().is_some() {
+            return ErrorKind::InvalidArchive;
+        }
+        match fail.downcast_ref::<HttpError>() {
+            Some(HttpError::InvalidUrl(_))
+            | Some(HttpError::HttpsUnsupported(_))
+            | Some(HttpError::InvalidHeader(_)) => return ErrorKind::Other,
+            Some(_) => return ErrorKind::Io,
+            None => {}
+        }
+        if fail.downcast_ref::<std::io::Error>().is_some() {
+            return ErrorKind::Io;
+        }
+    }
+    ErrorKind::Other
+}
+
+/// The error an unpack failed with as a JSON object, for scripts:
+/// `{"error": {"kind": ..., "exitCode": ..., "message": ...}}`.
+pub fn error_to_json(error: &Error) -> Value {
+    let kind = error_kind(error);
+    Value::Object(vec![(
+        "error".to_string(),
+        Value::Object(vec![
+            ("kind".to_string(), Value::from(kind.name())),
+            (
+                "exitCode".to_string(),
+                Value::from(kind.exit_code() as usize),
+            ),
+            ("message".to_string(), Value::from(error.to_string())),
+        ]),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, CancelToken, OverwriteMode, UnpackOptions};
+
+    #[test]
+    fn each_failure_has_a_kind_of_its_own() {
+        let dir = TempDir::new("error-kinds");
+        let kind_of = |path: &std::path::Path, options: &UnpackOptions| {
+            error_kind(&unpack_with_options(path, options).unwrap_err())
+        };
+
+        let missing = kind_of(&dir.join("missing.slpk"), &UnpackOptions::new());
+        let not_a_zip = dir.join("not-a-zip.slpk");
+        std::fs::write(&not_a_zip, b"not a zip archive").unwrap();
+        let invalid = kind_of(&not_a_zip, &UnpackOptions::new());
+
+        let package = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .write_to_file(&package)
+            .unwrap();
+        unpack_with_options(&package, &UnpackOptions::new()).unwrap();
+        let conflict = kind_of(
+            &package,
+            &UnpackOptions::new().overwrite(OverwriteMode::Error),
+        );
+
+        // A gzipped entry whose deflate stream starts with a block of the
+        // reserved type.
+        let corrupt = dir.join("corrupt.slpk");
+        let mut corrupt_bytes = SyntheticPackage::new()
+            .entry("nodes/0/geometries/0.bin.gz", b"plain")
+            .to_bytes()
+            .unwrap();
+        let stream = corrupt_bytes
+            .windows(2)
+            .position(|w| w == crate::archive::GZIP_MAGIC)
+            .unwrap();
+        corrupt_bytes[stream + 10] = 0xff;
+        std::fs::write(&corrupt, &corrupt_bytes).unwrap();
+        let entry = kind_of(
+            &corrupt,
+            &UnpackOptions::new().output_dir(dir.join("corrupt")),
+        );
+
+        let token = CancelToken::new();
+        token.cancel();
+        let cancelled = kind_of(
+            &package,
+            &UnpackOptions::new()
+                .output_dir(dir.join("cancelled"))
+                .cancel(token),
+        );
+        let atomic_resume = kind_of(&package, &UnpackOptions::new().atomic(true).resume(true));
+
+        assert_eq!(missing, ErrorKind::Io);
+        assert_eq!(invalid, ErrorKind::InvalidArchive);
+        assert_eq!(conflict, ErrorKind::OutputConflict);
+        assert_eq!(entry, ErrorKind::EntryFailed);
+        assert_eq!(cancelled, ErrorKind::Cancelled);
+        assert_eq!(atomic_resume, ErrorKind::Other);
+        let codes: Vec<i32> = [missing, invalid, conflict, entry, cancelled, atomic_resume]
+            .iter()
+            .map(|kind| kind.exit_code())
+            .collect();
+        assert_eq!(codes, [2, 3, 4, 5, 130, 1]);
+
+        let error = Error::from(UnpackError::TimedOut(1.0, 3));
+        let json = error_to_json(&error);
+        let object = json.get("error").unwrap();
+        assert_eq!(
+            object.get("kind").and_then(Value::as_str),
+            Some("timed-out")
+        );
+        assert_eq!(object.get("exitCode").and_then(Value::as_u64), Some(124));
+        assert!(object
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap()
+            .starts_with("Timed out"));
+    }
+}
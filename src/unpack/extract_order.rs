@@ -0,0 +1,128 @@
+// The order the entries of a package are handed to the worker threads in.
+// The order of the zip directory is kept by default, but a viewer opened on
+// the output of a large package while it is still being unpacked needs the
+// layer documents and node pages before anything else, and exporters often
+// write those last. `MetadataFirst` hands out the layer documents and
+// `metadata.json` first, then the node pages, then the node index
+// documents, and then everything else, each in the order of the zip
+// directory. The workers take entries from a shared queue, so the documents
+// are written within the first moments of a run, though a worker may still
+// be busy with one when another has started on the resources. Only the
+// order changes, never which entries are unpacked, so the summary is the
+// same either way.
+
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Unknown extract order '{}', expected archive or metadata-first",
+    _0
+)]
+pub struct UnknownExtractOrder(String);
+
+/// The order entries are unpacked in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExtractOrder {
+    /// The order of the zip directory.
+    #[default]
+    ArchiveOrder,
+    /// The documents a viewer reads first, before the resources.
+    MetadataFirst,
+}
+
+impl FromStr for ExtractOrder {
+    type Err = UnknownExtractOrder;
+
+    fn from_str(s: &str) -> Result<ExtractOrder, UnknownExtractOrder> {
+        match s {
+            "archive" => Ok(ExtractOrder::ArchiveOrder),
+            "metadata-first" => Ok(ExtractOrder::MetadataFirst),
+            _ => Err(UnknownExtractOrder(s.to_string())),
+        }
+    }
+}
+
+/// Where an entry comes with `MetadataFirst`, from 0 for the first.
+pub fn priority(entry_name: &str) -> u8 {
+    let name = entry_name.replace('\\', "/");
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    let (folder, file_name) = match name.rfind('/') {
+        Some(slash) => (&name[..slash], &name[slash + 1..]),
+        None => ("", name),
+    };
+    let in_node_pages = folder == "nodepages" || folder.ends_with("/nodepages");
+    match file_name {
+        "3dSceneLayer.json" | "metadata.json" => 0,
+        _ if in_node_pages && file_name.ends_with(".json") => 1,
+        "3dNodeIndexDocument.json" => 2,
+        _ => 3,
+    }
+}
+
+/// `entries`, given by their position and name, in `order`.
+pub fn ordered(order: ExtractOrder, mut entries: Vec<(usize, String)>) -> Vec<usize> {
+    if order == ExtractOrder::MetadataFirst {
+        // The sort is stable, so each class keeps the order of the zip
+        // directory.
+        entries.sort_by_key(|(_, name)| priority(name));
+    }
+    entries.into_iter().map(|(position, _)| position).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn documents_come_before_resources() {
+        let names = [
+            "nodes/0/geometries/0.bin.gz",
+            "nodes/0/3dNodeIndexDocument.json.gz",
+            "layers/1/nodepages/0.json.gz",
+            "nodes/1/textures/0.jpg",
+            "nodepages/1.json.gz",
+            "layers/1/3dSceneLayer.json.gz",
+            "metadata.json",
+        ];
+        let entries = names
+            .iter()
+            .enumerate()
+            .map(|(position, name)| (position, name.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ordered(ExtractOrder::MetadataFirst, entries.clone()),
+            vec![5, 6, 2, 4, 1, 0, 3]
+        );
+        assert_eq!(
+            ordered(ExtractOrder::ArchiveOrder, entries),
+            (0..names.len()).collect::<Vec<_>>()
+        );
+        assert_eq!(priority("nodes/0/nodepages.json"), 3);
+        assert!("metadata".parse::<ExtractOrder>().is_err());
+    }
+
+    #[test]
+    fn the_summary_is_the_same_in_either_order() {
+        let dir = TempDir::new("extract-order");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::standard().write_to_file(&path).unwrap();
+        let counts = |order| {
+            let summary =
+                unpack_with_options(&path, &UnpackOptions::new().threads(3).extract_order(order))
+                    .unwrap();
+            (
+                summary.entries_unpacked,
+                summary.entries_skipped,
+                summary.directories_created,
+                summary.bytes_written,
+            )
+        };
+        let in_archive_order = counts(ExtractOrder::ArchiveOrder);
+        let metadata_first = counts(ExtractOrder::MetadataFirst);
+
+        assert_eq!(metadata_first, in_archive_order);
+    }
+}
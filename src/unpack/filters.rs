@@ -0,0 +1,300 @@
+// Filters on the path, size and modification date of entries. They only
+// look at the zip headers, so excluded entries are never decompressed.
+
+use super::kinds::ResourceKind;
+use crate::glob::Pattern;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use zip::DateTime;
+
+#[derive(Debug, Fail)]
+pub enum FilterError {
+    #[fail(
+        display = "Invalid size '{}', expected a number of bytes with an optional unit such as KB, MB, GiB",
+        _0
+    )]
+    InvalidSize(String),
+
+    #[fail(
+        display = "Invalid date '{}', expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS",
+        _0
+    )]
+    InvalidDate(String),
+}
+
+/// A number of bytes, written with an optional decimal (KB, MB, GB) or
+/// binary (KiB, MiB, GiB) unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<ByteSize, FilterError> {
+        let invalid = || FilterError::InvalidSize(s.to_string());
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "kib" => 1 << 10,
+            "mib" => 1 << 20,
+            "gib" => 1 << 30,
+            _ => return Err(invalid()),
+        };
+        Ok(ByteSize((number * multiplier as f64).round() as u64))
+    }
+}
+
+/// A date and time to compare zip timestamps against. Both are local times
+/// without a zone, so they are compared field by field.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct EntryDate([u16; 6]);
+
+impl EntryDate {
+    fn of_entry(modified: DateTime) -> EntryDate {
+        EntryDate([
+            modified.year(),
+            u16::from(modified.month()),
+            u16::from(modified.day()),
+            u16::from(modified.hour()),
+            u16::from(modified.minute()),
+            u16::from(modified.second()),
+        ])
+    }
+}
+
+impl FromStr for EntryDate {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<EntryDate, FilterError> {
+        let invalid = || FilterError::InvalidDate(s.to_string());
+        let mut parts = s.trim().splitn(2, ['T', ' ']);
+        let date: Vec<&str> = parts.next().unwrap_or("").split('-').collect();
+        let time: Vec<&str> = match parts.next() {
+            Some(time) => time.split(':').collect(),
+            None => vec!["0", "0", "0"],
+        };
+        if date.len() != 3 || time.len() != 3 {
+            return Err(invalid());
+        }
+        let mut fields = [0u16; 6];
+        for (field, part) in fields.iter_mut().zip(date.iter().chain(time.iter())) {
+            *field = part.parse().map_err(|_| invalid())?;
+        }
+        let [_, month, day, hour, minute, second] = fields;
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(invalid());
+        }
+        Ok(EntryDate(fields))
+    }
+}
+
+/// A wildcard pattern for entry paths. A pattern without a `/` matches the
+/// file name of entries in any folder, so that `*.jpg` matches every JPEG.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathPattern {
+    pattern: Pattern,
+    whole_path: bool,
+}
+
+impl PathPattern {
+    /// A pattern of `*`, `?` and `[...]` wildcards.
+    pub fn new(pattern: &str) -> PathPattern {
+        PathPattern {
+            pattern: Pattern::new(pattern),
+            whole_path: pattern.contains('/'),
+        }
+    }
+
+    /// Whether the path, or its file name alone, matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.whole_path {
+            return self.pattern.matches_path(path);
+        }
+        path.file_name()
+            .is_some_and(|name| self.pattern.matches_path(Path::new(name)))
+    }
+}
+
+/// One of the filters of `EntryFilters`, named for its option.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Filter {
+    /// The kinds of resource of `--only`.
+    Only,
+    /// The patterns of `--include`.
+    Include,
+    /// The patterns of `--exclude`.
+    Exclude,
+    /// The smallest size of `--min-size`.
+    MinSize,
+    /// The largest size of `--max-size`.
+    MaxSize,
+    /// The date of `--newer-than`.
+    NewerThan,
+}
+
+impl Filter {
+    /// Every filter, in the order their counts of excluded entries are
+    /// printed.
+    pub const ALL: [Filter; 6] = [
+        Filter::Only,
+        Filter::Include,
+        Filter::Exclude,
+        Filter::MinSize,
+        Filter::MaxSize,
+        Filter::NewerThan,
+    ];
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Filter::Only => "--only",
+            Filter::Include => "--include",
+            Filter::Exclude => "--exclude",
+            Filter::MinSize => "--min-size",
+            Filter::MaxSize => "--max-size",
+            Filter::NewerThan => "--newer-than",
+        })
+    }
+}
+
+/// Entries must pass every filter which is set to be unpacked.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryFilters {
+    /// Only entries of these kinds, when there are any.
+    pub only: Vec<ResourceKind>,
+    /// Only entries whose path matches one of these, when there are any.
+    pub include: Vec<PathPattern>,
+    /// No entries whose path matches one of these.
+    pub exclude: Vec<PathPattern>,
+    /// No entries smaller than this, uncompressed.
+    pub min_size: Option<ByteSize>,
+    /// No entries larger than this, uncompressed.
+    pub max_size: Option<ByteSize>,
+    /// Only entries modified after this.
+    pub newer_than: Option<EntryDate>,
+}
+
+impl EntryFilters {
+    /// Whether any of the kind or pattern filters is set.
+    pub fn has_path_patterns(&self) -> bool {
+        !self.only.is_empty() || !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// Returns the kind or pattern filter which excludes an entry with this
+    /// path, if any does.
+    pub fn excludes_path(&self, path: &Path) -> Option<Filter> {
+        if !self.only.is_empty() && !self.only.contains(&ResourceKind::of_entry(path)) {
+            return Some(Filter::Only);
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|p| p.matches(path)) {
+            return Some(Filter::Include);
+        }
+        if self.exclude.iter().any(|p| p.matches(path)) {
+            return Some(Filter::Exclude);
+        }
+        None
+    }
+
+    /// Returns the first filter which excludes an entry with this
+    /// uncompressed size and modification time.
+    pub fn excludes(&self, size: u64, modified: DateTime) -> Option<Filter> {
+        if self.min_size.is_some_and(|min| size < min.0) {
+            return Some(Filter::MinSize);
+        }
+        if self.max_size.is_some_and(|max| size > max.0) {
+            return Some(Filter::MaxSize);
+        }
+        if self
+            .newer_than
+            .is_some_and(|date| EntryDate::of_entry(modified) <= date)
+        {
+            return Some(Filter::NewerThan);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!("512".parse::<ByteSize>().unwrap(), ByteSize(512));
+        assert_eq!("10MB".parse::<ByteSize>().unwrap(), ByteSize(10_000_000));
+        assert_eq!("1GiB".parse::<ByteSize>().unwrap(), ByteSize(1 << 30));
+        assert_eq!("1.5 kib".parse::<ByteSize>().unwrap(), ByteSize(1536));
+        assert!("10 parsecs".parse::<ByteSize>().is_err());
+        assert!("MB".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn filters_compose() {
+        let filters = EntryFilters {
+            min_size: Some(ByteSize(100)),
+            max_size: None,
+            newer_than: Some("2019-03-01".parse().unwrap()),
+            ..EntryFilters::default()
+        };
+        let march = DateTime::from_date_and_time(2019, 3, 1, 0, 0, 0).unwrap();
+        let april = DateTime::from_date_and_time(2019, 4, 1, 0, 0, 0).unwrap();
+        assert_eq!(filters.excludes(10, april), Some(Filter::MinSize));
+        assert_eq!(filters.excludes(1000, march), Some(Filter::NewerThan));
+        assert_eq!(filters.excludes(1000, april), None);
+        assert!("2019-13-01".parse::<EntryDate>().is_err());
+        assert!("2019-03-01T12:00:00".parse::<EntryDate>().is_ok());
+    }
+
+    #[test]
+    fn path_patterns_match_names_or_whole_paths() {
+        let filters = EntryFilters {
+            include: vec![
+                PathPattern::new("nodes/*/features/*"),
+                PathPattern::new("*.json.gz"),
+            ],
+            exclude: vec![PathPattern::new("**/statistics/**")],
+            ..EntryFilters::default()
+        };
+        let excludes = |path: &str| filters.excludes_path(Path::new(path));
+        assert_eq!(excludes("3dSceneLayer.json.gz"), None);
+        assert_eq!(excludes("nodes/12/3dNodeIndexDocument.json.gz"), None);
+        assert_eq!(excludes("nodes/12/features/0.json.gz"), None);
+        assert_eq!(excludes("nodes/12/features/0"), None);
+        assert_eq!(excludes("nodes/12/textures/0.jpg"), Some(Filter::Include));
+        assert_eq!(
+            excludes("sublayers/1/nodes/0/features/0"),
+            Some(Filter::Include)
+        );
+        assert_eq!(
+            excludes("layers/0/statistics/f_1/0.json.gz"),
+            Some(Filter::Exclude)
+        );
+        assert!(filters.has_path_patterns());
+        assert!(!EntryFilters::default().has_path_patterns());
+
+        let textures = EntryFilters {
+            only: vec![ResourceKind::Textures],
+            exclude: vec![PathPattern::new("*.png")],
+            ..EntryFilters::default()
+        };
+        let excludes = |path: &str| textures.excludes_path(Path::new(path));
+        assert_eq!(excludes("nodes/12/textures/0_0.jpg"), None);
+        assert_eq!(excludes("nodes/12/textures/0_0.png"), Some(Filter::Exclude));
+        assert_eq!(excludes("nodes/12/geometries/0.bin.gz"), Some(Filter::Only));
+    }
+}
@@ -0,0 +1,557 @@
+// A page for finding one's way around an unpacked package without opening
+// thousands of files: `index.html`, written at the root of the output
+// folder, summarizes the layers as `slpkg info` does, charts the size of
+// each class of resource from the figures of `slpkg stats`, nests the nodes
+// of a layer below their parents as `slpkg tree` does, and links the JSON
+// documents which were unpacked. Everything the page needs is inlined, so
+// that it works when opened from disk. The same figures are written to
+// `index.json` for scripts.
+//
+// The page is the template `html_index.html`, with each `{{name}}` replaced
+// by escaped markup. Meshes with hundreds of thousands of nodes are cut off
+// at the deepest level which keeps the tree below `MAX_TREE_NODES`.
+
+use crate::i3s::SceneLayer;
+use crate::info::{self, SlpkInfo};
+use crate::json::Value;
+use crate::stats::{self, PackageStats};
+use crate::tree::{self, NodeTree};
+use failure::Error;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+pub const INDEX_FILE: &str = "index.html";
+pub const INDEX_DATA_FILE: &str = "index.json";
+
+const TEMPLATE: &str = include_str!("html_index.html");
+
+/// The most nodes the tree of the page holds, beyond which deeper levels
+/// are left out.
+const MAX_TREE_NODES: usize = 20_000;
+
+/// What the page shows.
+#[derive(Clone, Debug)]
+pub struct PackageIndex {
+    pub title: String,
+    pub info: SlpkInfo,
+    pub stats: PackageStats,
+    /// The layer whose nodes are in `tree`, and the folder holding it.
+    pub tree_layer: (usize, String),
+    pub tree: NodeTree,
+    /// The unpacked JSON documents, relative to the output folder and with
+    /// forward slashes.
+    pub documents: Vec<String>,
+}
+
+impl PackageIndex {
+    /// Gathers the figures of a package, reading the gzip trailers through
+    /// `package`, and the tree of `layer`.
+    pub fn gather<R: Read + Seek, P: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        package: &mut P,
+        title: &str,
+        layer: &SceneLayer,
+        mut documents: Vec<String>,
+    ) -> Result<PackageIndex, Error> {
+        let info = info::info_of(archive, title)?;
+        let stats = stats::stats_of(archive, package)?;
+        // The nodes of every level are counted however deep the tree goes.
+        let nodes_per_level = tree::tree_of(archive, layer, Some(0))?.nodes_per_level;
+        let mut shown = 0;
+        let deepest = nodes_per_level
+            .iter()
+            .take_while(|&&count| {
+                shown += count;
+                shown <= MAX_TREE_NODES
+            })
+            .count();
+        let max_depth = if deepest == nodes_per_level.len() {
+            None
+        } else {
+            Some(deepest.saturating_sub(1))
+        };
+        let tree = tree::tree_of(archive, layer, max_depth)?;
+        documents.sort();
+        Ok(PackageIndex {
+            title: title.to_string(),
+            info,
+            stats,
+            tree_layer: (layer.id, layer.root.clone()),
+            tree,
+            documents,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let optional = |value: Option<Value>| value.unwrap_or(Value::Null);
+        let layers = self.info.layers.iter().map(|layer| {
+            Value::Object(vec![
+                ("id".to_string(), Value::from(layer.id)),
+                ("root".to_string(), Value::from(layer.root.as_str())),
+                (
+                    "type".to_string(),
+                    optional(layer.layer_type.as_deref().map(Value::from)),
+                ),
+                (
+                    "name".to_string(),
+                    optional(layer.name.as_deref().map(Value::from)),
+                ),
+                (
+                    "wkid".to_string(),
+                    optional(layer.wkid.map(|wkid| Value::from(u64::from(wkid)))),
+                ),
+                (
+                    "version".to_string(),
+                    Value::from(layer.version.to_string()),
+                ),
+                ("nodes".to_string(), optional(layer.nodes.map(Value::from))),
+                (
+                    "attributes".to_string(),
+                    Value::from(layer.attributes.len()),
+                ),
+                (
+                    "textureEncodings".to_string(),
+                    Value::Array(
+                        layer
+                            .texture_encodings
+                            .iter()
+                            .map(|encoding| Value::from(encoding.as_str()))
+                            .collect(),
+                    ),
+                ),
+            ])
+        });
+        let nodes_per_level = self
+            .tree
+            .nodes_per_level
+            .iter()
+            .map(|&count| Value::from(count));
+        Value::Object(vec![
+            ("package".to_string(), Value::from(self.title.as_str())),
+            (
+                "flavor".to_string(),
+                Value::from(self.info.flavor.to_string()),
+            ),
+            (
+                "version".to_string(),
+                Value::from(self.info.version.to_string()),
+            ),
+            ("layers".to_string(), Value::Array(layers.collect())),
+            ("stats".to_string(), self.stats.to_json()),
+            (
+                "tree".to_string(),
+                Value::Object(vec![
+                    ("layer".to_string(), Value::from(self.tree_layer.0)),
+                    (
+                        "nodesPerLevel".to_string(),
+                        Value::Array(nodes_per_level.collect()),
+                    ),
+                    (
+                        "unreachable".to_string(),
+                        Value::from(self.tree.unreachable),
+                    ),
+                ]),
+            ),
+            (
+                "documents".to_string(),
+                Value::Array(
+                    self.documents
+                        .iter()
+                        .map(|document| Value::from(document.as_str()))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
+
+    /// The node document of each node of the tree which was unpacked, by the
+    /// id of the node.
+    fn node_documents(&self) -> HashMap<&str, &str> {
+        let prefix = format!("{}nodes/", self.tree_layer.1);
+        self.documents
+            .iter()
+            .filter_map(|document| {
+                let node = document
+                    .strip_prefix(&prefix)?
+                    .strip_suffix("/3dNodeIndexDocument.json")?;
+                Some((node, document.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn to_html(&self) -> String {
+        let unknown = || "unknown".to_string();
+        let mut layers = String::new();
+        for layer in &self.info.layers {
+            let cells = [
+                layer.id.to_string(),
+                layer.layer_type.clone().unwrap_or_else(unknown),
+                layer.name.clone().unwrap_or_default(),
+                layer.wkid.map_or_else(unknown, |wkid| wkid.to_string()),
+                layer.version.to_string(),
+                layer.nodes.map_or_else(unknown, stats::grouped),
+                layer.attributes.len().to_string(),
+                layer.texture_encodings.join(", "),
+            ];
+            layers.push_str("<tr>");
+            for cell in &cells {
+                layers.push_str(&format!("<td>{}</td>", escape(cell)));
+            }
+            layers.push_str("</tr>\n");
+        }
+
+        let compressed: Vec<_> = self
+            .stats
+            .categories
+            .iter()
+            .map(|category| (category.category.to_string(), category.compressed_size))
+            .collect();
+        let unpacked: Vec<_> = self
+            .stats
+            .categories
+            .iter()
+            .map(|category| (category.category.to_string(), category.unpacked_size))
+            .collect();
+
+        let node_documents = self.node_documents();
+        let nodes: usize = self.tree.nodes_per_level.iter().sum();
+        let mut tree_summary = format!(
+            "{} nodes in {} levels",
+            stats::grouped(nodes as u64),
+            self.tree.nodes_per_level.len()
+        );
+        if self.tree.nodes.len() < nodes {
+            tree_summary.push_str(&format!(
+                ", of which the {} of the top {} levels are shown",
+                stats::grouped(self.tree.nodes.len() as u64),
+                self.tree
+                    .nodes
+                    .iter()
+                    .map(|node| node.depth + 1)
+                    .max()
+                    .unwrap_or(0)
+            ));
+        }
+        if self.tree.unreachable > 0 {
+            tree_summary.push_str(&format!(
+                ", and {} which can't be reached from the root",
+                self.tree.unreachable
+            ));
+        }
+        tree_summary.push('.');
+
+        let mut documents = String::new();
+        let listed = self
+            .documents
+            .iter()
+            .filter(|document| !node_documents.values().any(|node| node == document));
+        for document in listed {
+            documents.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape(&href(document)),
+                escape(document)
+            ));
+        }
+        if !node_documents.is_empty() {
+            documents.push_str(&format!(
+                "<li class=\"note\">The {} node documents are linked from the tree.</li>\n",
+                stats::grouped(node_documents.len() as u64)
+            ));
+        }
+
+        render(
+            TEMPLATE,
+            &[
+                ("title", escape(&self.title)),
+                ("flavor", escape(&self.info.flavor.to_string())),
+                ("version", escape(&self.info.version.to_string())),
+                ("data_file", escape(INDEX_DATA_FILE)),
+                ("layers", layers.trim_end().to_string()),
+                ("compressed_chart", chart(&compressed)),
+                ("unpacked_chart", chart(&unpacked)),
+                ("tree_layer", self.tree_layer.0.to_string()),
+                ("tree_summary", escape(&tree_summary)),
+                ("tree", tree_html(&self.tree, &node_documents)),
+                ("documents", documents.trim_end().to_string()),
+            ],
+        )
+    }
+}
+
+/// The template with each `{{name}}` replaced by its markup.
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut page = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        page.push_str(&rest[..start]);
+        let name = &rest[start + 2..end];
+        match values.iter().find(|(value_name, _)| *value_name == name) {
+            Some((_, value)) => page.push_str(value),
+            None => page.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    page.push_str(rest);
+    page
+}
+
+/// `text` with the characters which mean something to HTML escaped, for
+/// text and attribute values alike.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A relative URL for a file in the output folder, with every byte of its
+/// path which isn't a letter, digit, slash or one of `-._~` percent-encoded,
+/// so that names holding `#`, `?` or `%` still lead to their file.
+fn href(path: &str) -> String {
+    let mut href = String::with_capacity(path.len());
+    for &byte in path.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => {
+                href.push(byte as char)
+            }
+            byte => href.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    href
+}
+
+/// A row per class, with a bar as long as its share of the largest class.
+fn chart(rows: &[(String, u64)]) -> String {
+    let largest = rows
+        .iter()
+        .map(|(_, bytes)| *bytes)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let total: u64 = rows.iter().map(|(_, bytes)| bytes).sum();
+    let mut chart = String::new();
+    for (name, bytes) in rows {
+        let share = if total == 0 {
+            0.0
+        } else {
+            *bytes as f64 * 100.0 / total as f64
+        };
+        chart.push_str(&format!(
+            "<span>{}</span><div class=\"bar\" style=\"width: {:.1}%\"></div><span>{} bytes, {:.1}%</span>\n",
+            escape(name),
+            *bytes as f64 * 100.0 / largest as f64,
+            stats::grouped(*bytes),
+            share
+        ));
+    }
+    chart.trim_end().to_string()
+}
+
+/// The nodes of the tree, each nested in a `details` element of its parent,
+/// with the roots open.
+fn tree_html(tree: &NodeTree, node_documents: &HashMap<&str, &str>) -> String {
+    let mut html = String::new();
+    // The depths of the `details` elements still open.
+    let mut open: Vec<usize> = Vec::new();
+    for (position, node) in tree.nodes.iter().enumerate() {
+        while open.last().is_some_and(|&depth| depth >= node.depth) {
+            open.pop();
+            html.push_str("</details>\n");
+        }
+        let mut label = escape(&node.to_string());
+        if let Some(document) = node_documents.get(node.id.as_str()) {
+            label.push_str(&format!(
+                " <a href=\"{}\">document</a>",
+                escape(&href(document))
+            ));
+        }
+        let has_children = tree
+            .nodes
+            .get(position + 1)
+            .is_some_and(|next| next.depth > node.depth);
+        if has_children {
+            let state = if node.depth == 0 { " open" } else { "" };
+            html.push_str(&format!("<details{}><summary>{}</summary>\n", state, label));
+            open.push(node.depth);
+        } else {
+            html.push_str(&format!(
+                "<details class=\"leaf\"><summary>{}</summary></details>\n",
+                label
+            ));
+        }
+    }
+    for _ in open {
+        html.push_str("</details>\n");
+    }
+    html.trim_end().to_string()
+}
+
+/// The JSON documents below `folder`, relative to it and with forward
+/// slashes, leaving out the files unpacking writes beside the entries.
+pub fn documents_in(folder: &Path) -> Result<Vec<String>, Error> {
+    let mut documents = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(current) = folders.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                folders.push(path);
+                continue;
+            }
+            let name = match path.strip_prefix(folder) {
+                Ok(name) => name
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                Err(_) => continue,
+            };
+            if name.ends_with(".json") && !super::is_bookkeeping_file(&name) {
+                documents.push(name);
+            }
+        }
+    }
+    Ok(documents)
+}
+
+/// Writes the page and its data to the root of `unpack_folder`, returning
+/// the path of the page.
+pub fn write_index(unpack_folder: &Path, index: &PackageIndex) -> Result<PathBuf, Error> {
+    std::fs::write(
+        unpack_folder.join(INDEX_DATA_FILE),
+        index.to_json().to_pretty_string(2) + "\n",
+    )?;
+    let page_path = unpack_folder.join(INDEX_FILE);
+    std::fs::write(&page_path, index.to_html())?;
+    Ok(page_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    /// The elements HTML closes by itself.
+    const VOID_ELEMENTS: &[&str] = &["meta", "br", "hr", "img", "input", "link"];
+
+    /// Checks that every element of `html` is closed in the order it was
+    /// opened, that every attribute value is quoted, and that `&` only
+    /// starts a reference.
+    fn check_well_formed(html: &str) -> Result<(), String> {
+        let mut open: Vec<String> = Vec::new();
+        let mut rest = html.trim_start_matches("<!DOCTYPE html>");
+        while let Some(start) = rest.find(['<', '&']) {
+            if rest[start..].starts_with('&') {
+                let reference = &rest[start + 1..];
+                let end = reference.find(';').ok_or("an & which isn't escaped")?;
+                let name = &reference[..end];
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') {
+                    return Err(format!("an & which isn't escaped, before {}", name));
+                }
+                rest = &reference[end + 1..];
+                continue;
+            }
+            let end = rest[start..].find('>').ok_or("a tag which isn't closed")? + start;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                match open.pop() {
+                    Some(opened) if opened == name => continue,
+                    opened => return Err(format!("</{}> closes {:?}", name, opened)),
+                }
+            }
+            let mut parts = tag.splitn(2, ' ');
+            let name = parts.next().unwrap_or("").to_string();
+            if let Some(attributes) = parts.next() {
+                for attribute in attributes.split("\" ") {
+                    let attribute = attribute.trim();
+                    if attribute != "open" && !attribute.contains("=\"") {
+                        return Err(format!("the attribute {} of <{}>", attribute, name));
+                    }
+                }
+            }
+            if name == "script" || name == "style" {
+                let close = format!("</{}>", name);
+                let end = rest
+                    .find(&close)
+                    .ok_or(format!("<{}> isn't closed", name))?;
+                rest = &rest[end + close.len()..];
+            } else if !VOID_ELEMENTS.contains(&name.as_str()) {
+                open.push(name);
+            }
+        }
+        match open.is_empty() {
+            true => Ok(()),
+            false => Err(format!("{:?} aren't closed", open)),
+        }
+    }
+
+    #[test]
+    fn the_page_is_well_formed_and_links_the_documents() {
+        let dir = TempDir::new("html-index");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"3DObject","name":"Houses & <sheds>","store":{"version":"1.6"}}"#,
+            )
+            .entry(
+                "nodes/root/3dNodeIndexDocument.json.gz",
+                br#"{"id":"root","children":[{"id":"1"}]}"#,
+            )
+            .entry(
+                "nodes/1/3dNodeIndexDocument.json.gz",
+                br#"{"id":"1","parentNode":{"id":"root"}}"#,
+            )
+            .entry("nodes/1/geometries/0.bin.gz", &[0; 64])
+            .entry("statistics/f_#1/0.json.gz", b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        unpack_with_options(&path, &UnpackOptions::new().emit_index(true)).unwrap();
+        let unpacked = dir.join("package");
+        let page = std::fs::read_to_string(unpacked.join(INDEX_FILE)).unwrap();
+        let data = std::fs::read_to_string(unpacked.join(INDEX_DATA_FILE)).unwrap();
+
+        check_well_formed(&page).unwrap();
+        assert!(!page.contains("{{"));
+        assert!(!page.contains("http"));
+        assert!(page.contains("<td>Houses &amp; &lt;sheds&gt;</td>"));
+        assert!(page.contains("<a href=\"statistics/f_%231/0.json\">statistics/f_#1/0.json</a>"));
+        assert!(page.contains("<a href=\"nodes/1/3dNodeIndexDocument.json\">document</a>"));
+        assert!(page.contains("<details open><summary>root"));
+        let data = crate::json::parse(&data).unwrap();
+        assert_eq!(
+            data.get("tree").and_then(|tree| tree.get("nodesPerLevel")),
+            Some(&Value::Array(vec![
+                Value::from(1usize),
+                Value::from(1usize)
+            ]))
+        );
+        assert_eq!(
+            data.get("documents")
+                .and_then(Value::as_array)
+                .map(Vec::len),
+            Some(4)
+        );
+
+        assert!(check_well_formed("<p>a & b</p>").is_err());
+        assert!(check_well_formed("<div><p></div></p>").is_err());
+        assert!(check_well_formed("<a href=x>x</a>").is_err());
+    }
+}
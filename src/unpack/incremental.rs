@@ -0,0 +1,410 @@
+// Unpacks only what changed since the last unpack into the same folder, for
+// packages republished with most of their entries as they were. Every unpack
+// which writes a folder records in it, in `slpkg-entries.json`, the CRC and
+// size the zip directory gives each entry it unpacked, with the file that
+// entry was unpacked to. An incremental run compares the entries of the
+// package with that record, and leaves the file of each entry with the same
+// name, CRC and size as it is, as long as the file is still there. Every
+// other entry is unpacked over what is there, and the files of entries the
+// package no longer has are deleted, along with the folders that leaves
+// empty. Only files the record lists are ever deleted.
+//
+// Paths are recorded relative to the output folder, with forward slashes, as
+// the checksum manifest gives them, so that a folder can be moved between
+// runs. Files routed outside of the folder are recorded in full. A run which
+// kept an earlier output keeps the records of the entries it didn't unpack,
+// so that the record still covers all of them. A record which is missing or
+// can't be read, as in a folder unpacked by an older slpkg, makes an
+// incremental run unpack everything, which can't know which files to delete.
+//
+// The record is read from a folder anyone may have written to, so each path
+// in it must lead to a file below the output folder, or below the folder of
+// a route, as the path of an entry must. A record with any other path is not
+// valid, and nothing it lists is left or deleted.
+//
+// The record also gives the options which decide what each entry was
+// unpacked as, and where, such as whether gzipped entries were kept or how
+// JSON documents were formatted. When they aren't those of this run, the
+// files on disk aren't what this run would write, so every entry is unpacked
+// again, and the files of the earlier run which this one doesn't write over
+// are deleted.
+
+use super::checksums::listed_path;
+use super::{path_in_folder, UnpackOptions};
+use crate::json;
+use crate::json::Value;
+use failure::Error;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub const RECORD_FILE: &str = "slpkg-entries.json";
+
+#[derive(Debug, Fail)]
+#[fail(display = "The entry record {} is not valid", _0)]
+pub struct InvalidRecord(String);
+
+/// An entry as an earlier run unpacked it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryRecord {
+    pub name: String,
+    pub crc32: u32,
+    pub size: u64,
+    /// The file the entry was unpacked to, as the checksum manifest lists
+    /// it.
+    pub path: String,
+}
+
+impl EntryRecord {
+    pub fn new(folder: &Path, name: &str, (crc32, size): (u32, u64), file: &Path) -> EntryRecord {
+        EntryRecord {
+            name: name.to_string(),
+            crc32,
+            size,
+            path: listed_path(folder, file),
+        }
+    }
+
+    /// Where the file of the entry is, below `folder` unless it was routed
+    /// below one of `roots`, or `None` if its path leads anywhere else.
+    pub fn file(&self, folder: &Path, roots: &[&Path]) -> Option<PathBuf> {
+        let path = Path::new(&self.path);
+        for root in roots {
+            if let Ok(below) = path.strip_prefix(root) {
+                return path_in_folder(root, below).ok();
+            }
+        }
+        path_in_folder(folder, path).ok()
+    }
+
+    fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("name".to_string(), Value::from(self.name.as_str())),
+            ("crc32".to_string(), Value::from(u64::from(self.crc32))),
+            ("size".to_string(), Value::from(self.size)),
+            ("path".to_string(), Value::from(self.path.as_str())),
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<EntryRecord> {
+        Some(EntryRecord {
+            name: value.get("name")?.as_str()?.to_string(),
+            crc32: value
+                .get("crc32")?
+                .as_u64()
+                .filter(|&crc32| crc32 <= u64::from(u32::MAX))? as u32,
+            size: value.get("size")?.as_u64()?,
+            path: value.get("path")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// The options which change what the entries of a package are unpacked as,
+/// or where, as the record gives them.
+pub fn output_options(options: &UnpackOptions) -> Value {
+    let flag = |name: &str, set: bool| (name.to_string(), Value::Bool(set));
+    Value::Object(vec![
+        flag("keepGzip", options.content.keep_gzip),
+        (
+            "jsonFormatting".to_string(),
+            Value::from(format!("{:?}", options.content.json_formatting)),
+        ),
+        flag("sortKeys", options.content.sort_keys),
+        (
+            "stripPrefix".to_string(),
+            options
+                .selection
+                .strip_prefix
+                .as_ref()
+                .map_or(Value::Null, |prefix| Value::from(format!("{:?}", prefix))),
+        ),
+        flag("portableNames", options.output.portable_names),
+        flag("trustExtensions", options.content.trust_extensions),
+        (
+            "routes".to_string(),
+            Value::Array(
+                options
+                    .output
+                    .routes
+                    .iter()
+                    .map(|route| {
+                        Value::from(format!("{}={}", route.class, route.root.to_string_lossy()))
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+/// What an earlier run recorded in its output folder.
+#[derive(Debug)]
+pub struct Record {
+    /// The options it unpacked with, as `output_options` gives them, or
+    /// `Value::Null` for a record which doesn't have them.
+    pub options: Value,
+    pub entries: Vec<EntryRecord>,
+}
+
+/// The record of the entries an earlier run unpacked into `folder`, or
+/// `None` if there is none. Files may only have been routed below `roots`.
+pub fn read_record(folder: &Path, roots: &[&Path]) -> Result<Option<Record>, Error> {
+    let path = folder.join(RECORD_FILE);
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::from(e)),
+    };
+    let invalid = || InvalidRecord(path.to_string_lossy().into_owned());
+    let record = json::parse_bytes(&contents).map_err(|_| invalid())?;
+    let records = record
+        .get("entries")
+        .and_then(Value::as_array)
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|entry| {
+            EntryRecord::from_json(entry)
+                .filter(|record| record.file(folder, roots).is_some())
+                .ok_or_else(invalid)
+        })
+        .collect::<Result<Vec<_>, InvalidRecord>>()?;
+    Ok(Some(Record {
+        options: record.get("options").cloned().unwrap_or(Value::Null),
+        entries: records,
+    }))
+}
+
+/// Writes the record of `records`, unpacked with `options`, into `folder`, in
+/// the order of their names.
+pub fn write_record(
+    folder: &Path,
+    options: Value,
+    records: impl IntoIterator<Item = EntryRecord>,
+) -> Result<(), Error> {
+    let records: BTreeMap<String, EntryRecord> = records
+        .into_iter()
+        .map(|record| (record.name.clone(), record))
+        .collect();
+    let record = Value::Object(vec![
+        ("options".to_string(), options),
+        (
+            "entries".to_string(),
+            Value::Array(records.values().map(EntryRecord::to_json).collect()),
+        ),
+    ]);
+    std::fs::write(folder.join(RECORD_FILE), record.to_pretty_string(2) + "\n")?;
+    Ok(())
+}
+
+/// What an incremental run left as it was.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IncrementalStats {
+    /// Entries whose files were as the package holds them, and were left.
+    pub entries_unchanged: usize,
+    /// Files an earlier run wrote which the package no longer holds.
+    pub files_deleted: usize,
+}
+
+/// What an incremental run leaves as it is, and what it deletes.
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// The names of the entries whose files are left as they are.
+    pub unchanged: HashSet<String>,
+    /// The entries the package no longer has.
+    pub removed: Vec<EntryRecord>,
+    /// The entries it still has whose files were written with other
+    /// options, and may not be written over.
+    pub superseded: Vec<EntryRecord>,
+}
+
+/// Compares the entries of a package, given by their name, CRC and size,
+/// with the record of an earlier run into `folder`, for a run with the
+/// options `options` gives.
+pub fn find_changes(
+    folder: &Path,
+    roots: &[&Path],
+    earlier: &Record,
+    options: &Value,
+    entries: impl IntoIterator<Item = (String, u32, u64)>,
+) -> Changes {
+    let same_options = earlier.options == *options;
+    let entries: HashMap<String, (u32, u64)> = entries
+        .into_iter()
+        .map(|(name, crc32, size)| (name, (crc32, size)))
+        .collect();
+    let mut changes = Changes::default();
+    for record in &earlier.entries {
+        match entries.get(&record.name) {
+            None => changes.removed.push(record.clone()),
+            Some(_) if !same_options => changes.superseded.push(record.clone()),
+            Some(&checksum) if checksum == (record.crc32, record.size) => {
+                if record
+                    .file(folder, roots)
+                    .is_some_and(|file| file.is_file())
+                {
+                    changes.unchanged.insert(record.name.clone());
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    changes
+}
+
+/// Deletes the files of `removed` which aren't among `written`, and the
+/// folders below `folder` that leaves empty. Gives the number of files
+/// deleted.
+pub fn delete_removed<'a>(
+    folder: &Path,
+    roots: &[&Path],
+    removed: impl IntoIterator<Item = &'a EntryRecord>,
+    written: &HashSet<String>,
+) -> Result<usize, Error> {
+    let mut deleted = 0;
+    for record in removed
+        .into_iter()
+        .filter(|record| !written.contains(&record.path))
+    {
+        // The records read have all been checked, so this only leaves out
+        // those made up by hand.
+        let file = match record.file(folder, roots) {
+            Some(file) => file,
+            None => continue,
+        };
+        match std::fs::remove_file(&file) {
+            Ok(()) => deleted += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::from(e)),
+        }
+        let mut parent = file.parent();
+        while let Some(dir) = parent.filter(|dir| dir.starts_with(folder) && *dir != folder) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn only_changed_entries_are_unpacked_again() {
+        let folder = TempDir::new("incremental");
+        std::fs::create_dir_all(folder.join("nodes/1")).unwrap();
+        std::fs::write(folder.join("3dSceneLayer.json"), b"{}").unwrap();
+        std::fs::write(folder.join("nodes/1/0.bin"), b"geometry").unwrap();
+        let record = |name: &str, crc32, path: &str| EntryRecord {
+            name: name.to_string(),
+            crc32,
+            size: 8,
+            path: path.to_string(),
+        };
+        let options = output_options(&UnpackOptions::new());
+        write_record(
+            &folder,
+            options.clone(),
+            vec![
+                record("nodes/1/0.bin.gz", 1, "nodes/1/0.bin"),
+                record("3dSceneLayer.json.gz", 2, "3dSceneLayer.json"),
+                record("nodes/0/0.bin.gz", 3, "nodes/0/0.bin"),
+                record("metadata.json", 4, "metadata.json"),
+            ],
+        )
+        .unwrap();
+        let earlier = read_record(&folder, &[]).unwrap().unwrap();
+        assert_eq!(
+            earlier.entries[0],
+            record("3dSceneLayer.json.gz", 2, "3dSceneLayer.json")
+        );
+
+        // The file of node 0 has gone, and the layer document changed.
+        let entries = vec![
+            ("nodes/0/0.bin.gz".to_string(), 3, 8),
+            ("3dSceneLayer.json.gz".to_string(), 5, 8),
+            ("metadata.json".to_string(), 4, 8),
+        ];
+        let changes = find_changes(&folder, &[], &earlier, &options, entries);
+        assert_eq!(changes.unchanged, HashSet::new());
+        assert_eq!(
+            changes.removed,
+            vec![record("nodes/1/0.bin.gz", 1, "nodes/1/0.bin")]
+        );
+        let deleted = delete_removed(&folder, &[], &changes.removed, &HashSet::new()).unwrap();
+        let node_left = folder.join("nodes/1").exists();
+        let nodes_left = folder.join("nodes").exists();
+
+        let unchanged = find_changes(
+            &folder,
+            &[],
+            &earlier,
+            &options,
+            vec![("3dSceneLayer.json.gz".to_string(), 2, 8)],
+        );
+        let other_options = output_options(&UnpackOptions::new().keep_gzip(true));
+        let superseded = find_changes(
+            &folder,
+            &[],
+            &earlier,
+            &other_options,
+            vec![("3dSceneLayer.json.gz".to_string(), 2, 8)],
+        );
+        std::fs::write(folder.join(RECORD_FILE), b"{\"entries\":[{}]}").unwrap();
+        let corrupted = read_record(&folder, &[]);
+
+        assert_eq!((deleted, node_left, nodes_left), (1, false, false));
+        assert!(unchanged.unchanged.contains("3dSceneLayer.json.gz"));
+        assert!(superseded.unchanged.is_empty());
+        assert_eq!(
+            superseded.superseded,
+            vec![record("3dSceneLayer.json.gz", 2, "3dSceneLayer.json")]
+        );
+        assert!(corrupted.is_err());
+    }
+
+    #[test]
+    fn records_may_only_lead_into_the_folder() {
+        let dir = TempDir::new("incremental-paths");
+        let folder = dir.join("out/package");
+        let textures = dir.join("textures");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(dir.join("victim.txt"), b"kept").unwrap();
+        let record = |path: String| EntryRecord {
+            name: "gone.json".to_string(),
+            crc32: 0,
+            size: 0,
+            path,
+        };
+        let options = output_options(&UnpackOptions::new());
+        let read = |path: String| {
+            write_record(&folder, options.clone(), vec![record(path)]).unwrap();
+            read_record(&folder, &[&textures])
+        };
+        let outside = vec![
+            "../../victim.txt".to_string(),
+            "nodes/../../../victim.txt".to_string(),
+            dir.join("victim.txt").to_string_lossy().into_owned(),
+            textures
+                .join("../victim.txt")
+                .to_string_lossy()
+                .into_owned(),
+        ];
+        let refused = outside.into_iter().all(|path| read(path).is_err());
+        let routed = read(textures.join("0.jpg").to_string_lossy().into_owned());
+        let inside = read("nodes/0/0.bin".to_string());
+        let victim = std::fs::read(dir.join("victim.txt"));
+
+        assert!(refused);
+        assert_eq!(
+            routed.unwrap().unwrap().entries[0].file(&folder, &[&textures]),
+            Some(textures.join("0.jpg"))
+        );
+        assert_eq!(
+            inside.unwrap().unwrap().entries[0].file(&folder, &[]),
+            Some(folder.join("nodes/0/0.bin"))
+        );
+        assert_eq!(victim.unwrap(), b"kept");
+    }
+}
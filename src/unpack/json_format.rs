@@ -0,0 +1,250 @@
+// JSON documents are normally written exactly as the package holds them.
+// They can instead be pretty-printed, for reading and diffing, or minified,
+// to save disk space on attribute heavy packages. A document is read to its
+// end and parsed before any of it is written, so reformatting holds one
+// document in memory at a time. Documents which don't parse are written as
+// they are, since unpacking isn't the place to reject them.
+//
+// The keys of reformatted documents can also be sorted, at every level, so
+// that unpacking the output of two versions of an exporter and diffing them
+// only shows what changed, rather than the order each version writes keys
+// in. The members are sorted where they were parsed, so this costs time
+// rather than memory, but a document with millions of members, such as the
+// statistics of a large layer, takes long enough that documents above a size
+// are written with their keys in the order given, with a warning.
+
+use crate::json;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Invalid JSON format '{}', expected as-is, minify, pretty, pretty:<spaces> or pretty:tab",
+    _0
+)]
+pub struct InvalidJsonFormatting(String);
+
+/// The largest document whose keys are sorted, unless another size is
+/// given.
+pub const DEFAULT_SORT_KEYS_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// What each level of a pretty-printed document is indented by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indentation {
+    /// This many spaces.
+    Spaces(usize),
+    /// A tab.
+    Tab,
+}
+
+impl Indentation {
+    fn unit(self) -> String {
+        match self {
+            Indentation::Spaces(spaces) => " ".repeat(spaces),
+            Indentation::Tab => "\t".to_string(),
+        }
+    }
+}
+
+/// How the JSON documents of a package are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JsonFormatting {
+    /// Byte for byte as they are stored, once decompressed.
+    #[default]
+    AsIs,
+    /// Reformatted with a member or item on each line.
+    Pretty(Indentation),
+    /// Reformatted without any whitespace.
+    Minify,
+}
+
+impl FromStr for JsonFormatting {
+    type Err = InvalidJsonFormatting;
+
+    fn from_str(s: &str) -> Result<JsonFormatting, InvalidJsonFormatting> {
+        let indentation = match s {
+            "as-is" => return Ok(JsonFormatting::AsIs),
+            "minify" => return Ok(JsonFormatting::Minify),
+            "pretty" => Indentation::Spaces(2),
+            "pretty:tab" => Indentation::Tab,
+            _ => match s.strip_prefix("pretty:").map(str::parse) {
+                Some(Ok(spaces)) => Indentation::Spaces(spaces),
+                _ => return Err(InvalidJsonFormatting(s.to_string())),
+            },
+        };
+        Ok(JsonFormatting::Pretty(indentation))
+    }
+}
+
+/// Whether a file holds a JSON document, from its extension, so that
+/// `layer.json` and `footprints.geojson` do but `notjson` doesn't.
+pub fn is_json_file(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("json") || extension.eq_ignore_ascii_case("geojson")
+        }
+        None => false,
+    }
+}
+
+/// Reads a document through `inner`, reformatting it before the first
+/// read returns. With `JsonFormatting::AsIs` it reads straight through.
+pub struct FormattedJson<R> {
+    inner: R,
+    formatting: JsonFormatting,
+    /// The name of the entry and the largest document whose keys are
+    /// sorted, when they are.
+    sort_keys: Option<(String, u64)>,
+    formatted: Option<Cursor<Vec<u8>>>,
+}
+
+impl<R: Read> FormattedJson<R> {
+    pub fn new(inner: R, formatting: JsonFormatting) -> FormattedJson<R> {
+        FormattedJson {
+            inner,
+            formatting,
+            sort_keys: None,
+            formatted: None,
+        }
+    }
+
+    /// Sorts the keys of the document of `entry_name` as it is reformatted,
+    /// when it is no larger than `max_size`. Documents which are written as
+    /// they are stored keep their keys in any case.
+    pub fn sorting_keys(mut self, entry_name: &str, max_size: Option<u64>) -> FormattedJson<R> {
+        if self.formatting != JsonFormatting::AsIs {
+            self.sort_keys = max_size.map(|max_size| (entry_name.to_string(), max_size));
+        }
+        self
+    }
+
+    fn format(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut document = Vec::new();
+        self.inner.read_to_end(&mut document)?;
+        let mut value = match json::parse_bytes(&document) {
+            Ok(value) => value,
+            Err(_) => return Ok(document),
+        };
+        match &self.sort_keys {
+            Some((_, max_size)) if document.len() as u64 <= *max_size => value.sort_keys(),
+            Some((entry_name, max_size)) => warn!(
+                "{} is {} bytes, more than the {} whose keys are sorted, so its keys are left in their order",
+                entry_name,
+                document.len(),
+                max_size
+            ),
+            None => {}
+        }
+        Ok(match self.formatting {
+            JsonFormatting::AsIs => return Ok(document),
+            JsonFormatting::Pretty(indentation) => {
+                value.to_indented_string(&indentation.unit()) + "\n"
+            }
+            JsonFormatting::Minify => value.to_compact_string(),
+        }
+        .into_bytes())
+    }
+}
+
+impl<R: Read> Read for FormattedJson<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.formatting == JsonFormatting::AsIs {
+            return self.inner.read(buf);
+        }
+        if self.formatted.is_none() {
+            self.formatted = Some(Cursor::new(self.format()?));
+        }
+        self.formatted
+            .as_mut()
+            .map_or(Ok(0), |formatted| formatted.read(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn documents_are_written_in_the_chosen_format() {
+        let dir = TempDir::new("json-format");
+        let path = dir.join("package.slpk");
+        let document = b"{ \"id\": 0,\n  \"fields\": [ 1, 2 ] }";
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", document)
+            .entry("footprints.geojson", document)
+            .entry("nodes/0/notjson", document)
+            .entry("metadata.json", b"{ not JSON")
+            .write_to_file(&path)
+            .unwrap();
+        let written = |formatting: &str| {
+            let out = dir.join(formatting.replace(':', "-"));
+            let options = UnpackOptions::new()
+                .output_dir(out.clone())
+                .json_formatting(formatting.parse().unwrap());
+            let summary = unpack_with_options(&path, &options).unwrap();
+            let read = |name: &str| {
+                String::from_utf8(std::fs::read(out.join("package").join(name)).unwrap()).unwrap()
+            };
+            let files = (
+                read("3dSceneLayer.json"),
+                read("footprints.geojson"),
+                read("nodes/0/notjson"),
+                read("metadata.json"),
+            );
+            let bytes = files.0.len() + files.1.len() + files.2.len() + files.3.len();
+            assert_eq!(summary.bytes_written, bytes as u64);
+            files
+        };
+        let as_is = written("as-is");
+        let minified = written("minify");
+        let pretty = written("pretty:tab");
+        let invalid = "pretty:wide".parse::<JsonFormatting>();
+
+        let original = String::from_utf8(document.to_vec()).unwrap();
+        assert_eq!(as_is.0, original);
+        assert_eq!(minified.0, r#"{"id":0,"fields":[1,2]}"#);
+        assert_eq!(minified.1, minified.0);
+        assert_eq!(
+            pretty.0,
+            "{\n\t\"id\": 0,\n\t\"fields\": [\n\t\t1,\n\t\t2\n\t]\n}\n"
+        );
+        // notjson isn't named as a document, and metadata.json doesn't hold
+        // one.
+        assert_eq!((minified.2, pretty.2), (original.clone(), original));
+        assert_eq!(pretty.3, "{ not JSON");
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn keys_are_sorted_in_documents_up_to_the_limit() {
+        let dir = TempDir::new("sort-keys");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"z": 0, "a": {"y": 1, "b": 2}}"#,
+            )
+            .entry(
+                "statistics/f_0/0.json.gz",
+                br#"{"stats": {"max": 9, "min": 1}}"#,
+            )
+            .write_to_file(&path)
+            .unwrap();
+        let options = UnpackOptions::new()
+            .output_dir(dir.to_path_buf())
+            .json_formatting(JsonFormatting::Minify)
+            .sort_keys(true)
+            .sort_keys_max_size(31);
+        unpack_with_options(&path, &options).unwrap();
+        let read = |name: &str| std::fs::read_to_string(dir.join("package").join(name)).unwrap();
+        let layer = read("3dSceneLayer.json");
+        let statistics = read("statistics/f_0/0.json");
+
+        assert_eq!(layer, r#"{"a":{"b":2,"y":1},"z":0}"#);
+        // Larger than the limit, so only minified.
+        assert_eq!(statistics, r#"{"stats":{"max":9,"min":1}}"#);
+    }
+}
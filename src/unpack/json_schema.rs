@@ -0,0 +1,108 @@
+// Checks the JSON resources of a package against their schemas as they are
+// unpacked. The check reads what the decoder gives the writer, keeping a
+// copy of the document as it goes by, so nothing is decompressed twice, and
+// checks the copy once the document has been read to its end. Only the
+// documents `schema::schema_of_entry` knows a schema for are copied, and
+// those are small beside the geometry and textures of a package. A document
+// which doesn't match its schema is a warning, unless checks are strict, in
+// which case its read fails, so that its staged file is removed and the
+// entry fails like any other.
+
+use crate::schema;
+use std::io::{self, Read};
+
+/// Reads `inner`, checking what it reads against the schema of `entry_name`
+/// once it is read to its end.
+pub struct SchemaCheck<'a, R> {
+    inner: R,
+    entry_name: &'a str,
+    /// What has been read so far, when the entry has a schema to check.
+    document: Option<Vec<u8>>,
+    strict: bool,
+}
+
+impl<'a, R: Read> SchemaCheck<'a, R> {
+    /// Checks nothing unless `check` is set.
+    pub fn new(inner: R, entry_name: &'a str, check: bool, strict: bool) -> SchemaCheck<'a, R> {
+        let document =
+            Some(Vec::new()).filter(|_| check && schema::schema_of_entry(entry_name).is_some());
+        SchemaCheck {
+            inner,
+            entry_name,
+            document,
+            strict,
+        }
+    }
+}
+
+impl<R: Read> Read for SchemaCheck<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 || buf.is_empty() {
+            if let Some(document) = &mut self.document {
+                document.extend_from_slice(&buf[..read]);
+            }
+            return Ok(read);
+        }
+        if let Some(document) = self.document.take() {
+            if let Some(violation) = schema::check_entry(self.entry_name, &document) {
+                if self.strict {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} doesn't match its schema: {}",
+                            self.entry_name, violation
+                        ),
+                    ));
+                }
+                warn!(
+                    "{} doesn't match its schema: {}",
+                    self.entry_name, violation
+                );
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn documents_which_dont_match_fail_only_when_strict() {
+        let dir = TempDir::new("json-schema");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"3DObject"}"#,
+            )
+            .entry("nodepages/0.json.gz", br#"{"nodes":[{"index":"0"}]}"#)
+            .write_to_file(&path)
+            .unwrap();
+        let unpack_into = |name: &str, strict: bool| {
+            let options = UnpackOptions::new()
+                .validate_json(true)
+                .strict_json(strict)
+                .output_dir(dir.join(name));
+            let result =
+                unpack_with_options(&path, &options).map(|summary| summary.entries_unpacked);
+            let page = dir.join(name).join("package/nodepages/0.json");
+            (result, page.exists())
+        };
+        let (warned, warned_page) = unpack_into("warn", false);
+        let (failed, failed_page) = unpack_into("strict", true);
+
+        assert_eq!(warned.unwrap(), 2);
+        assert!(warned_page);
+        let failed = failed.unwrap_err().to_string();
+        assert!(failed.contains("/nodes/0/index"), "{}", failed);
+        assert!(!failed_page);
+
+        let mut unchecked = SchemaCheck::new(&b"{"[..], "3dSceneLayer.json", false, true);
+        assert!(io::copy(&mut unchecked, &mut io::sink()).is_ok());
+    }
+}
@@ -0,0 +1,143 @@
+// Presets for unpacking one kind of resource, which know where a package
+// keeps each kind rather than leaving it to path patterns. Resources are
+// classified by the folder they have below their node, `nodes/<id>/`, which
+// is the same for I3S 1.6 and for the node page layout of 1.7 and later,
+// though the names of the files in it differ. JSON documents are metadata
+// wherever they are stored.
+
+use std::fmt;
+use std::path::{Component, Path};
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Invalid resource kind '{}', expected textures, geometry, attributes or metadata",
+    _0
+)]
+pub struct InvalidResourceKind(String);
+
+/// The kinds of resource `--only` selects.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResourceKind {
+    /// Texture payloads, such as `0.jpg`, `0_0_1.bin.dds.gz` or `1.ktx2`.
+    Textures,
+    /// Geometry buffers, such as `0.bin.gz`.
+    Geometry,
+    /// Attribute values, such as `f_1/0.bin.gz`.
+    Attributes,
+    /// Every JSON document, from the layer document and `metadata.json` to
+    /// node pages, node index documents, features and statistics.
+    Metadata,
+    /// Anything else, such as the hash index.
+    Other,
+}
+
+impl ResourceKind {
+    /// Classifies a sanitized entry path.
+    pub fn of_entry(entry_path: &Path) -> ResourceKind {
+        let name = entry_path.to_string_lossy();
+        let name = name.strip_suffix(".gz").unwrap_or(&name);
+        if name.ends_with(".json") || name.ends_with(".geojson") {
+            return ResourceKind::Metadata;
+        }
+        let components: Vec<&str> = entry_path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        // The folder after `nodes/<id>/`, as long as the file is below it.
+        let folder = components
+            .windows(4)
+            .find(|window| window[0] == "nodes")
+            .map(|window| window[2]);
+        match folder {
+            Some("textures") => ResourceKind::Textures,
+            Some("geometries") => ResourceKind::Geometry,
+            Some("attributes") => ResourceKind::Attributes,
+            _ => ResourceKind::Other,
+        }
+    }
+}
+
+impl FromStr for ResourceKind {
+    type Err = InvalidResourceKind;
+
+    fn from_str(s: &str) -> Result<ResourceKind, InvalidResourceKind> {
+        match s {
+            "textures" => Ok(ResourceKind::Textures),
+            "geometry" => Ok(ResourceKind::Geometry),
+            "attributes" => Ok(ResourceKind::Attributes),
+            "metadata" => Ok(ResourceKind::Metadata),
+            _ => Err(InvalidResourceKind(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ResourceKind::Textures => "textures",
+            ResourceKind::Geometry => "geometry",
+            ResourceKind::Attributes => "attributes",
+            ResourceKind::Metadata => "metadata",
+            ResourceKind::Other => "other",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_resources_of_either_layout() {
+        let kind = |name| ResourceKind::of_entry(Path::new(name));
+        // I3S 1.6, with a node index document per node.
+        assert_eq!(kind("nodes/root/textures/0_0.jpg"), ResourceKind::Textures);
+        assert_eq!(
+            kind("nodes/12/textures/0_0_1.bin.dds.gz"),
+            ResourceKind::Textures
+        );
+        assert_eq!(kind("nodes/12/geometries/0.bin.gz"), ResourceKind::Geometry);
+        assert_eq!(
+            kind("nodes/12/3dNodeIndexDocument.json.gz"),
+            ResourceKind::Metadata
+        );
+        assert_eq!(kind("nodes/12/features/0.json.gz"), ResourceKind::Metadata);
+        assert_eq!(
+            kind("nodes/12/shared/sharedResource.json.gz"),
+            ResourceKind::Metadata
+        );
+        // I3S 1.7 and later, with node pages.
+        assert_eq!(kind("nodes/3/textures/1.ktx2"), ResourceKind::Textures);
+        assert_eq!(kind("nodes/3/geometries/1.bin.gz"), ResourceKind::Geometry);
+        assert_eq!(
+            kind("layers/0/nodes/3/attributes/f_1/0.bin.gz"),
+            ResourceKind::Attributes
+        );
+        assert_eq!(kind("nodepages/0.json.gz"), ResourceKind::Metadata);
+        // Point clouds, whose attributes are a file per key.
+        assert_eq!(
+            kind("nodes/5/geometries/0.bin.pccxyz"),
+            ResourceKind::Geometry
+        );
+        assert_eq!(
+            kind("nodes/5/attributes/2.bin.gz"),
+            ResourceKind::Attributes
+        );
+        assert_eq!(kind("metadata.json"), ResourceKind::Metadata);
+        assert_eq!(kind("3dSceneLayer.json.gz"), ResourceKind::Metadata);
+        // A folder named like a resource folder, but not below a node.
+        assert_eq!(kind("textures/0.jpg"), ResourceKind::Other);
+        assert_eq!(kind("nodes/3/textures"), ResourceKind::Other);
+        assert_eq!(kind("@specialIndexFileHASH128@"), ResourceKind::Other);
+
+        assert_eq!(
+            "geometry".parse::<ResourceKind>().unwrap(),
+            ResourceKind::Geometry
+        );
+        assert!("other".parse::<ResourceKind>().is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// Selects the nodes down to a level of detail, for unpacking a preview of a
+// layer whose full resolution is far larger than its coarse levels. The
+// depth of each node is that of the hierarchy the other commands read, with
+// the root at level 0, so the levels kept are those `slpkg tree` prints.
+// Everything stored below the folder of a node which is kept is unpacked,
+// and nothing below the folder of any other node, so the nodes at the cut
+// are complete but refer to children which don't exist. Entries which
+// aren't below `nodes/`, such as the layer document and the node pages,
+// are always unpacked. Nodes which can't be reached from the root, which
+// the hierarchy leaves at level 0, are kept.
+
+use crate::i3s;
+use crate::i3s::Node;
+use failure::Error;
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+/// The folders of the nodes kept in each layer, below which everything is
+/// unpacked.
+#[derive(Debug, Default)]
+pub struct NodeFolders {
+    /// The `nodes/` folder of each layer, with the folders of the nodes
+    /// kept below it, such as `12/`.
+    layers: Vec<(String, HashSet<String>)>,
+}
+
+impl NodeFolders {
+    /// Keeps the folders of `nodes`, of `layer`.
+    pub fn keep<'a>(&mut self, layer: &i3s::SceneLayer, nodes: impl IntoIterator<Item = &'a Node>) {
+        let mut folders = HashSet::new();
+        for node in nodes {
+            // A node of the node pages is looked for in the folder named by
+            // its index as well, as `slpkg extract-node` does.
+            for resource in [&node.id, &node.resource]
+                .iter()
+                .copied()
+                .chain(&node.other_resources)
+            {
+                folders.insert(format!("{}/", resource));
+            }
+        }
+        self.layers.push((layer.entry_name("nodes/"), folders));
+    }
+
+    pub fn skips(&self, entry_name: &str) -> bool {
+        let layer = self
+            .layers
+            .iter()
+            .find(|(nodes_folder, _)| entry_name.starts_with(nodes_folder.as_str()));
+        let (nodes_folder, folders) = match layer {
+            Some(layer) => layer,
+            None => return false,
+        };
+        let name = &entry_name[nodes_folder.len()..];
+        match name.find('/') {
+            Some(end) => !folders.contains(&name[..=end]),
+            None => false,
+        }
+    }
+}
+
+/// The folders of the nodes down to a level, in each layer.
+#[derive(Debug, Default)]
+pub struct ShallowNodes {
+    /// The number of nodes kept, in every layer.
+    pub kept: usize,
+    /// The number of nodes left out, in every layer.
+    pub left_out: usize,
+    folders: NodeFolders,
+}
+
+impl ShallowNodes {
+    pub fn skips(&self, entry_name: &str) -> bool {
+        self.folders.skips(entry_name)
+    }
+}
+
+/// Finds the nodes of one layer at `max_level` or above, adding them to
+/// `shallow_nodes`.
+pub fn find_shallow_nodes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &i3s::SceneLayer,
+    max_level: usize,
+    shallow_nodes: &mut ShallowNodes,
+) -> Result<(), Error> {
+    let hierarchy = i3s::load_hierarchy(archive, layer)?;
+    let (kept, left_out): (Vec<&Node>, Vec<&Node>) = hierarchy
+        .nodes
+        .iter()
+        .partition(|node| node.level <= max_level);
+    shallow_nodes.kept += kept.len();
+    shallow_nodes.left_out += left_out.len();
+    shallow_nodes.folders.keep(layer, kept);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::SyntheticPackage;
+    use std::io::Cursor;
+
+    #[test]
+    fn keeps_the_folders_of_nodes_down_to_the_level() {
+        let package = SyntheticPackage::new()
+            .entry("3dSceneLayer.json", br#"{"nodePages":{}}"#)
+            .entry(
+                "nodepages/0.json",
+                br#"{"nodes":[
+                    {"index":0,"children":[1,2]},
+                    {"index":1,"children":[3],"mesh":{"geometry":{"resource":1},"material":{"resource":9}}},
+                    {"index":2},
+                    {"index":3,"mesh":{"geometry":{"resource":3}}}]}"#,
+            )
+            .to_bytes()
+            .unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(package)).unwrap();
+        let layer = i3s::read_scene_layer(&mut archive).unwrap();
+        let mut shallow_nodes = ShallowNodes::default();
+        find_shallow_nodes(&mut archive, &layer, 1, &mut shallow_nodes).unwrap();
+
+        assert_eq!((shallow_nodes.kept, shallow_nodes.left_out), (3, 1));
+        assert!(!shallow_nodes.skips("nodes/1/geometries/0.bin.gz"));
+        assert!(!shallow_nodes.skips("nodes/9/textures/0.jpg"));
+        assert!(!shallow_nodes.skips("nodes/2/3dNodeIndexDocument.json.gz"));
+        assert!(shallow_nodes.skips("nodes/3/geometries/0.bin.gz"));
+        assert!(shallow_nodes.skips("nodes/31/geometries/0.bin.gz"));
+        assert!(!shallow_nodes.skips("nodepages/0.json"));
+        assert!(!shallow_nodes.skips("3dSceneLayer.json"));
+    }
+}
@@ -0,0 +1,107 @@
+// Windows refuses paths longer than MAX_PATH, 260 characters, unless they
+// are verbatim: absolute, separated by backslashes alone, and prefixed with
+// `\\?\`, which has them passed to the file system as they are. Large
+// integrated mesh packages nest their nodes deeply enough to pass the limit
+// below an output folder which is deep itself, failing with a bare "os
+// error 3". So the unpack folder is made verbatim once, by canonicalizing
+// it, and every folder and file joined to it is verbatim too; folders and
+// files are still created through `long_path` in case one isn't. Elsewhere
+// paths are used as they are, and all of this compiles away.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// `folder` as a verbatim path on Windows, or as it is when it can't be
+/// canonicalized, such as when a dry run hasn't created it.
+#[cfg(windows)]
+pub fn verbatim_folder(folder: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&folder).unwrap_or(folder)
+}
+
+#[cfg(not(windows))]
+pub fn verbatim_folder(folder: PathBuf) -> PathBuf {
+    folder
+}
+
+/// The path to create a folder or file at: on Windows, `path` with the
+/// verbatim prefix when it is absolute and doesn't have it already.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let mut verbatim = match components.next() {
+        Some(Component::Prefix(prefix)) if path.has_root() => match prefix.kind() {
+            Prefix::Disk(disk) => PathBuf::from(format!(r"\\?\{}:\", char::from(disk))),
+            Prefix::UNC(server, share) => {
+                let mut verbatim = PathBuf::from(r"\\?\UNC\");
+                verbatim.push(server);
+                verbatim.push(share);
+                verbatim
+            }
+            _ => return Cow::Borrowed(path),
+        },
+        _ => return Cow::Borrowed(path),
+    };
+    // A verbatim path isn't normalized, so `.` and `..` are resolved here.
+    for component in components {
+        match component {
+            Component::Normal(name) => verbatim.push(name),
+            Component::ParentDir => {
+                verbatim.pop();
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(verbatim)
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn entries_deeper_than_max_path_are_unpacked() {
+        let dir = TempDir::new("long-paths");
+        let path = dir.join("package.slpk");
+        let folder = "f".repeat(60);
+        let entry = format!("nodes/{}/{0}/{0}/{0}/{0}/features/0.json", folder);
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{}")
+            .entry(&entry, b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        let options = UnpackOptions::new().output_dir(dir.join("out"));
+        let summary = unpack_with_options(&path, &options);
+        let target = dir.join("out").join("package").join(&entry);
+        let written = std::fs::read(long_path(&target));
+        std::fs::remove_dir_all(long_path(&dir)).unwrap();
+
+        assert!(target.as_os_str().len() > 300);
+        summary.unwrap();
+        assert_eq!(written.unwrap(), b"{}");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn absolute_paths_are_made_verbatim() {
+        let long = |path: &str| long_path(Path::new(path)).into_owned();
+        assert_eq!(
+            long(r"C:\out\..\data/nodes"),
+            Path::new(r"\\?\C:\data\nodes")
+        );
+        assert_eq!(
+            long(r"\\server\share\out"),
+            Path::new(r"\\?\UNC\server\share\out")
+        );
+        assert_eq!(long(r"\\?\C:\out"), Path::new(r"\\?\C:\out"));
+        assert_eq!(long(r"relative\out"), Path::new(r"relative\out"));
+    }
+}
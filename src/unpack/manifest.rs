@@ -0,0 +1,395 @@
+// A manifest of what each unpacked file is in I3S terms, so that scripts can
+// find the textures of a node at some level without parsing the layer
+// documents themselves.
+
+use super::verify::{Mismatch, UnpackedFile};
+use crate::i3s;
+use crate::i3s::SceneLayer;
+use crate::json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What a file is to the layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Role {
+    LayerDocument,
+    NodePage,
+    NodeDocument,
+    Geometry,
+    Texture,
+    Attribute,
+    Features,
+    Shared,
+    Statistics,
+    Other,
+}
+
+impl Role {
+    fn of(path_in_layer: &str) -> Role {
+        let parts: Vec<&str> = path_in_layer.split('/').collect();
+        match parts.as_slice() {
+            [document] if document.starts_with("3dSceneLayer.json") => Role::LayerDocument,
+            ["nodepages", _] => Role::NodePage,
+            ["statistics", ..] => Role::Statistics,
+            ["nodes", _, document] if document.starts_with("3dNodeIndexDocument.json") => {
+                Role::NodeDocument
+            }
+            ["nodes", _, "geometries", ..] => Role::Geometry,
+            ["nodes", _, "textures", ..] => Role::Texture,
+            ["nodes", _, "attributes", ..] => Role::Attribute,
+            ["nodes", _, "features", ..] => Role::Features,
+            ["nodes", _, "shared", ..] => Role::Shared,
+            _ => Role::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Role::LayerDocument => "layer",
+            Role::NodePage => "node-page",
+            Role::NodeDocument => "node-index",
+            Role::Geometry => "geometry",
+            Role::Texture => "texture",
+            Role::Attribute => "attribute",
+            Role::Features => "features",
+            Role::Shared => "shared",
+            Role::Statistics => "statistics",
+            Role::Other => "other",
+        }
+    }
+
+    /// The field of the node which refers to a resource of this role. Node
+    /// pages and node index documents name them differently.
+    fn referenced_by(self, node_pages: bool) -> Option<&'static str> {
+        match (self, node_pages) {
+            (Role::Geometry, true) => Some("mesh.geometry.resource"),
+            (Role::Texture, true) => Some("mesh.material.resource"),
+            (Role::Attribute, true) => Some("mesh.attribute.resource"),
+            (Role::Geometry, false) => Some("geometryData"),
+            (Role::Texture, false) => Some("textureData"),
+            (Role::Attribute, false) => Some("attributeData"),
+            (Role::Features, false) => Some("featureData"),
+            (Role::Shared, false) => Some("sharedResource"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NodeInfo {
+    id: String,
+    level: usize,
+    parent: Option<String>,
+}
+
+#[derive(Debug)]
+struct LayerInfo {
+    id: usize,
+    root: String,
+    node_pages: bool,
+    /// By the name of the node's folder under `nodes/`.
+    nodes: HashMap<String, NodeInfo>,
+}
+
+/// The nodes of every layer, read before unpacking. Nothing about a package
+/// which can't be parsed stops it being unpacked; the manifest just says less
+/// about it.
+#[derive(Debug, Default)]
+pub struct NodeIndex {
+    layers: Vec<LayerInfo>,
+    problems: Vec<String>,
+}
+
+impl NodeIndex {
+    pub fn read<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        selected_layer: Option<&SceneLayer>,
+    ) -> NodeIndex {
+        let mut index = NodeIndex::default();
+        let layers = match selected_layer {
+            Some(layer) => vec![layer.clone()],
+            None => match i3s::read_layers(archive) {
+                Ok(layers) => layers,
+                Err(e) => {
+                    index.problems.push(e.to_string());
+                    return index;
+                }
+            },
+        };
+        for layer in layers {
+            let mut nodes = HashMap::new();
+            match i3s::load_hierarchy(archive, &layer) {
+                Ok(hierarchy) => {
+                    for node in &hierarchy.nodes {
+                        let parent = node.parent.map(|p| hierarchy.nodes[p].id.clone());
+                        nodes.insert(
+                            node.resource.clone(),
+                            NodeInfo {
+                                id: node.id.clone(),
+                                level: node.level,
+                                parent,
+                            },
+                        );
+                    }
+                }
+                Err(e) => index.problems.push(format!("layer {}: {}", layer.id, e)),
+            }
+            index.layers.push(LayerInfo {
+                id: layer.id,
+                root: layer.root.clone(),
+                node_pages: layer.uses_node_pages(),
+                nodes,
+            });
+        }
+        index
+    }
+
+    /// Why the manifest says less than it should, if it does.
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// The layer an entry belongs to. Layers below `layers/` are nested in
+    /// the folder of a layer at the root, so the longest root wins.
+    fn layer_of(&self, entry_name: &str) -> Option<&LayerInfo> {
+        self.layers
+            .iter()
+            .filter(|layer| entry_name.starts_with(layer.root.as_str()))
+            .max_by_key(|layer| layer.root.len())
+    }
+
+    fn describe(&self, unpacked: &UnpackedFile, unpack_folder: &Path) -> Value {
+        let entry_name = unpacked.entry_name.as_str();
+        let written = &unpacked.file;
+        let file = written
+            .path
+            .strip_prefix(unpack_folder)
+            .unwrap_or(&written.path);
+        let mut members = vec![
+            (
+                "file".to_string(),
+                Value::from(file.to_string_lossy().replace('\\', "/")),
+            ),
+            ("entry".to_string(), Value::from(entry_name)),
+            ("index".to_string(), Value::from(unpacked.entry_index)),
+            ("dataOffset".to_string(), Value::from(unpacked.data_offset)),
+            ("bytes".to_string(), Value::from(written.bytes)),
+            (
+                "crc32".to_string(),
+                Value::from(format!("{:08x}", written.crc32)),
+            ),
+        ];
+        if let Some(gzip) = &written.gzip {
+            members.push(("gzipHeader".to_string(), gzip.to_json()));
+        }
+        let layer = match self.layer_of(entry_name) {
+            Some(layer) => layer,
+            None => {
+                members.push(("role".to_string(), Value::from(Role::Other.name())));
+                return Value::Object(members);
+            }
+        };
+        let path_in_layer = &entry_name[layer.root.len()..];
+        let role = Role::of(path_in_layer);
+        members.push(("role".to_string(), Value::from(role.name())));
+        members.push(("layer".to_string(), Value::from(layer.id)));
+
+        let mut parts = path_in_layer.splitn(3, '/');
+        if let (Some("nodes"), Some(resource)) = (parts.next(), parts.next()) {
+            if let Some(node) = layer.nodes.get(resource) {
+                members.push(("node".to_string(), Value::from(node.id.as_str())));
+                members.push(("level".to_string(), Value::from(node.level)));
+                members.push((
+                    "parent".to_string(),
+                    node.parent
+                        .as_deref()
+                        .map(Value::from)
+                        .unwrap_or(Value::Null),
+                ));
+            }
+            if let Some(field) = role.referenced_by(layer.node_pages) {
+                members.push(("referencedBy".to_string(), Value::from(field)));
+            }
+        }
+        Value::Object(members)
+    }
+}
+
+/// Describes each unpacked file, along with the outcome of verifying them
+/// when they were. The files of the entries in `renamed`, which were
+/// unpacked under other names, are listed with their entries again.
+pub fn to_json(
+    index: &NodeIndex,
+    files: &[UnpackedFile],
+    renamed: &HashSet<usize>,
+    mismatches: Option<&[Mismatch]>,
+    unpack_folder: &Path,
+) -> Value {
+    let mut members = vec![(
+        "partial".to_string(),
+        Value::Bool(!index.problems.is_empty()),
+    )];
+    if !index.problems.is_empty() {
+        members.push((
+            "problems".to_string(),
+            Value::Array(
+                index
+                    .problems
+                    .iter()
+                    .map(|p| Value::from(p.as_str()))
+                    .collect(),
+            ),
+        ));
+    }
+    members.push((
+        "files".to_string(),
+        Value::Array(
+            files
+                .iter()
+                .map(|unpacked| index.describe(unpacked, unpack_folder))
+                .collect(),
+        ),
+    ));
+    let mut renamed_files: Vec<&UnpackedFile> = files
+        .iter()
+        .filter(|unpacked| renamed.contains(&unpacked.entry_index))
+        .collect();
+    if !renamed_files.is_empty() {
+        renamed_files.sort_by_key(|unpacked| unpacked.entry_index);
+        let renamed_files = renamed_files
+            .iter()
+            .map(|unpacked| {
+                let file = unpacked
+                    .file
+                    .path
+                    .strip_prefix(unpack_folder)
+                    .unwrap_or(&unpacked.file.path);
+                Value::Object(vec![
+                    (
+                        "entry".to_string(),
+                        Value::from(unpacked.entry_name.as_str()),
+                    ),
+                    (
+                        "file".to_string(),
+                        Value::from(file.to_string_lossy().replace('\\', "/")),
+                    ),
+                ])
+            })
+            .collect();
+        members.push(("renamedEntries".to_string(), Value::Array(renamed_files)));
+    }
+    if let Some(mismatches) = mismatches {
+        let mismatches = mismatches
+            .iter()
+            .map(|m| {
+                Value::Object(vec![
+                    ("entry".to_string(), Value::from(m.entry_name.as_str())),
+                    ("problem".to_string(), Value::from(m.problem.as_str())),
+                ])
+            })
+            .collect();
+        members.push((
+            "verification".to_string(),
+            Value::Object(vec![
+                ("verified".to_string(), Value::from(files.len())),
+                ("mismatches".to_string(), Value::Array(mismatches)),
+            ]),
+        ));
+    }
+    Value::Object(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unpack::verify::WrittenFile;
+    use std::io::{Cursor, Write};
+    use std::path::PathBuf;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn package(node_page: &[u8]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, contents: &[u8]| {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        };
+        add("3dSceneLayer.json", br#"{"nodePages":{}}"#);
+        add("nodepages/0.json", node_page);
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn describes_files_by_node_and_role() {
+        let mut archive = package(br#"{"nodes":[{"index":0,"children":[1]},{"index":1}]}"#);
+        let index = NodeIndex::read(&mut archive, None);
+        let folder = Path::new("out");
+        let unpacked = |entry_name: &str, path: PathBuf| UnpackedFile {
+            entry_index: 7,
+            entry_name: entry_name.to_string(),
+            data_offset: 1234,
+            file: WrittenFile {
+                path,
+                decompressed: false,
+                crc32: 0xcafe,
+                bytes: 4,
+                gzip: None,
+                sha256: None,
+            },
+        };
+        let files = vec![
+            unpacked(
+                "nodes/1/geometries/0.bin.gz",
+                PathBuf::from("/geometry/nodes/1/geometries/0.bin"),
+            ),
+            unpacked(
+                "nodes/1/textures/0.jpg",
+                folder.join("nodes/1/textures/0.jpg"),
+            ),
+        ];
+        let manifest = to_json(&index, &files, &HashSet::new(), None, folder);
+        assert_eq!(manifest.get("partial"), Some(&Value::Bool(false)));
+        let files = manifest.get("files").and_then(Value::as_array).unwrap();
+
+        let geometry = &files[0];
+        assert_eq!(
+            geometry.get("file").and_then(Value::as_str),
+            Some("/geometry/nodes/1/geometries/0.bin")
+        );
+        assert_eq!(
+            geometry.get("role").and_then(Value::as_str),
+            Some("geometry")
+        );
+        assert_eq!(
+            geometry.get("referencedBy").and_then(Value::as_str),
+            Some("mesh.geometry.resource")
+        );
+        let texture = &files[1];
+        assert_eq!(
+            texture.get("file").and_then(Value::as_str),
+            Some("nodes/1/textures/0.jpg")
+        );
+        assert_eq!(texture.get("node").and_then(Value::as_str), Some("1"));
+        assert_eq!(texture.get("index").and_then(Value::as_u64), Some(7));
+        assert_eq!(
+            texture.get("dataOffset").and_then(Value::as_u64),
+            Some(1234)
+        );
+        assert_eq!(texture.get("level").and_then(Value::as_u64), Some(1));
+        assert_eq!(texture.get("parent").and_then(Value::as_str), Some("0"));
+        assert_eq!(
+            texture.get("crc32").and_then(Value::as_str),
+            Some("0000cafe")
+        );
+
+        let mut broken = package(b"{\"nodes\":");
+        let index = NodeIndex::read(&mut broken, None);
+        let manifest = to_json(&index, &[], &HashSet::new(), Some(&[]), folder);
+        assert_eq!(manifest.get("partial"), Some(&Value::Bool(true)));
+        assert!(manifest.get("verification").is_some());
+    }
+}
@@ -0,0 +1,222 @@
+// A budget for the memory the workers hold for entry contents, so that an
+// unpack on a small machine stays below its limit instead of being killed.
+// Each entry reserves what it needs before it is read, waiting while other
+// workers hold too much of the budget.
+
+use std::io::Read;
+use std::sync::{Condvar, Mutex};
+
+/// A rough cost of unpacking an entry as a stream: the inflate state and
+/// buffers of the zip and gzip decoders, plus the copy buffer.
+pub const STREAM_COST: u64 = 128 * 1024;
+
+const CHUNK: usize = 8 * 1024;
+
+#[derive(Debug, Default)]
+struct Usage {
+    in_use: u64,
+    peak: u64,
+}
+
+/// Limits the memory the workers of an unpack hold at once. Workers wait
+/// for enough to be released before reserving more.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    usage: Mutex<Usage>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    /// A budget of `limit` bytes.
+    pub fn new(limit: u64) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            usage: Mutex::new(Usage::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// The bytes which can be reserved at once.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// The most that was reserved at once.
+    pub fn peak(&self) -> u64 {
+        self.usage.lock().unwrap().peak
+    }
+
+    /// Waits until `bytes` of the budget are free and reserves them. Returns
+    /// `None` straight away when `bytes` is more than the whole budget, as
+    /// it could never be reserved.
+    ///
+    /// A worker only waits here while it holds no other reservation, so the
+    /// workers can't end up waiting on each other.
+    pub fn reserve(&self, bytes: u64) -> Option<Reservation<'_>> {
+        if bytes > self.limit {
+            return None;
+        }
+        let mut usage = self.usage.lock().unwrap();
+        while usage.in_use + bytes > self.limit {
+            usage = self.released.wait(usage).unwrap();
+        }
+        usage.in_use += bytes;
+        usage.peak = usage.peak.max(usage.in_use);
+        Some(Reservation {
+            budget: self,
+            bytes,
+        })
+    }
+
+    /// Reserves whatever a streamed entry costs, along with the buffer its
+    /// file is written through. A budget smaller than that still lets one
+    /// entry be unpacked at a time.
+    pub fn reserve_stream(&self, write_buffer_size: usize) -> Reservation<'_> {
+        self.reserve((STREAM_COST + write_buffer_size as u64).min(self.limit))
+            .expect("a reservation no larger than the limit")
+    }
+}
+
+/// Part of a budget, given back when dropped.
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl<'a> Reservation<'a> {
+    /// Reserves `bytes` more if they are free right now. This never waits,
+    /// since other workers may be waiting for this reservation.
+    fn try_grow(&mut self, bytes: u64) -> bool {
+        let mut usage = self.budget.usage.lock().unwrap();
+        if usage.in_use + bytes > self.budget.limit {
+            return false;
+        }
+        usage.in_use += bytes;
+        usage.peak = usage.peak.max(usage.in_use);
+        self.bytes += bytes;
+        true
+    }
+}
+
+impl<'a> Drop for Reservation<'a> {
+    fn drop(&mut self) {
+        self.budget.usage.lock().unwrap().in_use -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// The start of an entry's contents, read into memory.
+pub struct Buffered<'a> {
+    pub contents: Vec<u8>,
+    /// Whether `contents` is all there is. Otherwise the budget ran out,
+    /// and the rest has to be streamed after it.
+    pub complete: bool,
+    // Held until the contents are written.
+    _reservation: Reservation<'a>,
+}
+
+/// Reads `reader` into memory, keeping room in the budget for `copies` of
+/// its contents on top of the cost of streaming. `size_hint` is what the
+/// contents are expected to take, which is reserved before reading; when
+/// they turn out larger, the reservation grows as far as the budget allows.
+/// Returns `None` when even `size_hint` doesn't fit the budget.
+pub fn read_within_budget<'a>(
+    reader: &mut dyn Read,
+    size_hint: u64,
+    copies: u64,
+    budget: &'a MemoryBudget,
+) -> std::io::Result<Option<Buffered<'a>>> {
+    let mut reservation = match budget.reserve(STREAM_COST + copies * size_hint) {
+        Some(reservation) => reservation,
+        None => return Ok(None),
+    };
+    let mut contents = Vec::with_capacity(size_hint as usize);
+    let mut chunk = [0u8; CHUNK];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if read == 0 {
+            return Ok(Some(Buffered {
+                contents,
+                complete: true,
+                _reservation: reservation,
+            }));
+        }
+        if contents.len() + read > contents.capacity() {
+            // Grow by doubling, as a Vec would, but with the budget's
+            // consent.
+            let more = contents.capacity().max(CHUNK);
+            if !reservation.try_grow(copies * more as u64) {
+                // The chunk in hand is covered by the cost of streaming.
+                contents.extend_from_slice(&chunk[..read]);
+                return Ok(Some(Buffered {
+                    contents,
+                    complete: false,
+                    _reservation: reservation,
+                }));
+            }
+            contents.reserve_exact(contents.capacity() + more - contents.len());
+        }
+        contents.extend_from_slice(&chunk[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use crate::unpack::{unpack_with_options, DedupMode, UnpackOptions};
+    use std::io::{Cursor, Write};
+    use std::sync::Arc;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn large_entries_unpack_within_a_small_budget() {
+        let dir = TempDir::new("memory");
+        let package_path = dir.join("large.slpk");
+
+        let texture: Vec<u8> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+        let huge = vec![7u8; 400 * 1024];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for node in 0..4 {
+            writer
+                .start_file(
+                    format!("nodes/{}/textures/0.bin", node),
+                    FileOptions::default(),
+                )
+                .unwrap();
+            writer.write_all(&texture).unwrap();
+        }
+        writer
+            .start_file("nodes/4/textures/0.bin", FileOptions::default())
+            .unwrap();
+        writer.write_all(&huge).unwrap();
+        std::fs::write(&package_path, writer.finish().unwrap().into_inner()).unwrap();
+
+        // Room for one buffered texture at a time, and none for the huge one.
+        let budget = Arc::new(MemoryBudget::new(600 * 1024));
+        let result = unpack_with_options(
+            &package_path,
+            &UnpackOptions::new()
+                .threads(4)
+                .dedup(DedupMode::Copy)
+                .memory_budget(budget.clone()),
+        );
+        let unpacked = [
+            std::fs::read(dir.join("large/nodes/3/textures/0.bin")),
+            std::fs::read(dir.join("large/nodes/4/textures/0.bin")),
+        ];
+
+        assert_eq!(result.unwrap().entries_unpacked, 5);
+        assert_eq!(unpacked[0].as_ref().unwrap(), &texture);
+        assert_eq!(unpacked[1].as_ref().unwrap(), &huge);
+        assert!(budget.peak() > STREAM_COST);
+        assert!(budget.peak() <= budget.limit());
+    }
+}
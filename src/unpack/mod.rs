@@ -1,13 +1,115 @@
-mod split_indices;
+mod atomic;
+mod cancel;
+mod checksums;
+mod collisions;
+mod conflicts;
+mod deadline;
+mod dedup;
+mod degraded;
+mod directory;
+mod dry_run;
+mod empty_nodes;
+mod errors;
+mod extract_order;
+mod filters;
+mod html_index;
+mod incremental;
+mod json_format;
+mod json_schema;
+mod kinds;
+mod levels;
+mod long_paths;
+mod manifest;
+mod memory;
+mod options;
+mod order;
+mod paranoid;
+mod permissions;
+mod portable;
+mod prefix;
+mod progress;
+mod quarantine;
+mod routes;
+mod scheduler;
+mod spatial;
+mod spool;
+mod staging;
+mod sublayers;
+mod symlinks;
+mod tar;
+mod timings;
+mod trace;
+mod verify;
+mod worker;
 
+use self::atomic::AtomicFolder;
+pub use self::cancel::CancelToken;
+use self::cancel::CancellableReader;
+pub use self::checksums::{check_manifest, ChecksumMismatch, ChecksumReport, CHECKSUM_FILE};
+pub use self::conflicts::ConflictPolicy;
+use self::conflicts::{ExpectedFile, Resolution};
+pub use self::deadline::TimeLimit;
+use self::deadline::{Deadline, DeadlineReader};
+use self::dedup::Deduplicator;
+pub use self::dedup::{DedupMode, DedupStats};
+use self::degraded::{DecodeWatch, MalformedGzip};
+use self::directory::PackageReader;
+use self::dry_run::DryRun;
+pub use self::dry_run::DryRunSummary;
+pub use self::errors::{error_kind, error_to_json, ErrorKind};
+pub use self::extract_order::ExtractOrder;
+pub use self::filters::{ByteSize, EntryDate, EntryFilters, Filter, PathPattern};
+use self::incremental::EntryRecord;
+pub use self::incremental::IncrementalStats;
+use self::json_format::FormattedJson;
+pub use self::json_format::{Indentation, JsonFormatting, DEFAULT_SORT_KEYS_MAX_SIZE};
+use self::json_schema::SchemaCheck;
+pub use self::kinds::ResourceKind;
+pub use self::memory::MemoryBudget;
+pub use self::options::{
+    ContentOptions, EncryptionOptions, IntegrityOptions, OutputOptions, ReportOptions, RunOptions,
+    SelectionOptions, UnpackOptions,
+};
+use self::paranoid::ExpansionLimit;
+pub use self::prefix::StripPrefix;
+pub use self::progress::Progress;
+use self::progress::ProgressCounters;
+pub use self::quarantine::RetryPolicy;
+pub use self::routes::ClassRoute;
+use self::routes::ResourceClass;
+pub(crate) use self::scheduler::EntryQueue;
+pub use self::spatial::BoundingBox;
+use self::sublayers::SublayerRouting;
+use self::tar::TarStream;
+pub use self::tar::TarTarget;
+pub use self::timings::StageTimings;
+use self::timings::{TimedReader, TimedWriter};
+use self::trace::TraceEvent;
+use self::verify::{ChecksumReader, UnpackedFile, WrittenFile};
+use crate::archive;
+pub use crate::archive::encryption::EncryptionError;
+use crate::archive::encryption::{self, PackageView};
+use crate::archive::raw::RawEntry;
+pub use crate::archive::PackageFlavor;
+use crate::http;
+pub use crate::http::{is_url, HttpHeader};
+use crate::i3s;
+use crate::json::Value;
 use failure::Error;
-use flate2::read::GzDecoder;
+use flate2::bufread::GzDecoder;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
-use std::io::Seek;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
+use std::path::{Component, Path};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
+use std::time::{Instant, SystemTime};
 use zip::read::ZipFile;
 use zip::ZipArchive;
 
@@ -15,22 +117,250 @@ use zip::ZipArchive;
 enum UnpackError {
     #[fail(display = "Unable to create a folder for unpacking the package")]
     NoFolderForPackage,
+
+    #[fail(
+        display = "A package read from a stream has no file name to name a folder after, so it needs an output folder to unpack into"
+    )]
+    NoOutputDirForStream,
     #[fail(
         display = "The output folder cannot be created because a file with the same name already exists"
     )]
     OutputFolderIsAFile,
 
+    #[fail(
+        display = "The output folder {} already exists, and was left as it is",
+        _0
+    )]
+    OutputFolderExists(String),
+
     #[fail(display = "Package entries with an absolute path will not be extracted")]
     PackageEntryHasAbsolutePath,
+
+    #[fail(
+        display = "Package entry {} leads out of the folder it is unpacked into, and will not be extracted",
+        _0
+    )]
+    PackageEntryOutsideFolder(String),
+
+    #[fail(display = "There is no output folder from an earlier run to retry failed entries in")]
+    NoFolderToRetryIn,
+
+    #[fail(
+        display = "{} files already exist in the output folder and were not unpacked:\n{}",
+        _0, _1
+    )]
+    FileConflicts(usize, String),
+
+    #[fail(
+        display = "{} entries would be unpacked over other files where case is ignored, as on Windows and macOS, so nothing was unpacked; --rename-collisions unpacks them under other names:\n{}",
+        _0, _1
+    )]
+    CaseCollisions(usize, String),
+
+    #[fail(display = "{} unpacked files don't match the package", _0)]
+    VerificationFailed(usize),
+
+    #[fail(display = "Reformatted JSON documents can't be verified against the package")]
+    ReformattedVerification,
+
+    #[fail(display = "{} is not unpacked, as {}", _0, _1)]
+    UnsafeEntryPath(String, String),
+
+    #[fail(display = "{} isn't gzipped, though it is named .gz", _0)]
+    NotGzipped(String),
+
+    #[fail(display = "{} is gzipped, though it isn't named .gz", _0)]
+    UnexpectedlyGzipped(String),
+
+    #[fail(display = "{} has data after the end of its gzip stream", _0)]
+    DataAfterGzipStream(String),
+
+    #[fail(
+        display = "The package says it holds {} entries, more than the {} allowed in hardened mode",
+        _0, _1
+    )]
+    TooManyEntries(u64, u64),
+
+    #[fail(
+        display = "An entry name of {} bytes, starting {}, is longer than the {} allowed in hardened mode",
+        _0, _1, _2
+    )]
+    NameTooLong(usize, String, usize),
+
+    #[fail(
+        display = "Timed out after {:.1} s with {} entries left, which a run with --resume will unpack",
+        _0, _1
+    )]
+    TimedOut(f64, usize),
+
+    #[fail(display = "Cancelled after unpacking {} entries", _0)]
+    Cancelled(usize),
+
+    #[fail(
+        display = "An atomic unpack replaces the whole output folder, so it can't be combined with {}",
+        _0
+    )]
+    AtomicWith(&'static str),
+
+    #[fail(
+        display = "A tar stream is written in order and holds nothing but the files, so it can't be combined with {}",
+        _0
+    )]
+    TarWith(&'static str),
+
+    #[fail(display = "A worker thread panicked: {}", _0)]
+    WorkerPanicked(String),
+}
+
+/// A failure to unpack an entry, naming the entry and the file it was being
+/// unpacked to.
+#[derive(Debug)]
+pub struct EntryError {
+    /// The name of the entry in the package.
+    pub entry: String,
+    /// The file it was being unpacked to.
+    pub target: PathBuf,
+    /// What went wrong.
+    pub cause: Error,
+}
+
+impl std::fmt::Display for EntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Unpacking {} to {} failed: {}",
+            self.entry,
+            self.target.to_string_lossy(),
+            self.cause
+        )
+    }
+}
+
+// Written out rather than derived, as the cause is a failure::Error, which
+// isn't a Fail itself.
+impl failure::Fail for EntryError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        Some(self.cause.as_fail())
+    }
+}
+
+/// Tells the other workers to stop once a worker finishes early with an
+/// error, or panics, so that a failed unpack doesn't carry on in the
+/// background.
+struct StopOthersOnError<'a> {
+    stop: &'a AtomicBool,
+    finished: bool,
+}
+
+impl Drop for StopOthersOnError<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.stop.store(true, Ordering::SeqCst);
+        }
+    }
 }
 
-fn open_slpk_archive(slpk_file_path: PathBuf) -> Result<ZipArchive<impl Read + Seek>, Error> {
-    let file = File::open(slpk_file_path)?;
-    let buf_reader = BufReader::new(file);
-    Ok(ZipArchive::new(buf_reader)?)
+/// The message a worker thread panicked with, which is a `&str` or a
+/// `String` unless it was given something else to panic with.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "no message was given".to_string(),
+        },
+    }
 }
 
-fn get_unpack_folder(mut slpk_file_path: PathBuf) -> Result<PathBuf, Error> {
+/// The exit code of an unpack which ran out of time, the same as that of
+/// the `timeout` command.
+pub const TIMED_OUT_EXIT_CODE: i32 = 124;
+
+/// The size of the buffer each file is written through, unless the options
+/// give another. Most resources fit in it whole, so they are written to the
+/// file at once rather than in pieces of the size the decoders produce.
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 128 * 1024;
+
+/// The size of the buffer a gzipped entry is read through on its way to the
+/// decoder, which reads it in small pieces. Entries smaller than this get a
+/// buffer their own size.
+const GZIP_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Whether an unpack failed by running out of time, leaving a resume file.
+pub fn is_timeout(error: &Error) -> bool {
+    matches!(
+        error.downcast_ref::<UnpackError>(),
+        Some(UnpackError::TimedOut(..))
+    )
+}
+
+/// The number of entries unpacked before an unpack was cancelled, when it
+/// failed by being cancelled.
+pub fn cancelled_after(error: &Error) -> Option<usize> {
+    match error.downcast_ref::<UnpackError>() {
+        Some(UnpackError::Cancelled(entries_unpacked)) => Some(*entries_unpacked),
+        _ => None,
+    }
+}
+
+/// A name given for an `OverwriteMode` which isn't one.
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Unknown overwrite mode '{}', expected error, overwrite or merge",
+    _0
+)]
+pub struct UnknownOverwriteMode(String);
+
+/// What to do when the output folder already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverwriteMode {
+    /// Fail without touching it.
+    Error,
+    /// Delete it, and unpack into a new folder.
+    #[default]
+    Overwrite,
+    /// Unpack into it, replacing the files the package holds and leaving
+    /// any others alone.
+    Merge,
+}
+
+impl FromStr for OverwriteMode {
+    type Err = UnknownOverwriteMode;
+
+    fn from_str(s: &str) -> Result<OverwriteMode, UnknownOverwriteMode> {
+        match s {
+            "error" => Ok(OverwriteMode::Error),
+            "overwrite" => Ok(OverwriteMode::Overwrite),
+            "merge" => Ok(OverwriteMode::Merge),
+            _ => Err(UnknownOverwriteMode(s.to_string())),
+        }
+    }
+}
+
+/// What happens to an output folder left by an earlier run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExistingFolder {
+    /// Fail if there is one, leaving it as it is.
+    Refuse,
+    /// Replace it with an empty folder.
+    Replace,
+    /// Unpack into it, creating it if needed.
+    Merge,
+    /// Unpack into it, failing if there is none.
+    Reuse,
+}
+
+/// The folder a package is unpacked into: the package path without its
+/// extension, or a folder of that name in `output_dir` when one is given. A
+/// relative `output_dir` is taken from the current directory, and is created
+/// along with any missing parents. With `dry_run`, the folder is checked in
+/// the same way, but nothing is created. The folder itself is made ready by
+/// `prepare_unpack_folder`, or stands in for an atomic unpack.
+pub(crate) fn unpack_folder_path(
+    mut slpk_file_path: PathBuf,
+    output_dir: Option<&Path>,
+    dry_run: bool,
+) -> Result<PathBuf, Error> {
     // Try to extract the file stem. This name will be used as the folder name which
     // the package will be unpacked into. If the package has no file_stem, then
     // it cannot be unpacked. We could come up with some other name to use, but
@@ -38,7 +368,7 @@ fn get_unpack_folder(mut slpk_file_path: PathBuf) -> Result<PathBuf, Error> {
     match slpk_file_path.extension() {
         Some(_) => {
             if let Some(file_stem) = slpk_file_path.file_stem() {
-                slpk_file_path.set_file_name(file_stem.to_os_string());
+                slpk_file_path = slpk_file_path.with_file_name(file_stem);
             } else {
                 // This probably shouldn't happen. Tough to have a file with an
                 // extension but no file stem.
@@ -51,138 +381,3359 @@ fn get_unpack_folder(mut slpk_file_path: PathBuf) -> Result<PathBuf, Error> {
             return Err(Error::from(UnpackError::NoFolderForPackage));
         }
     }
+    if let Some(output_dir) = output_dir {
+        let folder_name = slpk_file_path
+            .file_name()
+            .ok_or(UnpackError::NoFolderForPackage)?;
+        slpk_file_path = named_unpack_folder(output_dir, Path::new(folder_name), dry_run)?;
+    }
+    Ok(slpk_file_path)
+}
 
-    // TODO: Probably the behaviour with respect to existing directories
-    // should be configurable.
+/// The folder named `folder_name` in `output_dir`, creating `output_dir`
+/// unless `dry_run` is set. The name has to be that of a single folder.
+fn named_unpack_folder(
+    output_dir: &Path,
+    folder_name: &Path,
+    dry_run: bool,
+) -> Result<PathBuf, Error> {
+    let mut components = folder_name.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => {}
+        _ => return Err(Error::from(UnpackError::NoFolderForPackage)),
+    }
+    let output_dir = std::env::current_dir()?.join(output_dir);
+    if !dry_run {
+        std::fs::create_dir_all(&output_dir)?;
+    }
+    Ok(output_dir.join(folder_name))
+}
 
-    if slpk_file_path.exists() {
-        if slpk_file_path.is_dir() {
-            println!("Deleting folder: {}", slpk_file_path.to_string_lossy());
-            std::fs::remove_dir_all(slpk_file_path.clone())?;
-        } else if slpk_file_path.is_file() {
-            // Don't clobber an existing file with the unpack folder.
-            return Err(Error::from(UnpackError::OutputFolderIsAFile));
+/// Makes `unpack_folder` ready to unpack into, as `existing` says to when it
+/// exists already, returning it.
+fn prepare_unpack_folder(
+    unpack_folder: PathBuf,
+    existing: ExistingFolder,
+    dry_run: bool,
+) -> Result<PathBuf, Error> {
+    // Don't clobber an existing file with the unpack folder.
+    if unpack_folder.exists() && !unpack_folder.is_dir() {
+        return Err(Error::from(UnpackError::OutputFolderIsAFile));
+    }
+    match existing {
+        ExistingFolder::Refuse if dry_run && unpack_folder.is_dir() => {
+            return Err(Error::from(UnpackError::OutputFolderExists(
+                unpack_folder.to_string_lossy().into_owned(),
+            )));
         }
+        ExistingFolder::Refuse => {}
+        ExistingFolder::Replace if dry_run && unpack_folder.is_dir() => {
+            info!("Would delete folder: {}", unpack_folder.to_string_lossy());
+        }
+        ExistingFolder::Replace if unpack_folder.is_dir() => {
+            info!("Deleting folder: {}", unpack_folder.to_string_lossy());
+            std::fs::remove_dir_all(unpack_folder.clone())?;
+        }
+        ExistingFolder::Replace => {}
+        ExistingFolder::Merge if dry_run => return Ok(unpack_folder),
+        ExistingFolder::Merge => {
+            // Another process creating the folder at the same time is as
+            // good as it having been there.
+            std::fs::create_dir_all(&unpack_folder)?;
+            return Ok(unpack_folder);
+        }
+        ExistingFolder::Reuse if unpack_folder.is_dir() => return Ok(unpack_folder),
+        ExistingFolder::Reuse => return Err(Error::from(UnpackError::NoFolderToRetryIn)),
     }
 
-    std::fs::create_dir(slpk_file_path.clone())?;
-    Ok(slpk_file_path)
+    if dry_run {
+        return Ok(unpack_folder);
+    }
+    // Creating the folder fails if it exists, so a folder which appeared
+    // since it was checked for, or deleted, is never unpacked into.
+    match std::fs::create_dir(&unpack_folder) {
+        Ok(()) => Ok(unpack_folder),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(Error::from(
+            UnpackError::OutputFolderExists(unpack_folder.to_string_lossy().into_owned()),
+        )),
+        Err(e) => Err(Error::from(e)),
+    }
 }
 
-fn create_folder_for_entry(
-    mut target_directory: PathBuf,
-    zip_entry: &PathBuf,
-) -> Result<PathBuf, Error> {
-    if let Some(parent_path) = zip_entry.parent() {
-        if parent_path.is_absolute() {
-            return Err(Error::from(UnpackError::PackageEntryHasAbsolutePath));
-        } else {
-            target_directory.push(parent_path);
-            std::fs::create_dir_all(target_directory.clone())?;
+/// The path of an entry below `target_directory`. The zip library already
+/// drops `..`, roots and drive letters from entry names, but routing builds
+/// paths of its own, so the joined path is checked component by component
+/// to lead strictly inside the folder, whatever the entry is named.
+fn path_in_folder(target_directory: &Path, zip_entry: &Path) -> Result<PathBuf, Error> {
+    if zip_entry.has_root() || zip_entry.is_absolute() {
+        return Err(Error::from(UnpackError::PackageEntryHasAbsolutePath));
+    }
+    let target_path = target_directory.join(zip_entry);
+    let inside = match target_path.strip_prefix(target_directory) {
+        Ok(rest) => {
+            rest.components().next().is_some()
+                && rest
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
         }
+        Err(_) => false,
+    };
+    if !inside {
+        return Err(Error::from(UnpackError::PackageEntryOutsideFolder(
+            zip_entry.to_string_lossy().into_owned(),
+        )));
     }
+    Ok(target_path)
+}
+
+/// Whether `folder` holds nothing, or isn't there.
+fn folder_is_empty(folder: &Path) -> bool {
+    std::fs::read_dir(folder).map_or(true, |mut entries| entries.next().is_none())
+}
 
+/// The folder an entry is unpacked into below `target_directory`.
+fn folder_for_entry(target_directory: PathBuf, zip_entry: &Path) -> Result<PathBuf, Error> {
     // Entries which don't have a parent will be extracted into the
-    // target directory.
+    // target directory, and those without a name are skipped later.
+    if zip_entry.as_os_str().is_empty() {
+        return Ok(target_directory);
+    }
+    match path_in_folder(&target_directory, zip_entry)?.parent() {
+        Some(parent_path) => Ok(parent_path.to_path_buf()),
+        None => Ok(target_directory),
+    }
+}
+
+fn create_folder_for_entry(target_directory: PathBuf, zip_entry: &Path) -> Result<PathBuf, Error> {
+    let target_directory = folder_for_entry(target_directory, zip_entry)?;
+    std::fs::create_dir_all(long_paths::long_path(&target_directory))?;
     Ok(target_directory)
 }
 
+/// The file an entry is unpacked to below `target_directory`, or `None` for
+/// an entry without a file name.
+fn entry_target_path(
+    target_directory: &Path,
+    archive_entry_path: &Path,
+    keep_gzip: bool,
+) -> Result<Option<PathBuf>, Error> {
+    match target_file_name(archive_entry_path, keep_gzip).1 {
+        Some(name) => Ok(Some(
+            folder_for_entry(target_directory.to_path_buf(), archive_entry_path)?.join(name),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Whether an entry is named `.gz`, and the name of the file it is unpacked
+/// to. The .gz suffix is dropped even when sniffing finds the entry isn't
+/// gzipped, since the name says what the content should have been, unless
+/// `keep_gzip` keeps the entry as it is stored.
+fn target_file_name(
+    archive_entry_path: &Path,
+    keep_gzip: bool,
+) -> (bool, Option<&std::ffi::OsStr>) {
+    let named_gzip = archive_entry_path
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        == Some("gz");
+    let target_name = if named_gzip && !keep_gzip {
+        archive_entry_path.file_stem()
+    } else {
+        archive_entry_path.file_name()
+    };
+    (named_gzip, target_name)
+}
+
+/// Streams the contents of an entry to its file, after `head` if some of
+/// them were read already, staging it when `staging` names a worker.
+/// Returns the time spent writing. The file is written through a buffer of
+/// `write_buffer_size`, or of `size_hint` when that is smaller, so that the
+/// many small documents of a package don't each allocate a large buffer
+/// they never fill.
+fn stream_target_file(
+    head: &[u8],
+    contents: &mut dyn Read,
+    target_file_path: &Path,
+    staging: Option<usize>,
+    size_hint: u64,
+    write_buffer_size: usize,
+) -> Result<std::time::Duration, Error> {
+    let start = Instant::now();
+    let mut span = Default::default();
+    let capacity = size_hint.min(write_buffer_size as u64) as usize;
+    let write_time = staging::write_staged(target_file_path, staging, |path| {
+        let mut target_file = BufWriter::with_capacity(
+            capacity,
+            TimedWriter::new(File::create(long_paths::long_path(path))?),
+        );
+        let create_time = start.elapsed();
+        target_file.write_all(head)?;
+        std::io::copy(contents, &mut target_file)?;
+        // Flushed here rather than when dropped, so that a failure to write
+        // the end of the file fails the entry.
+        let target_file = target_file.into_inner().map_err(|e| e.into_error())?;
+        span = start.elapsed();
+        Ok(create_time + target_file.elapsed)
+    })?;
+    // Renaming the staged file into place counts as writing too.
+    Ok(write_time + start.elapsed().checked_sub(span).unwrap_or_default())
+}
+
+/// Writes the contents of an entry, returning the time spent writing.
+/// `size_hint` is the expected size of the contents, which decides how much
+/// of the memory budget deduplicating them takes.
+fn write_target_file(
+    contents: &mut dyn Read,
+    target_file_path: &Path,
+    dedup: Option<&Deduplicator>,
+    size_hint: u64,
+    budget: Option<&MemoryBudget>,
+    staging: Option<usize>,
+    write_buffer_size: usize,
+) -> Result<std::time::Duration, Error> {
+    let dedup = match dedup {
+        Some(dedup) => dedup,
+        None => {
+            let _reservation = budget.map(|budget| budget.reserve_stream(write_buffer_size));
+            return stream_target_file(
+                &[],
+                contents,
+                target_file_path,
+                staging,
+                size_hint,
+                write_buffer_size,
+            );
+        }
+    };
+    let budget = match budget {
+        Some(budget) => budget,
+        None => {
+            let mut buffer = Vec::new();
+            contents.read_to_end(&mut buffer)?;
+            let start = Instant::now();
+            dedup.write(&buffer, target_file_path, staging)?;
+            return Ok(start.elapsed());
+        }
+    };
+    // Deduplicating holds the payload in memory to hash it. Payloads which
+    // don't fit the budget are written without being deduplicated, after
+    // removing any link an earlier run left, as the deduplicator would.
+    if target_file_path.symlink_metadata().is_ok() {
+        std::fs::remove_file(target_file_path)?;
+    }
+    dedup.forget(target_file_path);
+    match memory::read_within_budget(contents, size_hint, 1, budget)? {
+        Some(buffered) if buffered.complete => {
+            let start = Instant::now();
+            dedup.write(&buffered.contents, target_file_path, staging)?;
+            Ok(start.elapsed())
+        }
+        Some(buffered) => stream_target_file(
+            &buffered.contents,
+            contents,
+            target_file_path,
+            staging,
+            size_hint,
+            write_buffer_size,
+        ),
+        None => {
+            let _reservation = budget.reserve_stream(write_buffer_size);
+            stream_target_file(
+                &[],
+                contents,
+                target_file_path,
+                staging,
+                size_hint,
+                write_buffer_size,
+            )
+        }
+    }
+}
+
+/// Writes the contents of an entry to its file, or appends them to the tar
+/// stream as `path_in_tar`, with the time and mode it records, returning
+/// the time spent writing.
+fn write_output(
+    contents: &mut dyn Read,
+    target_file_path: &Path,
+    path_in_tar: &Path,
+    modified: Option<SystemTime>,
+    mode: Option<u32>,
+    size_hint: u64,
+    options: &EntryOptions,
+) -> Result<std::time::Duration, Error> {
+    let tar = match options.tar {
+        Some(tar) => tar,
+        None => {
+            return write_target_file(
+                contents,
+                target_file_path,
+                options.dedup,
+                size_hint,
+                options.budget,
+                options.staging,
+                options.write_buffer_size,
+            )
+        }
+    };
+    // The header gives the size of the file, so the whole of it is read
+    // before any of it is written.
+    let mut buffer = Vec::with_capacity(size_hint.min(archive::MAX_PREALLOCATION) as usize);
+    contents.read_to_end(&mut buffer)?;
+    let start = Instant::now();
+    tar.lock()
+        .unwrap()
+        .append_file(path_in_tar, &buffer, modified, mode)?;
+    Ok(start.elapsed())
+}
+
+/// The time the file of an entry takes: that of the entry, unless the gzip
+/// header has a time which should be restored instead.
+fn file_modified(
+    gzip: Option<&archive::GzipProvenance>,
+    entry_modified: zip::DateTime,
+    restore_gzip_mtime: bool,
+) -> Option<SystemTime> {
+    gzip.and_then(archive::GzipProvenance::modified)
+        .filter(|_| restore_gzip_mtime)
+        .or_else(|| conflicts::recorded_time(entry_modified))
+}
+
+/// Decides whether an entry's output file may be written, when the output
+/// folder can hold files from an earlier run.
+fn check_conflict(
+    conflict_policy: Option<ConflictPolicy>,
+    archive_entry: &ZipFile,
+    target_file_path: &Path,
+    transformed: bool,
+) -> Result<Resolution, Error> {
+    let expected = if transformed {
+        ExpectedFile::Transformed
+    } else {
+        ExpectedFile::Copy {
+            size: archive_entry.size(),
+            crc32: archive_entry.crc32(),
+        }
+    };
+    match conflict_policy {
+        Some(policy) => Ok(conflicts::resolve(
+            policy,
+            target_file_path,
+            archive_entry.last_modified(),
+            expected,
+        )?),
+        None => Ok(Resolution::Write),
+    }
+}
+
+/// Whether the file written for an entry may differ from the entry, as it
+/// does when it is decompressed or reformatted. With `sniff_compression`,
+/// any entry may turn out to be gzipped.
+fn is_transformed(
+    named_gzip: bool,
+    target_file_path: &Path,
+    keep_gzip: bool,
+    sniff_compression: bool,
+    json_formatting: JsonFormatting,
+) -> bool {
+    ((named_gzip || sniff_compression) && !keep_gzip)
+        || (json_formatting != JsonFormatting::AsIs && json_format::is_json_file(target_file_path))
+}
+
+/// How a worker writes each entry.
+struct EntryOptions<'a> {
+    dedup: Option<&'a Deduplicator>,
+    conflict_policy: Option<ConflictPolicy>,
+    sniff_compression: bool,
+    /// Whether entries named .gz which turn out not to be gzipped are
+    /// warned about, as they are by design in an ESLPK.
+    warn_not_gzipped: bool,
+    keep_gzip: bool,
+    json_formatting: JsonFormatting,
+    /// The largest document whose keys are sorted, when they are.
+    sort_keys: Option<u64>,
+    /// Whether the SHA-256 of each file is taken as it is written.
+    checksum_manifest: bool,
+    budget: Option<&'a MemoryBudget>,
+    restore_gzip_mtime: bool,
+    strict_content: bool,
+    max_expansion_ratio: Option<u64>,
+    /// Whether JSON resources are checked against their schemas, and
+    /// whether those which don't match fail.
+    validate_json: bool,
+    strict_json: bool,
+    /// The worker whose staging suffix files are written under, when they
+    /// are staged.
+    staging: Option<usize>,
+    write_buffer_size: usize,
+    /// The tar stream files are appended to instead of being written below
+    /// the output folder.
+    tar: Option<&'a Mutex<TarStream>>,
+    deadline: Option<Deadline>,
+    cancel: Option<&'a CancelToken>,
+    progress: Option<&'a ProgressCounters>,
+}
+
+impl EntryOptions<'_> {
+    fn entry_done(&self, bytes_written: u64) {
+        if let Some(progress) = self.progress {
+            progress.entry_done(bytes_written);
+        }
+    }
+}
+
+/// What became of an entry.
+struct EntryOutcome {
+    timings: StageTimings,
+    resolution: Resolution,
+    /// The file written, unless the entry was skipped or has no file name.
+    written: Option<WrittenFile>,
+    /// Why the entry couldn't be decompressed, when it was copied as it is
+    /// instead.
+    degraded: Option<String>,
+}
+
+/// The contents of an entry as they are written to `target_file_path`,
+/// from `contents`, which are decompressed already when `decompressed` is
+/// set: limited to what the entry may expand to, and reformatted as
+/// `json_formatting` says when the file is a JSON document. `sizes` are the
+/// compressed size and size of the entry, as `ExpansionLimit` takes them.
+pub(crate) fn written_contents<R: Read>(
+    contents: R,
+    entry_name: &str,
+    target_file_path: &Path,
+    sizes: (u64, u64),
+    decompressed: bool,
+    max_expansion_ratio: Option<u64>,
+    json_formatting: JsonFormatting,
+) -> FormattedJson<ExpansionLimit<R>> {
+    let json_formatting = if json_format::is_json_file(target_file_path) {
+        json_formatting
+    } else {
+        JsonFormatting::AsIs
+    };
+    FormattedJson::new(
+        ExpansionLimit::new(
+            contents,
+            entry_name,
+            sizes,
+            decompressed,
+            max_expansion_ratio,
+        ),
+        json_formatting,
+    )
+}
+
 fn unpack_entry(
     mut archive_entry: ZipFile,
+    archive_entry_path: &Path,
     unpack_folder: PathBuf,
-    verbose: bool,
-) -> Result<(), Error> {
-    let archive_entry_path = archive_entry.sanitized_name();
-    let target_folder = create_folder_for_entry(unpack_folder, &archive_entry_path)?;
+    options: &EntryOptions,
+) -> Result<EntryOutcome, Error> {
+    let start = Instant::now();
+    // Nothing is written below the output folder when streaming a tar.
+    let target_folder = match options.tar {
+        Some(_) => folder_for_entry(unpack_folder, archive_entry_path)?,
+        None => create_folder_for_entry(unpack_folder, archive_entry_path)?,
+    };
+    let mut timings = StageTimings {
+        write: start.elapsed(),
+        ..StageTimings::default()
+    };
 
-    if let Some("gz") = archive_entry_path
-        .extension()
-        .and_then(std::ffi::OsStr::to_str)
+    let (named_gzip, target_name) = target_file_name(archive_entry_path, options.keep_gzip);
+    let (target_file_path, path_in_tar) = match target_name {
+        Some(name) => (
+            target_folder.join(name),
+            archive_entry_path.with_file_name(name),
+        ),
+        None => {
+            options.entry_done(0);
+            return Ok(EntryOutcome {
+                timings,
+                resolution: Resolution::Write,
+                written: None,
+                degraded: None,
+            });
+        }
+    };
+
+    // The first bytes are read before anything else, so that only entries
+    // which turn out to be gzipped are taken to be transformed.
+    let (magic, is_gzip) = if options.sniff_compression || options.strict_content {
+        let mut magic = Vec::with_capacity(archive::GZIP_MAGIC.len());
+        (&mut archive_entry)
+            .take(archive::GZIP_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        let is_gzip = archive::is_gzip(&magic);
+        (magic, is_gzip)
+    } else {
+        (Vec::new(), named_gzip)
+    };
+    let transformed = is_transformed(
+        is_gzip,
+        &target_file_path,
+        options.keep_gzip,
+        false,
+        options.json_formatting,
+    );
+    let resolution = check_conflict(
+        options.conflict_policy,
+        &archive_entry,
+        &target_file_path,
+        transformed,
+    )?;
+    if let Resolution::Skip | Resolution::AlreadyUnpacked | Resolution::Refuse = resolution {
+        options.entry_done(0);
+        return Ok(EntryOutcome {
+            timings,
+            resolution,
+            written: None,
+            degraded: None,
+        });
+    }
+    let entry_name = archive_entry.name().to_string();
+    let entry_modified = archive_entry.last_modified();
+    let entry_mode = permissions::sanitized_mode(archive_entry.unix_mode());
+    let size_hint = archive_entry.size();
+    let compressed_size = archive_entry.compressed_size();
+
+    let reader = TimedReader::new(DeadlineReader::new(
+        CancellableReader::new(archive_entry, options.cancel),
+        options.deadline,
+    ));
+    if options.strict_content && is_gzip != named_gzip {
+        return Err(Error::from(if named_gzip {
+            UnpackError::NotGzipped(entry_name)
+        } else {
+            UnpackError::UnexpectedlyGzipped(entry_name)
+        }));
+    }
+    if named_gzip && !is_gzip && options.warn_not_gzipped && !options.keep_gzip {
+        warn!(
+            "{} is named .gz but isn't gzipped, so it is copied as it is",
+            entry_name
+        );
+    }
+    let contents = std::io::Cursor::new(magic).chain(reader);
+    let sizes = (compressed_size, size_hint);
+
+    // The output is checksummed as it is written, for --verify-after.
+    let mut gzip = None;
+    let decompress = is_gzip && !options.keep_gzip;
+    let (checksum, sha256) = if decompress {
+        debug!(
+            "Decompress: {} -> {}",
+            entry_name,
+            target_file_path.to_string_lossy()
+        );
+
+        // Whatever time isn't spent reading or writing was spent in the
+        // decoder.
+        let start = Instant::now();
+        let capacity = compressed_size.max(1).min(GZIP_READ_BUFFER_SIZE as u64) as usize;
+        let mut buffered = BufReader::with_capacity(capacity, contents);
+        let watch = DecodeWatch::default();
+        let decoder = GzDecoder::new(watch.source(&mut buffered));
+        gzip = decoder
+            .header()
+            .and_then(archive::GzipProvenance::of_header);
+        let decoder = SchemaCheck::new(
+            watch.decoded(decoder),
+            &entry_name,
+            options.validate_json,
+            options.strict_json,
+        );
+        let mut gz_reader = ChecksumReader::new(
+            written_contents(
+                decoder,
+                &entry_name,
+                &target_file_path,
+                sizes,
+                true,
+                options.max_expansion_ratio,
+                options.json_formatting,
+            )
+            .sorting_keys(&entry_name, options.sort_keys),
+        )
+        .with_sha256(options.checksum_manifest);
+        let written = write_output(
+            &mut gz_reader,
+            &target_file_path,
+            &path_in_tar,
+            file_modified(gzip.as_ref(), entry_modified, options.restore_gzip_mtime),
+            entry_mode,
+            size_hint,
+            options,
+        );
+        let write_time = match (written, watch.decode_error()) {
+            (Ok(write_time), _) => write_time,
+            (Err(_), Some(cause)) if !options.strict_content => {
+                // Nothing is appended to a tar stream until the whole file
+                // has been read.
+                if options.tar.is_none() && target_file_path.symlink_metadata().is_ok() {
+                    std::fs::remove_file(&target_file_path)?;
+                }
+                if let Some(dedup) = options.dedup {
+                    dedup.forget(&target_file_path);
+                }
+                return Err(Error::from(MalformedGzip {
+                    entry: entry_name,
+                    cause,
+                }));
+            }
+            (Err(e), _) => return Err(e),
+        };
+        let checksum = (gz_reader.checksum(), gz_reader.sha256());
+        drop(gz_reader);
+        // Reading the entry to its end also has the zip library check its
+        // CRC.
+        if options.strict_content && std::io::copy(&mut buffered, &mut std::io::sink())? > 0 {
+            return Err(Error::from(UnpackError::DataAfterGzipStream(entry_name)));
+        }
+        let read_time = buffered.get_ref().get_ref().1.elapsed;
+        timings.read += read_time;
+        timings.write += write_time;
+        timings.decompress += start
+            .elapsed()
+            .checked_sub(read_time + write_time)
+            .unwrap_or_default();
+        checksum
+    } else {
+        debug!(
+            "Copy: {} -> {}",
+            entry_name,
+            target_file_path.to_string_lossy()
+        );
+
+        let mut contents = contents;
+        let mut checked = ChecksumReader::new(
+            written_contents(
+                SchemaCheck::new(
+                    &mut contents,
+                    &entry_name,
+                    options.validate_json,
+                    options.strict_json,
+                ),
+                &entry_name,
+                &target_file_path,
+                sizes,
+                false,
+                options.max_expansion_ratio,
+                options.json_formatting,
+            )
+            .sorting_keys(&entry_name, options.sort_keys),
+        )
+        .with_sha256(options.checksum_manifest);
+        timings.write += write_output(
+            &mut checked,
+            &target_file_path,
+            &path_in_tar,
+            file_modified(None, entry_modified, options.restore_gzip_mtime),
+            entry_mode,
+            size_hint,
+            options,
+        )?;
+        let checksum = (checked.checksum(), checked.sha256());
+        drop(checked);
+        timings.read += contents.get_ref().1.elapsed;
+        checksum
+    };
+
+    // Files take the time and mode of their entry, which a tar stream
+    // records in their headers. Setting either through a link left by
+    // deduplicating would set it on the original.
+    let modified = file_modified(gzip.as_ref(), entry_modified, options.restore_gzip_mtime);
+    if options.tar.is_none()
+        && (modified.is_some() || entry_mode.is_some())
+        && !target_file_path
+            .symlink_metadata()?
+            .file_type()
+            .is_symlink()
     {
-        if let Some(non_gzip_name) = archive_entry_path.file_stem() {
-            let mut target_file_path = target_folder;
-            target_file_path.push(non_gzip_name);
-
-            if verbose {
-                println!(
-                    "Decompress: {} -> {}",
-                    archive_entry.name(),
-                    target_file_path.to_string_lossy()
-                );
+        if let Some(modified) = modified {
+            File::options()
+                .write(true)
+                .open(&target_file_path)?
+                .set_modified(modified)?;
+        }
+        if let Some(mode) = entry_mode {
+            permissions::apply(&target_file_path, mode)?;
+        }
+    }
+
+    options.entry_done(checksum.1);
+    Ok(EntryOutcome {
+        timings,
+        resolution,
+        written: Some(WrittenFile {
+            path: target_file_path,
+            decompressed: decompress,
+            crc32: checksum.0,
+            bytes: checksum.1,
+            gzip,
+            sha256,
+        }),
+        degraded: None,
+    })
+}
+
+/// What one worker thread unpacked.
+#[derive(Debug, Default)]
+struct WorkerSummary {
+    entries_unpacked: usize,
+    directories_created: usize,
+    bytes_written: u64,
+    entries_skipped: usize,
+    entries_of_other_layers: usize,
+    /// Entries left as they were unpacked by the last run, with
+    /// `incremental`.
+    entries_unchanged: usize,
+    entries_filtered: HashMap<Filter, usize>,
+    /// What a dry run would have written.
+    dry_run: DryRun,
+    entries_retried: usize,
+    quarantined_entries: Vec<String>,
+    /// The entries which failed, with `keep_going`.
+    failed_entries: Vec<EntryError>,
+    files_overwritten: usize,
+    files_kept: usize,
+    files_already_unpacked: usize,
+    conflicting_entries: Vec<String>,
+    unassigned_entries: Vec<String>,
+    /// The entries left out for not being below the prefix which is
+    /// stripped.
+    unprefixed_entries: Vec<String>,
+    /// The entries copied as they are, as they couldn't be decompressed.
+    degraded_entries: Vec<String>,
+    symlinks_created: usize,
+    /// The symbolic links left out, with why each was.
+    skipped_symlinks: Vec<(String, String)>,
+    /// The entry which was being unpacked when time ran out.
+    remaining_entries: Vec<String>,
+    /// Whether the worker stopped as the unpack was cancelled.
+    cancelled: bool,
+    /// Everything unpacked, when writing a manifest or verifying.
+    unpacked_files: Vec<UnpackedFile>,
+    entries_per_class: HashMap<ResourceClass, usize>,
+    timings: StageTimings,
+    trace_events: Vec<TraceEvent>,
+}
+
+impl WorkerSummary {
+    /// Adds what another worker unpacked.
+    fn add(&mut self, summary: WorkerSummary) {
+        self.entries_unpacked += summary.entries_unpacked;
+        self.directories_created += summary.directories_created;
+        self.bytes_written += summary.bytes_written;
+        self.entries_skipped += summary.entries_skipped;
+        self.entries_of_other_layers += summary.entries_of_other_layers;
+        self.entries_unchanged += summary.entries_unchanged;
+        self.entries_retried += summary.entries_retried;
+        self.dry_run.merge(summary.dry_run);
+        for (filter, n) in summary.entries_filtered {
+            *self.entries_filtered.entry(filter).or_insert(0) += n;
+        }
+        self.quarantined_entries.extend(summary.quarantined_entries);
+        self.failed_entries.extend(summary.failed_entries);
+        self.trace_events.extend(summary.trace_events);
+        self.files_overwritten += summary.files_overwritten;
+        self.files_kept += summary.files_kept;
+        self.files_already_unpacked += summary.files_already_unpacked;
+        self.conflicting_entries.extend(summary.conflicting_entries);
+        self.timings.add(&summary.timings);
+        self.unassigned_entries.extend(summary.unassigned_entries);
+        self.unprefixed_entries.extend(summary.unprefixed_entries);
+        self.degraded_entries.extend(summary.degraded_entries);
+        self.symlinks_created += summary.symlinks_created;
+        self.skipped_symlinks.extend(summary.skipped_symlinks);
+        self.remaining_entries.extend(summary.remaining_entries);
+        self.cancelled |= summary.cancelled;
+        self.unpacked_files.extend(summary.unpacked_files);
+        for (class, n) in summary.entries_per_class {
+            *self.entries_per_class.entry(class).or_insert(0) += n;
+        }
+    }
+}
+
+/// What an unpack did.
+#[derive(Debug, Default)]
+pub struct UnpackSummary {
+    /// The entries written to a file, or a tar stream.
+    pub entries_unpacked: usize,
+    /// The folders created for directory entries, which aren't counted as
+    /// entries unpacked.
+    pub directories_created: usize,
+    /// Entries left out by a filter, the layer selection,
+    /// `exclude_empty_nodes`, `max_level` or `bbox`, or whose existing file
+    /// was kept.
+    pub entries_skipped: usize,
+    /// Entries whose existing file was complete, with
+    /// `ConflictPolicy::Complete`, which aren't counted as skipped.
+    pub entries_already_unpacked: usize,
+    /// The symbolic links recreated, with `allow_symlinks`, which aren't
+    /// counted as entries unpacked. Those left out are counted as skipped.
+    pub symlinks_created: usize,
+    /// The bytes of every file written, decompressed when the entry is
+    /// gzipped.
+    pub bytes_written: u64,
+    /// How long the whole unpack took.
+    pub elapsed: std::time::Duration,
+    /// How long each stage of the unpack took.
+    pub timings: StageTimings,
+    /// What would have been written, for a dry run, which writes nothing.
+    pub dry_run: Option<DryRunSummary>,
+    /// The entries which failed and were left out, with `keep_going`, in
+    /// the order of their names.
+    pub failed_entries: Vec<EntryError>,
+    /// The entries named .gz which couldn't be decompressed, and were
+    /// copied as they are under their .gz names, in the order of their
+    /// names. They are counted as entries unpacked.
+    pub degraded_entries: Vec<String>,
+    /// Whether the package gzipped its resources.
+    pub flavor: PackageFlavor,
+    /// The folder the package was unpacked into, or would be by a dry run.
+    pub unpack_folder: PathBuf,
+    /// The duplicates found, and the bytes linking them saved, with
+    /// `dedup`.
+    pub dedup: Option<DedupStats>,
+    /// The entries left as they were and the files deleted, with
+    /// `incremental`.
+    pub incremental: Option<IncrementalStats>,
+}
+
+impl fmt::Display for UnpackSummary {
+    /// The totals the CLI prints once an unpack is done.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.directories_created > 0 {
+            write!(
+                f,
+                "{} files, {} directories unpacked",
+                self.entries_unpacked, self.directories_created
+            )?;
+        } else {
+            write!(f, "{} files unpacked", self.entries_unpacked)?;
+        }
+        if let Some(incremental) = &self.incremental {
+            write!(
+                f,
+                ", {} unchanged, {} deleted",
+                incremental.entries_unchanged, incremental.files_deleted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl UnpackSummary {
+    /// The summary as a JSON object, for scripts which drive an unpack.
+    /// Failed entries are given with the error each failed with.
+    pub fn to_json(&self) -> Value {
+        let failed_entries = self
+            .failed_entries
+            .iter()
+            .map(|failure| {
+                Value::Object(vec![
+                    ("entry".to_string(), Value::from(failure.entry.as_str())),
+                    (
+                        "target".to_string(),
+                        Value::from(failure.target.to_string_lossy().into_owned()),
+                    ),
+                    ("error".to_string(), Value::from(failure.cause.to_string())),
+                ])
+            })
+            .collect();
+        let mut members = vec![
+            (
+                "entriesUnpacked".to_string(),
+                Value::from(self.entries_unpacked),
+            ),
+            (
+                "directoriesCreated".to_string(),
+                Value::from(self.directories_created),
+            ),
+            (
+                "entriesAlreadyUnpacked".to_string(),
+                Value::from(self.entries_already_unpacked),
+            ),
+            (
+                "entriesSkipped".to_string(),
+                Value::from(self.entries_skipped),
+            ),
+            (
+                "symlinksCreated".to_string(),
+                Value::from(self.symlinks_created),
+            ),
+            ("failedEntries".to_string(), Value::Array(failed_entries)),
+            (
+                "degradedEntries".to_string(),
+                Value::Array(
+                    self.degraded_entries
+                        .iter()
+                        .map(|entry| Value::from(entry.as_str()))
+                        .collect(),
+                ),
+            ),
+            ("bytesWritten".to_string(), Value::from(self.bytes_written)),
+            (
+                "unpackFolder".to_string(),
+                Value::from(self.unpack_folder.to_string_lossy().into_owned()),
+            ),
+            ("flavor".to_string(), Value::from(self.flavor.to_string())),
+            (
+                "seconds".to_string(),
+                Value::from(self.elapsed.as_secs_f64()),
+            ),
+            ("timings".to_string(), self.timings.to_json()),
+        ];
+        if let Some(dry_run) = &self.dry_run {
+            members.push(("dryRun".to_string(), dry_run.to_json()));
+        }
+        if let Some(dedup) = &self.dedup {
+            members.push((
+                "dedup".to_string(),
+                Value::Object(vec![
+                    ("duplicates".to_string(), Value::from(dedup.duplicates)),
+                    ("bytesSaved".to_string(), Value::from(dedup.bytes_saved)),
+                    ("linkFailures".to_string(), Value::from(dedup.link_failures)),
+                ]),
+            ));
+        }
+        if let Some(incremental) = &self.incremental {
+            members.push((
+                "incremental".to_string(),
+                Value::Object(vec![
+                    (
+                        "entriesUnchanged".to_string(),
+                        Value::from(incremental.entries_unchanged),
+                    ),
+                    (
+                        "filesDeleted".to_string(),
+                        Value::from(incremental.files_deleted),
+                    ),
+                ]),
+            ));
+        }
+        Value::Object(members)
+    }
+}
+
+/// Whether a file in an output folder is one which unpacking writes beside
+/// the entries, rather than one a package held, by its path in the folder.
+pub fn is_bookkeeping_file(name_in_folder: &str) -> bool {
+    name_in_folder == manifest::MANIFEST_FILE
+        || name_in_folder == checksums::CHECKSUM_FILE
+        || name_in_folder == html_index::INDEX_FILE
+        || name_in_folder == html_index::INDEX_DATA_FILE
+        || name_in_folder == deadline::RESUME_FILE
+        || name_in_folder == incremental::RECORD_FILE
+        || name_in_folder.ends_with(&format!(".{}", quarantine::MARKER_EXTENSION))
+        || staging::is_staging_path(Path::new(name_in_folder))
+}
+
+/// The decoded name of an entry, from its raw name in `central_directory`
+/// when that is UTF-8, and from the zip library otherwise.
+fn decoded_entry_name<R: Read + Seek>(
+    central_directory: &[RawEntry],
+    archive: &mut ZipArchive<R>,
+    entry_idx: usize,
+) -> Result<String, Error> {
+    match central_directory
+        .get(entry_idx)
+        .map(|entry| std::str::from_utf8(&entry.name))
+    {
+        Some(Ok(name)) => Ok(name.to_string()),
+        _ => {
+            let entry = archive.by_index(entry_idx)?;
+            Ok(archive::names::decode_name(entry.name_raw(), entry.name()).0)
+        }
+    }
+}
+
+/// The names of the entries at `indices`, or of those in `only` when it is
+/// given.
+fn remaining_entries<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    indices: &[usize],
+    only: Option<&HashSet<String>>,
+) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    for &entry_idx in indices {
+        let name = archive.by_index(entry_idx)?.name().to_string();
+        if only.map_or(true, |only| only.contains(&name)) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Unpacks a package next to itself, or into `options.output_dir`.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::unpack::{unpack_with_options, UnpackOptions};
+/// use std::path::Path;
+///
+/// # let output_dir = std::env::temp_dir().join(format!("slpkg-doc-{}", std::process::id()));
+/// let options = UnpackOptions::new().output_dir(output_dir.clone());
+/// let summary = unpack_with_options(Path::new("tests/fixtures/building.slpk"), &options)?;
+/// assert_eq!(summary.entries_unpacked, 13);
+/// assert!(output_dir.join("building/3dSceneLayer.json").is_file());
+/// # std::fs::remove_dir_all(&output_dir)?;
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn unpack_with_options(
+    slpk_file_path: &Path,
+    options: &UnpackOptions,
+) -> Result<UnpackSummary, Error> {
+    let decryption =
+        encryption::decryption(slpk_file_path, options.encryption.password.as_deref())?;
+    let decryption = decryption.as_ref();
+    if options.run.mmap {
+        match archive::mmap::Mapping::open(slpk_file_path) {
+            Ok(mapping) => {
+                return unpack_package(
+                    || {
+                        let reader = std::io::Cursor::new(mapping.as_slice());
+                        Ok(PackageView::new(reader, decryption))
+                    },
+                    Package::File(slpk_file_path),
+                    options,
+                )
             }
+            Err(e) => info!(
+                "Reading {} from the file, as it can't be mapped: {}",
+                slpk_file_path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    unpack_package(
+        || {
+            let reader = BufReader::new(File::open(slpk_file_path)?);
+            Ok(PackageView::new(reader, decryption))
+        },
+        Package::File(slpk_file_path),
+        options,
+    )
+}
+
+/// Unpacks a package next to itself with the default options, as slpkg did
+/// before it took any. The file each entry is written to is logged at the
+/// debug level, and with `verbose` so is the time spent in each stage.
+#[deprecated(
+    note = "use unpack_with_options, which takes UnpackOptions and returns an UnpackSummary"
+)]
+pub fn unpack(slpk_file_path: &Path, verbose: bool) -> Result<(), Error> {
+    unpack_with_options(slpk_file_path, &UnpackOptions::new().show_timings(verbose))?;
+    Ok(())
+}
+
+/// Unpacks a package from a reader into `unpack_folder`, such as one over a
+/// package held in memory. Each worker thread reads through a reader of its
+/// own, which `make_reader` is called for, so every reader it makes has to
+/// read the same package. `options.output_dir` isn't used, but what happens
+/// when `unpack_folder` exists still follows `options.overwrite`.
+pub fn unpack_from_reader<R, F>(
+    make_reader: F,
+    unpack_folder: &Path,
+    options: &UnpackOptions,
+) -> Result<UnpackSummary, Error>
+where
+    R: Read + Seek + Send,
+    F: Fn() -> Result<R, Error> + Sync,
+{
+    unpack_package(make_reader, Package::Reader { unpack_folder }, options)
+}
+
+/// Unpacks a package from a web server with HTTP range requests, without
+/// downloading all of it first, into a folder named after it in
+/// `options.output_dir` or in the current folder. `headers` are sent with
+/// every request. Only http:// URLs can be fetched.
+pub fn unpack_url(
+    url: &str,
+    headers: &[HttpHeader],
+    options: &UnpackOptions,
+) -> Result<UnpackSummary, Error> {
+    let folder_name = http::file_name(url)
+        .map(Path::new)
+        .filter(|name| name.extension().is_some())
+        .and_then(Path::file_stem)
+        .ok_or(UnpackError::NoFolderForPackage)?;
+    let output_dir = match &options.output.output_dir {
+        Some(output_dir) => output_dir.clone(),
+        None => std::env::current_dir()?,
+    };
+    let unpack_folder = output_dir.join(folder_name);
+    let remote = http::RemotePackage::open(url, headers)?;
+    unpack_package(
+        || Ok(remote.reader()),
+        Package::Url {
+            url,
+            unpack_folder: &unpack_folder,
+        },
+        options,
+    )
+}
+
+/// Unpacks a package from a stream which can't seek, such as standard
+/// input, into the folder `folder_name` in `options.output_dir`, which has
+/// to be given. The stream is spooled into a temporary file first, which is
+/// removed once the unpack is done.
+pub fn unpack_stream(
+    stream: impl Read,
+    folder_name: &Path,
+    options: &UnpackOptions,
+) -> Result<UnpackSummary, Error> {
+    // A tar stream is written instead of any folder.
+    let unpack_folder = match (&options.output.output_dir, &options.output.tar) {
+        (_, Some(_)) => std::env::current_dir()?.join(folder_name),
+        (Some(output_dir), None) => {
+            named_unpack_folder(output_dir, folder_name, options.output.dry_run)?
+        }
+        (None, None) => return Err(Error::from(UnpackError::NoOutputDirForStream)),
+    };
+    let spooled = spool::spool(stream, &std::env::temp_dir())?;
+    let decryption =
+        encryption::decryption(spooled.path(), options.encryption.password.as_deref())?;
+    unpack_package(
+        || {
+            let reader = BufReader::new(File::open(spooled.path())?);
+            Ok(PackageView::new(reader, decryption.as_ref()))
+        },
+        Package::Reader {
+            unpack_folder: &unpack_folder,
+        },
+        options,
+    )
+}
 
-            let mut gz_reader = GzDecoder::new(archive_entry);
-            let mut target_file = File::create(target_file_path)?;
-            std::io::copy(&mut gz_reader, &mut target_file)?;
+/// Where a package is read from, which decides where it is unpacked to.
+#[derive(Clone, Copy)]
+enum Package<'a> {
+    /// A package file, unpacked next to itself or into `output_dir`.
+    File(&'a Path),
+    /// A package read through readers, unpacked into `unpack_folder`.
+    Reader { unpack_folder: &'a Path },
+    /// A package on a web server, unpacked into `unpack_folder`.
+    Url {
+        url: &'a str,
+        unpack_folder: &'a Path,
+    },
+}
+
+/// The entries an unpack hands to its workers, and what decides what each
+/// is unpacked as, worked out from the package before the output folder is
+/// touched.
+struct EntrySelection {
+    /// The raw entries of the zip directory.
+    central_directory: Vec<RawEntry>,
+    /// The indices of the entries left once those of nodes and paths which
+    /// are left out are taken out, in the order they are handed out.
+    entry_indices: Vec<usize>,
+    selected_layer: Option<i3s::SceneLayer>,
+    /// How sublayers are routed, along with the root of their building
+    /// layer.
+    routing: Option<(SublayerRouting, String)>,
+    empty_nodes: Option<empty_nodes::EmptyNodes>,
+    strip_prefix: Option<PathBuf>,
+    portable_names: bool,
+    collisions: collisions::CaseCollisions,
+    /// The entries unpacked under a name the package doesn't give them.
+    renamed_entries: HashSet<usize>,
+    entries_filtered_by_path: HashMap<Filter, usize>,
+    entries_below_max_level: usize,
+    entries_outside_box: usize,
+}
+
+/// The folder an unpack writes to, with what an earlier run left in it,
+/// set up before the workers start.
+struct OutputPlan<'a> {
+    existing_folder: ExistingFolder,
+    /// Whether nothing is written to the output folder, for a dry run or a
+    /// tar stream.
+    leaves_folder: bool,
+    /// Whether the files written are recorded, for the record of the entries
+    /// unpacked into the folder.
+    record_files: bool,
+    /// The folder the summary names, which an atomic unpack only writes to
+    /// once it is done.
+    summary_folder: PathBuf,
+    unpack_folder: PathBuf,
+    atomic_folder: Option<AtomicFolder>,
+    stage_files: bool,
+    retry_policy: Option<RetryPolicy>,
+    /// The only entries unpacked, when resuming or retrying failed ones.
+    retry_set: Option<HashSet<String>>,
+    route_roots: Vec<&'a Path>,
+    /// The options the record of the entries is written with.
+    output_options: Value,
+    /// The entries an incremental run leaves as they are.
+    unchanged: Option<HashSet<String>>,
+    removed: Vec<EntryRecord>,
+    superseded: Vec<EntryRecord>,
+}
+
+/// An unpack of a package, which `unpack_package` runs through its phases:
+/// selecting the entries, preparing the output folder, running the workers,
+/// reporting what they did, verifying it, and recording it.
+struct Unpack<'a, R> {
+    make_reader: &'a (dyn Fn() -> Result<R, Error> + Sync),
+    package: Package<'a>,
+    /// What the package is called, or nothing for one read through readers.
+    package_name: String,
+    options: &'a UnpackOptions,
+    flavor: PackageFlavor,
+    /// Whether entries are decompressed by their first bytes rather than by
+    /// their name.
+    sniff_compression: bool,
+    unpack_start: Instant,
+    trace_start: Option<Instant>,
+}
+
+fn unpack_package<R, F>(
+    make_reader: F,
+    package: Package,
+    options: &UnpackOptions,
+) -> Result<UnpackSummary, Error>
+where
+    R: Read + Seek + Send,
+    F: Fn() -> Result<R, Error> + Sync,
+{
+    let trace_start = options.reports.trace_path.as_ref().map(|_| Instant::now());
+    let unpack_start = Instant::now();
+    let keep_gzip = options.content.keep_gzip;
+    if options.integrity.verify_after && options.content.json_formatting != JsonFormatting::AsIs {
+        return Err(Error::from(UnpackError::ReformattedVerification));
+    }
+
+    if options.integrity.hardened {
+        paranoid::check_entry_count(&mut make_reader()?)?;
+    }
+    let mut slpk_archive = ZipArchive::new(PackageReader::new(&make_reader))?;
+    let package_name = match package {
+        Package::File(slpk_file_path) => slpk_file_path.to_string_lossy().into_owned(),
+        Package::Url { url, .. } => url.to_string(),
+        Package::Reader { .. } => String::new(),
+    };
+    let flavor = PackageFlavor::of_package(&mut slpk_archive, &package_name)?;
+    // Entries of an ESLPK are only decompressed when they turn out to be
+    // gzipped, whatever they are named.
+    let sniff_compression =
+        !options.content.trust_extensions || (flavor == PackageFlavor::Eslpk && !keep_gzip);
+    if flavor == PackageFlavor::Eslpk {
+        info!("The package is an ESLPK, with its resources stored as they are");
+    }
+    let unpack = Unpack {
+        make_reader: &make_reader,
+        package,
+        package_name,
+        options,
+        flavor,
+        sniff_compression,
+        unpack_start,
+        trace_start,
+    };
+
+    let selection = unpack.select_entries(&mut slpk_archive)?;
+    let node_index = if options.reports.semantic_manifest {
+        let node_index =
+            manifest::NodeIndex::read(&mut slpk_archive, selection.selected_layer.as_ref());
+        for problem in node_index.problems() {
+            warn!("The manifest will be partial: {}", problem);
         }
-    } else if let Some(name) = archive_entry_path.file_name() {
-        let mut target_file_path = target_folder;
-        target_file_path.push(name);
+        Some(node_index)
+    } else {
+        None
+    };
+
+    let output = unpack.prepare_output(&mut slpk_archive, &selection)?;
+    let deduplicator = options.output.dedup.map(Deduplicator::new);
+    let mut total = unpack.run_workers(
+        &mut slpk_archive,
+        &selection,
+        &output,
+        deduplicator.as_ref(),
+    )?;
 
-        if verbose {
-            println!(
-                "Copy: {} -> {}",
-                archive_entry.name(),
-                target_file_path.to_string_lossy()
+    let dry_run = options.output.dry_run;
+    let unpack_folder = &output.unpack_folder;
+    let entries_skipped = total.entries_skipped
+        + selection.entries_below_max_level
+        + selection.entries_outside_box
+        + total.entries_of_other_layers
+        + total.entries_filtered.values().sum::<usize>()
+        + total.unprefixed_entries.len()
+        + total.skipped_symlinks.len()
+        + total.files_kept;
+    if options.selection.incremental {
+        info!(
+            "{} unchanged entries {} left as they were",
+            total.entries_unchanged,
+            if dry_run { "would be" } else { "were" }
+        );
+        if dry_run && !output.removed.is_empty() {
+            info!(
+                "Would delete the files of {} entries the package no longer has",
+                output.removed.len()
             );
         }
+    }
+    if dry_run {
+        return Ok(UnpackSummary {
+            entries_skipped,
+            elapsed: unpack_start.elapsed(),
+            dry_run: Some(total.dry_run.summary()),
+            flavor,
+            unpack_folder: output.summary_folder,
+            ..UnpackSummary::default()
+        });
+    }
+    if !total.remaining_entries.is_empty() {
+        total.remaining_entries.sort();
+        deadline::write_resume_file(
+            unpack_folder,
+            unpack_start.elapsed(),
+            total.entries_unpacked,
+            &total.remaining_entries,
+        )?;
+        warn!(
+            "Timed out with {} entries left, which are listed in {}",
+            total.remaining_entries.len(),
+            unpack_folder.join(deadline::RESUME_FILE).to_string_lossy()
+        );
+    }
+    unpack.report_outcome(&mut total, &selection);
+    let dedup = deduplicator.map(Deduplicator::into_stats);
+    if let Some(dedup) = &dedup {
+        info!("{}", dedup);
+    }
+    unpack.report_skipped_entries(&mut total, &selection);
 
-        let mut target_file = File::create(target_file_path)?;
-        std::io::copy(&mut archive_entry, &mut target_file)?;
+    let (mismatches, output_mismatches) = unpack.verify_unpacked(&mut slpk_archive, &mut total)?;
+    unpack.write_reports(
+        &mut slpk_archive,
+        &selection,
+        &output,
+        node_index.as_ref(),
+        &mut total,
+        mismatches.as_deref(),
+    )?;
+
+    if !total.remaining_entries.is_empty() {
+        return Err(Error::from(UnpackError::TimedOut(
+            unpack_start.elapsed().as_secs_f64(),
+            total.remaining_entries.len(),
+        )));
     }
 
-    Ok(())
+    // Every entry has been checked, so all of the conflicts are listed at
+    // once rather than one per run.
+    if !total.conflicting_entries.is_empty() {
+        total.conflicting_entries.sort();
+        return Err(Error::from(UnpackError::FileConflicts(
+            total.conflicting_entries.len(),
+            total
+                .conflicting_entries
+                .iter()
+                .map(|name| format!("    {}", name))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )));
+    }
+
+    let mismatched = mismatches.map_or(0, |m| m.len()) + output_mismatches;
+    if mismatched > 0 {
+        return Err(Error::from(UnpackError::VerificationFailed(mismatched)));
+    }
+
+    let files_deleted = unpack.record_entries(&selection, &output, &total)?;
+
+    let OutputPlan {
+        summary_folder,
+        atomic_folder,
+        ..
+    } = output;
+    if let Some(atomic_folder) = atomic_folder {
+        for failure in &mut total.failed_entries {
+            failure.target = atomic_folder.committed_path(&failure.target);
+        }
+        atomic_folder.commit()?;
+    }
+
+    Ok(UnpackSummary {
+        entries_unpacked: total.entries_unpacked,
+        directories_created: total.directories_created,
+        entries_skipped,
+        entries_already_unpacked: total.files_already_unpacked,
+        symlinks_created: total.symlinks_created,
+        bytes_written: total.bytes_written,
+        elapsed: unpack_start.elapsed(),
+        timings: total.timings,
+        dry_run: None,
+        failed_entries: total.failed_entries,
+        degraded_entries: total.degraded_entries,
+        flavor,
+        unpack_folder: summary_folder,
+        dedup,
+        incremental: if options.selection.incremental {
+            Some(IncrementalStats {
+                entries_unchanged: total.entries_unchanged,
+                files_deleted,
+            })
+        } else {
+            None
+        },
+    })
 }
 
-pub fn unpack(slpk_file_path: &PathBuf, verbose: bool) -> Result<(), Error> {
-    println!("Unpacking archive: {}", slpk_file_path.to_string_lossy());
+impl<R: Read + Seek + Send> Unpack<'_, R> {
+    /// Works out which entries are handed to the workers, and what each is
+    /// unpacked as.
+    fn select_entries(
+        &self,
+        slpk_archive: &mut ZipArchive<PackageReader<R>>,
+    ) -> Result<EntrySelection, Error> {
+        let options = self.options;
+        let filters = &options.filters;
+        let keep_gzip = options.content.keep_gzip;
+        // The layer and routing are worked out before touching the output
+        // folder, so that a package which can't be split doesn't cost the user
+        // their old output.
+        let selected_layer = match options.selection.layer {
+            Some(id) => Some(i3s::select_layer(slpk_archive, Some(id))?),
+            None => None,
+        };
 
-    let slpk_archive = open_slpk_archive(slpk_file_path.clone())?;
-    let unpack_folder = get_unpack_folder(slpk_file_path.clone())?;
+        let routing = if options.output.split_sublayers {
+            let layer = match &selected_layer {
+                Some(layer) => layer.clone(),
+                None => i3s::read_scene_layer(slpk_archive)?,
+            };
+            let routing = SublayerRouting::from_layer_document(&layer.document)?;
+            Some((routing, layer.root.clone()))
+        } else {
+            None
+        };
+
+        let empty_nodes = if options.selection.exclude_empty_nodes {
+            let mut empty_nodes = empty_nodes::EmptyNodes::default();
+            match &selected_layer {
+                Some(layer) => {
+                    empty_nodes::find_empty_nodes(slpk_archive, layer, &mut empty_nodes)?
+                }
+                None => {
+                    for layer in &i3s::read_layers(slpk_archive)? {
+                        empty_nodes::find_empty_nodes(slpk_archive, layer, &mut empty_nodes)?;
+                    }
+                }
+            }
+            if empty_nodes.ids.is_empty() {
+                info!("The package has no empty nodes");
+            } else {
+                info!(
+                    "Skipping the resources of {} empty nodes: {}",
+                    empty_nodes.ids.len(),
+                    empty_nodes.ids.join(", ")
+                );
+            }
+            Some(empty_nodes)
+        } else {
+            None
+        };
 
-    let num_entries = slpk_archive.len();
-    let num_cores = num_cpus::get();
+        let shallow_nodes = match options.selection.max_level {
+            Some(max_level) => {
+                let mut shallow_nodes = levels::ShallowNodes::default();
+                match &selected_layer {
+                    Some(layer) => levels::find_shallow_nodes(
+                        slpk_archive,
+                        layer,
+                        max_level,
+                        &mut shallow_nodes,
+                    )?,
+                    None => {
+                        for layer in &i3s::read_layers(slpk_archive)? {
+                            levels::find_shallow_nodes(
+                                slpk_archive,
+                                layer,
+                                max_level,
+                                &mut shallow_nodes,
+                            )?;
+                        }
+                    }
+                }
+                info!(
+                    "Unpacking {} nodes down to level {}, leaving out {}",
+                    shallow_nodes.kept, max_level, shallow_nodes.left_out
+                );
+                Some(shallow_nodes)
+            }
+            None => None,
+        };
 
-    let splits = split_indices::split_indices_into_ranges(num_entries, num_cores);
-    let mut threads = Vec::with_capacity(splits.len());
+        let nodes_in_box = match &options.selection.bbox {
+            Some(bbox) => {
+                let mut nodes_in_box = spatial::NodesInBox::default();
+                let wgs84 = options.selection.bbox_wgs84;
+                match &selected_layer {
+                    Some(layer) => spatial::find_nodes_in_box(
+                        slpk_archive,
+                        layer,
+                        bbox,
+                        wgs84,
+                        &mut nodes_in_box,
+                    )?,
+                    None => {
+                        for layer in &i3s::read_layers(slpk_archive)? {
+                            spatial::find_nodes_in_box(
+                                slpk_archive,
+                                layer,
+                                bbox,
+                                wgs84,
+                                &mut nodes_in_box,
+                            )?;
+                        }
+                    }
+                }
+                info!(
+                    "Unpacking {} nodes in the box or above them, leaving out {}",
+                    nodes_in_box.kept, nodes_in_box.left_out
+                );
+                Some(nodes_in_box)
+            }
+            None => None,
+        };
 
-    for (start_entry, end_entry) in splits {
-        let slpk_file_path = slpk_file_path.clone();
-        let unpack_folder = unpack_folder.clone();
-        threads.push(thread::spawn(move || -> Result<usize, Error> {
-            let mut slpk_archive = open_slpk_archive(slpk_file_path.clone())?;
+        // Entries are matched against path patterns and the nodes they belong
+        // to up front, so that the workers share out only the entries which are
+        // left. Otherwise a worker whose range holds the textures would have
+        // nothing to do when they are excluded.
+        let mut entries_filtered_by_path: HashMap<Filter, usize> = HashMap::new();
+        let mut entries_below_max_level = 0;
+        let mut entries_outside_box = 0;
+        let entry_indices: Vec<usize> =
+            if filters.has_path_patterns() || shallow_nodes.is_some() || nodes_in_box.is_some() {
+                let mut selected = Vec::new();
+                for entry_idx in 0..slpk_archive.len() {
+                    let entry = slpk_archive.by_index(entry_idx)?;
+                    if shallow_nodes
+                        .as_ref()
+                        .is_some_and(|shallow_nodes| shallow_nodes.skips(entry.name()))
+                    {
+                        entries_below_max_level += 1;
+                        continue;
+                    }
+                    if nodes_in_box
+                        .as_ref()
+                        .is_some_and(|nodes_in_box| nodes_in_box.skips(entry.name()))
+                    {
+                        entries_outside_box += 1;
+                        continue;
+                    }
+                    if !filters.has_path_patterns() {
+                        selected.push(entry_idx);
+                        continue;
+                    }
+                    let path = archive::names::entry_path(&entry);
+                    match filters.excludes_path(&path) {
+                        Some(filter) => *entries_filtered_by_path.entry(filter).or_insert(0) += 1,
+                        None => selected.push(entry_idx),
+                    }
+                }
+                selected
+            } else {
+                (0..slpk_archive.len()).collect()
+            };
 
-            let mut entries_unpacked = 0;
-            for entry_idx in start_entry..end_entry {
-                let archive_entry = slpk_archive.by_index(entry_idx)?;
-                unpack_entry(archive_entry, unpack_folder.clone(), verbose)?;
-                entries_unpacked += 1;
+        // The entries are taken from the central directory alone for the checks
+        // below, since reading the local header of every entry would fetch most
+        // of a package read from a URL.
+        let central_directory =
+            archive::raw::central_directory_entries(&mut (self.make_reader)()?)?;
+        let keep_unprefixed = options.selection.keep_unprefixed;
+        let strip_prefix = match &options.selection.strip_prefix {
+            Some(StripPrefix::Folder(folder)) => Some(folder.clone()),
+            Some(StripPrefix::Auto) => {
+                let entry_names = (0..slpk_archive.len())
+                    .map(|entry_idx| {
+                        decoded_entry_name(&central_directory, slpk_archive, entry_idx)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let folder = prefix::common_folder(entry_names.iter().map(String::as_str));
+                match &folder {
+                    Some(folder) => info!(
+                        "Every entry is in {}, which is stripped from their paths",
+                        folder.to_string_lossy()
+                    ),
+                    None => info!("The entries aren't all in one folder, so nothing is stripped"),
+                }
+                folder
             }
+            None => None,
+        };
 
-            Ok(entries_unpacked)
-        }));
+        // Collisions are looked for among the entries which are left, before
+        // the output folder is touched, so that a package which would unpack
+        // one file over another doesn't cost the user their old output.
+        let portable_names = cfg!(windows) || options.output.portable_names;
+        let mut escaped = HashSet::new();
+        let collisions = {
+            let mut paths = Vec::with_capacity(entry_indices.len());
+            for &entry_idx in &entry_indices {
+                let name = decoded_entry_name(&central_directory, slpk_archive, entry_idx)?;
+                if name.ends_with('/') || name.ends_with('\\') {
+                    continue;
+                }
+                let path = archive::names::sanitized_path(&name);
+                let path = match &strip_prefix {
+                    Some(prefix) => match prefix::strip(&path, prefix) {
+                        Some(stripped) => stripped,
+                        None if keep_unprefixed => path,
+                        None => continue,
+                    },
+                    None => path,
+                };
+                let path = match portable::portable_path(&path).filter(|_| portable_names) {
+                    Some(portable) => {
+                        warn!(
+                            "Entry {} is unpacked as {}, as Windows doesn't allow its name",
+                            name,
+                            portable.to_string_lossy()
+                        );
+                        escaped.insert(entry_idx);
+                        portable
+                    }
+                    None => path,
+                };
+                paths.push((entry_idx, path));
+            }
+            collisions::find_case_collisions(paths, &escaped, keep_gzip)
+        };
+        if !collisions.is_empty() {
+            if !options.output.rename_collisions
+                && collisions.pairs.len() > collisions.escaped_pairs
+            {
+                return Err(Error::from(UnpackError::CaseCollisions(
+                    collisions.pairs.len(),
+                    collisions.describe(),
+                )));
+            }
+            info!(
+                "Renaming {} entries whose files collide with others when case is ignored",
+                collisions.pairs.len()
+            );
+        }
+        // The entries unpacked under a name the package doesn't give them.
+        let renamed_entries: HashSet<usize> = escaped
+            .into_iter()
+            .chain(collisions.renamed_entries())
+            .collect();
+        // Reordered only once collisions are found, so that the same entries are
+        // renamed whatever the order.
+        let entry_indices = match options.run.extract_order {
+            ExtractOrder::ArchiveOrder => entry_indices,
+            order => {
+                let mut entries = Vec::with_capacity(entry_indices.len());
+                for &entry_idx in &entry_indices {
+                    let name = decoded_entry_name(&central_directory, slpk_archive, entry_idx)?;
+                    entries.push((entry_idx, name));
+                }
+                extract_order::ordered(order, entries)
+            }
+        };
+        Ok(EntrySelection {
+            central_directory,
+            entry_indices,
+            selected_layer,
+            routing,
+            empty_nodes,
+            strip_prefix,
+            portable_names,
+            collisions,
+            renamed_entries,
+            entries_filtered_by_path,
+            entries_below_max_level,
+            entries_outside_box,
+        })
     }
 
-    let mut total_entries_unpacked = 0;
-    for t in threads {
-        let thread_result = t.join();
-        match thread_result {
-            Ok(Ok(n)) => {
-                total_entries_unpacked += n;
+    /// Creates, replaces or reopens the output folder, and works out which
+    /// of the entries an earlier run unpacked into it are left alone.
+    fn prepare_output(
+        &self,
+        slpk_archive: &mut ZipArchive<PackageReader<R>>,
+        selection: &EntrySelection,
+    ) -> Result<OutputPlan<'_>, Error> {
+        let options = self.options;
+        let central_directory = &selection.central_directory;
+        let dry_run = options.output.dry_run;
+        if options.output.tar.is_some() {
+            let writes_folder = [
+                (dry_run, "dry_run"),
+                (options.output.atomic, "atomic"),
+                (options.selection.resume, "resume"),
+                (options.selection.incremental, "incremental"),
+                (options.selection.retry_failed, "retry_failed"),
+                (options.run.retry.is_some(), "retry"),
+                (
+                    options.output.on_file_conflict.is_some(),
+                    "on_file_conflict",
+                ),
+                (options.run.timeout.is_some(), "timeout"),
+                (options.integrity.verify_after, "verify_after"),
+                (options.integrity.verify_output, "verify_output"),
+                (options.output.stage_files, "stage_files"),
+                (options.output.dedup.is_some(), "dedup"),
+                (!options.output.routes.is_empty(), "routes"),
+                (options.reports.semantic_manifest, "semantic_manifest"),
+                (options.reports.checksum_manifest, "checksum_manifest"),
+                (options.reports.emit_index, "emit_index"),
+            ];
+            if let Some((_, option)) = writes_folder.iter().find(|(set, _)| *set) {
+                return Err(Error::from(UnpackError::TarWith(option)));
+            }
+        }
+        // Nothing is written to the output folder when the files go to a tar
+        // stream, as with a dry run.
+        let leaves_folder = dry_run || options.output.tar.is_some();
+        // The files written are always recorded for the record of the entries
+        // unpacked into the folder.
+        let record_files = !leaves_folder;
+        if let Some(order_file) = options.reports.order_file.as_ref().filter(|_| !dry_run) {
+            let entries = order::write_order_file(slpk_archive, order_file)?;
+            info!(
+                "The order of {} entries was written to {}",
+                entries,
+                order_file.to_string_lossy()
+            );
+        }
+
+        let existing_folder = if options.selection.retry_failed || options.selection.resume {
+            ExistingFolder::Reuse
+        } else if options.output.on_file_conflict.is_some() || options.selection.incremental {
+            ExistingFolder::Merge
+        } else {
+            match options.output.overwrite {
+                OverwriteMode::Error => ExistingFolder::Refuse,
+                OverwriteMode::Overwrite => ExistingFolder::Replace,
+                OverwriteMode::Merge => ExistingFolder::Merge,
             }
-            Ok(Err(e)) => {
-                eprintln!("{}", e);
-                // TODO: Should this return, or wait for other threads to finish?
-                return Err(e);
+        };
+        let target_folder = match self.package {
+            Package::File(slpk_file_path) => unpack_folder_path(
+                slpk_file_path.to_path_buf(),
+                options.output.output_dir.as_deref(),
+                leaves_folder,
+            )?,
+            Package::Reader { unpack_folder } | Package::Url { unpack_folder, .. } => {
+                if let Some(parent) = unpack_folder.parent().filter(|_| !leaves_folder) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                unpack_folder.to_path_buf()
             }
-            Err(e) => {
-                eprintln!("{:?}", e);
-                panic!("Thread panicked!")
+        };
+        let atomic_folder = if options.output.atomic && !dry_run {
+            let refuse_existing = match existing_folder {
+                ExistingFolder::Refuse => true,
+                ExistingFolder::Replace => false,
+                _ if options.selection.resume => {
+                    return Err(Error::from(UnpackError::AtomicWith("resume")))
+                }
+                _ if options.selection.retry_failed => {
+                    return Err(Error::from(UnpackError::AtomicWith("retry_failed")))
+                }
+                _ if options.selection.incremental => {
+                    return Err(Error::from(UnpackError::AtomicWith("incremental")))
+                }
+                _ => {
+                    return Err(Error::from(UnpackError::AtomicWith(
+                        "merging into the existing folder",
+                    )))
+                }
+            };
+            if options.run.timeout.is_some() {
+                return Err(Error::from(UnpackError::AtomicWith("timeout")));
+            }
+            Some(AtomicFolder::create(&target_folder, refuse_existing)?)
+        } else {
+            None
+        };
+        let summary_folder = target_folder.clone();
+        let unpack_folder = match &atomic_folder {
+            Some(atomic_folder) => atomic_folder.path().to_path_buf(),
+            None if options.output.tar.is_some() => target_folder,
+            None => prepare_unpack_folder(target_folder, existing_folder, dry_run)?,
+        };
+        let unpack_folder = long_paths::verbatim_folder(unpack_folder);
+        let retry_policy = match &options.run.retry {
+            Some(policy) => Some(policy.clone()),
+            None if options.selection.retry_failed => Some(RetryPolicy {
+                retries: 0,
+                backoff: Default::default(),
+            }),
+            None => None,
+        };
+        let retry_set = if options.selection.resume {
+            let resume_file = if dry_run {
+                deadline::read_resume_file(&unpack_folder)?
+            } else {
+                deadline::take_resume_file(&unpack_folder)?
+            };
+            let names = match resume_file {
+                Some(names) => names,
+                None => {
+                    info!("There is no resume file, so the earlier run finished");
+                    HashSet::new()
+                }
+            };
+            info!(
+                "Resuming with {} entries left by an earlier run",
+                names.len()
+            );
+            Some(names)
+        } else if options.selection.retry_failed {
+            let markers = quarantine::find_markers(&unpack_folder)?;
+            info!(
+                "Retrying {} entries which failed in an earlier run",
+                markers.len()
+            );
+            // Entries which fail again get a fresh marker.
+            for (marker, _) in markers.iter().filter(|_| !dry_run) {
+                std::fs::remove_file(marker)?;
+            }
+            let names: HashSet<String> = markers.into_iter().map(|(_, name)| name).collect();
+            Some(names)
+        } else {
+            None
+        };
+        let route_roots: Vec<&Path> = options
+            .output
+            .routes
+            .iter()
+            .map(|route| route.root.as_path())
+            .collect();
+        let output_options = incremental::output_options(options);
+        let changes = if options.selection.incremental {
+            match incremental::read_record(&unpack_folder, &route_roots) {
+                Ok(Some(earlier)) => {
+                    let mut entries = Vec::with_capacity(central_directory.len());
+                    for (entry_idx, entry) in central_directory.iter().enumerate() {
+                        // The workers match entries by the name the zip library
+                        // gives them.
+                        let name = match std::str::from_utf8(&entry.name) {
+                            Ok(name) => name.to_string(),
+                            Err(_) => slpk_archive.by_index(entry_idx)?.name().to_string(),
+                        };
+                        entries.push((name, entry.crc32, entry.size));
+                    }
+                    let changes = incremental::find_changes(
+                        &unpack_folder,
+                        &route_roots,
+                        &earlier,
+                        &output_options,
+                        entries,
+                    );
+                    if !changes.superseded.is_empty() {
+                        warn!(
+                            "The last run unpacked with other options, so every entry is unpacked"
+                        );
+                    }
+                    info!(
+                    "{} entries are unchanged since the last run, and {} are gone from the package",
+                    changes.unchanged.len(),
+                    changes.removed.len()
+                );
+                    Some(changes)
+                }
+                // There is nothing to warn of in a folder this run created.
+                Ok(None) if folder_is_empty(&unpack_folder) => None,
+                Ok(None) => {
+                    warn!(
+                        "There is no {} in the output folder, so every entry is unpacked",
+                        incremental::RECORD_FILE
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("{}, so every entry is unpacked", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let (unchanged, removed, superseded) = match changes {
+            Some(changes) => (Some(changes.unchanged), changes.removed, changes.superseded),
+            None => (None, Vec::new(), Vec::new()),
+        };
+        // Unlike the output folder, routed folders are shared with other data, so
+        // they are never deleted.
+        for route in options.output.routes.iter().filter(|_| !dry_run) {
+            std::fs::create_dir_all(&route.root)?;
+        }
+        // Whatever an interrupted run left staged is removed whether or not this
+        // run stages files, since no package holds such names.
+        let mut partial_files_removed = 0;
+        if !leaves_folder {
+            partial_files_removed += staging::remove_leftovers(&unpack_folder)?;
+            for route in options.output.routes.iter() {
+                partial_files_removed += staging::remove_leftovers(&route.root)?;
             }
         }
+        if partial_files_removed > 0 {
+            info!(
+                "Removed {} partial files left by an interrupted run",
+                partial_files_removed
+            );
+        }
+        let stage_files = options.output.stage_files
+            || options.integrity.verify_after
+            || options.run.timeout.is_some()
+            || options.run.cancel.is_some()
+            || (options.integrity.validate_json && options.integrity.strict_json)
+            || existing_folder == ExistingFolder::Merge
+            || existing_folder == ExistingFolder::Reuse;
+        Ok(OutputPlan {
+            existing_folder,
+            leaves_folder,
+            record_files,
+            summary_folder,
+            unpack_folder,
+            atomic_folder,
+            stage_files,
+            retry_policy,
+            retry_set,
+            route_roots,
+            output_options,
+            unchanged,
+            removed,
+            superseded,
+        })
     }
 
-    println!("{} files unpacked", total_entries_unpacked);
+    /// Unpacks the selected entries on the worker threads, and adds up what
+    /// they did.
+    fn run_workers(
+        &self,
+        slpk_archive: &mut ZipArchive<PackageReader<R>>,
+        selection: &EntrySelection,
+        output: &OutputPlan,
+        deduplicator: Option<&Deduplicator>,
+    ) -> Result<WorkerSummary, Error> {
+        let options = self.options;
+        let entry_indices = &selection.entry_indices;
+        let retry_set = &output.retry_set;
+        let tar_stream = match &options.output.tar {
+            Some(target) => Some(Mutex::new(target.open()?)),
+            None => None,
+        };
+        let num_entries = entry_indices.len();
+        let progress = options
+            .run
+            .progress
+            .clone()
+            .map(|progress| ProgressCounters::new(progress, num_entries));
+        let queue = EntryQueue::new(num_entries);
+        let stop = AtomicBool::new(false);
+        let workers = worker::Workers {
+            make_reader: self.make_reader,
+            options,
+            selection,
+            output,
+            flavor: self.flavor,
+            sniff_compression: self.sniff_compression,
+            sort_keys: if options.content.sort_keys {
+                Some(
+                    options
+                        .content
+                        .sort_keys_max_size
+                        .unwrap_or(DEFAULT_SORT_KEYS_MAX_SIZE),
+                )
+            } else {
+                None
+            },
+            write_buffer_size: options
+                .output
+                .write_buffer_size
+                .unwrap_or(DEFAULT_WRITE_BUFFER_SIZE),
+            deadline: options
+                .run
+                .timeout
+                .map(|timeout| Deadline(self.unpack_start + timeout)),
+            trace_start: self.trace_start,
+            deduplicator,
+            tar: tar_stream.as_ref(),
+            progress: progress.as_ref(),
+            queue: &queue,
+            stop: &stop,
+        };
 
-    Ok(())
+        // There are never more workers than entries. A single worker runs on
+        // the calling thread, as does the only one writing a tar stream.
+        let num_threads = match tar_stream {
+            Some(_) => 1,
+            None => options.run.threads.unwrap_or_else(num_cpus::get),
+        };
+        let num_workers = num_threads.max(1).min(num_entries);
+        let mut finished = Vec::new();
+        thread::scope(|scope| {
+            let mut threads = Vec::with_capacity(num_workers);
+            for worker_idx in 0..num_workers {
+                let slpk_archive = slpk_archive.clone();
+                let workers = &workers;
+                let work = move || workers.run(worker_idx, slpk_archive);
+                // A panic stops the other workers as it unwinds, and is caught
+                // so that it fails the unpack like any other error once every
+                // worker has stopped.
+                if num_workers == 1 {
+                    finished.push(panic::catch_unwind(AssertUnwindSafe(work)));
+                } else {
+                    threads.push(scope.spawn(work));
+                }
+            }
+            finished.extend(threads.into_iter().map(thread::ScopedJoinHandle::join));
+        });
+
+        let mut total = WorkerSummary {
+            entries_filtered: selection.entries_filtered_by_path.clone(),
+            ..WorkerSummary::default()
+        };
+        // Every worker is waited for, even once one has failed, as the others
+        // stop at their next entry and may still be writing to the output.
+        let mut first_error = None;
+        for thread_result in finished {
+            match thread_result {
+                Ok(Ok(summary)) => total.add(summary),
+                Ok(Err(e)) => {
+                    first_error.get_or_insert(e);
+                }
+                Err(payload) => {
+                    first_error.get_or_insert(Error::from(UnpackError::WorkerPanicked(
+                        panic_message(payload),
+                    )));
+                }
+            }
+        }
+        // An entry cut short by the cancel may fail in ways its worker doesn't
+        // tell apart, such as a corrupt gzip stream.
+        let cancelled = options
+            .run
+            .cancel
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled);
+        if total.cancelled || (cancelled && first_error.is_some()) {
+            return Err(Error::from(UnpackError::Cancelled(total.entries_unpacked)));
+        }
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+        if let Some(tar_stream) = tar_stream {
+            tar_stream.into_inner().unwrap().finish()?;
+        }
+        let unclaimed = queue.unclaimed();
+        if unclaimed < num_entries {
+            total.remaining_entries.extend(remaining_entries(
+                slpk_archive,
+                &entry_indices[unclaimed..],
+                retry_set.as_ref(),
+            )?);
+        }
+
+        Ok(total)
+    }
+
+    /// Logs what was skipped, overwritten, kept, retried and left out.
+    fn report_outcome(&self, total: &mut WorkerSummary, selection: &EntrySelection) {
+        let routes = &self.options.output.routes;
+        let entries_below_max_level = selection.entries_below_max_level;
+        let entries_outside_box = selection.entries_outside_box;
+        if total.entries_skipped > 0 {
+            info!("{} entries of empty nodes skipped", total.entries_skipped);
+        }
+        for filter in &Filter::ALL {
+            if let Some(n) = total.entries_filtered.get(filter) {
+                info!("{} entries excluded by {}", n, filter);
+            }
+        }
+        if total.entries_of_other_layers > 0 {
+            info!(
+                "{} entries of other layers skipped",
+                total.entries_of_other_layers
+            );
+        }
+        if entries_below_max_level > 0 {
+            info!(
+                "{} entries of nodes below the maximum level skipped",
+                entries_below_max_level
+            );
+        }
+        if entries_outside_box > 0 {
+            info!(
+                "{} entries of nodes outside of the box skipped",
+                entries_outside_box
+            );
+        }
+        for (i, route) in routes.iter().enumerate() {
+            // A later route for the same class takes precedence.
+            if routes[i + 1..].iter().any(|r| r.class == route.class) {
+                continue;
+            }
+            info!(
+                "    {} {} files written to {}",
+                total.entries_per_class.get(&route.class).unwrap_or(&0),
+                route.class,
+                route.root.to_string_lossy()
+            );
+        }
+
+        if total.files_overwritten > 0 {
+            info!("{} existing files overwritten", total.files_overwritten);
+        }
+        if total.files_already_unpacked > 0 {
+            info!(
+                "{} files were already unpacked and were kept",
+                total.files_already_unpacked
+            );
+        }
+        if total.files_kept > 0 {
+            info!(
+                "{} existing files kept instead of being overwritten",
+                total.files_kept
+            );
+        }
+
+        if total.entries_retried > 0 {
+            info!(
+                "{} entries failed at first and were unpacked after retrying",
+                total.entries_retried
+            );
+        }
+        if !total.quarantined_entries.is_empty() {
+            total.quarantined_entries.sort();
+            warn!("{} entries kept failing and were set aside, rerun with --retry-failed to try them again:",
+            total.quarantined_entries.len()
+        );
+            for name in &total.quarantined_entries {
+                warn!("    {}", name);
+            }
+        }
+        total.degraded_entries.sort();
+        if !total.failed_entries.is_empty() {
+            total.failed_entries.sort_by(|a, b| a.entry.cmp(&b.entry));
+            warn!(
+                "{} entries failed and were left out:",
+                total.failed_entries.len()
+            );
+            for failure in &total.failed_entries {
+                warn!("    {}", failure);
+            }
+        }
+    }
+
+    /// Logs the entries which weren't unpacked as the package has them:
+    /// those left out by the prefix stripped and the links left out, and
+    /// those which went to no sublayer.
+    fn report_skipped_entries(&self, total: &mut WorkerSummary, selection: &EntrySelection) {
+        let strip_prefix = &selection.strip_prefix;
+        if let (false, Some(prefix)) = (total.unprefixed_entries.is_empty(), &strip_prefix) {
+            total.unprefixed_entries.sort();
+            warn!(
+            "{} entries aren't in {} and were left out, --keep-unprefixed unpacks them as they are:",
+            total.unprefixed_entries.len(),
+            prefix.to_string_lossy()
+        );
+            for name in &total.unprefixed_entries {
+                warn!("    {}", name);
+            }
+        }
+
+        if !total.skipped_symlinks.is_empty() {
+            total.skipped_symlinks.sort();
+            warn!(
+                "{} symbolic links were left out:",
+                total.skipped_symlinks.len()
+            );
+            for (name, reason) in &total.skipped_symlinks {
+                warn!("    {}: {}", name, reason);
+            }
+        }
+        if total.symlinks_created > 0 {
+            info!("{} symbolic links recreated", total.symlinks_created);
+        }
+
+        if !total.unassigned_entries.is_empty() {
+            total.unassigned_entries.sort();
+            warn!(
+                "{} entries could not be attributed to a sublayer and were unpacked into {}:",
+                total.unassigned_entries.len(),
+                sublayers::UNASSIGNED_FOLDER
+            );
+            for name in &total.unassigned_entries {
+                warn!("    {}", name);
+            }
+        }
+    }
+
+    /// Checks what was unpacked against the package and reads it back from
+    /// disk, as the options say to, giving the mismatches of the first and
+    /// the number of the second.
+    fn verify_unpacked(
+        &self,
+        slpk_archive: &mut ZipArchive<PackageReader<R>>,
+        total: &mut WorkerSummary,
+    ) -> Result<(Option<Vec<verify::Mismatch>>, usize), Error> {
+        let options = self.options;
+        let dry_run = options.output.dry_run;
+        let json_formatting = options.content.json_formatting;
+        total
+            .unpacked_files
+            .sort_by(|a, b| a.entry_name.cmp(&b.entry_name));
+        let mismatches = if options.integrity.verify_after {
+            let mismatches = verify::verify(slpk_archive, &total.unpacked_files)?;
+            if mismatches.is_empty() {
+                info!(
+                    "Verified: all {} unpacked files match the package",
+                    total.unpacked_files.len()
+                );
+            } else {
+                warn!(
+                    "Verification failed: {} of {} unpacked files don't match the package:",
+                    mismatches.len(),
+                    total.unpacked_files.len()
+                );
+                for mismatch in &mismatches {
+                    warn!("    {}: {}", mismatch.entry_name, mismatch.problem);
+                }
+            }
+            Some(mismatches)
+        } else {
+            None
+        };
+        let output_mismatches = if options.integrity.verify_output && !dry_run {
+            let mismatches = verify::verify_output(
+                slpk_archive,
+                &total.unpacked_files,
+                json_formatting == JsonFormatting::AsIs,
+                options.run.threads.unwrap_or_else(num_cpus::get),
+            )?;
+            if mismatches.is_empty() {
+                info!(
+                    "Verified: all {} unpacked files read back as they were unpacked",
+                    total.unpacked_files.len()
+                );
+            } else {
+                warn!(
+                "Verification failed: {} of {} unpacked files don't read back as they were unpacked:",
+                mismatches.len(),
+                total.unpacked_files.len()
+            );
+                for mismatch in &mismatches {
+                    warn!("    {}: {}", mismatch.entry_name, mismatch.problem);
+                }
+            }
+            mismatches.len()
+        } else {
+            0
+        };
+
+        Ok((mismatches, output_mismatches))
+    }
+
+    /// Writes the manifests, index page and trace the options ask for, and
+    /// logs the memory and time the unpack took.
+    fn write_reports(
+        &self,
+        slpk_archive: &mut ZipArchive<PackageReader<R>>,
+        selection: &EntrySelection,
+        output: &OutputPlan,
+        node_index: Option<&manifest::NodeIndex>,
+        total: &mut WorkerSummary,
+        mismatches: Option<&[verify::Mismatch]>,
+    ) -> Result<(), Error> {
+        let options = self.options;
+        let (package, package_name) = (self.package, &self.package_name);
+        let (unpack_folder, summary_folder) = (&output.unpack_folder, &output.summary_folder);
+        let selected_layer = &selection.selected_layer;
+        let renamed_entries = &selection.renamed_entries;
+        let trace_start = self.trace_start;
+        if let Some(node_index) = &node_index {
+            let manifest_path = unpack_folder.join(manifest::MANIFEST_FILE);
+            let manifest = manifest::to_json(
+                node_index,
+                &total.unpacked_files,
+                renamed_entries,
+                mismatches,
+                unpack_folder,
+            );
+            std::fs::write(&manifest_path, manifest.to_pretty_string(2) + "\n")?;
+            info!("Manifest written to {}", manifest_path.to_string_lossy());
+        }
+
+        if options.reports.checksum_manifest {
+            let digests: Vec<_> = total
+                .unpacked_files
+                .iter()
+                .filter_map(|unpacked| Some((unpacked.file.path.clone(), unpacked.file.sha256?)))
+                .collect();
+            let manifest_path = checksums::write_manifest(unpack_folder, &digests)?;
+            info!("Checksums written to {}", manifest_path.to_string_lossy());
+        }
+
+        if options.reports.emit_index && !output.leaves_folder {
+            let first_layer;
+            let layer = match &selected_layer {
+                Some(layer) => layer,
+                None => {
+                    first_layer = i3s::read_scene_layer(slpk_archive)?;
+                    &first_layer
+                }
+            };
+            let title = Path::new(&package_name)
+                .file_name()
+                .or_else(|| summary_folder.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let documents = html_index::documents_in(unpack_folder)?;
+            let index = html_index::PackageIndex::gather(
+                slpk_archive,
+                &mut (self.make_reader)()?,
+                &title,
+                layer,
+                documents,
+            )?;
+            let page_path = html_index::write_index(unpack_folder, &index)?;
+            info!("Index written to {}", page_path.to_string_lossy());
+        }
+
+        if let Some(budget) = &options.run.memory_budget {
+            info!(
+                "At most {} bytes of the {} byte memory budget were in use",
+                budget.peak(),
+                budget.limit()
+            );
+        }
+
+        if options.reports.show_timings {
+            info!("Time spent in each stage, summed over the worker threads:");
+            info!("{}", total.timings.breakdown().trim_end());
+        }
+
+        if let (Some(trace_path), Some(trace_start)) = (&options.reports.trace_path, trace_start) {
+            let mut event = TraceEvent::span(trace_start, trace_start, "unpack", "unpack", 0);
+            match package {
+                Package::File(slpk_file_path) => {
+                    event = event.arg(
+                        "package",
+                        Value::from(slpk_file_path.to_string_lossy().into_owned()),
+                    )
+                }
+                Package::Url { url, .. } => event = event.arg("package", Value::from(url)),
+                Package::Reader { .. } => {}
+            }
+            total
+                .trace_events
+                .push(event.arg("entries", Value::from(total.entries_unpacked)));
+            std::fs::write(
+                trace_path,
+                trace::to_json(&total.trace_events).to_pretty_string(2) + "\n",
+            )?;
+            info!("Trace written to {}", trace_path.to_string_lossy());
+        }
+
+        Ok(())
+    }
+
+    /// Writes the record of the entries unpacked into the output folder,
+    /// and deletes the files of those an incremental run found gone,
+    /// giving the number of files deleted.
+    fn record_entries(
+        &self,
+        selection: &EntrySelection,
+        output: &OutputPlan,
+        total: &WorkerSummary,
+    ) -> Result<usize, Error> {
+        let central_directory = &selection.central_directory;
+        let existing_folder = output.existing_folder;
+        let unpack_folder = &output.unpack_folder;
+        let route_roots = &output.route_roots;
+        let output_options = &output.output_options;
+        let mut files_deleted = 0;
+        if !output.leaves_folder {
+            // The records of an earlier run are kept for the entries this one
+            // didn't unpack, as long as their files were kept too, and were
+            // written as this run would write them.
+            let mut records: HashMap<String, EntryRecord> = HashMap::new();
+            if existing_folder == ExistingFolder::Merge || existing_folder == ExistingFolder::Reuse
+            {
+                // A record which can't be read has been warned about already.
+                if let Ok(Some(earlier)) = incremental::read_record(unpack_folder, route_roots)
+                    .map(|record| record.filter(|record| record.options == *output_options))
+                {
+                    records.extend(
+                        earlier
+                            .entries
+                            .into_iter()
+                            .map(|record| (record.name.clone(), record)),
+                    );
+                }
+            }
+            let mut written = HashSet::new();
+            for unpacked in &total.unpacked_files {
+                let entry = match central_directory.get(unpacked.entry_index) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let record = EntryRecord::new(
+                    unpack_folder,
+                    &unpacked.entry_name,
+                    (entry.crc32, entry.size),
+                    &unpacked.file.path,
+                );
+                written.insert(record.path.clone());
+                records.insert(record.name.clone(), record);
+            }
+            for record in &output.removed {
+                records.remove(&record.name);
+            }
+            files_deleted = incremental::delete_removed(
+                unpack_folder,
+                route_roots,
+                output.removed.iter().chain(&output.superseded),
+                &written,
+            )?;
+            if files_deleted > 0 {
+                info!(
+                "{} files the last run unpacked, which this one didn't write again, were deleted",
+                files_deleted
+            );
+            }
+            incremental::write_record(
+                unpack_folder,
+                output_options.clone(),
+                records.into_values(),
+            )?;
+        }
+
+        Ok(files_deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use std::sync::Arc;
+
+    /// The folder a package is unpacked into, made ready as an unpack makes
+    /// it.
+    fn get_unpack_folder(
+        slpk_file_path: PathBuf,
+        output_dir: Option<&Path>,
+        existing: ExistingFolder,
+        dry_run: bool,
+    ) -> Result<PathBuf, Error> {
+        let unpack_folder = unpack_folder_path(slpk_file_path, output_dir, dry_run)?;
+        prepare_unpack_folder(unpack_folder, existing, dry_run)
+    }
+
+    #[test]
+    fn unpack_folders_go_in_the_output_dir() {
+        let dir = TempDir::new("output-dir");
+        let package = dir.join("share").join("city.slpk");
+        let next_to_package =
+            get_unpack_folder(package.clone(), None, ExistingFolder::Replace, false);
+        let output_dir = dir.join("ssd/extract");
+        let in_output_dir = get_unpack_folder(
+            package.clone(),
+            Some(&output_dir),
+            ExistingFolder::Replace,
+            false,
+        );
+        std::fs::write(output_dir.join("blocked"), b"keep me").unwrap();
+        let blocked = get_unpack_folder(
+            dir.join("blocked.slpk"),
+            Some(&output_dir),
+            ExistingFolder::Replace,
+            false,
+        );
+        let kept = std::fs::read(output_dir.join("blocked")).unwrap();
+
+        // The folder of the package doesn't exist, so unpacking next to it
+        // fails, where the output folder is created.
+        assert!(next_to_package.is_err());
+        assert_eq!(in_output_dir.unwrap(), output_dir.join("city"));
+        assert!(blocked.is_err());
+        assert_eq!(kept, b"keep me");
+    }
+
+    fn folder_exists(result: &Result<PathBuf, Error>) -> bool {
+        matches!(
+            result.as_ref().map_err(|e| e.downcast_ref::<UnpackError>()),
+            Err(Some(UnpackError::OutputFolderExists(..)))
+        )
+    }
+
+    #[test]
+    fn existing_output_folders_follow_the_overwrite_mode() {
+        let dir = TempDir::new("overwrite");
+        let package = SyntheticPackage::new()
+            .entry("3dSceneLayer.json", b"{}")
+            .entry("nodes/0/geometries/0.bin", b"new");
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let folder = dir.join("package");
+        let unpack_with =
+            |overwrite| unpack_with_options(&path, &UnpackOptions::new().overwrite(overwrite));
+
+        // A file in the way is never replaced.
+        std::fs::write(&folder, b"not a folder").unwrap();
+        for mode in [
+            OverwriteMode::Error,
+            OverwriteMode::Overwrite,
+            OverwriteMode::Merge,
+        ] {
+            assert!(unpack_with(mode).is_err());
+            assert_eq!(std::fs::read(&folder).unwrap(), b"not a folder");
+        }
+        std::fs::remove_file(&folder).unwrap();
+
+        let fill = || {
+            std::fs::create_dir_all(folder.join("nodes/0/geometries")).unwrap();
+            std::fs::write(folder.join("nodes/0/geometries/0.bin"), b"old").unwrap();
+            std::fs::write(folder.join("notes.txt"), b"mine").unwrap();
+        };
+        let geometry = || std::fs::read(folder.join("nodes/0/geometries/0.bin")).unwrap();
+        fill();
+        let refused = unpack_with(OverwriteMode::Error).unwrap_err();
+        assert!(matches!(
+            refused.downcast_ref::<UnpackError>(),
+            Some(UnpackError::OutputFolderExists(..))
+        ));
+        assert_eq!(geometry(), b"old");
+        assert!(folder.join("notes.txt").exists());
+
+        unpack_with(OverwriteMode::Merge).unwrap();
+        assert_eq!(geometry(), b"new");
+        assert_eq!(std::fs::read(folder.join("notes.txt")).unwrap(), b"mine");
+
+        fill();
+        unpack_with(OverwriteMode::Overwrite).unwrap();
+        assert_eq!(geometry(), b"new");
+        let notes_kept = folder.join("notes.txt").exists();
+        assert!(!notes_kept);
+    }
+
+    #[test]
+    fn folders_created_concurrently_are_not_shared_unless_merging() {
+        let dir = TempDir::new("concurrent");
+        let race = |name: &str, existing: ExistingFolder| {
+            let package = dir.join(name);
+            let barrier = Arc::new(std::sync::Barrier::new(8));
+            let threads: Vec<_> = (0..8)
+                .map(|_| {
+                    let package = package.clone();
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        barrier.wait();
+                        get_unpack_folder(package, None, existing, false)
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Only one of the unpacks racing to create the folder gets it, and
+        // the others are told it exists.
+        let refused = race("refused.slpk", ExistingFolder::Refuse);
+        assert_eq!(refused.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(refused.iter().all(|r| r.is_ok() || folder_exists(r)));
+        let merged = race("merged.slpk", ExistingFolder::Merge);
+        assert!(merged.iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn entries_excluded_by_path_are_not_shared_out() {
+        let dir = TempDir::new("path-filters");
+        let mut package = SyntheticPackage::new().entry("3dSceneLayer.json.gz", b"{}");
+        for i in 0..30 {
+            package = package.entry(&format!("nodes/{}/textures/0.jpg", i), b"jpeg");
+        }
+        for i in 0..6 {
+            package = package.entry(&format!("nodes/{}/3dNodeIndexDocument.json.gz", i), b"{}");
+        }
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let totals = Arc::new(std::sync::Mutex::new(HashSet::new()));
+        let reported = totals.clone();
+        let options = UnpackOptions::new()
+            .threads(4)
+            .filters(EntryFilters {
+                include: vec![PathPattern::new("*.json.gz")],
+                exclude: vec![PathPattern::new("nodes/5/**")],
+                ..EntryFilters::default()
+            })
+            .progress(Progress::new(move |_, total, _| {
+                reported.lock().unwrap().insert(total);
+            }));
+        let summary = unpack_with_options(&path, &options).unwrap();
+        let folder = dir.join("package");
+        let texture_written = folder.join("nodes/0/textures/0.jpg").exists();
+        let excluded_written = folder.join("nodes/5/3dNodeIndexDocument.json").exists();
+        let kept_written = folder.join("nodes/4/3dNodeIndexDocument.json").exists();
+
+        assert_eq!(summary.entries_unpacked, 6);
+        assert!(!texture_written && !excluded_written && kept_written);
+        // The workers only ever see the entries which passed the patterns.
+        assert_eq!(*totals.lock().unwrap(), HashSet::from([6]));
+    }
+
+    #[test]
+    fn built_options_unpack_and_summarise() {
+        let dir = TempDir::new("builder");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", b"{\"id\":0}")
+            .entry("nodes/0/geometries/0.bin", &[0; 300])
+            .entry("nodes/0/textures/0.jpg", b"jpeg")
+            .write_to_file(&path)
+            .unwrap();
+        let options = UnpackOptions::new()
+            .threads(2)
+            .output_dir(dir.join("out"))
+            .filters(EntryFilters {
+                exclude: vec![PathPattern::new("*.jpg")],
+                ..EntryFilters::default()
+            });
+        assert_eq!(options.run.threads, Some(2));
+        let summary = unpack_with_options(&path, &options).unwrap();
+        let unpacked = dir.join("out/package/nodes/0/geometries/0.bin").exists();
+
+        assert!(unpacked);
+        assert_eq!(summary.entries_unpacked, 2);
+        assert_eq!(summary.entries_skipped, 1);
+        assert_eq!(summary.bytes_written, 308);
+        assert_eq!(summary.unpack_folder, dir.join("out/package"));
+        assert_eq!(summary.to_string(), "2 files unpacked");
+        assert!(summary.dry_run.is_none());
+        let json = summary.to_json();
+        assert_eq!(json.get("entriesUnpacked").and_then(Value::as_u64), Some(2));
+        assert_eq!(json.get("bytesWritten").and_then(Value::as_u64), Some(308));
+        assert!(json.get("dryRun").is_none());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn the_old_unpack_still_unpacks_next_to_the_package() {
+        let dir = TempDir::new("old-unpack");
+        let path = dir.join("package.slpk");
+        let package = SyntheticPackage::standard();
+        package.write_to_file(&path).unwrap();
+
+        unpack(&path, true).unwrap();
+        for (file, contents) in package.expected_files(false) {
+            assert_eq!(
+                std::fs::read(dir.join("package").join(file)).unwrap(),
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn files_are_written_whole_whatever_the_write_buffer() {
+        let dir = TempDir::new("write-buffer");
+        let path = dir.join("package.slpk");
+        let document: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        SyntheticPackage::new()
+            .entry("nodes/0/geometries/0.bin.gz", &document)
+            .entry("nodes/0/geometries/1.bin", &document)
+            .write_to_file(&path)
+            .unwrap();
+        let mut written = Vec::new();
+        for size in [7, DEFAULT_WRITE_BUFFER_SIZE] {
+            let out = dir.join(format!("out-{}", size));
+            unpack_with_options(
+                &path,
+                &UnpackOptions::new()
+                    .output_dir(out.clone())
+                    .write_buffer_size(size),
+            )
+            .unwrap();
+            for name in ["0.bin", "1.bin"] {
+                written.push(
+                    std::fs::read(out.join("package/nodes/0/geometries").join(name)).unwrap(),
+                );
+            }
+        }
+
+        assert!(written.iter().all(|file| *file == document));
+    }
+
+    #[test]
+    fn duplicate_textures_are_linked_and_summarised() {
+        let dir = TempDir::new("unpack-dedup");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("nodes/0/textures/0.jpg", &[1; 1000])
+            .entry("nodes/1/textures/0.jpg", &[1; 1000])
+            .entry("nodes/2/textures/0.jpg", &[2; 1000])
+            .write_to_file(&path)
+            .unwrap();
+        let summary = unpack_with_options(
+            &path,
+            &UnpackOptions::new()
+                .output_dir(dir.join("out"))
+                .threads(2)
+                .dedup(DedupMode::Hardlink),
+        )
+        .unwrap();
+        let duplicate = std::fs::read(dir.join("out/package/nodes/1/textures/0.jpg")).unwrap();
+
+        assert_eq!(duplicate, [1; 1000]);
+        assert_eq!(
+            summary.dedup,
+            Some(DedupStats {
+                duplicates: 1,
+                bytes_saved: 1000,
+                link_failures: 0,
+            })
+        );
+        let json = summary.to_json();
+        let dedup = json.get("dedup").unwrap();
+        assert_eq!(dedup.get("bytesSaved").and_then(Value::as_u64), Some(1000));
+    }
+
+    #[test]
+    fn entries_colliding_without_case_fail_unless_renamed() {
+        let dir = TempDir::new("unpack-case");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("nodes/0/textures/0.jpg", b"lower")
+            .entry("nodes/0/textures/0.JPG", b"upper")
+            .entry("nodes/0/3dNodeIndexDocument.json", b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        let out = dir.join("out");
+        let error =
+            unpack_with_options(&path, &UnpackOptions::new().output_dir(out.clone())).unwrap_err();
+        let unpacked_anything = out.exists();
+        unpack_with_options(
+            &path,
+            &UnpackOptions::new()
+                .output_dir(out.clone())
+                .rename_collisions(true),
+        )
+        .unwrap();
+        let textures = out.join("package/nodes/0/textures");
+        let renamed = std::fs::read(textures.join("0~1.jpg")).unwrap();
+        let kept = std::fs::read(textures.join("0.JPG")).unwrap();
+
+        assert_eq!(errors::error_kind(&error), ErrorKind::InvalidArchive);
+        assert!(error
+            .to_string()
+            .ends_with("nodes/0/textures/0.JPG and nodes/0/textures/0.jpg"));
+        assert!(!unpacked_anything);
+        // Upper case comes first in name order.
+        assert_eq!((kept, renamed), (b"upper".to_vec(), b"lower".to_vec()));
+    }
+
+    #[test]
+    fn incremental_runs_only_unpack_what_changed() {
+        let dir = TempDir::new("unpack-incremental");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json", br#"{"id":0}"#)
+            .entry("nodes/0/textures/0.jpg", b"jpeg")
+            .entry("nodes/1/textures/0.jpg", b"old")
+            .entry("nodes/2/geometries/0.bin", &[0; 8])
+            .write_to_file(&path)
+            .unwrap();
+        let out = dir.join("out");
+        let options = UnpackOptions::new().output_dir(out.clone());
+        unpack_with_options(&path, &options).unwrap();
+
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json", br#"{"id":0}"#)
+            .entry("nodes/0/textures/0.jpg", b"jpeg")
+            .entry("nodes/1/textures/0.jpg", b"new")
+            .entry("metadata.json", b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        let options = options.incremental(true);
+        let updated = unpack_with_options(&path, &options).unwrap();
+        let texture = std::fs::read(out.join("package/nodes/1/textures/0.jpg")).unwrap();
+        let node_left = out.join("package/nodes/2").exists();
+
+        // Without a record to go by, everything is unpacked.
+        std::fs::write(out.join("package").join(incremental::RECORD_FILE), b"[").unwrap();
+        let fell_back = unpack_with_options(&path, &options).unwrap();
+        let unchanged = unpack_with_options(&path, &options).unwrap();
+
+        assert_eq!(updated.entries_unpacked, 2);
+        assert_eq!(
+            updated.incremental,
+            Some(IncrementalStats {
+                entries_unchanged: 2,
+                files_deleted: 1,
+            })
+        );
+        assert_eq!((texture.as_slice(), node_left), (&b"new"[..], false));
+        assert_eq!(fell_back.entries_unpacked, 4);
+        assert_eq!(unchanged.entries_unpacked, 0);
+        assert_eq!(unchanged.incremental.unwrap().entries_unchanged, 4);
+    }
+
+    #[test]
+    fn a_folder_every_entry_is_in_is_stripped() {
+        let dir = TempDir::new("unpack-prefix");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("MyLayer/3dSceneLayer.json.gz", br#"{"id":0}"#)
+            .entry("MyLayer/nodes/0/textures/0.jpg", b"jpeg")
+            .write_to_file(&path)
+            .unwrap();
+        let out = dir.join("out");
+        unpack_with_options(
+            &path,
+            &UnpackOptions::new()
+                .output_dir(out.clone())
+                .strip_prefix(StripPrefix::Auto),
+        )
+        .unwrap();
+        let stripped = std::fs::read(out.join("package/nodes/0/textures/0.jpg")).unwrap();
+
+        // An entry outside the folder is left out, or with keep_unprefixed
+        // unpacked as it is.
+        SyntheticPackage::new()
+            .entry("MyLayer/3dSceneLayer.json.gz", br#"{"id":0}"#)
+            .entry("readme.txt", b"zipped by hand")
+            .write_to_file(&path)
+            .unwrap();
+        let prefix = "MyLayer".parse::<StripPrefix>().unwrap();
+        let options = UnpackOptions::new()
+            .output_dir(out.clone())
+            .strip_prefix(prefix);
+        let left_out = unpack_with_options(&path, &options).unwrap();
+        let kept = unpack_with_options(&path, &options.clone().keep_unprefixed(true)).unwrap();
+        let readme = out.join("package/readme.txt").exists();
+
+        assert_eq!(stripped, b"jpeg");
+        assert_eq!(
+            (left_out.entries_unpacked, left_out.entries_skipped),
+            (1, 1)
+        );
+        assert_eq!(kept.entries_unpacked, 2);
+        assert!(readme);
+    }
+
+    #[test]
+    fn entry_errors_name_the_entry_and_its_file() {
+        let dir = TempDir::new("entry-error");
+        let path = dir.join("package.slpk");
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer
+            .start_file("nodes/7/features/0.json.gz", options)
+            .unwrap();
+        // A gzip header cut short after its magic number.
+        writer.write_all(&[0x1f, 0x8b, 8]).unwrap();
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+
+        // Malformed gzip members are otherwise copied as they are.
+        let error =
+            unpack_with_options(&path, &UnpackOptions::new().strict_content(true)).unwrap_err();
+        let entry_error = error.downcast_ref::<EntryError>().unwrap();
+        assert_eq!(entry_error.entry, "nodes/7/features/0.json.gz");
+        assert_eq!(
+            entry_error.target,
+            dir.join("package").join("nodes/7/features/0.json")
+        );
+        assert!(error.to_string().contains("nodes/7/features/0.json.gz"));
+    }
+
+    #[test]
+    fn entries_are_decompressed_by_their_content() {
+        let dir = TempDir::new("sniffed");
+        let path = dir.join("package.slpk");
+        let gzip = |contents: &[u8]| {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(contents).unwrap();
+            encoder.finish().unwrap()
+        };
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in &[
+            ("3dSceneLayer.json.gz", gzip(b"{}")),
+            ("nodes/0/3dNodeIndexDocument.json", gzip(b"{\"id\":\"0\"}")),
+            (
+                "nodes/0/features/0.json.gz",
+                b"{\"featureData\":[]}".to_vec(),
+            ),
+        ] {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+
+        let summary = unpack_with_options(&path, &UnpackOptions::default()).unwrap();
+        let unpacked = dir.join("package");
+        let document = std::fs::read(unpacked.join("nodes/0/3dNodeIndexDocument.json")).unwrap();
+        let features = std::fs::read(unpacked.join("nodes/0/features/0.json")).unwrap();
+        let by_name =
+            unpack_with_options(&path, &UnpackOptions::new().trust_extensions(true)).unwrap();
+        let copied = std::fs::read(unpacked.join("nodes/0/features/0.json.gz")).unwrap();
+
+        assert_eq!(summary.entries_unpacked, 3);
+        assert!(summary.degraded_entries.is_empty());
+        assert_eq!(document, b"{\"id\":\"0\"}");
+        assert_eq!(features, b"{\"featureData\":[]}");
+        // Trusted, the name leads to a gzip member which isn't one.
+        assert_eq!(by_name.degraded_entries, ["nodes/0/features/0.json.gz"]);
+        assert_eq!(copied, b"{\"featureData\":[]}");
+    }
+
+    #[test]
+    fn uneven_entries_are_each_unpacked_once() {
+        let dir = TempDir::new("uneven");
+        // Most of the bytes are in the first few entries, as they would all
+        // have been in the range of the first worker.
+        let mut package = SyntheticPackage::new();
+        let mut names = Vec::new();
+        for i in 0..100 {
+            let name = format!("nodes/{}/textures/0.bin", i);
+            let size = if i < 5 { 1 << 20 } else { 100 };
+            package = package.entry(&name, &vec![i as u8; size]);
+            names.push(name);
+        }
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let trace_path = dir.join("trace.json");
+        let options = UnpackOptions::new()
+            .threads(4)
+            .trace_path(trace_path.clone());
+        let summary = unpack_with_options(&path, &options).unwrap();
+        let trace = crate::json::parse(&std::fs::read_to_string(&trace_path).unwrap()).unwrap();
+        let large = std::fs::read(dir.join("package/nodes/4/textures/0.bin")).unwrap();
+
+        assert_eq!(summary.entries_unpacked, 100);
+        assert_eq!(large, vec![4; 1 << 20]);
+        let mut unpacked: Vec<&str> = trace
+            .get("traceEvents")
+            .and_then(Value::as_array)
+            .unwrap()
+            .iter()
+            .filter(|event| event.get("cat").and_then(Value::as_str) == Some("entry"))
+            .filter_map(|event| event.get("name").and_then(Value::as_str))
+            .collect();
+        unpacked.sort();
+        names.sort();
+        assert_eq!(unpacked, names);
+    }
+
+    #[test]
+    fn one_thread_unpacks_on_the_calling_thread() {
+        let dir = TempDir::new("serial");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::standard().write_to_file(&path).unwrap();
+        let mut unpacked = Vec::new();
+        for threads in [0, 1] {
+            let reported_from = Arc::new(std::sync::Mutex::new(HashSet::new()));
+            let collected = reported_from.clone();
+            let options = UnpackOptions::new()
+                .threads(threads)
+                .progress(Progress::new(move |_, _, _| {
+                    collected
+                        .lock()
+                        .unwrap()
+                        .insert(std::thread::current().id());
+                }));
+            unpacked.push(
+                unpack_with_options(&path, &options)
+                    .unwrap()
+                    .entries_unpacked,
+            );
+            let reported_from = reported_from.lock().unwrap();
+            assert_eq!(*reported_from, HashSet::from([std::thread::current().id()]));
+        }
+
+        assert!(unpacked[0] > 0);
+        assert_eq!(unpacked[0], unpacked[1]);
+    }
+
+    #[test]
+    fn a_panicking_worker_fails_the_unpack_once_every_worker_stopped() {
+        use std::sync::atomic::AtomicUsize;
+
+        let dir = TempDir::new("worker-panic");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::standard().write_to_file(&path).unwrap();
+        let mut outcomes = Vec::new();
+        for threads in [1, 4] {
+            let reports = Arc::new(AtomicUsize::new(0));
+            let counted = reports.clone();
+            let options = UnpackOptions::new()
+                .threads(threads)
+                .progress(Progress::new(move |done, _, _| {
+                    counted.fetch_add(1, Ordering::SeqCst);
+                    if done == 2 {
+                        panic!("entry handler failed");
+                    }
+                }));
+            let error = unpack_with_options(&path, &options).unwrap_err();
+            let reported = reports.load(Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            outcomes.push((
+                error.to_string(),
+                error_kind(&error),
+                reports.load(Ordering::SeqCst) == reported,
+            ));
+        }
+
+        for (message, kind, stopped) in outcomes {
+            assert_eq!(message, "A worker thread panicked: entry handler failed");
+            assert_eq!(kind, ErrorKind::Other);
+            assert!(stopped);
+        }
+    }
+
+    #[test]
+    fn packages_in_memory_unpack_from_readers() {
+        let dir = TempDir::new("from-reader");
+        let package = SyntheticPackage::standard();
+        let bytes = package.to_bytes().unwrap();
+        let readers = std::sync::atomic::AtomicUsize::new(0);
+        let unpack_folder = dir.join("layers/package");
+        let options = UnpackOptions::new().threads(4).verify_after(true);
+        let summary = unpack_from_reader(
+            || {
+                readers.fetch_add(1, Ordering::SeqCst);
+                Ok(std::io::Cursor::new(&bytes[..]))
+            },
+            &unpack_folder,
+            &options,
+        )
+        .unwrap();
+        let mut files = Vec::new();
+        crate::glob::walk(&unpack_folder, None, &mut files).unwrap();
+        files.retain(|file| !file.ends_with(incremental::RECORD_FILE));
+        let mut unpacked: Vec<(PathBuf, Vec<u8>)> = files
+            .iter()
+            .map(|file| {
+                (
+                    file.strip_prefix(&unpack_folder).unwrap().to_path_buf(),
+                    std::fs::read(file).unwrap(),
+                )
+            })
+            .collect();
+        unpacked.sort();
+
+        assert_eq!(unpacked, package.expected_files(false));
+        assert_eq!(summary.entries_unpacked, unpacked.len());
+        // One for the unpack as a whole, and one for each worker which was
+        // handed an entry.
+        assert!((2..=5).contains(&readers.load(Ordering::SeqCst)));
+    }
+
+    #[test]
+    fn streamed_packages_unpack_into_the_folder_named_for_them() {
+        let dir = TempDir::new("from-stream");
+        let package = SyntheticPackage::standard();
+        let bytes = package.to_bytes().unwrap();
+        let no_output_dir = unpack_stream(&bytes[..], Path::new("layer"), &UnpackOptions::new());
+        let options = UnpackOptions::new()
+            .threads(4)
+            .output_dir(dir.to_path_buf());
+        let nested = unpack_stream(&bytes[..], Path::new("a/layer"), &options);
+        let summary = unpack_stream(&bytes[..], Path::new("layer"), &options).unwrap();
+        let layer_document = std::fs::read(dir.join("layer/3dSceneLayer.json"));
+        let left = std::fs::read_dir(&dir).unwrap().count();
+
+        assert!(no_output_dir.is_err());
+        assert!(nested.is_err());
+        assert_eq!(
+            summary.entries_unpacked,
+            package.expected_files(false).len()
+        );
+        assert!(layer_document.is_ok());
+        assert_eq!(left, 1);
+    }
+
+    #[test]
+    fn keeping_going_lists_the_entries_which_failed() {
+        let dir = TempDir::new("keep-going");
+        let path = dir.join("package.slpk");
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for i in 0..20 {
+            writer
+                .start_file(format!("nodes/{}/textures/0.jpg.gz", i), options)
+                .unwrap();
+            if i % 7 == 3 {
+                // A gzip header cut short after its magic number.
+                writer.write_all(&[0x1f, 0x8b, 8]).unwrap();
+            } else {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&[i; 10]).unwrap();
+                writer.write_all(&encoder.finish().unwrap()).unwrap();
+            }
+        }
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+
+        // Malformed gzip members are otherwise copied as they are.
+        let options = UnpackOptions::new()
+            .threads(4)
+            .overwrite(OverwriteMode::Overwrite)
+            .strict_content(true);
+        let failed_fast = unpack_with_options(&path, &options).is_err();
+        let summary = unpack_with_options(&path, &options.keep_going(true)).unwrap();
+        let unpacked = std::fs::read(dir.join("package/nodes/19/textures/0.jpg")).unwrap();
+
+        assert!(failed_fast);
+        assert_eq!(summary.entries_unpacked, 17);
+        assert_eq!(unpacked, [19; 10]);
+        let failed: Vec<&str> = summary
+            .failed_entries
+            .iter()
+            .map(|failure| failure.entry.as_str())
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "nodes/10/textures/0.jpg.gz",
+                "nodes/17/textures/0.jpg.gz",
+                "nodes/3/textures/0.jpg.gz"
+            ]
+        );
+    }
+
+    #[test]
+    fn kept_gzip_entries_are_copied_as_stored() {
+        let dir = TempDir::new("keep-gzip");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", &[b' '; 5000])
+            .entry("nodes/0/geometries/0.bin", &[0; 300])
+            .write_to_file(&path)
+            .unwrap();
+        let mut archive = crate::archive::open_slpk_archive(&path).unwrap();
+        let mut gzipped = Vec::new();
+        archive
+            .by_name("3dSceneLayer.json.gz")
+            .unwrap()
+            .read_to_end(&mut gzipped)
+            .unwrap();
+        let options = UnpackOptions::new()
+            .output_dir(dir.join("out"))
+            .keep_gzip(true)
+            .verify_after(true);
+        let planned = unpack_with_options(&path, &options.clone().dry_run(true)).unwrap();
+        let summary = unpack_with_options(&path, &options).unwrap();
+        let written = std::fs::read(dir.join("out/package/3dSceneLayer.json.gz")).unwrap();
+        let decompressed = dir.join("out/package/3dSceneLayer.json").exists();
+
+        assert_eq!(written, gzipped);
+        assert!(!decompressed);
+        assert_eq!(summary.bytes_written, gzipped.len() as u64 + 300);
+        let planned = planned.dry_run.unwrap();
+        assert_eq!(
+            (planned.bytes, planned.gzipped_files),
+            (summary.bytes_written, 0)
+        );
+    }
+
+    #[test]
+    fn files_take_the_modification_time_of_their_entry() {
+        let dir = TempDir::new("entry-mtime");
+        let path = dir.join("package.slpk");
+        let march = zip::DateTime::from_date_and_time(2019, 3, 1, 12, 30, 10).unwrap();
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gzipped.write_all(b"{}").unwrap();
+        let entries: [(&str, Vec<u8>, zip::DateTime); 3] = [
+            ("3dSceneLayer.json.gz", gzipped.finish().unwrap(), march),
+            ("nodes/0/geometries/0.bin", vec![0; 10], march),
+            (
+                "nodes/0/textures/0.jpg",
+                b"jpeg".to_vec(),
+                zip::DateTime::default(),
+            ),
+        ];
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, contents, modified) in &entries {
+            let options = zip::write::FileOptions::default().last_modified_time(*modified);
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+        let before = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+        unpack_with_options(&path, &UnpackOptions::new().threads(1)).unwrap();
+        let modified = |name: &str| {
+            std::fs::metadata(dir.join("package").join(name))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        let times = [
+            modified("3dSceneLayer.json"),
+            modified("nodes/0/geometries/0.bin"),
+            modified("nodes/0/textures/0.jpg"),
+        ];
+
+        let march = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_551_443_410);
+        assert_eq!(times[..2], [march, march]);
+        // The start of the zip epoch means no time was recorded.
+        assert!(times[2] > before);
+    }
+
+    #[test]
+    fn entries_never_land_outside_the_output_folder() {
+        use crate::archive::raw::{RawEntry, RawWriter};
+
+        let dir = TempDir::new("zip-slip");
+        let root = dir.join("nested");
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("package.slpk");
+        let names = [
+            "3dSceneLayer.json",
+            "nodes/../../escaped-parent.json",
+            "../../../escaped-up.json",
+            "/tmp/escaped-absolute.json",
+            "C:\\escaped-drive.json",
+            "\\\\server\\share\\escaped-unc.json",
+        ];
+        let mut writer = RawWriter::new(std::io::Cursor::new(Vec::new()));
+        for name in &names {
+            let mut entry = RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            let data = entry.replace_contents(b"{}").unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        std::fs::write(&path, writer.finish(b"").unwrap().into_inner()).unwrap();
+        let summary = unpack_with_options(&path, &UnpackOptions::new().threads(1)).unwrap();
+        let mut files = Vec::new();
+        crate::glob::walk(&dir, None, &mut files).unwrap();
+        let escaped = std::path::Path::new("/tmp/escaped-absolute.json").exists();
+
+        // Every entry was unpacked, and the only file outside the output
+        // folder is the package.
+        assert_eq!(summary.entries_unpacked, names.len());
+        let outside: Vec<_> = files
+            .iter()
+            .filter(|file| !file.starts_with(root.join("package")))
+            .collect();
+        assert_eq!(outside, [&path]);
+        assert!(!escaped);
+
+        // Paths which routing builds are checked too.
+        let folder = Path::new("out");
+        assert!(path_in_folder(folder, Path::new("nodes/0/0.bin")).is_ok());
+        for entry in ["../escaped", "nodes/../../escaped", "/escaped", "", "."] {
+            assert!(
+                path_in_folder(folder, Path::new(entry)).is_err(),
+                "{}",
+                entry
+            );
+        }
+        #[cfg(windows)]
+        assert!(path_in_folder(folder, Path::new("C:\\escaped")).is_err());
+    }
+
+    #[test]
+    fn directory_entries_create_their_folder() {
+        let dir = TempDir::new("directories");
+        let path = dir.join("package.slpk");
+        // nodes/1 is only implied by the file below it, and statistics holds
+        // nothing.
+        SyntheticPackage::new()
+            .entry("nodes/", b"")
+            .entry("nodes/0/", b"")
+            .entry("nodes/0/geometries/0.bin", &[0; 10])
+            .entry("nodes/1/features/0.json", b"{}")
+            .entry("statistics\\", b"")
+            .write_to_file(&path)
+            .unwrap();
+        let planned = unpack_with_options(&path, &UnpackOptions::new().dry_run(true))
+            .unwrap()
+            .dry_run
+            .unwrap();
+        let summary = unpack_with_options(&path, &UnpackOptions::new()).unwrap();
+        let out = dir.join("package");
+        let is_dir = |name: &str| out.join(name).is_dir();
+        let folders = [
+            is_dir("nodes/0"),
+            is_dir("nodes/0/geometries"),
+            is_dir("nodes/1/features"),
+            is_dir("statistics"),
+        ];
+        let geometry = std::fs::read(out.join("nodes/0/geometries/0.bin")).unwrap();
+
+        assert_eq!(folders, [true; 4]);
+        assert_eq!(geometry, [0; 10]);
+        assert_eq!(
+            (summary.entries_unpacked, summary.directories_created),
+            (2, 3)
+        );
+        assert_eq!((planned.files, planned.folders), (2, 5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_take_the_sanitized_mode_of_their_entry() {
+        use crate::archive::raw::{RawEntry, RawWriter};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("entry-mode");
+        let path = dir.join("package.slpk");
+        let mut writer = RawWriter::new(std::io::Cursor::new(Vec::new()));
+        // Made on Unix, except for the last, which was made on Windows and
+        // has its read-only bit set.
+        let entries = [
+            ("3dSceneLayer.json", 3 << 8, 0o100_644 << 16),
+            ("tools/convert.sh", 3 << 8, 0o104_755 << 16),
+            ("nodes/0/geometries/0.bin", 3 << 8, 0o100_000 << 16),
+            ("tools/open.sh", 3 << 8, 0o104_777 << 16),
+            ("nodes/0/textures/0.jpg", 0, 0x21),
+        ];
+        for (name, version_made_by, external_attributes) in &entries {
+            let mut entry = RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            entry.version_made_by = *version_made_by;
+            entry.external_attributes = *external_attributes;
+            let data = entry.replace_contents(b"{}").unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        std::fs::write(&path, writer.finish(b"").unwrap().into_inner()).unwrap();
+        unpack_with_options(&path, &UnpackOptions::new().threads(1)).unwrap();
+        let mode = |name: &str| {
+            std::fs::metadata(dir.join("package").join(name))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o7777
+        };
+        let modes = [
+            mode("3dSceneLayer.json"),
+            mode("tools/convert.sh"),
+            mode("nodes/0/geometries/0.bin"),
+            mode("tools/open.sh"),
+        ];
+        let default = std::fs::File::create(dir.join("default")).unwrap();
+        let default_mode = default.metadata().unwrap().permissions().mode() & 0o7777;
+        let windows_mode = mode("nodes/0/textures/0.jpg");
+
+        // The setuid bit is dropped, an entry nobody could read is readable
+        // by its owner, and one anybody could write is only writable by its
+        // owner.
+        assert_eq!(modes, [0o644, 0o755, 0o600, 0o755]);
+        assert_eq!(windows_mode, default_mode);
+    }
 }
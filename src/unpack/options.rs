@@ -0,0 +1,428 @@
+// The options of an unpack, grouped by what they decide: which entries are
+// unpacked, where and how their files are written, which of their bytes are
+// changed on the way, how strictly they are checked, what is reported of the
+// run, how it runs, and how an encrypted package is read. Every option has a
+// setter on `UnpackOptions` itself, so that callers build them up without
+// knowing which group each is in.
+
+use super::paranoid;
+use super::{
+    BoundingBox, CancelToken, ClassRoute, ConflictPolicy, DedupMode, EntryFilters, ExtractOrder,
+    JsonFormatting, MemoryBudget, OverwriteMode, Progress, RetryPolicy, StripPrefix, TarTarget,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How to unpack a package. The default unpacks every entry, as it is,
+/// into a folder named after the package next to it, replacing that folder
+/// if it exists.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::unpack::{OverwriteMode, UnpackOptions};
+///
+/// let options = UnpackOptions::new()
+///     .threads(4)
+///     .keep_going(true)
+///     .overwrite(OverwriteMode::Merge);
+/// assert_eq!(options.run.threads, Some(4));
+/// assert_eq!(options.output.overwrite, OverwriteMode::Merge);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UnpackOptions {
+    /// Only unpack entries whose kind, path, size and modification date
+    /// pass these.
+    pub filters: EntryFilters,
+    /// Which entries are unpacked, by the layer and nodes they belong to,
+    /// and by what an earlier run left.
+    pub selection: SelectionOptions,
+    /// Where the files are written, and what happens to those there already.
+    pub output: OutputOptions,
+    /// How the contents of entries are changed as they are written.
+    pub content: ContentOptions,
+    /// How strictly entries are checked, while and after they are unpacked.
+    pub integrity: IntegrityOptions,
+    /// What is written beside the files about the package and the run.
+    pub reports: ReportOptions,
+    /// How the unpack runs: its threads, failures, limits and progress.
+    pub run: RunOptions,
+    /// How an encrypted package is read.
+    pub encryption: EncryptionOptions,
+}
+
+/// Which entries are unpacked, beyond what `EntryFilters` leaves out.
+#[derive(Clone, Debug, Default)]
+pub struct SelectionOptions {
+    /// Only unpack the entries of this layer of a multi-layer package.
+    pub layer: Option<usize>,
+    /// Only unpack the resources of the nodes down to this level below the
+    /// root, which is at level 0, along with every entry which isn't below
+    /// a node.
+    pub max_level: Option<usize>,
+    /// Only unpack the resources of the nodes whose bounding volume meets
+    /// this box and of their ancestors, along with every entry which isn't
+    /// below a node. The box is in the spatial reference of the layer.
+    pub bbox: Option<BoundingBox>,
+    /// Take `bbox` as WGS84 longitude and latitude instead.
+    pub bbox_wgs84: bool,
+    /// Skip the resources of nodes with no vertices, no features, and only
+    /// placeholder textures.
+    pub exclude_empty_nodes: bool,
+    /// Strip this folder from the start of the path of every entry, or the
+    /// one every entry is in with `StripPrefix::Auto`. Entries which aren't
+    /// in it are left out, with a warning.
+    pub strip_prefix: Option<StripPrefix>,
+    /// Unpack entries which aren't in the folder `strip_prefix` strips as
+    /// they are, rather than leaving them out.
+    pub keep_unprefixed: bool,
+    /// Only unpack the entries set aside by an earlier run, into its output
+    /// folder.
+    pub retry_failed: bool,
+    /// Only unpack the entries which the resume file of a timed out run
+    /// lists, into its output folder.
+    pub resume: bool,
+    /// Only unpack the entries which changed since the last unpack into the
+    /// output folder, by the record it left there, and delete the files of
+    /// entries the package no longer has.
+    pub incremental: bool,
+}
+
+/// Where the files of an unpack are written.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    /// Unpack into a folder named after the package in this folder, rather
+    /// than next to the package.
+    pub output_dir: Option<PathBuf>,
+    /// Unpack each sublayer of a Building Scene Layer into its own folder.
+    pub split_sublayers: bool,
+    /// Classes of resource to write below other base folders instead of the
+    /// output folder.
+    pub routes: Vec<ClassRoute>,
+    /// What to do when the output folder already exists. Retrying failed
+    /// entries, resuming, and conflict policies always merge.
+    pub overwrite: OverwriteMode,
+    /// Unpack into the existing output folder, resolving each file which
+    /// already exists with this policy.
+    pub on_file_conflict: Option<ConflictPolicy>,
+    /// Unpack entries whose files collide with those of others when case is
+    /// ignored under names suffixed `~1`, `~2` and so on, rather than
+    /// failing before anything is unpacked.
+    pub rename_collisions: bool,
+    /// Escape the characters of file names which Windows refuses, as it
+    /// always is on Windows.
+    pub portable_names: bool,
+    /// Recreate the symbolic links a package holds, on Unix, when their
+    /// target is inside the folder they are unpacked into, rather than
+    /// leaving them out. `strict_paths` refuses them whatever this is.
+    pub allow_symlinks: bool,
+    /// How texture payloads identical to one already written are written.
+    pub dedup: Option<DedupMode>,
+    /// Deduplicate geometry payloads as well as textures.
+    pub dedup_geometry: bool,
+    /// Write each file under a temporary name, and rename it into place
+    /// once it is complete. Files are always staged when verifying, or when
+    /// unpacking into the output folder of an earlier run or with a time
+    /// limit.
+    pub stage_files: bool,
+    /// Unpack into a temporary folder beside the output folder, which only
+    /// replaces it once every entry has been unpacked.
+    pub atomic: bool,
+    /// Write the files as a tar stream, one entry at a time, instead of
+    /// into the output folder, which is left alone.
+    pub tar: Option<TarTarget>,
+    /// Work out what would be written, and how large it would be, without
+    /// creating or deleting anything.
+    pub dry_run: bool,
+    /// The size of the buffer each file is written through, or
+    /// `DEFAULT_WRITE_BUFFER_SIZE` when `None`.
+    pub write_buffer_size: Option<usize>,
+}
+
+/// How the contents of entries are changed as they are written.
+#[derive(Clone, Debug, Default)]
+pub struct ContentOptions {
+    /// Decide whether to decompress an entry from its name alone, rather
+    /// than from whether its first bytes start a gzip stream.
+    pub trust_extensions: bool,
+    /// Write gzipped entries exactly as they are stored, under their own
+    /// names, rather than decompressing them.
+    pub keep_gzip: bool,
+    /// How files named `.json` or `.geojson` are written. Reformatted
+    /// documents can't be verified with `verify_after`, and dry runs count
+    /// them as they are stored.
+    pub json_formatting: JsonFormatting,
+    /// Sort the keys of every object in the documents `json_formatting`
+    /// reformats, so that documents which only differ in the order of their
+    /// keys are written the same.
+    pub sort_keys: bool,
+    /// With `sort_keys`, documents larger than this, or than
+    /// `DEFAULT_SORT_KEYS_MAX_SIZE` when `None`, are reformatted with their
+    /// keys in the order given, with a warning.
+    pub sort_keys_max_size: Option<u64>,
+    /// Give files unpacked from a gzipped entry the modification time in
+    /// its gzip header, when it has one, rather than that of the entry.
+    pub restore_gzip_mtime: bool,
+}
+
+/// How strictly the entries of a package are checked.
+#[derive(Clone, Debug, Default)]
+pub struct IntegrityOptions {
+    /// Once every entry is unpacked, check what was written against the
+    /// package, failing on any difference.
+    pub verify_after: bool,
+    /// Once every entry is unpacked, read every file back from disk and
+    /// check it holds what was unpacked to it, failing on any difference.
+    pub verify_output: bool,
+    /// Refuse entries whose path is absolute or leads out of the output
+    /// folder, symbolic links, and names Windows reserves for devices,
+    /// instead of sanitizing their names.
+    pub strict_paths: bool,
+    /// Fail on entries whose content doesn't match what their name says
+    /// about compression, or with data after their gzip stream. Gzipped
+    /// entries are read to their end, so that their zip CRC is checked.
+    pub strict_content: bool,
+    /// Fail on entries which expand to more than this many times their
+    /// compressed size, or plain entries which hold more than their zip
+    /// header records.
+    pub max_expansion_ratio: Option<u64>,
+    /// Check the layer documents, node pages, node index documents, shared
+    /// resources and statistics of the package against their schemas as
+    /// they are unpacked, warning of the first violation in each.
+    pub validate_json: bool,
+    /// Fail on JSON resources which don't match their schemas, rather than
+    /// warning of them. Only has an effect along with `validate_json`.
+    pub strict_json: bool,
+    /// Refuse packages which say they hold more entries than
+    /// `MAX_ENTRIES`, before reading their zip directory, and entries whose
+    /// name is longer than `MAX_NAME_LEN` bytes.
+    pub hardened: bool,
+}
+
+/// What is written beside the files about the package and the run.
+#[derive(Clone, Debug, Default)]
+pub struct ReportOptions {
+    /// Write a manifest of the node and role of every unpacked file.
+    pub semantic_manifest: bool,
+    /// Write `manifest.sha256`, listing the SHA-256 of every unpacked file
+    /// in the format of `sha256sum`.
+    pub checksum_manifest: bool,
+    /// Write `index.html`, a page summarizing the package and linking its
+    /// documents, along with its figures in `index.json`.
+    pub emit_index: bool,
+    /// List the entries of the package in this file, in the order they were
+    /// written.
+    pub order_file: Option<PathBuf>,
+    /// Write a Chrome trace of the time spent on each entry to this file.
+    pub trace_path: Option<PathBuf>,
+    /// Log how long each stage of unpacking took at the end of the run.
+    pub show_timings: bool,
+}
+
+/// How an unpack runs.
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    /// The number of worker threads, or one per core when `None`. With
+    /// `Some(0)` or `Some(1)`, entries are unpacked one at a time on the
+    /// calling thread, without starting any threads.
+    pub threads: Option<usize>,
+    /// The order the entries are handed to the workers in.
+    pub extract_order: ExtractOrder,
+    /// Record entries which fail and carry on with the rest, instead of
+    /// stopping at the first. Entries which fail when retrying are set aside
+    /// instead.
+    pub keep_going: bool,
+    /// Retry entries which fail, and set aside those which keep failing
+    /// instead of stopping the unpack.
+    pub retry: Option<RetryPolicy>,
+    /// Keep the memory held for entry contents within this budget, which
+    /// can be shared with other unpacks.
+    pub memory_budget: Option<Arc<MemoryBudget>>,
+    /// Stop once this long has passed, recording what is left to unpack in
+    /// a resume file.
+    pub timeout: Option<Duration>,
+    /// Stop between entries once this is cancelled, failing with an error
+    /// which `cancelled_after` gives the number of entries unpacked for.
+    pub cancel: Option<CancelToken>,
+    /// Called from the worker threads as each entry is done, whether it was
+    /// written or skipped.
+    pub progress: Option<Progress>,
+    /// Read the package through a memory mapping of it, shared by the
+    /// worker threads, rather than through a file handle for each. Packages
+    /// which can't be mapped are read from the file.
+    pub mmap: bool,
+}
+
+/// How an encrypted package is read.
+#[derive(Clone, Debug, Default)]
+pub struct EncryptionOptions {
+    /// The password of a package whose entries are encrypted with
+    /// ZipCrypto, which are decrypted as they are read. Only package files
+    /// and streams can be decrypted.
+    pub password: Option<String>,
+}
+
+// Every option has a setter of the same name, so that options can be built
+// up as `UnpackOptions::new().threads(4).keep_going(true)`. The setters of
+// optional options take the value itself.
+macro_rules! setters {
+    ($group:ident: $($field:ident: $type:ty,)* ; $($optional:ident: $inner:ty,)*) => {
+        impl UnpackOptions {
+            $(
+                #[doc = concat!(
+                    "Sets `", stringify!($group), ".", stringify!($field), "`."
+                )]
+                pub fn $field(mut self, $field: $type) -> UnpackOptions {
+                    self.$group.$field = $field;
+                    self
+                }
+            )*
+            $(
+                #[doc = concat!(
+                    "Sets `", stringify!($group), ".", stringify!($optional), "` to this."
+                )]
+                pub fn $optional(mut self, $optional: $inner) -> UnpackOptions {
+                    self.$group.$optional = Some($optional);
+                    self
+                }
+            )*
+        }
+    };
+}
+
+setters! {
+    selection:
+    bbox_wgs84: bool,
+    exclude_empty_nodes: bool,
+    keep_unprefixed: bool,
+    retry_failed: bool,
+    resume: bool,
+    incremental: bool,
+    ;
+    layer: usize,
+    max_level: usize,
+    bbox: BoundingBox,
+    strip_prefix: StripPrefix,
+}
+
+setters! {
+    output:
+    split_sublayers: bool,
+    routes: Vec<ClassRoute>,
+    overwrite: OverwriteMode,
+    rename_collisions: bool,
+    portable_names: bool,
+    allow_symlinks: bool,
+    dedup_geometry: bool,
+    stage_files: bool,
+    atomic: bool,
+    dry_run: bool,
+    ;
+    output_dir: PathBuf,
+    on_file_conflict: ConflictPolicy,
+    dedup: DedupMode,
+    tar: TarTarget,
+    write_buffer_size: usize,
+}
+
+setters! {
+    content:
+    trust_extensions: bool,
+    keep_gzip: bool,
+    json_formatting: JsonFormatting,
+    sort_keys: bool,
+    restore_gzip_mtime: bool,
+    ;
+    sort_keys_max_size: u64,
+}
+
+setters! {
+    integrity:
+    verify_after: bool,
+    verify_output: bool,
+    strict_paths: bool,
+    strict_content: bool,
+    validate_json: bool,
+    strict_json: bool,
+    hardened: bool,
+    ;
+    max_expansion_ratio: u64,
+}
+
+setters! {
+    reports:
+    semantic_manifest: bool,
+    checksum_manifest: bool,
+    emit_index: bool,
+    show_timings: bool,
+    ;
+    order_file: PathBuf,
+    trace_path: PathBuf,
+}
+
+setters! {
+    run:
+    extract_order: ExtractOrder,
+    keep_going: bool,
+    mmap: bool,
+    ;
+    threads: usize,
+    retry: RetryPolicy,
+    memory_budget: Arc<MemoryBudget>,
+    timeout: Duration,
+    cancel: CancelToken,
+    progress: Progress,
+}
+
+setters! {
+    encryption:
+    ;
+    password: String,
+}
+
+impl UnpackOptions {
+    /// The default options, to build on with the setters.
+    pub fn new() -> UnpackOptions {
+        UnpackOptions::default()
+    }
+
+    /// Sets `filters`.
+    pub fn filters(mut self, filters: EntryFilters) -> UnpackOptions {
+        self.filters = filters;
+        self
+    }
+
+    /// Every integrity check at once, for packages which can't be trusted:
+    /// the zip CRC of every entry is checked along with the CRC and length
+    /// in the trailer of gzipped entries, paths and content are checked
+    /// strictly, expansion is limited, and every file is verified once
+    /// written. The limits of hardened mode apply as well. The first violation
+    /// fails the unpack, so failing entries aren't retried.
+    pub fn paranoid(self) -> UnpackOptions {
+        let max_expansion_ratio = self
+            .integrity
+            .max_expansion_ratio
+            .unwrap_or(paranoid::PARANOID_EXPANSION_RATIO);
+        UnpackOptions {
+            content: ContentOptions {
+                trust_extensions: false,
+                ..self.content
+            },
+            integrity: IntegrityOptions {
+                strict_paths: true,
+                strict_content: true,
+                max_expansion_ratio: Some(max_expansion_ratio),
+                verify_after: true,
+                hardened: true,
+                ..self.integrity
+            },
+            run: RunOptions {
+                retry: None,
+                keep_going: false,
+                ..self.run
+            },
+            ..self
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// The order the exporter wrote the entries of a package in. It says things
+// about the exporter, such as whether node pages were written before or after
+// the resources they describe, and lets a repack keep the same order.
+
+use failure::Error;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// The entry names of a package in the order their data comes in the file,
+/// which is usually but not always the order of the zip directory.
+pub fn entry_order<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Vec<String>, Error> {
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        entries.push((entry.data_start(), entry.name().to_string()));
+    }
+    entries.sort();
+    Ok(entries.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Writes the entry names of a package one per line, in the order their
+/// data comes in the file. Returns the number of entries.
+pub fn write_order_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &Path,
+) -> Result<usize, Error> {
+    let order = entry_order(archive)?;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for name in &order {
+        writeln!(file, "{}", name)?;
+    }
+    file.flush()?;
+    Ok(order.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use std::io::Cursor;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn lists_entries_in_the_order_they_were_written() {
+        let names = [
+            "nodes/0/geometries/0.bin",
+            "nodepages/0.json",
+            "3dSceneLayer.json",
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in &names {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(b"{}").unwrap();
+        }
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let dir = TempDir::new("order");
+        let path = dir.join("order.txt");
+        let written = write_order_file(&mut archive, &path);
+        let contents = std::fs::read_to_string(&path);
+
+        assert_eq!(written.unwrap(), 3);
+        assert_eq!(contents.unwrap(), names.join("\n") + "\n");
+    }
+}
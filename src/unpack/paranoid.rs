@@ -0,0 +1,327 @@
+// Defences for unpacking packages which can't be trusted. Normally the names
+// of entries are sanitized and their sizes taken on trust; with these checks
+// an entry which tries to escape the output folder, or to expand far beyond
+// its compressed size, stops the unpack instead.
+
+use super::UnpackError;
+use crate::archive::raw;
+use failure::Error;
+use std::io::{Read, Seek};
+
+/// How many times its compressed size an entry may expand to with
+/// `--paranoid`. Meshes and textures barely compress, and even verbose JSON
+/// rarely compresses 20 times over.
+pub const PARANOID_EXPANSION_RATIO: u64 = 100;
+
+/// The most entries a package may hold in hardened mode. The largest
+/// packages exported so far hold a few million.
+pub const MAX_ENTRIES: u64 = 20_000_000;
+
+/// The longest entry name allowed in hardened mode, in bytes. Zip allows
+/// names of up to 64 KiB, but I3S names are paths a few folders deep.
+pub const MAX_NAME_LEN: usize = 1024;
+
+/// How much of a name which is too long is shown.
+const NAME_PREFIX_LEN: usize = 64;
+
+/// Entries which expand to less than this are never too large, however well
+/// they compress.
+const EXPANSION_ALLOWANCE: u64 = 1024 * 1024;
+
+/// Names which Windows reserves for devices, with or without an extension.
+pub(super) const RESERVED_NAMES: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+const FILE_TYPE_MASK: u32 = 0o170_000;
+const SYMLINK: u32 = 0o120_000;
+
+/// Why an entry's path isn't safe to unpack, if it isn't.
+pub fn unsafe_path(entry_name: &str, unix_mode: Option<u32>) -> Option<String> {
+    if entry_name.contains('\0') {
+        return Some("its name contains a NUL character".to_string());
+    }
+    let bytes = entry_name.as_bytes();
+    if entry_name.starts_with('/')
+        || entry_name.starts_with('\\')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+    {
+        return Some("its path is absolute".to_string());
+    }
+    if unix_mode.map(|mode| mode & FILE_TYPE_MASK) == Some(SYMLINK) {
+        return Some("it is a symbolic link".to_string());
+    }
+    for component in entry_name.split(['/', '\\']) {
+        if component == ".." {
+            return Some("its path leads out of the output folder".to_string());
+        }
+        let stem = component.split('.').next().unwrap_or_default();
+        if RESERVED_NAMES.contains(&stem.to_lowercase().as_str()) {
+            return Some(format!("{} is a device name on Windows", component));
+        }
+    }
+    None
+}
+
+/// Fails when the package says it holds more than `MAX_ENTRIES` entries. The
+/// zip library reads every entry of the directory into memory, so this is
+/// checked from the end of central directory record first.
+pub fn check_entry_count<R: Read + Seek>(package: &mut R) -> Result<(), Error> {
+    let entries = raw::declared_entries(package)?;
+    if entries > MAX_ENTRIES {
+        return Err(Error::from(UnpackError::TooManyEntries(
+            entries,
+            MAX_ENTRIES,
+        )));
+    }
+    Ok(())
+}
+
+/// Fails when an entry name is longer than `MAX_NAME_LEN` bytes.
+pub fn check_name_length(entry_name: &str) -> Result<(), Error> {
+    if entry_name.len() <= MAX_NAME_LEN {
+        return Ok(());
+    }
+    let prefix: String = entry_name.chars().take(NAME_PREFIX_LEN).collect();
+    Err(Error::from(UnpackError::NameTooLong(
+        entry_name.len(),
+        prefix,
+        MAX_NAME_LEN,
+    )))
+}
+
+/// Fails reading once more has been read than an entry may expand to.
+pub struct ExpansionLimit<R> {
+    inner: R,
+    remaining: u64,
+    violation: String,
+}
+
+impl<R: Read> ExpansionLimit<R> {
+    /// Limits the contents of an entry to `ratio` times its compressed
+    /// size, and plain entries to the size their zip header records, given
+    /// as `(compressed_size, size)`. There is no limit without a `ratio`.
+    pub fn new(
+        inner: R,
+        entry_name: &str,
+        (compressed_size, size): (u64, u64),
+        decompressed: bool,
+        ratio: Option<u64>,
+    ) -> ExpansionLimit<R> {
+        let ratio = match ratio {
+            Some(ratio) => ratio,
+            None => {
+                return ExpansionLimit {
+                    inner,
+                    remaining: u64::MAX,
+                    violation: String::new(),
+                }
+            }
+        };
+        let expansion = compressed_size
+            .saturating_mul(ratio)
+            .max(EXPANSION_ALLOWANCE);
+        let (limit, violation) = if !decompressed && size <= expansion {
+            (
+                size,
+                format!(
+                    "{} holds more than the {} bytes its zip header records",
+                    entry_name, size
+                ),
+            )
+        } else {
+            (
+                expansion,
+                format!(
+                    "{} expands to more than {} times its compressed size of {} bytes",
+                    entry_name, ratio, compressed_size
+                ),
+            )
+        };
+        ExpansionLimit {
+            inner,
+            remaining: limit,
+            violation,
+        }
+    }
+}
+
+impl<R: Read> Read for ExpansionLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // One byte more than the limit is asked for, to tell an entry which
+        // ends at the limit from one which goes on.
+        let wanted = buf.len().min(self.remaining.saturating_add(1) as usize);
+        let read = self.inner.read(&mut buf[..wanted])?;
+        if read as u64 > self.remaining {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                self.violation.clone(),
+            ));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::raw::{RawEntry, RawWriter};
+    use crate::synthetic::TempDir;
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+
+    fn gzip(contents: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// A package of one stored entry, unless `edit` says otherwise.
+    fn package(name: &str, contents: &[u8], edit: impl Fn(&mut RawEntry)) -> Vec<u8> {
+        let mut entry = RawEntry::default();
+        entry.name = name.as_bytes().to_vec();
+        // Made on Unix, as a regular file.
+        entry.version_made_by = 3 << 8;
+        entry.external_attributes = 0o100_644 << 16;
+        edit(&mut entry);
+        let data = entry.replace_contents(contents).unwrap();
+        // Edit again, for fields which replacing the contents sets.
+        edit(&mut entry);
+        let mut writer = RawWriter::new(Cursor::new(Vec::new()));
+        writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        writer.finish(b"").unwrap().into_inner()
+    }
+
+    /// Only the end of a zip64 package, which says it holds `entries`.
+    fn zip64_end(entries: u64) -> Vec<u8> {
+        let mut end = Vec::new();
+        end.write_u32::<LittleEndian>(0x0606_4b50).unwrap();
+        end.write_u64::<LittleEndian>(44).unwrap();
+        end.write_u16::<LittleEndian>(45).unwrap();
+        end.write_u16::<LittleEndian>(45).unwrap();
+        end.write_u32::<LittleEndian>(0).unwrap();
+        end.write_u32::<LittleEndian>(0).unwrap();
+        end.write_u64::<LittleEndian>(entries).unwrap();
+        end.write_u64::<LittleEndian>(entries).unwrap();
+        end.write_u64::<LittleEndian>(0).unwrap();
+        end.write_u64::<LittleEndian>(0).unwrap();
+        // The locator, pointing back at the record.
+        end.write_u32::<LittleEndian>(0x0706_4b50).unwrap();
+        end.write_u32::<LittleEndian>(0).unwrap();
+        end.write_u64::<LittleEndian>(0).unwrap();
+        end.write_u32::<LittleEndian>(1).unwrap();
+        end.write_u32::<LittleEndian>(0x0605_4b50).unwrap();
+        end.write_u16::<LittleEndian>(0).unwrap();
+        end.write_u16::<LittleEndian>(0).unwrap();
+        end.write_u16::<LittleEndian>(0xffff).unwrap();
+        end.write_u16::<LittleEndian>(0xffff).unwrap();
+        end.write_u32::<LittleEndian>(0xffff_ffff).unwrap();
+        end.write_u32::<LittleEndian>(0xffff_ffff).unwrap();
+        end.write_u16::<LittleEndian>(0).unwrap();
+        end
+    }
+
+    #[test]
+    fn paranoid_unpacks_stop_at_hostile_entries() {
+        let dir = TempDir::new("paranoid");
+        let deflate = |entry: &mut RawEntry| entry.compression_method = 8;
+        let mut trailing = gzip(b"{}");
+        trailing.extend_from_slice(b"hidden payload");
+        let cases: Vec<(&str, Vec<u8>, &str)> = vec![
+            (
+                "traversal",
+                package("nodes/../../escaped.json", b"{}", |_| {}),
+                "leads out of the output folder",
+            ),
+            (
+                "absolute",
+                package("/etc/escaped.json", b"{}", |_| {}),
+                "absolute",
+            ),
+            (
+                "symlink",
+                package("nodes/0/link", b"/etc/passwd", |entry| {
+                    entry.external_attributes = 0o120_777 << 16
+                }),
+                "symbolic link",
+            ),
+            (
+                "reserved",
+                package("nodes/0/CON.json", b"{}", |_| {}),
+                "device name",
+            ),
+            (
+                "bomb",
+                package(
+                    "nodes/0/geometries/0.bin.gz",
+                    &gzip(&vec![0u8; 8 * 1024 * 1024]),
+                    |_| {},
+                ),
+                "times its compressed size",
+            ),
+            (
+                "understated",
+                package(
+                    "nodes/0/features/0.json",
+                    &vec![b' '; 2 * 1024 * 1024],
+                    |entry| {
+                        deflate(entry);
+                        entry.size = 10;
+                    },
+                ),
+                "its zip header records",
+            ),
+            (
+                "long-name",
+                package(&format!("nodes/{}.json", "0".repeat(2000)), b"{}", |_| {}),
+                "longer than the 1024 allowed",
+            ),
+            (
+                "many-entries",
+                zip64_end(1 << 40),
+                "holds 1099511627776 entries",
+            ),
+            (
+                "misnamed",
+                package("3dSceneLayer.json.gz", b"{}", |_| {}),
+                "isn't gzipped",
+            ),
+            (
+                "trailing",
+                package("3dSceneLayer.json.gz", &trailing, |_| {}),
+                "after the end of its gzip stream",
+            ),
+            (
+                "outer-crc",
+                package("3dSceneLayer.json.gz", &gzip(b"{}"), |entry| {
+                    entry.crc32 ^= 1
+                }),
+                "checksum",
+            ),
+        ];
+
+        let paranoid = UnpackOptions::default().paranoid();
+        let mut outcomes = Vec::new();
+        for (case, contents, _) in &cases {
+            let path = dir.join(format!("{}.slpk", case));
+            std::fs::write(&path, contents).unwrap();
+            let outcome = unpack_with_options(&path, &paranoid).map(|_| ());
+            outcomes.push((outcome, dir.join("escaped.json").exists()));
+        }
+        // The CRC of a gzipped entry is only checked when it is read to its
+        // end.
+        let trusted = dir.join("outer-crc.slpk");
+        let trusted_outcome = unpack_with_options(&trusted, &UnpackOptions::default());
+
+        for ((case, _, expected), (outcome, escaped)) in cases.iter().zip(outcomes) {
+            let error = outcome.expect_err(case).to_string();
+            assert!(error.contains(expected), "{}: {}", case, error);
+            assert!(!escaped, "{}", case);
+        }
+        assert!(trusted_outcome.is_ok());
+    }
+}
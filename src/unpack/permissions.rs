@@ -0,0 +1,62 @@
+// Packages made on Unix record the mode of each file in the high half of its
+// external attributes, and unpacked files are given it back, so that scripts
+// packed beside the resources stay executable. Only the permission bits are
+// kept: setuid, setgid and the sticky bit are dropped, as is write permission
+// for the group and others, so that a file is never left writable by other
+// users whatever its entry says. The owner can always read and write the
+// file, so that a later unpack can replace it.
+//
+// The zip library makes a mode up for entries made on Windows, from their
+// read-only bit, and only entries whose mode says they are regular files are
+// taken to have a real one. The made up mode of a writable entry looks like a
+// real one, but is 0o664, which is as good as the default.
+
+use std::path::Path;
+
+const FILE_TYPE_MASK: u32 = 0o170_000;
+const REGULAR_FILE: u32 = 0o100_000;
+/// The permissions kept from an entry: all but write for the group and
+/// others.
+const PERMISSION_BITS: u32 = 0o755;
+/// What the owner of an unpacked file can always do with it.
+const OWNER_READ_WRITE: u32 = 0o600;
+
+/// The mode to give a file unpacked from an entry with `unix_mode`, or
+/// `None` to leave it with the one it was created with.
+pub fn sanitized_mode(unix_mode: Option<u32>) -> Option<u32> {
+    let mode = unix_mode?;
+    if mode & FILE_TYPE_MASK != REGULAR_FILE {
+        return None;
+    }
+    Some(mode & PERMISSION_BITS | OWNER_READ_WRITE)
+}
+
+#[cfg(unix)]
+pub fn apply(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+/// Windows has no mode to give, and files keep their default attributes.
+#[cfg(not(unix))]
+pub fn apply(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_safe_permission_bits_are_kept() {
+        assert_eq!(sanitized_mode(Some(0o100_755)), Some(0o755));
+        assert_eq!(sanitized_mode(Some(0o100_000)), Some(0o600));
+        assert_eq!(sanitized_mode(Some(0o106_755)), Some(0o755));
+        assert_eq!(sanitized_mode(Some(0o104_777)), Some(0o755));
+        assert_eq!(sanitized_mode(Some(0o100_666)), Some(0o644));
+        // Made up for an entry made on Windows with its read-only bit set.
+        assert_eq!(sanitized_mode(Some(0o444)), None);
+        assert_eq!(sanitized_mode(Some(0o120_777)), None);
+        assert_eq!(sanitized_mode(None), None);
+    }
+}
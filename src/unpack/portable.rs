@@ -0,0 +1,168 @@
+// Windows refuses file names holding any of `< > : " | ? *` or a control
+// character, names ending in a dot or a space, and the names it keeps for
+// devices, such as `CON` or `nul.json`. A package made elsewhere can hold any
+// of them, such as a statistics document for a field named `height?`. On
+// Windows the whole unpack would then fail when the file is created. So on
+// Windows, and elsewhere when asked to, each such character is written as
+// `%` and its code in hex, as `%3F` for `?`. For a device name, its first
+// letter is written that way, so that `CON.json` becomes `%43ON.json`.
+//
+// The escaped name can be the name of another entry, so entries are
+// renamed before collisions are looked for. Two entries which end up with
+// the same file are always told apart, as neither name was chosen by the
+// package. Each entry unpacked under another name is logged with a warning,
+// and listed in the semantic manifest, so that the names it had can be put
+// back when the files are packed again.
+
+use super::paranoid::RESERVED_NAMES;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Characters Windows refuses anywhere in a file name, besides control
+/// characters.
+const INVALID_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// `c` as `%` and its code in hex.
+fn escaped(c: char) -> String {
+    let mut bytes = [0; 4];
+    c.encode_utf8(&mut bytes)
+        .bytes()
+        .map(|byte| format!("%{:02X}", byte))
+        .collect()
+}
+
+/// `name` with its trailing dots and spaces escaped.
+fn escape_trailing(name: &str) -> String {
+    let kept = name.trim_end_matches(['.', ' ']);
+    let mut escaped_name = kept.to_string();
+    for c in name[kept.len()..].chars() {
+        escaped_name.push_str(&escaped(c));
+    }
+    escaped_name
+}
+
+/// A file or folder name Windows accepts, which is `name` itself if it does
+/// already.
+pub fn portable_name(name: &str) -> Cow<'_, str> {
+    let mut portable: String = name
+        .chars()
+        .map(|c| {
+            if INVALID_CHARS.contains(&c) || c.is_ascii_control() {
+                escaped(c)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+    // A file unpacked from an entry named .gz loses the suffix, and with it
+    // the end of the name Windows looks at.
+    portable = match portable.strip_suffix(".gz") {
+        Some(stem) => escape_trailing(stem) + ".gz",
+        None => escape_trailing(&portable),
+    };
+    let stem = portable.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.contains(&stem.to_lowercase().as_str()) {
+        let first = portable.chars().next().unwrap_or_default();
+        portable = escaped(first) + &portable[first.len_utf8()..];
+    }
+    if portable == name {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(portable)
+    }
+}
+
+/// `path` with every file and folder name made one Windows accepts, if any
+/// weren't.
+pub fn portable_path(path: &Path) -> Option<PathBuf> {
+    let mut changed = false;
+    let portable = path
+        .iter()
+        .map(|component| {
+            let component = component.to_string_lossy();
+            match portable_name(&component) {
+                Cow::Borrowed(_) => component.into_owned(),
+                Cow::Owned(portable) => {
+                    changed = true;
+                    portable
+                }
+            }
+        })
+        .collect();
+    if changed {
+        Some(portable)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn names_windows_refuses_are_escaped() {
+        assert_eq!(portable_name("f_height?.json.gz"), "f_height%3F.json.gz");
+        assert_eq!(portable_name("a<b>c:d\"e|f*g"), "a%3Cb%3Ec%3Ad%22e%7Cf%2Ag");
+        assert_eq!(portable_name("tab\there"), "tab%09here");
+        assert_eq!(portable_name("trailing. "), "trailing%2E%20");
+        assert_eq!(portable_name("trailing..gz"), "trailing%2E.gz");
+        assert_eq!(portable_name("CON"), "%43ON");
+        assert_eq!(portable_name("nul.json"), "%6Eul.json");
+        assert_eq!(portable_name("console.json"), "console.json");
+        assert!(matches!(portable_name("0.bin.gz"), Cow::Borrowed(_)));
+        assert_eq!(
+            portable_path(Path::new("nodes/aux/0.jpg")),
+            Some(PathBuf::from("nodes/%61ux/0.jpg"))
+        );
+        assert_eq!(portable_path(Path::new("nodes/0/0.jpg")), None);
+    }
+
+    #[test]
+    fn escaped_names_which_collide_are_told_apart() {
+        let dir = TempDir::new("portable");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::standard()
+            .entry("statistics/f_height?.json", b"{\"escaped\":true}")
+            .entry("statistics/f_height%3F.json", b"{\"escaped\":false}")
+            .write_to_file(&path)
+            .unwrap();
+        let options = UnpackOptions::new()
+            .portable_names(true)
+            .semantic_manifest(true);
+        unpack_with_options(&path, &options).unwrap();
+        let unpacked = dir.join("package/statistics");
+        let read = |name: &str| std::fs::read_to_string(unpacked.join(name)).unwrap();
+        let files = (read("f_height%3F.json"), read("f_height%3F~1.json"));
+        let manifest =
+            json::parse(&std::fs::read_to_string(dir.join("package/manifest.json")).unwrap())
+                .unwrap();
+
+        // Sorted by path, then by their place in the package.
+        assert_eq!(files.0, "{\"escaped\":true}");
+        assert_eq!(files.1, "{\"escaped\":false}");
+        let renamed = manifest.get("renamedEntries").unwrap().as_array().unwrap();
+        let renamed: Vec<(&str, &str)> = renamed
+            .iter()
+            .map(|renamed| {
+                (
+                    renamed.get("entry").and_then(json::Value::as_str).unwrap(),
+                    renamed.get("file").and_then(json::Value::as_str).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            renamed,
+            vec![
+                ("statistics/f_height?.json", "statistics/f_height%3F.json"),
+                (
+                    "statistics/f_height%3F.json",
+                    "statistics/f_height%3F~1.json"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+// Strips a leading folder from the entries of a package, for packages made
+// by zipping the folder a layer was exported to rather than its contents,
+// whose every entry starts with something like `MyLayer/`, so that they
+// unpack to `MyLayer/3dSceneLayer.json` rather than
+// `MyLayer/MyLayer/3dSceneLayer.json`. The prefix is matched against the
+// sanitized path of each entry, component by component, so `MyLayer`
+// doesn't strip `MyLayer2/`, and everything after it, including the checks
+// that a path stays inside the output folder, sees the stripped path.
+//
+// With `auto`, the prefix is the single folder at the top of every entry of
+// the package, if there is one. A package with any file at its root, such
+// as `3dSceneLayer.json.gz` where it belongs, or with more than one folder
+// there, has nothing stripped.
+
+use crate::archive::names::sanitized_path;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+#[fail(display = "'{}' names no folder to strip from the entries", _0)]
+pub struct NoPrefix(String);
+
+/// Which folder is stripped from the start of every entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StripPrefix {
+    /// The folder every entry of the package is in, if there is one.
+    Auto,
+    /// This folder.
+    Folder(PathBuf),
+}
+
+impl FromStr for StripPrefix {
+    type Err = NoPrefix;
+
+    fn from_str(s: &str) -> Result<StripPrefix, NoPrefix> {
+        if s == "auto" {
+            return Ok(StripPrefix::Auto);
+        }
+        let folder = sanitized_path(s);
+        if folder.as_os_str().is_empty() {
+            return Err(NoPrefix(s.to_string()));
+        }
+        Ok(StripPrefix::Folder(folder))
+    }
+}
+
+/// The folder at the top of every one of `names`, which are decoded entry
+/// names, if they all have the same one.
+pub fn common_folder<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<PathBuf> {
+    let mut folder: Option<PathBuf> = None;
+    for name in names {
+        let path = sanitized_path(name);
+        let mut components = path.components();
+        let top = match components.next() {
+            Some(Component::Normal(top)) => top,
+            _ => continue,
+        };
+        // A file at the top has no folder to share.
+        let is_folder = name.ends_with('/') || name.ends_with('\\');
+        if components.next().is_none() && !is_folder {
+            return None;
+        }
+        match &folder {
+            Some(folder) if folder.as_os_str() != top => return None,
+            Some(_) => {}
+            None => folder = Some(PathBuf::from(top)),
+        }
+    }
+    folder
+}
+
+/// `path` without `prefix`, or `None` if it isn't below it.
+pub fn strip(path: &Path, prefix: &Path) -> Option<PathBuf> {
+    path.strip_prefix(prefix).ok().map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_folder_every_entry_is_in_is_found() {
+        let zipped_folder = [
+            "MyLayer/",
+            "MyLayer/3dSceneLayer.json.gz",
+            "MyLayer/nodes/0/3dNodeIndexDocument.json.gz",
+        ];
+        assert_eq!(
+            common_folder(zipped_folder.iter().copied()),
+            Some(PathBuf::from("MyLayer"))
+        );
+        assert_eq!(
+            common_folder(["3dSceneLayer.json.gz", "nodes/0/a.bin"].iter().copied()),
+            None
+        );
+        assert_eq!(
+            common_folder(
+                ["a/3dSceneLayer.json.gz", "b/nodes/0/a.bin"]
+                    .iter()
+                    .copied()
+            ),
+            None
+        );
+        assert_eq!(common_folder(["MyLayer"].iter().copied()), None);
+
+        let prefix = "MyLayer/".parse::<StripPrefix>().unwrap();
+        assert_eq!(prefix, StripPrefix::Folder(PathBuf::from("MyLayer")));
+        assert!("/".parse::<StripPrefix>().is_err());
+        let prefix = Path::new("MyLayer");
+        assert_eq!(
+            strip(Path::new("MyLayer/nodes/0/a.bin"), prefix),
+            Some(PathBuf::from("nodes/0/a.bin"))
+        );
+        assert_eq!(strip(Path::new("MyLayer2/a.bin"), prefix), None);
+    }
+}
@@ -0,0 +1,91 @@
+// Progress is counted by the worker threads as they finish each entry, and
+// passed to a callback which any of them may call at any time. Entries which
+// are skipped count as done, so that the count reaches the total when every
+// entry has been looked at.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Called with the number of entries done, the number of entries in the
+/// package, and the bytes written so far.
+pub type ProgressCallback = dyn Fn(usize, usize, u64) + Send + Sync;
+
+/// The callback progress is reported to, shared between the workers.
+#[derive(Clone)]
+pub struct Progress(pub Arc<ProgressCallback>);
+
+impl Progress {
+    /// Progress reported to `callback`.
+    pub fn new(callback: impl Fn(usize, usize, u64) + Send + Sync + 'static) -> Progress {
+        Progress(Arc::new(callback))
+    }
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+/// The counts shared by the workers of one unpack.
+pub struct ProgressCounters {
+    entries_done: AtomicUsize,
+    bytes_written: AtomicU64,
+    entries_total: usize,
+    progress: Progress,
+}
+
+impl ProgressCounters {
+    pub fn new(progress: Progress, entries_total: usize) -> ProgressCounters {
+        ProgressCounters {
+            entries_done: AtomicUsize::new(0),
+            bytes_written: AtomicU64::new(0),
+            entries_total,
+            progress,
+        }
+    }
+
+    pub fn entry_done(&self, bytes_written: u64) {
+        // The bytes are added first, so that the report of the last entry
+        // has every byte.
+        self.bytes_written
+            .fetch_add(bytes_written, Ordering::SeqCst);
+        let done = self.entries_done.fetch_add(1, Ordering::SeqCst) + 1;
+        let bytes = self.bytes_written.load(Ordering::SeqCst);
+        (self.progress.0)(done, self.entries_total, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+    use std::sync::Mutex;
+
+    #[test]
+    fn workers_report_every_entry_and_byte() {
+        let dir = TempDir::new("progress");
+        let mut package = SyntheticPackage::new();
+        for i in 0..40 {
+            package = package.entry(&format!("nodes/{}/geometries/0.bin", i), &[1; 1000]);
+        }
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let collected = reports.clone();
+        let options =
+            UnpackOptions::new()
+                .threads(4)
+                .progress(Progress::new(move |done, total, bytes| {
+                    collected.lock().unwrap().push((done, total, bytes))
+                }));
+        unpack_with_options(&path, &options).unwrap();
+
+        let mut reports = reports.lock().unwrap().clone();
+        assert_eq!(reports.len(), 40);
+        reports.sort();
+        assert_eq!(reports.last(), Some(&(40, 40, 40_000)));
+        assert!(reports.iter().all(|&(_, total, _)| total == 40));
+    }
+}
@@ -0,0 +1,185 @@
+// Entries which keep failing, such as when a network filesystem returns
+// errors for a few reads, are retried a few times and then set aside, so
+// that one bad entry doesn't cost the rest of the unpack. Each entry set
+// aside leaves a marker file in the output folder, from which a later run
+// can retry exactly those entries.
+
+use crate::glob;
+use failure::Error;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Extension added to an entry's output path to name its marker file.
+pub const MARKER_EXTENSION: &str = "failed";
+
+const ENTRY_FIELD: &str = "entry: ";
+
+/// How entries which fail to unpack are tried again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// How many times a failing entry is tried again.
+    pub retries: usize,
+    /// The wait before the first retry, doubled before each later one.
+    pub backoff: Duration,
+}
+
+/// The outcome of calling an operation until it succeeds or runs out of
+/// retries, with the number of attempts which failed.
+pub struct Attempts<T> {
+    pub result: Result<T, Error>,
+    pub failures: usize,
+}
+
+impl RetryPolicy {
+    /// Calls `operation` until it succeeds, runs out of retries, or fails
+    /// when `keep_trying` says not to.
+    pub fn run<T>(
+        &self,
+        keep_trying: impl Fn() -> bool,
+        mut operation: impl FnMut() -> Result<T, Error>,
+    ) -> Attempts<T> {
+        let mut failures = 0;
+        let mut backoff = self.backoff;
+        loop {
+            match operation() {
+                Ok(value) => {
+                    return Attempts {
+                        result: Ok(value),
+                        failures,
+                    }
+                }
+                Err(e) if failures >= self.retries || !keep_trying() => {
+                    return Attempts {
+                        result: Err(e),
+                        failures: failures + 1,
+                    }
+                }
+                Err(_) => {
+                    failures += 1;
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+}
+
+pub fn marker_path(unpack_folder: &Path, entry_path: &Path) -> PathBuf {
+    let mut marker = unpack_folder.join(entry_path).into_os_string();
+    marker.push(".");
+    marker.push(MARKER_EXTENSION);
+    PathBuf::from(marker)
+}
+
+/// Records why an entry was set aside. The first line names the entry, so
+/// that the marker can be matched back to it.
+pub fn write_marker(
+    marker_path: &Path,
+    entry_name: &str,
+    attempts: usize,
+    error: &Error,
+) -> Result<(), Error> {
+    if let Some(parent) = marker_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(
+        marker_path,
+        format!(
+            "{}{}\nattempts: {}\nerror: {}\n",
+            ENTRY_FIELD, entry_name, attempts, error
+        ),
+    )?;
+    Ok(())
+}
+
+/// Finds the marker files below the output folder of an earlier run,
+/// returning each marker's path with the name of its entry.
+pub fn find_markers(unpack_folder: &Path) -> Result<Vec<(PathBuf, String)>, Error> {
+    let mut files = Vec::new();
+    glob::walk(unpack_folder, None, &mut files)?;
+    let mut markers = Vec::new();
+    for file in files {
+        if file.extension().and_then(|e| e.to_str()) != Some(MARKER_EXTENSION) {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&file)?;
+        let entry_name = contents
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix(ENTRY_FIELD));
+        if let Some(entry_name) = entry_name {
+            markers.push((file.clone(), entry_name.to_string()));
+        }
+    }
+    markers.sort();
+    Ok(markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    /// Fails the first `failing_reads` calls, like a flaky file share.
+    struct FlakySource {
+        failing_reads: usize,
+        reads: usize,
+    }
+
+    impl FlakySource {
+        fn read(&mut self) -> Result<&'static [u8], Error> {
+            self.reads += 1;
+            if self.reads <= self.failing_reads {
+                Err(format_err!("read {} failed", self.reads))
+            } else {
+                Ok(b"contents")
+            }
+        }
+    }
+
+    fn policy(retries: usize) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn retries_until_the_source_recovers() {
+        let mut source = FlakySource {
+            failing_reads: 2,
+            reads: 0,
+        };
+        let attempts = policy(3).run(|| true, || source.read());
+        assert_eq!(attempts.result.unwrap(), b"contents");
+        assert_eq!(attempts.failures, 2);
+
+        let mut source = FlakySource {
+            failing_reads: 5,
+            reads: 0,
+        };
+        let attempts = policy(3).run(|| true, || source.read());
+        assert_eq!(attempts.result.unwrap_err().to_string(), "read 4 failed");
+        assert_eq!((attempts.failures, source.reads), (4, 4));
+    }
+
+    #[test]
+    fn markers_name_their_entry() {
+        let folder = TempDir::new("quarantine");
+        let marker = marker_path(&folder, Path::new("nodes/0/geometries/0.bin"));
+        assert!(marker.ends_with("nodes/0/geometries/0.bin.failed"));
+        write_marker(
+            &marker,
+            "nodes/0/geometries/0.bin.gz",
+            3,
+            &format_err!("corrupt deflate stream"),
+        )
+        .unwrap();
+        let markers = find_markers(&folder).unwrap();
+        assert_eq!(
+            markers,
+            vec![(marker, "nodes/0/geometries/0.bin.gz".to_string())]
+        );
+    }
+}
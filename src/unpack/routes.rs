@@ -0,0 +1,150 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+pub enum RouteError {
+    #[fail(
+        display = "Invalid route '{}', expected <class>=<folder> where class is one of metadata, geometry, textures, attributes or other",
+        _0
+    )]
+    InvalidRoute(String),
+}
+
+/// The kinds of resource found in a package, as far as where they should be
+/// stored is concerned.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResourceClass {
+    Metadata,
+    Geometry,
+    Textures,
+    Attributes,
+    Other,
+}
+
+impl ResourceClass {
+    /// Classifies an entry by the resource folder it is stored in, or for
+    /// entries outside of those, by whether it is a JSON document.
+    pub fn of_entry(entry_path: &Path) -> ResourceClass {
+        for component in entry_path.components() {
+            if let Component::Normal(name) = component {
+                match name.to_str() {
+                    Some("geometries") => return ResourceClass::Geometry,
+                    Some("textures") => return ResourceClass::Textures,
+                    Some("attributes") => return ResourceClass::Attributes,
+                    _ => {}
+                }
+            }
+        }
+
+        let name = entry_path.to_string_lossy();
+        let name = name.trim_end_matches(".gz");
+        if name.ends_with(".json") {
+            ResourceClass::Metadata
+        } else {
+            ResourceClass::Other
+        }
+    }
+}
+
+impl fmt::Display for ResourceClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ResourceClass::Metadata => "metadata",
+            ResourceClass::Geometry => "geometry",
+            ResourceClass::Textures => "textures",
+            ResourceClass::Attributes => "attributes",
+            ResourceClass::Other => "other",
+        })
+    }
+}
+
+/// Sends one class of resource to its own base folder. The layout below the
+/// base folder is the same as in the default output folder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassRoute {
+    /// The class of resource.
+    pub class: ResourceClass,
+    /// The folder the resources of the class are written below.
+    pub root: PathBuf,
+}
+
+impl FromStr for ClassRoute {
+    type Err = RouteError;
+
+    fn from_str(s: &str) -> Result<ClassRoute, RouteError> {
+        let invalid = || RouteError::InvalidRoute(s.to_string());
+        let mut parts = s.splitn(2, '=');
+        let class = match parts.next() {
+            Some("metadata") => ResourceClass::Metadata,
+            Some("geometry") => ResourceClass::Geometry,
+            Some("textures") => ResourceClass::Textures,
+            Some("attributes") => ResourceClass::Attributes,
+            Some("other") => ResourceClass::Other,
+            _ => return Err(invalid()),
+        };
+        match parts.next() {
+            Some(root) if !root.is_empty() => Ok(ClassRoute {
+                class,
+                root: PathBuf::from(root),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Returns the base folder for an entry: the last route given for its
+/// class, or `default` when its class isn't routed.
+pub fn root_for<'a>(routes: &'a [ClassRoute], class: ResourceClass, default: &'a Path) -> &'a Path {
+    routes
+        .iter()
+        .rev()
+        .find(|route| route.class == class)
+        .map_or(default, |route| route.root.as_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_entries_by_resource_folder() {
+        let class = |name| ResourceClass::of_entry(Path::new(name));
+        assert_eq!(
+            class("nodes/0/geometries/0.bin.gz"),
+            ResourceClass::Geometry
+        );
+        assert_eq!(class("nodes/0/textures/0.jpg"), ResourceClass::Textures);
+        assert_eq!(
+            class("nodes/0/attributes/f_1/0.bin.gz"),
+            ResourceClass::Attributes
+        );
+        assert_eq!(
+            class("nodes/0/3dNodeIndexDocument.json.gz"),
+            ResourceClass::Metadata
+        );
+        assert_eq!(class("metadata.json"), ResourceClass::Metadata);
+        assert_eq!(class("@specialIndexFileHASH128@"), ResourceClass::Other);
+    }
+
+    #[test]
+    fn parses_routes() {
+        assert_eq!(
+            "textures=/mnt/bulk".parse::<ClassRoute>().unwrap(),
+            ClassRoute {
+                class: ResourceClass::Textures,
+                root: PathBuf::from("/mnt/bulk")
+            }
+        );
+        assert!("textures".parse::<ClassRoute>().is_err());
+        assert!("meshes=/mnt/bulk".parse::<ClassRoute>().is_err());
+
+        let routes = vec!["metadata=fast".parse().unwrap()];
+        let default = Path::new("out");
+        assert_eq!(
+            root_for(&routes, ResourceClass::Metadata, default),
+            Path::new("fast")
+        );
+        assert_eq!(root_for(&routes, ResourceClass::Textures, default), default);
+    }
+}
@@ -0,0 +1,179 @@
+// The worker threads take entries one at a time from a shared counter, rather
+// than each being given an even range of the entries up front. The entries of
+// a package are of very different sizes, a few large textures among thousands
+// of small node documents, so a worker whose range held the large textures
+// would still be busy long after the others had finished.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Hands out the positions `0..len`, each of them once.
+#[derive(Debug)]
+pub struct EntryQueue {
+    next: AtomicUsize,
+    len: usize,
+}
+
+impl EntryQueue {
+    pub fn new(len: usize) -> EntryQueue {
+        EntryQueue {
+            next: AtomicUsize::new(0),
+            len,
+        }
+    }
+
+    /// The next position to unpack, or `None` once every position has been
+    /// handed out.
+    pub fn next(&self) -> Option<usize> {
+        let position = self.next.fetch_add(1, Ordering::SeqCst);
+        if position < self.len {
+            Some(position)
+        } else {
+            None
+        }
+    }
+
+    /// The first position which hasn't been handed out. Every later
+    /// position hasn't been either.
+    pub fn unclaimed(&self) -> usize {
+        self.next.load(Ordering::SeqCst).min(self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{is_bookkeeping_file, unpack_with_options, UnpackOptions};
+    use std::collections::BTreeSet;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const THREADS: usize = 8;
+
+    /// Numbers of entries around the number of threads, and primes, which
+    /// no number of threads divides.
+    const COUNTS: [usize; 7] = [0, 1, THREADS - 1, THREADS, THREADS + 1, 13, 97];
+
+    /// The positions `len` entries are handed out as by `THREADS` workers,
+    /// in the order they are taken.
+    fn hand_out(len: usize) -> Vec<usize> {
+        let queue = Arc::new(EntryQueue::new(len));
+        let workers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let queue = queue.clone();
+                std::thread::spawn(move || std::iter::from_fn(|| queue.next()).collect::<Vec<_>>())
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    }
+
+    /// The paths of the files below `folder`, relative to it, other than
+    /// those unpacking writes beside the entries.
+    fn files_below(folder: &Path) -> BTreeSet<String> {
+        let mut files = BTreeSet::new();
+        let mut folders = vec![folder.to_path_buf()];
+        while let Some(current) = folders.pop() {
+            for entry in std::fs::read_dir(&current).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    folders.push(path);
+                } else {
+                    let relative = path.strip_prefix(folder).unwrap();
+                    let relative = relative.to_string_lossy().replace('\\', "/");
+                    if !is_bookkeeping_file(&relative) {
+                        files.insert(relative);
+                    }
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn every_position_is_handed_out_once() {
+        let queue = Arc::new(EntryQueue::new(10_000));
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let queue = queue.clone();
+                std::thread::spawn(move || {
+                    let mut taken = Vec::new();
+                    while let Some(position) = queue.next() {
+                        taken.push(position);
+                    }
+                    taken
+                })
+            })
+            .collect();
+        let mut taken: Vec<usize> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+        taken.sort();
+
+        assert_eq!(taken, (0..10_000).collect::<Vec<_>>());
+        assert_eq!(queue.unclaimed(), 10_000);
+        assert_eq!(queue.next(), None);
+    }
+
+    #[test]
+    fn packages_with_fewer_entries_than_threads_unpack() {
+        let dir = TempDir::new("scheduler");
+        let mut unpacked = Vec::new();
+        for num_entries in [0, 6, 32] {
+            let mut package = SyntheticPackage::new();
+            for i in 0..num_entries {
+                package = package.entry(&format!("nodes/{}/geometries/0.bin", i), &[1; 10]);
+            }
+            let path = dir.join(format!("package-{}.slpk", num_entries));
+            package.write_to_file(&path).unwrap();
+            let summary = unpack_with_options(&path, &UnpackOptions::new().threads(32)).unwrap();
+            unpacked.push(summary.entries_unpacked);
+        }
+
+        assert_eq!(unpacked, [0, 6, 32]);
+        assert_eq!(EntryQueue::new(0).next(), None);
+    }
+
+    #[test]
+    fn every_position_is_handed_out_once_whatever_the_count() {
+        for len in COUNTS {
+            let mut taken = hand_out(len);
+            let handed_out = taken.len();
+            taken.sort();
+            taken.dedup();
+
+            assert_eq!(handed_out, len, "{} entries", len);
+            assert_eq!(taken, (0..len).collect::<Vec<_>>(), "{} entries", len);
+        }
+    }
+
+    #[test]
+    fn every_entry_is_unpacked_once_whatever_the_count() {
+        let dir = TempDir::new("scheduler-counts");
+        for len in COUNTS {
+            let names: BTreeSet<String> = (0..len)
+                .map(|i| format!("nodes/{}/geometries/0.bin", i))
+                .collect();
+            let mut package = SyntheticPackage::new();
+            for name in &names {
+                package = package.entry(name, name.as_bytes());
+            }
+            let path = dir.join(format!("package-{}.slpk", len));
+            package.write_to_file(&path).unwrap();
+            let summary =
+                unpack_with_options(&path, &UnpackOptions::new().threads(THREADS)).unwrap();
+            let folder = dir.join(format!("package-{}", len));
+            let unpacked = files_below(&folder);
+            let contents_match = names.iter().all(|name| {
+                std::fs::read(folder.join(name)).ok().as_deref() == Some(name.as_bytes())
+            });
+
+            assert_eq!(summary.entries_unpacked, len, "{} entries", len);
+            assert_eq!(unpacked, names, "{} entries", len);
+            assert!(contents_match, "{} entries", len);
+        }
+    }
+}
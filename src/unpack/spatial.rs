@@ -0,0 +1,234 @@
+// Selects the nodes whose bounding volume meets a box, for unpacking the
+// part of a layer around a known location rather than all of it. The box
+// is given in the spatial reference of the layer, or in WGS84 longitude and
+// latitude, and only its x and y are compared, so a node meets it whatever
+// its height. A node's oriented bounding box is compared by the rectangle
+// around its corners, as seen from above, and a bounding sphere by the
+// circle it makes, which is exact in a projected reference. In a geographic
+// reference, or when the box is in WGS84 and the layer isn't, each volume is
+// taken by the rectangle around its outline in longitude and latitude, as
+// `slpkg footprints` draws it. Nodes without a bounding volume are kept, as
+// they may well be in the box.
+//
+// The ancestors of every node kept are kept too, so that the hierarchy
+// still leads from the root to each of them. As with `--max-level`,
+// everything below the folder of a node which is kept is unpacked, and
+// everything which isn't below `nodes/` is unpacked whatever the box.
+
+use super::levels::NodeFolders;
+use crate::footprint::Projector;
+use crate::geo;
+use crate::geo::Crs;
+use crate::i3s;
+use crate::i3s::BoundingVolume;
+use failure::Error;
+use std::io::{Read, Seek};
+use std::str::FromStr;
+use zip::ZipArchive;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "'{}' is not a box: give it as xmin,ymin,xmax,ymax, with each minimum below its maximum",
+    _0
+)]
+pub struct InvalidBox(String);
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "The spatial reference {} of the layer can't be converted to WGS84, so the box needs to be given in it",
+    _0
+)]
+pub struct NoWgs84Box(String);
+
+/// A box around an area, from its lower left corner to its upper right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    /// The left edge.
+    pub xmin: f64,
+    /// The bottom edge.
+    pub ymin: f64,
+    /// The right edge.
+    pub xmax: f64,
+    /// The top edge.
+    pub ymax: f64,
+}
+
+impl FromStr for BoundingBox {
+    type Err = InvalidBox;
+
+    fn from_str(s: &str) -> Result<BoundingBox, InvalidBox> {
+        let invalid = || InvalidBox(s.to_string());
+        let bounds = s
+            .split(',')
+            .map(|bound| bound.trim().parse::<f64>().ok().filter(|b| b.is_finite()))
+            .collect::<Option<Vec<f64>>>()
+            .ok_or_else(invalid)?;
+        match bounds.as_slice() {
+            &[xmin, ymin, xmax, ymax] if xmin <= xmax && ymin <= ymax => Ok(BoundingBox {
+                xmin,
+                ymin,
+                xmax,
+                ymax,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl BoundingBox {
+    /// Whether the rectangle around `points` meets the box.
+    fn meets_points(&self, points: &[(f64, f64)]) -> bool {
+        if points.is_empty() {
+            return true;
+        }
+        let (mut xmin, mut ymin) = (f64::INFINITY, f64::INFINITY);
+        let (mut xmax, mut ymax) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in points {
+            xmin = xmin.min(x);
+            ymin = ymin.min(y);
+            xmax = xmax.max(x);
+            ymax = ymax.max(y);
+        }
+        xmin <= self.xmax && xmax >= self.xmin && ymin <= self.ymax && ymax >= self.ymin
+    }
+
+    /// Whether the circle around `center` meets the box.
+    fn meets_circle(&self, center: [f64; 3], radius: f64) -> bool {
+        let dx = center[0] - center[0].max(self.xmin).min(self.xmax);
+        let dy = center[1] - center[1].max(self.ymin).min(self.ymax);
+        dx * dx + dy * dy <= radius * radius
+    }
+}
+
+/// The folders of the nodes which meet a box, and of their ancestors, in
+/// each layer.
+#[derive(Debug, Default)]
+pub struct NodesInBox {
+    /// The number of nodes kept, in every layer.
+    pub kept: usize,
+    /// The number of nodes left out, in every layer.
+    pub left_out: usize,
+    folders: NodeFolders,
+}
+
+impl NodesInBox {
+    pub fn skips(&self, entry_name: &str) -> bool {
+        self.folders.skips(entry_name)
+    }
+}
+
+/// Finds the nodes of one layer which meet `bbox`, given in WGS84 if
+/// `wgs84` is set, and their ancestors, adding them to `nodes_in_box`.
+pub fn find_nodes_in_box<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    layer: &i3s::SceneLayer,
+    bbox: &BoundingBox,
+    wgs84: bool,
+    nodes_in_box: &mut NodesInBox,
+) -> Result<(), Error> {
+    let crs = Crs::from_wkid(layer.wkid.unwrap_or(0));
+    if wgs84 && crs.to_wgs84(0.0, 0.0).is_none() {
+        let wkid = layer
+            .wkid
+            .map_or_else(|| "(none given)".to_string(), |wkid| wkid.to_string());
+        return Err(Error::from(NoWgs84Box(wkid)));
+    }
+    let projector = Projector::for_layer(layer);
+    let meets = |volume: &BoundingVolume| match volume {
+        _ if wgs84 || crs.is_geographic() => bbox.meets_points(&projector.footprint(volume)),
+        BoundingVolume::Mbs { center, radius } => bbox.meets_circle(*center, *radius),
+        BoundingVolume::Obb {
+            center,
+            half_size,
+            quaternion,
+        } => {
+            let mut corners = Vec::with_capacity(8);
+            for &sx in &[-1.0, 1.0] {
+                for &sy in &[-1.0, 1.0] {
+                    for &sz in &[-1.0, 1.0] {
+                        let offset = geo::rotate(
+                            *quaternion,
+                            [sx * half_size[0], sy * half_size[1], sz * half_size[2]],
+                        );
+                        corners.push((center[0] + offset[0], center[1] + offset[1]));
+                    }
+                }
+            }
+            bbox.meets_points(&corners)
+        }
+    };
+
+    let hierarchy = i3s::load_hierarchy(archive, layer)?;
+    let mut kept = vec![false; hierarchy.nodes.len()];
+    for (idx, node) in hierarchy.nodes.iter().enumerate() {
+        if !node.volume.as_ref().map_or(true, meets) {
+            continue;
+        }
+        let mut ancestor = Some(idx);
+        while let Some(idx) = ancestor.filter(|&idx| !kept[idx]) {
+            kept[idx] = true;
+            ancestor = hierarchy.nodes[idx].parent;
+        }
+    }
+    let kept_nodes: Vec<_> = hierarchy
+        .nodes
+        .iter()
+        .zip(&kept)
+        .filter(|(_, &kept)| kept)
+        .map(|(node, _)| node)
+        .collect();
+    nodes_in_box.kept += kept_nodes.len();
+    nodes_in_box.left_out += hierarchy.nodes.len() - kept_nodes.len();
+    nodes_in_box.folders.keep(layer, kept_nodes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::SyntheticPackage;
+    use std::io::Cursor;
+
+    #[test]
+    fn keeps_the_nodes_in_the_box_and_their_ancestors() {
+        let package = SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json",
+                br#"{"nodePages":{},"spatialReference":{"wkid":32631}}"#,
+            )
+            .entry(
+                "nodepages/0.json",
+                br#"{"nodes":[
+                    {"index":0,"children":[1,2,3],"mbs":[500,500,0,800]},
+                    {"index":1,"parentIndex":0,"children":[4],"mbs":[100,100,0,50]},
+                    {"index":2,"parentIndex":0,"obb":{"center":[900,900,0],"halfSize":[10,10,10],"quaternion":[0,0,0,1]}},
+                    {"index":3,"parentIndex":0},
+                    {"index":4,"parentIndex":1,"mbs":[120,120,0,5]}]}"#,
+            )
+            .to_bytes()
+            .unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(package)).unwrap();
+        let layer = i3s::read_scene_layer(&mut archive).unwrap();
+        let bbox = "110,110,130,130".parse::<BoundingBox>().unwrap();
+        let mut nodes_in_box = NodesInBox::default();
+        find_nodes_in_box(&mut archive, &layer, &bbox, false, &mut nodes_in_box).unwrap();
+
+        // Node 2 is far off, and node 3 has no volume to tell.
+        assert_eq!((nodes_in_box.kept, nodes_in_box.left_out), (4, 1));
+        assert!(!nodes_in_box.skips("nodes/0/3dNodeIndexDocument.json.gz"));
+        assert!(!nodes_in_box.skips("nodes/4/geometries/0.bin.gz"));
+        assert!(!nodes_in_box.skips("nodes/3/geometries/0.bin.gz"));
+        assert!(nodes_in_box.skips("nodes/2/geometries/0.bin.gz"));
+        assert!(!nodes_in_box.skips("nodepages/0.json"));
+
+        assert!((BoundingBox {
+            xmin: 0.0,
+            ymin: 0.0,
+            xmax: 10.0,
+            ymax: 10.0
+        })
+        .meets_circle([13.0, 14.0, 0.0], 5.0));
+        assert!("1,2,3".parse::<BoundingBox>().is_err());
+        assert!("3,0,1,2".parse::<BoundingBox>().is_err());
+    }
+}
@@ -0,0 +1,97 @@
+// Spools a package read from a stream which can't seek, such as standard
+// input fed by `curl`, into a temporary file, since a zip is read from its
+// central directory at its end. The package is then unpacked from that file
+// as from any other, with every worker thread reading through a file handle
+// of its own. The file is removed once the unpack is done, whether or not it
+// succeeded, and when spooling fails part way. It is written to the
+// temporary folder of the system, which `TMPDIR` sets on Unix and `TEMP` on
+// Windows, so that folder needs room for the whole package.
+
+use failure::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many bytes are spooled between reports of how far spooling got.
+const REPORT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+/// Numbers the packages spooled by this process.
+static SPOOLED: AtomicUsize = AtomicUsize::new(0);
+
+/// A package spooled into a temporary file, which is removed when this is
+/// dropped.
+#[derive(Debug)]
+pub struct SpooledPackage {
+    path: PathBuf,
+    pub bytes: u64,
+}
+
+impl SpooledPackage {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledPackage {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Copies `stream` to its end into a new file in `folder`.
+pub fn spool(mut stream: impl Read, folder: &Path) -> Result<SpooledPackage, Error> {
+    let path = folder.join(format!(
+        "slpkg-stream-{}-{}.slpk",
+        std::process::id(),
+        SPOOLED.fetch_add(1, Ordering::SeqCst)
+    ));
+    let mut file: File = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    // The file is removed from here on if anything fails.
+    let mut spooled = SpooledPackage { path, bytes: 0 };
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut next_report = REPORT_INTERVAL;
+    loop {
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::from(e)),
+        };
+        file.write_all(&buffer[..read])?;
+        spooled.bytes += read as u64;
+        if spooled.bytes >= next_report {
+            info!("Spooled {} MiB so far", spooled.bytes >> 20);
+            next_report += REPORT_INTERVAL;
+        }
+    }
+    file.sync_all()?;
+    info!(
+        "Spooled {:.1} MiB to {}",
+        spooled.bytes as f64 / (1024.0 * 1024.0),
+        spooled.path.to_string_lossy()
+    );
+    Ok(spooled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn the_spooled_file_is_removed_when_dropped() {
+        let dir = TempDir::new("spool");
+        let spooled = spool(&[7u8; 3000][..], &dir).unwrap();
+        let contents = std::fs::read(spooled.path()).unwrap();
+        let path = spooled.path().to_path_buf();
+        drop(spooled);
+        let removed = !path.exists();
+
+        assert_eq!(contents, vec![7u8; 3000]);
+        assert!(removed);
+    }
+}
@@ -0,0 +1,147 @@
+// Files can be staged: written under a temporary name next to their final
+// path, and renamed into place once they are complete, so that a file at its
+// final path is never one which was cut short by a crash. Each worker thread
+// stages under its own suffix, so anything left with that suffix is evidence
+// of an interrupted run rather than something the package holds.
+
+use crate::glob;
+use failure::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+const PARTIAL_SUFFIX: &str = ".partial-";
+
+/// Where `worker` stages the file for `target`.
+pub fn staging_path(target: &Path, worker: usize) -> PathBuf {
+    let mut name = OsString::from(target.as_os_str());
+    name.push(format!("{}{}", PARTIAL_SUFFIX, worker));
+    PathBuf::from(name)
+}
+
+pub fn is_staging_path(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    match name.rfind(PARTIAL_SUFFIX) {
+        Some(at) => {
+            let worker = &name[at + PARTIAL_SUFFIX.len()..];
+            !worker.is_empty() && worker.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Whether a rename failed because its target is on another filesystem.
+fn crosses_devices(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    const CROSSES_DEVICES: i32 = libc::EXDEV;
+    // ERROR_NOT_SAME_DEVICE
+    #[cfg(not(unix))]
+    const CROSSES_DEVICES: i32 = 17;
+    error.raw_os_error() == Some(CROSSES_DEVICES)
+}
+
+/// Moves a staged file to its final path, replacing whatever is there.
+pub fn rename_into_place(staged: &Path, target: &Path) -> std::io::Result<()> {
+    let error = match std::fs::rename(staged, target) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if crosses_devices(&error) {
+        // Only when the target is on another filesystem than its folder,
+        // such as a mount point. Copying can't be atomic, but the file is
+        // complete before the copy starts.
+        std::fs::copy(staged, target)?;
+        return std::fs::remove_file(staged);
+    }
+    if cfg!(windows) && target.symlink_metadata().is_ok() {
+        // Windows won't replace a file which is read-only, or which another
+        // process has open without sharing it for deletion.
+        std::fs::remove_file(target)?;
+        return std::fs::rename(staged, target);
+    }
+    Err(error)
+}
+
+/// Runs `write` on the staging path of `target` and moves the result into
+/// place, or runs it on `target` itself when `worker` is `None`. A staged
+/// file is removed again if writing it fails.
+pub fn write_staged<T>(
+    target: &Path,
+    worker: Option<usize>,
+    write: impl FnOnce(&Path) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let worker = match worker {
+        Some(worker) => worker,
+        None => return write(target),
+    };
+    let staged = staging_path(target, worker);
+    match write(&staged) {
+        Ok(result) => {
+            rename_into_place(&staged, target)?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged);
+            Err(e)
+        }
+    }
+}
+
+/// Removes the staged files an interrupted run left below `folder`,
+/// returning how many there were.
+pub fn remove_leftovers(folder: &Path) -> Result<usize, Error> {
+    if !folder.is_dir() {
+        return Ok(0);
+    }
+    let mut files = Vec::new();
+    glob::walk(folder, None, &mut files)?;
+    let mut removed = 0;
+    for file in files.iter().filter(|file| is_staging_path(file)) {
+        std::fs::remove_file(file)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+
+    #[test]
+    fn staged_files_appear_complete_or_not_at_all() {
+        let dir = TempDir::new("staging");
+        let target = dir.join("0.bin");
+        std::fs::write(&target, b"old").unwrap();
+
+        let failed: Result<(), Error> = write_staged(&target, Some(3), |staged| {
+            assert_eq!(staged, dir.join("0.bin.partial-3"));
+            std::fs::write(staged, b"half")?;
+            Err(format_err!("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read(&target).unwrap(), b"old");
+        assert!(!staging_path(&target, 3).exists());
+
+        write_staged(&target, Some(3), |staged| {
+            Ok(std::fs::write(staged, b"new")?)
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+
+        // What a crash leaves behind, and files which only look like it.
+        std::fs::create_dir_all(dir.join("nodes/0")).unwrap();
+        for name in ["nodes/0/0.jpg.partial-0", "1.bin.partial-12"] {
+            std::fs::write(dir.join(name), b"cut short").unwrap();
+        }
+        for name in ["2.bin.partial-", "3.partial-notes.txt"] {
+            std::fs::write(dir.join(name), b"kept").unwrap();
+        }
+        let removed = remove_leftovers(&dir).unwrap();
+        let kept = dir.join("3.partial-notes.txt").exists() && dir.join("2.bin.partial-").exists();
+        assert_eq!(removed, 2);
+        assert!(kept);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::json::Value;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const UNASSIGNED_FOLDER: &str = "_unassigned";
+
+#[derive(Debug, Fail)]
+pub enum SublayerError {
+    #[fail(display = "Only Building scene layer packages can be split into sublayers")]
+    NotABuildingLayer,
+}
+
+/// Maps the `sublayers/<id>/` folders of a Building Scene Layer package to
+/// one output folder per sublayer, named after the sublayer.
+#[derive(Debug, Default)]
+pub struct SublayerRouting {
+    folder_of_id: HashMap<String, String>,
+}
+
+fn folder_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    if cleaned.is_empty() || cleaned == ".." {
+        "_".to_string()
+    } else {
+        cleaned
+    }
+}
+
+impl SublayerRouting {
+    /// Builds the routing from the building layer document. Group layers
+    /// are walked, but only their leaf sublayers get folders.
+    pub fn from_layer_document(document: &Value) -> Result<SublayerRouting, SublayerError> {
+        if document.get("layerType").and_then(Value::as_str) != Some("Building") {
+            return Err(SublayerError::NotABuildingLayer);
+        }
+
+        let mut leaves = Vec::new();
+        let mut pending: Vec<&Value> = document
+            .get("sublayers")
+            .and_then(Value::as_array)
+            .map(|s| s.iter().collect())
+            .unwrap_or_default();
+        while let Some(sublayer) = pending.pop() {
+            match sublayer.get("sublayers").and_then(Value::as_array) {
+                Some(children) => pending.extend(children.iter()),
+                None => leaves.push(sublayer),
+            }
+        }
+
+        let mut routing = SublayerRouting::default();
+        let mut used_names = HashSet::new();
+        // Sort by id so that collisions are disambiguated the same way each run.
+        let mut leaves: Vec<(String, String)> = leaves
+            .into_iter()
+            .filter_map(|s| {
+                let id = match s.get("id")? {
+                    Value::Number(n) => format!("{}", n),
+                    Value::String(id) => id.clone(),
+                    _ => return None,
+                };
+                let name = s.get("name").and_then(Value::as_str).unwrap_or(&id);
+                Some((id.clone(), folder_name(name)))
+            })
+            .collect();
+        leaves.sort();
+        for (id, name) in leaves {
+            let name = if used_names.contains(&name) || name == UNASSIGNED_FOLDER {
+                format!("{}_{}", name, id)
+            } else {
+                name
+            };
+            used_names.insert(name.clone());
+            routing.folder_of_id.insert(id, name);
+        }
+        Ok(routing)
+    }
+
+    /// Returns the path, relative to the output folder, that an entry is
+    /// extracted to, and whether it could be attributed to a sublayer.
+    pub fn route(&self, entry_path: &Path) -> (PathBuf, bool) {
+        let mut components = entry_path.components();
+        let first = components.next();
+        let second = components.next();
+        match (first, second) {
+            (Some(Component::Normal(sublayers)), Some(Component::Normal(id)))
+                if sublayers == "sublayers" =>
+            {
+                let rest = components.as_path();
+                match self.folder_of_id.get(&*id.to_string_lossy()) {
+                    Some(folder) => (Path::new(folder).join(rest), true),
+                    None => (Path::new(UNASSIGNED_FOLDER).join(id).join(rest), false),
+                }
+            }
+            // Building level resources, such as the building layer document
+            // and its statistics, stay at the root of the output folder.
+            _ => (entry_path.to_path_buf(), true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    fn routing() -> SublayerRouting {
+        let document = json::parse(
+            r#"{"layerType":"Building","sublayers":[
+                {"id":0,"name":"Overview","layerType":"3DObject"},
+                {"id":100,"name":"FullModel","layerType":"group","sublayers":[
+                    {"id":200,"name":"Architectural","layerType":"group","sublayers":[
+                        {"id":1,"name":"Doors","layerType":"3DObject"},
+                        {"id":2,"name":"Generic/Models","layerType":"3DObject"}]},
+                    {"id":300,"name":"Structural","layerType":"group","sublayers":[
+                        {"id":3,"name":"Generic/Models","layerType":"3DObject"}]}]}]}"#,
+        )
+        .unwrap();
+        SublayerRouting::from_layer_document(&document).unwrap()
+    }
+
+    #[test]
+    fn routes_sublayer_entries_by_name() {
+        let routing = routing();
+        assert_eq!(
+            routing.route(Path::new("sublayers/1/nodes/0/geometries/0.bin.gz")),
+            (PathBuf::from("Doors/nodes/0/geometries/0.bin.gz"), true)
+        );
+        assert_eq!(
+            routing.route(Path::new("sublayers/0/3dSceneLayer.json.gz")),
+            (PathBuf::from("Overview/3dSceneLayer.json.gz"), true)
+        );
+        assert_eq!(
+            routing.route(Path::new("3dSceneLayer.json.gz")),
+            (PathBuf::from("3dSceneLayer.json.gz"), true)
+        );
+    }
+
+    #[test]
+    fn colliding_names_are_disambiguated() {
+        let routing = routing();
+        assert_eq!(
+            routing.route(Path::new("sublayers/2/x")).0,
+            PathBuf::from("Generic_Models/x")
+        );
+        assert_eq!(
+            routing.route(Path::new("sublayers/3/x")).0,
+            PathBuf::from("Generic_Models_3/x")
+        );
+    }
+
+    #[test]
+    fn unknown_sublayers_are_unassigned() {
+        assert_eq!(
+            routing().route(Path::new(
+                "sublayers/42/nodes/0/3dNodeIndexDocument.json.gz"
+            )),
+            (
+                PathBuf::from("_unassigned/42/nodes/0/3dNodeIndexDocument.json.gz"),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_non_building_layers() {
+        let document = json::parse(r#"{"layerType":"3DObject"}"#).unwrap();
+        assert!(SublayerRouting::from_layer_document(&document).is_err());
+    }
+}
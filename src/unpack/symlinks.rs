@@ -0,0 +1,236 @@
+// Entries which hold a symbolic link, as `zip -y` packs them on Unix: the
+// mode in their external attributes says they are links, and their content is
+// the path they point to. Written out as they are, they would become small
+// files holding a path, which breaks any resource that refers to them. Links
+// are therefore left out with a warning by default. With `allow_symlinks`
+// they are recreated on Unix, as long as the target stays inside the folder
+// the entry is unpacked into. Windows always leaves them out, because it
+// needs special rights to create links.
+//
+// The target is checked against the folders the link is really in, since a
+// link recreated earlier may lead somewhere else. It may only climb with `..`
+// at its start, and not past the root of the folder. After that it may only
+// go down, and the path it ends at must pass the same containment check as
+// the path of any entry. Each link that passes this leads inside the folder,
+// and a path going down through links which lead inside can't leave it
+// either. Absolute targets, and targets with a `..` after their first folder
+// name, are always refused.
+
+use failure::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const FILE_TYPE_MASK: u32 = 0o170_000;
+const SYMLINK: u32 = 0o120_000;
+
+/// The longest target read, in bytes, which is the longest path Linux
+/// allows.
+const MAX_TARGET_LEN: usize = 4096;
+
+/// What came of recreating a link.
+#[derive(Debug, PartialEq)]
+pub enum Linked {
+    Created,
+    /// The link was left out, for this reason.
+    Refused(String),
+}
+
+/// Whether an entry whose external attributes give `unix_mode` is a
+/// symbolic link.
+pub fn is_symlink(unix_mode: Option<u32>) -> bool {
+    unix_mode.map(|mode| mode & FILE_TYPE_MASK) == Some(SYMLINK)
+}
+
+/// Where a link in the folder `link_folder` which points to `target` leads,
+/// with both relative to the root of the unpack folder, or why it would
+/// leave the folder.
+fn resolve_target(link_folder: &Path, target: &str) -> Result<PathBuf, String> {
+    if target.is_empty() {
+        return Err("its target is empty".to_string());
+    }
+    if target.contains('\0') {
+        return Err("its target contains a NUL character".to_string());
+    }
+    let bytes = target.as_bytes();
+    if target.starts_with('/')
+        || target.starts_with('\\')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+    {
+        return Err(format!("its target {} is absolute", target));
+    }
+    let mut resolved = link_folder.to_path_buf();
+    let mut climbing = true;
+    for component in target.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." if climbing => {
+                if !resolved.pop() {
+                    return Err(format!(
+                        "its target {} leads out of the output folder",
+                        target
+                    ));
+                }
+            }
+            ".." => {
+                return Err(format!(
+                    "its target {} climbs back up after going down",
+                    target
+                ))
+            }
+            _ => {
+                climbing = false;
+                resolved.push(component);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// Recreates the link that `entry` holds at `link_path`, below `root`, unless
+/// its target would lead out of `root`.
+pub fn recreate(entry: impl Read, root: &Path, link_path: &Path) -> Result<Linked, Error> {
+    let mut target = Vec::new();
+    entry
+        .take(MAX_TARGET_LEN as u64 + 1)
+        .read_to_end(&mut target)?;
+    if target.len() > MAX_TARGET_LEN {
+        return Ok(Linked::Refused(format!(
+            "its target is longer than {} bytes",
+            MAX_TARGET_LEN
+        )));
+    }
+    let target = match String::from_utf8(target) {
+        Ok(target) => target,
+        Err(_) => return Ok(Linked::Refused("its target isn't UTF-8".to_string())),
+    };
+    if !cfg!(unix) {
+        return Ok(Linked::Refused(
+            "symbolic links are only recreated on Unix".to_string(),
+        ));
+    }
+    let link_folder = link_path.parent().unwrap_or(root);
+    std::fs::create_dir_all(link_folder)?;
+    let root = std::fs::canonicalize(root)?;
+    let link_folder = match std::fs::canonicalize(link_folder)?.strip_prefix(&root) {
+        Ok(link_folder) => link_folder.to_path_buf(),
+        Err(_) => {
+            return Ok(Linked::Refused(
+                "its folder is reached through a link out of the output folder".to_string(),
+            ))
+        }
+    };
+    let resolved = match resolve_target(&link_folder, &target) {
+        Ok(resolved) => resolved,
+        Err(reason) => return Ok(Linked::Refused(reason)),
+    };
+    if super::path_in_folder(&root, &resolved).is_err() {
+        return Ok(Linked::Refused(format!(
+            "its target {} leads out of the output folder",
+            target
+        )));
+    }
+    match std::fs::symlink_metadata(link_path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => std::fs::remove_file(link_path)?,
+        Ok(_) => {
+            return Ok(Linked::Refused(
+                "something other than a link is already in its place".to_string(),
+            ))
+        }
+        Err(_) => {}
+    }
+    create(&target, link_path)?;
+    Ok(Linked::Created)
+}
+
+#[cfg(unix)]
+fn create(target: &str, link_path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link_path)
+}
+
+/// Windows never gets this far.
+#[cfg(not(unix))]
+fn create(_target: &str, _link_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::raw::{RawEntry, RawWriter};
+    use crate::synthetic::TempDir;
+    use crate::unpack::{unpack_with_options, UnpackOptions};
+
+    #[test]
+    fn targets_stay_inside_the_folder() {
+        let folder = Path::new("nodes/0/textures");
+        assert_eq!(
+            resolve_target(folder, "../../1/textures/0.jpg"),
+            Ok(PathBuf::from("nodes/1/textures/0.jpg"))
+        );
+        assert_eq!(
+            resolve_target(folder, "./0.jpg"),
+            Ok(PathBuf::from("nodes/0/textures/0.jpg"))
+        );
+        assert!(resolve_target(folder, "../../../../etc/passwd").is_err());
+        assert!(resolve_target(folder, "/etc/passwd").is_err());
+        assert!(resolve_target(folder, "C:\\Windows").is_err());
+        assert!(resolve_target(folder, "sub/../../x").is_err());
+        assert!(is_symlink(Some(0o120_777)));
+        assert!(!is_symlink(Some(0o100_644)));
+        assert!(!is_symlink(None));
+    }
+
+    #[test]
+    fn links_are_left_out_unless_allowed() {
+        let dir = TempDir::new("symlinks");
+        let path = dir.join("package.slpk");
+        let mut writer = RawWriter::new(std::io::Cursor::new(Vec::new()));
+        let entries: [(&str, &[u8], u32); 4] = [
+            ("nodes/0/textures/0.jpg", b"jpeg", 0o100_644),
+            (
+                "nodes/1/textures/0.jpg",
+                b"../../0/textures/0.jpg",
+                0o120_777,
+            ),
+            (
+                "nodes/1/textures/1.jpg",
+                b"../../../../etc/passwd",
+                0o120_777,
+            ),
+            ("nodes/1/textures/2.jpg", b"/etc/passwd", 0o120_777),
+        ];
+        for (name, contents, mode) in &entries {
+            let mut entry = RawEntry::default();
+            entry.name = name.as_bytes().to_vec();
+            entry.version_made_by = 3 << 8;
+            entry.external_attributes = mode << 16;
+            let data = entry.replace_contents(contents).unwrap();
+            writer.write_entry(&entry, &mut data.as_slice()).unwrap();
+        }
+        std::fs::write(&path, writer.finish(b"").unwrap().into_inner()).unwrap();
+        let unpacked = dir.join("package/nodes/1/textures");
+
+        let skipped = unpack_with_options(&path, &UnpackOptions::new().threads(1)).unwrap();
+        let written_by_default = unpacked.exists();
+        let allowed =
+            unpack_with_options(&path, &UnpackOptions::new().threads(1).allow_symlinks(true))
+                .unwrap();
+        let link = std::fs::symlink_metadata(unpacked.join("0.jpg"));
+        let linked = std::fs::read(unpacked.join("0.jpg"));
+        let refused = (
+            unpacked.join("1.jpg").symlink_metadata().is_err(),
+            unpacked.join("2.jpg").symlink_metadata().is_err(),
+        );
+
+        assert_eq!((skipped.entries_unpacked, skipped.entries_skipped), (1, 3));
+        assert!(!written_by_default);
+        assert_eq!(refused, (true, true));
+        if cfg!(unix) {
+            assert_eq!((allowed.symlinks_created, allowed.entries_skipped), (1, 2));
+            assert!(link.unwrap().file_type().is_symlink());
+            assert_eq!(linked.unwrap(), b"jpeg");
+        } else {
+            assert_eq!((allowed.symlinks_created, allowed.entries_skipped), (0, 3));
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Writes the unpacked files as a tar stream rather than into a folder, for
+// pipelines which hand the contents straight to another tool without
+// touching the local disk. slpkg can't depend on the `tar` crate, so this
+// writes the ustar format itself, which every tar reads: a 512 byte header
+// before the contents of each file, padded to a block, and two empty blocks
+// at the end. A path which doesn't fit the 100 bytes of the name field is
+// split into a prefix and a name at one of its slashes, as ustar allows, and
+// one which can't be split, or a file of 8 GiB or more, whose size doesn't
+// fit its field, gets a pax extended header saying what does, as GNU and
+// BSD tar both write and read.
+//
+// The header says how large a file is before its contents, which isn't
+// known for an entry being decompressed or reformatted until it has been, so
+// the contents of each file are held in memory until they are complete. A
+// tar stream is written in order, so unpacking to one runs on a single
+// worker, and an entry which fails leaves nothing of itself in the stream
+// for a retry to clash with.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BLOCK_SIZE: usize = 512;
+const NAME_LEN: usize = 100;
+const PREFIX_LEN: usize = 155;
+/// The largest size the 11 octal digits of the size field can hold.
+const MAX_USTAR_SIZE: u64 = 0o77_777_777_777;
+
+/// Where a tar stream of the unpacked files is written.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TarTarget {
+    /// Standard output.
+    Stdout,
+    /// This file.
+    File(PathBuf),
+}
+
+/// The target a command line argument names, where `-` is standard output.
+impl<T: AsRef<OsStr> + ?Sized> From<&T> for TarTarget {
+    fn from(arg: &T) -> TarTarget {
+        let arg = arg.as_ref();
+        if arg == "-" {
+            TarTarget::Stdout
+        } else {
+            TarTarget::File(PathBuf::from(arg))
+        }
+    }
+}
+
+impl TarTarget {
+    /// Opens the stream the tar is written to.
+    pub fn open(&self) -> io::Result<TarStream> {
+        let inner: Box<dyn Write + Send> = match self {
+            TarTarget::Stdout => Box::new(io::stdout()),
+            TarTarget::File(path) => Box::new(File::create(path)?),
+        };
+        Ok(TarWriter::new(Box::new(BufWriter::new(inner))))
+    }
+}
+
+/// The tar stream an unpack writes to.
+pub type TarStream = TarWriter<Box<dyn Write + Send>>;
+
+/// Writes files and folders to a tar stream, in the order they are
+/// appended.
+pub struct TarWriter<W: Write> {
+    inner: W,
+}
+
+/// What the header of a file or folder records.
+struct Header<'a> {
+    path: &'a str,
+    size: u64,
+    modified: Option<SystemTime>,
+    mode: u32,
+    kind: u8,
+}
+
+/// Writes `value` as octal digits filling `field` but for its last byte,
+/// which is left as NUL.
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+/// A pax record, which starts with its own length in decimal.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {}={}\n", key, value);
+    let mut len = rest.len() + 1;
+    while format!("{}{}", len, rest).len() != len {
+        len += 1;
+    }
+    format!("{}{}", len, rest)
+}
+
+/// Splits `path` into the prefix and name fields of a ustar header, if it
+/// fits them.
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= NAME_LEN {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(at, _)| (&path[..at], &path[at + 1..]))
+        .find(|(prefix, name)| {
+            prefix.len() <= PREFIX_LEN && name.len() <= NAME_LEN && !name.is_empty()
+        })
+}
+
+/// `path` with forward slashes, as tar names its files whatever the
+/// operating system.
+fn tar_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl<W: Write> TarWriter<W> {
+    pub fn new(inner: W) -> TarWriter<W> {
+        TarWriter { inner }
+    }
+
+    fn write_header(&mut self, header: &Header) -> io::Result<()> {
+        let split = split_path(header.path);
+        let mut records = String::new();
+        if split.is_none() {
+            records.push_str(&pax_record("path", header.path));
+        }
+        if header.size > MAX_USTAR_SIZE {
+            records.push_str(&pax_record("size", &header.size.to_string()));
+        }
+        if !records.is_empty() {
+            self.write_header(&Header {
+                path: "PaxHeader",
+                size: records.len() as u64,
+                modified: header.modified,
+                mode: 0o644,
+                kind: b'x',
+            })?;
+            self.write_padded(records.as_bytes())?;
+        }
+
+        // What didn't fit is in the pax header, so what is written here is
+        // only what an old tar would fall back on.
+        let (prefix, name) = split.unwrap_or(("", header.path));
+        let name = &name.as_bytes()[name.len().saturating_sub(NAME_LEN)..];
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..name.len()].copy_from_slice(name);
+        octal(&mut block[100..108], u64::from(header.mode & 0o7777));
+        octal(&mut block[108..116], 0);
+        octal(&mut block[116..124], 0);
+        octal(&mut block[124..136], header.size.min(MAX_USTAR_SIZE));
+        let modified = header
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+        octal(&mut block[136..148], modified);
+        block[156] = header.kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        // The checksum is taken with its own field as spaces.
+        block[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = block.iter().map(|&b| u32::from(b)).sum();
+        octal(&mut block[148..155], u64::from(checksum));
+        block[155] = b' ';
+        self.inner.write_all(&block)
+    }
+
+    /// Writes `contents`, padded with zeroes to a whole block.
+    fn write_padded(&mut self, contents: &[u8]) -> io::Result<()> {
+        self.inner.write_all(contents)?;
+        let padding = (BLOCK_SIZE - contents.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        self.inner.write_all(&[0; BLOCK_SIZE][..padding])
+    }
+
+    /// Appends a file at `path`, relative to the root of the stream, with
+    /// the permissions `mode` gives, or read and write for its owner and
+    /// read for anyone else.
+    pub fn append_file(
+        &mut self,
+        path: &Path,
+        contents: &[u8],
+        modified: Option<SystemTime>,
+        mode: Option<u32>,
+    ) -> io::Result<()> {
+        self.write_header(&Header {
+            path: &tar_path(path),
+            size: contents.len() as u64,
+            modified,
+            mode: mode.unwrap_or(0o644),
+            kind: b'0',
+        })?;
+        self.write_padded(contents)
+    }
+
+    /// Appends a folder at `path`, relative to the root of the stream.
+    pub fn append_folder(&mut self, path: &Path, modified: Option<SystemTime>) -> io::Result<()> {
+        self.write_header(&Header {
+            path: &(tar_path(path) + "/"),
+            size: 0,
+            modified,
+            mode: 0o755,
+            kind: b'5',
+        })
+    }
+
+    /// Ends the stream, flushing it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(&[0; 2 * BLOCK_SIZE])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use crate::unpack::{unpack_with_options, JsonFormatting, UnpackOptions};
+    use std::time::Duration;
+
+    /// The files of a tar stream, with their contents, as a tar would read
+    /// them, checking the checksum of each header.
+    fn read_tar(stream: &[u8]) -> Vec<(String, u8, Vec<u8>)> {
+        let field = |block: &[u8], range: std::ops::Range<usize>| {
+            let bytes = &block[range];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8(bytes[..end].to_vec()).unwrap()
+        };
+        let mut files = Vec::new();
+        let mut pax_path = None;
+        let mut at = 0;
+        while stream[at..at + BLOCK_SIZE].iter().any(|&b| b != 0) {
+            let block = &stream[at..at + BLOCK_SIZE];
+            let mut blank = block.to_vec();
+            blank[148..156].copy_from_slice(b"        ");
+            let checksum: u32 = blank.iter().map(|&b| u32::from(b)).sum();
+            assert_eq!(
+                u32::from_str_radix(field(block, 148..155).trim(), 8),
+                Ok(checksum)
+            );
+            let size = u64::from_str_radix(&field(block, 124..135), 8).unwrap() as usize;
+            let contents = stream[at + BLOCK_SIZE..at + BLOCK_SIZE + size].to_vec();
+            at += BLOCK_SIZE + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+            let kind = block[156];
+            if kind == b'x' {
+                let records = String::from_utf8(contents).unwrap();
+                pax_path = Some(
+                    records
+                        .split_once("path=")
+                        .unwrap()
+                        .1
+                        .trim_end()
+                        .to_string(),
+                );
+                continue;
+            }
+            let prefix = field(block, 345..500);
+            let name = match pax_path.take() {
+                Some(path) => path,
+                None if prefix.is_empty() => field(block, 0..100),
+                None => format!("{}/{}", prefix, field(block, 0..100)),
+            };
+            files.push((name, kind, contents));
+        }
+        assert!(stream[at..].iter().all(|&b| b == 0));
+        assert_eq!(stream.len() % BLOCK_SIZE, 0);
+        files
+    }
+
+    #[test]
+    fn long_paths_are_split_or_given_a_pax_header() {
+        let split = format!("{}/{}", "a".repeat(120), "b".repeat(90));
+        let unsplittable = format!("nodes/{}.bin", "c".repeat(200));
+        let modified = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let mut tar = TarWriter::new(Vec::new());
+        tar.append_folder(Path::new("nodes"), None).unwrap();
+        for path in &["nodes/0.bin", &split, &unsplittable] {
+            tar.append_file(
+                Path::new(path),
+                path.as_bytes(),
+                Some(modified),
+                Some(0o600),
+            )
+            .unwrap();
+        }
+        let stream = tar.finish().unwrap();
+
+        let files = read_tar(&stream);
+        let names: Vec<(&str, u8)> = files
+            .iter()
+            .map(|(name, kind, _)| (name.as_str(), *kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("nodes/", b'5'),
+                ("nodes/0.bin", b'0'),
+                (split.as_str(), b'0'),
+                (unsplittable.as_str(), b'0'),
+            ]
+        );
+        for (name, _, contents) in &files[1..] {
+            assert_eq!(contents, name.as_bytes());
+        }
+        assert_eq!(&stream[BLOCK_SIZE + 136..BLOCK_SIZE + 147], b"13132027400");
+        assert_eq!(&stream[BLOCK_SIZE + 100..BLOCK_SIZE + 107], b"0000600");
+    }
+
+    #[test]
+    fn packages_unpack_to_a_tar_stream_in_order() {
+        let dir = TempDir::new("tar");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"id":0}"#)
+            .entry("nodes/", b"")
+            .entry("nodes/0/geometries/0.bin.gz", &[7; 3000])
+            .entry("nodes/0/textures/0.jpg", b"jpeg")
+            .write_to_file(&path)
+            .unwrap();
+        let tar_path = dir.join("package.tar");
+        let summary = unpack_with_options(
+            &path,
+            &UnpackOptions::new()
+                .threads(4)
+                .json_formatting(JsonFormatting::Minify)
+                .tar(TarTarget::File(tar_path.clone())),
+        )
+        .unwrap();
+        let stream = std::fs::read(&tar_path).unwrap();
+        let unpacked_folder = dir.join("package").exists();
+        let refused = unpack_with_options(
+            &path,
+            &UnpackOptions::new()
+                .atomic(true)
+                .tar(TarTarget::File(tar_path)),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            read_tar(&stream),
+            vec![
+                (
+                    "3dSceneLayer.json".to_string(),
+                    b'0',
+                    br#"{"id":0}"#.to_vec()
+                ),
+                ("nodes/".to_string(), b'5', Vec::new()),
+                ("nodes/0/geometries/0.bin".to_string(), b'0', vec![7; 3000]),
+                ("nodes/0/textures/0.jpg".to_string(), b'0', b"jpeg".to_vec()),
+            ]
+        );
+        assert_eq!((summary.entries_unpacked, summary.bytes_written), (3, 3012));
+        assert!(!unpacked_folder);
+        assert!(refused.to_string().contains("atomic"));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::json::Value;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+/// Time spent in each stage of unpacking entries, summed over entries and
+/// worker threads.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StageTimings {
+    /// Reading entry data from the package.
+    pub read: Duration,
+    /// Decompressing gzipped entries.
+    pub decompress: Duration,
+    /// Writing files to disk.
+    pub write: Duration,
+}
+
+impl StageTimings {
+    /// Adds the time of another worker.
+    pub fn add(&mut self, other: &StageTimings) {
+        self.read += other.read;
+        self.decompress += other.decompress;
+        self.write += other.write;
+    }
+
+    /// The time of every stage.
+    pub fn total(&self) -> Duration {
+        self.read + self.decompress + self.write
+    }
+
+    fn stages(&self) -> [(&'static str, Duration); 3] {
+        [
+            ("read", self.read),
+            ("decompress", self.decompress),
+            ("write", self.write),
+        ]
+    }
+
+    fn percent(&self, stage: Duration) -> f64 {
+        let total = self.total().as_secs_f64();
+        if total > 0.0 {
+            100.0 * stage.as_secs_f64() / total
+        } else {
+            0.0
+        }
+    }
+
+    /// The timings as JSON, in seconds and as shares of the total.
+    pub fn to_json(&self) -> Value {
+        let mut members: Vec<(String, Value)> = self
+            .stages()
+            .iter()
+            .map(|&(name, stage)| {
+                (
+                    name.to_string(),
+                    Value::Object(vec![
+                        ("seconds".to_string(), Value::from(stage.as_secs_f64())),
+                        ("percent".to_string(), Value::from(self.percent(stage))),
+                    ]),
+                )
+            })
+            .collect();
+        members.push((
+            "totalSeconds".to_string(),
+            Value::from(self.total().as_secs_f64()),
+        ));
+        Value::Object(members)
+    }
+
+    /// One line per stage, for printing at the end of a run.
+    pub fn breakdown(&self) -> String {
+        let mut lines = String::new();
+        for &(name, stage) in self.stages().iter() {
+            lines.push_str(&format!(
+                "    {:<10} {:>9.3}s {:>5.1}%\n",
+                name,
+                stage.as_secs_f64(),
+                self.percent(stage)
+            ));
+        }
+        lines
+    }
+}
+
+/// Adds up the time spent in `read` calls of the wrapped reader. Reading
+/// the monotonic clock doesn't need a system call on the platforms we run
+/// on, so this is cheap enough to do for every chunk.
+pub struct TimedReader<R> {
+    inner: R,
+    pub elapsed: Duration,
+}
+
+impl<R: Read> TimedReader<R> {
+    pub fn new(inner: R) -> TimedReader<R> {
+        TimedReader {
+            inner,
+            elapsed: Duration::default(),
+        }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.read(buf);
+        self.elapsed += start.elapsed();
+        result
+    }
+}
+
+/// Adds up the time spent in `write` calls of the wrapped writer.
+pub struct TimedWriter<W> {
+    inner: W,
+    pub elapsed: Duration,
+}
+
+impl<W: Write> TimedWriter<W> {
+    pub fn new(inner: W) -> TimedWriter<W> {
+        TimedWriter {
+            inner,
+            elapsed: Duration::default(),
+        }
+    }
+}
+
+impl<W: Write> Write for TimedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.write(buf);
+        self.elapsed += start.elapsed();
+        result
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let start = Instant::now();
+        let result = self.inner.flush();
+        self.elapsed += start.elapsed();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_has_seconds_and_percentages() {
+        let timings = StageTimings {
+            read: Duration::from_millis(250),
+            decompress: Duration::from_millis(500),
+            write: Duration::from_millis(250),
+        };
+        let json = timings.to_json();
+        let decompress = json.get("decompress").unwrap();
+        assert_eq!(decompress.get("seconds").and_then(Value::as_f64), Some(0.5));
+        assert_eq!(
+            decompress.get("percent").and_then(Value::as_f64),
+            Some(50.0)
+        );
+        assert_eq!(json.get("totalSeconds").and_then(Value::as_f64), Some(1.0));
+
+        let empty = StageTimings::default().to_json();
+        let read = empty.get("read").unwrap();
+        assert_eq!(read.get("percent").and_then(Value::as_f64), Some(0.0));
+    }
+
+    #[test]
+    fn timed_streams_pass_data_through() {
+        let mut reader = TimedReader::new(&b"contents"[..]);
+        let mut sink = TimedWriter::new(Vec::new());
+        std::io::copy(&mut reader, &mut sink).unwrap();
+        assert_eq!(sink.inner, b"contents");
+    }
+}
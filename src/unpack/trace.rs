@@ -0,0 +1,125 @@
+// A timeline of what each worker thread did, in the Chrome trace event
+// format, which chrome://tracing, Perfetto and speedscope can all load.
+
+use crate::json::Value;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct TraceEvent {
+    pub name: String,
+    pub category: &'static str,
+    /// The worker thread, or 0 for the unpack as a whole.
+    pub thread: usize,
+    /// Time since the start of the trace.
+    pub start: Duration,
+    /// `None` for an instant event, such as a failure.
+    pub duration: Option<Duration>,
+    pub args: Vec<(String, Value)>,
+}
+
+impl TraceEvent {
+    /// An event covering the time from `started` until now.
+    pub fn span(
+        trace_start: Instant,
+        started: Instant,
+        name: &str,
+        category: &'static str,
+        thread: usize,
+    ) -> TraceEvent {
+        TraceEvent {
+            name: name.to_string(),
+            category,
+            thread,
+            start: started.duration_since(trace_start),
+            duration: Some(started.elapsed()),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn instant(
+        trace_start: Instant,
+        name: &str,
+        category: &'static str,
+        thread: usize,
+    ) -> TraceEvent {
+        TraceEvent {
+            name: name.to_string(),
+            category,
+            thread,
+            start: trace_start.elapsed(),
+            duration: None,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &str, value: Value) -> TraceEvent {
+        self.args.push((name.to_string(), value));
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        // Timestamps are in microseconds.
+        let micros = |d: Duration| Value::from(d.as_secs_f64() * 1e6);
+        let mut members = vec![
+            ("name".to_string(), Value::from(self.name.as_str())),
+            ("cat".to_string(), Value::from(self.category)),
+            (
+                "ph".to_string(),
+                Value::from(if self.duration.is_some() { "X" } else { "i" }),
+            ),
+            ("ts".to_string(), micros(self.start)),
+            ("pid".to_string(), Value::from(1usize)),
+            ("tid".to_string(), Value::from(self.thread)),
+        ];
+        match self.duration {
+            Some(duration) => members.push(("dur".to_string(), micros(duration))),
+            // Instant events are drawn across their thread only.
+            None => members.push(("s".to_string(), Value::from("t"))),
+        }
+        if !self.args.is_empty() {
+            members.push(("args".to_string(), Value::Object(self.args.clone())));
+        }
+        Value::Object(members)
+    }
+}
+
+pub fn to_json(events: &[TraceEvent]) -> Value {
+    Value::Object(vec![
+        (
+            "traceEvents".to_string(),
+            Value::Array(events.iter().map(TraceEvent::to_json).collect()),
+        ),
+        ("displayTimeUnit".to_string(), Value::from("ms")),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_and_instants_use_trace_event_phases() {
+        let trace_start = Instant::now();
+        let events = vec![
+            TraceEvent::span(trace_start, trace_start, "worker 1", "worker", 1)
+                .arg("entries", Value::from(2usize)),
+            TraceEvent::instant(trace_start, "nodes/0/0.bin.gz", "failure", 1)
+                .arg("error", Value::from("invalid gzip header")),
+        ];
+        let json = to_json(&events);
+        let events = json.get("traceEvents").and_then(Value::as_array).unwrap();
+
+        assert_eq!(events[0].get("ph").and_then(Value::as_str), Some("X"));
+        assert!(events[0].get("dur").is_some());
+        assert_eq!(
+            events[0]
+                .get("args")
+                .and_then(|args| args.get("entries"))
+                .and_then(Value::as_u64),
+            Some(2)
+        );
+        assert_eq!(events[1].get("ph").and_then(Value::as_str), Some("i"));
+        assert_eq!(events[1].get("tid").and_then(Value::as_u64), Some(1));
+        assert!(events[1].get("dur").is_none());
+    }
+}
@@ -0,0 +1,361 @@
+// Checks that what was unpacked is what the package holds. The output is
+// checksummed on its way to disk, so verifying only re-reads the package:
+// plain entries are compared against the CRC in the zip directory, and
+// gzipped ones against the CRC in their gzip trailer.
+//
+// Verifying the output reads every unpacked file back from disk instead, on
+// as many threads as unpacking uses, as a file a failing drive or a full
+// network share cut short is only found there. A file copied as it is
+// stored is compared with the CRC and size of its entry in the zip
+// directory. Decompressed and reformatted files don't have those, and are
+// compared with the CRC and size of what was written to them, which were
+// checked against the gzip trailer of the entry as it was decompressed.
+
+use crate::archive::GzipProvenance;
+use crate::sha256::Sha256;
+use byteorder::{ByteOrder, LittleEndian};
+use failure::Error;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::thread;
+use zip::ZipArchive;
+
+/// A file as it was written.
+#[derive(Clone, Debug)]
+pub struct WrittenFile {
+    pub path: PathBuf,
+    /// Whether the entry was gzipped, so that the file holds its
+    /// decompressed content.
+    pub decompressed: bool,
+    pub crc32: u32,
+    pub bytes: u64,
+    /// What the gzip header of the entry records, if anything.
+    pub gzip: Option<GzipProvenance>,
+    /// The SHA-256 of the file, when a checksum manifest is written.
+    pub sha256: Option<[u8; 32]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UnpackedFile {
+    pub entry_index: usize,
+    pub entry_name: String,
+    /// Where the entry's data starts in the package.
+    pub data_offset: u64,
+    pub file: WrittenFile,
+}
+
+/// Checksums what is read through it, and with `with_sha256` also hashes
+/// it.
+pub struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    sha256: Option<Sha256>,
+    bytes: u64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> ChecksumReader<R> {
+        ChecksumReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            sha256: None,
+            bytes: 0,
+        }
+    }
+
+    pub fn with_sha256(mut self, enabled: bool) -> ChecksumReader<R> {
+        self.sha256 = if enabled {
+            Some(Sha256::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// The CRC and length of what was read.
+    pub fn checksum(&self) -> (u32, u64) {
+        (self.hasher.clone().finalize(), self.bytes)
+    }
+
+    /// The SHA-256 of what was read, with `with_sha256`.
+    pub fn sha256(&self) -> Option<[u8; 32]> {
+        self.sha256.clone().map(Sha256::finish)
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(&buf[..read]);
+        }
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+/// The CRC and length of the last gzip member of `reader`, from its trailer.
+/// The length is only recorded modulo 2^32.
+fn gzip_trailer(reader: &mut dyn Read) -> std::io::Result<Option<(u32, u32)>> {
+    let mut tail = Vec::with_capacity(8);
+    let mut chunk = [0u8; 8 * 1024];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        tail.extend_from_slice(&chunk[..read]);
+        let excess = tail.len().saturating_sub(8);
+        tail.drain(..excess);
+    }
+    if tail.len() < 8 {
+        return Ok(None);
+    }
+    Ok(Some((
+        LittleEndian::read_u32(&tail[..4]),
+        LittleEndian::read_u32(&tail[4..]),
+    )))
+}
+
+#[derive(Debug)]
+pub struct Mismatch {
+    pub entry_name: String,
+    pub problem: String,
+}
+
+/// Compares every unpacked file with its entry in the package. Reading
+/// gzipped entries to their end also checks them against the CRC in the zip
+/// directory.
+pub fn verify<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    files: &[UnpackedFile],
+) -> Result<Vec<Mismatch>, Error> {
+    let mut mismatches = Vec::new();
+    for unpacked in files {
+        let written = &unpacked.file;
+        let mut entry = slpk_archive.by_index(unpacked.entry_index)?;
+        let problem = if written.decompressed {
+            match gzip_trailer(&mut entry) {
+                Err(e) => Some(format!("the entry can't be read: {}", e)),
+                Ok(None) => Some("the entry is too short to be gzipped".to_string()),
+                Ok(Some((crc32, bytes))) => {
+                    if (crc32, bytes) == (written.crc32, written.bytes as u32) {
+                        None
+                    } else {
+                        Some(format!(
+                            "wrote CRC {:08x} for {} bytes, the gzip trailer has {:08x} for {} bytes",
+                            written.crc32, written.bytes, crc32, bytes
+                        ))
+                    }
+                }
+            }
+        } else if (entry.crc32(), entry.size()) == (written.crc32, written.bytes) {
+            None
+        } else {
+            Some(format!(
+                "wrote CRC {:08x} for {} bytes, the package has {:08x} for {} bytes",
+                written.crc32,
+                written.bytes,
+                entry.crc32(),
+                entry.size()
+            ))
+        };
+        if let Some(problem) = problem {
+            mismatches.push(Mismatch {
+                entry_name: unpacked.entry_name.clone(),
+                problem,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// The CRC and length of the file at `path`.
+fn file_checksum(path: &Path) -> io::Result<(u32, u64)> {
+    let file = BufReader::with_capacity(64 * 1024, File::open(path)?);
+    let mut reader = ChecksumReader::new(file);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.checksum())
+}
+
+/// Reads every unpacked file back from disk, on `threads` threads, and
+/// compares it with what it should hold. With `as_stored`, files which
+/// weren't decompressed hold their entries exactly as the package stores
+/// them.
+pub fn verify_output<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    files: &[UnpackedFile],
+    as_stored: bool,
+    threads: usize,
+) -> Result<Vec<Mismatch>, Error> {
+    let mut expected = Vec::with_capacity(files.len());
+    for unpacked in files {
+        let written = &unpacked.file;
+        expected.push(if written.decompressed || !as_stored {
+            (written.crc32, written.bytes, "were written")
+        } else {
+            let entry = slpk_archive.by_index(unpacked.entry_index)?;
+            (entry.crc32(), entry.size(), "the package has")
+        });
+    }
+
+    let chunk_size = files.len() / threads.max(1) + 1;
+    let check = |files: &[UnpackedFile], expected: &[(u32, u64, &str)]| {
+        let mut mismatches = Vec::new();
+        for (unpacked, &(crc32, bytes, source)) in files.iter().zip(expected) {
+            let problem = match file_checksum(&unpacked.file.path) {
+                Err(e) => format!("{} can't be read: {}", unpacked.file.path.display(), e),
+                Ok(actual) if actual == (crc32, bytes) => continue,
+                Ok((actual_crc32, actual_bytes)) => format!(
+                    "the file has CRC {:08x} for {} bytes, {} {:08x} for {} bytes",
+                    actual_crc32, actual_bytes, source, crc32, bytes
+                ),
+            };
+            mismatches.push(Mismatch {
+                entry_name: unpacked.entry_name.clone(),
+                problem,
+            });
+        }
+        mismatches
+    };
+    let mismatches = thread::scope(|scope| {
+        let workers: Vec<_> = files
+            .chunks(chunk_size)
+            .zip(expected.chunks(chunk_size))
+            .map(|(files, expected)| scope.spawn(move || check(files, expected)))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn finds_files_which_differ_from_their_entry() {
+        let dir = TempDir::new("verify-after");
+        let path = dir.join("package.slpk");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("0.bin.gz", FileOptions::default())
+            .unwrap();
+        writer.write_all(&encoder.finish().unwrap()).unwrap();
+        writer
+            .start_file("metadata.json", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"{}").unwrap();
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+
+        let written = |contents: &[u8], decompressed| {
+            let mut reader = ChecksumReader::new(contents);
+            std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+            let (crc32, bytes) = reader.checksum();
+            WrittenFile {
+                path: PathBuf::new(),
+                decompressed,
+                crc32,
+                bytes,
+                gzip: None,
+                sha256: None,
+            }
+        };
+        let files = vec![
+            UnpackedFile {
+                entry_index: 0,
+                entry_name: "0.bin.gz".to_string(),
+                data_offset: 0,
+                file: written(b"geometry", true),
+            },
+            UnpackedFile {
+                entry_index: 1,
+                entry_name: "metadata.json".to_string(),
+                data_offset: 0,
+                file: written(b"{ }", false),
+            },
+        ];
+        let mismatches = verify(
+            &mut crate::archive::open_slpk_archive(&path).unwrap(),
+            &files,
+        );
+
+        let mismatches = mismatches.unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].entry_name, "metadata.json");
+    }
+
+    #[test]
+    fn finds_files_which_read_back_differently() {
+        let dir = TempDir::new("verify-output");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"geometry").unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [
+            ("0.bin.gz", encoder.finish().unwrap()),
+            ("metadata.json", b"{}".to_vec()),
+            ("1.jpg", b"jpeg".to_vec()),
+        ] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(&contents).unwrap();
+        }
+        let package = writer.finish().unwrap().into_inner();
+
+        // The geometry lost its last byte on its way to disk, and the texture
+        // is gone.
+        let unpacked = |entry_index, entry_name: &str, contents: &[u8], on_disk: Option<&[u8]>| {
+            let path = dir.join(entry_name.trim_end_matches(".gz"));
+            if let Some(on_disk) = on_disk {
+                std::fs::write(&path, on_disk).unwrap();
+            }
+            let mut reader = ChecksumReader::new(contents);
+            std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+            let (crc32, bytes) = reader.checksum();
+            UnpackedFile {
+                entry_index,
+                entry_name: entry_name.to_string(),
+                data_offset: 0,
+                file: WrittenFile {
+                    path,
+                    decompressed: entry_name.ends_with(".gz"),
+                    crc32,
+                    bytes,
+                    gzip: None,
+                    sha256: None,
+                },
+            }
+        };
+        let files = vec![
+            unpacked(0, "0.bin.gz", b"geometry", Some(b"geometr")),
+            unpacked(1, "metadata.json", b"{}", Some(b"{}")),
+            unpacked(2, "1.jpg", b"jpeg", None),
+        ];
+        let mismatches = verify_output(
+            &mut ZipArchive::new(Cursor::new(package)).unwrap(),
+            &files,
+            true,
+            2,
+        );
+
+        let mismatches = mismatches.unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].entry_name, "0.bin.gz");
+        assert!(mismatches[0].problem.contains("for 7 bytes, were written"));
+        assert!(mismatches[0].problem.ends_with("for 8 bytes"));
+        assert_eq!(mismatches[1].entry_name, "1.jpg");
+        assert!(mismatches[1].problem.contains("can't be read"));
+    }
+}
@@ -0,0 +1,541 @@
+// The workers of an unpack, which each take the next entry from the queue
+// they share and unpack it, until there are none left, time runs out, the
+// unpack is cancelled or another worker fails. A worker reads the package
+// through a handle of its own, while the rest of what it needs is worked
+// out before any of them start, and shared.
+
+use super::conflicts::Resolution;
+use super::deadline::Deadline;
+use super::dedup::Deduplicator;
+use super::degraded::MalformedGzip;
+use super::directory::PackageReader;
+use super::progress::ProgressCounters;
+use super::routes::{self, ResourceClass};
+use super::tar::TarStream;
+use super::trace::TraceEvent;
+use super::verify::UnpackedFile;
+use super::{
+    check_conflict, conflicts, dry_run, entry_target_path, is_transformed, paranoid,
+    path_in_folder, portable, prefix, quarantine, remaining_entries, symlinks, target_file_name,
+    unpack_entry, CancelToken, EntryError, EntryOptions, EntryQueue, EntrySelection,
+    IntegrityOptions, OutputPlan, StopOthersOnError, UnpackError, UnpackOptions, WorkerSummary,
+};
+use crate::archive::{self, PackageFlavor};
+use crate::json::Value;
+use failure::Error;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use zip::ZipArchive;
+
+/// What the workers of an unpack share.
+pub(super) struct Workers<'a, R> {
+    pub make_reader: &'a (dyn Fn() -> Result<R, Error> + Sync),
+    pub options: &'a UnpackOptions,
+    pub selection: &'a EntrySelection,
+    pub output: &'a OutputPlan<'a>,
+    pub flavor: PackageFlavor,
+    /// Whether entries are decompressed by their first bytes rather than by
+    /// their name.
+    pub sniff_compression: bool,
+    pub sort_keys: Option<u64>,
+    pub write_buffer_size: usize,
+    pub deadline: Option<Deadline>,
+    pub trace_start: Option<Instant>,
+    pub deduplicator: Option<&'a Deduplicator>,
+    pub tar: Option<&'a Mutex<TarStream>>,
+    pub progress: Option<&'a ProgressCounters>,
+    pub queue: &'a EntryQueue,
+    /// Set once a worker fails, for the others to stop at their next entry.
+    pub stop: &'a AtomicBool,
+}
+
+impl<R: Read + Seek> Workers<'_, R> {
+    /// Unpacks entries from the queue through `slpk_archive` until there
+    /// are none left or the worker has to stop, as worker `worker_idx`.
+    pub fn run(
+        &self,
+        worker_idx: usize,
+        mut slpk_archive: ZipArchive<PackageReader<R>>,
+    ) -> Result<WorkerSummary, Error> {
+        let (options, selection, output) = (self.options, self.selection, self.output);
+        // Thread 0 of the trace is the unpack as a whole.
+        let trace_thread = worker_idx + 1;
+        let (deadline, trace_start, tar) = (self.deadline, self.trace_start, self.tar);
+        let (queue, stop, cancel) = (self.queue, self.stop, options.run.cancel.as_ref());
+        let (sniff_compression, flavor, sort_keys) =
+            (self.sniff_compression, self.flavor, self.sort_keys);
+        let write_buffer_size = self.write_buffer_size;
+        let dry_run = options.output.dry_run;
+        let conflict_policy = options.output.on_file_conflict;
+        let (keep_gzip, json_formatting) =
+            (options.content.keep_gzip, options.content.json_formatting);
+        let IntegrityOptions {
+            strict_paths,
+            strict_content,
+            max_expansion_ratio,
+            validate_json,
+            strict_json,
+            hardened,
+            ..
+        } = options.integrity;
+        let EntrySelection {
+            entry_indices,
+            selected_layer,
+            routing,
+            empty_nodes,
+            strip_prefix,
+            collisions,
+            ..
+        } = selection;
+        let (portable_names, keep_unprefixed) =
+            (selection.portable_names, options.selection.keep_unprefixed);
+        let (allow_symlinks, dedup_geometry) =
+            (options.output.allow_symlinks, options.output.dedup_geometry);
+        let restore_gzip_mtime = options.content.restore_gzip_mtime;
+        let checksum_manifest = options.reports.checksum_manifest;
+        let keep_going = options.run.keep_going;
+        let record_files = output.record_files;
+        let (retry_set, unchanged, retry_policy) =
+            (&output.retry_set, &output.unchanged, &output.retry_policy);
+        let unpack_folder = &output.unpack_folder;
+        let routes = &options.output.routes;
+        let filters = &options.filters;
+        let worker_start = Instant::now();
+        let mut stop_others = StopOthersOnError {
+            stop: self.stop,
+            finished: false,
+        };
+        // Stored entries are read directly for a dry run.
+        let mut package_file = if dry_run {
+            Some((self.make_reader)()?)
+        } else {
+            None
+        };
+        let skipped = || {
+            if let Some(progress) = self.progress {
+                progress.entry_done(0);
+            }
+        };
+
+        let cancelled = || cancel.is_some_and(CancelToken::is_cancelled);
+        let mut summary = WorkerSummary::default();
+        loop {
+            // Another worker failed, so the unpack has failed already.
+            // When time has run out, the entries which haven't been
+            // handed out are listed once every worker has stopped.
+            if stop.load(Ordering::SeqCst) || deadline.is_some_and(Deadline::passed) {
+                break;
+            }
+            if cancelled() {
+                summary.cancelled = true;
+                break;
+            }
+            let position = match queue.next() {
+                Some(position) => position,
+                None => break,
+            };
+            let entry_idx = entry_indices[position];
+            let archive_entry = slpk_archive.by_index(entry_idx)?;
+            if let Some(retry_set) = &retry_set {
+                if !retry_set.contains(archive_entry.name()) {
+                    skipped();
+                    continue;
+                }
+            }
+            if let Some(unchanged) = &unchanged {
+                if unchanged.contains(archive_entry.name()) {
+                    summary.entries_unchanged += 1;
+                    skipped();
+                    continue;
+                }
+            }
+            if let Some(layer) = &selected_layer {
+                if !layer.contains(archive_entry.name()) {
+                    summary.entries_of_other_layers += 1;
+                    skipped();
+                    continue;
+                }
+            }
+            if let Some(empty_nodes) = &empty_nodes {
+                if empty_nodes.skips(archive_entry.name()) {
+                    summary.entries_skipped += 1;
+                    skipped();
+                    continue;
+                }
+            }
+            if let Some(filter) =
+                filters.excludes(archive_entry.size(), archive_entry.last_modified())
+            {
+                *summary.entries_filtered.entry(filter).or_insert(0) += 1;
+                skipped();
+                continue;
+            }
+            let entry_name = archive_entry.name().to_string();
+            if hardened {
+                paranoid::check_name_length(&entry_name)?;
+            }
+            if strict_paths {
+                if let Some(reason) = paranoid::unsafe_path(&entry_name, archive_entry.unix_mode())
+                {
+                    return Err(Error::from(UnpackError::UnsafeEntryPath(
+                        entry_name, reason,
+                    )));
+                }
+            }
+            let original_path =
+                archive::names::sanitized_path(&archive::names::decoded_name(&archive_entry));
+            let original_path = match &strip_prefix {
+                Some(prefix) => match prefix::strip(&original_path, prefix) {
+                    Some(stripped) => stripped,
+                    None if keep_unprefixed => original_path,
+                    None => {
+                        summary.unprefixed_entries.push(entry_name);
+                        skipped();
+                        continue;
+                    }
+                },
+                None => original_path,
+            };
+            let original_path = match portable::portable_path(&original_path) {
+                Some(portable) if portable_names => portable,
+                _ => original_path,
+            };
+            let (compressed_size, size) = (archive_entry.compressed_size(), archive_entry.size());
+            let data_offset = archive_entry.data_start();
+            let is_symlink = symlinks::is_symlink(archive_entry.unix_mode());
+            drop(archive_entry);
+            // Some producers write an entry for each folder, which
+            // holds nothing and only needs the folder created, where
+            // the package has it. An entry for the root of the
+            // package has nothing to create.
+            if entry_name.ends_with('/') || entry_name.ends_with('\\') {
+                if !original_path.as_os_str().is_empty() {
+                    let directory = path_in_folder(unpack_folder, &original_path)?;
+                    match (&package_file, tar) {
+                        (Some(_), _) => summary.dry_run.add_folder(&directory),
+                        (None, Some(tar)) => {
+                            let modified = conflicts::recorded_time(
+                                slpk_archive.by_index(entry_idx)?.last_modified(),
+                            );
+                            tar.lock()
+                                .unwrap()
+                                .append_folder(&original_path, modified)?;
+                            summary.directories_created += 1;
+                        }
+                        (None, None) => {
+                            std::fs::create_dir_all(&directory)?;
+                            summary.directories_created += 1;
+                        }
+                    }
+                }
+                skipped();
+                continue;
+            }
+            let mut archive_entry_path = match collisions.renamed(entry_idx) {
+                Some(renamed) => renamed.to_path_buf(),
+                None => original_path.clone(),
+            };
+            let class = ResourceClass::of_entry(&archive_entry_path);
+            *summary.entries_per_class.entry(class).or_insert(0) += 1;
+            if let Some((routing, layer_root)) = routing.as_ref() {
+                // Sublayers are routed relative to the root of their
+                // building layer, which keeps its place in the output.
+                let (root, path_in_layer) =
+                    match archive_entry_path.strip_prefix(layer_root.as_str()) {
+                        Ok(path_in_layer) => (Path::new(layer_root.as_str()), path_in_layer),
+                        Err(_) => (Path::new(""), archive_entry_path.as_path()),
+                    };
+                let (routed_path, assigned) = routing.route(path_in_layer);
+                if !assigned {
+                    summary.unassigned_entries.push(entry_name.clone());
+                }
+                archive_entry_path = root.join(routed_path);
+            }
+            let target_root = routes::root_for(routes, class, unpack_folder);
+            if is_symlink {
+                let refusal = if !allow_symlinks {
+                    Some("links are only recreated with --allow-symlinks".to_string())
+                } else if package_file.is_some() || tar.is_some() {
+                    Some("links aren't recreated by a dry run or in a tar stream".to_string())
+                } else {
+                    let link_path = path_in_folder(target_root, &archive_entry_path)?;
+                    match symlinks::recreate(
+                        slpk_archive.by_index(entry_idx)?,
+                        target_root,
+                        &link_path,
+                    )? {
+                        symlinks::Linked::Created => None,
+                        symlinks::Linked::Refused(reason) => Some(reason),
+                    }
+                };
+                match refusal {
+                    Some(reason) => summary.skipped_symlinks.push((entry_name, reason)),
+                    None => summary.symlinks_created += 1,
+                }
+                skipped();
+                continue;
+            }
+            let target_file_path = entry_target_path(target_root, &archive_entry_path, keep_gzip);
+            if let Some(package_file) = package_file.as_mut() {
+                // Kept gzipped entries are planned as plain ones.
+                let named_gzip = !keep_gzip && target_file_name(&archive_entry_path, false).0;
+                let target_file_path = match target_file_path? {
+                    Some(target_file_path) => target_file_path,
+                    None => {
+                        skipped();
+                        continue;
+                    }
+                };
+                let transformed = is_transformed(
+                    named_gzip,
+                    &target_file_path,
+                    keep_gzip,
+                    sniff_compression,
+                    json_formatting,
+                );
+                let resolution = check_conflict(
+                    conflict_policy,
+                    &slpk_archive.by_index(entry_idx)?,
+                    &target_file_path,
+                    transformed,
+                )?;
+                if let Resolution::Skip | Resolution::AlreadyUnpacked | Resolution::Refuse =
+                    resolution
+                {
+                    summary.dry_run.files_kept += 1;
+                    skipped();
+                    continue;
+                }
+                let (gzipped, bytes) = dry_run::planned_file(
+                    &mut slpk_archive,
+                    package_file,
+                    entry_idx,
+                    named_gzip,
+                    sniff_compression && !keep_gzip,
+                )?;
+                summary.dry_run.add(&target_file_path, gzipped, bytes);
+                if let Some(progress) = self.progress {
+                    progress.entry_done(bytes);
+                }
+                continue;
+            }
+            let dedup = self.deduplicator.filter(|_| match class {
+                ResourceClass::Textures => true,
+                ResourceClass::Geometry => dedup_geometry,
+                _ => false,
+            });
+            let entry_options = EntryOptions {
+                dedup,
+                conflict_policy,
+                sniff_compression,
+                warn_not_gzipped: flavor != PackageFlavor::Eslpk,
+                keep_gzip,
+                json_formatting,
+                sort_keys,
+                checksum_manifest,
+                budget: options.run.memory_budget.as_deref(),
+                restore_gzip_mtime,
+                strict_content,
+                max_expansion_ratio,
+                validate_json,
+                strict_json,
+                staging: if output.stage_files {
+                    Some(worker_idx)
+                } else {
+                    None
+                },
+                write_buffer_size,
+                tar,
+                deadline,
+                cancel,
+                progress: self.progress,
+            };
+            let entry_start = Instant::now();
+            // Each attempt reads the entry from the start again.
+            // Errors name the entry and its file, as an error such as a
+            // corrupt gzip stream doesn't say where it came from.
+            let context_target = match &target_file_path {
+                Ok(Some(target_file_path)) => target_file_path.clone(),
+                _ => target_root.join(&archive_entry_path),
+            };
+            let mut attempt = || {
+                slpk_archive
+                    .by_index(entry_idx)
+                    .map_err(Error::from)
+                    .and_then(|archive_entry| {
+                        unpack_entry(
+                            archive_entry,
+                            &archive_entry_path,
+                            target_root.to_path_buf(),
+                            &entry_options,
+                        )
+                    })
+                    .or_else(|e| match e.downcast::<MalformedGzip>() {
+                        // Read from the start again, to be copied as
+                        // it is.
+                        Ok(malformed) => {
+                            let copied = EntryOptions {
+                                keep_gzip: true,
+                                ..entry_options
+                            };
+                            let mut outcome = unpack_entry(
+                                slpk_archive.by_index(entry_idx)?,
+                                &archive_entry_path,
+                                target_root.to_path_buf(),
+                                &copied,
+                            )?;
+                            outcome.degraded = Some(malformed.cause);
+                            Ok(outcome)
+                        }
+                        Err(e) => Err(e),
+                    })
+                    .map_err(|cause| {
+                        Error::from(EntryError {
+                            entry: entry_name.clone(),
+                            target: context_target.clone(),
+                            cause,
+                        })
+                    })
+            };
+            let timed_out = || deadline.is_some_and(Deadline::passed);
+            let (outcome, failed_attempts) = match &retry_policy {
+                None => match attempt() {
+                    Ok(outcome) => (outcome, 0),
+                    Err(_) if cancelled() => {
+                        summary.cancelled = true;
+                        break;
+                    }
+                    Err(_) if timed_out() => {
+                        summary.remaining_entries = remaining_entries(
+                            &mut slpk_archive,
+                            &entry_indices[position..=position],
+                            retry_set.as_ref(),
+                        )?;
+                        break;
+                    }
+                    Err(e) if keep_going => match e.downcast::<EntryError>() {
+                        Ok(failure) => {
+                            summary.failed_entries.push(failure);
+                            skipped();
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    },
+                    Err(e) => return Err(e),
+                },
+                Some(policy) => {
+                    let attempts = policy.run(|| !timed_out() && !cancelled(), attempt);
+                    match attempts.result {
+                        Ok(outcome) => {
+                            if attempts.failures > 0 {
+                                summary.entries_retried += 1;
+                            }
+                            (outcome, attempts.failures)
+                        }
+                        Err(_) if cancelled() => {
+                            summary.cancelled = true;
+                            break;
+                        }
+                        Err(_) if timed_out() => {
+                            summary.remaining_entries = remaining_entries(
+                                &mut slpk_archive,
+                                &entry_indices[position..=position],
+                                retry_set.as_ref(),
+                            )?;
+                            break;
+                        }
+                        Err(e) => {
+                            if let Some(trace_start) = trace_start {
+                                summary.trace_events.push(
+                                    TraceEvent::instant(
+                                        trace_start,
+                                        &entry_name,
+                                        "failure",
+                                        trace_thread,
+                                    )
+                                    .arg("attempts", Value::from(attempts.failures))
+                                    .arg("error", Value::from(e.to_string())),
+                                );
+                            }
+                            quarantine::write_marker(
+                                &quarantine::marker_path(unpack_folder, &original_path),
+                                &entry_name,
+                                attempts.failures,
+                                &e,
+                            )?;
+                            summary.quarantined_entries.push(entry_name);
+                            skipped();
+                            continue;
+                        }
+                    }
+                }
+            };
+            if let Some(trace_start) = trace_start {
+                let mut event =
+                    TraceEvent::span(trace_start, entry_start, &entry_name, "entry", trace_thread)
+                        .arg("index", Value::from(entry_idx))
+                        .arg("dataOffset", Value::from(data_offset))
+                        .arg("compressedBytes", Value::from(compressed_size))
+                        .arg("bytes", Value::from(size));
+                if failed_attempts > 0 {
+                    event = event.arg("failedAttempts", Value::from(failed_attempts));
+                }
+                summary.trace_events.push(event);
+            }
+            summary.timings.add(&outcome.timings);
+            if let Some(cause) = &outcome.degraded {
+                warn!(
+                    "{} couldn't be decompressed, so it was copied as it is: {}",
+                    entry_name, cause
+                );
+                summary.degraded_entries.push(entry_name.clone());
+            }
+            match outcome.resolution {
+                Resolution::Write => {}
+                Resolution::Overwrite => summary.files_overwritten += 1,
+                Resolution::Skip => {
+                    summary.files_kept += 1;
+                    continue;
+                }
+                Resolution::AlreadyUnpacked => {
+                    summary.files_already_unpacked += 1;
+                    continue;
+                }
+                Resolution::Refuse => {
+                    summary.conflicting_entries.push(entry_name);
+                    continue;
+                }
+            }
+            if let Some(file) = &outcome.written {
+                summary.bytes_written += file.bytes;
+            }
+            if let (true, Some(file)) = (record_files, outcome.written) {
+                summary.unpacked_files.push(UnpackedFile {
+                    entry_index: entry_idx,
+                    entry_name,
+                    data_offset,
+                    file,
+                });
+            }
+            summary.entries_unpacked += 1;
+        }
+
+        if let Some(trace_start) = trace_start {
+            summary.trace_events.push(
+                TraceEvent::span(
+                    trace_start,
+                    worker_start,
+                    &format!("worker {}", trace_thread),
+                    "worker",
+                    trace_thread,
+                )
+                .arg("entries", Value::from(summary.entries_unpacked)),
+            );
+        }
+        stop_others.finished = true;
+        Ok(summary)
+    }
+}
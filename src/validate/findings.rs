@@ -0,0 +1,249 @@
+// What validation finds, one problem at a time, so that findings can be
+// handed on as soon as they are found rather than collected into a report.
+// Each problem belongs to the check which found it, and has a severity:
+// resources which are referred to but missing, entries which don't
+// decompress or parse, and entries readers can't reach through the hash
+// index, are errors, while anything which readers get by without is a
+// warning.
+
+use super::{MissingResource, SchemaViolation, ValidateError};
+use crate::i3s::SlpkVersion;
+use crate::lint::Severity;
+use crate::repair::NamingProblem;
+use std::fmt;
+use std::str::FromStr;
+
+/// A check validation runs.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Check {
+    /// Whether the resources the node pages refer to are stored, and
+    /// whether anything is stored which nothing refers to.
+    Refs,
+    /// Whether the content of each entry matches its name.
+    Naming,
+    /// Whether each gzipped entry decompresses, and matches its CRC.
+    Gzip,
+    /// Whether each JSON entry parses.
+    Json,
+    /// Whether the JSON resources match their schemas.
+    Schema,
+    /// Whether the hash index agrees with the central directory.
+    Index,
+}
+
+/// Every check, in the order each entry goes through them.
+pub const CHECKS: [Check; 6] = [
+    Check::Refs,
+    Check::Naming,
+    Check::Gzip,
+    Check::Json,
+    Check::Schema,
+    Check::Index,
+];
+
+impl Check {
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for Check {
+    type Err = ValidateError;
+
+    fn from_str(s: &str) -> Result<Check, ValidateError> {
+        CHECKS
+            .iter()
+            .find(|check| check.to_string() == s)
+            .copied()
+            .ok_or_else(|| ValidateError::UnknownCheck(s.to_string()))
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Check::Refs => "refs",
+            Check::Naming => "naming",
+            Check::Gzip => "gzip",
+            Check::Json => "json",
+            Check::Schema => "schema",
+            Check::Index => "index",
+        })
+    }
+}
+
+/// Something wrong with a package, and what it was found in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// The layout of a layer isn't understood, and it wasn't checked.
+    UnknownLayout {
+        /// The id of the layer.
+        layer: usize,
+        /// The I3S version the layer declares.
+        version: SlpkVersion,
+        /// What about the layout isn't understood.
+        reason: String,
+    },
+    /// A resource a node refers to which isn't stored.
+    Missing {
+        /// The id of the layer.
+        layer: usize,
+        /// The resource, and the node referring to it.
+        resource: MissingResource,
+    },
+    /// An entry below `nodes/` which no node of the layer refers to.
+    Orphaned {
+        /// The id of the layer.
+        layer: usize,
+        /// The name of the entry.
+        entry_name: String,
+    },
+    /// Something a layer declares which this build can't decode, and why.
+    Undecodable {
+        /// The id of the layer.
+        layer: usize,
+        /// What can't be decoded, and why.
+        note: String,
+    },
+    /// An entry whose content doesn't match its name.
+    Naming(NamingProblem),
+    /// A gzipped entry which doesn't decompress, or whose data doesn't
+    /// match its CRC.
+    CorruptGzip {
+        /// The name of the entry.
+        entry_name: String,
+        /// Why it doesn't decompress.
+        error: String,
+    },
+    /// A JSON entry which doesn't parse.
+    InvalidJson {
+        /// The name of the entry.
+        entry_name: String,
+        /// Why it doesn't parse.
+        error: String,
+    },
+    /// A JSON resource which doesn't match its schema.
+    Schema(SchemaViolation),
+    /// An entry whose hash index record points elsewhere.
+    StaleIndexRecord(String),
+    /// An entry which has no record in the hash index.
+    UnindexedEntry(String),
+    /// The number of hash index records whose key matches no entry.
+    DanglingIndexRecords(usize),
+    /// The number of keys which appear in more than one record.
+    DuplicateIndexKeys(usize),
+    /// Hash index records which aren't sorted by key.
+    UnsortedIndex,
+}
+
+impl Problem {
+    /// The check which finds this problem.
+    pub fn check(&self) -> Check {
+        match self {
+            Problem::UnknownLayout { .. }
+            | Problem::Missing { .. }
+            | Problem::Orphaned { .. }
+            | Problem::Undecodable { .. } => Check::Refs,
+            Problem::Naming(_) => Check::Naming,
+            Problem::CorruptGzip { .. } => Check::Gzip,
+            Problem::InvalidJson { .. } => Check::Json,
+            Problem::Schema(_) => Check::Schema,
+            Problem::StaleIndexRecord(_)
+            | Problem::UnindexedEntry(_)
+            | Problem::DanglingIndexRecords(_)
+            | Problem::DuplicateIndexKeys(_)
+            | Problem::UnsortedIndex => Check::Index,
+        }
+    }
+
+    /// How much the problem matters to readers of the package.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Problem::Missing { .. }
+            | Problem::CorruptGzip { .. }
+            | Problem::InvalidJson { .. }
+            | Problem::StaleIndexRecord(_)
+            | Problem::UnindexedEntry(_) => Severity::Error,
+            Problem::Undecodable { .. } => Severity::Info,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::UnknownLayout {
+                layer,
+                version,
+                reason,
+            } => write!(
+                f,
+                "layer {} of I3S version {} has an unknown layout, {}, and wasn't checked",
+                layer, version, reason
+            ),
+            Problem::Missing { layer, resource } => write!(
+                f,
+                "layer {}: {} is missing, for node {}",
+                layer, resource.entry_name, resource.node_id
+            ),
+            Problem::Orphaned { layer, entry_name } => {
+                write!(f, "layer {}: {} is orphaned", layer, entry_name)
+            }
+            Problem::Undecodable { layer, note } => write!(f, "layer {}: {}", layer, note),
+            Problem::Schema(violation) => write!(
+                f,
+                "{} doesn't match, {}",
+                violation.entry_name, violation.violation
+            ),
+            Problem::Naming(problem) => write!(f, "{} {}", problem.name, problem.mismatch),
+            Problem::CorruptGzip { entry_name, error } => {
+                write!(f, "{} doesn't decompress, {}", entry_name, error)
+            }
+            Problem::InvalidJson { entry_name, error } => {
+                write!(f, "{} isn't valid JSON, {}", entry_name, error)
+            }
+            Problem::StaleIndexRecord(name) => {
+                write!(f, "the record of {} points elsewhere", name)
+            }
+            Problem::UnindexedEntry(name) => write!(f, "{} has no record", name),
+            Problem::DanglingIndexRecords(records) => {
+                write!(f, "{} records match no entry", records)
+            }
+            Problem::DuplicateIndexKeys(keys) => {
+                write!(f, "{} keys appear in more than one record", keys)
+            }
+            Problem::UnsortedIndex => f.write_str("the records aren't sorted by key"),
+        }
+    }
+}
+
+/// A problem, as it is handed on while validating.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    /// The severity of the problem.
+    pub severity: Severity,
+    /// What was found.
+    pub problem: Problem,
+}
+
+impl Finding {
+    /// A finding with the severity of its problem.
+    pub fn new(problem: Problem) -> Finding {
+        Finding {
+            severity: problem.severity(),
+            problem,
+        }
+    }
+
+    /// The check which found it.
+    pub fn check(&self) -> Check {
+        self.problem.check()
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.severity, self.check(), self.problem)
+    }
+}
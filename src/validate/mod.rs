@@ -0,0 +1,1277 @@
+// Validates the structure of a package, straight from the archive: each
+// layer document must parse, every resource the node pages refer to must be
+// stored, and nothing may be stored below `nodes/` which no node refers to.
+// Only the node page layout of I3S 1.7 and 1.8 is understood, and that of
+// version 2.0 of point clouds, whose nodes name their resource folder and
+// whose resources are only known up to their extension, which depends on how
+// they are encoded. Layers laid out any other way, such as older ones with a
+// node index document per node, are reported as of an unknown layout rather
+// than checked. The JSON resources
+// of the package can also be checked against their schemas, in which case
+// the first violation in each is reported, whatever the layout. A hash index
+// is checked against the central directory too, as readers which trust it
+// can't reach an entry whose record points elsewhere. The first bytes of
+// every entry are compared against its name, as `repair --check` does, since
+// consumers go by the .gz suffix to decide whether to decompress it. Gzipped
+// entries can be decompressed in full and JSON entries parsed as well, and
+// each check can be left out. The entries are shared out between worker
+// threads. What is found is handed on as it is found, in the same order
+// whatever the number of threads, and the report is only collected for
+// callers which want it.
+
+mod findings;
+
+pub use self::findings::{Check, Finding, Problem, CHECKS};
+use crate::archive;
+use crate::archive::encryption;
+use crate::features;
+use crate::i3s;
+use crate::i3s::{SceneLayer, SlpkVersion};
+use crate::index::{self, IndexReport};
+use crate::json::{self, Value};
+use crate::lint::SeverityCounts;
+use crate::repair::{self, NamingProblem};
+use crate::schema::{self, Violation};
+use crate::unpack::EntryQueue;
+use failure::Error;
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zip::ZipArchive;
+
+/// The versions of the point cloud profile whose layout is understood.
+const KNOWN_POINT_CLOUD_VERSIONS: [&str; 1] = ["2.0"];
+
+/// The folders below each node which hold resources the node pages refer
+/// to. Entries in these folders which no node refers to are orphans.
+const RESOURCE_FOLDERS: [&str; 3] = ["geometries", "textures", "attributes"];
+
+/// Why the options of a validation couldn't be parsed.
+#[derive(Debug, Fail)]
+pub enum ValidateError {
+    /// A check name which isn't one of the checks.
+    #[fail(
+        display = "Unknown check '{}', expected refs, naming, gzip, json, schema or index",
+        _0
+    )]
+    UnknownCheck(String),
+}
+
+/// A resource which a node refers to, but which the package doesn't hold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingResource {
+    /// The index of the node in the node pages.
+    pub node_id: String,
+    /// The entry the resource was expected in, without any `.gz` suffix,
+    /// or for a point cloud without any extension.
+    pub entry_name: String,
+}
+
+/// What was found of a layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerReport {
+    /// The id of the layer.
+    pub id: usize,
+    /// The I3S version from the store of the layer document, or that of
+    /// the package when the store has none.
+    pub version: SlpkVersion,
+    /// Why the layout of the layer isn't understood, in which case nothing
+    /// else was checked.
+    pub unknown_layout: Option<String>,
+    /// The nodes of the node pages.
+    pub nodes: usize,
+    /// In the order of the node pages.
+    pub missing: Vec<MissingResource>,
+    /// Entries below `nodes/` which no node refers to, in the order of their
+    /// names without any `.gz` suffix.
+    pub orphaned: Vec<String>,
+}
+
+impl LayerReport {
+    /// Whether nothing is missing or orphaned, and the layout was understood.
+    pub fn passed(&self) -> bool {
+        self.unknown_layout.is_none() && self.missing.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// A JSON resource which doesn't match its schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    /// The name of the entry.
+    pub entry_name: String,
+    /// Where and how it doesn't match.
+    pub violation: Violation,
+}
+
+/// What a validation found, all at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    /// What was found of each layer.
+    pub layers: Vec<LayerReport>,
+    /// In the order of the entries, and empty unless schemas were checked.
+    pub schema_violations: Vec<SchemaViolation>,
+    /// `None` when the package has no hash index.
+    pub hash_index: Option<IndexReport>,
+    /// Entries whose content doesn't match their name, in the order of the
+    /// entries.
+    pub naming_problems: Vec<NamingProblem>,
+}
+
+impl ValidationReport {
+    /// What didn't pass, in a line, such as "missing resources: 3, orphaned
+    /// entries: 1".
+    pub fn problems(&self) -> String {
+        let count = |problems: usize, what: &str| {
+            Some(format!("{}: {}", what, problems)).filter(|_| problems > 0)
+        };
+        let layers = &self.layers;
+        let hash_index = self.hash_index.as_ref();
+        let problems: Vec<String> = [
+            count(
+                layers.iter().filter(|l| l.unknown_layout.is_some()).count(),
+                "layers of unknown layout",
+            ),
+            count(
+                layers.iter().map(|l| l.missing.len()).sum(),
+                "missing resources",
+            ),
+            count(
+                layers.iter().map(|l| l.orphaned.len()).sum(),
+                "orphaned entries",
+            ),
+            count(self.schema_violations.len(), "schema violations"),
+            count(
+                self.naming_problems.len(),
+                "entries misnamed for their content",
+            ),
+            hash_index
+                .filter(|index| !index.is_consistent())
+                .map(|_| "the hash index is inconsistent".to_string()),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+        problems.join(", ")
+    }
+
+    /// Whether nothing was found, in any layer or in the hash index.
+    pub fn passed(&self) -> bool {
+        self.layers.iter().all(LayerReport::passed)
+            && self.schema_violations.is_empty()
+            && self.naming_problems.is_empty()
+            && self
+                .hash_index
+                .as_ref()
+                .map_or(true, IndexReport::is_consistent)
+    }
+}
+
+/// The file name extension of a texture format named by a texture set
+/// definition.
+fn texture_extension(format: &str) -> &str {
+    match format {
+        "dds" => "bin.dds",
+        "ktx-etc2" => "ktx",
+        format => format,
+    }
+}
+
+/// The definition at `index` in the array `array` of the layer document, or
+/// the first one when the node doesn't say which.
+fn definition<'a>(layer: &'a SceneLayer, array: &str, index: Option<&Value>) -> Option<&'a Value> {
+    let index = index.and_then(Value::as_u64).unwrap_or(0) as usize;
+    layer
+        .document
+        .get(array)
+        .and_then(Value::as_array)
+        .and_then(|definitions| definitions.get(index))
+}
+
+/// The entries, relative to the layer root, holding the resources of a node
+/// from the node pages.
+pub(crate) fn expected_entries(
+    layer: &SceneLayer,
+    node: &Value,
+    attribute_keys: &[String],
+) -> Vec<String> {
+    let mut expected = Vec::new();
+    let mesh = match node.get("mesh") {
+        Some(mesh) => mesh,
+        None => return expected,
+    };
+    let resource_of = |member: &Value| member.get("resource").and_then(Value::as_u64);
+
+    if let Some(geometry) = mesh.get("geometry") {
+        if let Some(resource) = resource_of(geometry) {
+            let buffers = definition(layer, "geometryDefinitions", geometry.get("definition"))
+                .and_then(|definition| definition.get("geometryBuffers"))
+                .and_then(Value::as_array)
+                .map_or(1, Vec::len);
+            for buffer in 0..buffers {
+                expected.push(format!("nodes/{}/geometries/{}.bin", resource, buffer));
+            }
+        }
+    }
+
+    if let Some(material) = mesh.get("material") {
+        let texture_set = definition(layer, "materialDefinitions", material.get("definition"))
+            .and_then(|definition| definition.get("pbrMetallicRoughness"))
+            .and_then(|pbr| pbr.get("baseColorTexture"))
+            .and_then(|texture| texture.get("textureSetDefinitionId"))
+            .and_then(|id| definition(layer, "textureSetDefinitions", Some(id)));
+        let formats = texture_set
+            .and_then(|set| set.get("formats"))
+            .and_then(Value::as_array);
+        if let (Some(resource), Some(formats)) = (resource_of(material), formats) {
+            for format in formats {
+                if let (Some(name), Some(format)) = (
+                    format.get("name").and_then(Value::as_str),
+                    format.get("format").and_then(Value::as_str),
+                ) {
+                    expected.push(format!(
+                        "nodes/{}/textures/{}.{}",
+                        resource,
+                        name,
+                        texture_extension(format)
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(resource) = mesh.get("attribute").and_then(resource_of) {
+        for key in attribute_keys {
+            expected.push(format!("nodes/{}/attributes/{}/0.bin", resource, key));
+        }
+    }
+    expected
+}
+
+/// The entries, relative to the layer root and without their extensions,
+/// holding the resources of a node of a point cloud: its positions and each
+/// of its attributes but the elevation, which is taken from the positions.
+fn expected_point_cloud_entries(
+    node: &Value,
+    position: usize,
+    attribute_keys: &[String],
+) -> Vec<String> {
+    let resource = node
+        .get("resourceId")
+        .and_then(Value::as_u64)
+        .unwrap_or(position as u64);
+    let mut expected = vec![format!("nodes/{}/geometries/0", resource)];
+    for key in attribute_keys {
+        expected.push(format!("nodes/{}/attributes/{}", resource, key));
+    }
+    expected
+}
+
+/// The keys of the attributes of a point cloud which are stored below each
+/// node.
+fn point_cloud_attribute_keys(layer: &SceneLayer) -> Vec<String> {
+    let storage = layer
+        .document
+        .get("attributeStorageInfo")
+        .and_then(Value::as_array);
+    storage
+        .into_iter()
+        .flatten()
+        .filter(|info| info.get("encoding").and_then(Value::as_str) != Some("embedded-elevation"))
+        .filter_map(|info| info.get("key").and_then(Value::as_str).map(str::to_string))
+        .collect()
+}
+
+/// Why the layout of a layer isn't understood, if it isn't.
+fn unknown_layout(layer: &SceneLayer, version: &SlpkVersion) -> Option<String> {
+    if !layer.uses_node_pages() {
+        return Some("the layer has no node pages".to_string());
+    }
+    let understood = match version {
+        // Layers which don't say are checked as if they were of a version
+        // which is understood.
+        _ if version.is_missing() => true,
+        SlpkVersion::Unknown(version) if layer.is_point_cloud() => KNOWN_POINT_CLOUD_VERSIONS
+            .iter()
+            .any(|known| version == known || version.starts_with(&format!("{}.", known))),
+        _ => !layer.is_point_cloud() && version.has_node_pages() == Some(true),
+    };
+    if understood {
+        None
+    } else {
+        Some(format!("I3S version {} isn't supported", version))
+    }
+}
+
+/// What was found of a layer, besides its findings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerSummary {
+    /// The id of the layer.
+    pub id: usize,
+    /// The I3S version from the store of the layer document, or that of
+    /// the package when the store has none.
+    pub version: SlpkVersion,
+    /// Why the layout of the layer isn't understood, in which case nothing
+    /// else was checked.
+    pub unknown_layout: Option<String>,
+    /// The nodes of the node pages.
+    pub nodes: usize,
+    /// The resources the nodes refer to which aren't stored.
+    pub missing: usize,
+    /// The entries below `nodes/` which no node refers to.
+    pub orphaned: usize,
+}
+
+/// What a validation found, besides the findings themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidateSummary {
+    /// The findings of each severity.
+    pub counts: SeverityCounts,
+    /// What was found of each layer.
+    pub layers: Vec<LayerSummary>,
+    /// `None` when the package has no hash index, or it wasn't checked.
+    pub hash_index: Option<IndexReport>,
+    /// The time spent in each check which ran, summed over the worker
+    /// threads.
+    pub timings: Vec<(Check, Duration)>,
+}
+
+impl ValidateSummary {
+    /// Whether nothing was found, of any severity.
+    pub fn passed(&self) -> bool {
+        self.counts == SeverityCounts::default()
+    }
+}
+
+/// The checks run unless others are asked for.
+pub const DEFAULT_CHECKS: [Check; 3] = [Check::Refs, Check::Naming, Check::Index];
+
+/// What validation checks, and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidateOptions {
+    /// The checks to run, in any order.
+    pub checks: Vec<Check>,
+    /// The password which decrypts the encrypted entries of the package
+    /// as they are read.
+    pub password: Option<String>,
+    /// The number of worker threads the entries are checked by, one per
+    /// core by default.
+    pub threads: Option<usize>,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> ValidateOptions {
+        ValidateOptions {
+            checks: DEFAULT_CHECKS.to_vec(),
+            password: None,
+            threads: None,
+        }
+    }
+}
+
+impl ValidateOptions {
+    /// Whether `check` is one of the checks to run.
+    pub fn runs(&self, check: Check) -> bool {
+        self.checks.contains(&check)
+    }
+}
+
+/// `entry_names` maps the logical name of each entry, without any `.gz`
+/// suffix, to its name. Passes what is missing and orphaned to `found`, in
+/// the order of the node pages and then of the entry names.
+fn validate_layer<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    layer: &SceneLayer,
+    package_version: &SlpkVersion,
+    entry_names: &BTreeMap<String, String>,
+    found: &mut impl FnMut(Problem),
+) -> Result<LayerSummary, Error> {
+    let version = SlpkVersion::of_layer(layer).unwrap_or_else(|| package_version.clone());
+    let mut summary = LayerSummary {
+        id: layer.id,
+        unknown_layout: unknown_layout(layer, &version),
+        version,
+        nodes: 0,
+        missing: 0,
+        orphaned: 0,
+    };
+    // These come from the layer document alone, so they are noted whether
+    // or not the layout is understood.
+    for note in features::undecodable(&layer.document) {
+        found(Problem::Undecodable {
+            layer: layer.id,
+            note,
+        });
+    }
+    if let Some(reason) = &summary.unknown_layout {
+        found(Problem::UnknownLayout {
+            layer: layer.id,
+            version: summary.version.clone(),
+            reason: reason.clone(),
+        });
+        return Ok(summary);
+    }
+    let mut missing = |node_id: &str, entry_name: &str| {
+        summary.missing += 1;
+        found(Problem::Missing {
+            layer: layer.id,
+            resource: MissingResource {
+                node_id: node_id.to_string(),
+                entry_name: entry_name.to_string(),
+            },
+        });
+    };
+
+    let attribute_keys: Vec<String> = if layer.is_point_cloud() {
+        point_cloud_attribute_keys(layer)
+    } else {
+        i3s::declared_attributes(&layer.document)
+            .into_iter()
+            .filter_map(|attribute| attribute.key)
+            .collect()
+    };
+    let page_nodes = i3s::read_page_nodes(slpk_archive, layer)?;
+    let mut referenced = BTreeSet::new();
+    for (position, node) in page_nodes.iter().enumerate() {
+        let node_id = node
+            .get("index")
+            .and_then(Value::as_u64)
+            .unwrap_or(position as u64)
+            .to_string();
+        if layer.is_point_cloud() {
+            for expected in expected_point_cloud_entries(node, position, &attribute_keys) {
+                let stem = layer.entry_name(&expected);
+                let prefix = format!("{}.", stem);
+                let stored: Vec<String> = entry_names
+                    .range(prefix.clone()..)
+                    .take_while(|(name, _)| name.starts_with(&prefix))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                if stored.is_empty() {
+                    missing(&node_id, &stem);
+                }
+                referenced.insert(stem);
+                referenced.extend(stored);
+            }
+            continue;
+        }
+        for expected in expected_entries(layer, node, &attribute_keys) {
+            let entry_name = layer.entry_name(&expected);
+            if !entry_names.contains_key(&entry_name) {
+                missing(&node_id, &entry_name);
+            }
+            referenced.insert(entry_name);
+        }
+    }
+    summary.nodes = page_nodes.len();
+
+    // The folders of nodes which are referred to, for entries which aren't
+    // resources the node pages describe, such as node index documents.
+    let referenced_folders: BTreeSet<&str> = referenced
+        .iter()
+        .filter_map(|name| {
+            let relative_name = &name[layer.root.len()..];
+            let folder_len = relative_name.match_indices('/').nth(1)?.0 + 1;
+            Some(&name[..layer.root.len() + folder_len])
+        })
+        .collect();
+    for (entry_name, stored_name) in entry_names {
+        let relative_name = match entry_name.strip_prefix(layer.root.as_str()) {
+            Some(relative_name) if layer.contains(entry_name) => relative_name,
+            _ => continue,
+        };
+        let mut parts = relative_name.splitn(4, '/');
+        let (resource, folder) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("nodes"), Some(resource), Some(folder), rest) => (resource, rest.map(|_| folder)),
+            _ => continue,
+        };
+        let orphaned = match folder {
+            Some(folder) if RESOURCE_FOLDERS.contains(&folder) => !referenced.contains(entry_name),
+            _ => {
+                !referenced_folders.contains(&layer.entry_name(&format!("nodes/{}/", resource))[..])
+            }
+        };
+        if orphaned {
+            summary.orphaned += 1;
+            found(Problem::Orphaned {
+                layer: layer.id,
+                entry_name: stored_name.clone(),
+            });
+        }
+    }
+    Ok(summary)
+}
+
+/// What was found of one entry, in the order its checks ran.
+struct EntryFindings {
+    index: usize,
+    problems: Vec<Problem>,
+    timings: [Duration; CHECKS.len()],
+}
+
+/// Runs the checks of `options` which look at one entry at a time on the
+/// entry at `index`: compares its first bytes against its name, decompresses
+/// it if it is gzipped, parses it if it is JSON, and checks it against its
+/// schema if it has one. An entry which doesn't parse isn't checked against
+/// its schema when JSON is checked too.
+fn validate_entry<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    index: usize,
+    options: &ValidateOptions,
+) -> Result<EntryFindings, Error> {
+    let mut findings = EntryFindings {
+        index,
+        problems: Vec::new(),
+        timings: [Duration::default(); CHECKS.len()],
+    };
+    let mut start = Instant::now();
+    let mut time = |check: Check, timings: &mut [Duration; CHECKS.len()]| {
+        let now = Instant::now();
+        timings[check.index()] += now - start;
+        start = now;
+    };
+    let mut entry = slpk_archive.by_index(index)?;
+    let name = entry.name().to_string();
+    let mut magic = Vec::with_capacity(archive::GZIP_MAGIC.len());
+    (&mut entry)
+        .take(archive::GZIP_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    if options.runs(Check::Naming) && repair::has_content_to_check(&name, entry.size()) {
+        if let Some(mismatch) = repair::naming_mismatch(&name, &magic) {
+            findings.problems.push(Problem::Naming(NamingProblem {
+                name: name.clone(),
+                mismatch,
+            }));
+        }
+        time(Check::Naming, &mut findings.timings);
+    }
+
+    let gzipped = name.ends_with(".gz") && archive::is_gzip(&magic);
+    let parses = options.runs(Check::Json) && name.trim_end_matches(".gz").ends_with(".json");
+    let has_schema = options.runs(Check::Schema) && schema::schema_of_entry(&name).is_some();
+    let decompresses = options.runs(Check::Gzip) && gzipped;
+    if !(parses || has_schema || decompresses) {
+        return Ok(findings);
+    }
+    // The whole entry is read, even past the end of its gzip stream, so that
+    // its CRC is checked.
+    let mut contents = Cursor::new(magic).chain(entry);
+    let mut document = Vec::new();
+    let read = if gzipped {
+        let mut decoder = GzDecoder::new(&mut contents);
+        if parses || has_schema {
+            decoder.read_to_end(&mut document).map(|_| ())
+        } else {
+            io::copy(&mut decoder, &mut io::sink()).map(|_| ())
+        }
+    } else {
+        contents.read_to_end(&mut document).map(|_| ())
+    };
+    let read = read.and_then(|_| io::copy(&mut contents, &mut io::sink()).map(|_| ()));
+    // Reading is put down to the first check which needed it.
+    let reader = [Check::Gzip, Check::Json, Check::Schema]
+        .iter()
+        .copied()
+        .find(|&check| match check {
+            Check::Gzip => decompresses,
+            Check::Json => parses,
+            _ => has_schema,
+        })
+        .unwrap_or(Check::Schema);
+    match read {
+        Ok(()) => {}
+        Err(e) if options.runs(Check::Gzip) => {
+            let error = if e.to_string() == "Invalid checksum" {
+                "the data doesn't match its CRC".to_string()
+            } else {
+                e.to_string()
+            };
+            findings.problems.push(Problem::CorruptGzip {
+                entry_name: name,
+                error,
+            });
+            time(reader, &mut findings.timings);
+            return Ok(findings);
+        }
+        Err(e) => return Err(Error::from(e)),
+    }
+    time(reader, &mut findings.timings);
+
+    let mut value = None;
+    if parses {
+        match json::parse_bytes(&document) {
+            Ok(parsed) => value = Some(parsed),
+            Err(e) => findings.problems.push(Problem::InvalidJson {
+                entry_name: name.clone(),
+                error: e.to_string(),
+            }),
+        }
+        time(Check::Json, &mut findings.timings);
+    }
+    if has_schema && (value.is_some() || !parses) {
+        let violation = match &value {
+            Some(value) => schema::check_value(&name, value),
+            None => schema::check_entry(&name, &document),
+        };
+        if let Some(violation) = violation {
+            findings.problems.push(Problem::Schema(SchemaViolation {
+                entry_name: name,
+                violation,
+            }));
+        }
+        time(Check::Schema, &mut findings.timings);
+    }
+    Ok(findings)
+}
+
+/// The problems of a hash index, in the order its report lists them.
+fn index_problems(report: &IndexReport) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = report
+        .stale
+        .iter()
+        .cloned()
+        .map(Problem::StaleIndexRecord)
+        .chain(report.missing.iter().cloned().map(Problem::UnindexedEntry))
+        .collect();
+    if report.dangling > 0 {
+        problems.push(Problem::DanglingIndexRecords(report.dangling));
+    }
+    if report.duplicate_keys > 0 {
+        problems.push(Problem::DuplicateIndexKeys(report.duplicate_keys));
+    }
+    if !report.sorted {
+        problems.push(Problem::UnsortedIndex);
+    }
+    problems
+}
+
+/// Validates a package as `validate` does, passing each finding to
+/// `on_finding` as soon as it is found rather than holding on to them, so
+/// that packages with hundreds of thousands of findings can be validated in
+/// constant memory. Validation waits while `on_finding` runs, so a slow
+/// consumer simply slows it down.
+///
+/// The references of each layer are checked first, layer by layer. The
+/// entries are then shared out between worker threads, each with its own
+/// handle on the package, which take the next entry to check until there
+/// are none left, and last the hash index is checked. The findings of the
+/// entries come in the order of the central directory whatever the number
+/// of threads, those of each entry in the order of `CHECKS`, so the output
+/// is the same from run to run. The severity counts of the summary are kept
+/// as the findings are handed on.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::validate::{self, Check, ValidateOptions};
+/// use std::path::Path;
+///
+/// let options = ValidateOptions {
+///     checks: vec![Check::Gzip, Check::Json],
+///     ..ValidateOptions::default()
+/// };
+/// let mut findings = Vec::new();
+/// let summary = validate::validate_streaming(
+///     Path::new("tests/fixtures/building.slpk"),
+///     &options,
+///     |finding| findings.push(finding.to_string()),
+/// )?;
+/// assert!(findings.is_empty());
+/// assert!(summary.passed());
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn validate_streaming<F: FnMut(Finding)>(
+    slpk_file_path: &Path,
+    options: &ValidateOptions,
+    mut on_finding: F,
+) -> Result<ValidateSummary, Error> {
+    let decryption = encryption::decryption(slpk_file_path, options.password.as_deref())?;
+    let decryption = decryption.as_ref();
+    let mut slpk_archive = encryption::open_archive(slpk_file_path, decryption)?;
+    let mut counts = SeverityCounts::default();
+    let mut timings = [Duration::default(); CHECKS.len()];
+    let mut found = |problem: Problem| {
+        let finding = Finding::new(problem);
+        counts.add(finding.severity);
+        on_finding(finding);
+    };
+
+    let mut layers = Vec::new();
+    if options.runs(Check::Refs) {
+        let start = Instant::now();
+        let scene_layers = i3s::read_layers(&mut slpk_archive)?;
+        if scene_layers.is_empty() {
+            return Err(Error::from(i3s::I3sError::MissingSceneLayerDocument));
+        }
+        let version = i3s::detect_version(&mut slpk_archive)?;
+        // Resources are looked up by their logical name, whether or not
+        // they are gzipped.
+        let mut entry_names = BTreeMap::new();
+        for i in 0..slpk_archive.len() {
+            let name = slpk_archive.by_index(i)?.name().to_string();
+            entry_names.insert(name.trim_end_matches(".gz").to_string(), name);
+        }
+        for layer in &scene_layers {
+            layers.push(validate_layer(
+                &mut slpk_archive,
+                layer,
+                &version,
+                &entry_names,
+                &mut found,
+            )?);
+        }
+        timings[Check::Refs.index()] += start.elapsed();
+    }
+
+    let num_entries = slpk_archive.len();
+    let per_entry = [Check::Naming, Check::Gzip, Check::Json, Check::Schema];
+    if per_entry.iter().any(|&check| options.runs(check)) {
+        let num_threads = options
+            .threads
+            .unwrap_or_else(num_cpus::get)
+            .clamp(1, num_entries.max(1));
+        let queue = EntryQueue::new(num_entries);
+        thread::scope(|scope| -> Result<(), Error> {
+            // Bounded, so that the workers wait for a slow consumer rather
+            // than queueing up findings.
+            let (sender, receiver) = mpsc::sync_channel(num_threads * 4);
+            for _ in 0..num_threads {
+                let sender = sender.clone();
+                let queue = &queue;
+                scope.spawn(move || {
+                    let mut slpk_archive =
+                        match encryption::open_archive(slpk_file_path, decryption) {
+                            Ok(slpk_archive) => slpk_archive,
+                            Err(e) => {
+                                let _ = sender.send(Err(e));
+                                return;
+                            }
+                        };
+                    while let Some(index) = queue.next() {
+                        let result = validate_entry(&mut slpk_archive, index, options);
+                        let failed = result.is_err();
+                        // The consumer hangs up when it stops on an error.
+                        if sender.send(result).is_err() || failed {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            // Entries are handed out in order, so only the few which
+            // overtake a slower worker wait here.
+            let mut pending = BTreeMap::new();
+            let mut next_to_report = 0;
+            for result in receiver {
+                let entry: EntryFindings = result?;
+                for check in &CHECKS {
+                    timings[check.index()] += entry.timings[check.index()];
+                }
+                pending.insert(entry.index, entry.problems);
+                while let Some(problems) = pending.remove(&next_to_report) {
+                    problems.into_iter().for_each(&mut found);
+                    next_to_report += 1;
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut hash_index = None;
+    if options.runs(Check::Index) {
+        let start = Instant::now();
+        hash_index = index::check_decrypted_hash_index(slpk_file_path, decryption)?;
+        for problem in hash_index.iter().flat_map(index_problems) {
+            found(problem);
+        }
+        timings[Check::Index.index()] += start.elapsed();
+    }
+    Ok(ValidateSummary {
+        counts,
+        layers,
+        hash_index,
+        timings: CHECKS
+            .iter()
+            .filter(|&&check| options.runs(check))
+            .map(|&check| (check, timings[check.index()]))
+            .collect(),
+    })
+}
+
+/// Validates every layer of a package, and with `check_schemas`, every JSON
+/// resource which has a schema. The layer documents and node pages must
+/// exist and parse, or an error is returned.
+pub fn validate(slpk_file_path: &Path, check_schemas: bool) -> Result<ValidationReport, Error> {
+    let mut options = ValidateOptions::default();
+    if check_schemas {
+        options.checks.push(Check::Schema);
+    }
+    validate_with_options(slpk_file_path, &options)
+}
+
+/// Collects the findings of `validate_streaming` into a report, for callers
+/// which want them all at once.
+pub fn validate_with_options(
+    slpk_file_path: &Path,
+    options: &ValidateOptions,
+) -> Result<ValidationReport, Error> {
+    let mut problems = Vec::new();
+    let summary = validate_streaming(slpk_file_path, options, |finding| {
+        problems.push(finding.problem)
+    })?;
+    let mut report = ValidationReport {
+        layers: summary
+            .layers
+            .into_iter()
+            .map(|layer| LayerReport {
+                id: layer.id,
+                version: layer.version,
+                unknown_layout: layer.unknown_layout,
+                nodes: layer.nodes,
+                missing: Vec::with_capacity(layer.missing),
+                orphaned: Vec::with_capacity(layer.orphaned),
+            })
+            .collect(),
+        schema_violations: Vec::new(),
+        hash_index: summary.hash_index,
+        naming_problems: Vec::new(),
+    };
+    // The findings of a layer come before those of the next, so those of a
+    // layer whose id is repeated belong to the first one with it from the
+    // layer of the findings before.
+    let mut layer_at = 0;
+    let mut layer_of = |layers: &[LayerReport], id: usize| -> usize {
+        layer_at = (layer_at..layers.len())
+            .find(|&at| layers[at].id == id)
+            .unwrap_or(layer_at);
+        layer_at
+    };
+    for problem in problems {
+        match problem {
+            Problem::Missing { layer, resource } => {
+                let at = layer_of(&report.layers, layer);
+                report.layers[at].missing.push(resource);
+            }
+            Problem::Orphaned { layer, entry_name } => {
+                let at = layer_of(&report.layers, layer);
+                report.layers[at].orphaned.push(entry_name);
+            }
+            Problem::Schema(violation) => report.schema_violations.push(violation),
+            Problem::Naming(problem) => report.naming_problems.push(problem),
+            // The summary has the layout of each layer and the report of
+            // the hash index as they are.
+            _ => {}
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::{FailOn, Severity};
+    use crate::synthetic::{SyntheticPackage, TempDir};
+
+    const LAYER: &str = r#"{"layerType":"3DObject","store":{"version":"1.8"},"nodePages":{"nodesPerPage":64},
+        "geometryDefinitions":[{"geometryBuffers":[{"offset":8},{"compressedAttributes":{"encoding":"draco"}}]}],
+        "materialDefinitions":[{"pbrMetallicRoughness":{"baseColorTexture":{"textureSetDefinitionId":0}}}],
+        "textureSetDefinitions":[{"formats":[{"name":"0","format":"jpg"},{"name":"0_0_1","format":"dds"}]}],
+        "attributeStorageInfo":[{"key":"f_0","name":"NAME"}],"fields":[{"name":"NAME","type":"esriFieldTypeString"}]}"#;
+
+    const NODE_PAGE: &str = r#"{"nodes":[{"index":0,"children":[1,2]},
+        {"index":1,"mesh":{"geometry":{"definition":0,"resource":0},"material":{"definition":0,"resource":0},"attribute":{"resource":0}}},
+        {"index":2,"mesh":{"geometry":{"definition":0,"resource":1},"attribute":{"resource":1}}}]}"#;
+
+    #[test]
+    fn missing_and_orphaned_resources_are_reported() {
+        let dir = TempDir::new("validate");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", LAYER.as_bytes())
+            .entry("nodepages/0.json.gz", NODE_PAGE.as_bytes())
+            .entry("nodes/0/geometries/0.bin.gz", &[0; 10])
+            .entry("nodes/0/geometries/1.bin.gz", &[0; 10])
+            .entry("nodes/0/textures/0.jpg", &[0; 10])
+            .entry("nodes/0/textures/0_0_1.bin.dds.gz", &[0; 10])
+            .entry("nodes/0/attributes/f_0/0.bin.gz", &[0; 10])
+            .entry("nodes/0/3dNodeIndexDocument.json.gz", b"{}")
+            .entry("nodes/1/geometries/0.bin.gz", &[0; 10])
+            .entry("nodes/1/textures/0.jpg", &[0; 10])
+            .entry("nodes/7/geometries/0.bin.gz", &[0; 10])
+            .write_to_file(&path)
+            .unwrap();
+        let report = validate(&path, false).unwrap();
+        let summary = validate_streaming(&path, &ValidateOptions::default(), |_| {}).unwrap();
+        let passed = !FailOn::Warning.fails(&summary.counts);
+
+        assert!(!passed);
+        let layer = &report.layers[0];
+        assert_eq!((layer.nodes, layer.unknown_layout.as_ref()), (3, None));
+        assert_eq!(
+            layer
+                .missing
+                .iter()
+                .map(|missing| (missing.node_id.as_str(), missing.entry_name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2", "nodes/1/geometries/1.bin"),
+                ("2", "nodes/1/attributes/f_0/0.bin"),
+            ]
+        );
+        // Node 2 has no material, and nothing refers to resource 7.
+        assert_eq!(
+            layer.orphaned,
+            vec!["nodes/1/textures/0.jpg", "nodes/7/geometries/0.bin.gz"]
+        );
+    }
+
+    #[test]
+    fn findings_are_streamed_check_by_check_with_their_counts() {
+        let dir = TempDir::new("validate-streaming");
+        let path = dir.join("package.slpk");
+        let layer = LAYER.replace(r#"{"layerType""#, r#"{"id":0,"layerType""#);
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", layer.as_bytes())
+            .entry("nodepages/0.json.gz", NODE_PAGE.as_bytes())
+            .entry("nodes/0/geometries/0.bin.gz", &[0; 10])
+            .entry("nodes/0/geometries/1.bin.gz", &[0; 10])
+            .entry("nodes/0/textures/0.jpg", &[0; 10])
+            .entry("nodes/0/textures/0_0_1.bin.dds.gz", &[0; 10])
+            .entry("nodes/0/attributes/f_0/0.bin.gz", &[0; 10])
+            .entry("nodes/1/geometries/0.bin.gz", &[0; 10])
+            .entry("nodes/7/geometries/0.bin.gz", &[0; 10])
+            .entry("statistics/f_0/0.json", &archive::GZIP_MAGIC)
+            .with_hash_index_record("nodepages/0.json.gz", 0)
+            .write_to_file(&path)
+            .unwrap();
+        let mut findings = Vec::new();
+        let summary = validate_streaming(&path, &ValidateOptions::default(), |finding| {
+            findings.push(finding)
+        })
+        .unwrap();
+        let report = validate(&path, false).unwrap();
+
+        assert_eq!(
+            findings
+                .iter()
+                .map(|finding| (
+                    finding.severity,
+                    finding.check(),
+                    finding.problem.to_string()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Severity::Info,
+                    Check::Refs,
+                    "layer 0: Draco compressed geometry buffers are present, but decoding Draco \
+                     compressed geometry isn't in any build of slpkg yet"
+                        .to_string()
+                ),
+                (
+                    Severity::Error,
+                    Check::Refs,
+                    "layer 0: nodes/1/geometries/1.bin is missing, for node 2".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Check::Refs,
+                    "layer 0: nodes/1/attributes/f_0/0.bin is missing, for node 2".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Check::Refs,
+                    "layer 0: nodes/7/geometries/0.bin.gz is orphaned".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Check::Naming,
+                    "statistics/f_0/0.json is gzipped but not named .gz".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Check::Index,
+                    "the record of nodepages/0.json.gz points elsewhere".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            summary.counts,
+            SeverityCounts {
+                errors: 3,
+                warnings: 2,
+                info: 1,
+            }
+        );
+        assert_eq!(summary.layers[0].nodes, 3);
+        assert_eq!(
+            report.problems(),
+            "missing resources: 2, orphaned entries: 1, entries misnamed for their content: 1, \
+             the hash index is inconsistent"
+        );
+    }
+
+    /// A package with a problem for each check in most of its entries.
+    fn troubled_package(path: &Path) {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+        use zip::write::FileOptions;
+        use zip::{CompressionMethod, ZipWriter};
+
+        let gzip = |contents: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(contents).unwrap();
+            encoder.finish().unwrap()
+        };
+        let layer = LAYER.replace(r#"{"layerType""#, r#"{"id":0,"layerType""#);
+        let mut entries = vec![
+            ("3dSceneLayer.json.gz".to_string(), gzip(layer.as_bytes())),
+            (
+                "nodepages/0.json.gz".to_string(),
+                gzip(NODE_PAGE.as_bytes()),
+            ),
+        ];
+        for i in 0..60 {
+            let (name, contents) = match i % 5 {
+                0 => ("3dNodeIndexDocument.json.gz", gzip(br#"{"id":"#)),
+                1 => ("3dNodeIndexDocument.json.gz", {
+                    let gzipped = gzip(&[b'{'; 4096]);
+                    gzipped[..gzipped.len() / 2].to_vec()
+                }),
+                2 => ("3dNodeIndexDocument.json", gzip(b"{}")),
+                3 => ("geometries/0.bin.gz", vec![0; 10]),
+                _ => ("3dNodeIndexDocument.json.gz", gzip(br#"{"id":[]}"#)),
+            };
+            entries.push((format!("nodes/{}/{}", i, name), contents));
+        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in &entries {
+            writer.start_file(name.as_str(), stored).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[test]
+    fn findings_are_the_same_whatever_the_number_of_threads() {
+        let dir = TempDir::new("validate-threads");
+        let path = dir.join("package.slpk");
+        troubled_package(&path);
+        let findings = |threads: usize| {
+            let options = ValidateOptions {
+                checks: CHECKS.to_vec(),
+                threads: Some(threads),
+                ..ValidateOptions::default()
+            };
+            let mut findings = Vec::new();
+            let summary =
+                validate_streaming(&path, &options, |finding| findings.push(finding)).unwrap();
+            (findings, summary.counts)
+        };
+        let (one, one_counts) = findings(1);
+        let (eight, eight_counts) = findings(8);
+
+        assert_eq!(one, eight);
+        assert_eq!(one_counts, eight_counts);
+        // Gzip streams without a .gz suffix aren't decompressed, as readers
+        // wouldn't, so they don't parse either.
+        let of = |check: Check| one.iter().filter(|f| f.check() == check).count();
+        assert_eq!(
+            [Check::Naming, Check::Gzip, Check::Json, Check::Schema].map(of),
+            [24, 12, 24, 12]
+        );
+    }
+
+    #[test]
+    fn only_the_checks_asked_for_run() {
+        let dir = TempDir::new("validate-checks");
+        let path = dir.join("package.slpk");
+        troubled_package(&path);
+        let options = ValidateOptions {
+            checks: vec![Check::Gzip, Check::Schema],
+            ..ValidateOptions::default()
+        };
+        let mut findings = Vec::new();
+        let summary =
+            validate_streaming(&path, &options, |finding| findings.push(finding)).unwrap();
+
+        assert!(findings
+            .iter()
+            .all(|f| [Check::Gzip, Check::Schema].contains(&f.check())));
+        assert_eq!(
+            findings[1].to_string(),
+            "error gzip: nodes/1/3dNodeIndexDocument.json.gz doesn't decompress, corrupt deflate stream"
+        );
+        assert_eq!(summary.layers, Vec::new());
+        assert_eq!(
+            summary
+                .timings
+                .iter()
+                .map(|(check, _)| *check)
+                .collect::<Vec<_>>(),
+            vec![Check::Gzip, Check::Schema]
+        );
+        assert_eq!("gzip".parse::<Check>().unwrap(), Check::Gzip);
+        assert!("crc".parse::<Check>().is_err());
+    }
+
+    #[test]
+    fn point_cloud_resources_are_matched_whatever_their_encoding() {
+        let dir = TempDir::new("validate-points");
+        let path = dir.join("package.slpk");
+        // The elevation is taken from the positions, and stored nowhere.
+        SyntheticPackage::new()
+            .entry(
+                "3dSceneLayer.json.gz",
+                br#"{"id":0,"layerType":"PointCloud","store":{"version":"2.0","index":{}},
+                    "attributeStorageInfo":[{"key":"1","name":"ELEVATION","encoding":"embedded-elevation"},
+                        {"key":"2","name":"INTENSITY","encoding":"lepcc-intensity"},{"key":"4","name":"CLASS_CODE"}]}"#,
+            )
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"resourceId":0,"firstChild":1,"childCount":1},{"resourceId":3}]}"#,
+            )
+            .entry("nodes/0/geometries/0.bin.pccxyz", &[0; 10])
+            .entry("nodes/0/attributes/2.bin.pccint", &[0; 10])
+            .entry("nodes/0/attributes/4.bin.gz", &[0; 10])
+            .entry("nodes/3/geometries/0.bin.pccxyz", &[0; 10])
+            .entry("nodes/3/attributes/2.bin.pccint", &[0; 10])
+            .entry("nodes/3/attributes/40.bin.gz", &[0; 10])
+            .write_to_file(&path)
+            .unwrap();
+        let report = validate(&path, true).unwrap();
+
+        let layer = &report.layers[0];
+        assert_eq!(layer.unknown_layout, None);
+        assert_eq!(layer.nodes, 2);
+        assert_eq!(
+            layer.missing,
+            vec![MissingResource {
+                node_id: "1".to_string(),
+                entry_name: "nodes/3/attributes/4".to_string(),
+            }]
+        );
+        assert_eq!(layer.orphaned, ["nodes/3/attributes/40.bin.gz"]);
+        assert_eq!(report.schema_violations, Vec::new());
+    }
+
+    #[test]
+    fn layers_without_node_pages_have_an_unknown_layout() {
+        let dir = TempDir::new("validate-old");
+        let path = dir.join("package.slpk");
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", br#"{"store":{"version":"1.6"}}"#)
+            .entry("nodes/root/3dNodeIndexDocument.json.gz", b"{}")
+            .write_to_file(&path)
+            .unwrap();
+        let report = validate(&path, false).unwrap();
+        let without_layer = dir.join("without-layer.slpk");
+        SyntheticPackage::new()
+            .entry("metadata.json", b"{}")
+            .write_to_file(&without_layer)
+            .unwrap();
+        let missing_layer = validate(&without_layer, false).unwrap_err();
+
+        assert!(!report.passed());
+        assert_eq!(
+            report.layers[0].unknown_layout.as_deref(),
+            Some("the layer has no node pages")
+        );
+        assert!(report.layers[0].orphaned.is_empty());
+        assert!(missing_layer.to_string().contains("3dSceneLayer.json"));
+    }
+
+    #[test]
+    fn schema_violations_fail_validation_when_checked() {
+        let dir = TempDir::new("validate-schema");
+        let path = dir.join("package.slpk");
+        let layer = LAYER.replace(r#"{"layerType""#, r#"{"id":0,"layerType""#);
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", layer.as_bytes())
+            .entry(
+                "nodepages/0.json.gz",
+                br#"{"nodes":[{"index":0,"children":"1"}]}"#,
+            )
+            .write_to_file(&path)
+            .unwrap();
+        let unchecked = validate(&path, false).unwrap();
+        let checked = validate(&path, true).unwrap();
+
+        assert!(unchecked.passed());
+        assert_eq!(unchecked.hash_index, None);
+        assert!(!checked.passed());
+        assert_eq!(
+            checked.schema_violations,
+            vec![SchemaViolation {
+                entry_name: "nodepages/0.json.gz".to_string(),
+                violation: Violation {
+                    pointer: "/nodes/0/children".to_string(),
+                    message: "expected an array, found a string".to_string(),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn stale_hash_index_records_fail_validation() {
+        let dir = TempDir::new("validate-index");
+        let path = dir.join("package.slpk");
+        let layer = LAYER.replace(r#"{"layerType""#, r#"{"id":0,"layerType""#);
+        let package = SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", layer.as_bytes())
+            .entry("nodepages/0.json.gz", br#"{"nodes":[{"index":0}]}"#);
+        package
+            .clone()
+            .with_hash_index()
+            .write_to_file(&path)
+            .unwrap();
+        let indexed = validate(&path, false).unwrap();
+        // The record of the node page points at the layer document, at the
+        // start of the package.
+        package
+            .with_hash_index_record("nodepages/0.json.gz", 0)
+            .write_to_file(&path)
+            .unwrap();
+        let stale = validate(&path, false).unwrap();
+
+        assert!(indexed.passed());
+        assert_eq!(indexed.hash_index.map(|index| index.covered), Some(2));
+        assert!(!stale.passed());
+        assert_eq!(stale.hash_index.unwrap().stale, vec!["nodepages/0.json.gz"]);
+    }
+
+    #[test]
+    fn entries_misnamed_for_their_content_fail_validation() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let dir = TempDir::new("validate-naming");
+        let path = dir.join("package.slpk");
+        let mut statistics = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        statistics.write_all(br#"{"stats":{}}"#).unwrap();
+        let layer = LAYER.replace(r#"{"layerType""#, r#"{"id":0,"layerType""#);
+        SyntheticPackage::new()
+            .entry("3dSceneLayer.json.gz", layer.as_bytes())
+            .entry("nodepages/0.json.gz", br#"{"nodes":[{"index":0}]}"#)
+            .entry("statistics/f_0/0.json", &statistics.finish().unwrap())
+            .write_to_file(&path)
+            .unwrap();
+        let report = validate(&path, false).unwrap();
+
+        assert!(!report.passed());
+        assert_eq!(
+            report.naming_problems,
+            vec![NamingProblem {
+                name: "statistics/f_0/0.json".to_string(),
+                mismatch: repair::Mismatch::GzipWithoutSuffix,
+            }]
+        );
+        assert_eq!(report.problems(), "entries misnamed for their content: 1");
+    }
+}
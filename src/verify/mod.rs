@@ -0,0 +1,259 @@
+// Verifies a package without unpacking it, for packages which may have been
+// corrupted in transit. Every entry is read to its end, so that the zip
+// library checks it against the CRC in the zip directory, and every gzipped
+// entry is decompressed too: a gzip stream which was cut short before it was
+// stored matches its CRC, and only fails once a viewer decompresses it. The
+// entries are shared out between worker threads as they are for an unpack,
+// each with its own handle on the package, and nothing is written. The size
+// and date filters of unpack can leave entries out, going by the zip headers.
+
+use crate::archive;
+use crate::archive::encryption;
+use crate::unpack::{EntryFilters, EntryQueue, Filter};
+use failure::Error;
+use flate2::bufread::GzDecoder;
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::thread;
+use zip::ZipArchive;
+
+/// An entry which failed, and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedEntry {
+    /// The name of the entry.
+    pub entry_name: String,
+    /// Why it failed.
+    pub problem: String,
+}
+
+/// What verifying the entries of a package found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    /// The entries which were verified.
+    pub entries: usize,
+    /// The entries which failed, in the order of the zip directory.
+    pub failed_entries: Vec<FailedEntry>,
+    /// How many entries each filter left out.
+    pub excluded: HashMap<Filter, usize>,
+}
+
+impl VerifyReport {
+    /// Whether every entry verified.
+    pub fn passed(&self) -> bool {
+        self.failed_entries.is_empty()
+    }
+}
+
+/// Verifies every entry of a package, with a worker thread per core.
+///
+/// # Examples
+///
+/// ```
+/// use slpkg::verify;
+/// use std::path::Path;
+///
+/// let report = verify::verify(Path::new("tests/fixtures/building.slpk"))?;
+/// assert_eq!(report.entries, 13);
+/// assert!(report.passed());
+/// # Ok::<(), failure::Error>(())
+/// ```
+pub fn verify(slpk_file_path: &Path) -> Result<VerifyReport, Error> {
+    verify_with_threads(slpk_file_path, None, None)
+}
+
+/// Verifies every entry of a package with `threads` worker threads, or one
+/// per core when `None`. An error is returned only when the package itself
+/// can't be opened, or has encrypted entries which don't decrypt with
+/// `password`; entries which fail are listed in the report.
+pub fn verify_with_threads(
+    slpk_file_path: &Path,
+    threads: Option<usize>,
+    password: Option<&str>,
+) -> Result<VerifyReport, Error> {
+    verify_filtered(slpk_file_path, threads, password, &EntryFilters::default())
+}
+
+/// Verifies the entries of a package which pass the size and date filters
+/// of `filters`, as `verify_with_threads` does.
+pub fn verify_filtered(
+    slpk_file_path: &Path,
+    threads: Option<usize>,
+    password: Option<&str>,
+    filters: &EntryFilters,
+) -> Result<VerifyReport, Error> {
+    let decryption = encryption::decryption(slpk_file_path, password)?;
+    let decryption = decryption.as_ref();
+    let mut slpk_archive = encryption::open_archive(slpk_file_path, decryption)?;
+    let mut selected = Vec::with_capacity(slpk_archive.len());
+    let mut excluded = HashMap::new();
+    for entry_idx in 0..slpk_archive.len() {
+        if *filters != EntryFilters::default() {
+            let entry = slpk_archive.by_index(entry_idx)?;
+            if let Some(filter) = filters.excludes(entry.size(), entry.last_modified()) {
+                *excluded.entry(filter).or_insert(0) += 1;
+                continue;
+            }
+        }
+        selected.push(entry_idx);
+    }
+    let entries = selected.len();
+    let queue = EntryQueue::new(entries);
+    let num_workers = threads.unwrap_or_else(num_cpus::get).max(1).min(entries);
+    let mut failed = thread::scope(|scope| -> Result<Vec<(usize, FailedEntry)>, Error> {
+        let workers: Vec<_> = (0..num_workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<Vec<(usize, FailedEntry)>, Error> {
+                    let mut slpk_archive = encryption::open_archive(slpk_file_path, decryption)?;
+                    let mut failed = Vec::new();
+                    while let Some(position) = queue.next() {
+                        let entry_idx = selected[position];
+                        if let Some(failure) = verify_entry(&mut slpk_archive, entry_idx) {
+                            failed.push((entry_idx, failure));
+                        }
+                    }
+                    Ok(failed)
+                })
+            })
+            .collect();
+        let mut failed = Vec::new();
+        for worker in workers {
+            failed.extend(worker.join().expect("A verify worker panicked")?);
+        }
+        Ok(failed)
+    })?;
+    failed.sort_by_key(|&(entry_idx, _)| entry_idx);
+    Ok(VerifyReport {
+        entries,
+        failed_entries: failed.into_iter().map(|(_, failure)| failure).collect(),
+        excluded,
+    })
+}
+
+/// Describes an error reading an entry, which the zip library reports as
+/// "Invalid checksum" when the data doesn't match its CRC.
+fn read_problem(e: &io::Error) -> String {
+    if e.to_string() == "Invalid checksum" {
+        "the data doesn't match its CRC".to_string()
+    } else {
+        format!("the entry can't be read: {}", e)
+    }
+}
+
+fn verify_entry<R: Read + Seek>(
+    slpk_archive: &mut ZipArchive<R>,
+    entry_idx: usize,
+) -> Option<FailedEntry> {
+    let entry = match slpk_archive.by_index(entry_idx) {
+        Ok(entry) => entry,
+        Err(e) => {
+            return Some(FailedEntry {
+                entry_name: format!("entry {} of the zip directory", entry_idx),
+                problem: format!("the entry can't be read: {}", e),
+            })
+        }
+    };
+    let entry_name = archive::names::decoded_name(&entry);
+    let mut buffered = BufReader::new(entry);
+    let gzip_problem = if entry_name.ends_with(".gz") {
+        let mut decoder = GzDecoder::new(&mut buffered);
+        io::copy(&mut decoder, &mut io::sink()).err()
+    } else {
+        None
+    };
+    // The rest of the entry is read even when it doesn't decompress, as a
+    // CRC mismatch says more about why.
+    let problem = match (io::copy(&mut buffered, &mut io::sink()), gzip_problem) {
+        (Err(e), _) => read_problem(&e),
+        (Ok(_), Some(e)) => format!("the entry doesn't decompress as gzip: {}", e),
+        (Ok(_), None) => return None,
+    };
+    Some(FailedEntry {
+        entry_name,
+        problem,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticPackage, TempDir};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    #[test]
+    fn corrupted_and_truncated_entries_are_reported() {
+        let dir = TempDir::new("verify");
+        let mut package = SyntheticPackage::new();
+        for i in 0..20 {
+            package = package.entry(&format!("nodes/{}/geometries/0.bin", i), &[i; 1000]);
+        }
+        let mut bytes = package.to_bytes().unwrap();
+        let good = dir.join("good.slpk");
+        std::fs::write(&good, &bytes).unwrap();
+
+        // A byte of the sixth geometry flipped in transit.
+        let start = bytes.windows(1000).position(|w| w == [5; 1000]).unwrap();
+        bytes[start + 500] = 6;
+        let corrupted = dir.join("corrupted.slpk");
+        std::fs::write(&corrupted, &bytes).unwrap();
+
+        // A gzipped document cut short before it was stored, so that its
+        // CRC is that of the truncated stream.
+        let mut gzipped = Vec::new();
+        let mut encoder = flate2::write::GzEncoder::new(&mut gzipped, Default::default());
+        encoder.write_all(&[b'x'; 5000]).unwrap();
+        encoder.finish().unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("3dSceneLayer.json.gz", stored).unwrap();
+        writer.write_all(&gzipped[..gzipped.len() - 6]).unwrap();
+        writer.start_file("metadata.json", stored).unwrap();
+        writer.write_all(b"{}").unwrap();
+        let truncated = dir.join("truncated.slpk");
+        std::fs::write(&truncated, writer.finish().unwrap().into_inner()).unwrap();
+
+        let reports: Vec<VerifyReport> = [(&good, 4), (&corrupted, 3), (&truncated, 1)]
+            .iter()
+            .map(|(path, threads)| verify_with_threads(path, Some(*threads), None).unwrap())
+            .collect();
+        let passed = verify_with_threads(&corrupted, Some(2), None)
+            .unwrap()
+            .passed();
+        let none_small_enough = EntryFilters {
+            max_size: Some("1".parse().unwrap()),
+            ..EntryFilters::default()
+        };
+        let filtered = verify_filtered(&corrupted, Some(2), None, &none_small_enough).unwrap();
+
+        assert_eq!(
+            reports[0],
+            VerifyReport {
+                entries: 20,
+                failed_entries: vec![],
+                excluded: HashMap::new(),
+            }
+        );
+        assert!(filtered.passed());
+        assert_eq!(filtered.entries, 0);
+        assert_eq!(filtered.excluded.get(&Filter::MaxSize), Some(&20));
+        assert_eq!(
+            reports[1].failed_entries,
+            vec![FailedEntry {
+                entry_name: "nodes/5/geometries/0.bin".to_string(),
+                problem: "the data doesn't match its CRC".to_string(),
+            }]
+        );
+        assert!(!passed);
+        assert_eq!(reports[2].entries, 2);
+        assert_eq!(reports[2].failed_entries.len(), 1);
+        assert_eq!(
+            reports[2].failed_entries[0].entry_name,
+            "3dSceneLayer.json.gz"
+        );
+        assert!(reports[2].failed_entries[0]
+            .problem
+            .starts_with("the entry doesn't decompress as gzip"));
+    }
+}
@@ -0,0 +1,219 @@
+// Re-runs an operation whenever a package file is replaced. There is no
+// portable change notification available to us, so the file is polled,
+// and a change only counts once the file has stopped changing for a while:
+// exporters write multi-gigabyte packages slowly, and unpacking a package
+// which is still being written would just fail.
+//
+// Each cycle after the first is usually a new version of a package with
+// most of its entries as they were, so the unpack of each cycle is meant to
+// be incremental, and the summary of a cycle gives how many entries it left
+// unchanged and how many files it deleted.
+
+use crate::unpack::UnpackSummary;
+use failure::Error;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    // A second Ctrl-C kills the process, in case the current cycle hangs.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    let handler: extern "C" fn(libc::c_int) = on_interrupt;
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() {
+    // Without a handler Ctrl-C ends the process immediately, which is
+    // still an acceptable way out of the watch loop.
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+fn file_state(path: &Path) -> Option<FileState> {
+    std::fs::metadata(path).ok().map(|metadata| FileState {
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+    })
+}
+
+/// How often a package is checked for changes, and how long it must be
+/// left before it is unpacked.
+#[derive(Clone, Copy, Debug)]
+pub struct WatchTiming {
+    /// How often the package file is checked for changes.
+    pub poll_interval: Duration,
+    /// How long the file must stay unchanged before it is processed.
+    pub quiet_period: Duration,
+}
+
+impl Default for WatchTiming {
+    fn default() -> WatchTiming {
+        WatchTiming {
+            poll_interval: Duration::from_millis(500),
+            quiet_period: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Sleeps in short steps so that an interrupt is noticed promptly.
+/// Returns false if the watch was interrupted.
+fn sleep_unless_interrupted(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep(std::cmp::min(deadline - now, Duration::from_millis(100)));
+    }
+    false
+}
+
+/// Blocks until the file differs from `last_state` and has then been stable
+/// for the quiet period. Returns the new state, or `None` if interrupted.
+fn wait_for_change(
+    path: &Path,
+    last_state: &Option<FileState>,
+    timing: WatchTiming,
+) -> Option<Option<FileState>> {
+    loop {
+        if !sleep_unless_interrupted(timing.poll_interval) {
+            return None;
+        }
+        let state = file_state(path);
+        if &state == last_state {
+            continue;
+        }
+
+        // Wait for writes to quiesce. Any change restarts the quiet period.
+        let mut settled_state = state;
+        let mut stable_since = Instant::now();
+        while stable_since.elapsed() < timing.quiet_period {
+            if !sleep_unless_interrupted(timing.poll_interval) {
+                return None;
+            }
+            let state = file_state(path);
+            if state != settled_state {
+                settled_state = state;
+                stable_since = Instant::now();
+            }
+        }
+
+        // A package which was deleted rather than replaced isn't processed.
+        if settled_state.is_some() {
+            return Some(settled_state);
+        }
+    }
+}
+
+/// What a watch has come to, for its caller to report.
+#[derive(Debug)]
+pub enum WatchEvent<'a> {
+    /// The watch has started, and the first cycle is about to run.
+    Started,
+    /// A cycle has finished, the first being number 1.
+    Cycle {
+        /// The number of the cycle.
+        number: usize,
+        /// What the unpack of the cycle gave.
+        result: &'a Result<UnpackSummary, Error>,
+        /// How long the cycle took.
+        elapsed: Duration,
+    },
+    /// The watch was interrupted, and no more cycles will run.
+    Stopped,
+}
+
+/// Runs `cycle` once, then again each time the package file changes, until
+/// interrupted with Ctrl-C, calling `on_event` as it goes. Failing cycles
+/// are reported but don't end the watch, since the next export will usually
+/// fix them.
+pub fn watch<F, E>(
+    slpk_file_path: &Path,
+    timing: WatchTiming,
+    mut cycle: F,
+    mut on_event: E,
+) -> Result<(), Error>
+where
+    F: FnMut() -> Result<UnpackSummary, Error>,
+    E: FnMut(WatchEvent),
+{
+    install_interrupt_handler();
+    on_event(WatchEvent::Started);
+
+    let mut state = file_state(slpk_file_path);
+    let mut number = 1;
+    loop {
+        let start = Instant::now();
+        let result = cycle();
+        on_event(WatchEvent::Cycle {
+            number,
+            result: &result,
+            elapsed: start.elapsed(),
+        });
+        number += 1;
+
+        match wait_for_change(slpk_file_path, &state, timing) {
+            Some(new_state) => state = new_state,
+            None => break,
+        }
+    }
+
+    on_event(WatchEvent::Stopped);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::TempDir;
+    use std::io::Write;
+
+    #[test]
+    fn change_is_reported_after_writes_settle() {
+        let dir = TempDir::new("watch");
+        let path = dir.join("package.slpk");
+        std::fs::write(&path, b"first").unwrap();
+        let initial_state = file_state(&path);
+
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            for _ in 0..3 {
+                thread::sleep(Duration::from_millis(30));
+                let mut file = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&writer_path)
+                    .unwrap();
+                file.write_all(b"more").unwrap();
+            }
+        });
+
+        let timing = WatchTiming {
+            poll_interval: Duration::from_millis(10),
+            quiet_period: Duration::from_millis(100),
+        };
+        let new_state = wait_for_change(&path, &initial_state, timing).unwrap();
+        writer.join().unwrap();
+
+        // All the appends must have landed before the change was reported.
+        assert_eq!(new_state.unwrap().len, 5 + 3 * 4);
+    }
+}
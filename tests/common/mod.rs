@@ -0,0 +1,34 @@
+// Helpers shared by the integration tests, which build their packages with
+// `slpkg::synthetic` as the unit tests and the self-test command do.
+
+use slpkg::synthetic::TempDir;
+use std::path::{Path, PathBuf};
+
+/// A folder of its own for a test, created empty below the temporary folder
+/// and removed when the test is done with it.
+pub fn test_dir(name: &str) -> TempDir {
+    TempDir::new(&format!("it-{}", name))
+}
+
+/// Every file below `folder` other than those unpacking writes beside the
+/// entries, with its path relative to `folder` and its contents, in order of
+/// path.
+pub fn unpacked_files(folder: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(current) = folders.pop() {
+        for entry in std::fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                folders.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(folder).unwrap().to_path_buf();
+            if !slpkg::unpack::is_bookkeeping_file(&relative.to_string_lossy()) {
+                files.push((relative, std::fs::read(&path).unwrap()));
+            }
+        }
+    }
+    files.sort();
+    files
+}
@@ -0,0 +1,534 @@
+
+using namespace System.Management.Automation
+using namespace System.Management.Automation.Language
+
+Register-ArgumentCompleter -Native -CommandName 'slpkg' -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $commandElements = $commandAst.CommandElements
+    $command = @(
+        'slpkg'
+        for ($i = 1; $i -lt $commandElements.Count; $i++) {
+            $element = $commandElements[$i]
+            if ($element -isnot [StringConstantExpressionAst] -or
+                $element.StringConstantType -ne [StringConstantType]::BareWord -or
+                $element.Value.StartsWith('-')) {
+                break
+        }
+        $element.Value
+    }) -join ';'
+
+    $completions = @(switch ($command) {
+        'slpkg' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('pack', 'pack', [CompletionResultType]::ParameterValue, 'Packs an unpacked folder into a .slpk file')
+            [CompletionResult]::new('unpack', 'unpack', [CompletionResultType]::ParameterValue, 'Unpacks a .slpk file into a directory')
+            [CompletionResult]::new('footprints', 'footprints', [CompletionResultType]::ParameterValue, 'Writes a GeoJSON footprint of the top-level nodes of a .slpk file')
+            [CompletionResult]::new('bounds', 'bounds', [CompletionResultType]::ParameterValue, 'Exports node bounding volumes of a .slpk file as GeoJSON or KML')
+            [CompletionResult]::new('index', 'index', [CompletionResultType]::ParameterValue, 'Checks the hash table index of a .slpk file against its contents')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Checks that a package can be read, without unpacking it')
+            [CompletionResult]::new('verify', 'verify', [CompletionResultType]::ParameterValue, 'Reads every entry of a package to check its CRC, and decompresses every gzipped one, without unpacking it')
+            [CompletionResult]::new('check-manifest', 'check-manifest', [CompletionResultType]::ParameterValue, 'Checks the files of an unpacked folder against the manifest.sha256 written by unpack --manifest')
+            [CompletionResult]::new('diff', 'diff', [CompletionResultType]::ParameterValue, 'Lists the entries added, removed and modified from one .slpk file to another, without unpacking either')
+            [CompletionResult]::new('validate', 'validate', [CompletionResultType]::ParameterValue, 'Checks that every resource the node pages refer to is in a package, and that nothing below nodes/ is left unreferenced, without unpacking it')
+            [CompletionResult]::new('extract-node', 'extract-node', [CompletionResultType]::ParameterValue, 'Extracts the entries of one node, and the resources it refers to')
+            [CompletionResult]::new('tree', 'tree', [CompletionResultType]::ParameterValue, 'Prints the node hierarchy of a package, each node indented below its parent')
+            [CompletionResult]::new('export', 'export', [CompletionResultType]::ParameterValue, 'Writes the mesh of a node as a Wavefront OBJ or glTF 2.0 file, with its texture beside it')
+            [CompletionResult]::new('export-attributes', 'export-attributes', [CompletionResultType]::ParameterValue, 'Writes the attributes of every node as CSV, a row per feature')
+            [CompletionResult]::new('textures', 'textures', [CompletionResultType]::ParameterValue, 'Lists the textures of a package with their format and size, and converts them to PNG')
+            [CompletionResult]::new('cat', 'cat', [CompletionResultType]::ParameterValue, 'Writes a single entry to the standard output, decompressed')
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'Lists the entries of a package, without unpacking them')
+            [CompletionResult]::new('stats', 'stats', [CompletionResultType]::ParameterValue, 'Shows where the bytes of a package go, by kind of resource, and its largest entries')
+            [CompletionResult]::new('info', 'info', [CompletionResultType]::ParameterValue, 'Describes each layer of a package from its layer document')
+            [CompletionResult]::new('repair', 'repair', [CompletionResultType]::ParameterValue, 'Fixes entries whose .gz suffix doesn''t match whether they are gzipped')
+            [CompletionResult]::new('recompress', 'recompress', [CompletionResultType]::ParameterValue, 'Writes a smaller copy of a package, gzipping its .gz entries again')
+            [CompletionResult]::new('lint', 'lint', [CompletionResultType]::ParameterValue, 'Reports tiny textures, near-empty meshes and inverted LOD thresholds')
+            [CompletionResult]::new('patch', 'patch', [CompletionResultType]::ParameterValue, 'Makes and applies patches which turn one version of a package into the next')
+            [CompletionResult]::new('batch', 'batch', [CompletionResultType]::ParameterValue, 'Runs a command over many packages, sharing the machine between them')
+            [CompletionResult]::new('mirror', 'mirror', [CompletionResultType]::ParameterValue, 'Mirrors a layer of a scene service into a .slpk file')
+            [CompletionResult]::new('serve', 'serve', [CompletionResultType]::ParameterValue, 'Serves a package over the I3S REST API of a scene service, for previewing it in a viewer')
+            [CompletionResult]::new('self-test', 'self-test', [CompletionResultType]::ParameterValue, 'Checks that packages can be unpacked on this machine')
+            [CompletionResult]::new('config', 'config', [CompletionResultType]::ParameterValue, 'Prints the defaults the slpkg.toml files set for unpack')
+            [CompletionResult]::new('features', 'features', [CompletionResultType]::ParameterValue, 'Prints which optional parts of slpkg this build has')
+            [CompletionResult]::new('completions', 'completions', [CompletionResultType]::ParameterValue, 'Prints a shell completion script to stdout')
+            [CompletionResult]::new('cli-spec', 'cli-spec', [CompletionResultType]::ParameterValue, 'Prints a description of every command and argument')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Prints this message or the help of the given subcommand(s)')
+            break
+        }
+        'slpkg;pack' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The package to write, the folder name with .slpk added by default')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The package to write, the folder name with .slpk added by default')
+            [CompletionResult]::new('--timestamp', 'timestamp', [CompletionResultType]::ParameterName, 'The timestamp of every entry, in seconds since 1970-01-01 UTC, or mtime for the modification time of each file. By default it is SOURCE_DATE_EPOCH, or 1980-01-01 when that isn''t set')
+            [CompletionResult]::new('--compression-level', 'compression-level', [CompletionResultType]::ParameterName, 'The gzip level of JSON resources, from 1 for the fastest to 9 for the smallest package, 6 by default')
+            [CompletionResult]::new('--ignore-file', 'ignore-file', [CompletionResultType]::ParameterName, 'An ignore file in .gitignore syntax, read after the .slpkignore of the folder')
+            [CompletionResult]::new('--no-compress', 'no-compress', [CompletionResultType]::ParameterName, 'Store JSON resources as they are, without .gz in their names, as an ESLPK does')
+            [CompletionResult]::new('--no-default-ignores', 'no-default-ignores', [CompletionResultType]::ParameterName, 'Pack the junk files which are ignored by default, such as .DS_Store, Thumbs.db, editor backups and .git/')
+            [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'List every ignored file')
+            [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'List every ignored file')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;unpack' {
+            [CompletionResult]::new('--header', 'header', [CompletionResultType]::ParameterName, 'A header to send with every request when unpacking from a URL, such as "Authorization: Bearer <token>"')
+            [CompletionResult]::new('--threads', 'threads', [CompletionResultType]::ParameterName, 'The number of worker threads, one per core by default. 0 or 1 unpacks one entry at a time without starting any threads')
+            [CompletionResult]::new('--name', 'name', [CompletionResultType]::ParameterName, 'The name of the folder in --output-dir a package read from standard input is unpacked into')
+            [CompletionResult]::new('--output-dir', 'output-dir', [CompletionResultType]::ParameterName, 'Unpack into a folder named after the package in this folder, instead of next to the package')
+            [CompletionResult]::new('--route', 'route', [CompletionResultType]::ParameterName, 'Write one class of resource (metadata, geometry, textures, attributes or other) below another folder, as <class>=<folder>')
+            [CompletionResult]::new('--dedup', 'dedup', [CompletionResultType]::ParameterName, 'Link texture payloads identical to one already unpacked instead of writing them again, or with "copy" only report them')
+            [CompletionResult]::new('--max-level', 'max-level', [CompletionResultType]::ParameterName, 'Only unpack the resources of nodes down to this level below the root, which is at level 0')
+            [CompletionResult]::new('--bbox', 'bbox', [CompletionResultType]::ParameterName, 'Only unpack the resources of nodes meeting this box, given as xmin,ymin,xmax,ymax in the spatial reference of the layer, and of their ancestors')
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Only unpack layer <n> of a package which stores its layers below layers/<n>/')
+            [CompletionResult]::new('--retries', 'retries', [CompletionResultType]::ParameterName, 'Retry an entry which fails up to this many times, then set it aside with a .failed marker file and carry on')
+            [CompletionResult]::new('--retry-backoff-ms', 'retry-backoff-ms', [CompletionResultType]::ParameterName, 'Milliseconds to wait before the first retry, doubling each time')
+            [CompletionResult]::new('--if-exists', 'if-exists', [CompletionResultType]::ParameterName, 'When the output folder already exists, fail, delete it first, or unpack into it, leaving any files the package doesn''t have')
+            [CompletionResult]::new('--on-file-conflict', 'on-file-conflict', [CompletionResultType]::ParameterName, 'Unpack into the existing output folder, and resolve each file which already exists by overwriting it, skipping the entry, overwriting it only when the entry is newer, keeping it when it is complete, or failing')
+            [CompletionResult]::new('--strip-prefix', 'strip-prefix', [CompletionResultType]::ParameterName, 'Strip this folder from the start of every entry, or with auto the one folder every entry is in, leaving out entries which aren''t in it')
+            [CompletionResult]::new('--json-format', 'json-format', [CompletionResultType]::ParameterName, 'Write .json and .geojson files as they are stored (as-is, the default), minified, or pretty-printed with two spaces, <n> spaces or a tab')
+            [CompletionResult]::new('--sort-keys-max-size', 'sort-keys-max-size', [CompletionResultType]::ParameterName, 'Leave the keys of documents larger than this in their order with --sort-keys, with a warning (64MiB by default)')
+            [CompletionResult]::new('--only', 'only', [CompletionResultType]::ParameterName, 'Only unpack resources of these kinds: textures, geometry, attributes or metadata')
+            [CompletionResult]::new('--include', 'include', [CompletionResultType]::ParameterName, 'Only unpack entries whose path matches one of these patterns, such as "*.json.gz" or "nodes/*/features/*"')
+            [CompletionResult]::new('--exclude', 'exclude', [CompletionResultType]::ParameterName, 'Don''t unpack entries whose path matches one of these patterns')
+            [CompletionResult]::new('--min-size', 'min-size', [CompletionResultType]::ParameterName, 'Only unpack entries at least this large, such as 10MB or 1GiB')
+            [CompletionResult]::new('--max-size', 'max-size', [CompletionResultType]::ParameterName, 'Only unpack entries at most this large')
+            [CompletionResult]::new('--newer-than', 'newer-than', [CompletionResultType]::ParameterName, 'Only unpack entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS')
+            [CompletionResult]::new('--max-memory', 'max-memory', [CompletionResultType]::ParameterName, 'Keep the memory held for entry contents below this, such as 512MiB')
+            [CompletionResult]::new('--write-buffer', 'write-buffer', [CompletionResultType]::ParameterName, 'The size of the buffer each file is written through, 128KiB by default')
+            [CompletionResult]::new('--max-expansion-ratio', 'max-expansion-ratio', [CompletionResultType]::ParameterName, 'Fail on entries which expand to more than this many times their compressed size')
+            [CompletionResult]::new('--password', 'password', [CompletionResultType]::ParameterName, 'The password of a package whose entries are encrypted')
+            [CompletionResult]::new('--restore-order-file', 'restore-order-file', [CompletionResultType]::ParameterName, 'Write the names of the entries to this file, one per line, in the order they were written to the package')
+            [CompletionResult]::new('--extract-order', 'extract-order', [CompletionResultType]::ParameterName, 'The order entries are unpacked in: archive, that of the zip directory, or metadata-first, the layer documents and node pages before the resources')
+            [CompletionResult]::new('--timeout', 'timeout', [CompletionResultType]::ParameterName, 'Stop after this long, such as 90s or 20m, leaving a resume file listing the entries which are left')
+            [CompletionResult]::new('--trace-json', 'trace-json', [CompletionResultType]::ParameterName, 'Write a timeline of the entries each worker thread unpacked, in the Chrome trace format')
+            [CompletionResult]::new('--to-tar', 'to-tar', [CompletionResultType]::ParameterName, 'Write the files as a tar stream to this file, or to standard output with -, instead of into the output folder')
+            [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'Log every file unpacked, and with -vv also how long each stage of unpacking took')
+            [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'Log every file unpacked, and with -vv also how long each stage of unpacking took')
+            [CompletionResult]::new('-q', 'q', [CompletionResultType]::ParameterName, 'Only print warnings and errors')
+            [CompletionResult]::new('--quiet', 'quiet', [CompletionResultType]::ParameterName, 'Only print warnings and errors')
+            [CompletionResult]::new('--progress', 'progress', [CompletionResultType]::ParameterName, 'Draw a progress bar of the entries unpacked and bytes written')
+            [CompletionResult]::new('--watch', 'watch', [CompletionResultType]::ParameterName, 'Unpack again each time the package file is replaced, until Ctrl-C')
+            [CompletionResult]::new('--split-sublayers', 'split-sublayers', [CompletionResultType]::ParameterName, 'Unpack each sublayer of a Building Scene Layer into its own folder')
+            [CompletionResult]::new('--dedup-geometry', 'dedup-geometry', [CompletionResultType]::ParameterName, 'Deduplicate geometry payloads as well as textures')
+            [CompletionResult]::new('--exclude-empty-nodes', 'exclude-empty-nodes', [CompletionResultType]::ParameterName, 'Skip the resources of nodes with no vertices, no features and only placeholder textures')
+            [CompletionResult]::new('--bbox-wgs84', 'bbox-wgs84', [CompletionResultType]::ParameterName, 'Give the --bbox in WGS84 longitude and latitude')
+            [CompletionResult]::new('--keep-going', 'keep-going', [CompletionResultType]::ParameterName, 'Carry on past entries which fail, listing them at the end, rather than stopping at the first')
+            [CompletionResult]::new('--retry-failed', 'retry-failed', [CompletionResultType]::ParameterName, 'Only unpack the entries set aside by an earlier run, into its output folder')
+            [CompletionResult]::new('--rename-collisions', 'rename-collisions', [CompletionResultType]::ParameterName, 'Unpack entries whose files would collide with others where case is ignored under names suffixed ~1, ~2 and so on, rather than failing')
+            [CompletionResult]::new('--portable-names', 'portable-names', [CompletionResultType]::ParameterName, 'Escape the characters of file names which Windows doesn''t allow, as is always done on Windows')
+            [CompletionResult]::new('--keep-unprefixed', 'keep-unprefixed', [CompletionResultType]::ParameterName, 'Unpack entries which aren''t in the folder --strip-prefix strips as they are, rather than leaving them out')
+            [CompletionResult]::new('--allow-symlinks', 'allow-symlinks', [CompletionResultType]::ParameterName, 'Recreate symbolic links whose target is inside the output folder, on Unix, rather than leaving them out')
+            [CompletionResult]::new('--sniff-compression', 'sniff-compression', [CompletionResultType]::ParameterName, 'Entries are decompressed when they start like a gzip stream whatever their names say, which this used to turn on')
+            [CompletionResult]::new('--trust-extensions', 'trust-extensions', [CompletionResultType]::ParameterName, 'Decompress entries named .gz and copy the rest, without looking at what they hold')
+            [CompletionResult]::new('--keep-gzip', 'keep-gzip', [CompletionResultType]::ParameterName, 'Write gzipped entries exactly as they are stored, keeping their .gz names, rather than decompressing them')
+            [CompletionResult]::new('--sort-keys', 'sort-keys', [CompletionResultType]::ParameterName, 'Sort the keys of every object in the documents --json-format reformats, so that unpacked packages diff cleanly')
+            [CompletionResult]::new('--semantic-manifest', 'semantic-manifest', [CompletionResultType]::ParameterName, 'Write manifest.json, giving the node, level and role of every unpacked file')
+            [CompletionResult]::new('--manifest', 'manifest', [CompletionResultType]::ParameterName, 'Write manifest.sha256, giving the SHA-256 of every unpacked file in the format of sha256sum')
+            [CompletionResult]::new('--emit-index', 'emit-index', [CompletionResultType]::ParameterName, 'Write index.html, a page summarizing the package with its node tree and links to its documents, and its figures in index.json')
+            [CompletionResult]::new('--restore-gzip-mtime', 'restore-gzip-mtime', [CompletionResultType]::ParameterName, 'Give files from gzipped entries the modification time recorded in their gzip header')
+            [CompletionResult]::new('--verify-after', 'verify-after', [CompletionResultType]::ParameterName, 'Check every unpacked file against the package once unpacking is done, failing if any differ')
+            [CompletionResult]::new('--verify-output', 'verify-output', [CompletionResultType]::ParameterName, 'Read every unpacked file back from disk once unpacking is done, failing if any doesn''t hold what was unpacked to it')
+            [CompletionResult]::new('--paranoid', 'paranoid', [CompletionResultType]::ParameterName, 'Turn on every integrity check, and fail on the first violation, for packages which can''t be trusted')
+            [CompletionResult]::new('--validate-json', 'validate-json', [CompletionResultType]::ParameterName, 'Check the layer documents, node pages, node index documents, shared resources and statistics against their schemas, warning of the first violation in each')
+            [CompletionResult]::new('--strict', 'strict', [CompletionResultType]::ParameterName, 'Fail on JSON resources which don''t match their schemas, rather than warning of them')
+            [CompletionResult]::new('--hardened', 'hardened', [CompletionResultType]::ParameterName, 'Refuse packages with more entries, or entries with longer names, than real packages have')
+            [CompletionResult]::new('--stage-files', 'stage-files', [CompletionResultType]::ParameterName, 'Write each file under a temporary name, and rename it into place once it is complete')
+            [CompletionResult]::new('--mmap', 'mmap', [CompletionResultType]::ParameterName, 'Read the package through a memory mapping of it, rather than through a file handle for each thread')
+            [CompletionResult]::new('--ask-password', 'ask-password', [CompletionResultType]::ParameterName, 'Ask for the password of an encrypted package on the terminal, without showing it')
+            [CompletionResult]::new('--dry-run', 'dry-run', [CompletionResultType]::ParameterName, 'Print how many files and bytes unpacking would write, without writing anything')
+            [CompletionResult]::new('--resume', 'resume', [CompletionResultType]::ParameterName, 'Only unpack the entries left by a run which timed out, into its output folder')
+            [CompletionResult]::new('--incremental', 'incremental', [CompletionResultType]::ParameterName, 'Only unpack the entries which changed since the last unpack into the output folder, deleting the files of those which are gone')
+            [CompletionResult]::new('--atomic', 'atomic', [CompletionResultType]::ParameterName, 'Unpack into a temporary folder next to the output folder, which only replaces it once every entry has been unpacked')
+            [CompletionResult]::new('--json', 'json', [CompletionResultType]::ParameterName, 'Print a JSON summary on standard output once the unpack is done, and every other message on standard error')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;footprints' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The GeoJSON file to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The GeoJSON file to write')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;bounds' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The file to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The file to write')
+            [CompletionResult]::new('--level', 'level', [CompletionResultType]::ParameterName, 'Only export nodes at this level, where the root node is level 0')
+            [CompletionResult]::new('--format', 'format', [CompletionResultType]::ParameterName, 'Output format')
+            [CompletionResult]::new('--max-features', 'max-features', [CompletionResultType]::ParameterName, 'Sample at most this many nodes, spread across the layer')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;index' {
+            [CompletionResult]::new('--list', 'list', [CompletionResultType]::ParameterName, 'Print every record of the index')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;check' {
+            [CompletionResult]::new('--min-size', 'min-size', [CompletionResultType]::ParameterName, 'Only take entries at least this large, such as 10MB or 1GiB')
+            [CompletionResult]::new('--max-size', 'max-size', [CompletionResultType]::ParameterName, 'Only take entries at most this large')
+            [CompletionResult]::new('--newer-than', 'newer-than', [CompletionResultType]::ParameterName, 'Only take entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS')
+            [CompletionResult]::new('--quick', 'quick', [CompletionResultType]::ParameterName, 'Only read the zip directory, the layer document and the root node, rather than every entry')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;verify' {
+            [CompletionResult]::new('--threads', 'threads', [CompletionResultType]::ParameterName, 'The number of worker threads, one per core by default')
+            [CompletionResult]::new('--password', 'password', [CompletionResultType]::ParameterName, 'The password of a package whose entries are encrypted')
+            [CompletionResult]::new('--ask-password', 'ask-password', [CompletionResultType]::ParameterName, 'Ask for the password of an encrypted package on the terminal, without showing it')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;check-manifest' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;diff' {
+            [CompletionResult]::new('--detailed', 'detailed', [CompletionResultType]::ParameterName, 'List every entry added, removed or modified')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;validate' {
+            [CompletionResult]::new('--password', 'password', [CompletionResultType]::ParameterName, 'The password of a package whose entries are encrypted')
+            [CompletionResult]::new('--checks', 'checks', [CompletionResultType]::ParameterName, 'Runs only these checks, such as gzip,refs,schema, out of refs, naming, gzip, json, schema and index')
+            [CompletionResult]::new('--fail-on', 'fail-on', [CompletionResultType]::ParameterName, 'Fails on findings of this severity or worse')
+            [CompletionResult]::new('--threads', 'threads', [CompletionResultType]::ParameterName, 'The number of worker threads, one per core by default')
+            [CompletionResult]::new('--validate-json', 'validate-json', [CompletionResultType]::ParameterName, 'Also check the JSON resources of the package against their schemas')
+            [CompletionResult]::new('--ask-password', 'ask-password', [CompletionResultType]::ParameterName, 'Ask for the password of an encrypted package on the terminal, without showing it')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;extract-node' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The folder to extract into, which is created if it doesn''t exist')
+            [CompletionResult]::new('--output-dir', 'output-dir', [CompletionResultType]::ParameterName, 'The folder to extract into, which is created if it doesn''t exist')
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Extract from layer <n> of a package which stores its layers below layers/<n>/, rather than the first')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;tree' {
+            [CompletionResult]::new('--max-depth', 'max-depth', [CompletionResultType]::ParameterName, 'Only print nodes down to this depth, where the root is at depth 0')
+            [CompletionResult]::new('--counts-only', 'counts-only', [CompletionResultType]::ParameterName, 'Only print the number of nodes at each level')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;export' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The file to write, in the format its extension names unless --format is given')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The file to write, in the format its extension names unless --format is given')
+            [CompletionResult]::new('--format', 'format', [CompletionResultType]::ParameterName, 'Write OBJ or glTF whatever the extension of the output file')
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Export from layer <n> of a package which stores its layers below layers/<n>/, rather than the first')
+            [CompletionResult]::new('--target-crs', 'target-crs', [CompletionResultType]::ParameterName, 'Reproject the positions into ecef, wgs84 or epsg:<code>, for Web Mercator or a UTM zone, rather than writing them as they are stored')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;export-attributes' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The folder to write the CSV files to, which is created if it doesn''t exist')
+            [CompletionResult]::new('--output-dir', 'output-dir', [CompletionResultType]::ParameterName, 'The folder to write the CSV files to, which is created if it doesn''t exist')
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Export from layer <n> of a package which stores its layers below layers/<n>/, rather than the first')
+            [CompletionResult]::new('--merge', 'merge', [CompletionResultType]::ParameterName, 'Write a single attributes.csv with a column for the node, rather than a file per node')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;textures' {
+            [CompletionResult]::new('--convert', 'convert', [CompletionResultType]::ParameterName, 'Decode the JPEG and DXT compressed DDS textures into PNGs in the folder the package unpacks into')
+            [CompletionResult]::new('--output-dir', 'output-dir', [CompletionResultType]::ParameterName, 'Convert into a folder named after the package in this folder, instead of next to the package')
+            [CompletionResult]::new('--split-atlas', 'split-atlas', [CompletionResultType]::ParameterName, 'Crop the texture atlas of each node into a PNG for each UV region of its features, in the folder the package unpacks into')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;cat' {
+            [CompletionResult]::new('--json-format', 'json-format', [CompletionResultType]::ParameterName, 'Write a JSON document pretty-printed with two spaces (pretty, the default), with <n> spaces or a tab, minified, or as-is')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;list' {
+            [CompletionResult]::new('--password', 'password', [CompletionResultType]::ParameterName, 'The password of a package whose entries are encrypted, which --deep needs to read them')
+            [CompletionResult]::new('--min-size', 'min-size', [CompletionResultType]::ParameterName, 'Only take entries at least this large, such as 10MB or 1GiB')
+            [CompletionResult]::new('--max-size', 'max-size', [CompletionResultType]::ParameterName, 'Only take entries at most this large')
+            [CompletionResult]::new('--newer-than', 'newer-than', [CompletionResultType]::ParameterName, 'Only take entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS')
+            [CompletionResult]::new('--sort-by-size', 'sort-by-size', [CompletionResultType]::ParameterName, 'List the largest entries first')
+            [CompletionResult]::new('--json', 'json', [CompletionResultType]::ParameterName, 'Print the entries as a JSON array')
+            [CompletionResult]::new('--deep', 'deep', [CompletionResultType]::ParameterName, 'Also read the gzip header of every gzipped entry, and list the original name, time and comment it records')
+            [CompletionResult]::new('--ask-password', 'ask-password', [CompletionResultType]::ParameterName, 'Ask for the password of an encrypted package on the terminal, without showing it')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;stats' {
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Count the coded values of layer <n> of a package which stores its layers below layers/<n>/, rather than of the first')
+            [CompletionResult]::new('--json', 'json', [CompletionResultType]::ParameterName, 'Print the breakdown as a JSON object')
+            [CompletionResult]::new('--coded-values', 'coded-values', [CompletionResultType]::ParameterName, 'Also count the features holding each value of the attributes with a coded-value domain, labelled by the domain')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;info' {
+            [CompletionResult]::new('--attributes', 'attributes', [CompletionResultType]::ParameterName, 'Lists the attributes, joining fields with attributeStorageInfo')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;repair' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The repaired copy of the package to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The repaired copy of the package to write')
+            [CompletionResult]::new('--check', 'check', [CompletionResultType]::ParameterName, 'Only list the entries which need repairing')
+            [CompletionResult]::new('--write-metadata', 'write-metadata', [CompletionResultType]::ParameterName, 'Add the metadata.json inferred from the layer to a copy of a package which has none')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;recompress' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The recompressed copy of the package to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The recompressed copy of the package to write')
+            [CompletionResult]::new('--compression-level', 'compression-level', [CompletionResultType]::ParameterName, 'The gzip level of the .gz entries, from 1 for the fastest to 9 for the smallest package, 9 by default')
+            [CompletionResult]::new('--minify-json', 'minify-json', [CompletionResultType]::ParameterName, 'Minify the JSON inside .json.gz entries before gzipping it again')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;lint' {
+            [CompletionResult]::new('--min-texture-area', 'min-texture-area', [CompletionResultType]::ParameterName, 'Flag textures with fewer pixels than this')
+            [CompletionResult]::new('--min-vertices', 'min-vertices', [CompletionResultType]::ParameterName, 'Flag meshes below the root with fewer vertices than this')
+            [CompletionResult]::new('--severity', 'severity', [CompletionResultType]::ParameterName, 'Sets the severity of a rule, such as small-texture=error or inverted-lod=off')
+            [CompletionResult]::new('--checks', 'checks', [CompletionResultType]::ParameterName, 'Runs only these rules, such as small-texture,inverted-lod')
+            [CompletionResult]::new('--fail-on', 'fail-on', [CompletionResultType]::ParameterName, 'Fails on findings of this severity or worse')
+            [CompletionResult]::new('--threads', 'threads', [CompletionResultType]::ParameterName, 'The number of worker threads, one per core by default')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;patch' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('create', 'create', [CompletionResultType]::ParameterValue, 'Writes a patch which turns one package into another')
+            [CompletionResult]::new('apply', 'apply', [CompletionResultType]::ParameterValue, 'Applies a patch to the package it was made for')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Prints this message or the help of the given subcommand(s)')
+            break
+        }
+        'slpkg;patch;create' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The patch to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The patch to write')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;patch;apply' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The patched package to write')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The patched package to write')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;patch;help' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;batch' {
+            [CompletionResult]::new('--command', 'command', [CompletionResultType]::ParameterName, 'The command to run on each package')
+            [CompletionResult]::new('-j', 'j', [CompletionResultType]::ParameterName, 'How many packages to process at once')
+            [CompletionResult]::new('--jobs', 'jobs', [CompletionResultType]::ParameterName, 'How many packages to process at once')
+            [CompletionResult]::new('--threads', 'threads', [CompletionResultType]::ParameterName, 'The worker threads shared by the packages processed at once, by default the number of cores')
+            [CompletionResult]::new('--report', 'report', [CompletionResultType]::ParameterName, 'The JSON report to write')
+            [CompletionResult]::new('--output-dir', 'output-dir', [CompletionResultType]::ParameterName, 'Unpack into a folder named after the package in this folder, instead of next to the package')
+            [CompletionResult]::new('--route', 'route', [CompletionResultType]::ParameterName, 'Write one class of resource (metadata, geometry, textures, attributes or other) below another folder, as <class>=<folder>')
+            [CompletionResult]::new('--dedup', 'dedup', [CompletionResultType]::ParameterName, 'Link texture payloads identical to one already unpacked instead of writing them again, or with "copy" only report them')
+            [CompletionResult]::new('--max-level', 'max-level', [CompletionResultType]::ParameterName, 'Only unpack the resources of nodes down to this level below the root, which is at level 0')
+            [CompletionResult]::new('--bbox', 'bbox', [CompletionResultType]::ParameterName, 'Only unpack the resources of nodes meeting this box, given as xmin,ymin,xmax,ymax in the spatial reference of the layer, and of their ancestors')
+            [CompletionResult]::new('--layer', 'layer', [CompletionResultType]::ParameterName, 'Only unpack layer <n> of a package which stores its layers below layers/<n>/')
+            [CompletionResult]::new('--retries', 'retries', [CompletionResultType]::ParameterName, 'Retry an entry which fails up to this many times, then set it aside with a .failed marker file and carry on')
+            [CompletionResult]::new('--retry-backoff-ms', 'retry-backoff-ms', [CompletionResultType]::ParameterName, 'Milliseconds to wait before the first retry, doubling each time')
+            [CompletionResult]::new('--if-exists', 'if-exists', [CompletionResultType]::ParameterName, 'When the output folder already exists, fail, delete it first, or unpack into it, leaving any files the package doesn''t have')
+            [CompletionResult]::new('--on-file-conflict', 'on-file-conflict', [CompletionResultType]::ParameterName, 'Unpack into the existing output folder, and resolve each file which already exists by overwriting it, skipping the entry, overwriting it only when the entry is newer, keeping it when it is complete, or failing')
+            [CompletionResult]::new('--strip-prefix', 'strip-prefix', [CompletionResultType]::ParameterName, 'Strip this folder from the start of every entry, or with auto the one folder every entry is in, leaving out entries which aren''t in it')
+            [CompletionResult]::new('--json-format', 'json-format', [CompletionResultType]::ParameterName, 'Write .json and .geojson files as they are stored (as-is, the default), minified, or pretty-printed with two spaces, <n> spaces or a tab')
+            [CompletionResult]::new('--sort-keys-max-size', 'sort-keys-max-size', [CompletionResultType]::ParameterName, 'Leave the keys of documents larger than this in their order with --sort-keys, with a warning (64MiB by default)')
+            [CompletionResult]::new('--only', 'only', [CompletionResultType]::ParameterName, 'Only unpack resources of these kinds: textures, geometry, attributes or metadata')
+            [CompletionResult]::new('--include', 'include', [CompletionResultType]::ParameterName, 'Only unpack entries whose path matches one of these patterns, such as "*.json.gz" or "nodes/*/features/*"')
+            [CompletionResult]::new('--exclude', 'exclude', [CompletionResultType]::ParameterName, 'Don''t unpack entries whose path matches one of these patterns')
+            [CompletionResult]::new('--min-size', 'min-size', [CompletionResultType]::ParameterName, 'Only unpack entries at least this large, such as 10MB or 1GiB')
+            [CompletionResult]::new('--max-size', 'max-size', [CompletionResultType]::ParameterName, 'Only unpack entries at most this large')
+            [CompletionResult]::new('--newer-than', 'newer-than', [CompletionResultType]::ParameterName, 'Only unpack entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS')
+            [CompletionResult]::new('--max-memory', 'max-memory', [CompletionResultType]::ParameterName, 'Keep the memory held for entry contents below this, such as 512MiB')
+            [CompletionResult]::new('--write-buffer', 'write-buffer', [CompletionResultType]::ParameterName, 'The size of the buffer each file is written through, 128KiB by default')
+            [CompletionResult]::new('--max-expansion-ratio', 'max-expansion-ratio', [CompletionResultType]::ParameterName, 'Fail on entries which expand to more than this many times their compressed size')
+            [CompletionResult]::new('--password', 'password', [CompletionResultType]::ParameterName, 'The password of a package whose entries are encrypted')
+            [CompletionResult]::new('--restore-order-file', 'restore-order-file', [CompletionResultType]::ParameterName, 'Write the names of the entries to this file, one per line, in the order they were written to the package')
+            [CompletionResult]::new('--extract-order', 'extract-order', [CompletionResultType]::ParameterName, 'The order entries are unpacked in: archive, that of the zip directory, or metadata-first, the layer documents and node pages before the resources')
+            [CompletionResult]::new('--timeout', 'timeout', [CompletionResultType]::ParameterName, 'Stop after this long, such as 90s or 20m, leaving a resume file listing the entries which are left')
+            [CompletionResult]::new('--fail-fast', 'fail-fast', [CompletionResultType]::ParameterName, 'Don''t start any more packages once one has failed')
+            [CompletionResult]::new('--split-sublayers', 'split-sublayers', [CompletionResultType]::ParameterName, 'Unpack each sublayer of a Building Scene Layer into its own folder')
+            [CompletionResult]::new('--dedup-geometry', 'dedup-geometry', [CompletionResultType]::ParameterName, 'Deduplicate geometry payloads as well as textures')
+            [CompletionResult]::new('--exclude-empty-nodes', 'exclude-empty-nodes', [CompletionResultType]::ParameterName, 'Skip the resources of nodes with no vertices, no features and only placeholder textures')
+            [CompletionResult]::new('--bbox-wgs84', 'bbox-wgs84', [CompletionResultType]::ParameterName, 'Give the --bbox in WGS84 longitude and latitude')
+            [CompletionResult]::new('--keep-going', 'keep-going', [CompletionResultType]::ParameterName, 'Carry on past entries which fail, listing them at the end, rather than stopping at the first')
+            [CompletionResult]::new('--retry-failed', 'retry-failed', [CompletionResultType]::ParameterName, 'Only unpack the entries set aside by an earlier run, into its output folder')
+            [CompletionResult]::new('--rename-collisions', 'rename-collisions', [CompletionResultType]::ParameterName, 'Unpack entries whose files would collide with others where case is ignored under names suffixed ~1, ~2 and so on, rather than failing')
+            [CompletionResult]::new('--portable-names', 'portable-names', [CompletionResultType]::ParameterName, 'Escape the characters of file names which Windows doesn''t allow, as is always done on Windows')
+            [CompletionResult]::new('--keep-unprefixed', 'keep-unprefixed', [CompletionResultType]::ParameterName, 'Unpack entries which aren''t in the folder --strip-prefix strips as they are, rather than leaving them out')
+            [CompletionResult]::new('--allow-symlinks', 'allow-symlinks', [CompletionResultType]::ParameterName, 'Recreate symbolic links whose target is inside the output folder, on Unix, rather than leaving them out')
+            [CompletionResult]::new('--sniff-compression', 'sniff-compression', [CompletionResultType]::ParameterName, 'Entries are decompressed when they start like a gzip stream whatever their names say, which this used to turn on')
+            [CompletionResult]::new('--trust-extensions', 'trust-extensions', [CompletionResultType]::ParameterName, 'Decompress entries named .gz and copy the rest, without looking at what they hold')
+            [CompletionResult]::new('--keep-gzip', 'keep-gzip', [CompletionResultType]::ParameterName, 'Write gzipped entries exactly as they are stored, keeping their .gz names, rather than decompressing them')
+            [CompletionResult]::new('--sort-keys', 'sort-keys', [CompletionResultType]::ParameterName, 'Sort the keys of every object in the documents --json-format reformats, so that unpacked packages diff cleanly')
+            [CompletionResult]::new('--semantic-manifest', 'semantic-manifest', [CompletionResultType]::ParameterName, 'Write manifest.json, giving the node, level and role of every unpacked file')
+            [CompletionResult]::new('--manifest', 'manifest', [CompletionResultType]::ParameterName, 'Write manifest.sha256, giving the SHA-256 of every unpacked file in the format of sha256sum')
+            [CompletionResult]::new('--emit-index', 'emit-index', [CompletionResultType]::ParameterName, 'Write index.html, a page summarizing the package with its node tree and links to its documents, and its figures in index.json')
+            [CompletionResult]::new('--restore-gzip-mtime', 'restore-gzip-mtime', [CompletionResultType]::ParameterName, 'Give files from gzipped entries the modification time recorded in their gzip header')
+            [CompletionResult]::new('--verify-after', 'verify-after', [CompletionResultType]::ParameterName, 'Check every unpacked file against the package once unpacking is done, failing if any differ')
+            [CompletionResult]::new('--verify-output', 'verify-output', [CompletionResultType]::ParameterName, 'Read every unpacked file back from disk once unpacking is done, failing if any doesn''t hold what was unpacked to it')
+            [CompletionResult]::new('--paranoid', 'paranoid', [CompletionResultType]::ParameterName, 'Turn on every integrity check, and fail on the first violation, for packages which can''t be trusted')
+            [CompletionResult]::new('--validate-json', 'validate-json', [CompletionResultType]::ParameterName, 'Check the layer documents, node pages, node index documents, shared resources and statistics against their schemas, warning of the first violation in each')
+            [CompletionResult]::new('--strict', 'strict', [CompletionResultType]::ParameterName, 'Fail on JSON resources which don''t match their schemas, rather than warning of them')
+            [CompletionResult]::new('--hardened', 'hardened', [CompletionResultType]::ParameterName, 'Refuse packages with more entries, or entries with longer names, than real packages have')
+            [CompletionResult]::new('--stage-files', 'stage-files', [CompletionResultType]::ParameterName, 'Write each file under a temporary name, and rename it into place once it is complete')
+            [CompletionResult]::new('--mmap', 'mmap', [CompletionResultType]::ParameterName, 'Read the package through a memory mapping of it, rather than through a file handle for each thread')
+            [CompletionResult]::new('--ask-password', 'ask-password', [CompletionResultType]::ParameterName, 'Ask for the password of an encrypted package on the terminal, without showing it')
+            [CompletionResult]::new('--dry-run', 'dry-run', [CompletionResultType]::ParameterName, 'Print how many files and bytes unpacking would write, without writing anything')
+            [CompletionResult]::new('--resume', 'resume', [CompletionResultType]::ParameterName, 'Only unpack the entries left by a run which timed out, into its output folder')
+            [CompletionResult]::new('--incremental', 'incremental', [CompletionResultType]::ParameterName, 'Only unpack the entries which changed since the last unpack into the output folder, deleting the files of those which are gone')
+            [CompletionResult]::new('--atomic', 'atomic', [CompletionResultType]::ParameterName, 'Unpack into a temporary folder next to the output folder, which only replaces it once every entry has been unpacked')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;mirror' {
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'The package to write, named after the service by default')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'The package to write, named after the service by default')
+            [CompletionResult]::new('--max-level', 'max-level', [CompletionResultType]::ParameterName, 'The deepest level of the node tree to mirror, where the root is level 0, for a quick preview of a large layer')
+            [CompletionResult]::new('--concurrency', 'concurrency', [CompletionResultType]::ParameterName, 'How many resources to fetch at once')
+            [CompletionResult]::new('--retries', 'retries', [CompletionResultType]::ParameterName, 'How many times to send a request again when it fails on the way or the server is busy')
+            [CompletionResult]::new('--header', 'header', [CompletionResultType]::ParameterName, 'A header to send with every request, such as "Authorization: Bearer <token>"')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;serve' {
+            [CompletionResult]::new('--port', 'port', [CompletionResultType]::ParameterName, 'The port to listen on')
+            [CompletionResult]::new('--bind', 'bind', [CompletionResultType]::ParameterName, 'The address to listen on, by default one only this machine can connect to')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;self-test' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;config' {
+            [CompletionResult]::new('--show', 'show', [CompletionResultType]::ParameterName, 'Print every value the files set, with the file it comes from, which is needed for now')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;features' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;completions' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;cli-spec' {
+            [CompletionResult]::new('--json', 'json', [CompletionResultType]::ParameterName, 'Print the description as JSON instead of an outline')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+        'slpkg;help' {
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Prints help information')
+            [CompletionResult]::new('-V', 'V', [CompletionResultType]::ParameterName, 'Prints version information')
+            [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Prints version information')
+            break
+        }
+    })
+
+    $completions.Where{ $_.CompletionText -like "$wordToComplete*" } |
+        Sort-Object -Property ListItemText
+}
@@ -0,0 +1,1210 @@
+_slpkg() {
+    local i cur prev opts cmds
+    COMPREPLY=()
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    cmd=""
+    opts=""
+
+    for i in ${COMP_WORDS[@]}
+    do
+        case "${i}" in
+            slpkg)
+                cmd="slpkg"
+                ;;
+            
+            apply)
+                cmd+="__apply"
+                ;;
+            batch)
+                cmd+="__batch"
+                ;;
+            bounds)
+                cmd+="__bounds"
+                ;;
+            cat)
+                cmd+="__cat"
+                ;;
+            check)
+                cmd+="__check"
+                ;;
+            check-manifest)
+                cmd+="__check__manifest"
+                ;;
+            cli-spec)
+                cmd+="__cli__spec"
+                ;;
+            completions)
+                cmd+="__completions"
+                ;;
+            config)
+                cmd+="__config"
+                ;;
+            create)
+                cmd+="__create"
+                ;;
+            diff)
+                cmd+="__diff"
+                ;;
+            export)
+                cmd+="__export"
+                ;;
+            export-attributes)
+                cmd+="__export__attributes"
+                ;;
+            export-bounds)
+                cmd+="__export__bounds"
+                ;;
+            extract-node)
+                cmd+="__extract__node"
+                ;;
+            features)
+                cmd+="__features"
+                ;;
+            footprints)
+                cmd+="__footprints"
+                ;;
+            help)
+                cmd+="__help"
+                ;;
+            index)
+                cmd+="__index"
+                ;;
+            info)
+                cmd+="__info"
+                ;;
+            lint)
+                cmd+="__lint"
+                ;;
+            list)
+                cmd+="__list"
+                ;;
+            mirror)
+                cmd+="__mirror"
+                ;;
+            pack)
+                cmd+="__pack"
+                ;;
+            patch)
+                cmd+="__patch"
+                ;;
+            recompress)
+                cmd+="__recompress"
+                ;;
+            repair)
+                cmd+="__repair"
+                ;;
+            self-test)
+                cmd+="__self__test"
+                ;;
+            serve)
+                cmd+="__serve"
+                ;;
+            stats)
+                cmd+="__stats"
+                ;;
+            textures)
+                cmd+="__textures"
+                ;;
+            tree)
+                cmd+="__tree"
+                ;;
+            unpack)
+                cmd+="__unpack"
+                ;;
+            validate)
+                cmd+="__validate"
+                ;;
+            verify)
+                cmd+="__verify"
+                ;;
+            *)
+                ;;
+        esac
+    done
+
+    case "${cmd}" in
+        slpkg)
+            opts=" -h -V  --help --version   pack unpack footprints bounds index check verify check-manifest diff validate extract-node tree export export-attributes textures cat list stats info repair recompress lint patch batch mirror serve self-test config features completions cli-spec help  export-bounds"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 1 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        
+        slpkg__batch)
+            opts=" -h -V -j  --fail-fast --split-sublayers --dedup-geometry --exclude-empty-nodes --bbox-wgs84 --keep-going --retry-failed --rename-collisions --portable-names --keep-unprefixed --allow-symlinks --sniff-compression --trust-extensions --keep-gzip --sort-keys --semantic-manifest --manifest --emit-index --restore-gzip-mtime --verify-after --verify-output --paranoid --validate-json --strict --hardened --stage-files --mmap --ask-password --dry-run --resume --incremental --atomic --help --version --command --jobs --threads --report --output-dir --route --dedup --max-level --bbox --layer --retries --retry-backoff-ms --if-exists --on-file-conflict --strip-prefix --json-format --sort-keys-max-size --only --include --exclude --min-size --max-size --newer-than --max-memory --write-buffer --max-expansion-ratio --password --restore-order-file --extract-order --timeout  <packages>... "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --command)
+                    COMPREPLY=($(compgen -W "unpack check verify validate" -- ${cur}))
+                    return 0
+                    ;;
+                --jobs)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -j)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --threads)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --report)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --output-dir)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --route)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --dedup)
+                    COMPREPLY=($(compgen -W "hardlink symlink copy" -- ${cur}))
+                    return 0
+                    ;;
+                --max-level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --bbox)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --retries)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --retry-backoff-ms)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --if-exists)
+                    COMPREPLY=($(compgen -W "error overwrite merge" -- ${cur}))
+                    return 0
+                    ;;
+                --on-file-conflict)
+                    COMPREPLY=($(compgen -W "overwrite skip newer complete error" -- ${cur}))
+                    return 0
+                    ;;
+                --strip-prefix)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --json-format)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --sort-keys-max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --only)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --include)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --exclude)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --min-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --newer-than)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-memory)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --write-buffer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-expansion-ratio)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --password)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --restore-order-file)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --extract-order)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --timeout)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__bounds)
+            opts=" -h -V -o  --help --version --output --level --format --max-features  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --format)
+                    COMPREPLY=($(compgen -W "geojson kml" -- ${cur}))
+                    return 0
+                    ;;
+                --max-features)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__cat)
+            opts=" -h -V  --help --version --json-format  <src_file> <entry> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --json-format)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__check)
+            opts=" -h -V  --quick --help --version --min-size --max-size --newer-than  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --min-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --newer-than)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__check__manifest)
+            opts=" -h -V  --help --version  <folder> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__cli__spec)
+            opts=" -h -V  --json --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__completions)
+            opts=" -h -V  --help --version  <shell> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__config)
+            opts=" -h -V  --show --help --version  <package> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__diff)
+            opts=" -h -V  --detailed --help --version  <left> <right> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__export)
+            opts=" -h -V -o  --help --version --output --format --layer --target-crs  <src_file> <node_id> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --format)
+                    COMPREPLY=($(compgen -W "obj gltf" -- ${cur}))
+                    return 0
+                    ;;
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --target-crs)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__export__attributes)
+            opts=" -h -V -o  --merge --help --version --output-dir --layer  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output-dir)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__export__bounds)
+            opts=" -h -V -o  --help --version --output --level --format --max-features  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --format)
+                    COMPREPLY=($(compgen -W "geojson kml" -- ${cur}))
+                    return 0
+                    ;;
+                --max-features)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__extract__node)
+            opts=" -h -V -o  --help --version --output-dir --layer  <src_file> <node_id> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output-dir)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__features)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__footprints)
+            opts=" -h -V -o  --help --version --output  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__help)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__index)
+            opts=" -h -V  --list --help --version  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__info)
+            opts=" -h -V  --attributes --help --version  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__lint)
+            opts=" -h -V  --help --version --min-texture-area --min-vertices --severity --checks --fail-on --threads  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --min-texture-area)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --min-vertices)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --severity)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --checks)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --fail-on)
+                    COMPREPLY=($(compgen -W "error warning never" -- ${cur}))
+                    return 0
+                    ;;
+                --threads)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__list)
+            opts=" -h -V  --sort-by-size --json --deep --ask-password --help --version --password --min-size --max-size --newer-than  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --password)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --min-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --newer-than)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__mirror)
+            opts=" -h -V -o  --help --version --output --max-level --concurrency --retries --header  <url> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --concurrency)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --retries)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --header)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__pack)
+            opts=" -v -h -V -o  --no-compress --no-default-ignores --verbose --help --version --output --timestamp --compression-level --ignore-file  <src_dir> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --timestamp)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --compression-level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --ignore-file)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__patch)
+            opts=" -h -V  --help --version   create apply help"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__patch__apply)
+            opts=" -h -V -o  --help --version --output  <old> <patch> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__patch__create)
+            opts=" -h -V -o  --help --version --output  <old> <new> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__patch__help)
+            opts=" -h -V  --help --version  "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__recompress)
+            opts=" -h -V -o  --minify-json --help --version --output --compression-level  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --compression-level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__repair)
+            opts=" -h -V -o  --check --write-metadata --help --version --output  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --output)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                    -o)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__self__test)
+            opts=" -h -V  --help --version  <dir> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__serve)
+            opts=" -h -V  --help --version --port --bind  <src> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --port)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --bind)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__stats)
+            opts=" -h -V  --json --coded-values --help --version --layer  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__textures)
+            opts=" -h -V  --split-atlas --help --version --convert --output-dir  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --convert)
+                    COMPREPLY=($(compgen -W "png" -- ${cur}))
+                    return 0
+                    ;;
+                --output-dir)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__tree)
+            opts=" -h -V  --counts-only --help --version --max-depth  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --max-depth)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__unpack)
+            opts=" -v -q -h -V  --verbose --quiet --progress --watch --split-sublayers --dedup-geometry --exclude-empty-nodes --bbox-wgs84 --keep-going --retry-failed --rename-collisions --portable-names --keep-unprefixed --allow-symlinks --sniff-compression --trust-extensions --keep-gzip --sort-keys --semantic-manifest --manifest --emit-index --restore-gzip-mtime --verify-after --verify-output --paranoid --validate-json --strict --hardened --stage-files --mmap --ask-password --dry-run --resume --incremental --atomic --json --help --version --header --threads --name --output-dir --route --dedup --max-level --bbox --layer --retries --retry-backoff-ms --if-exists --on-file-conflict --strip-prefix --json-format --sort-keys-max-size --only --include --exclude --min-size --max-size --newer-than --max-memory --write-buffer --max-expansion-ratio --password --restore-order-file --extract-order --timeout --trace-json --to-tar  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --header)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --threads)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --name)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --output-dir)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --route)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --dedup)
+                    COMPREPLY=($(compgen -W "hardlink symlink copy" -- ${cur}))
+                    return 0
+                    ;;
+                --max-level)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --bbox)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --layer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --retries)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --retry-backoff-ms)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --if-exists)
+                    COMPREPLY=($(compgen -W "error overwrite merge" -- ${cur}))
+                    return 0
+                    ;;
+                --on-file-conflict)
+                    COMPREPLY=($(compgen -W "overwrite skip newer complete error" -- ${cur}))
+                    return 0
+                    ;;
+                --strip-prefix)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --json-format)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --sort-keys-max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --only)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --include)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --exclude)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --min-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-size)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --newer-than)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-memory)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --write-buffer)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --max-expansion-ratio)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --password)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --restore-order-file)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --extract-order)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --timeout)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --trace-json)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --to-tar)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__validate)
+            opts=" -h -V  --validate-json --ask-password --help --version --password --checks --fail-on --threads  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --password)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --checks)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --fail-on)
+                    COMPREPLY=($(compgen -W "error warning never" -- ${cur}))
+                    return 0
+                    ;;
+                --threads)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+        slpkg__verify)
+            opts=" -h -V  --ask-password --help --version --threads --password  <src_file> "
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+                return 0
+            fi
+            case "${prev}" in
+                
+                --threads)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                --password)
+                    COMPREPLY=($(compgen -f ${cur}))
+                    return 0
+                    ;;
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
+            return 0
+            ;;
+    esac
+}
+
+complete -F _slpkg -o bashdefault -o default slpkg
@@ -0,0 +1,313 @@
+complete -c slpkg -n "__fish_use_subcommand" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_use_subcommand" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "pack" -d 'Packs an unpacked folder into a .slpk file'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "unpack" -d 'Unpacks a .slpk file into a directory'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "footprints" -d 'Writes a GeoJSON footprint of the top-level nodes of a .slpk file'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "bounds" -d 'Exports node bounding volumes of a .slpk file as GeoJSON or KML'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "index" -d 'Checks the hash table index of a .slpk file against its contents'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "check" -d 'Checks that a package can be read, without unpacking it'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "verify" -d 'Reads every entry of a package to check its CRC, and decompresses every gzipped one, without unpacking it'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "check-manifest" -d 'Checks the files of an unpacked folder against the manifest.sha256 written by unpack --manifest'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "diff" -d 'Lists the entries added, removed and modified from one .slpk file to another, without unpacking either'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "validate" -d 'Checks that every resource the node pages refer to is in a package, and that nothing below nodes/ is left unreferenced, without unpacking it'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "extract-node" -d 'Extracts the entries of one node, and the resources it refers to'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "tree" -d 'Prints the node hierarchy of a package, each node indented below its parent'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "export" -d 'Writes the mesh of a node as a Wavefront OBJ or glTF 2.0 file, with its texture beside it'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "export-attributes" -d 'Writes the attributes of every node as CSV, a row per feature'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "textures" -d 'Lists the textures of a package with their format and size, and converts them to PNG'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "cat" -d 'Writes a single entry to the standard output, decompressed'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "list" -d 'Lists the entries of a package, without unpacking them'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "stats" -d 'Shows where the bytes of a package go, by kind of resource, and its largest entries'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "info" -d 'Describes each layer of a package from its layer document'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "repair" -d 'Fixes entries whose .gz suffix doesn\'t match whether they are gzipped'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "recompress" -d 'Writes a smaller copy of a package, gzipping its .gz entries again'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "lint" -d 'Reports tiny textures, near-empty meshes and inverted LOD thresholds'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "patch" -d 'Makes and applies patches which turn one version of a package into the next'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "batch" -d 'Runs a command over many packages, sharing the machine between them'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "mirror" -d 'Mirrors a layer of a scene service into a .slpk file'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "serve" -d 'Serves a package over the I3S REST API of a scene service, for previewing it in a viewer'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "self-test" -d 'Checks that packages can be unpacked on this machine'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "config" -d 'Prints the defaults the slpkg.toml files set for unpack'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "features" -d 'Prints which optional parts of slpkg this build has'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "completions" -d 'Prints a shell completion script to stdout'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "cli-spec" -d 'Prints a description of every command and argument'
+complete -c slpkg -n "__fish_use_subcommand" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -s o -l output -d 'The package to write, the folder name with .slpk added by default'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -l timestamp -d 'The timestamp of every entry, in seconds since 1970-01-01 UTC, or mtime for the modification time of each file. By default it is SOURCE_DATE_EPOCH, or 1980-01-01 when that isn\'t set'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -l compression-level -d 'The gzip level of JSON resources, from 1 for the fastest to 9 for the smallest package, 6 by default'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -l ignore-file -d 'An ignore file in .gitignore syntax, read after the .slpkignore of the folder'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -l no-compress -d 'Store JSON resources as they are, without .gz in their names, as an ESLPK does'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -l no-default-ignores -d 'Pack the junk files which are ignored by default, such as .DS_Store, Thumbs.db, editor backups and .git/'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -s v -l verbose -d 'List every ignored file'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from pack" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l header -d 'A header to send with every request when unpacking from a URL, such as "Authorization: Bearer <token>"'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l threads -d 'The number of worker threads, one per core by default. 0 or 1 unpacks one entry at a time without starting any threads'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l name -d 'The name of the folder in --output-dir a package read from standard input is unpacked into'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l output-dir -d 'Unpack into a folder named after the package in this folder, instead of next to the package'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l route -d 'Write one class of resource (metadata, geometry, textures, attributes or other) below another folder, as <class>=<folder>'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l dedup -d 'Link texture payloads identical to one already unpacked instead of writing them again, or with "copy" only report them' -r -f -a "hardlink symlink copy"
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l max-level -d 'Only unpack the resources of nodes down to this level below the root, which is at level 0'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l bbox -d 'Only unpack the resources of nodes meeting this box, given as xmin,ymin,xmax,ymax in the spatial reference of the layer, and of their ancestors'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l layer -d 'Only unpack layer <n> of a package which stores its layers below layers/<n>/'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l retries -d 'Retry an entry which fails up to this many times, then set it aside with a .failed marker file and carry on'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l retry-backoff-ms -d 'Milliseconds to wait before the first retry, doubling each time'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l if-exists -d 'When the output folder already exists, fail, delete it first, or unpack into it, leaving any files the package doesn\'t have' -r -f -a "error overwrite merge"
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l on-file-conflict -d 'Unpack into the existing output folder, and resolve each file which already exists by overwriting it, skipping the entry, overwriting it only when the entry is newer, keeping it when it is complete, or failing' -r -f -a "overwrite skip newer complete error"
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l strip-prefix -d 'Strip this folder from the start of every entry, or with auto the one folder every entry is in, leaving out entries which aren\'t in it'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l json-format -d 'Write .json and .geojson files as they are stored (as-is, the default), minified, or pretty-printed with two spaces, <n> spaces or a tab'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l sort-keys-max-size -d 'Leave the keys of documents larger than this in their order with --sort-keys, with a warning (64MiB by default)'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l only -d 'Only unpack resources of these kinds: textures, geometry, attributes or metadata'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l include -d 'Only unpack entries whose path matches one of these patterns, such as "*.json.gz" or "nodes/*/features/*"'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l exclude -d 'Don\'t unpack entries whose path matches one of these patterns'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l min-size -d 'Only unpack entries at least this large, such as 10MB or 1GiB'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l max-size -d 'Only unpack entries at most this large'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l newer-than -d 'Only unpack entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l max-memory -d 'Keep the memory held for entry contents below this, such as 512MiB'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l write-buffer -d 'The size of the buffer each file is written through, 128KiB by default'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l max-expansion-ratio -d 'Fail on entries which expand to more than this many times their compressed size'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l password -d 'The password of a package whose entries are encrypted'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l restore-order-file -d 'Write the names of the entries to this file, one per line, in the order they were written to the package'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l extract-order -d 'The order entries are unpacked in: archive, that of the zip directory, or metadata-first, the layer documents and node pages before the resources'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l timeout -d 'Stop after this long, such as 90s or 20m, leaving a resume file listing the entries which are left'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l trace-json -d 'Write a timeline of the entries each worker thread unpacked, in the Chrome trace format'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l to-tar -d 'Write the files as a tar stream to this file, or to standard output with -, instead of into the output folder'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -s v -l verbose -d 'Log every file unpacked, and with -vv also how long each stage of unpacking took'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -s q -l quiet -d 'Only print warnings and errors'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l progress -d 'Draw a progress bar of the entries unpacked and bytes written'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l watch -d 'Unpack again each time the package file is replaced, until Ctrl-C'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l split-sublayers -d 'Unpack each sublayer of a Building Scene Layer into its own folder'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l dedup-geometry -d 'Deduplicate geometry payloads as well as textures'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l exclude-empty-nodes -d 'Skip the resources of nodes with no vertices, no features and only placeholder textures'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l bbox-wgs84 -d 'Give the --bbox in WGS84 longitude and latitude'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l keep-going -d 'Carry on past entries which fail, listing them at the end, rather than stopping at the first'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l retry-failed -d 'Only unpack the entries set aside by an earlier run, into its output folder'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l rename-collisions -d 'Unpack entries whose files would collide with others where case is ignored under names suffixed ~1, ~2 and so on, rather than failing'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l portable-names -d 'Escape the characters of file names which Windows doesn\'t allow, as is always done on Windows'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l keep-unprefixed -d 'Unpack entries which aren\'t in the folder --strip-prefix strips as they are, rather than leaving them out'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l allow-symlinks -d 'Recreate symbolic links whose target is inside the output folder, on Unix, rather than leaving them out'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l sniff-compression -d 'Entries are decompressed when they start like a gzip stream whatever their names say, which this used to turn on'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l trust-extensions -d 'Decompress entries named .gz and copy the rest, without looking at what they hold'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l keep-gzip -d 'Write gzipped entries exactly as they are stored, keeping their .gz names, rather than decompressing them'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l sort-keys -d 'Sort the keys of every object in the documents --json-format reformats, so that unpacked packages diff cleanly'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l semantic-manifest -d 'Write manifest.json, giving the node, level and role of every unpacked file'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l manifest -d 'Write manifest.sha256, giving the SHA-256 of every unpacked file in the format of sha256sum'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l emit-index -d 'Write index.html, a page summarizing the package with its node tree and links to its documents, and its figures in index.json'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l restore-gzip-mtime -d 'Give files from gzipped entries the modification time recorded in their gzip header'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l verify-after -d 'Check every unpacked file against the package once unpacking is done, failing if any differ'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l verify-output -d 'Read every unpacked file back from disk once unpacking is done, failing if any doesn\'t hold what was unpacked to it'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l paranoid -d 'Turn on every integrity check, and fail on the first violation, for packages which can\'t be trusted'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l validate-json -d 'Check the layer documents, node pages, node index documents, shared resources and statistics against their schemas, warning of the first violation in each'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l strict -d 'Fail on JSON resources which don\'t match their schemas, rather than warning of them'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l hardened -d 'Refuse packages with more entries, or entries with longer names, than real packages have'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l stage-files -d 'Write each file under a temporary name, and rename it into place once it is complete'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l mmap -d 'Read the package through a memory mapping of it, rather than through a file handle for each thread'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l ask-password -d 'Ask for the password of an encrypted package on the terminal, without showing it'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l dry-run -d 'Print how many files and bytes unpacking would write, without writing anything'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l resume -d 'Only unpack the entries left by a run which timed out, into its output folder'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l incremental -d 'Only unpack the entries which changed since the last unpack into the output folder, deleting the files of those which are gone'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l atomic -d 'Unpack into a temporary folder next to the output folder, which only replaces it once every entry has been unpacked'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -l json -d 'Print a JSON summary on standard output once the unpack is done, and every other message on standard error'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from unpack" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from footprints" -s o -l output -d 'The GeoJSON file to write'
+complete -c slpkg -n "__fish_seen_subcommand_from footprints" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from footprints" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -s o -l output -d 'The file to write'
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -l level -d 'Only export nodes at this level, where the root node is level 0'
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -l format -d 'Output format' -r -f -a "geojson kml"
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -l max-features -d 'Sample at most this many nodes, spread across the layer'
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from bounds" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from index" -l list -d 'Print every record of the index'
+complete -c slpkg -n "__fish_seen_subcommand_from index" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from index" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -l min-size -d 'Only take entries at least this large, such as 10MB or 1GiB'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -l max-size -d 'Only take entries at most this large'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -l newer-than -d 'Only take entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -l quick -d 'Only read the zip directory, the layer document and the root node, rather than every entry'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from check" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from verify" -l threads -d 'The number of worker threads, one per core by default'
+complete -c slpkg -n "__fish_seen_subcommand_from verify" -l password -d 'The password of a package whose entries are encrypted'
+complete -c slpkg -n "__fish_seen_subcommand_from verify" -l ask-password -d 'Ask for the password of an encrypted package on the terminal, without showing it'
+complete -c slpkg -n "__fish_seen_subcommand_from verify" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from verify" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from check-manifest" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from check-manifest" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from diff" -l detailed -d 'List every entry added, removed or modified'
+complete -c slpkg -n "__fish_seen_subcommand_from diff" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from diff" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l password -d 'The password of a package whose entries are encrypted'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l checks -d 'Runs only these checks, such as gzip,refs,schema, out of refs, naming, gzip, json, schema and index'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l fail-on -d 'Fails on findings of this severity or worse' -r -f -a "error warning never"
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l threads -d 'The number of worker threads, one per core by default'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l validate-json -d 'Also check the JSON resources of the package against their schemas'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -l ask-password -d 'Ask for the password of an encrypted package on the terminal, without showing it'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from validate" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from extract-node" -s o -l output-dir -d 'The folder to extract into, which is created if it doesn\'t exist'
+complete -c slpkg -n "__fish_seen_subcommand_from extract-node" -l layer -d 'Extract from layer <n> of a package which stores its layers below layers/<n>/, rather than the first'
+complete -c slpkg -n "__fish_seen_subcommand_from extract-node" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from extract-node" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from tree" -l max-depth -d 'Only print nodes down to this depth, where the root is at depth 0'
+complete -c slpkg -n "__fish_seen_subcommand_from tree" -l counts-only -d 'Only print the number of nodes at each level'
+complete -c slpkg -n "__fish_seen_subcommand_from tree" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from tree" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from export" -s o -l output -d 'The file to write, in the format its extension names unless --format is given'
+complete -c slpkg -n "__fish_seen_subcommand_from export" -l format -d 'Write OBJ or glTF whatever the extension of the output file' -r -f -a "obj gltf"
+complete -c slpkg -n "__fish_seen_subcommand_from export" -l layer -d 'Export from layer <n> of a package which stores its layers below layers/<n>/, rather than the first'
+complete -c slpkg -n "__fish_seen_subcommand_from export" -l target-crs -d 'Reproject the positions into ecef, wgs84 or epsg:<code>, for Web Mercator or a UTM zone, rather than writing them as they are stored'
+complete -c slpkg -n "__fish_seen_subcommand_from export" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from export" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from export-attributes" -s o -l output-dir -d 'The folder to write the CSV files to, which is created if it doesn\'t exist'
+complete -c slpkg -n "__fish_seen_subcommand_from export-attributes" -l layer -d 'Export from layer <n> of a package which stores its layers below layers/<n>/, rather than the first'
+complete -c slpkg -n "__fish_seen_subcommand_from export-attributes" -l merge -d 'Write a single attributes.csv with a column for the node, rather than a file per node'
+complete -c slpkg -n "__fish_seen_subcommand_from export-attributes" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from export-attributes" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from textures" -l convert -d 'Decode the JPEG and DXT compressed DDS textures into PNGs in the folder the package unpacks into' -r -f -a "png"
+complete -c slpkg -n "__fish_seen_subcommand_from textures" -l output-dir -d 'Convert into a folder named after the package in this folder, instead of next to the package'
+complete -c slpkg -n "__fish_seen_subcommand_from textures" -l split-atlas -d 'Crop the texture atlas of each node into a PNG for each UV region of its features, in the folder the package unpacks into'
+complete -c slpkg -n "__fish_seen_subcommand_from textures" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from textures" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from cat" -l json-format -d 'Write a JSON document pretty-printed with two spaces (pretty, the default), with <n> spaces or a tab, minified, or as-is'
+complete -c slpkg -n "__fish_seen_subcommand_from cat" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from cat" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l password -d 'The password of a package whose entries are encrypted, which --deep needs to read them'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l min-size -d 'Only take entries at least this large, such as 10MB or 1GiB'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l max-size -d 'Only take entries at most this large'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l newer-than -d 'Only take entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l sort-by-size -d 'List the largest entries first'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l json -d 'Print the entries as a JSON array'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l deep -d 'Also read the gzip header of every gzipped entry, and list the original name, time and comment it records'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -l ask-password -d 'Ask for the password of an encrypted package on the terminal, without showing it'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from stats" -l layer -d 'Count the coded values of layer <n> of a package which stores its layers below layers/<n>/, rather than of the first'
+complete -c slpkg -n "__fish_seen_subcommand_from stats" -l json -d 'Print the breakdown as a JSON object'
+complete -c slpkg -n "__fish_seen_subcommand_from stats" -l coded-values -d 'Also count the features holding each value of the attributes with a coded-value domain, labelled by the domain'
+complete -c slpkg -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from info" -l attributes -d 'Lists the attributes, joining fields with attributeStorageInfo'
+complete -c slpkg -n "__fish_seen_subcommand_from info" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from info" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from repair" -s o -l output -d 'The repaired copy of the package to write'
+complete -c slpkg -n "__fish_seen_subcommand_from repair" -l check -d 'Only list the entries which need repairing'
+complete -c slpkg -n "__fish_seen_subcommand_from repair" -l write-metadata -d 'Add the metadata.json inferred from the layer to a copy of a package which has none'
+complete -c slpkg -n "__fish_seen_subcommand_from repair" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from repair" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from recompress" -s o -l output -d 'The recompressed copy of the package to write'
+complete -c slpkg -n "__fish_seen_subcommand_from recompress" -l compression-level -d 'The gzip level of the .gz entries, from 1 for the fastest to 9 for the smallest package, 9 by default'
+complete -c slpkg -n "__fish_seen_subcommand_from recompress" -l minify-json -d 'Minify the JSON inside .json.gz entries before gzipping it again'
+complete -c slpkg -n "__fish_seen_subcommand_from recompress" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from recompress" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l min-texture-area -d 'Flag textures with fewer pixels than this'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l min-vertices -d 'Flag meshes below the root with fewer vertices than this'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l severity -d 'Sets the severity of a rule, such as small-texture=error or inverted-lod=off'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l checks -d 'Runs only these rules, such as small-texture,inverted-lod'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l fail-on -d 'Fails on findings of this severity or worse' -r -f -a "error warning never"
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -l threads -d 'The number of worker threads, one per core by default'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from lint" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from patch" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from patch" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from patch" -f -a "create" -d 'Writes a patch which turns one package into another'
+complete -c slpkg -n "__fish_seen_subcommand_from patch" -f -a "apply" -d 'Applies a patch to the package it was made for'
+complete -c slpkg -n "__fish_seen_subcommand_from patch" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
+complete -c slpkg -n "__fish_seen_subcommand_from create" -s o -l output -d 'The patch to write'
+complete -c slpkg -n "__fish_seen_subcommand_from create" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from create" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from apply" -s o -l output -d 'The patched package to write'
+complete -c slpkg -n "__fish_seen_subcommand_from apply" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from apply" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from help" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from help" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l command -d 'The command to run on each package' -r -f -a "unpack check verify validate"
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -s j -l jobs -d 'How many packages to process at once'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l threads -d 'The worker threads shared by the packages processed at once, by default the number of cores'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l report -d 'The JSON report to write'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l output-dir -d 'Unpack into a folder named after the package in this folder, instead of next to the package'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l route -d 'Write one class of resource (metadata, geometry, textures, attributes or other) below another folder, as <class>=<folder>'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l dedup -d 'Link texture payloads identical to one already unpacked instead of writing them again, or with "copy" only report them' -r -f -a "hardlink symlink copy"
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l max-level -d 'Only unpack the resources of nodes down to this level below the root, which is at level 0'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l bbox -d 'Only unpack the resources of nodes meeting this box, given as xmin,ymin,xmax,ymax in the spatial reference of the layer, and of their ancestors'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l layer -d 'Only unpack layer <n> of a package which stores its layers below layers/<n>/'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l retries -d 'Retry an entry which fails up to this many times, then set it aside with a .failed marker file and carry on'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l retry-backoff-ms -d 'Milliseconds to wait before the first retry, doubling each time'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l if-exists -d 'When the output folder already exists, fail, delete it first, or unpack into it, leaving any files the package doesn\'t have' -r -f -a "error overwrite merge"
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l on-file-conflict -d 'Unpack into the existing output folder, and resolve each file which already exists by overwriting it, skipping the entry, overwriting it only when the entry is newer, keeping it when it is complete, or failing' -r -f -a "overwrite skip newer complete error"
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l strip-prefix -d 'Strip this folder from the start of every entry, or with auto the one folder every entry is in, leaving out entries which aren\'t in it'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l json-format -d 'Write .json and .geojson files as they are stored (as-is, the default), minified, or pretty-printed with two spaces, <n> spaces or a tab'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l sort-keys-max-size -d 'Leave the keys of documents larger than this in their order with --sort-keys, with a warning (64MiB by default)'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l only -d 'Only unpack resources of these kinds: textures, geometry, attributes or metadata'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l include -d 'Only unpack entries whose path matches one of these patterns, such as "*.json.gz" or "nodes/*/features/*"'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l exclude -d 'Don\'t unpack entries whose path matches one of these patterns'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l min-size -d 'Only unpack entries at least this large, such as 10MB or 1GiB'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l max-size -d 'Only unpack entries at most this large'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l newer-than -d 'Only unpack entries modified after this date, as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l max-memory -d 'Keep the memory held for entry contents below this, such as 512MiB'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l write-buffer -d 'The size of the buffer each file is written through, 128KiB by default'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l max-expansion-ratio -d 'Fail on entries which expand to more than this many times their compressed size'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l password -d 'The password of a package whose entries are encrypted'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l restore-order-file -d 'Write the names of the entries to this file, one per line, in the order they were written to the package'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l extract-order -d 'The order entries are unpacked in: archive, that of the zip directory, or metadata-first, the layer documents and node pages before the resources'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l timeout -d 'Stop after this long, such as 90s or 20m, leaving a resume file listing the entries which are left'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l fail-fast -d 'Don\'t start any more packages once one has failed'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l split-sublayers -d 'Unpack each sublayer of a Building Scene Layer into its own folder'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l dedup-geometry -d 'Deduplicate geometry payloads as well as textures'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l exclude-empty-nodes -d 'Skip the resources of nodes with no vertices, no features and only placeholder textures'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l bbox-wgs84 -d 'Give the --bbox in WGS84 longitude and latitude'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l keep-going -d 'Carry on past entries which fail, listing them at the end, rather than stopping at the first'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l retry-failed -d 'Only unpack the entries set aside by an earlier run, into its output folder'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l rename-collisions -d 'Unpack entries whose files would collide with others where case is ignored under names suffixed ~1, ~2 and so on, rather than failing'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l portable-names -d 'Escape the characters of file names which Windows doesn\'t allow, as is always done on Windows'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l keep-unprefixed -d 'Unpack entries which aren\'t in the folder --strip-prefix strips as they are, rather than leaving them out'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l allow-symlinks -d 'Recreate symbolic links whose target is inside the output folder, on Unix, rather than leaving them out'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l sniff-compression -d 'Entries are decompressed when they start like a gzip stream whatever their names say, which this used to turn on'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l trust-extensions -d 'Decompress entries named .gz and copy the rest, without looking at what they hold'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l keep-gzip -d 'Write gzipped entries exactly as they are stored, keeping their .gz names, rather than decompressing them'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l sort-keys -d 'Sort the keys of every object in the documents --json-format reformats, so that unpacked packages diff cleanly'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l semantic-manifest -d 'Write manifest.json, giving the node, level and role of every unpacked file'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l manifest -d 'Write manifest.sha256, giving the SHA-256 of every unpacked file in the format of sha256sum'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l emit-index -d 'Write index.html, a page summarizing the package with its node tree and links to its documents, and its figures in index.json'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l restore-gzip-mtime -d 'Give files from gzipped entries the modification time recorded in their gzip header'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l verify-after -d 'Check every unpacked file against the package once unpacking is done, failing if any differ'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l verify-output -d 'Read every unpacked file back from disk once unpacking is done, failing if any doesn\'t hold what was unpacked to it'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l paranoid -d 'Turn on every integrity check, and fail on the first violation, for packages which can\'t be trusted'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l validate-json -d 'Check the layer documents, node pages, node index documents, shared resources and statistics against their schemas, warning of the first violation in each'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l strict -d 'Fail on JSON resources which don\'t match their schemas, rather than warning of them'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l hardened -d 'Refuse packages with more entries, or entries with longer names, than real packages have'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l stage-files -d 'Write each file under a temporary name, and rename it into place once it is complete'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l mmap -d 'Read the package through a memory mapping of it, rather than through a file handle for each thread'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l ask-password -d 'Ask for the password of an encrypted package on the terminal, without showing it'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l dry-run -d 'Print how many files and bytes unpacking would write, without writing anything'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l resume -d 'Only unpack the entries left by a run which timed out, into its output folder'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l incremental -d 'Only unpack the entries which changed since the last unpack into the output folder, deleting the files of those which are gone'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -l atomic -d 'Unpack into a temporary folder next to the output folder, which only replaces it once every entry has been unpacked'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from batch" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -s o -l output -d 'The package to write, named after the service by default'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -l max-level -d 'The deepest level of the node tree to mirror, where the root is level 0, for a quick preview of a large layer'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -l concurrency -d 'How many resources to fetch at once'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -l retries -d 'How many times to send a request again when it fails on the way or the server is busy'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -l header -d 'A header to send with every request, such as "Authorization: Bearer <token>"'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from mirror" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from serve" -l port -d 'The port to listen on'
+complete -c slpkg -n "__fish_seen_subcommand_from serve" -l bind -d 'The address to listen on, by default one only this machine can connect to'
+complete -c slpkg -n "__fish_seen_subcommand_from serve" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from serve" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from self-test" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from self-test" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from config" -l show -d 'Print every value the files set, with the file it comes from, which is needed for now'
+complete -c slpkg -n "__fish_seen_subcommand_from config" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from config" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from features" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from features" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from completions" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from completions" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from cli-spec" -l json -d 'Print the description as JSON instead of an outline'
+complete -c slpkg -n "__fish_seen_subcommand_from cli-spec" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from cli-spec" -s V -l version -d 'Prints version information'
+complete -c slpkg -n "__fish_seen_subcommand_from help" -s h -l help -d 'Prints help information'
+complete -c slpkg -n "__fish_seen_subcommand_from help" -s V -l version -d 'Prints version information'
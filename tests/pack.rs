@@ -0,0 +1,44 @@
+// Packs an unpacked synthetic package again, and checks the new package
+// with the commands which read packages, through the public API.
+
+mod common;
+
+use slpkg::synthetic::SyntheticPackage;
+use slpkg::unpack::{unpack_with_options, UnpackOptions};
+
+#[test]
+fn an_unpacked_package_packs_into_one_which_unpacks_the_same() {
+    let dir = common::test_dir("pack-round-trip");
+    let path = dir.join("package.slpk");
+    let package = SyntheticPackage::standard();
+    package.write_to_file(&path).unwrap();
+    unpack_with_options(&path, &UnpackOptions::new()).unwrap();
+    let repacked = dir.join("repacked.slpk");
+    let summary = slpkg::pack::pack(&dir.join("package"), &repacked).unwrap();
+    unpack_with_options(&repacked, &UnpackOptions::new()).unwrap();
+    let unpacked = common::unpacked_files(&dir.join("repacked"));
+    let verified = slpkg::verify::verify(&repacked).unwrap();
+    let listed = slpkg::list::list(&repacked).unwrap();
+
+    assert_eq!(summary.entries, package.expected_files(false).len());
+    assert_eq!(unpacked, package.expected_files(false));
+    assert!(verified.passed());
+    assert_eq!(verified.entries, summary.entries);
+    // JSON resources are gzipped again, and metadata.json never is.
+    let names: Vec<&str> = listed.iter().map(|entry| entry.name.as_str()).collect();
+    assert!(names.contains(&"3dSceneLayer.json.gz"));
+    assert!(names.contains(&"metadata.json"));
+}
+
+#[test]
+fn a_package_with_a_stale_hash_index_fails_the_index_check() {
+    let dir = common::test_dir("pack-stale-index");
+    let path = dir.join("package.slpk");
+    SyntheticPackage::standard()
+        .with_hash_index_record("metadata.json", 7)
+        .write_to_file(&path)
+        .unwrap();
+    let report = slpkg::index::check_hash_index(&path).unwrap().unwrap();
+
+    assert_eq!(report.stale, ["metadata.json"]);
+}
@@ -0,0 +1,55 @@
+// Unpacks the standard synthetic package through the public API, and checks
+// every file against what the package holds, byte for byte.
+
+mod common;
+
+use slpkg::synthetic::SyntheticPackage;
+use slpkg::unpack::{unpack_with_options, UnpackOptions};
+use std::path::PathBuf;
+
+#[test]
+fn the_standard_package_unpacks_byte_for_byte() {
+    let package = SyntheticPackage::standard();
+    for (name, options, split_sublayers) in [
+        ("plain", UnpackOptions::new(), false),
+        ("threads", UnpackOptions::new().threads(4), false),
+        ("split", UnpackOptions::new().split_sublayers(true), true),
+        ("paranoid", UnpackOptions::new().paranoid(), false),
+    ] {
+        let dir = common::test_dir(&format!("unpack-{}", name));
+        let path = dir.join("package.slpk");
+        package.write_to_file(&path).unwrap();
+        unpack_with_options(&path, &options).unwrap();
+        let unpacked = common::unpacked_files(&dir.join("package"));
+
+        let expected = package.expected_files(split_sublayers);
+        let paths = |files: &[(PathBuf, Vec<u8>)]| -> Vec<PathBuf> {
+            files.iter().map(|(path, _)| path.clone()).collect()
+        };
+        assert_eq!(paths(&unpacked), paths(&expected), "{}", name);
+        assert!(unpacked == expected, "{}: the contents differ", name);
+    }
+}
+
+#[test]
+fn an_existing_output_folder_is_refused_once_and_replaced_atomically() {
+    let dir = common::test_dir("unpack-existing");
+    let path = dir.join("package.slpk");
+    let package = SyntheticPackage::standard();
+    package.write_to_file(&path).unwrap();
+    let stale = dir.join("package/stale.json");
+    std::fs::create_dir_all(stale.parent().unwrap()).unwrap();
+    std::fs::write(&stale, b"{}").unwrap();
+
+    let refused = unpack_with_options(
+        &path,
+        &UnpackOptions::new().overwrite(slpkg::unpack::OverwriteMode::Error),
+    );
+    let kept = stale.exists();
+    unpack_with_options(&path, &UnpackOptions::new().atomic(true)).unwrap();
+    let unpacked = common::unpacked_files(&dir.join("package"));
+
+    assert!(refused.is_err());
+    assert!(kept);
+    assert_eq!(unpacked, package.expected_files(false));
+}